@@ -0,0 +1,71 @@
+//! Replay-style verification throughput: `verify_checksum` per frame vs a
+//! reused `ChecksumVerifier`, over 100k updates to one book.
+//!
+//! cargo bench -p blackbox-core --bench checksum_verify
+
+use blackbox_core::checksum::{verify_checksum, ChecksumVerifier};
+use blackbox_core::orderbook::Orderbook;
+use blackbox_core::types::InstrumentInfo;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use rust_decimal::Decimal;
+
+const FRAMES: i64 = 100_000;
+
+fn book() -> Orderbook {
+    let bids = (0..25)
+        .map(|i| (Decimal::new(5_000_000 - i * 5, 1), Decimal::new(1_2345_6789 + i, 8)))
+        .collect();
+    let asks = (0..25)
+        .map(|i| (Decimal::new(5_000_001 + i * 5, 1), Decimal::new(9_8765_4321 + i, 8)))
+        .collect();
+    let mut book = Orderbook::new();
+    book.apply_snapshot(bids, asks);
+    book
+}
+
+/// Touch one of the top levels, like a typical update frame
+fn apply_frame(book: &mut Orderbook, frame: i64) {
+    let price = Decimal::new(5_000_001 + (frame % 10) * 5, 1);
+    book.apply_updates(vec![], vec![(price, Decimal::new(1_0000_0000 + frame, 8))]);
+}
+
+fn bench_verify(c: &mut Criterion) {
+    let mut group = c.benchmark_group("verify_100k_frames");
+    group.sample_size(10);
+    
+    group.bench_function("verify_checksum", |b| {
+        b.iter(|| {
+            let mut book = book();
+            let mut matched = 0;
+            for frame in 0..FRAMES {
+                apply_frame(&mut book, frame);
+                matched += verify_checksum(&book, 0, 1, 8) as usize;
+            }
+            black_box(matched)
+        })
+    });
+    
+    group.bench_function("checksum_verifier", |b| {
+        let mut verifier = ChecksumVerifier::new();
+        verifier.set_instrument(&InstrumentInfo {
+            symbol: "BTC/USD".to_string(),
+            price_precision: 1,
+            qty_precision: 8,
+            ..Default::default()
+        });
+        b.iter(|| {
+            let mut book = book();
+            let mut matched = 0;
+            for frame in 0..FRAMES {
+                apply_frame(&mut book, frame);
+                matched += verifier.verify("BTC/USD", &book, 0).is_some_and(|v| v.matched) as usize;
+            }
+            black_box(matched)
+        })
+    });
+    
+    group.finish();
+}
+
+criterion_group!(benches, bench_verify);
+criterion_main!(benches);
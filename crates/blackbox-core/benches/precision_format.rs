@@ -0,0 +1,51 @@
+//! Checksum level formatting: `format_fixed` per level vs a per-instrument
+//! `PrecisionFormatter` writing into one buffer.
+//!
+//! cargo bench -p blackbox-core --bench precision_format
+
+use blackbox_core::precision::{format_fixed, PrecisionFormatter};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use rust_decimal::Decimal;
+
+/// Ten levels per side, stored at and beyond the instrument's precision
+fn levels() -> Vec<(Decimal, Decimal)> {
+    (0..20)
+        .map(|i| {
+            let price = Decimal::new(5_000_000 + i * 5, 1);
+            let qty = if i % 2 == 0 { Decimal::new(1_2345_6789 + i, 8) } else { Decimal::new(123_456_789_123 + i, 11) };
+            (price, qty)
+        })
+        .collect()
+}
+
+fn bench_format(c: &mut Criterion) {
+    let levels = levels();
+    
+    let mut group = c.benchmark_group("checksum_levels_20");
+    group.bench_function("format_fixed", |b| {
+        b.iter(|| {
+            let mut out = String::new();
+            for (price, qty) in &levels {
+                out.push_str(&format_fixed(price, 1));
+                out.push_str(&format_fixed(qty, 8));
+            }
+            black_box(out)
+        })
+    });
+    group.bench_function("precision_formatter", |b| {
+        let formatter = PrecisionFormatter::new(1, 8);
+        let mut out = String::new();
+        b.iter(|| {
+            out.clear();
+            for (price, qty) in &levels {
+                formatter.format_price(price, &mut out);
+                formatter.format_qty(qty, &mut out);
+            }
+            black_box(out.len())
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_format);
+criterion_main!(benches);
@@ -0,0 +1,68 @@
+//! Top-of-book access: allocating `*_vec(Some(n))` vs the `top_*` iterators.
+//!
+//! cargo bench -p blackbox-core --bench top_levels
+
+use blackbox_core::checksum::{build_checksum_string, CHECKSUM_DEPTH};
+use blackbox_core::orderbook::Orderbook;
+use blackbox_core::precision::format_fixed;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use rust_decimal::Decimal;
+
+fn book(levels: i64) -> Orderbook {
+    let bids = (0..levels)
+        .map(|i| (Decimal::new(5_000_000 - i * 5, 1), Decimal::new(1_2345_6789 + i, 8)))
+        .collect();
+    let asks = (0..levels)
+        .map(|i| (Decimal::new(5_000_001 + i * 5, 1), Decimal::new(9_8765_4321 + i, 8)))
+        .collect();
+    let mut book = Orderbook::new();
+    book.apply_snapshot(bids, asks);
+    book
+}
+
+/// Checksum string built the way it was before the `top_*` iterators
+fn checksum_string_from_vecs(book: &Orderbook, price_precision: u32, qty_precision: u32) -> String {
+    let mut checksum_str = String::new();
+    for (price, qty) in book.asks_vec(Some(CHECKSUM_DEPTH)) {
+        checksum_str.push_str(&format_fixed(&price, price_precision));
+        checksum_str.push_str(&format_fixed(&qty, qty_precision));
+    }
+    for (price, qty) in book.bids_vec(Some(CHECKSUM_DEPTH)) {
+        checksum_str.push_str(&format_fixed(&price, price_precision));
+        checksum_str.push_str(&format_fixed(&qty, qty_precision));
+    }
+    checksum_str
+}
+
+fn bench_top_levels(c: &mut Criterion) {
+    let book = book(1000);
+    
+    let mut group = c.benchmark_group("top10");
+    group.bench_function("vec", |b| {
+        b.iter(|| {
+            let asks = book.asks_vec(Some(CHECKSUM_DEPTH));
+            let bids = book.bids_vec(Some(CHECKSUM_DEPTH));
+            black_box(asks.iter().chain(bids.iter()).map(|(_, q)| *q).sum::<Decimal>())
+        })
+    });
+    group.bench_function("iter", |b| {
+        b.iter(|| {
+            let asks = book.top_asks(CHECKSUM_DEPTH);
+            let bids = book.top_bids(CHECKSUM_DEPTH);
+            black_box(asks.chain(bids).map(|(_, q)| *q).sum::<Decimal>())
+        })
+    });
+    group.finish();
+    
+    let mut group = c.benchmark_group("checksum_string");
+    group.bench_function("vec", |b| {
+        b.iter(|| black_box(checksum_string_from_vecs(&book, 1, 8)))
+    });
+    group.bench_function("iter", |b| {
+        b.iter(|| black_box(build_checksum_string(&book, 1, 8)))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_top_levels);
+criterion_main!(benches);
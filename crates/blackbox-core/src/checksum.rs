@@ -1,6 +1,58 @@
-use crate::orderbook::Orderbook;
-use crate::precision::format_fixed;
+use crate::orderbook::{Orderbook, Side};
+use crate::precision::PrecisionFormatter;
+use crate::types::InstrumentInfo;
 use crc32fast::Hasher;
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// Number of levels per side included in the checksum
+pub const CHECKSUM_DEPTH: usize = 10;
+
+/// Characters of the checksum string kept in `ChecksumVerification::preview`
+pub const CHECKSUM_PREVIEW_LEN: usize = 64;
+
+/// Outcome of a checksum check, with the details integrity views need
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChecksumVerification {
+    pub expected: u32,
+    pub computed: u32,
+    pub matched: bool,
+    pub checksum_len: usize,
+    pub preview: String, // first CHECKSUM_PREVIEW_LEN chars of the checksum string
+    pub elapsed: Duration,
+    pub negative_level: Option<NegativeLevel>, // only looked for on mismatch
+}
+
+/// A checksummed level with a negative price or qty. Kraken never sends one,
+/// so it comes from a fault or a bad update, and its '-' is in the checksum string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NegativeLevel {
+    pub side: Side,
+    pub price: Decimal,
+    pub qty: Decimal,
+}
+
+impl fmt::Display for NegativeLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let field = if self.qty.is_sign_negative() { "qty" } else { "price" };
+        let side = match self.side {
+            Side::Bid => "bid",
+            Side::Ask => "ask",
+        };
+        write!(f, "negative {} in book: {} {} @ {}", field, side, self.qty, self.price)
+    }
+}
+
+/// First level among the top `levels` asks then bids with a negative price or qty
+pub fn find_negative_level(orderbook: &Orderbook, levels: usize) -> Option<NegativeLevel> {
+    let asks = orderbook.top_asks(levels).map(|level| (Side::Ask, level));
+    let bids = orderbook.top_bids(levels).map(|level| (Side::Bid, level));
+    asks.chain(bids)
+        .find(|(_, (price, qty))| price.is_sign_negative() || qty.is_sign_negative())
+        .map(|(side, (price, qty))| NegativeLevel { side, price: *price, qty: *qty })
+}
 
 /// Build checksum string from orderbook per Kraken v2 spec:
 /// - Top 10 asks (low->high) then top 10 bids (high->low)
@@ -11,30 +63,38 @@ pub fn build_checksum_string(
     orderbook: &Orderbook,
     price_precision: u32,
     qty_precision: u32,
+) -> String {
+    build_checksum_string_with_levels(orderbook, price_precision, qty_precision, CHECKSUM_DEPTH)
+}
+
+/// Same as `build_checksum_string` over the top `levels` levels per side.
+/// Sides shallower than `levels` contribute all the levels they have.
+pub fn build_checksum_string_with_levels(
+    orderbook: &Orderbook,
+    price_precision: u32,
+    qty_precision: u32,
+    levels: usize,
 ) -> String {
     let mut checksum_str = String::new();
-    
-    // Top 10 asks (low->high, ascending)
-    let asks_iter = orderbook.asks_iter().take(10);
-    for (price, qty) in asks_iter {
-        let price_str = format_fixed(price, price_precision);
-        let qty_str = format_fixed(qty, qty_precision);
-        checksum_str.push_str(&price_str);
-        checksum_str.push_str(&qty_str);
-    }
-    
-    // Top 10 bids (high->low, descending)
-    let bids_iter = orderbook.bids_iter_rev().take(10);
-    for (price, qty) in bids_iter {
-        let price_str = format_fixed(price, price_precision);
-        let qty_str = format_fixed(qty, qty_precision);
-        checksum_str.push_str(&price_str);
-        checksum_str.push_str(&qty_str);
-    }
-    
+    let formatter = PrecisionFormatter::new(price_precision, qty_precision);
+    write_checksum_string(orderbook, &formatter, levels, &mut checksum_str);
     checksum_str
 }
 
+/// Append the checksum string over the top `levels` levels per side to `out`
+pub fn write_checksum_string(
+    orderbook: &Orderbook,
+    formatter: &PrecisionFormatter,
+    levels: usize,
+    out: &mut String,
+) {
+    // Top N asks (low->high, ascending), then top N bids (high->low, descending)
+    for (price, qty) in orderbook.top_asks(levels).chain(orderbook.top_bids(levels)) {
+        formatter.format_price(price, out);
+        formatter.format_qty(qty, out);
+    }
+}
+
 /// Compute CRC32 checksum from string
 pub fn compute_crc32(s: &str) -> u32 {
     let mut hasher = Hasher::new();
@@ -42,70 +102,169 @@ pub fn compute_crc32(s: &str) -> u32 {
     hasher.finalize()
 }
 
-/// Verify checksum against orderbook state
+/// Verify checksum against orderbook state, over the book's `checksum_levels`
 pub fn verify_checksum(
     orderbook: &Orderbook,
     expected_checksum: u32,
     price_precision: u32,
     qty_precision: u32,
 ) -> bool {
-    let checksum_str = build_checksum_string(orderbook, price_precision, qty_precision);
+    let checksum_str = build_checksum_string_with_levels(
+        orderbook,
+        price_precision,
+        qty_precision,
+        orderbook.checksum_levels(),
+    );
     let computed = compute_crc32(&checksum_str);
     computed == expected_checksum
 }
 
+/// Verify checksum using the orderbook's cached checksum string.
+/// Only rebuilds the string when a checksummed level changed since the last verify,
+/// so updates deeper in the book cost nothing.
+pub fn verify_checksum_cached(
+    orderbook: &mut Orderbook,
+    expected_checksum: u32,
+    price_precision: u32,
+    qty_precision: u32,
+) -> bool {
+    verify_checksum_detailed(orderbook, expected_checksum, price_precision, qty_precision).matched
+}
+
+/// Like `verify_checksum_cached`, but also returns the computed value, the
+/// checksum string length and preview, and how long the check took
+pub fn verify_checksum_detailed(
+    orderbook: &mut Orderbook,
+    expected_checksum: u32,
+    price_precision: u32,
+    qty_precision: u32,
+) -> ChecksumVerification {
+    verify_checksum_formatted(orderbook, expected_checksum, &PrecisionFormatter::new(price_precision, qty_precision))
+}
+
+/// `verify_checksum_detailed` with an instrument's prebuilt formatter
+pub fn verify_checksum_formatted(
+    orderbook: &mut Orderbook,
+    expected_checksum: u32,
+    formatter: &PrecisionFormatter,
+) -> ChecksumVerification {
+    let start = Instant::now();
+    let computed = orderbook.checksum_with(formatter);
+    let checksum_str = orderbook.checksum_string_with(formatter);
+    let checksum_len = checksum_str.len();
+    let preview = checksum_str.chars().take(CHECKSUM_PREVIEW_LEN).collect();
+    let matched = computed == expected_checksum;
+    let negative_level = if matched { None } else { find_negative_level(orderbook, orderbook.checksum_levels()) };
+    
+    ChecksumVerification {
+        expected: expected_checksum,
+        computed,
+        matched,
+        checksum_len,
+        preview,
+        elapsed: start.elapsed(),
+        negative_level,
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct SymbolPrecision {
+    formatter: PrecisionFormatter,
+    levels: usize,
+}
+
+/// Verifies checksums for many books in a row, e.g. over a whole recording.
+/// Owns the string buffer between calls and formats levels straight into it,
+/// so verifying a frame doesn't allocate beyond the result's preview.
+#[derive(Debug, Default)]
+pub struct ChecksumVerifier {
+    buf: String,
+    precisions: HashMap<String, SymbolPrecision>,
+}
+
+impl ChecksumVerifier {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Remember the precisions and checksum level count for an instrument
+    pub fn set_instrument(&mut self, instrument: &InstrumentInfo) {
+        self.precisions.insert(instrument.symbol.clone(), SymbolPrecision {
+            formatter: PrecisionFormatter::from_instrument(instrument),
+            levels: instrument.checksum_levels,
+        });
+    }
+
+    pub fn knows(&self, symbol: &str) -> bool {
+        self.precisions.contains_key(symbol)
+    }
+
+    /// Verify `book` against `expected` with the symbol's cached precisions.
+    /// `None` if no instrument was registered for the symbol.
+    pub fn verify(&mut self, symbol: &str, book: &Orderbook, expected: u32) -> Option<ChecksumVerification> {
+        let start = Instant::now();
+        let precision = *self.precisions.get(symbol)?;
+        
+        self.buf.clear();
+        write_checksum_string(book, &precision.formatter, precision.levels, &mut self.buf);
+        let computed = compute_crc32(&self.buf);
+        let matched = computed == expected;
+        
+        Some(ChecksumVerification {
+            expected,
+            computed,
+            matched,
+            checksum_len: self.buf.len(),
+            preview: self.buf.chars().take(CHECKSUM_PREVIEW_LEN).collect(),
+            elapsed: start.elapsed(),
+            negative_level: if matched { None } else { find_negative_level(book, precision.levels) },
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::orderbook::Orderbook;
+    use crate::precision::format_fixed;
+    use rust_decimal::Decimal;
     use rust_decimal_macros::dec;
 
     #[test]
     fn test_kraken_example_checksum() {
-        // Example from Kraken docs (must equal 3310070434)
-        // This test uses the exact example from the documentation
+        // BTC/USD snapshot from the Kraken WebSocket v2 book docs (must equal 3310070434)
         let mut book = Orderbook::new();
+        book.apply_snapshot(
+            vec![
+                (dec!(45283.5), dec!(0.10000000)),
+                (dec!(45283.4), dec!(1.54582015)),
+                (dec!(45282.1), dec!(0.10000000)),
+                (dec!(45281.0), dec!(0.10000000)),
+                (dec!(45280.3), dec!(1.54592586)),
+                (dec!(45279.0), dec!(0.07990000)),
+                (dec!(45277.6), dec!(0.03310103)),
+                (dec!(45277.5), dec!(0.30000000)),
+                (dec!(45277.3), dec!(1.54602737)),
+                (dec!(45276.6), dec!(0.15445238)),
+            ],
+            vec![
+                (dec!(45285.2), dec!(0.00100000)),
+                (dec!(45286.4), dec!(1.54571953)),
+                (dec!(45286.6), dec!(1.54571109)),
+                (dec!(45289.6), dec!(1.54560911)),
+                (dec!(45290.2), dec!(0.15890660)),
+                (dec!(45291.8), dec!(1.54553491)),
+                (dec!(45294.7), dec!(0.04454749)),
+                (dec!(45296.1), dec!(0.35380000)),
+                (dec!(45297.5), dec!(0.09945542)),
+                (dec!(45299.5), dec!(0.18772827)),
+            ],
+        );
         
-        // Example asks (low to high)
-        book.update_ask(dec!(50000.1), dec!(1.0));
-        book.update_ask(dec!(50000.2), dec!(2.0));
-        book.update_ask(dec!(50000.3), dec!(3.0));
-        book.update_ask(dec!(50000.4), dec!(4.0));
-        book.update_ask(dec!(50000.5), dec!(5.0));
-        book.update_ask(dec!(50000.6), dec!(6.0));
-        book.update_ask(dec!(50000.7), dec!(7.0));
-        book.update_ask(dec!(50000.8), dec!(8.0));
-        book.update_ask(dec!(50000.9), dec!(9.0));
-        book.update_ask(dec!(50001.0), dec!(10.0));
-        
-        // Example bids (high to low)
-        book.update_bid(dec!(49999.9), dec!(1.0));
-        book.update_bid(dec!(49999.8), dec!(2.0));
-        book.update_bid(dec!(49999.7), dec!(3.0));
-        book.update_bid(dec!(49999.6), dec!(4.0));
-        book.update_bid(dec!(49999.5), dec!(5.0));
-        book.update_bid(dec!(49999.4), dec!(6.0));
-        book.update_bid(dec!(49999.3), dec!(7.0));
-        book.update_bid(dec!(49999.2), dec!(8.0));
-        book.update_bid(dec!(49999.1), dec!(9.0));
-        book.update_bid(dec!(49999.0), dec!(10.0));
-        
-        // Build checksum string with precision 1 for both price and qty
-        let checksum_str = build_checksum_string(&book, 1, 1);
-        
-        // According to Kraken docs, this should produce checksum 3310070434
-        // Let's verify the actual computation
-        let computed = compute_crc32(&checksum_str);
-        
-        // Note: The exact example from Kraken docs may need adjustment
-        // This test ensures our implementation is correct
-        // If the example doesn't match, we'll need to verify with real data
-        println!("Checksum string: {}", checksum_str);
-        println!("Computed CRC32: {}", computed);
-        
-        // For now, we verify the function works correctly
-        // The actual value 3310070434 will be verified with real Kraken data
-        assert!(computed > 0);
+        let checksum_str = build_checksum_string(&book, 1, 8);
+        assert!(checksum_str.starts_with("452852100000"));
+        assert_eq!(compute_crc32(&checksum_str), 3310070434);
+        assert!(verify_checksum(&book, 3310070434, 1, 8));
     }
     
     #[test]
@@ -121,5 +280,193 @@ mod tests {
         assert!(checksum_str.contains("5000012"));
         assert!(checksum_str.contains("123"));
     }
+
+    fn assert_cache_matches(book: &mut Orderbook, price_precision: u32, qty_precision: u32) {
+        let fresh = build_checksum_string(book, price_precision, qty_precision);
+        assert_eq!(book.checksum_string(price_precision, qty_precision), fresh);
+        assert_eq!(book.checksum(price_precision, qty_precision), compute_crc32(&fresh));
+        assert!(verify_checksum_cached(book, compute_crc32(&fresh), price_precision, qty_precision));
+    }
+
+    fn level_string(levels: &[(Decimal, Decimal)]) -> String {
+        levels.iter().map(|(p, q)| format_fixed(p, 1) + &format_fixed(q, 8)).collect()
+    }
+
+    #[test]
+    fn test_checksum_levels() {
+        let bids: Vec<_> = (0..15).map(|i| (dec!(50000.0) - Decimal::from(i), dec!(1.5))).collect();
+        let asks: Vec<_> = (0..15).map(|i| (dec!(50001.0) + Decimal::from(i), dec!(2.5))).collect();
+        
+        for levels in [5, 10, 25] {
+            let mut book = Orderbook::new().with_checksum_levels(levels);
+            book.apply_snapshot(bids.clone(), asks.clone());
+            
+            // The 25 level book only has 15 per side; all of them are included
+            let included = levels.min(15);
+            let expected = level_string(&asks[..included]) + &level_string(&bids[..included]);
+            assert_eq!(build_checksum_string_with_levels(&book, 1, 8, levels), expected);
+            assert_eq!(book.checksum_string(1, 8), expected);
+            assert!(verify_checksum(&book, compute_crc32(&expected), 1, 8));
+            
+            // A change just outside the checksummed range leaves the checksum alone
+            // (a shallow book has no such range), one just inside rebuilds it
+            let before = book.checksum(1, 8);
+            if levels < 15 {
+                let outside = dec!(50001.0) + Decimal::from(levels as i64);
+                book.apply_updates(vec![], vec![(outside, dec!(9))]);
+                assert_eq!(book.checksum(1, 8), before);
+            }
+            let inside = dec!(50001.0) + Decimal::from(included as i64 - 1);
+            book.apply_updates(vec![], vec![(inside, dec!(9))]);
+            assert_ne!(book.checksum(1, 8), before);
+            let fresh = build_checksum_string_with_levels(&book, 1, 8, levels);
+            assert_eq!(book.checksum_string(1, 8), fresh);
+        }
+        
+        // Default stays at Kraken's 10
+        let mut book = Orderbook::new();
+        book.apply_snapshot(bids.clone(), asks.clone());
+        assert_eq!(book.checksum_levels(), CHECKSUM_DEPTH);
+        let fresh = build_checksum_string(&book, 1, 8);
+        assert_eq!(book.checksum_string(1, 8), fresh);
+        
+        // Changing the level count rebuilds the cached string
+        book.set_checksum_levels(5);
+        assert_eq!(book.checksum_string(1, 8), level_string(&asks[..5]) + &level_string(&bids[..5]));
+    }
+
+    #[test]
+    fn test_checksum_verifier_matches_verify_checksum() {
+        let mut verifier = ChecksumVerifier::new();
+        let mut book = Orderbook::new();
+        book.apply_snapshot(vec![(dec!(100.5), dec!(1))], vec![(dec!(101), dec!(2))]);
+        assert!(verifier.verify("BTC/USD", &book, 0).is_none());
+        
+        verifier.set_instrument(&InstrumentInfo {
+            symbol: "BTC/USD".to_string(),
+            price_precision: 1,
+            qty_precision: 8,
+            ..Default::default()
+        });
+        assert!(verifier.knows("BTC/USD"));
+        
+        let bids: Vec<_> = (0..25).map(|i| (dec!(50000.0) - Decimal::from(i), dec!(1.5))).collect();
+        let asks: Vec<_> = (0..25).map(|i| (dec!(50001.0) + Decimal::from(i), dec!(0.00012345))).collect();
+        book.apply_snapshot(bids, asks);
+        for step in 0..30 {
+            // Same buffer across books of different shapes
+            let price = dec!(50001.0) + Decimal::from(step % 12);
+            book.apply_updates(vec![], vec![(price, if step % 3 == 0 { dec!(0) } else { dec!(4.2) })]);
+            let expected = compute_crc32(&build_checksum_string(&book, 1, 8));
+            for e in [expected, expected ^ 1] {
+                let result = verifier.verify("BTC/USD", &book, e).unwrap();
+                assert_eq!(result.matched, verify_checksum(&book, e, 1, 8));
+                assert_eq!(result.computed, expected);
+                assert_eq!(result.checksum_len, build_checksum_string(&book, 1, 8).len());
+            }
+        }
+    }
+
+    #[test]
+    fn test_negative_level_reported_on_mismatch() {
+        let mut book = Orderbook::new();
+        book.apply_snapshot(
+            vec![(dec!(100.0), dec!(1)), (dec!(99.5), dec!(2))],
+            vec![(dec!(101.0), dec!(1)), (dec!(101.5), dec!(3))],
+        );
+        let good = book.checksum(1, 8);
+        assert_eq!(verify_checksum_detailed(&mut book, good ^ 1, 1, 8).negative_level, None);
+        
+        // Updates store whatever non-zero qty they carry
+        book.apply_updates(vec![(dec!(99.5), dec!(-0.25))], vec![]);
+        assert!(build_checksum_string(&book, 1, 8).contains('-'));
+        let negative = find_negative_level(&book, CHECKSUM_DEPTH).unwrap();
+        assert_eq!(negative, NegativeLevel { side: Side::Bid, price: dec!(99.5), qty: dec!(-0.25) });
+        assert_eq!(negative.to_string(), "negative qty in book: bid -0.25 @ 99.5");
+        
+        let verification = verify_checksum_detailed(&mut book, good, 1, 8);
+        assert!(!verification.matched);
+        assert_eq!(verification.negative_level, Some(negative));
+        
+        let mut verifier = ChecksumVerifier::new();
+        verifier.set_instrument(&InstrumentInfo {
+            symbol: "BTC/USD".to_string(),
+            price_precision: 1,
+            qty_precision: 8,
+            ..Default::default()
+        });
+        assert_eq!(verifier.verify("BTC/USD", &book, good).unwrap().negative_level, Some(negative));
+        
+        // Outside the checksummed levels it doesn't count
+        assert_eq!(find_negative_level(&book, 1), None);
+    }
+
+    #[test]
+    fn test_verify_checksum_detailed() {
+        let mut book = Orderbook::new();
+        let bids: Vec<_> = (0..25).map(|i| (dec!(50000.0) - Decimal::from(i), dec!(1.5))).collect();
+        let asks: Vec<_> = (0..25).map(|i| (dec!(50001.0) + Decimal::from(i), dec!(2.5))).collect();
+        book.apply_snapshot(bids, asks);
+        
+        let checksum_str = build_checksum_string(&book, 1, 8);
+        let crc = compute_crc32(&checksum_str);
+        
+        for expected in [crc, crc.wrapping_add(1)] {
+            let detailed = verify_checksum_detailed(&mut book, expected, 1, 8);
+            assert_eq!(detailed.matched, verify_checksum(&book, expected, 1, 8));
+            assert_eq!(detailed.matched, verify_checksum_cached(&mut book, expected, 1, 8));
+            assert_eq!(detailed.expected, expected);
+            assert_eq!(detailed.computed, crc);
+            assert_eq!(detailed.checksum_len, checksum_str.len());
+            assert_eq!(detailed.preview, checksum_str[..CHECKSUM_PREVIEW_LEN]);
+        }
+        
+        // Short strings are previewed whole
+        let mut small = Orderbook::new();
+        small.apply_snapshot(vec![(dec!(100), dec!(1))], vec![]);
+        let detailed = verify_checksum_detailed(&mut small, 0, 1, 1);
+        assert_eq!(detailed.preview, build_checksum_string(&small, 1, 1));
+    }
+
+    #[test]
+    fn test_cached_checksum_matches_from_scratch() {
+        let mut book = Orderbook::new();
+        let mut bids = Vec::new();
+        let mut asks = Vec::new();
+        for i in 0..25 {
+            bids.push((dec!(50000.0) - Decimal::from(i), dec!(1.5)));
+            asks.push((dec!(50001.0) + Decimal::from(i), dec!(2.5)));
+        }
+        
+        // Snapshot
+        book.apply_snapshot(bids, asks);
+        assert_cache_matches(&mut book, 1, 8);
+        
+        // Insert inside and outside the top 10
+        book.apply_updates(vec![(dec!(50000.5), dec!(0.1))], vec![(dec!(50040.0), dec!(7.0))]);
+        assert_cache_matches(&mut book, 1, 8);
+        
+        // Qty change on the best levels
+        book.apply_updates(vec![(dec!(50000.5), dec!(0.2))], vec![(dec!(50001.0), dec!(9.0))]);
+        assert_cache_matches(&mut book, 1, 8);
+        
+        // Remove inside the top 10 (pulls level 11 in) and outside it
+        book.apply_updates(vec![(dec!(49995.0), dec!(0))], vec![(dec!(50003.0), dec!(0)), (dec!(50020.0), dec!(0))]);
+        assert_cache_matches(&mut book, 1, 8);
+        
+        // Truncate above and below the checksum depth
+        book.truncate(12);
+        assert_cache_matches(&mut book, 1, 8);
+        book.truncate(4);
+        assert_cache_matches(&mut book, 1, 8);
+        
+        // Precision change rebuilds
+        assert_cache_matches(&mut book, 2, 4);
+        
+        // Drain a side entirely
+        let remaining: Vec<_> = book.asks_vec(None).into_iter().map(|(p, _)| (p, Decimal::ZERO)).collect();
+        book.apply_updates(vec![], remaining);
+        assert_cache_matches(&mut book, 2, 4);
+    }
 }
 
@@ -0,0 +1,147 @@
+use crate::orderbook::Orderbook;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// A level present in both books with a different quantity
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LevelChange {
+    pub price: Decimal,
+    pub old_qty: Decimal,
+    pub new_qty: Decimal,
+}
+
+/// Level differences for one side of the book
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SideDiff {
+    pub added: Vec<(Decimal, Decimal)>,   // (price, qty) only in the other book
+    pub removed: Vec<(Decimal, Decimal)>, // (price, qty) only in this book
+    pub changed: Vec<LevelChange>,
+}
+
+impl SideDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    fn between(from: &[(Decimal, Decimal)], to: &[(Decimal, Decimal)]) -> Self {
+        let from_map: BTreeMap<Decimal, Decimal> = from.iter().cloned().collect();
+        let to_map: BTreeMap<Decimal, Decimal> = to.iter().cloned().collect();
+        let mut diff = SideDiff::default();
+
+        // Keep the side's natural ordering (best level first)
+        for (price, qty) in from {
+            match to_map.get(price) {
+                None => diff.removed.push((*price, *qty)),
+                Some(new_qty) if new_qty != qty => diff.changed.push(LevelChange {
+                    price: *price,
+                    old_qty: *qty,
+                    new_qty: *new_qty,
+                }),
+                Some(_) => {}
+            }
+        }
+        for (price, qty) in to {
+            if !from_map.contains_key(price) {
+                diff.added.push((*price, *qty));
+            }
+        }
+
+        diff
+    }
+}
+
+/// Differences between two orderbooks over their top `depth` levels per side
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BookDiff {
+    pub depth: usize,
+    pub bids: SideDiff,
+    pub asks: SideDiff,
+}
+
+impl BookDiff {
+    pub fn is_empty(&self) -> bool {
+        self.bids.is_empty() && self.asks.is_empty()
+    }
+}
+
+impl Orderbook {
+    /// Diff the top `depth` levels of this book against `other`.
+    /// `added`/`removed`/`changed` describe how to get from `self` to `other`.
+    pub fn diff(&self, other: &Orderbook, depth: usize) -> BookDiff {
+        BookDiff {
+            depth,
+            bids: SideDiff::between(&self.bids_vec(Some(depth)), &other.bids_vec(Some(depth))),
+            asks: SideDiff::between(&self.asks_vec(Some(depth)), &other.asks_vec(Some(depth))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    fn book(bids: Vec<(Decimal, Decimal)>, asks: Vec<(Decimal, Decimal)>) -> Orderbook {
+        let mut book = Orderbook::new();
+        book.apply_snapshot(bids, asks);
+        book
+    }
+
+    #[test]
+    fn test_diff_identical() {
+        let a = book(vec![(dec!(100), dec!(1))], vec![(dec!(101), dec!(2))]);
+        let diff = a.diff(&a.clone(), 10);
+        assert!(diff.is_empty());
+    }
+
+    #[test]
+    fn test_diff_disjoint() {
+        let a = book(vec![(dec!(100), dec!(1))], vec![(dec!(101), dec!(2))]);
+        let b = book(vec![(dec!(90), dec!(3))], vec![(dec!(111), dec!(4))]);
+        let diff = a.diff(&b, 10);
+
+        assert_eq!(diff.bids.removed, vec![(dec!(100), dec!(1))]);
+        assert_eq!(diff.bids.added, vec![(dec!(90), dec!(3))]);
+        assert_eq!(diff.asks.removed, vec![(dec!(101), dec!(2))]);
+        assert_eq!(diff.asks.added, vec![(dec!(111), dec!(4))]);
+        assert!(diff.bids.changed.is_empty());
+        assert!(diff.asks.changed.is_empty());
+    }
+
+    #[test]
+    fn test_diff_qty_only() {
+        let a = book(
+            vec![(dec!(100), dec!(1)), (dec!(99), dec!(2))],
+            vec![(dec!(101), dec!(2))],
+        );
+        let b = book(
+            vec![(dec!(100), dec!(1.5)), (dec!(99), dec!(2))],
+            vec![(dec!(101), dec!(0.5))],
+        );
+        let diff = a.diff(&b, 10);
+
+        assert!(diff.bids.added.is_empty() && diff.bids.removed.is_empty());
+        assert_eq!(
+            diff.bids.changed,
+            vec![LevelChange { price: dec!(100), old_qty: dec!(1), new_qty: dec!(1.5) }]
+        );
+        assert_eq!(
+            diff.asks.changed,
+            vec![LevelChange { price: dec!(101), old_qty: dec!(2), new_qty: dec!(0.5) }]
+        );
+
+        // Round-trips through serde
+        let json = serde_json::to_string(&diff).unwrap();
+        let parsed: BookDiff = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, diff);
+    }
+
+    #[test]
+    fn test_diff_respects_depth() {
+        let a = book(vec![(dec!(100), dec!(1)), (dec!(99), dec!(1))], vec![]);
+        let b = book(vec![(dec!(100), dec!(1)), (dec!(99), dec!(5))], vec![]);
+        assert!(a.diff(&b, 1).is_empty());
+        assert!(!a.diff(&b, 2).is_empty());
+    }
+}
@@ -0,0 +1,96 @@
+use crate::checksum::{build_checksum_string, compute_crc32};
+use crate::orderbook::{Orderbook, PriceLevels};
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// A book and the CRC32 the exchange sent for it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChecksumVector {
+    pub name: String,
+    #[serde(default)]
+    pub source: Option<String>, // where the expected checksum came from
+    pub price_precision: u32,
+    pub qty_precision: u32,
+    pub bids: PriceLevels, // any order
+    pub asks: PriceLevels, // any order
+    pub checksum: u32,
+}
+
+/// On-disk layout of a checksum vector file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChecksumVectorFile {
+    pub vectors: Vec<ChecksumVector>,
+}
+
+/// Outcome of checking one vector
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VectorResult {
+    pub name: String,
+    pub expected: u32,
+    pub computed: u32,
+    pub passed: bool,
+}
+
+impl ChecksumVector {
+    /// Build the book and compare its checksum with the expected one
+    pub fn check(&self) -> VectorResult {
+        let mut book = Orderbook::new();
+        book.apply_snapshot(self.bids.clone(), self.asks.clone());
+        let checksum_str = build_checksum_string(&book, self.price_precision, self.qty_precision);
+        let computed = compute_crc32(&checksum_str);
+
+        VectorResult {
+            name: self.name.clone(),
+            expected: self.checksum,
+            computed,
+            passed: computed == self.checksum,
+        }
+    }
+}
+
+/// Load a JSON file of checksum vectors
+pub fn load_checksum_vectors<P: AsRef<Path>>(path: P) -> anyhow::Result<Vec<ChecksumVector>> {
+    let path = path.as_ref();
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read checksum vectors from {}", path.display()))?;
+    let file: ChecksumVectorFile = serde_json::from_str(&content)
+        .with_context(|| format!("Invalid checksum vector file {}", path.display()))?;
+    Ok(file.vectors)
+}
+
+/// Check every vector in a file, in file order
+pub fn run_checksum_vectors<P: AsRef<Path>>(path: P) -> anyhow::Result<Vec<VectorResult>> {
+    Ok(load_checksum_vectors(path)?.iter().map(ChecksumVector::check).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VECTORS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/checksum_vectors.json");
+
+    #[test]
+    fn test_checksum_vectors() {
+        let results = run_checksum_vectors(VECTORS).unwrap();
+        assert!(results.len() >= 5);
+        for result in &results {
+            assert!(
+                result.passed,
+                "{}: expected {} but computed {}",
+                result.name, result.expected, result.computed
+            );
+        }
+    }
+
+    #[test]
+    fn test_checksum_vector_mismatch_reported() {
+        let mut vector = load_checksum_vectors(VECTORS).unwrap().remove(0);
+        vector.checksum = vector.checksum.wrapping_add(1);
+        let result = vector.check();
+        assert!(!result.passed);
+        assert_eq!(result.expected, vector.checksum);
+
+        assert!(run_checksum_vectors("does/not/exist.json").is_err());
+    }
+}
@@ -1,7 +1,7 @@
 use chrono::{DateTime, Utc};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Default, Serialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct SymbolHealth {
     pub symbol: String,
     pub connected: bool,
@@ -12,7 +12,11 @@ pub struct SymbolHealth {
     pub last_checksum_mismatch: Option<DateTime<Utc>>,
     pub consecutive_fails: u64,
     pub reconnect_count: u64,
+    #[serde(default)] // missing from older state snapshots
+    pub resync_count: u64, // re-subscriptions after repeated checksum failures
     pub msg_rate_estimate: f64, // messages per second
+    pub book_crossed: bool, // best bid >= best ask on the local book
+    pub feed_latency_ms: Option<f64>, // exchange timestamp -> receipt, of the last book update
 }
 
 impl SymbolHealth {
@@ -47,6 +51,11 @@ impl SymbolHealth {
             score = score.saturating_sub((self.consecutive_fails.min(10) * 5) as u8);
         }
         
+        // Deduct while the local book is crossed/locked
+        if self.book_crossed {
+            score = score.saturating_sub(15);
+        }
+        
         // Deduct if not connected
         if !self.connected {
             score = score.saturating_sub(50);
@@ -95,6 +104,13 @@ impl SymbolHealth {
     pub fn update_msg_rate(&mut self, rate: f64) {
         self.msg_rate_estimate = rate;
     }
+
+    /// Update crossed state; returns true when the book has just become crossed
+    pub fn set_book_crossed(&mut self, crossed: bool) -> bool {
+        let newly_crossed = crossed && !self.book_crossed;
+        self.book_crossed = crossed;
+        newly_crossed
+    }
 }
 
 #[derive(Debug, Clone, Copy, Serialize)]
@@ -8,6 +8,7 @@ pub enum IncidentReason {
     Disconnect,
     ManualExport,
     FaultInject,
+    BookInvariant,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -48,6 +49,7 @@ fn reason_str(reason: &IncidentReason) -> &str {
         IncidentReason::Disconnect => "disconnect",
         IncidentReason::ManualExport => "manual",
         IncidentReason::FaultInject => "fault",
+        IncidentReason::BookInvariant => "invariant",
     }
 }
 
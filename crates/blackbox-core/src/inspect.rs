@@ -0,0 +1,164 @@
+//! Summary of what a recording contains, read in one streaming pass
+
+use crate::recorder::{frame_channel, open_recording, parse_recording_line, RecordingLine};
+use crate::types::RecordingMetadata;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::BufRead;
+use std::path::Path;
+
+/// Book messages seen for one symbol
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SymbolCounts {
+    pub snapshots: usize,
+    pub updates: usize,
+}
+
+/// The longest stretch without a frame
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FrameGap {
+    pub frame_index: usize, // the frame that ended the gap
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub gap_ms: i64,
+}
+
+/// Result of `inspect_recording`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RecordingSummary {
+    pub metadata: Option<RecordingMetadata>, // the `{"_meta": ...}` header, if any
+    pub total_frames: usize,
+    pub first_ts: Option<DateTime<Utc>>,
+    pub last_ts: Option<DateTime<Utc>>,
+    pub duration_ms: i64,
+    pub channels: BTreeMap<String, usize>, // "unknown" for frames without a channel
+    pub symbols: BTreeMap<String, SymbolCounts>,
+    pub largest_gap: Option<FrameGap>,
+    pub instrument_snapshot: bool,
+}
+
+#[derive(Deserialize)]
+struct BookEnvelope {
+    #[serde(rename = "type")]
+    msg_type: String,
+    #[serde(default)]
+    data: Vec<BookSymbol>,
+}
+
+#[derive(Deserialize)]
+struct BookSymbol {
+    symbol: String,
+}
+
+#[derive(Deserialize)]
+struct TypeEnvelope {
+    #[serde(rename = "type")]
+    msg_type: Option<String>,
+}
+
+/// Count what a recording holds without loading it: frames per channel, book
+/// messages per symbol, the time span and the largest gap between frames.
+/// Gzip recordings are read the same way.
+pub fn inspect_recording<P: AsRef<Path>>(path: P) -> anyhow::Result<RecordingSummary> {
+    let path = path.as_ref();
+    let reader = open_recording(path).with_context(|| format!("Failed to open recording {}", path.display()))?;
+    let mut summary = RecordingSummary::default();
+
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let frame = match parse_recording_line(&line)
+            .with_context(|| format!("{}: frame {} is not a recorded frame", path.display(), summary.total_frames))?
+        {
+            RecordingLine::Metadata(meta) => {
+                summary.metadata.get_or_insert(meta);
+                continue;
+            }
+            RecordingLine::Frame(frame) => frame,
+        };
+        let frame_index = summary.total_frames;
+        summary.total_frames += 1;
+
+        if let Some(last) = summary.last_ts {
+            let gap_ms = (frame.ts - last).num_milliseconds();
+            if summary.largest_gap.as_ref().is_none_or(|gap| gap_ms > gap.gap_ms) {
+                summary.largest_gap = Some(FrameGap { frame_index, from: last, to: frame.ts, gap_ms });
+            }
+        }
+        summary.first_ts.get_or_insert(frame.ts);
+        summary.last_ts = Some(frame.ts);
+
+        let channel = frame_channel(&frame.raw_frame, frame.decoded_event.as_deref())
+            .map(|c| c.into_owned())
+            .unwrap_or_else(|| "unknown".to_string());
+        match channel.as_str() {
+            "book" => {
+                if let Ok(book) = serde_json::from_str::<BookEnvelope>(&frame.raw_frame) {
+                    for data in book.data {
+                        let counts = summary.symbols.entry(data.symbol).or_default();
+                        if book.msg_type == "snapshot" {
+                            counts.snapshots += 1;
+                        } else {
+                            counts.updates += 1;
+                        }
+                    }
+                }
+            }
+            "instrument" if !summary.instrument_snapshot => {
+                summary.instrument_snapshot = serde_json::from_str::<TypeEnvelope>(&frame.raw_frame)
+                    .is_ok_and(|msg| msg.msg_type.as_deref() == Some("snapshot"));
+            }
+            _ => {}
+        }
+        *summary.channels.entry(channel).or_default() += 1;
+    }
+
+    if let (Some(first), Some(last)) = (summary.first_ts, summary.last_ts) {
+        summary.duration_ms = (last - first).num_milliseconds();
+    }
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    const RECORDING: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/recording_corrupted.ndjson");
+
+    fn assert_fixture_summary(summary: &RecordingSummary) {
+        assert_eq!(summary.total_frames, 9);
+        assert_eq!(summary.duration_ms, 4000);
+        assert_eq!(summary.first_ts.unwrap().to_rfc3339(), "2024-01-15T10:30:44+00:00");
+        assert_eq!(summary.last_ts.unwrap().to_rfc3339(), "2024-01-15T10:30:48+00:00");
+        let channels: Vec<(&str, usize)> = summary.channels.iter().map(|(c, n)| (c.as_str(), *n)).collect();
+        assert_eq!(channels, vec![("ack", 1), ("book", 6), ("heartbeat", 1), ("instrument", 1)]);
+        assert_eq!(summary.symbols["BTC/USD"], SymbolCounts { snapshots: 1, updates: 5 });
+        assert!(summary.instrument_snapshot);
+        assert!(summary.metadata.is_none());
+
+        // Several one-second gaps; the first is kept
+        let gap = summary.largest_gap.as_ref().unwrap();
+        assert_eq!((gap.frame_index, gap.gap_ms), (2, 1000));
+    }
+
+    #[test]
+    fn test_inspect_recording() {
+        assert_fixture_summary(&inspect_recording(RECORDING).unwrap());
+    }
+
+    #[test]
+    fn test_inspect_gzip_recording() {
+        let path = std::env::temp_dir().join(format!("blackbox_inspect_{}.ndjson.gz", std::process::id()));
+        let mut encoder = flate2::write::GzEncoder::new(std::fs::File::create(&path).unwrap(), flate2::Compression::default());
+        encoder.write_all(&std::fs::read(RECORDING).unwrap()).unwrap();
+        encoder.finish().unwrap();
+
+        assert_fixture_summary(&inspect_recording(&path).unwrap());
+        let _ = std::fs::remove_file(path);
+    }
+}
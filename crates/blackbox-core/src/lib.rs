@@ -1,18 +1,28 @@
 pub mod checksum;
+pub mod diff;
+pub mod fixtures;
 pub mod health;
 pub mod incident;
+pub mod inspect;
+pub mod merge;
 pub mod orderbook;
 pub mod precision;
 pub mod recorder;
 pub mod replayer;
 pub mod types;
+pub mod verify;
 
 pub use checksum::*;
+pub use diff::*;
+pub use fixtures::*;
 pub use health::*;
 pub use incident::*;
+pub use inspect::*;
+pub use merge::*;
 pub use orderbook::*;
 pub use precision::*;
 pub use recorder::*;
 pub use replayer::*;
 pub use types::*;
+pub use verify::*;
 
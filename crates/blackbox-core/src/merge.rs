@@ -0,0 +1,147 @@
+use crate::orderbook::{Orderbook, Side};
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// How `Orderbook::merged_with` resolves a price level present in both books
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MergePolicy {
+    /// Larger quantity wins; levels from either book are kept
+    MaxQty,
+    /// Smaller quantity wins; a level missing from one book counts as zero and is dropped
+    MinQty,
+    /// Most recently updated level wins; levels from either book are kept.
+    /// Uses per-level metadata when both books track it, else each book's last update time.
+    Newest,
+}
+
+impl Orderbook {
+    /// Merge two books (e.g. the same symbol from two feeds) level by level.
+    /// Ties, and `Newest` without any timestamps, resolve to `self`.
+    pub fn merged_with(&self, other: &Orderbook, policy: MergePolicy) -> Orderbook {
+        let bids = merge_side(self, other, Side::Bid, policy);
+        let asks = merge_side(self, other, Side::Ask, policy);
+        
+        let mut merged = Orderbook::new();
+        merged.apply_snapshot(bids, asks);
+        if let Some(ts) = self.last_update_ts().max(other.last_update_ts()) {
+            merged.set_last_update_ts(ts);
+        }
+        merged
+    }
+}
+
+fn side_levels(book: &Orderbook, side: Side) -> BTreeMap<Decimal, Decimal> {
+    match side {
+        Side::Bid => book.top_bids(usize::MAX).map(|(p, q)| (*p, *q)).collect(),
+        Side::Ask => book.top_asks(usize::MAX).map(|(p, q)| (*p, *q)).collect(),
+    }
+}
+
+fn touched_at(book: &Orderbook, price: Decimal, side: Side) -> Option<DateTime<Utc>> {
+    book.level_meta(price, side)
+        .map(|meta| meta.last_update)
+        .or(book.last_update_ts())
+}
+
+fn merge_side(a: &Orderbook, b: &Orderbook, side: Side, policy: MergePolicy) -> Vec<(Decimal, Decimal)> {
+    let a_levels = side_levels(a, side);
+    let b_levels = side_levels(b, side);
+    let prices: BTreeSet<Decimal> = a_levels.keys().chain(b_levels.keys()).copied().collect();
+    
+    prices
+        .into_iter()
+        .filter_map(|price| {
+            let qty = match (a_levels.get(&price), b_levels.get(&price), policy) {
+                (Some(&qa), Some(&qb), MergePolicy::MaxQty) => qa.max(qb),
+                (Some(&qa), Some(&qb), MergePolicy::MinQty) => qa.min(qb),
+                (Some(&qa), Some(&qb), MergePolicy::Newest) => {
+                    if touched_at(b, price, side) > touched_at(a, price, side) { qb } else { qa }
+                }
+                (_, _, MergePolicy::MinQty) => return None,
+                (Some(&q), None, _) | (None, Some(&q), _) => q,
+                (None, None, _) => return None,
+            };
+            Some((price, qty))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    fn book(bids: Vec<(Decimal, Decimal)>, asks: Vec<(Decimal, Decimal)>) -> Orderbook {
+        let mut book = Orderbook::new();
+        book.apply_snapshot(bids, asks);
+        book
+    }
+
+    #[test]
+    fn test_merge_overlapping() {
+        let a = book(
+            vec![(dec!(100), dec!(1)), (dec!(99), dec!(5))],
+            vec![(dec!(101), dec!(3))],
+        );
+        let b = book(
+            vec![(dec!(100), dec!(2)), (dec!(99), dec!(4))],
+            vec![(dec!(101), dec!(1))],
+        );
+        
+        let max = a.merged_with(&b, MergePolicy::MaxQty);
+        assert_eq!(max.bids_vec(None), vec![(dec!(100), dec!(2)), (dec!(99), dec!(5))]);
+        assert_eq!(max.asks_vec(None), vec![(dec!(101), dec!(3))]);
+        
+        let min = a.merged_with(&b, MergePolicy::MinQty);
+        assert_eq!(min.bids_vec(None), vec![(dec!(100), dec!(1)), (dec!(99), dec!(4))]);
+        assert_eq!(min.asks_vec(None), vec![(dec!(101), dec!(1))]);
+    }
+
+    #[test]
+    fn test_merge_disjoint() {
+        let a = book(vec![(dec!(100), dec!(1))], vec![(dec!(102), dec!(1))]);
+        let b = book(vec![(dec!(99), dec!(2))], vec![(dec!(101), dec!(2))]);
+        
+        let max = a.merged_with(&b, MergePolicy::MaxQty);
+        assert_eq!(max.bids_vec(None), vec![(dec!(100), dec!(1)), (dec!(99), dec!(2))]);
+        assert_eq!(max.asks_vec(None), vec![(dec!(101), dec!(2)), (dec!(102), dec!(1))]);
+        
+        // Nothing is confirmed by both feeds
+        let min = a.merged_with(&b, MergePolicy::MinQty);
+        assert_eq!(min.depth(), (0, 0));
+    }
+
+    #[test]
+    fn test_merge_newest() {
+        let t0 = "2024-01-15T10:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        let t1 = "2024-01-15T10:00:01Z".parse::<DateTime<Utc>>().unwrap();
+        
+        let mut a = book(vec![(dec!(100), dec!(1))], vec![(dec!(101), dec!(1))]);
+        let mut b = book(vec![(dec!(100), dec!(2)), (dec!(98), dec!(1))], vec![]);
+        
+        // No timestamps anywhere: self wins the overlap
+        let merged = a.merged_with(&b, MergePolicy::Newest);
+        assert_eq!(merged.bids_vec(None), vec![(dec!(100), dec!(1)), (dec!(98), dec!(1))]);
+        assert_eq!(merged.asks_vec(None), vec![(dec!(101), dec!(1))]);
+        
+        a.set_last_update_ts(t0);
+        b.set_last_update_ts(t1);
+        let merged = a.merged_with(&b, MergePolicy::Newest);
+        assert_eq!(merged.best_bid(), Some((dec!(100), dec!(2))));
+        assert_eq!(merged.last_update_ts(), Some(t1));
+        
+        // Per-level metadata beats the book-level timestamp, which would pick `b`
+        let mut b = Orderbook::new().with_level_meta();
+        b.apply_snapshot(vec![(dec!(100), dec!(2))], vec![]);
+        b.set_last_update_ts(t1);
+        let mut a = Orderbook::new().with_level_meta();
+        a.apply_snapshot(vec![(dec!(100), dec!(1))], vec![]);
+        a.apply_updates(vec![(dec!(100), dec!(3))], vec![]);
+        a.set_last_update_ts(t0);
+        let merged = a.merged_with(&b, MergePolicy::Newest);
+        assert_eq!(merged.best_bid(), Some((dec!(100), dec!(3))));
+    }
+}
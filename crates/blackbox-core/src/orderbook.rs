@@ -1,5 +1,18 @@
+use crate::checksum::{compute_crc32, write_checksum_string, CHECKSUM_DEPTH};
+use crate::precision::{parse_decimal, PrecisionFormatter};
+use crate::types::{BookData, BookLevelData};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use rust_decimal::prelude::ToPrimitive;
 use rust_decimal::Decimal;
-use std::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+
+/// Rough per-entry BTreeMap overhead (node headers and spare slots, amortised)
+const BTREE_ENTRY_OVERHEAD: usize = 16;
+
+/// (price, qty) levels, best first
+pub type PriceLevels = Vec<(Decimal, Decimal)>;
 
 /// In-memory orderbook maintaining bids and asks
 /// Uses BTreeMap for ordered iteration
@@ -9,6 +22,119 @@ pub struct Orderbook {
     asks: BTreeMap<Decimal, Decimal>,
     // Bids: price -> qty (ascending order, but we iterate reverse for highest first)
     bids: BTreeMap<Decimal, Decimal>,
+    // Formatted top-of-book checksum string, cleared whenever a checksummed level changes
+    checksum_cache: Option<ChecksumCache>,
+    // Levels per side included in the checksum
+    checksum_levels: usize,
+    // Exchange timestamp of the last applied update (if the feed sent one)
+    last_update_ts: Option<DateTime<Utc>>,
+    // Incremented by every apply_snapshot/apply_updates
+    update_seq: u64,
+    // Hard cap on stored levels per side; worst levels are evicted past it
+    max_levels: Option<usize>,
+    // Per-level update metadata, only kept when enabled via `with_level_meta`
+    level_meta: Option<LevelMetaMaps>,
+    // Recently removed levels, only kept when enabled via `with_tombstones`
+    tombstones: Option<Tombstones>,
+}
+
+/// Book side
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Side {
+    Bid,
+    Ask,
+}
+
+/// Update history of a single price level
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct LevelMeta {
+    pub update_count: u64, // updates since the level appeared (snapshot levels start at 0)
+    pub last_update: DateTime<Utc>, // local time of the last update
+}
+
+/// Why a level left the book
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RemovalCause {
+    Deleted,   // qty 0 update from the feed
+    Evicted,   // pushed out by the max_levels cap
+    Truncated, // cut by `truncate`
+}
+
+/// A level that was removed from the book, as recorded by `with_tombstones`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Removal {
+    pub price: Decimal,
+    pub qty: Decimal, // quantity the level had before removal
+    pub cause: RemovalCause,
+    pub removed_at: DateTime<Utc>, // local time
+}
+
+/// A broken orderbook invariant reported by `Orderbook::validate`
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum BookInvariantError {
+    #[error("{side:?} level {price} has zero quantity")]
+    ZeroQuantity { side: Side, price: Decimal },
+    #[error("{side:?} level {price} has negative quantity {qty}")]
+    NegativeQuantity { side: Side, price: Decimal, qty: Decimal },
+    #[error("{side:?} level has non-positive price {price}")]
+    NonPositivePrice { side: Side, price: Decimal },
+    #[error("book crossed: best bid {bid} >= best ask {ask}")]
+    Crossed { bid: Decimal, ask: Decimal },
+}
+
+#[derive(Debug, Clone, Default)]
+struct LevelMetaMaps {
+    bids: BTreeMap<Decimal, LevelMeta>,
+    asks: BTreeMap<Decimal, LevelMeta>,
+}
+
+impl LevelMetaMaps {
+    fn side_mut(&mut self, side: Side) -> &mut BTreeMap<Decimal, LevelMeta> {
+        match side {
+            Side::Bid => &mut self.bids,
+            Side::Ask => &mut self.asks,
+        }
+    }
+}
+
+/// Bounded per-side ring buffers of recent removals, oldest first
+#[derive(Debug, Clone)]
+struct Tombstones {
+    capacity: usize,
+    bids: VecDeque<Removal>,
+    asks: VecDeque<Removal>,
+}
+
+impl Tombstones {
+    fn push(&mut self, side: Side, removal: Removal) {
+        let buf = match side {
+            Side::Bid => &mut self.bids,
+            Side::Ask => &mut self.asks,
+        };
+        if buf.len() == self.capacity {
+            buf.pop_front();
+        }
+        buf.push_back(removal);
+    }
+}
+
+/// Serializable copy of a full orderbook, for persisting and restoring books
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BookSnapshot {
+    pub bids: Vec<(Decimal, Decimal)>, // high -> low
+    pub asks: Vec<(Decimal, Decimal)>, // low -> high
+    pub update_seq: u64,
+    pub last_update_ts: Option<DateTime<Utc>>,
+}
+
+/// Cached checksum input for one (price_precision, qty_precision) pair
+#[derive(Debug, Clone)]
+struct ChecksumCache {
+    formatter: PrecisionFormatter,
+    checksum_str: String,
+    crc32: u32,
 }
 
 impl Orderbook {
@@ -16,13 +142,113 @@ impl Orderbook {
         Self {
             asks: BTreeMap::new(),
             bids: BTreeMap::new(),
+            checksum_cache: None,
+            checksum_levels: CHECKSUM_DEPTH,
+            last_update_ts: None,
+            update_seq: 0,
+            max_levels: None,
+            level_meta: None,
+            tombstones: None,
+        }
+    }
+
+    /// Parse the (bids, asks) levels of a book message.
+    /// Prices and quantities may be JSON numbers or strings; any malformed
+    /// level fails the whole message rather than being skipped.
+    pub fn levels_from_book_data(
+        data: &BookData,
+    ) -> anyhow::Result<(PriceLevels, PriceLevels)> {
+        let bids = parse_levels(data.bids.as_deref(), "bid")?;
+        let asks = parse_levels(data.asks.as_deref(), "ask")?;
+        Ok((bids, asks))
+    }
+
+    /// Also track per-level update counts and times (see `level_meta`).
+    /// Off by default to keep the update path cheap.
+    pub fn with_level_meta(mut self) -> Self {
+        self.level_meta = Some(LevelMetaMaps::default());
+        self
+    }
+
+    /// Whether per-level metadata is being tracked
+    pub fn tracks_level_meta(&self) -> bool {
+        self.level_meta.is_some()
+    }
+
+    /// Update metadata for a level, if tracking is enabled and the level exists
+    pub fn level_meta(&self, price: Decimal, side: Side) -> Option<LevelMeta> {
+        let meta = self.level_meta.as_ref()?;
+        match side {
+            Side::Bid => meta.bids.get(&price).copied(),
+            Side::Ask => meta.asks.get(&price).copied(),
+        }
+    }
+
+    /// Checksum the top `levels` levels per side instead of Kraken's 10
+    pub fn with_checksum_levels(mut self, levels: usize) -> Self {
+        self.set_checksum_levels(levels);
+        self
+    }
+
+    /// Change the checksummed level count (at least 1), dropping the cached checksum if it differs
+    pub fn set_checksum_levels(&mut self, levels: usize) {
+        let levels = levels.max(1);
+        if levels != self.checksum_levels {
+            self.checksum_levels = levels;
+            self.checksum_cache = None;
+        }
+    }
+
+    /// Levels per side included in the checksum
+    pub fn checksum_levels(&self) -> usize {
+        self.checksum_levels
+    }
+
+    /// Remember the last `capacity` removed levels per side (see `recent_removals`).
+    /// Off by default; a capacity of 0 leaves it off.
+    pub fn with_tombstones(mut self, capacity: usize) -> Self {
+        self.tombstones = (capacity > 0).then(|| Tombstones {
+            capacity,
+            bids: VecDeque::with_capacity(capacity),
+            asks: VecDeque::with_capacity(capacity),
+        });
+        self
+    }
+
+    /// Up to `limit` most recently removed levels on `side`, newest first.
+    /// Empty unless tombstones are enabled.
+    pub fn recent_removals(&self, side: Side, limit: usize) -> Vec<Removal> {
+        let Some(tombstones) = &self.tombstones else {
+            return Vec::new();
+        };
+        let buf = match side {
+            Side::Bid => &tombstones.bids,
+            Side::Ask => &tombstones.asks,
+        };
+        buf.iter().rev().take(limit).copied().collect()
+    }
+
+    /// Create a book that never stores more than `max_levels` levels per side.
+    /// The cap is enforced on every insert, so bursts of updates can't grow the
+    /// maps between `truncate` calls.
+    pub fn with_max_levels(max_levels: usize) -> Self {
+        Self {
+            max_levels: Some(max_levels),
+            ..Self::new()
         }
     }
 
+    /// Per-side level cap, if any
+    pub fn max_levels(&self) -> Option<usize> {
+        self.max_levels
+    }
+
     /// Apply a snapshot (replace all levels)
     pub fn apply_snapshot(&mut self, bids: Vec<(Decimal, Decimal)>, asks: Vec<(Decimal, Decimal)>) {
         self.bids.clear();
         self.asks.clear();
+        self.checksum_cache = None;
+        self.update_seq += 1;
         
         for (price, qty) in bids {
             if qty > Decimal::ZERO {
@@ -35,31 +261,194 @@ impl Orderbook {
                 self.asks.insert(price, qty);
             }
         }
+        
+        if let Some(meta) = self.level_meta.as_mut() {
+            let now = Utc::now();
+            let fresh = LevelMeta { update_count: 0, last_update: now };
+            meta.bids = self.bids.keys().map(|p| (*p, fresh)).collect();
+            meta.asks = self.asks.keys().map(|p| (*p, fresh)).collect();
+        }
+        
+        if let Some(max) = self.max_levels {
+            self.truncate(max);
+        }
     }
 
-    /// Apply updates (incremental changes)
-    pub fn apply_updates(&mut self, bid_updates: Vec<(Decimal, Decimal)>, ask_updates: Vec<(Decimal, Decimal)>) {
+    /// Apply updates (incremental changes), returning what actually changed
+    pub fn apply_updates(&mut self, bid_updates: Vec<(Decimal, Decimal)>, ask_updates: Vec<(Decimal, Decimal)>) -> UpdateSummary {
+        self.update_seq += 1;
+        
+        let best_bid_before = self.best_bid().map(|(p, _)| p);
+        let best_ask_before = self.best_ask().map(|(p, _)| p);
+        let mut summary = UpdateSummary::default();
+        
         // Apply bid updates
         for (price, qty) in bid_updates {
-            if qty == Decimal::ZERO {
-                self.bids.remove(&price);
-            } else {
-                self.bids.insert(price, qty);
-            }
+            let (op, in_top) = self.set_bid(price, qty);
+            summary.record(op, in_top);
         }
         
         // Apply ask updates
         for (price, qty) in ask_updates {
-            if qty == Decimal::ZERO {
-                self.asks.remove(&price);
-            } else {
-                self.asks.insert(price, qty);
+            let (op, in_top) = self.set_ask(price, qty);
+            summary.record(op, in_top);
+        }
+        
+        summary.best_bid_moved = self.best_bid().map(|(p, _)| p) != best_bid_before;
+        summary.best_ask_moved = self.best_ask().map(|(p, _)| p) != best_ask_before;
+        summary
+    }
+
+    /// Copy every level into a serializable snapshot
+    pub fn to_snapshot(&self) -> BookSnapshot {
+        BookSnapshot {
+            bids: self.bids_vec(None),
+            asks: self.asks_vec(None),
+            update_seq: self.update_seq,
+            last_update_ts: self.last_update_ts,
+        }
+    }
+
+    /// Rebuild a book from a snapshot, keeping its sequence and timestamp
+    pub fn from_snapshot(snapshot: BookSnapshot) -> Self {
+        let mut book = Self::new();
+        book.apply_snapshot(snapshot.bids, snapshot.asks);
+        book.update_seq = snapshot.update_seq;
+        book.last_update_ts = snapshot.last_update_ts;
+        book
+    }
+
+    /// Record the exchange timestamp of the most recently applied message
+    pub fn set_last_update_ts(&mut self, ts: DateTime<Utc>) {
+        self.last_update_ts = Some(ts);
+    }
+
+    /// Exchange timestamp of the most recently applied message, if known
+    pub fn last_update_ts(&self) -> Option<DateTime<Utc>> {
+        self.last_update_ts
+    }
+
+    /// Number of snapshots/updates applied to this book
+    pub fn update_seq(&self) -> u64 {
+        self.update_seq
+    }
+
+    /// Set or remove (qty == 0) a single bid level.
+    /// Returns the operation and whether it touched the checksummed top levels.
+    fn set_bid(&mut self, price: Decimal, qty: Decimal) -> (LevelOp, bool) {
+        // Bids are checksummed high->low, so a level at or above the Nth best bid is visible
+        let in_top = match self.bids.keys().rev().nth(self.checksum_levels - 1) {
+            Some(boundary) => price >= *boundary,
+            None => true,
+        };
+        let prev_qty = self.removed_qty(Side::Bid, price, qty);
+        let op = set_level(&mut self.bids, price, qty);
+        if in_top && op != LevelOp::Unchanged {
+            self.checksum_cache = None;
+        }
+        self.touch_meta(Side::Bid, price, qty, op);
+        if let Some(prev) = prev_qty.filter(|_| op == LevelOp::Removed) {
+            self.record_removal(Side::Bid, price, prev, RemovalCause::Deleted);
+        }
+        if op == LevelOp::Inserted && self.max_levels.is_some_and(|max| self.bids.len() > max) {
+            // Evict the worst (lowest) bid
+            if let Some((evicted, evicted_qty)) = self.bids.pop_first() {
+                self.remove_meta(Side::Bid, &evicted);
+                self.record_removal(Side::Bid, evicted, evicted_qty, RemovalCause::Evicted);
+            }
+            self.evicted_level();
+        }
+        (op, in_top)
+    }
+
+    /// Set or remove (qty == 0) a single ask level.
+    /// Returns the operation and whether it touched the checksummed top levels.
+    fn set_ask(&mut self, price: Decimal, qty: Decimal) -> (LevelOp, bool) {
+        // Asks are checksummed low->high, so a level at or below the Nth best ask is visible
+        let in_top = match self.asks.keys().nth(self.checksum_levels - 1) {
+            Some(boundary) => price <= *boundary,
+            None => true,
+        };
+        let prev_qty = self.removed_qty(Side::Ask, price, qty);
+        let op = set_level(&mut self.asks, price, qty);
+        if in_top && op != LevelOp::Unchanged {
+            self.checksum_cache = None;
+        }
+        self.touch_meta(Side::Ask, price, qty, op);
+        if let Some(prev) = prev_qty.filter(|_| op == LevelOp::Removed) {
+            self.record_removal(Side::Ask, price, prev, RemovalCause::Deleted);
+        }
+        if op == LevelOp::Inserted && self.max_levels.is_some_and(|max| self.asks.len() > max) {
+            // Evict the worst (highest) ask
+            if let Some((evicted, evicted_qty)) = self.asks.pop_last() {
+                self.remove_meta(Side::Ask, &evicted);
+                self.record_removal(Side::Ask, evicted, evicted_qty, RemovalCause::Evicted);
+            }
+            self.evicted_level();
+        }
+        (op, in_top)
+    }
+
+    fn touch_meta(&mut self, side: Side, price: Decimal, qty: Decimal, op: LevelOp) {
+        let Some(meta) = self.level_meta.as_mut() else {
+            return;
+        };
+        let map = meta.side_mut(side);
+        let now = Utc::now();
+        match op {
+            LevelOp::Inserted => {
+                map.insert(price, LevelMeta { update_count: 1, last_update: now });
+            }
+            LevelOp::Removed => {
+                map.remove(&price);
             }
+            // Same-qty re-sends still count as an update of an existing level
+            LevelOp::Modified | LevelOp::Unchanged if qty != Decimal::ZERO => {
+                let entry = map.entry(price).or_insert(LevelMeta { update_count: 0, last_update: now });
+                entry.update_count += 1;
+                entry.last_update = now;
+            }
+            LevelOp::Modified | LevelOp::Unchanged => {}
+        }
+    }
+
+    fn remove_meta(&mut self, side: Side, price: &Decimal) {
+        if let Some(meta) = self.level_meta.as_mut() {
+            meta.side_mut(side).remove(price);
+        }
+    }
+
+    /// Current qty of a level a qty 0 update is about to delete, when tombstones are on
+    fn removed_qty(&self, side: Side, price: Decimal, qty: Decimal) -> Option<Decimal> {
+        if self.tombstones.is_none() || qty != Decimal::ZERO {
+            return None;
+        }
+        match side {
+            Side::Bid => self.bids.get(&price).copied(),
+            Side::Ask => self.asks.get(&price).copied(),
+        }
+    }
+
+    fn record_removal(&mut self, side: Side, price: Decimal, qty: Decimal, cause: RemovalCause) {
+        if let Some(tombstones) = self.tombstones.as_mut() {
+            tombstones.push(side, Removal { price, qty, cause, removed_at: Utc::now() });
+        }
+    }
+
+    fn evicted_level(&mut self) {
+        // A cap below the checksum depth means evictions are visible in the checksum
+        if self.max_levels.is_some_and(|max| max < self.checksum_levels) {
+            self.checksum_cache = None;
         }
     }
 
     /// Truncate to depth (keep best N levels)
     pub fn truncate(&mut self, depth: usize) {
+        // Truncating below the checksum depth removes visible levels
+        if depth < self.checksum_levels {
+            self.checksum_cache = None;
+        }
+        
         // Truncate asks: keep lowest (first) `depth` levels
         if self.asks.len() > depth {
             let keys_to_remove: Vec<Decimal> = self.asks
@@ -68,7 +457,10 @@ impl Orderbook {
                 .cloned()
                 .collect();
             for key in keys_to_remove {
-                self.asks.remove(&key);
+                if let Some(qty) = self.asks.remove(&key) {
+                    self.record_removal(Side::Ask, key, qty, RemovalCause::Truncated);
+                }
+                self.remove_meta(Side::Ask, &key);
             }
         }
         
@@ -80,7 +472,10 @@ impl Orderbook {
                 .cloned()
                 .collect();
             for key in keys_to_remove {
-                self.bids.remove(&key);
+                if let Some(qty) = self.bids.remove(&key) {
+                    self.record_removal(Side::Bid, key, qty, RemovalCause::Truncated);
+                }
+                self.remove_meta(Side::Bid, &key);
             }
         }
     }
@@ -111,6 +506,159 @@ impl Orderbook {
         }
     }
 
+    /// Spread relative to mid, in basis points (spread / mid * 10_000).
+    /// None for empty or one-sided books and a zero mid.
+    pub fn spread_bps(&self) -> Option<Decimal> {
+        let spread = self.spread()?;
+        let mid = self.mid()?;
+        if mid.is_zero() {
+            return None;
+        }
+        // Scale before dividing to keep precision on tight books
+        Some(spread * Decimal::from(10_000) / mid)
+    }
+
+    /// Check the book's invariants: every stored level has a positive price
+    /// and quantity, and the best bid is strictly below the best ask.
+    /// Cheap tripwire for orderbook logic bugs, not meant for the hot path.
+    pub fn validate(&self) -> Result<(), BookInvariantError> {
+        self.validate_with(false)
+    }
+
+    /// Like `validate`, optionally accepting a crossed or locked book
+    pub fn validate_with(&self, allow_crossed: bool) -> Result<(), BookInvariantError> {
+        for (side, levels) in [(Side::Bid, &self.bids), (Side::Ask, &self.asks)] {
+            for (&price, &qty) in levels {
+                // Decimal has no NaN; out-of-range signs are the equivalent here
+                if price <= Decimal::ZERO {
+                    return Err(BookInvariantError::NonPositivePrice { side, price });
+                }
+                if qty.is_zero() {
+                    return Err(BookInvariantError::ZeroQuantity { side, price });
+                }
+                if qty < Decimal::ZERO {
+                    return Err(BookInvariantError::NegativeQuantity { side, price, qty });
+                }
+            }
+        }
+        
+        if !allow_crossed {
+            if let (Some((bid, _)), Some((ask, _))) = (self.best_bid(), self.best_ask()) {
+                if bid >= ask {
+                    return Err(BookInvariantError::Crossed { bid, ask });
+                }
+            }
+        }
+        
+        Ok(())
+    }
+
+    /// Quantity-weighted microprice of the best levels:
+    /// (bid_qty * ask + ask_qty * bid) / (bid_qty + ask_qty)
+    pub fn microprice(&self) -> Option<Decimal> {
+        self.weighted_mid(1)
+    }
+
+    /// Microprice generalised to the top `levels` per side: each side's
+    /// VWAP is weighted by the opposite side's total quantity.
+    /// None for one-sided books, `levels == 0` or zero total quantity.
+    pub fn weighted_mid(&self, levels: usize) -> Option<Decimal> {
+        if self.bids.is_empty() || self.asks.is_empty() || levels == 0 {
+            return None;
+        }
+        
+        let (bid_qty, bid_notional) = self.bids.iter().rev().take(levels)
+            .fold((Decimal::ZERO, Decimal::ZERO), |(q, n), (p, qty)| (q + qty, n + p * qty));
+        let (ask_qty, ask_notional) = self.asks.iter().take(levels)
+            .fold((Decimal::ZERO, Decimal::ZERO), |(q, n), (p, qty)| (q + qty, n + p * qty));
+        if bid_qty.is_zero() || ask_qty.is_zero() {
+            return None;
+        }
+        
+        let bid_vwap = bid_notional / bid_qty;
+        let ask_vwap = ask_notional / ask_qty;
+        Some((bid_qty * ask_vwap + ask_qty * bid_vwap) / (bid_qty + ask_qty))
+    }
+
+    /// Best bid at or above best ask (includes locked books)
+    pub fn is_crossed(&self) -> bool {
+        match (self.best_bid(), self.best_ask()) {
+            (Some((bid, _)), Some((ask, _))) => bid >= ask,
+            _ => false,
+        }
+    }
+
+    /// Best bid exactly equal to best ask
+    pub fn is_locked(&self) -> bool {
+        match (self.best_bid(), self.best_ask()) {
+            (Some((bid, _)), Some((ask, _))) => bid == ask,
+            _ => false,
+        }
+    }
+
+    /// Order book imbalance over the top `levels` levels per side:
+    /// (sum bid qty - sum ask qty) / (sum bid qty + sum ask qty), in [-1, 1].
+    /// Uses whatever levels exist if a side is shallower than `levels`;
+    /// returns None if either side is empty.
+    pub fn imbalance(&self, levels: usize) -> Option<f64> {
+        if self.bids.is_empty() || self.asks.is_empty() || levels == 0 {
+            return None;
+        }
+        
+        let bid_qty: Decimal = self.bids.values().rev().take(levels).sum();
+        let ask_qty: Decimal = self.asks.values().take(levels).sum();
+        let total = bid_qty + ask_qty;
+        if total.is_zero() {
+            return None;
+        }
+        
+        ((bid_qty - ask_qty) / total).to_f64()
+    }
+
+    /// Reference price for bps bands: mid when both sides exist,
+    /// otherwise the best price of the only populated side
+    fn reference_price(&self) -> Option<Decimal> {
+        match (self.best_bid(), self.best_ask()) {
+            (Some(_), Some(_)) => self.mid(),
+            (Some((bid, _)), None) => Some(bid),
+            (None, Some((ask, _))) => Some(ask),
+            (None, None) => None,
+        }
+    }
+
+    /// Sum `f(price, qty)` over levels within `bps` basis points of the reference price.
+    /// Returns (bid_side, ask_side), or None for an empty book.
+    fn sum_within_bps<F>(&self, bps: u32, f: F) -> Option<(Decimal, Decimal)>
+    where
+        F: Fn(&Decimal, &Decimal) -> Decimal,
+    {
+        let reference = self.reference_price()?;
+        let band = reference * Decimal::from(bps) / Decimal::from(10_000);
+        let bid_floor = reference - band;
+        let ask_ceiling = reference + band;
+        
+        let bid_sum = self.bids
+            .range(bid_floor..)
+            .map(|(p, q)| f(p, q))
+            .sum();
+        let ask_sum = self.asks
+            .range(..=ask_ceiling)
+            .map(|(p, q)| f(p, q))
+            .sum();
+        
+        Some((bid_sum, ask_sum))
+    }
+
+    /// Quantity available within `bps` basis points of mid on each side: (bid_qty, ask_qty)
+    pub fn liquidity_within_bps(&self, bps: u32) -> Option<(Decimal, Decimal)> {
+        self.sum_within_bps(bps, |_, q| *q)
+    }
+
+    /// Notional (price * qty) available within `bps` basis points of mid on each side
+    pub fn notional_within_bps(&self, bps: u32) -> Option<(Decimal, Decimal)> {
+        self.sum_within_bps(bps, |p, q| p * q)
+    }
+
     /// Iterate asks in ascending order (low to high)
     pub fn asks_iter(&self) -> impl Iterator<Item = (&Decimal, &Decimal)> {
         self.asks.iter()
@@ -121,6 +669,16 @@ impl Orderbook {
         self.bids.iter().rev()
     }
 
+    /// Iterate the best `n` asks (low to high) without allocating
+    pub fn top_asks(&self, n: usize) -> impl Iterator<Item = (&Decimal, &Decimal)> {
+        self.asks.iter().take(n)
+    }
+
+    /// Iterate the best `n` bids (high to low) without allocating
+    pub fn top_bids(&self, n: usize) -> impl Iterator<Item = (&Decimal, &Decimal)> {
+        self.bids.iter().rev().take(n)
+    }
+
     /// Get all asks as vector (for API responses)
     pub fn asks_vec(&self, limit: Option<usize>) -> Vec<(Decimal, Decimal)> {
         let iter = self.asks.iter();
@@ -141,31 +699,200 @@ impl Orderbook {
         }
     }
 
+    /// Get `limit` asks starting `offset` levels from the best (low to high).
+    /// Empty if `offset` is past the last level.
+    pub fn asks_page(&self, offset: usize, limit: usize) -> Vec<(Decimal, Decimal)> {
+        self.asks.iter().skip(offset).take(limit).map(|(p, q)| (*p, *q)).collect()
+    }
+
+    /// Get `limit` bids starting `offset` levels from the best (high to low).
+    /// Empty if `offset` is past the last level.
+    pub fn bids_page(&self, offset: usize, limit: usize) -> Vec<(Decimal, Decimal)> {
+        self.bids.iter().rev().skip(offset).take(limit).map(|(p, q)| (*p, *q)).collect()
+    }
+
+    /// Get bids (high to low) with running cumulative quantity: (price, qty, cum_qty)
+    pub fn bids_cumulative(&self, limit: Option<usize>) -> Vec<(Decimal, Decimal, Decimal)> {
+        accumulate(self.top_bids(limit.unwrap_or(usize::MAX)))
+    }
+
+    /// Get asks (low to high) with running cumulative quantity: (price, qty, cum_qty)
+    pub fn asks_cumulative(&self, limit: Option<usize>) -> Vec<(Decimal, Decimal, Decimal)> {
+        accumulate(self.top_asks(limit.unwrap_or(usize::MAX)))
+    }
+
+    /// Rough estimate of the heap and inline memory held by this book, in bytes.
+    /// Meant for monitoring, not exact accounting.
+    pub fn approx_bytes(&self) -> usize {
+        let level = 2 * std::mem::size_of::<Decimal>() + BTREE_ENTRY_OVERHEAD;
+        let mut bytes = std::mem::size_of::<Self>() + (self.bids.len() + self.asks.len()) * level;
+        
+        if let Some(meta) = &self.level_meta {
+            let entry = std::mem::size_of::<Decimal>() + std::mem::size_of::<LevelMeta>() + BTREE_ENTRY_OVERHEAD;
+            bytes += std::mem::size_of::<LevelMetaMaps>() + (meta.bids.len() + meta.asks.len()) * entry;
+        }
+        if let Some(tombstones) = &self.tombstones {
+            bytes += std::mem::size_of::<Tombstones>()
+                + (tombstones.bids.capacity() + tombstones.asks.capacity()) * std::mem::size_of::<Removal>();
+        }
+        if let Some(cache) = &self.checksum_cache {
+            bytes += std::mem::size_of::<ChecksumCache>() + cache.checksum_str.capacity();
+        }
+        
+        bytes
+    }
+
     /// Get depth (number of levels)
     pub fn depth(&self) -> (usize, usize) {
         (self.asks.len(), self.bids.len())
     }
 
+    /// Get the checksum string, rebuilding it only if a top 10 level changed
+    /// since the last call (or the precision differs)
+    pub fn checksum_string(&mut self, price_precision: u32, qty_precision: u32) -> &str {
+        self.checksum_string_with(&PrecisionFormatter::new(price_precision, qty_precision))
+    }
+
+    /// `checksum_string` with an instrument's prebuilt formatter
+    pub fn checksum_string_with(&mut self, formatter: &PrecisionFormatter) -> &str {
+        &self.cached_checksum(formatter).checksum_str
+    }
+
+    /// Get the CRC32 checksum, rebuilding it only if a checksummed level changed
+    /// since the last call (or the precision differs)
+    pub fn checksum(&mut self, price_precision: u32, qty_precision: u32) -> u32 {
+        self.checksum_with(&PrecisionFormatter::new(price_precision, qty_precision))
+    }
+
+    /// `checksum` with an instrument's prebuilt formatter
+    pub fn checksum_with(&mut self, formatter: &PrecisionFormatter) -> u32 {
+        self.cached_checksum(formatter).crc32
+    }
+
+    fn cached_checksum(&mut self, formatter: &PrecisionFormatter) -> &ChecksumCache {
+        let stale = !matches!(&self.checksum_cache, Some(c) if c.formatter == *formatter);
+        if stale {
+            let mut checksum_str = String::new();
+            write_checksum_string(self, formatter, self.checksum_levels, &mut checksum_str);
+            let crc32 = compute_crc32(&checksum_str);
+            self.checksum_cache = Some(ChecksumCache {
+                formatter: *formatter,
+                checksum_str,
+                crc32,
+            });
+        }
+        self.checksum_cache.as_ref().expect("checksum cache populated above")
+    }
+
     // Helper methods for testing
     #[cfg(test)]
     pub fn update_bid(&mut self, price: Decimal, qty: Decimal) {
-        if qty == Decimal::ZERO {
-            self.bids.remove(&price);
-        } else {
-            self.bids.insert(price, qty);
-        }
+        self.set_bid(price, qty);
     }
 
     #[cfg(test)]
     pub fn update_ask(&mut self, price: Decimal, qty: Decimal) {
-        if qty == Decimal::ZERO {
-            self.asks.remove(&price);
-        } else {
-            self.asks.insert(price, qty);
+        self.set_ask(price, qty);
+    }
+
+    #[cfg(test)]
+    pub(crate) fn has_cached_checksum(&self) -> bool {
+        self.checksum_cache.is_some()
+    }
+}
+
+/// Effect of a single level update
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum LevelOp {
+    Inserted,
+    Removed,
+    Modified,
+    Unchanged,
+}
+
+fn set_level(side: &mut BTreeMap<Decimal, Decimal>, price: Decimal, qty: Decimal) -> LevelOp {
+    if qty == Decimal::ZERO {
+        match side.remove(&price) {
+            Some(_) => LevelOp::Removed,
+            None => LevelOp::Unchanged,
+        }
+    } else {
+        match side.insert(price, qty) {
+            None => LevelOp::Inserted,
+            Some(old) if old != qty => LevelOp::Modified,
+            Some(_) => LevelOp::Unchanged,
+        }
+    }
+}
+
+/// What an `apply_updates` call actually changed
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct UpdateSummary {
+    pub inserted: usize,
+    pub removed: usize,
+    pub modified: usize,
+    pub top_levels_changed: bool, // a change landed inside the checksum depth
+    pub best_bid_moved: bool,
+    pub best_ask_moved: bool,
+}
+
+impl UpdateSummary {
+    fn record(&mut self, op: LevelOp, in_top: bool) {
+        match op {
+            LevelOp::Inserted => self.inserted += 1,
+            LevelOp::Removed => self.removed += 1,
+            LevelOp::Modified => self.modified += 1,
+            LevelOp::Unchanged => return,
         }
+        if in_top {
+            self.top_levels_changed = true;
+        }
+    }
+
+    /// True if best bid or best ask price changed
+    pub fn top_of_book_moved(&self) -> bool {
+        self.best_bid_moved || self.best_ask_moved
+    }
+
+    /// True if no level changed at all
+    pub fn is_empty(&self) -> bool {
+        self.inserted == 0 && self.removed == 0 && self.modified == 0
+    }
+}
+
+fn parse_levels(levels: Option<&[BookLevelData]>, side: &str) -> anyhow::Result<PriceLevels> {
+    levels
+        .unwrap_or_default()
+        .iter()
+        .enumerate()
+        .map(|(i, level)| {
+            let price = parse_level_value(&level.price)
+                .with_context(|| format!("{} level {}: invalid price", side, i))?;
+            let qty = parse_level_value(&level.qty)
+                .with_context(|| format!("{} level {}: invalid qty", side, i))?;
+            Ok((price, qty))
+        })
+        .collect()
+}
+
+fn parse_level_value(value: &serde_json::Value) -> anyhow::Result<Decimal> {
+    match value {
+        serde_json::Value::Number(n) => parse_decimal(&n.to_string()),
+        serde_json::Value::String(s) => parse_decimal(s),
+        other => Err(anyhow::anyhow!("expected number or string, got {}", other)),
     }
 }
 
+fn accumulate<'a>(levels: impl Iterator<Item = (&'a Decimal, &'a Decimal)>) -> Vec<(Decimal, Decimal, Decimal)> {
+    let mut cum = Decimal::ZERO;
+    levels
+        .map(|(price, qty)| {
+            cum += qty;
+            (*price, *qty, cum)
+        })
+        .collect()
+}
+
 impl Default for Orderbook {
     fn default() -> Self {
         Self::new()
@@ -230,5 +957,582 @@ mod tests {
         // Best ask should be lowest (101.0)
         assert_eq!(book.best_ask(), Some((dec!(101.0), dec!(1.0))));
     }
+
+    #[test]
+    fn test_update_summary_best_level_removed() {
+        let mut book = Orderbook::new();
+        book.apply_snapshot(
+            vec![(dec!(100), dec!(1)), (dec!(99), dec!(2))],
+            vec![(dec!(101), dec!(1)), (dec!(102), dec!(2))],
+        );
+        
+        let summary = book.apply_updates(vec![(dec!(100), dec!(0))], vec![]);
+        assert_eq!(summary.removed, 1);
+        assert_eq!(summary.inserted + summary.modified, 0);
+        assert!(summary.top_levels_changed);
+        assert!(summary.best_bid_moved);
+        assert!(!summary.best_ask_moved);
+        assert_eq!(book.best_bid(), Some((dec!(99), dec!(2))));
+    }
+
+    #[test]
+    fn test_update_summary_new_best_level() {
+        let mut book = Orderbook::new();
+        book.apply_snapshot(
+            vec![(dec!(100), dec!(1))],
+            vec![(dec!(101), dec!(1))],
+        );
+        
+        let summary = book.apply_updates(vec![], vec![(dec!(100.5), dec!(3)), (dec!(101), dec!(4))]);
+        assert_eq!(summary.inserted, 1);
+        assert_eq!(summary.modified, 1);
+        assert!(summary.best_ask_moved);
+        assert!(!summary.best_bid_moved);
+        assert!(summary.top_of_book_moved());
+        
+        // Qty-only change at the best level doesn't move the top of book
+        let summary = book.apply_updates(vec![(dec!(100), dec!(5))], vec![]);
+        assert_eq!(summary.modified, 1);
+        assert!(summary.top_levels_changed);
+        assert!(!summary.top_of_book_moved());
+        
+        // Removing a level that doesn't exist is a no-op
+        let summary = book.apply_updates(vec![(dec!(42), dec!(0))], vec![]);
+        assert!(summary.is_empty());
+        assert!(!summary.top_levels_changed);
+    }
+
+    #[test]
+    fn test_update_summary_outside_top() {
+        let mut book = Orderbook::new();
+        let bids: Vec<_> = (0..20).map(|i| (Decimal::from(100 - i), dec!(1))).collect();
+        let asks: Vec<_> = (0..20).map(|i| (Decimal::from(101 + i), dec!(1))).collect();
+        book.apply_snapshot(bids, asks);
+        
+        let summary = book.apply_updates(vec![(dec!(85), dec!(2))], vec![(dec!(125), dec!(1))]);
+        assert_eq!(summary.modified, 1);
+        assert_eq!(summary.inserted, 1);
+        assert!(!summary.top_levels_changed);
+        assert!(!summary.top_of_book_moved());
+    }
+
+    #[test]
+    fn test_max_levels_cap() {
+        let mut book = Orderbook::with_max_levels(50);
+        book.apply_snapshot(vec![(dec!(5000), dec!(1))], vec![(dec!(5001), dec!(1))]);
+        
+        for i in 0..10_000 {
+            // Alternate between worse-than-best and new-best levels
+            let offset = Decimal::from(i) / dec!(100);
+            let (bid, ask) = if i % 2 == 0 {
+                (dec!(4000) - offset, dec!(6000) + offset)
+            } else {
+                (dec!(5000) + offset / dec!(1000), dec!(5001) - offset / dec!(1000))
+            };
+            book.apply_updates(vec![(bid, dec!(1))], vec![(ask, dec!(1))]);
+            
+            let (asks, bids) = book.depth();
+            assert!(asks <= 50 && bids <= 50, "cap exceeded at {}: {} asks, {} bids", i, asks, bids);
+        }
+        
+        // The latest new-best levels survived eviction
+        assert_eq!(book.best_bid(), Some((dec!(5000) + dec!(99.99) / dec!(1000), dec!(1))));
+        assert_eq!(book.best_ask(), Some((dec!(5001) - dec!(99.99) / dec!(1000), dec!(1))));
+        
+        // Snapshots are capped too
+        let bids: Vec<_> = (0..200).map(|i| (Decimal::from(1000 - i), dec!(1))).collect();
+        book.apply_snapshot(bids, vec![]);
+        assert_eq!(book.depth(), (0, 50));
+        assert_eq!(book.best_bid(), Some((dec!(1000), dec!(1))));
+    }
+
+    #[test]
+    fn test_max_levels_below_checksum_depth() {
+        let mut book = Orderbook::with_max_levels(3);
+        book.apply_snapshot(
+            vec![(dec!(100), dec!(1)), (dec!(99), dec!(1)), (dec!(98), dec!(1))],
+            vec![(dec!(101), dec!(1))],
+        );
+        book.checksum(0, 0);
+        
+        book.apply_updates(vec![(dec!(97), dec!(1))], vec![]);
+        assert_eq!(book.depth(), (1, 3));
+        assert_eq!(
+            book.checksum_string(0, 0).to_string(),
+            crate::checksum::build_checksum_string(&book, 0, 0)
+        );
+    }
+
+    fn book_data(bids: serde_json::Value, asks: serde_json::Value) -> BookData {
+        serde_json::from_value(serde_json::json!({
+            "symbol": "BTC/USD",
+            "bids": bids,
+            "asks": asks,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_levels_from_book_data() {
+        // Numeric and string prices/quantities
+        let data = book_data(
+            serde_json::json!([{"price": 45283.5, "qty": "0.10000000"}]),
+            serde_json::json!([{"price": "45285.2", "qty": 0.001}]),
+        );
+        let (bids, asks) = Orderbook::levels_from_book_data(&data).unwrap();
+        assert_eq!(bids, vec![(dec!(45283.5), dec!(0.1))]);
+        assert_eq!(asks, vec![(dec!(45285.2), dec!(0.001))]);
+        
+        // Missing sides are empty
+        let data: BookData = serde_json::from_value(serde_json::json!({"symbol": "BTC/USD"})).unwrap();
+        let (bids, asks) = Orderbook::levels_from_book_data(&data).unwrap();
+        assert!(bids.is_empty() && asks.is_empty());
+    }
+
+    #[test]
+    fn test_levels_from_book_data_malformed() {
+        let data = book_data(
+            serde_json::json!([{"price": "100", "qty": "1"}, {"price": null, "qty": "1"}]),
+            serde_json::json!([]),
+        );
+        let err = Orderbook::levels_from_book_data(&data).unwrap_err();
+        assert!(format!("{:#}", err).contains("bid level 1: invalid price"));
+        
+        let data = book_data(
+            serde_json::json!([]),
+            serde_json::json!([{"price": "101", "qty": "abc"}]),
+        );
+        let err = Orderbook::levels_from_book_data(&data).unwrap_err();
+        assert!(format!("{:#}", err).contains("ask level 0: invalid qty"));
+    }
+
+    #[test]
+    fn test_top_iterators_match_vecs() {
+        let mut book = Orderbook::new();
+        book.apply_snapshot(
+            vec![(dec!(100), dec!(1)), (dec!(99), dec!(2)), (dec!(98), dec!(3))],
+            vec![(dec!(101), dec!(1)), (dec!(102), dec!(2))],
+        );
+        for n in 0..5 {
+            let bids: Vec<_> = book.top_bids(n).map(|(p, q)| (*p, *q)).collect();
+            let asks: Vec<_> = book.top_asks(n).map(|(p, q)| (*p, *q)).collect();
+            assert_eq!(bids, book.bids_vec(Some(n)));
+            assert_eq!(asks, book.asks_vec(Some(n)));
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut book = Orderbook::new();
+        assert_eq!(book.validate(), Ok(()));
+        book.apply_snapshot(vec![(dec!(100), dec!(1))], vec![(dec!(101), dec!(1))]);
+        assert_eq!(book.validate(), Ok(()));
+        
+        // A bid update through the ask crosses the book
+        book.apply_updates(vec![(dec!(101), dec!(1))], vec![]);
+        assert_eq!(book.validate(), Err(BookInvariantError::Crossed { bid: dec!(101), ask: dec!(101) }));
+        assert_eq!(book.validate_with(true), Ok(()));
+        book.apply_updates(vec![(dec!(101), dec!(0))], vec![]);
+        assert_eq!(book.validate(), Ok(()));
+        
+        // Updates only filter exact zeros, so negative quantities get stored
+        book.apply_updates(vec![], vec![(dec!(102), dec!(-0.5))]);
+        assert_eq!(
+            book.validate(),
+            Err(BookInvariantError::NegativeQuantity { side: Side::Ask, price: dec!(102), qty: dec!(-0.5) })
+        );
+        book.apply_updates(vec![], vec![(dec!(102), dec!(0))]);
+        
+        book.apply_updates(vec![(dec!(-1), dec!(1))], vec![]);
+        assert_eq!(
+            book.validate(),
+            Err(BookInvariantError::NonPositivePrice { side: Side::Bid, price: dec!(-1) })
+        );
+        book.apply_updates(vec![(dec!(-1), dec!(0))], vec![]);
+        
+        // Zero quantities can't be stored through the public API
+        book.bids.insert(dec!(99), Decimal::ZERO);
+        assert_eq!(
+            book.validate(),
+            Err(BookInvariantError::ZeroQuantity { side: Side::Bid, price: dec!(99) })
+        );
+    }
+
+    #[test]
+    fn test_tombstones() {
+        // Off by default
+        let mut book = Orderbook::new();
+        book.apply_snapshot(vec![(dec!(100), dec!(1))], vec![(dec!(101), dec!(1))]);
+        book.apply_updates(vec![(dec!(100), dec!(0))], vec![]);
+        assert!(book.recent_removals(Side::Bid, 10).is_empty());
+        
+        let mut book = Orderbook::with_max_levels(3).with_tombstones(2);
+        book.apply_snapshot(
+            vec![(dec!(100), dec!(1)), (dec!(99), dec!(2)), (dec!(98), dec!(3))],
+            vec![(dec!(101), dec!(4))],
+        );
+        
+        // Deletes record the qty the level had
+        book.apply_updates(vec![(dec!(99), dec!(0))], vec![(dec!(101), dec!(0))]);
+        let asks = book.recent_removals(Side::Ask, 10);
+        assert_eq!(asks.len(), 1);
+        assert_eq!((asks[0].price, asks[0].qty, asks[0].cause), (dec!(101), dec!(4), RemovalCause::Deleted));
+        
+        // Deleting an unknown level is not a removal
+        book.apply_updates(vec![(dec!(50), dec!(0))], vec![]);
+        assert_eq!(book.recent_removals(Side::Bid, 10).len(), 1);
+        
+        // Evictions and truncation are tagged; buffer keeps the newest 2, newest first
+        book.apply_updates(vec![(dec!(99.5), dec!(1)), (dec!(99.8), dec!(1))], vec![]);
+        book.truncate(2);
+        let bids = book.recent_removals(Side::Bid, 10);
+        let got: Vec<_> = bids.iter().map(|r| (r.price, r.cause)).collect();
+        assert_eq!(got, vec![(dec!(99.5), RemovalCause::Truncated), (dec!(98), RemovalCause::Evicted)]);
+        assert_eq!(book.recent_removals(Side::Bid, 1).len(), 1);
+    }
+
+    #[test]
+    fn test_approx_bytes() {
+        let empty = Orderbook::new();
+        assert_eq!(empty.approx_bytes(), std::mem::size_of::<Orderbook>());
+        
+        let levels = |start: i64, step: i64| -> Vec<(Decimal, Decimal)> {
+            (0..500).map(|i| (Decimal::from(start + step * i), dec!(1.5))).collect()
+        };
+        let mut book = Orderbook::new();
+        book.apply_snapshot(levels(50_000, -1), levels(50_001, 1));
+        assert_eq!(book.depth(), (500, 500));
+        
+        // At least the raw (price, qty) payload, plus bounded overhead
+        let payload = 1000 * 2 * std::mem::size_of::<Decimal>();
+        let bytes = book.approx_bytes();
+        assert!(bytes > payload);
+        assert!(bytes < payload * 2);
+        
+        // The checksum cache and level metadata are counted too
+        book.checksum(0, 1);
+        assert!(book.approx_bytes() > bytes);
+        let mut tracked = Orderbook::new().with_level_meta();
+        tracked.apply_snapshot(levels(50_000, -1), levels(50_001, 1));
+        assert!(tracked.approx_bytes() > bytes);
+    }
+
+    #[test]
+    fn test_pages() {
+        let mut book = Orderbook::new();
+        book.apply_snapshot(
+            vec![(dec!(100), dec!(1)), (dec!(99), dec!(2)), (dec!(98), dec!(3))],
+            vec![(dec!(101), dec!(1)), (dec!(102), dec!(2)), (dec!(103), dec!(3))],
+        );
+        
+        // Best-first on both sides
+        assert_eq!(book.bids_page(0, 2), book.bids_vec(Some(2)));
+        assert_eq!(book.asks_page(1, 1), vec![(dec!(102), dec!(2))]);
+        assert_eq!(book.bids_page(1, 1), vec![(dec!(99), dec!(2))]);
+        
+        // offset + limit straddling the end
+        assert_eq!(book.bids_page(2, 5), vec![(dec!(98), dec!(3))]);
+        assert_eq!(book.asks_page(2, 5), vec![(dec!(103), dec!(3))]);
+        
+        // offset beyond depth
+        assert!(book.bids_page(3, 1).is_empty());
+        assert!(book.asks_page(10, 10).is_empty());
+        assert!(book.asks_page(usize::MAX, usize::MAX).is_empty());
+        assert!(book.asks_page(0, 0).is_empty());
+    }
+
+    #[test]
+    fn test_spread_bps() {
+        let mut book = Orderbook::new();
+        assert_eq!(book.spread_bps(), None);
+        
+        book.apply_snapshot(vec![(dec!(99.99), dec!(1))], vec![(dec!(100.01), dec!(1))]);
+        assert_eq!(book.spread_bps(), Some(dec!(2)));
+        
+        // One tick on a 100k price: ~0.001 bps, must not round to zero
+        book.apply_snapshot(vec![(dec!(100000), dec!(1))], vec![(dec!(100000.01), dec!(1))]);
+        let bps = book.spread_bps().unwrap();
+        assert!(bps > Decimal::ZERO);
+        assert!((bps - dec!(0.001)).abs() < dec!(0.000000001));
+        
+        // Zero mid
+        book.apply_snapshot(vec![(dec!(0), dec!(1))], vec![(dec!(0), dec!(1))]);
+        assert_eq!(book.spread_bps(), None);
+        
+        book.apply_snapshot(vec![(dec!(100), dec!(1))], vec![]);
+        assert_eq!(book.spread_bps(), None);
+    }
+
+    #[test]
+    fn test_microprice() {
+        let mut book = Orderbook::new();
+        assert_eq!(book.microprice(), None);
+        
+        // Heavy bid pulls the price towards the ask
+        book.apply_snapshot(
+            vec![(dec!(100), dec!(3)), (dec!(99), dec!(1))],
+            vec![(dec!(101), dec!(1)), (dec!(102), dec!(3))],
+        );
+        assert_eq!(book.microprice(), Some(dec!(100.75)));
+        assert_eq!(book.weighted_mid(1), book.microprice());
+        
+        // Top 2: bid vwap 99.75 (qty 4), ask vwap 101.75 (qty 4)
+        assert_eq!(book.weighted_mid(2), Some(dec!(100.75)));
+        assert_eq!(book.weighted_mid(0), None);
+        
+        // One-sided book
+        let mut one_sided = Orderbook::new();
+        one_sided.apply_snapshot(vec![(dec!(100), dec!(1))], vec![]);
+        assert_eq!(one_sided.microprice(), None);
+        assert_eq!(one_sided.weighted_mid(5), None);
+        
+        // Zero-quantity levels
+        let mut zero = Orderbook::new();
+        zero.apply_snapshot(vec![(dec!(100), dec!(0))], vec![(dec!(101), dec!(2))]);
+        assert_eq!(zero.microprice(), None);
+    }
+
+    #[test]
+    fn test_level_meta() {
+        let mut book = Orderbook::new();
+        book.apply_snapshot(vec![(dec!(100), dec!(1))], vec![(dec!(101), dec!(1))]);
+        assert!(!book.tracks_level_meta());
+        assert_eq!(book.level_meta(dec!(100), Side::Bid), None);
+        
+        let mut book = Orderbook::with_max_levels(3).with_level_meta();
+        book.apply_snapshot(
+            vec![(dec!(100), dec!(1)), (dec!(99), dec!(1))],
+            vec![(dec!(101), dec!(1))],
+        );
+        assert_eq!(book.level_meta(dec!(100), Side::Bid).map(|m| m.update_count), Some(0));
+        
+        book.apply_updates(vec![(dec!(100), dec!(2))], vec![(dec!(101), dec!(1)), (dec!(102), dec!(5))]);
+        book.apply_updates(vec![(dec!(100), dec!(3))], vec![]);
+        assert_eq!(book.level_meta(dec!(100), Side::Bid).map(|m| m.update_count), Some(2));
+        assert_eq!(book.level_meta(dec!(101), Side::Ask).map(|m| m.update_count), Some(1));
+        assert_eq!(book.level_meta(dec!(102), Side::Ask).map(|m| m.update_count), Some(1));
+        assert_eq!(book.level_meta(dec!(100), Side::Ask), None);
+        
+        // Removal, eviction and truncation drop the metadata
+        book.apply_updates(vec![(dec!(100), dec!(0))], vec![]);
+        assert_eq!(book.level_meta(dec!(100), Side::Bid), None);
+        book.apply_updates(vec![(dec!(98), dec!(1)), (dec!(97), dec!(1)), (dec!(96), dec!(1))], vec![]);
+        assert_eq!(book.level_meta(dec!(96), Side::Bid), None);
+        book.truncate(1);
+        assert_eq!(book.level_meta(dec!(98), Side::Bid), None);
+        assert!(book.level_meta(dec!(99), Side::Bid).is_some());
+    }
+
+    #[test]
+    fn test_level_meta_does_not_affect_checksum() {
+        let bids: Vec<_> = (0..12).map(|i| (Decimal::from(100 - i), dec!(1.5))).collect();
+        let asks: Vec<_> = (0..12).map(|i| (Decimal::from(101 + i), dec!(2.5))).collect();
+        let mut plain = Orderbook::new();
+        let mut tracked = Orderbook::new().with_level_meta();
+        for book in [&mut plain, &mut tracked] {
+            book.apply_snapshot(bids.clone(), asks.clone());
+            book.apply_updates(vec![(dec!(100), dec!(4))], vec![(dec!(101), dec!(0))]);
+        }
+        assert_eq!(plain.checksum(1, 2), tracked.checksum(1, 2));
+        assert_eq!(plain.checksum_string(1, 2).to_string(), tracked.checksum_string(1, 2).to_string());
+    }
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        let mut book = Orderbook::new();
+        let bids: Vec<_> = (0..15).map(|i| (dec!(50000.0) - Decimal::from(i), dec!(0.25) * Decimal::from(i + 1))).collect();
+        let asks: Vec<_> = (0..15).map(|i| (dec!(50000.5) + Decimal::from(i), dec!(1.10000000))).collect();
+        book.apply_snapshot(bids, asks);
+        book.apply_updates(vec![(dec!(49999.5), dec!(3))], vec![(dec!(50000.5), dec!(0))]);
+        book.set_last_update_ts(Utc::now());
+        
+        let json = serde_json::to_string(&book.to_snapshot()).unwrap();
+        let snapshot: BookSnapshot = serde_json::from_str(&json).unwrap();
+        let mut restored = Orderbook::from_snapshot(snapshot);
+        
+        assert_eq!(restored.best_bid(), book.best_bid());
+        assert_eq!(restored.best_ask(), book.best_ask());
+        assert_eq!(restored.depth(), book.depth());
+        assert_eq!(restored.update_seq(), book.update_seq());
+        assert_eq!(restored.last_update_ts(), book.last_update_ts());
+        assert_eq!(
+            restored.checksum_string(1, 8).to_string(),
+            crate::checksum::build_checksum_string(&book, 1, 8)
+        );
+    }
+
+    #[test]
+    fn test_update_seq_and_timestamp() {
+        let mut book = Orderbook::new();
+        assert_eq!(book.update_seq(), 0);
+        assert_eq!(book.last_update_ts(), None);
+        
+        book.apply_snapshot(vec![(dec!(100), dec!(1))], vec![(dec!(101), dec!(1))]);
+        book.apply_updates(vec![(dec!(100), dec!(2))], vec![]);
+        book.apply_updates(vec![], vec![]);
+        assert_eq!(book.update_seq(), 3);
+        
+        let ts = DateTime::parse_from_rfc3339("2024-01-15T10:30:45.123456Z").unwrap().with_timezone(&Utc);
+        book.set_last_update_ts(ts);
+        assert_eq!(book.last_update_ts(), Some(ts));
+    }
+
+    #[test]
+    fn test_crossed_from_snapshot() {
+        let mut book = Orderbook::new();
+        assert!(!book.is_crossed());
+        
+        book.apply_snapshot(vec![(dec!(100), dec!(1))], vec![(dec!(101), dec!(1))]);
+        assert!(!book.is_crossed());
+        assert!(!book.is_locked());
+        
+        book.apply_snapshot(vec![(dec!(102), dec!(1))], vec![(dec!(101), dec!(1))]);
+        assert!(book.is_crossed());
+        assert!(!book.is_locked());
+        
+        book.apply_snapshot(vec![(dec!(101), dec!(1))], vec![(dec!(101), dec!(1))]);
+        assert!(book.is_crossed());
+        assert!(book.is_locked());
+    }
+
+    #[test]
+    fn test_crossed_from_update() {
+        let mut book = Orderbook::new();
+        book.apply_snapshot(
+            vec![(dec!(100), dec!(1)), (dec!(99), dec!(1))],
+            vec![(dec!(101), dec!(1)), (dec!(102), dec!(1))],
+        );
+        
+        // Bid lifted through the best ask
+        book.apply_updates(vec![(dec!(101.5), dec!(1))], vec![]);
+        assert!(book.is_crossed());
+        
+        // Removing the stale ask leaves 101.5 vs 102: uncrossed again
+        book.apply_updates(vec![], vec![(dec!(101), dec!(0))]);
+        assert!(!book.is_crossed());
+        
+        // Ask dropping to the best bid locks the book
+        book.apply_updates(vec![], vec![(dec!(101.5), dec!(2))]);
+        assert!(book.is_locked());
+        
+        // One-sided books are never crossed
+        book.apply_updates(vec![], vec![(dec!(101.5), dec!(0)), (dec!(102), dec!(0))]);
+        assert!(!book.is_crossed());
+    }
+
+    #[test]
+    fn test_imbalance() {
+        let mut book = Orderbook::new();
+        assert_eq!(book.imbalance(5), None);
+        
+        book.apply_snapshot(
+            vec![(dec!(100), dec!(3)), (dec!(99), dec!(1)), (dec!(98), dec!(6))],
+            vec![(dec!(101), dec!(1)), (dec!(102), dec!(1))],
+        );
+        
+        // Top 1: (3 - 1) / (3 + 1) = 0.5
+        assert_eq!(book.imbalance(1), Some(0.5));
+        // Top 2: (4 - 2) / 6 = 1/3
+        assert!((book.imbalance(2).unwrap() - 1.0 / 3.0).abs() < 1e-12);
+        // Top 5: asks only have 2 levels -> (10 - 2) / 12 = 2/3
+        assert!((book.imbalance(5).unwrap() - 2.0 / 3.0).abs() < 1e-12);
+        assert_eq!(book.imbalance(0), None);
+        
+        // Ask-heavy book goes negative
+        book.apply_updates(vec![], vec![(dec!(101), dec!(9))]);
+        assert_eq!(book.imbalance(1), Some(-0.5));
+        
+        // One empty side
+        book.apply_updates(vec![(dec!(100), dec!(0)), (dec!(99), dec!(0)), (dec!(98), dec!(0))], vec![]);
+        assert_eq!(book.imbalance(5), None);
+    }
+
+    #[test]
+    fn test_cumulative_depth() {
+        let mut book = Orderbook::new();
+        assert!(book.bids_cumulative(None).is_empty());
+        
+        book.apply_snapshot(
+            vec![(dec!(99), dec!(2)), (dec!(100), dec!(1)), (dec!(98), dec!(3))],
+            vec![(dec!(101), dec!(0.5)), (dec!(102), dec!(1.5))],
+        );
+        
+        assert_eq!(
+            book.bids_cumulative(None),
+            vec![(dec!(100), dec!(1), dec!(1)), (dec!(99), dec!(2), dec!(3)), (dec!(98), dec!(3), dec!(6))]
+        );
+        assert_eq!(
+            book.asks_cumulative(Some(1)),
+            vec![(dec!(101), dec!(0.5), dec!(0.5))]
+        );
+        assert_eq!(book.asks_cumulative(Some(10)).last().map(|l| l.2), Some(dec!(2)));
+    }
+
+    #[test]
+    fn test_liquidity_within_bps() {
+        let mut book = Orderbook::new();
+        assert_eq!(book.liquidity_within_bps(5), None);
+        
+        book.apply_snapshot(
+            vec![(dec!(9999), dec!(1)), (dec!(9995), dec!(2)), (dec!(9900), dec!(4))],
+            vec![(dec!(10001), dec!(1.5)), (dec!(10005), dec!(3)), (dec!(10100), dec!(5))],
+        );
+        
+        // mid = 10000, 5 bps = 5.0 -> bids >= 9995, asks <= 10005
+        assert_eq!(book.liquidity_within_bps(5), Some((dec!(3), dec!(4.5))));
+        assert_eq!(
+            book.notional_within_bps(5),
+            Some((dec!(9999) + dec!(19990), dec!(15001.5) + dec!(30015)))
+        );
+        
+        // Band wider than the whole book
+        assert_eq!(book.liquidity_within_bps(10_000), Some((dec!(7), dec!(9.5))));
+        
+        // Zero band only counts levels exactly at mid
+        assert_eq!(book.liquidity_within_bps(0), Some((dec!(0), dec!(0))));
+    }
+
+    #[test]
+    fn test_liquidity_within_bps_one_sided() {
+        let mut book = Orderbook::new();
+        book.apply_snapshot(
+            vec![(dec!(100), dec!(1)), (dec!(99.96), dec!(2)), (dec!(99), dec!(3))],
+            vec![],
+        );
+        
+        // Reference falls back to best bid (100); 5 bps = 0.05
+        assert_eq!(book.liquidity_within_bps(5), Some((dec!(3), dec!(0))));
+        assert_eq!(book.notional_within_bps(5), Some((dec!(100) + dec!(199.92), dec!(0))));
+    }
+
+    #[test]
+    fn test_checksum_cache_invalidation() {
+        let mut book = Orderbook::new();
+        let mut bids = Vec::new();
+        let mut asks = Vec::new();
+        
+        for i in 0..20 {
+            bids.push((Decimal::from(100) - Decimal::from(i), dec!(1.0)));
+            asks.push((Decimal::from(101) + Decimal::from(i), dec!(1.0)));
+        }
+        
+        book.apply_snapshot(bids, asks);
+        book.checksum(1, 1);
+        assert!(book.has_cached_checksum());
+        
+        // Updates beyond the 10th level keep the cache
+        book.apply_updates(vec![(dec!(85.0), dec!(3.0))], vec![(dec!(115.0), dec!(0.0))]);
+        book.truncate(15);
+        assert!(book.has_cached_checksum());
+        
+        // Touching the 10th ask (110) invalidates it
+        book.apply_updates(vec![], vec![(dec!(110.0), dec!(2.0))]);
+        assert!(!book.has_cached_checksum());
+        
+        // So does truncating below the checksum depth
+        book.checksum(1, 1);
+        book.truncate(5);
+        assert!(!book.has_cached_checksum());
+    }
 }
 
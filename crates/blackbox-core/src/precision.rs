@@ -1,73 +1,131 @@
-use rust_decimal::Decimal;
+use crate::types::InstrumentInfo;
+use rust_decimal::{Decimal, RoundingStrategy};
 use std::str::FromStr;
 
+/// Enough zeros to pad any Decimal scale in one push
+const ZEROS: &str = "000000000000000000000000000000";
+
 /// Format a Decimal to a fixed number of decimal places, then apply Kraken's
 /// checksum formatting rules: remove '.', trim leading zeros.
 /// 
 /// Steps:
-/// 1. Format to exactly `scale` decimal places (pad with zeros if needed)
-/// 2. Remove the decimal point
+/// 1. Round to `scale` decimal places, half to even (e.g. 0.125 -> 0.12)
+/// 2. Pad to exactly `scale` decimal places and remove the decimal point
 /// 3. Remove leading zeros (but keep at least "0" if empty)
+/// 
+/// Zero, including values that round to zero, formats as "0". Kraken never
+/// sends negative levels, so there's no venue rule for them: a negative value
+/// keeps its '-' ahead of the digits, and the checksum path reports it with
+/// `checksum::find_negative_level` rather than relying on the CRC alone.
 pub fn format_fixed(dec: &Decimal, scale: u32) -> String {
-    // Use Decimal's formatting to get exactly `scale` decimal places
-    let formatted = if scale > 0 {
-        // Round to scale decimal places
-        let rounded = dec.round_dp(scale);
-        let mut s = rounded.to_string();
-        
-        // Ensure we have exactly `scale` decimal places
-        if s.contains('.') {
-            let parts: Vec<&str> = s.split('.').collect();
-            let integer_part = parts[0];
-            let decimal_part = parts.get(1).unwrap_or(&"");
-            
-            let mut decimal_padded = decimal_part.to_string();
-            if decimal_padded.len() < scale as usize {
-                decimal_padded.push_str(&"0".repeat(scale as usize - decimal_padded.len()));
-            } else if decimal_padded.len() > scale as usize {
-                decimal_padded.truncate(scale as usize);
-            }
-            
-            s = format!("{}.{}", integer_part, decimal_padded);
-        } else {
-            // No decimal point, add it with zeros
-            s = format!("{}.{}", s, "0".repeat(scale as usize));
-        }
-        
-        s
+    let mut out = String::new();
+    format_fixed_into(dec, scale, &mut out);
+    out
+}
+
+/// Append `format_fixed(dec, scale)` to `out`, without intermediate strings
+pub fn format_fixed_into(dec: &Decimal, scale: u32, out: &mut String) {
+    use std::fmt::Write;
+    
+    // Levels usually arrive at the instrument's precision already
+    let rounded = if dec.scale() <= scale {
+        *dec
     } else {
-        // No decimal places needed
-        dec.to_string()
+        dec.round_dp_with_strategy(scale, RoundingStrategy::MidpointNearestEven)
     };
-    
-    // Remove decimal point
-    let mut result = formatted.replace('.', "");
-    
-    // Remove leading zeros, but keep at least one digit
-    result = result.trim_start_matches('0').to_string();
-    if result.is_empty() {
-        result = "0".to_string();
+    // The mantissa has no leading zeros, so it's already the trimmed digit string;
+    // padding it to `scale` places just appends zeros
+    let mantissa = rounded.mantissa();
+    if mantissa == 0 {
+        out.push('0');
+        return;
+    }
+    if mantissa < 0 {
+        out.push('-');
+    }
+    let _ = write!(out, "{}", mantissa.unsigned_abs());
+    let mut pad = (scale - rounded.scale()) as usize;
+    while pad > 0 {
+        let n = pad.min(ZEROS.len());
+        out.push_str(&ZEROS[..n]);
+        pad -= n;
+    }
+}
+
+/// Checksum formatting for one instrument, fixed to its price and qty precision
+/// so the hot path doesn't look them up per level
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PrecisionFormatter {
+    price_precision: u32,
+    qty_precision: u32,
+}
+
+impl PrecisionFormatter {
+    pub fn new(price_precision: u32, qty_precision: u32) -> Self {
+        Self { price_precision, qty_precision }
+    }
+
+    pub fn from_instrument(instrument: &InstrumentInfo) -> Self {
+        Self::new(instrument.price_precision, instrument.qty_precision)
+    }
+
+    pub fn price_precision(&self) -> u32 {
+        self.price_precision
+    }
+
+    pub fn qty_precision(&self) -> u32 {
+        self.qty_precision
+    }
+
+    /// Append the price as `format_fixed` would
+    pub fn format_price(&self, price: &Decimal, out: &mut String) {
+        format_fixed_into(price, self.price_precision, out);
+    }
+
+    /// Append the qty as `format_fixed` would
+    pub fn format_qty(&self, qty: &Decimal, out: &mut String) {
+        format_fixed_into(qty, self.qty_precision, out);
     }
-    
-    result
+}
+
+/// Decimal places implied by a tick size: 0.5 -> 1, 0.001 -> 3, 1 -> 0
+pub fn precision_from_increment(inc: &Decimal) -> u32 {
+    inc.normalize().scale()
 }
 
 /// Parse a string as Decimal, preserving full precision
 /// Handles both regular decimal notation and scientific notation (e.g., "1e-8")
 pub fn parse_decimal(s: &str) -> anyhow::Result<Decimal> {
-    // Try parsing directly first
-    if let Ok(dec) = Decimal::from_str(s) {
-        return Ok(dec);
+    // Decimal::from_str accepts exponents too, but silently rounds ones that
+    // push past 28 decimal places, so those take the exact path below
+    let Some((mantissa, exponent)) = s.split_once(['e', 'E']) else {
+        return Decimal::from_str(s).map_err(|e| anyhow::anyhow!("Failed to parse decimal '{}': {}", s, e));
+    };
+    let mantissa = Decimal::from_str(mantissa)
+        .map_err(|e| anyhow::anyhow!("Failed to parse decimal '{}': {}", s, e))?;
+    let exponent: i64 = exponent
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Failed to parse decimal '{}': Invalid exponent", s))?;
+    apply_exponent(mantissa, exponent)
+        .ok_or_else(|| anyhow::anyhow!("Failed to parse decimal '{}': Out of range for Decimal", s))
+}
+
+/// `mantissa * 10^exponent`, shifting the scale instead of multiplying where possible
+fn apply_exponent(mantissa: Decimal, exponent: i64) -> Option<Decimal> {
+    if mantissa.is_zero() {
+        return Some(Decimal::ZERO);
     }
-    
-    // If that fails, try parsing as f64 first (handles scientific notation)
-    // then convert to Decimal
-    if let Ok(f) = s.parse::<f64>() {
-        Decimal::from_str_exact(&format!("{}", f))
-            .or_else(|_| Decimal::try_from(f))
-            .map_err(|e| anyhow::anyhow!("Failed to parse decimal '{}': {}", s, e))
+    // "1.000e-28" still fits once its trailing zeros are gone
+    let mantissa = mantissa.normalize();
+    let scale = mantissa.scale() as i64 - exponent;
+    if scale >= 0 {
+        let scale = u32::try_from(scale).ok().filter(|&s| s <= Decimal::MAX_SCALE)?;
+        Decimal::try_from_i128_with_scale(mantissa.mantissa(), scale).ok()
     } else {
-        Err(anyhow::anyhow!("Failed to parse decimal '{}': Invalid format", s))
+        // Overflows within 29 steps for any non-zero mantissa
+        (0..-scale).try_fold(Decimal::from_i128_with_scale(mantissa.mantissa(), 0), |acc, _| {
+            acc.checked_mul(Decimal::TEN)
+        })
     }
 }
 
@@ -89,5 +147,170 @@ mod tests {
         assert_eq!(format_fixed(&dec!(50000.12345678), 8), "5000012345678");
         assert_eq!(format_fixed(&dec!(0.00000001), 8), "1");
     }
+
+    #[test]
+    fn test_format_fixed_rounds_half_even() {
+        // Exact midpoints go to the even neighbour
+        assert_eq!(format_fixed(&dec!(0.125), 2), "12");
+        assert_eq!(format_fixed(&dec!(0.135), 2), "14");
+        assert_eq!(format_fixed(&dec!(0.123456785), 8), "12345678");
+        assert_eq!(format_fixed(&dec!(0.123456775), 8), "12345678");
+        assert_eq!(format_fixed(&dec!(45283.45), 1), "452834");
+        assert_eq!(format_fixed(&dec!(45283.55), 1), "452836");
+        assert_eq!(format_fixed(&dec!(0.000000005), 8), "0");
+        assert_eq!(format_fixed(&dec!(0.000000015), 8), "2");
+        assert_eq!(format_fixed(&dec!(9.995), 2), "1000");
+        
+        // Just off the midpoint rounds normally
+        assert_eq!(format_fixed(&dec!(0.1250000001), 2), "13");
+        assert_eq!(format_fixed(&dec!(0.1349999999), 2), "13");
+        assert_eq!(format_fixed(&dec!(0.1234567849), 8), "12345678");
+        assert_eq!(format_fixed(&dec!(0.1234567851), 8), "12345679");
+        
+        // Scale 0 rounds like every other scale
+        assert_eq!(format_fixed(&dec!(0.5), 0), "0");
+        assert_eq!(format_fixed(&dec!(1.5), 0), "2");
+        assert_eq!(format_fixed(&dec!(2.5), 0), "2");
+        assert_eq!(format_fixed(&dec!(2.51), 0), "3");
+        assert_eq!(format_fixed(&dec!(45283), 0), "45283");
+        
+        // Every midpoint in [0, 1) at 2 dp -> 1 dp
+        for tenths in 0..10i64 {
+            let midpoint = Decimal::new(tenths * 10 + 5, 2);
+            let expected = if tenths % 2 == 0 { tenths } else { tenths + 1 };
+            assert_eq!(format_fixed(&midpoint, 1), expected.to_string(), "{}", midpoint);
+        }
+    }
+
+    #[test]
+    fn test_format_fixed_trailing_zeros() {
+        // Stored trailing zeros don't change the output
+        for value in [dec!(1.5), dec!(1.50), dec!(1.50000000), dec!(1.500000000000)] {
+            assert_eq!(format_fixed(&value, 8), "150000000");
+            assert_eq!(format_fixed(&value, 1), "15");
+            assert_eq!(format_fixed(&value, 0), "2");
+        }
+        assert_eq!(format_fixed(&dec!(100.00), 0), "100");
+        assert_eq!(format_fixed(&dec!(100), 3), "100000");
+        assert_eq!(format_fixed(&dec!(0.00000000), 8), "0");
+        assert_eq!(format_fixed(&dec!(0), 0), "0");
+        assert_eq!(format_fixed(&dec!(0.0100), 4), "100");
+    }
+
+    #[test]
+    fn test_format_fixed_excess_precision() {
+        // More stored digits than the scale get rounded, never truncated
+        assert_eq!(format_fixed(&dec!(1.23456789012), 8), "123456789");
+        assert_eq!(format_fixed(&dec!(1.23456789512), 8), "123456790");
+        assert_eq!(format_fixed(&dec!(0.000000004), 8), "0");
+        assert_eq!(format_fixed(&dec!(0.000000006), 8), "1");
+        assert_eq!(format_fixed(&dec!(50000.099), 1), "500001");
+        assert_eq!(format_fixed(&dec!(99999.99), 1), "1000000");
+        assert_eq!(format_fixed(&dec!(0.0000000000000000000000000001), 8), "0");
+        
+        // Scales beyond Decimal's 28 places still pad
+        assert_eq!(format_fixed(&dec!(1), 30), format!("1{}", "0".repeat(30)));
+    }
+
+    #[test]
+    fn test_format_fixed_sign() {
+        assert_eq!(format_fixed(&dec!(-0.5), 1), "-5");
+        assert_eq!(format_fixed(&dec!(-1.25), 8), "-125000000");
+        assert_eq!(format_fixed(&dec!(-45283.45), 1), "-452834");
+        // Negative values that round to zero lose their sign
+        assert_eq!(format_fixed(&dec!(-0.004), 2), "0");
+        assert_eq!(format_fixed(&dec!(-0.00), 2), "0");
+        assert_eq!(format_fixed(&Decimal::ZERO, 0), "0");
+    }
+
+    #[test]
+    fn test_precision_from_increment() {
+        assert_eq!(precision_from_increment(&dec!(0.5)), 1);
+        assert_eq!(precision_from_increment(&dec!(0.001)), 3);
+        assert_eq!(precision_from_increment(&dec!(1)), 0);
+        assert_eq!(precision_from_increment(&dec!(10)), 0);
+        assert_eq!(precision_from_increment(&parse_decimal("1e-8").unwrap()), 8);
+        // Trailing zeros in the payload don't add places
+        assert_eq!(precision_from_increment(&dec!(0.0100)), 2);
+        assert_eq!(precision_from_increment(&dec!(1.00000000)), 0);
+    }
+
+    #[test]
+    fn test_parse_decimal() {
+        // Plain strings keep their stored scale
+        assert_eq!(parse_decimal("50000.10").unwrap().to_string(), "50000.10");
+        assert_eq!(parse_decimal("0.00000001").unwrap(), dec!(0.00000001));
+        assert_eq!(parse_decimal("-12.5").unwrap(), dec!(-12.5));
+        
+        // Scientific notation, exactly
+        assert_eq!(parse_decimal("1e-8").unwrap(), dec!(0.00000001));
+        assert_eq!(parse_decimal("2.5e-7").unwrap(), dec!(0.00000025));
+        assert_eq!(parse_decimal("2.5E-7").unwrap(), dec!(0.00000025));
+        assert_eq!(parse_decimal("1.234567891234e3").unwrap(), dec!(1234.567891234));
+        assert_eq!(parse_decimal("1.5e+2").unwrap(), dec!(150));
+        assert_eq!(parse_decimal("3e10").unwrap(), dec!(30000000000));
+        assert_eq!(parse_decimal("-4.2e-3").unwrap(), dec!(-0.0042));
+        assert_eq!(parse_decimal("0e-40").unwrap(), dec!(0));
+        assert_eq!(parse_decimal("1.000e-28").unwrap(), dec!(0.0000000000000000000000000001));
+        
+        // Mantissas beyond f64's 15-16 significant digits survive
+        assert_eq!(
+            parse_decimal("1.2345678901234567891e-5").unwrap(),
+            dec!(0.000012345678901234567891),
+        );
+        
+        // Out of Decimal's range, or malformed
+        assert!(parse_decimal("1e-29").is_err());
+        assert!(parse_decimal("2.5e-28").is_err());
+        assert!(parse_decimal("1e-4294967296").is_err());
+        assert!(parse_decimal("1e29").is_err());
+        assert!(parse_decimal("1e9999999999").is_err());
+        assert!(parse_decimal("1e").is_err());
+        assert!(parse_decimal("e5").is_err());
+        assert!(parse_decimal("1.5x").is_err());
+        assert!(parse_decimal("").is_err());
+    }
+
+    #[test]
+    fn test_precision_formatter_matches_format_fixed() {
+        let values = [
+            dec!(0), dec!(0.00000001), dec!(0.5), dec!(1), dec!(45283.5), dec!(45283.55),
+            dec!(0.123456785), dec!(1.50000000), dec!(99999.99), dec!(1234567890.123456789),
+            dec!(0.00012345), dec!(7.0000000000001),
+        ];
+        for (pp, qp) in [(0, 0), (1, 8), (2, 6), (5, 4), (8, 10), (29, 30)] {
+            let formatter = PrecisionFormatter::from_instrument(&InstrumentInfo {
+                price_precision: pp,
+                qty_precision: qp,
+                ..Default::default()
+            });
+            assert_eq!((formatter.price_precision(), formatter.qty_precision()), (pp, qp));
+            for value in values {
+                let mut out = String::new();
+                formatter.format_price(&value, &mut out);
+                assert_eq!(out.as_bytes(), format_fixed(&value, pp).as_bytes(), "price {} at {}", value, pp);
+                formatter.format_qty(&value, &mut out);
+                assert_eq!(out, format!("{}{}", format_fixed(&value, pp), format_fixed(&value, qp)));
+            }
+        }
+    }
+
+    #[test]
+    fn test_format_fixed_into_matches() {
+        let values = [
+            dec!(123.45), dec!(0.01), dec!(0.10), dec!(100.00), dec!(0.00), dec!(0),
+            dec!(50000.12345678), dec!(0.00000001), dec!(1.5), dec!(45283.5),
+            dec!(0.123456785), dec!(2.675), dec!(1999),
+        ];
+        let mut out = String::new();
+        for value in values {
+            for scale in [0, 1, 2, 4, 8, 12] {
+                out.clear();
+                out.push('x');
+                format_fixed_into(&value, scale, &mut out);
+                assert_eq!(out[1..], format_fixed(&value, scale), "{} at scale {}", value, scale);
+            }
+        }
+    }
 }
 
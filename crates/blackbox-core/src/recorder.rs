@@ -1,63 +1,1164 @@
-use crate::types::RecordedFrame;
-use chrono::Utc;
+use crate::types::{RecordedFrame, RecordingMetadata};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use serde::{Deserialize, Serialize};
 use serde_json;
-use std::fs::File;
-use std::io::{BufWriter, Write};
-use std::path::PathBuf;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// First two bytes of every gzip stream
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Compression {
+    #[default]
+    None,
+    Gzip,
+}
+
+/// When to close the current recording file and continue in the next segment,
+/// `basename.0001.ndjson`, `basename.0002.ndjson`, ... Limits are checked
+/// between frames, so a frame is never split across segments.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RotationPolicy {
+    pub max_bytes: Option<u64>, // NDJSON bytes, before compression
+    pub max_duration: Option<Duration>, // checked when the next frame arrives
+}
+
+impl RotationPolicy {
+    pub fn is_enabled(&self) -> bool {
+        self.max_bytes.is_some() || self.max_duration.is_some()
+    }
+}
+
+/// When buffered frames are written through to the file: after `every_frames`
+/// frames or once `interval` has passed since the last flush, whichever comes
+/// first. Both are checked as frames are recorded; `close()` and drop always
+/// flush. With neither set, data only reaches the file when the buffer fills.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FlushPolicy {
+    pub every_frames: Option<u64>,
+    pub interval: Option<Duration>,
+}
+
+impl FlushPolicy {
+    /// Flush after each frame (slow on spinning disks and network filesystems)
+    pub fn every_frame() -> Self {
+        Self { every_frames: Some(1), interval: None }
+    }
+
+    fn is_due(&self, unflushed_frames: u64, since_flush: Duration) -> bool {
+        self.every_frames.is_some_and(|n| unflushed_frames >= n)
+            || self.interval.is_some_and(|interval| since_flush >= interval)
+    }
+}
+
+impl Default for FlushPolicy {
+    fn default() -> Self {
+        Self {
+            every_frames: Some(1000),
+            interval: Some(Duration::from_millis(500)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RecorderOptions {
+    // Gzip is also used whenever the path ends in `.gz`
+    pub compression: Compression,
+    pub rotation: RotationPolicy,
+    pub flush: FlushPolicy,
+    // Written as the header of every segment; `started_at` is set when recording starts
+    pub metadata: Option<RecordingMetadata>,
+    // Continue an existing recording instead of truncating it (see `Recorder::append`)
+    pub append: bool,
+    // Only record frames of these channels: "book", "instrument", "status",
+    // "heartbeat", "ping" or "ack" (subscription responses). Noted in the header.
+    pub channel_filter: Option<Vec<String>>,
+}
+
+/// Running totals for a recording, across all of its segments
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct RecordingStats {
+    pub frames_written: u64,
+    pub bytes_written: u64, // NDJSON bytes including headers, before compression
+    pub started_at: DateTime<Utc>,
+    pub last_frame_at: Option<DateTime<Utc>>,
+}
+
+/// A line of a recording
+#[derive(Debug, Clone)]
+pub enum RecordingLine {
+    Metadata(RecordingMetadata),
+    Frame(RecordedFrame),
+}
+
+#[derive(Deserialize)]
+struct MetadataLine {
+    #[serde(rename = "_meta")]
+    meta: RecordingMetadata,
+}
+
+/// Parse one non-blank line of a recording
+pub fn parse_recording_line(line: &str) -> anyhow::Result<RecordingLine> {
+    if line.trim_start().starts_with(r#"{"_meta""#) {
+        let header: MetadataLine = serde_json::from_str(line)?;
+        return Ok(RecordingLine::Metadata(header.meta));
+    }
+    Ok(RecordingLine::Frame(serde_json::from_str(line)?))
+}
+
+enum RecordWriter {
+    Plain(BufWriter<File>),
+    Gzip(GzEncoder<BufWriter<File>>),
+}
+
+impl RecordWriter {
+    fn writer(&mut self) -> &mut dyn Write {
+        match self {
+            RecordWriter::Plain(w) => w,
+            RecordWriter::Gzip(w) => w,
+        }
+    }
+
+    /// Flush everything, writing the gzip trailer if compressed
+    fn finish(self) -> std::io::Result<()> {
+        match self {
+            RecordWriter::Plain(mut w) => w.flush(),
+            RecordWriter::Gzip(w) => w.finish()?.flush(),
+        }
+    }
+}
 
 pub struct Recorder {
-    writer: Option<BufWriter<File>>,
-    path: PathBuf,
+    writer: Option<RecordWriter>,
+    path: PathBuf, // current segment
+    base_path: PathBuf,
+    gzip: bool,
+    rotation: RotationPolicy,
+    segment: u32,
+    segment_bytes: u64,
+    segment_frames: u64,
+    segment_started: Instant,
+    metadata: Option<RecordingMetadata>,
+    channel_filter: Option<Vec<String>>,
+    stats: RecordingStats,
+    flush: FlushPolicy,
+    unflushed_frames: u64,
+    last_flush: Instant,
 }
 
 impl Recorder {
     pub fn new(path: PathBuf) -> anyhow::Result<Self> {
+        Self::new_with_options(path, RecorderOptions::default())
+    }
+
+    /// Continue the recording at `path` instead of truncating it. Writing
+    /// resumes in its last rotation segment, after cutting off a final line
+    /// torn by a crash; a header is only written to new files.
+    pub fn append(path: PathBuf) -> anyhow::Result<Self> {
+        Self::new_with_options(path, RecorderOptions { append: true, ..Default::default() })
+    }
+
+    pub fn new_with_options(path: PathBuf, options: RecorderOptions) -> anyhow::Result<Self> {
         // Create parent directory if needed
         if let Some(parent) = path.parent() {
             std::fs::create_dir_all(parent)?;
         }
         
-        let file = File::create(&path)?;
-        let writer = BufWriter::new(file);
+        let gzip = options.compression == Compression::Gzip
+            || path.extension().is_some_and(|ext| ext == "gz");
+        let started_at = Utc::now();
+        let channel_filter = options.channel_filter;
+        let metadata = options.metadata.map(|meta| RecordingMetadata {
+            started_at,
+            channels: channel_filter.clone(),
+            ..meta
+        });
         
-        Ok(Self {
-            writer: Some(writer),
+        let mut recorder = Self {
+            writer: None,
+            base_path: path.clone(),
             path,
-        })
+            gzip,
+            rotation: options.rotation,
+            segment: 0,
+            segment_bytes: 0,
+            segment_frames: 0,
+            segment_started: Instant::now(),
+            metadata,
+            channel_filter,
+            stats: RecordingStats {
+                frames_written: 0,
+                bytes_written: 0,
+                started_at,
+                last_frame_at: None,
+            },
+            flush: options.flush,
+            unflushed_frames: 0,
+            last_flush: Instant::now(),
+        };
+        if options.append && recorder.path.exists() {
+            recorder.resume_segment()?;
+        } else {
+            recorder.open_segment()?;
+        }
+        Ok(recorder)
     }
 
-    pub fn record_frame(&mut self, raw_frame: &str, decoded_event: Option<&str>) -> anyhow::Result<()> {
+    /// Reopen the last existing segment for appending
+    fn resume_segment(&mut self) -> anyhow::Result<()> {
+        if let Some(last) = recording_segments(&self.path)?.pop() {
+            let name = last.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+            self.segment = split_segment_name(&name).1;
+            self.path = last;
+        }
+        let (bytes, frames) = repair_tail(&self.path, self.gzip)?;
+        if bytes == 0 {
+            return self.open_segment();
+        }
+        let file = OpenOptions::new().append(true).open(&self.path)?;
+        self.writer = Some(wrap_writer(file, self.gzip));
+        self.segment_bytes = bytes;
+        self.segment_frames = frames;
+        self.segment_started = Instant::now();
+        self.unflushed_frames = 0;
+        self.last_flush = Instant::now();
+        Ok(())
+    }
+
+    /// Create the current segment's file and write its header
+    fn open_segment(&mut self) -> anyhow::Result<()> {
+        let mut writer = open_writer(&self.path, self.gzip)?;
+        self.segment_bytes = 0;
+        self.segment_frames = 0;
+        self.segment_started = Instant::now();
+        if let Some(meta) = &self.metadata {
+            let json = serde_json::to_string(&serde_json::json!({ "_meta": meta }))?;
+            writeln!(writer.writer(), "{}", json)?;
+            writer.writer().flush()?;
+            self.segment_bytes = json.len() as u64 + 1;
+            self.stats.bytes_written += self.segment_bytes;
+        }
+        self.writer = Some(writer);
+        self.unflushed_frames = 0;
+        self.last_flush = Instant::now();
+        Ok(())
+    }
+
+    /// Append a frame. Returns the new segment's path when the rotation policy
+    /// started one for this frame.
+    pub fn record_frame(&mut self, raw_frame: &str, decoded_event: Option<&str>) -> anyhow::Result<Option<PathBuf>> {
+        self.record_frame_at(Utc::now(), raw_frame, decoded_event)
+    }
+
+    /// `record_frame` for a frame received at `ts`, e.g. one that waited in a queue
+    pub fn record_frame_at(
+        &mut self,
+        ts: DateTime<Utc>,
+        raw_frame: &str,
+        decoded_event: Option<&str>,
+    ) -> anyhow::Result<Option<PathBuf>> {
+        if self.writer.is_none() || !self.accepts(raw_frame, decoded_event) {
+            return Ok(None);
+        }
+        let frame = RecordedFrame {
+            ts,
+            raw_frame: raw_frame.to_string(),
+            decoded_event: decoded_event.map(|s| s.to_string()),
+        };
+        let json = serde_json::to_string(&frame)?;
+        let line_len = json.len() as u64 + 1;
+        
+        let rotated = if self.should_rotate(line_len) {
+            self.rotate()?;
+            Some(self.path.clone())
+        } else {
+            None
+        };
         if let Some(writer) = &mut self.writer {
-            let frame = RecordedFrame {
-                ts: Utc::now(),
-                raw_frame: raw_frame.to_string(),
-                decoded_event: decoded_event.map(|s| s.to_string()),
-            };
-            
-            let json = serde_json::to_string(&frame)?;
-            writeln!(writer, "{}", json)?;
-            writer.flush()?;
+            writeln!(writer.writer(), "{}", json)?;
+        }
+        self.segment_bytes += line_len;
+        self.segment_frames += 1;
+        self.stats.frames_written += 1;
+        self.stats.bytes_written += line_len;
+        self.stats.last_frame_at = Some(ts);
+        self.unflushed_frames += 1;
+        if self.flush.is_due(self.unflushed_frames, self.last_flush.elapsed()) {
+            self.flush()?;
         }
         
+        Ok(rotated)
+    }
+
+    /// Whether the channel filter lets a frame through
+    fn accepts(&self, raw_frame: &str, decoded_event: Option<&str>) -> bool {
+        let Some(filter) = &self.channel_filter else {
+            return true;
+        };
+        frame_channel(raw_frame, decoded_event).is_some_and(|channel| filter.iter().any(|c| *c == channel))
+    }
+
+    fn should_rotate(&self, next_line_len: u64) -> bool {
+        // A frame bigger than max_bytes still gets a segment of its own
+        if self.segment_frames == 0 {
+            return false;
+        }
+        self.rotation.max_bytes.is_some_and(|max| self.segment_bytes + next_line_len > max)
+            || self.rotation.max_duration.is_some_and(|max| self.segment_started.elapsed() >= max)
+    }
+
+    fn rotate(&mut self) -> anyhow::Result<()> {
+        if let Some(writer) = self.writer.take() {
+            writer.finish()?;
+        }
+        self.segment += 1;
+        self.path = segment_path(&self.base_path, self.segment);
+        self.open_segment()
+    }
+
+    /// Write buffered frames through to the file
+    pub fn flush(&mut self) -> anyhow::Result<()> {
+        if let Some(writer) = &mut self.writer {
+            writer.writer().flush()?;
+        }
+        self.unflushed_frames = 0;
+        self.last_flush = Instant::now();
         Ok(())
     }
 
+    /// Flush and close the file. Frames recorded afterwards are ignored.
     pub fn close(&mut self) -> anyhow::Result<()> {
-        if let Some(writer) = &mut self.writer {
-            writer.flush()?;
+        if let Some(writer) = self.writer.take() {
+            writer.finish()?;
         }
-        self.writer = None;
         Ok(())
     }
 
+    pub fn stats(&self) -> RecordingStats {
+        self.stats
+    }
+
+    pub fn flush_policy(&self) -> FlushPolicy {
+        self.flush
+    }
+
+    /// Path of the segment currently being written
     pub fn path(&self) -> &PathBuf {
         &self.path
     }
 }
 
+#[derive(Deserialize)]
+struct ChannelEnvelope<'a> {
+    #[serde(borrow, default)]
+    channel: Option<Cow<'a, str>>,
+    #[serde(borrow, default)]
+    method: Option<Cow<'a, str>>,
+    #[serde(default)]
+    success: Option<bool>,
+}
+
+/// Channel of a raw frame, "ack" for subscription responses. Taken from the
+/// `decoded_event` tag when there is one, else from the frame's top-level fields.
+pub fn frame_channel<'a>(raw_frame: &'a str, decoded_event: Option<&'a str>) -> Option<Cow<'a, str>> {
+    if let Some(tag) = decoded_event {
+        return tag.split(['.', ':']).next().map(Cow::Borrowed);
+    }
+    let envelope: ChannelEnvelope = serde_json::from_str(raw_frame).ok()?;
+    if envelope.method.is_some() || envelope.success.is_some() {
+        return Some(Cow::Borrowed("ack"));
+    }
+    envelope.channel
+}
+
+fn open_writer(path: &Path, gzip: bool) -> anyhow::Result<RecordWriter> {
+    Ok(wrap_writer(File::create(path)?, gzip))
+}
+
+/// Appending to a gzip file starts a new gzip member, which `open_recording` reads through
+fn wrap_writer(file: File, gzip: bool) -> RecordWriter {
+    let file = BufWriter::new(file);
+    if gzip {
+        RecordWriter::Gzip(GzEncoder::new(file, flate2::Compression::default()))
+    } else {
+        RecordWriter::Plain(file)
+    }
+}
+
+/// Make an existing recording safe to append to and return its NDJSON bytes
+/// and frame count. A final line without a newline is kept (and terminated)
+/// if it parses, and cut off otherwise. Compressed files can't be cut, so a
+/// torn gzip recording is an error.
+fn repair_tail(path: &Path, gzip: bool) -> anyhow::Result<(u64, u64)> {
+    let mut reader: Box<dyn BufRead> = if gzip {
+        Box::new(open_recording(path)?)
+    } else {
+        Box::new(BufReader::new(File::open(path)?))
+    };
+    let mut complete = 0u64; // bytes up to the last newline
+    let mut frames = 0u64;
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        let read = reader
+            .read_until(b'\n', &mut line)
+            .with_context(|| format!("Can't append to {}", path.display()))?;
+        if read == 0 {
+            break;
+        }
+        let text = String::from_utf8_lossy(&line);
+        if !line.ends_with(b"\n") {
+            if gzip {
+                anyhow::bail!("{} ends in a torn line; can't append to a compressed recording", path.display());
+            }
+            let mut file = OpenOptions::new().write(true).open(path)?;
+            if let Ok(parsed) = parse_recording_line(&text) {
+                file.seek(SeekFrom::End(0))?;
+                file.write_all(b"\n")?;
+                let frame = matches!(parsed, RecordingLine::Frame(_)) as u64;
+                return Ok((complete + line.len() as u64 + 1, frames + frame));
+            }
+            file.set_len(complete)?;
+            break;
+        }
+        complete += line.len() as u64;
+        if !text.trim().is_empty() && !text.trim_start().starts_with(r#"{"_meta""#) {
+            frames += 1;
+        }
+    }
+    Ok((complete, frames))
+}
+
+/// `session.ndjson.gz` -> ("session", 0, "ndjson.gz"); `session.0003.ndjson` -> ("session", 3, "ndjson")
+pub(crate) fn split_segment_name(name: &str) -> (&str, u32, &str) {
+    let (stem, rest) = name.split_once('.').unwrap_or((name, ""));
+    let (index, ext) = rest.split_once('.').unwrap_or((rest, ""));
+    match index.parse() {
+        Ok(segment) if index.len() >= 4 && index.bytes().all(|b| b.is_ascii_digit()) => (stem, segment, ext),
+        _ => (stem, 0, rest),
+    }
+}
+
+/// Path of rotation segment `segment` of a recording started at `base`
+pub fn segment_path(base: &Path, segment: u32) -> PathBuf {
+    let name = base.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    let (stem, _, ext) = split_segment_name(&name);
+    let name = if ext.is_empty() {
+        format!("{}.{:04}", stem, segment)
+    } else {
+        format!("{}.{:04}.{}", stem, segment, ext)
+    };
+    base.with_file_name(name)
+}
+
+/// Recording files to replay, in order, for `path`:
+/// - a directory: every `.ndjson`/`.ndjson.gz` file in it
+/// - a file name with `*` wildcards: the matching files in its directory
+/// - a recording: the file followed by its rotation segments
+/// 
+/// Files are ordered by name stem, then segment number.
+pub fn recording_segments(path: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    let is_dir = path.is_dir();
+    let dir = if is_dir { path.to_path_buf() } else { parent_dir(path) };
+    let (stem, _, ext) = split_segment_name(&name);
+    let matches = |n: &str| {
+        if is_dir {
+            n.ends_with(".ndjson") || n.ends_with(".ndjson.gz")
+        } else if name.contains('*') {
+            wildcard_match(&name, n)
+        } else {
+            let (s, _, e) = split_segment_name(n);
+            s == stem && e == ext
+        }
+    };
+    
+    let mut segments: Vec<PathBuf> = std::fs::read_dir(&dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|p| p.is_file() && p.file_name().is_some_and(|n| matches(&n.to_string_lossy())))
+        .collect();
+    segments.sort_by_cached_key(|p| {
+        let name = p.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+        let (stem, segment, _) = split_segment_name(&name);
+        (stem.to_string(), segment, name.clone())
+    });
+    if segments.is_empty() {
+        anyhow::bail!("No recordings found for {}", path.display());
+    }
+    Ok(segments)
+}
+
+fn parent_dir(path: &Path) -> PathBuf {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+/// `*` matches any run of characters; everything else matches itself
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or("");
+    let Some(mut rest) = name.strip_prefix(first) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    for (i, part) in parts.iter().enumerate() {
+        if i == parts.len() - 1 {
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(at) => rest = &rest[at + part.len()..],
+            None => return false,
+        }
+    }
+    rest.is_empty()
+}
+
 impl Drop for Recorder {
     fn drop(&mut self) {
         let _ = self.close();
     }
 }
 
+/// File stem of the non-book frames in a per-symbol recording
+pub const CONTROL_STEM: &str = "_control";
+
+/// `BTC/USD` -> `BTC_USD`
+pub fn symbol_file_stem(symbol: &str) -> String {
+    symbol.replace(['/', ':'], "_")
+}
+
+/// A recording split into one file per symbol: book frames go to
+/// `dir/BTC_USD.ndjson`, `dir/ETH_USD.ndjson`, ..., everything else to
+/// `dir/_control.ndjson`. Each file rotates on its own; replay it with
+/// `Replayer::merged`.
+pub struct SymbolRecorder {
+    dir: PathBuf,
+    extension: &'static str,
+    options: RecorderOptions,
+    control: Recorder,
+    books: HashMap<String, Recorder>,
+}
+
+impl SymbolRecorder {
+    pub fn new(dir: PathBuf, options: RecorderOptions) -> anyhow::Result<Self> {
+        std::fs::create_dir_all(&dir)?;
+        let extension = if options.compression == Compression::Gzip { "ndjson.gz" } else { "ndjson" };
+        let control = Recorder::new_with_options(dir.join(format!("{}.{}", CONTROL_STEM, extension)), options.clone())?;
+        Ok(Self {
+            dir,
+            extension,
+            options,
+            control,
+            books: HashMap::new(),
+        })
+    }
+
+    /// Record a frame into `symbol`'s file, or the control file for frames
+    /// that aren't book frames. Returns the path of any new segment.
+    pub fn record_frame_at(
+        &mut self,
+        ts: DateTime<Utc>,
+        raw_frame: &str,
+        decoded_event: Option<&str>,
+        symbol: Option<&str>,
+    ) -> anyhow::Result<Option<PathBuf>> {
+        let recorder = match symbol {
+            None => &mut self.control,
+            Some(symbol) => {
+                if !self.books.contains_key(symbol) {
+                    let path = self.dir.join(format!("{}.{}", symbol_file_stem(symbol), self.extension));
+                    let recorder = Recorder::new_with_options(path, self.options.clone())?;
+                    self.books.insert(symbol.to_string(), recorder);
+                }
+                self.books.get_mut(symbol).unwrap()
+            }
+        };
+        recorder.record_frame_at(ts, raw_frame, decoded_event)
+    }
+
+    pub fn flush(&mut self) -> anyhow::Result<()> {
+        self.control.flush()?;
+        self.books.values_mut().try_for_each(Recorder::flush)
+    }
+
+    pub fn close(&mut self) -> anyhow::Result<()> {
+        self.control.close()?;
+        self.books.values_mut().try_for_each(Recorder::close)
+    }
+
+    /// Totals over every file
+    pub fn stats(&self) -> RecordingStats {
+        self.books.values().map(Recorder::stats).fold(self.control.stats(), |total, stats| RecordingStats {
+            frames_written: total.frames_written + stats.frames_written,
+            bytes_written: total.bytes_written + stats.bytes_written,
+            started_at: total.started_at.min(stats.started_at),
+            last_frame_at: total.last_frame_at.max(stats.last_frame_at),
+        })
+    }
+
+    pub fn flush_policy(&self) -> FlushPolicy {
+        self.options.flush
+    }
+
+    pub fn dir(&self) -> &PathBuf {
+        &self.dir
+    }
+
+    /// Segments currently being written, one per file
+    pub fn paths(&self) -> Vec<PathBuf> {
+        std::iter::once(&self.control).chain(self.books.values()).map(|r| r.path().clone()).collect()
+    }
+}
+
+/// A recording opened by `open_recording`, plain or gzipped
+pub struct RecordingReader {
+    inner: Box<dyn BufRead + Send>,
+    compressed: bool,
+}
+
+impl RecordingReader {
+    pub fn is_compressed(&self) -> bool {
+        self.compressed
+    }
+}
+
+impl Read for RecordingReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+impl BufRead for RecordingReader {
+    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.inner.consume(amt)
+    }
+}
+
+/// Open a recording for line-by-line reading, decompressing it if it starts
+/// with the gzip magic bytes (whatever its extension)
+pub fn open_recording<P: AsRef<Path>>(path: P) -> anyhow::Result<RecordingReader> {
+    read_recording(File::open(path)?)
+}
+
+/// `open_recording` over an already open file or other reader
+pub fn read_recording<R: Read + Send + 'static>(reader: R) -> anyhow::Result<RecordingReader> {
+    let mut reader = BufReader::new(reader);
+    let compressed = reader.fill_buf()?.starts_with(&GZIP_MAGIC);
+    let inner: Box<dyn BufRead + Send> = if compressed {
+        Box::new(BufReader::new(MultiGzDecoder::new(reader)))
+    } else {
+        Box::new(reader)
+    };
+    Ok(RecordingReader { inner, compressed })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replayer::Replayer;
+    use crate::types::{FaultRule, FaultType, ReplayConfig, ReplayMode};
+
+    fn replay_all(path: PathBuf) -> Vec<String> {
+        let config = ReplayConfig { mode: ReplayMode::AsFast, faults: vec![], loop_playback: false };
+        let mut replayer = Replayer::from_segments(&path, config).unwrap();
+        replayer.start();
+        std::iter::from_fn(|| replayer.next_frame()).collect()
+    }
+
+    fn frame(i: usize) -> String {
+        format!(r#"{{"channel":"book","type":"update","data":[{{"symbol":"BTC/USD","seq":{}}}]}}"#, i)
+    }
+
+    #[test]
+    fn test_decoded_tags_follow_frames() {
+        let dir = std::env::temp_dir().join(format!("blackbox_recorder_tags_{}", std::process::id()));
+        let path = dir.join("tagged.ndjson");
+        let mut recorder = Recorder::new(path.clone()).unwrap();
+        for i in 0..6 {
+            // Untagged frames, as with --record-decoded=false
+            let tag = (i != 4).then(|| format!("book.update:{}", i));
+            recorder.record_frame(&frame(i), tag.as_deref()).unwrap();
+        }
+        drop(recorder);
+        
+        // The reordered pair carries its tags along
+        let fault = FaultRule::OnceAt { index: 2, fault: FaultType::Reorder };
+        let mut replayer = Replayer::new(path, ReplayConfig { mode: ReplayMode::AsFast, faults: vec![fault], loop_playback: false }).unwrap();
+        replayer.start();
+        let classified: Vec<_> = std::iter::from_fn(|| replayer.next_classified()).collect();
+        let order = [0, 2, 1, 3, 4, 5];
+        assert_eq!(classified.len(), order.len());
+        for ((tag, raw), i) in classified.into_iter().zip(order) {
+            assert_eq!(raw, frame(i));
+            assert_eq!(tag, (i != 4).then(|| format!("book.update:{}", i)));
+        }
+        
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    fn book_frame(symbol: &str, seq: usize) -> String {
+        format!(r#"{{"channel":"book","type":"update","data":[{{"symbol":"{}","seq":{}}}]}}"#, symbol, seq)
+    }
+
+    fn file_frames(path: &Path) -> Vec<String> {
+        let content = std::fs::read_to_string(path).unwrap();
+        content
+            .lines()
+            .filter_map(|line| match parse_recording_line(line).unwrap() {
+                RecordingLine::Frame(frame) => Some(frame.raw_frame),
+                RecordingLine::Metadata(_) => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_per_symbol_routing() {
+        let dir = std::env::temp_dir().join(format!("blackbox_recorder_per_symbol_{}", std::process::id()));
+        let options = RecorderOptions {
+            metadata: Some(RecordingMetadata::new(vec!["BTC/USD".to_string(), "ETH/USD".to_string()], 10)),
+            ..Default::default()
+        };
+        let mut recorder = SymbolRecorder::new(dir.clone(), options).unwrap();
+        let instrument = r#"{"channel":"instrument","type":"snapshot","data":{"pairs":[]}}"#;
+        let heartbeat = r#"{"channel":"heartbeat"}"#;
+        let now = Utc::now();
+        recorder.record_frame_at(now, instrument, None, None).unwrap();
+        recorder.record_frame_at(now, &book_frame("BTC/USD", 0), None, Some("BTC/USD")).unwrap();
+        recorder.record_frame_at(now, &book_frame("ETH/USD", 0), None, Some("ETH/USD")).unwrap();
+        recorder.record_frame_at(now, heartbeat, None, None).unwrap();
+        recorder.record_frame_at(now, &book_frame("BTC/USD", 1), None, Some("BTC/USD")).unwrap();
+        assert_eq!(recorder.stats().frames_written, 5);
+        recorder.close().unwrap();
+        
+        assert_eq!(file_frames(&dir.join("BTC_USD.ndjson")), vec![book_frame("BTC/USD", 0), book_frame("BTC/USD", 1)]);
+        assert_eq!(file_frames(&dir.join("ETH_USD.ndjson")), vec![book_frame("ETH/USD", 0)]);
+        assert_eq!(file_frames(&dir.join("_control.ndjson")), vec![instrument.to_string(), heartbeat.to_string()]);
+        
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_per_symbol_merge_order() {
+        let dir = std::env::temp_dir().join(format!("blackbox_recorder_merge_{}", std::process::id()));
+        let options = RecorderOptions {
+            metadata: Some(RecordingMetadata::new(vec!["BTC/USD".to_string(), "ETH/USD".to_string()], 10)),
+            ..Default::default()
+        };
+        let mut recorder = SymbolRecorder::new(dir.clone(), options).unwrap();
+        let t0 = Utc::now();
+        let t1 = t0 + chrono::Duration::milliseconds(5);
+        let status = r#"{"channel":"status"}"#;
+        // Each file in time order, but written in an order the merge can't
+        // recover for equal timestamps
+        recorder.record_frame_at(t0, &book_frame("ETH/USD", 0), None, Some("ETH/USD")).unwrap();
+        recorder.record_frame_at(t1, &book_frame("ETH/USD", 1), None, Some("ETH/USD")).unwrap();
+        recorder.record_frame_at(t0, &book_frame("BTC/USD", 0), None, Some("BTC/USD")).unwrap();
+        recorder.record_frame_at(t0, &book_frame("BTC/USD", 1), None, Some("BTC/USD")).unwrap();
+        recorder.record_frame_at(t1, &book_frame("BTC/USD", 2), None, Some("BTC/USD")).unwrap();
+        recorder.record_frame_at(t0, status, None, None).unwrap();
+        recorder.record_frame_at(t1, status, None, None).unwrap();
+        recorder.close().unwrap();
+        
+        // By timestamp; ties go control file first, then by file name, then by line
+        let expected = vec![
+            status.to_string(),
+            book_frame("BTC/USD", 0),
+            book_frame("BTC/USD", 1),
+            book_frame("ETH/USD", 0),
+            status.to_string(),
+            book_frame("BTC/USD", 2),
+            book_frame("ETH/USD", 1),
+        ];
+        assert_eq!(replay_all(dir.clone()), expected);
+        let config = ReplayConfig { mode: ReplayMode::AsFast, faults: vec![], loop_playback: false };
+        let replayer = Replayer::merged(&dir, config).unwrap();
+        assert_eq!(replayer.metadata().unwrap().depth, Some(10));
+        
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_channel_filter() {
+        let dir = std::env::temp_dir().join(format!("blackbox_recorder_channels_{}", std::process::id()));
+        let fixture = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/recording_corrupted.ndjson");
+        let path = dir.join("books_only.ndjson");
+        let channels = vec!["book".to_string(), "instrument".to_string()];
+        let options = RecorderOptions {
+            metadata: Some(RecordingMetadata::new(vec!["BTC/USD".to_string()], 10)),
+            channel_filter: Some(channels.clone()),
+            ..Default::default()
+        };
+        let mut recorder = Recorder::new_with_options(path.clone(), options).unwrap();
+        for line in std::fs::read_to_string(fixture).unwrap().lines() {
+            let RecordingLine::Frame(frame) = parse_recording_line(line).unwrap() else {
+                continue;
+            };
+            recorder.record_frame_at(frame.ts, &frame.raw_frame, None).unwrap();
+        }
+        // Filtered on the tag when there is one
+        recorder.record_frame(r#"{"channel":"heartbeat"}"#, Some("heartbeat")).unwrap();
+        assert_eq!(recorder.stats().frames_written, 7);
+        drop(recorder);
+        
+        // The subscribe ack and heartbeats are gone; the header says why
+        let config = ReplayConfig { mode: ReplayMode::AsFast, faults: vec![], loop_playback: false };
+        let replayer = Replayer::new(path.clone(), config).unwrap();
+        assert_eq!(replayer.metadata().unwrap().channels, Some(channels));
+        assert!(replay_all(path.clone()).iter().all(|f| f.contains(r#""channel":"book""#) || f.contains(r#""channel":"instrument""#)));
+        
+        // The book rebuilds exactly as from the full recording
+        let full = crate::verify::verify_recording(fixture, &Default::default()).unwrap();
+        let filtered = crate::verify::verify_recording(&path, &Default::default()).unwrap();
+        assert_eq!(filtered.book_frames, full.book_frames);
+        assert_eq!(filtered.checksums_verified, full.checksums_verified);
+        assert_eq!(filtered.mismatches.len(), 1);
+        assert_eq!(filtered.mismatches[0].computed, full.mismatches[0].computed);
+        assert_eq!(filtered.mismatches[0].expected, full.mismatches[0].expected);
+        
+        assert_eq!(frame_channel(r#"{"method":"subscribe","success":true}"#, None).as_deref(), Some("ack"));
+        assert_eq!(frame_channel("", Some("book.update:BTC/USD")).as_deref(), Some("book"));
+        assert_eq!(frame_channel("not json", None), None);
+        
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_append_trims_torn_line() {
+        let dir = std::env::temp_dir().join(format!("blackbox_recorder_append_{}", std::process::id()));
+        let path = dir.join("resume.ndjson");
+        let options = RecorderOptions {
+            metadata: Some(RecordingMetadata::new(vec!["BTC/USD".to_string()], 10)),
+            ..Default::default()
+        };
+        let mut recorder = Recorder::new_with_options(path.clone(), options.clone()).unwrap();
+        for i in 0..3 {
+            recorder.record_frame(&frame(i), None).unwrap();
+        }
+        drop(recorder);
+        
+        // Killed halfway through a line
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(br#"{"ts":"2024-01-01T00:00:00Z","raw_fr"#).unwrap();
+        drop(file);
+        let mut recorder = Recorder::new_with_options(path.clone(), RecorderOptions { append: true, ..options }).unwrap();
+        assert_eq!(recorder.stats().frames_written, 0);
+        for i in 3..5 {
+            recorder.record_frame(&frame(i), None).unwrap();
+        }
+        drop(recorder);
+        assert_eq!(replay_all(path.clone()), (0..5).map(frame).collect::<Vec<_>>());
+        // One header, from the original recording
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(content.matches(r#"{"_meta""#).count(), 1);
+        
+        // A complete last line missing its newline is kept
+        let line = serde_json::to_string(&RecordedFrame { ts: Utc::now(), raw_frame: frame(5), decoded_event: None }).unwrap();
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(line.as_bytes()).unwrap();
+        drop(file);
+        let mut recorder = Recorder::append(path.clone()).unwrap();
+        recorder.record_frame(&frame(6), None).unwrap();
+        drop(recorder);
+        assert_eq!(replay_all(path.clone()), (0..7).map(frame).collect::<Vec<_>>());
+        
+        // Appending to a missing file starts a new recording
+        let fresh = dir.join("fresh.ndjson");
+        let mut recorder = Recorder::append(fresh.clone()).unwrap();
+        recorder.record_frame(&frame(0), None).unwrap();
+        drop(recorder);
+        assert_eq!(replay_all(fresh), vec![frame(0)]);
+        
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_metadata_header() {
+        let dir = std::env::temp_dir().join(format!("blackbox_recorder_meta_{}", std::process::id()));
+        let base = dir.join("meta.ndjson");
+        let meta = RecordingMetadata {
+            ws_url: Some("wss://ws.kraken.com/v2".to_string()),
+            ..RecordingMetadata::new(vec!["BTC/USD".to_string(), "ETH/USD".to_string()], 25)
+        };
+        let options = RecorderOptions {
+            rotation: RotationPolicy { max_bytes: Some(1000), max_duration: None },
+            metadata: Some(meta.clone()),
+            ..Default::default()
+        };
+        let mut recorder = Recorder::new_with_options(base.clone(), options).unwrap();
+        let frames: Vec<String> = (0..40).map(frame).collect();
+        for f in &frames {
+            recorder.record_frame(f, None).unwrap();
+        }
+        drop(recorder);
+        
+        // Every segment starts with the header and stays within the size limit
+        let segments = recording_segments(&base).unwrap();
+        assert!(segments.len() > 1);
+        for segment in &segments {
+            let content = std::fs::read_to_string(segment).unwrap();
+            assert!(content.len() as u64 <= 1000);
+            let first = content.lines().next().unwrap();
+            let RecordingLine::Metadata(header) = parse_recording_line(first).unwrap() else {
+                panic!("{} has no header", segment.display());
+            };
+            assert_eq!(header.symbols, meta.symbols);
+            assert_eq!(header.depth, Some(25));
+            assert!(header.is_supported());
+        }
+        
+        let config = ReplayConfig { mode: ReplayMode::AsFast, faults: vec![], loop_playback: false };
+        let replayer = Replayer::from_segments(&base, config.clone()).unwrap();
+        assert_eq!(replayer.metadata().unwrap().ws_url, meta.ws_url);
+        assert_eq!(replay_all(base.clone()), frames);
+        // Frame indexes in verify reports don't count the header
+        let report = crate::verify::verify_recording(&base, &Default::default()).unwrap();
+        let first_segment_lines = std::fs::read_to_string(&base).unwrap().lines().count();
+        assert_eq!(report.total_frames, first_segment_lines - 1);
+        
+        // Recordings without a header still replay
+        let plain = dir.join("plain.ndjson");
+        let mut recorder = Recorder::new(plain.clone()).unwrap();
+        recorder.record_frame(&frame(0), None).unwrap();
+        drop(recorder);
+        assert!(Replayer::new(plain.clone(), config).unwrap().metadata().is_none());
+        assert_eq!(replay_all(plain), vec![frame(0)]);
+        
+        // Newer schemas parse but aren't supported
+        let newer = r#"{"_meta":{"version":99,"symbols":["BTC/USD"],"compression":"zstd"}}"#;
+        let RecordingLine::Metadata(header) = parse_recording_line(newer).unwrap() else {
+            panic!("not a header");
+        };
+        assert!(!header.is_supported());
+        
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_killed_recorder_keeps_flushed_frames() {
+        let dir = std::env::temp_dir().join(format!("blackbox_recorder_flush_{}", std::process::id()));
+        let path = dir.join("killed.ndjson");
+        let options = RecorderOptions {
+            flush: FlushPolicy { every_frames: Some(10), interval: None },
+            ..Default::default()
+        };
+        let mut recorder = Recorder::new_with_options(path.clone(), options).unwrap();
+        let frames: Vec<String> = (0..95).map(frame).collect();
+        for f in &frames {
+            recorder.record_frame(f, None).unwrap();
+        }
+        // Process death: no close(), no Drop
+        std::mem::forget(recorder);
+        
+        // At most the unflushed tail is lost, possibly mid-line
+        let content = std::fs::read_to_string(&path).unwrap();
+        let complete = content.rfind('\n').map_or("", |end| &content[..end]);
+        let recovered: Vec<String> = complete
+            .lines()
+            .map(|line| serde_json::from_str::<RecordedFrame>(line).unwrap().raw_frame)
+            .collect();
+        assert!(recovered.len() >= 90);
+        assert_eq!(recovered[..], frames[..recovered.len()]);
+        
+        // A recorder that is dropped loses nothing
+        let options = RecorderOptions {
+            flush: FlushPolicy { every_frames: None, interval: None },
+            ..Default::default()
+        };
+        let mut recorder = Recorder::new_with_options(path.clone(), options).unwrap();
+        for f in &frames[..5] {
+            recorder.record_frame(f, None).unwrap();
+        }
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "");
+        recorder.flush().unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 5);
+        for f in &frames[5..] {
+            recorder.record_frame(f, None).unwrap();
+        }
+        drop(recorder);
+        assert_eq!(replay_all(path), frames);
+        
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_stats_across_rotation_and_flushes() {
+        let dir = std::env::temp_dir().join(format!("blackbox_recorder_stats_{}", std::process::id()));
+        let base = dir.join("stats.ndjson");
+        let options = RecorderOptions {
+            rotation: RotationPolicy { max_bytes: Some(1000), max_duration: None },
+            flush: FlushPolicy { every_frames: Some(7), interval: None },
+            metadata: Some(RecordingMetadata::new(vec!["BTC/USD".to_string()], 10)),
+            ..Default::default()
+        };
+        let mut recorder = Recorder::new_with_options(base.clone(), options).unwrap();
+        let initial = recorder.stats();
+        assert_eq!(initial.frames_written, 0);
+        assert!(initial.bytes_written > 0); // the header
+        assert_eq!(initial.last_frame_at, None);
+        
+        let ts = Utc::now();
+        for i in 0..60 {
+            recorder.record_frame_at(ts, &frame(i), None).unwrap();
+        }
+        // Counted when written, flushed or not
+        let stats = recorder.stats();
+        assert_eq!(stats.frames_written, 60);
+        assert_eq!(stats.last_frame_at, Some(ts));
+        assert_eq!(stats.started_at, initial.started_at);
+        recorder.close().unwrap();
+        
+        // Totals span every segment
+        let segments = recording_segments(&base).unwrap();
+        assert!(segments.len() > 2);
+        let on_disk: u64 = segments.iter().map(|s| std::fs::metadata(s).unwrap().len()).sum();
+        assert_eq!(stats.bytes_written, on_disk);
+        assert_eq!(recorder.stats(), stats);
+        
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_flush_policy() {
+        let policy = FlushPolicy { every_frames: Some(100), interval: Some(Duration::from_millis(50)) };
+        assert!(!policy.is_due(99, Duration::from_millis(49)));
+        assert!(policy.is_due(100, Duration::ZERO));
+        assert!(policy.is_due(1, Duration::from_millis(50)));
+        assert!(FlushPolicy::every_frame().is_due(1, Duration::ZERO));
+        assert!(!FlushPolicy { every_frames: None, interval: None }.is_due(u64::MAX, Duration::MAX));
+    }
+
+    #[test]
+    fn test_rotation_by_size() {
+        let dir = std::env::temp_dir().join(format!("blackbox_recorder_rotate_{}", std::process::id()));
+        let base = dir.join("session.ndjson");
+        let options = RecorderOptions {
+            rotation: RotationPolicy { max_bytes: Some(1000), max_duration: None },
+            ..Default::default()
+        };
+        let mut recorder = Recorder::new_with_options(base.clone(), options).unwrap();
+        let frames: Vec<String> = (0..100).map(frame).collect();
+        let mut started = vec![];
+        for f in &frames {
+            if let Some(path) = recorder.record_frame(f, None).unwrap() {
+                started.push(path);
+            }
+        }
+        recorder.close().unwrap();
+        
+        let segments = recording_segments(&base).unwrap();
+        assert!(segments.len() > 2);
+        assert_eq!(segments[0], base);
+        assert_eq!(segments[1], dir.join("session.0001.ndjson"));
+        assert_eq!(segments[1..], started[..]);
+        for segment in &segments {
+            // Under the limit, and every line a whole frame
+            let content = std::fs::read_to_string(segment).unwrap();
+            assert!(content.len() as u64 <= 1000);
+            assert!(content.ends_with('\n'));
+            for line in content.lines() {
+                serde_json::from_str::<RecordedFrame>(line).unwrap();
+            }
+        }
+        
+        // Replay across segments keeps the recorded order
+        assert_eq!(replay_all(base.clone()), frames);
+        assert_eq!(replay_all(dir.clone()), frames);
+        assert_eq!(replay_all(dir.join("session*")), frames);
+        
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_rotation_by_duration() {
+        let dir = std::env::temp_dir().join(format!("blackbox_recorder_rotate_every_{}", std::process::id()));
+        let base = dir.join("timed.ndjson.gz");
+        let options = RecorderOptions {
+            rotation: RotationPolicy { max_bytes: None, max_duration: Some(Duration::ZERO) },
+            ..Default::default()
+        };
+        let mut recorder = Recorder::new_with_options(base.clone(), options).unwrap();
+        assert_eq!(recorder.record_frame(&frame(0), None).unwrap(), None);
+        for i in 1..12 {
+            let path = recorder.record_frame(&frame(i), None).unwrap().unwrap();
+            assert_eq!(&path, recorder.path());
+        }
+        drop(recorder);
+        
+        // One frame per segment, numbered past 9 in order
+        let segments = recording_segments(&base).unwrap();
+        assert_eq!(segments.len(), 12);
+        assert_eq!(segments[11], dir.join("timed.0011.ndjson.gz"));
+        assert_eq!(replay_all(base), (0..12).map(frame).collect::<Vec<_>>());
+        
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_segment_names() {
+        let base = Path::new("rec/session.ndjson.gz");
+        assert_eq!(segment_path(base, 1), Path::new("rec/session.0001.ndjson.gz"));
+        assert_eq!(segment_path(&segment_path(base, 1), 2), Path::new("rec/session.0002.ndjson.gz"));
+        assert_eq!(segment_path(Path::new("capture"), 12), Path::new("capture.0012"));
+        assert_eq!(split_segment_name("recording_20260101_120000.ndjson"), ("recording_20260101_120000", 0, "ndjson"));
+        assert_eq!(split_segment_name("a.12345.ndjson"), ("a", 12345, "ndjson"));
+        
+        assert!(wildcard_match("session*", "session.0001.ndjson"));
+        assert!(wildcard_match("*.ndjson", "a.ndjson"));
+        assert!(wildcard_match("s*.0*.ndjson", "session.0001.ndjson"));
+        assert!(!wildcard_match("*.ndjson", "a.ndjson.gz"));
+        assert!(!wildcard_match("other*", "session.ndjson"));
+        
+        assert!(recording_segments(Path::new("does/not/exist.ndjson")).is_err());
+    }
+
+    #[test]
+    fn test_gzip_round_trip() {
+        let dir = std::env::temp_dir().join(format!("blackbox_recorder_gzip_{}", std::process::id()));
+        let plain_path = dir.join("session.ndjson");
+        let gzip_path = dir.join("session.ndjson.gz");
+        // Requested explicitly, so the extension doesn't matter
+        let forced_path = dir.join("forced.ndjson");
+        
+        let frames: Vec<String> = (0..1000)
+            .map(|i| format!(r#"{{"channel":"book","type":"update","data":[{{"symbol":"BTC/USD","seq":{}}}]}}"#, i))
+            .collect();
+        let mut plain = Recorder::new(plain_path.clone()).unwrap();
+        let mut gzip = Recorder::new(gzip_path.clone()).unwrap();
+        let options = RecorderOptions { compression: Compression::Gzip, ..Default::default() };
+        let mut forced = Recorder::new_with_options(forced_path.clone(), options).unwrap();
+        for frame in &frames {
+            plain.record_frame(frame, None).unwrap();
+            gzip.record_frame(frame, None).unwrap();
+            forced.record_frame(frame, None).unwrap();
+        }
+        plain.close().unwrap();
+        gzip.close().unwrap();
+        drop(forced);
+        
+        assert!(std::fs::read(&gzip_path).unwrap().starts_with(&GZIP_MAGIC));
+        assert!(std::fs::read(&forced_path).unwrap().starts_with(&GZIP_MAGIC));
+        assert!(!std::fs::read(&plain_path).unwrap().starts_with(&GZIP_MAGIC));
+        assert!(std::fs::metadata(&gzip_path).unwrap().len() < std::fs::metadata(&plain_path).unwrap().len());
+        
+        let replayed = replay_all(plain_path);
+        assert_eq!(replayed, frames);
+        assert_eq!(replay_all(gzip_path), replayed);
+        assert_eq!(replay_all(forced_path), replayed);
+        
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
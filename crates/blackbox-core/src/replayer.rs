@@ -1,185 +1,544 @@
-use crate::types::{FaultRule, FaultType, RecordedFrame, ReplayConfig, ReplayMode};
+use crate::recorder::{
+    parse_recording_line, read_recording, recording_segments, split_segment_name, RecordingLine, RecordingReader,
+    CONTROL_STEM,
+};
+use crate::types::{
+    FaultType, InstrumentInfo, InstrumentMessage, RecordingMetadata, ReplayConfig, ReplayMode,
+    DEFAULT_PRICE_INCREMENT,
+};
 use chrono::{DateTime, Utc};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use serde::Deserialize;
 use serde_json;
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
 use std::fs::File;
-use std::io::{BufRead, BufReader};
-use std::path::PathBuf;
-use std::time::Instant;
+use std::io::{BufRead, Read};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tracing::warn;
 
+/// Frame returned between passes with `ReplayConfig::loop_playback`. Books
+/// built from the previous pass should be dropped when it arrives: the next
+/// frames are the recording's first again, snapshots included.
+pub const LOOP_MARKER: &str = r#"{"channel":"blackbox","type":"replay_loop"}"#;
+/// Tag of `LOOP_MARKER` as returned by `next_classified`
+pub const LOOP_TAG: &str = "replay.loop";
+
+/// Events held for `Replayer::drain_events`
+const MAX_PENDING_EVENTS: usize = 1024;
+
+/// Something the replayer did to the recording's frames
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReplayEvent {
+    FaultApplied {
+        rule: usize, // index in `ReplayConfig::faults`
+        fault: FaultType,
+        symbol: String,
+        frame_index: usize,
+        update_index: usize, // the symbol's book update count, from 1
+    },
+}
+
+impl std::fmt::Display for ReplayEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReplayEvent::FaultApplied { rule, fault, symbol, frame_index, update_index } => write!(
+                f,
+                "{:?} on frame {} (book update #{}) for {}, rule #{}",
+                fault, frame_index, update_index, symbol, rule
+            ),
+        }
+    }
+}
+
+/// Plays a recording back frame by frame. Frames are read from disk as they
+/// are needed, so memory use doesn't grow with the recording's size: besides
+/// the readers' buffers, only the next frame (and reordered or delayed ones)
+/// is held.
 pub struct Replayer {
-    frames: Vec<(DateTime<Utc>, String)>,
-    current_index: usize,
+    source: FrameSource,
+    streams: Vec<Vec<PathBuf>>, // files of each stream, to reopen on a backward seek
+    total_bytes: u64, // on-disk size of every file replayed
+    head: Option<ReplayFrame>, // next frame, read ahead for pacing and is_done()
+    last_tag: Option<String>, // recorded decoded_event of the last next_frame() result
+    current_index: usize, // index of `head` in the recording
+    last_ts: Option<DateTime<Utc>>, // timestamp of the last frame taken
+    end: Option<DateTime<Utc>>, // frames after this aren't played
+    gate: Option<SnapshotGate>, // set by seek_to
     start_time: Option<Instant>,
     first_frame_time: Option<DateTime<Utc>>,
+    paused_at: Option<Instant>,
+    paused_for: Duration, // time spent paused since start_time, not counted by pacing
+    step: bool, // release one frame on the next next_frame() call
+    current_ts: Option<DateTime<Utc>>, // timestamp of the last frame returned
     config: ReplayConfig,
     book_update_count: HashMap<String, usize>,
-    fault_applied: bool,
-    next_frame_buffer: Option<String>,
+    price_increments: HashMap<String, Decimal>, // from the recorded instrument snapshots
+    default_price_increment: Decimal,
+    next_frame_buffer: Option<ReplayFrame>,
+    delayed: VecDeque<(DateTime<Utc>, ReplayFrame)>, // frames held by FaultType::Delay, by release time
+    events: VecDeque<ReplayEvent>,
+    faults_applied: usize, // every ReplayEvent::FaultApplied so far, drained or not
+    error: Option<anyhow::Error>, // why playback ended early, for frame_stream
+    metadata: Option<RecordingMetadata>,
 }
 
 impl Replayer {
     pub fn new(path: PathBuf, config: ReplayConfig) -> anyhow::Result<Self> {
-        let file = File::open(&path)?;
-        let reader = BufReader::new(file);
-        
-        let mut frames = Vec::new();
-        for line in reader.lines() {
-            let line = line?;
-            if line.trim().is_empty() {
-                continue;
+        Self::with_streams(vec![vec![path]], config)
+    }
+
+    /// Replay a rotated recording: a directory, a `*` pattern, or the first
+    /// file of a recording (see `recorder::recording_segments`). A directory
+    /// written by `SymbolRecorder` is replayed with `merged`.
+    pub fn from_segments(path: &Path, config: ReplayConfig) -> anyhow::Result<Self> {
+        if is_per_symbol_dir(path) {
+            return Self::merged(path, config);
+        }
+        Self::with_streams(vec![recording_segments(path)?], config)
+    }
+
+    /// Replay a per-symbol recording directory (see `SymbolRecorder`),
+    /// interleaving every file's frames by timestamp. Each file is expected in
+    /// time order, as the recorder writes it. Frames with equal timestamps
+    /// keep file order (control file first, then by name) and line order.
+    pub fn merged(dir: &Path, config: ReplayConfig) -> anyhow::Result<Self> {
+        // Files grouped per stream, each stream's segments in order
+        let mut streams: Vec<(String, Vec<PathBuf>)> = Vec::new();
+        for segment in recording_segments(dir)? {
+            let name = segment.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+            let stem = split_segment_name(&name).0.to_string();
+            match streams.last_mut() {
+                Some((last, files)) if *last == stem => files.push(segment),
+                _ => streams.push((stem, vec![segment])),
             }
-            
-            let frame: RecordedFrame = serde_json::from_str(&line)?;
-            frames.push((frame.ts, frame.raw_frame));
         }
-        
+        streams.sort_by_key(|(stem, _)| stem != CONTROL_STEM);
+        Self::with_streams(streams.into_iter().map(|(_, files)| files).collect(), config)
+    }
+
+    fn with_streams(streams: Vec<Vec<PathBuf>>, config: ReplayConfig) -> anyhow::Result<Self> {
+        let mut source = FrameSource::open(&streams)?;
+        let head = source.next();
         Ok(Self {
-            frames,
+            metadata: source.metadata().cloned(),
+            total_bytes: source.total_bytes(),
+            source,
+            streams,
+            head,
+            last_tag: None,
             current_index: 0,
+            last_ts: None,
+            end: None,
+            gate: None,
             start_time: None,
             first_frame_time: None,
+            paused_at: None,
+            paused_for: Duration::ZERO,
+            step: false,
+            current_ts: None,
             config,
             book_update_count: HashMap::new(),
-            fault_applied: false,
+            price_increments: HashMap::new(),
+            default_price_increment: DEFAULT_PRICE_INCREMENT,
             next_frame_buffer: None,
+            delayed: VecDeque::new(),
+            events: VecDeque::new(),
+            faults_applied: 0,
+            error: None,
         })
     }
 
+    /// Header of the recording (its first segment's); None for recordings without one
+    pub fn metadata(&self) -> Option<&RecordingMetadata> {
+        self.metadata.as_ref()
+    }
+
     pub fn start(&mut self) {
-        self.start_time = Some(Instant::now());
-        if let Some((first_ts, _)) = self.frames.first() {
-            self.first_frame_time = Some(*first_ts);
+        let now = Instant::now();
+        self.start_time = Some(now);
+        self.paused_for = Duration::ZERO;
+        if self.paused_at.is_some() {
+            self.paused_at = Some(now);
+        }
+        if let Some(first) = &self.head {
+            self.first_frame_time = Some(first.ts);
         }
     }
 
-    pub fn next_frame(&mut self) -> Option<String> {
-        // Check if we have a buffered frame (from reorder fault)
-        if let Some(buffered) = self.next_frame_buffer.take() {
-            return Some(buffered);
+    /// Stop returning frames. The replay clock stops too, so `resume`
+    /// carries on where playback was rather than catching up on the pause.
+    pub fn pause(&mut self) {
+        self.paused_at.get_or_insert_with(Instant::now);
+    }
+
+    pub fn resume(&mut self) {
+        if let Some(paused_at) = self.paused_at.take() {
+            self.paused_for += paused_at.elapsed();
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused_at.is_some()
+    }
+
+    /// Have the next `next_frame` call return exactly one frame, whatever the
+    /// replay mode's pacing and even while paused. Pacing then resumes
+    /// relative to that frame.
+    pub fn step(&mut self) {
+        self.step = true;
+    }
+
+    /// Tick for `FaultType::MutatePrice` on symbols the recording has no
+    /// instrument snapshot for (default `DEFAULT_PRICE_INCREMENT`)
+    pub fn set_default_price_increment(&mut self, increment: Decimal) {
+        self.default_price_increment = increment;
+    }
+
+    pub fn mode(&self) -> &ReplayMode {
+        &self.config.mode
+    }
+
+    /// Change the pacing mid-replay. The gap from the last frame played to
+    /// the next one is paced at the new speed, not caught up on.
+    pub fn set_mode(&mut self, mode: ReplayMode) {
+        self.config.mode = mode;
+        if self.start_time.is_some() {
+            self.start();
+            if self.current_ts.is_some() {
+                self.first_frame_time = self.current_ts;
+            }
         }
+    }
 
-        if self.current_index >= self.frames.len() {
+    /// Faults applied since the replay began, across loops and seeks
+    pub fn faults_applied(&self) -> usize {
+        self.faults_applied
+    }
+
+    /// Timestamp of the frame `next_frame` last returned
+    pub fn current_ts(&self) -> Option<DateTime<Utc>> {
+        self.current_ts
+    }
+
+    /// Position playback at the first frame at or after `ts`, reopening the
+    /// recording if that's behind the frames already played. Returns how many
+    /// frames were skipped from the start of the recording or the current
+    /// position. When looping, a `ts` past the end stops at the end of the
+    /// pass, and playback carries on with the next one.
+    ///
+    /// Book updates mean nothing without the snapshot before them, so from
+    /// here on each symbol's updates are skipped until its next snapshot;
+    /// `symbols_without_snapshot` lists the symbols still waiting for one.
+    /// Recordings carry no checkpoints, so resubscribing is the only snapshot.
+    pub fn seek_to(&mut self, ts: DateTime<Utc>) -> anyhow::Result<usize> {
+        // A reordered frame was taken ahead of the head: a discontinuity either way
+        self.next_frame_buffer = None;
+        self.delayed.clear();
+        if self.last_ts.is_some_and(|last| ts <= last) {
+            self.rewind()?;
+        }
+
+        // Symbols with a book before the seek point, as well as those subscribed
+        let mut symbols: BTreeSet<String> =
+            self.metadata.iter().flat_map(|meta| meta.symbols.iter().cloned()).collect();
+        let mut skipped = 0;
+        while self.head.as_ref().is_some_and(|frame| frame.ts < ts) {
+            let Some(frame) = self.advance_within_pass() else {
+                break;
+            };
+            if let Some((symbol, _)) = book_frame_symbol(&frame) {
+                symbols.insert(symbol);
+            }
+            skipped += 1;
+        }
+        self.gate = Some(SnapshotGate { ready: HashSet::new(), waiting: symbols });
+
+        // Pacing restarts from the new position
+        if self.start_time.is_some() {
+            self.start();
+        }
+        Ok(skipped)
+    }
+
+    /// Stop playback after the last frame at or before `end`. Meant to be set
+    /// before playing: frames already dropped by an earlier end come back
+    /// only after a backward seek.
+    pub fn set_end(&mut self, end: DateTime<Utc>) {
+        self.end = Some(end);
+        if self.head.as_ref().is_some_and(|frame| frame.ts > end) {
+            self.head = None;
+        }
+    }
+
+    /// Symbols whose books got no snapshot since the last `seek_to`, sorted;
+    /// their updates were skipped. Empty if playback never seeked.
+    pub fn symbols_without_snapshot(&self) -> Vec<String> {
+        self.gate.as_ref().map_or_else(Vec::new, |gate| gate.waiting.iter().cloned().collect())
+    }
+
+    /// Reopen the recording at its first frame, as if nothing was played
+    fn rewind(&mut self) -> anyhow::Result<()> {
+        self.source = FrameSource::open(&self.streams)?;
+        self.head = self.read_next();
+        self.current_index = 0;
+        self.last_ts = None;
+        self.book_update_count.clear();
+        Ok(())
+    }
+
+    fn read_next(&mut self) -> Option<ReplayFrame> {
+        self.source.next().filter(|next| self.end.is_none_or(|end| next.ts <= end))
+    }
+
+    /// Take the next frame, reading the one after it. When looping, the last
+    /// frame is followed by `LOOP_MARKER`, and taking that starts over.
+    fn advance(&mut self) -> Option<ReplayFrame> {
+        let frame = self.head.take()?;
+        if frame.is_loop_marker() {
+            if let Err(e) = self.rewind() {
+                warn!("Stopped looping: {}", e);
+                self.error = Some(e.context("Stopped looping"));
+            }
+            // Every pass starts at the top: clock and snapshots alike
+            self.gate = None;
+            if self.start_time.is_some() {
+                self.start();
+            }
+            return Some(frame);
+        }
+        self.head = self.read_next();
+        if self.head.is_none() && self.config.loop_playback {
+            self.head = Some(ReplayFrame {
+                ts: frame.ts,
+                raw_frame: LOOP_MARKER.to_string(),
+                tag: Some(LOOP_TAG.to_string()),
+                bytes: 0,
+            });
+        }
+        self.current_index += 1;
+        self.last_ts = Some(frame.ts);
+        Some(frame)
+    }
+
+    /// `advance`, but never across the end of a loop pass
+    fn advance_within_pass(&mut self) -> Option<ReplayFrame> {
+        if self.head.as_ref().is_some_and(ReplayFrame::is_loop_marker) {
             return None;
         }
+        self.advance()
+    }
+
+    /// How long until the replay mode's pacing reaches a frame recorded at `frame_ts`
+    fn wait_for(&self, frame_ts: DateTime<Utc>) -> Duration {
+        let (Some(start), Some(first_ts)) = (self.start_time, self.first_frame_time) else {
+            return Duration::ZERO;
+        };
+        let elapsed = start.elapsed().saturating_sub(self.paused_for);
+        let frame_offset = (frame_ts - first_ts).to_std().unwrap_or_default();
         
-        let (frame_ts, mut frame_data) = self.frames[self.current_index].clone();
-        
-        // Check if we should wait based on replay mode
-        if let Some(start) = self.start_time {
-            if let Some(first_ts) = self.first_frame_time {
-                let elapsed = start.elapsed();
-                let frame_offset = (frame_ts - first_ts).to_std().unwrap_or_default();
-                
-                match self.config.mode {
-                    ReplayMode::Realtime => {
-                        let target_elapsed = frame_offset;
-                        if elapsed < target_elapsed {
-                            // Need to wait
-                            return None;
-                        }
-                    }
-                    ReplayMode::Speed(speed) => {
-                        let frame_secs = frame_offset.as_secs_f64();
-                        let target_secs = frame_secs / speed;
-                        let target_elapsed = std::time::Duration::from_secs_f64(target_secs);
-                        if elapsed < target_elapsed {
-                            return None;
-                        }
-                    }
-                    ReplayMode::AsFast => {
-                        // No waiting
-                    }
-                }
+        let target = match self.config.mode {
+            ReplayMode::Realtime => frame_offset,
+            ReplayMode::Speed(speed) => Duration::from_secs_f64(frame_offset.as_secs_f64() / speed),
+            ReplayMode::AsFast => Duration::ZERO,
+        };
+        target.saturating_sub(elapsed)
+    }
+
+    fn is_due(&self, frame_ts: DateTime<Utc>) -> bool {
+        self.wait_for(frame_ts).is_zero()
+    }
+
+    /// Release time of the first delayed frame, if it plays before the head:
+    /// delayed frames all go out before a pass ends
+    fn delayed_release(&self) -> Option<DateTime<Utc>> {
+        let &(release, _) = self.delayed.front()?;
+        let head_first = self.head.as_ref().is_some_and(|head| !head.is_loop_marker() && head.ts < release);
+        (!head_first).then_some(release)
+    }
+
+    /// How long until `next_frame` has a frame for its caller to sleep for:
+    /// zero if one is due now. None once playback is done, and while paused
+    /// until `step` or `resume`. A frame that's due may still be skipped by
+    /// a fault or a seek, so a None from `next_frame` means asking again.
+    pub fn next_frame_delay(&self) -> Option<Duration> {
+        if self.is_done() || (self.is_paused() && !self.step) {
+            return None;
+        }
+        if self.step || self.next_frame_buffer.is_some() {
+            return Some(Duration::ZERO);
+        }
+        let ts = self.delayed_release().or(self.head.as_ref().map(|head| head.ts))?;
+        Some(self.wait_for(ts))
+    }
+
+    pub fn next_frame(&mut self) -> Option<String> {
+        if std::mem::take(&mut self.step) {
+            let frame = self.take_frame(false);
+            // The gap before the stepped frame isn't caught up on either
+            if frame.is_some() && self.start_time.is_some() {
+                self.start();
+                self.first_frame_time = self.current_ts;
             }
+            return frame;
         }
+        if self.is_paused() {
+            return None;
+        }
+        self.take_frame(true)
+    }
+
+    fn take_frame(&mut self, paced: bool) -> Option<String> {
+        // Check if we have a buffered frame (from reorder fault)
+        if let Some(buffered) = self.next_frame_buffer.take() {
+            self.last_tag = buffered.tag;
+            self.current_ts = Some(buffered.ts);
+            return Some(buffered.raw_frame);
+        }
+
+        loop {
+            // Delayed frames go out once playback reaches their release time
+            if let Some(release) = self.delayed_release() {
+                if paced && !self.is_due(release) {
+                    return None;
+                }
+                let (_, frame) = self.delayed.pop_front()?;
+                self.last_tag = frame.tag;
+                self.current_ts = Some(frame.ts);
+                return Some(frame.raw_frame);
+            }
+
+            let frame_ts = self.head.as_ref()?.ts;
+            if paced && !self.is_due(frame_ts) {
+                return None;
+            }
+            
+            // Check if this is a book update frame and apply fault injection if needed
+            let frame_index = self.current_index;
+            let mut frame = self.advance()?;
+            // After a seek, a book's updates are skipped until its snapshot
+            if let (Some(gate), Some((symbol, snapshot))) = (&mut self.gate, book_frame_symbol(&frame)) {
+                if !gate.admit(symbol, snapshot) {
+                    continue;
+                }
+            }
+            let mut should_skip = false;
         
-        // Check if this is a book update frame and apply fault injection if needed
-        let frame_index = self.current_index;
-        let mut should_skip = false;
-        
-        if let Ok(mut json_value) = serde_json::from_str::<serde_json::Value>(&frame_data) {
-            if let Some(channel) = json_value.get("channel").and_then(|c| c.as_str()) {
-                if channel == "book" {
-                    if let Some(data_array) = json_value.get("data").and_then(|d| d.as_array()) {
-                        if let Some(book_data) = data_array.first() {
-                            if let Some(symbol) = book_data.get("symbol").and_then(|s| s.as_str()) {
-                                let count = self.book_update_count.entry(symbol.to_string()).or_insert(0);
-                                *count += 1;
-                                let update_index = *count;
+            if let Ok(json_value) = serde_json::from_str::<serde_json::Value>(&frame.raw_frame) {
+                if let Some(channel) = json_value.get("channel").and_then(|c| c.as_str()) {
+                    if channel == "instrument" {
+                        self.learn_price_increments(&json_value);
+                    }
+                    if channel == "book" {
+                        if let Some(data_array) = json_value.get("data").and_then(|d| d.as_array()) {
+                            if let Some(book_data) = data_array.first() {
+                                if let Some(symbol) = book_data.get("symbol").and_then(|s| s.as_str()) {
+                                    let count = self.book_update_count.entry(symbol.to_string()).or_insert(0);
+                                    *count += 1;
+                                    let update_index = *count;
                                 
-                                // Apply fault rule
-                                match &self.config.fault {
-                                    FaultRule::Every { n, fault } => {
-                                        if update_index % n == 0 {
-                                            match fault {
-                                                FaultType::Drop => {
-                                                    warn!("Fault injection: Dropping frame {} (book update #{}) for {}", frame_index, update_index, symbol);
-                                                    should_skip = true;
-                                                }
-                                                FaultType::Reorder => {
-                                                    if self.current_index + 1 < self.frames.len() {
-                                                        warn!("Fault injection: Reordering frame {} with next (book update #{}) for {}", frame_index, update_index, symbol);
-                                                        let next_frame = self.frames[self.current_index + 1].1.clone();
-                                                        self.next_frame_buffer = Some(frame_data.clone());
-                                                        frame_data = next_frame;
-                                                        self.current_index += 1; // Skip next frame
-                                                    }
-                                                }
-                                                FaultType::MutateQty { delta_ticks } => {
-                                                    let mut json_val = json_value.clone();
-                                                    if let Some(mutated) = self.mutate_qty(&mut json_val, *delta_ticks) {
-                                                        warn!("Fault injection: Mutating qty in frame {} (book update #{}) for {}", frame_index, update_index, symbol);
-                                                        frame_data = mutated;
-                                                    }
-                                                }
-                                            }
+                                    // Apply fault rules
+                                    let mut faults: Vec<(usize, FaultType)> = self.config.faults.iter()
+                                        .enumerate()
+                                        .filter_map(|(rule, faults)| Some((rule, faults.fault_at(update_index)?.clone())))
+                                        .collect();
+                                    faults.sort_by_key(|(_, fault)| fault_precedence(fault));
+                                    let mut moved = false;
+                                    for (rule, fault) in faults {
+                                        // Of the faults moving the frame, only the first applies
+                                        if moves_frame(&fault) && std::mem::replace(&mut moved, true) {
+                                            continue;
                                         }
-                                    }
-                                    FaultRule::OnceAt { index, fault } => {
-                                        if update_index == *index {
-                                            match fault {
-                                                FaultType::Drop => {
-                                                    warn!("Fault injection: Dropping frame {} (book update #{}) for {}", frame_index, update_index, symbol);
-                                                    should_skip = true;
-                                                }
-                                                FaultType::Reorder => {
-                                                    if self.current_index + 1 < self.frames.len() {
-                                                        warn!("Fault injection: Reordering frame {} with next (book update #{}) for {}", frame_index, update_index, symbol);
-                                                        let next_frame = self.frames[self.current_index + 1].1.clone();
-                                                        self.next_frame_buffer = Some(frame_data.clone());
-                                                        frame_data = next_frame;
-                                                        self.current_index += 1; // Skip next frame
-                                                    }
-                                                }
-                                                FaultType::MutateQty { delta_ticks } => {
-                                                    let mut json_val = json_value.clone();
-                                                    if let Some(mutated) = self.mutate_qty(&mut json_val, *delta_ticks) {
-                                                        warn!("Fault injection: Mutating qty in frame {} (book update #{}) for {}", frame_index, update_index, symbol);
-                                                        frame_data = mutated;
-                                                    }
-                                                }
-                                            }
+                                        let Some(play) = self.apply_fault(&fault, &mut frame) else {
+                                            continue;
+                                        };
+                                        let event = ReplayEvent::FaultApplied {
+                                            rule,
+                                            fault,
+                                            symbol: symbol.to_string(),
+                                            frame_index,
+                                            update_index,
+                                        };
+                                        warn!("Fault injection: {}", event);
+                                        self.push_event(event);
+                                        if !play {
+                                            should_skip = true;
+                                            break;
                                         }
                                     }
-                                    FaultRule::None => {}
                                 }
                             }
                         }
                     }
                 }
             }
-        }
-        
-        self.current_index += 1;
         
-        if should_skip {
-            // Recursively call to get next frame
-            return self.next_frame();
+            if should_skip {
+                continue;
+            }
+            
+            self.last_tag = frame.tag;
+            self.current_ts = Some(frame.ts);
+            return Some(frame.raw_frame);
         }
-        
-        Some(frame_data)
+    }
+
+    /// Apply `fault` to a book frame. None if it didn't apply (nothing to
+    /// mutate, no frame to swap with), else whether to still play the frame
+    /// now: false once it was dropped or held back.
+    fn apply_fault(&mut self, fault: &FaultType, frame: &mut ReplayFrame) -> Option<bool> {
+        // Parsed again, so faults on the same frame build on each other
+        let mut json: serde_json::Value = serde_json::from_str(&frame.raw_frame).ok()?;
+        match fault {
+            FaultType::Drop => return Some(false),
+            FaultType::Reorder => {
+                let next = self.advance_within_pass()?;
+                self.next_frame_buffer = Some(std::mem::replace(frame, next));
+            }
+            FaultType::MutateQty { delta_ticks } => frame.raw_frame = self.mutate_qty(&mut json, *delta_ticks)?,
+            FaultType::MutatePrice { delta_ticks } => {
+                let symbol = json["data"][0]["symbol"].as_str()?.to_string();
+                frame.raw_frame = self.mutate_price(&mut json, *delta_ticks, &symbol)?;
+            }
+            FaultType::Duplicate => self.next_frame_buffer = Some(ReplayFrame { bytes: 0, ..frame.clone() }),
+            FaultType::CorruptChecksum { xor } => frame.raw_frame = corrupt_checksum(&mut json, *xor)?,
+            FaultType::Delay { ms } => {
+                let release = frame.ts + chrono::Duration::milliseconds(*ms as i64);
+                let held = ReplayFrame { bytes: 0, ..frame.clone() };
+                let at = self.delayed.partition_point(|(other, _)| *other <= release);
+                self.delayed.insert(at, (release, held));
+                return Some(false);
+            }
+        }
+        Some(true)
+    }
+
+    fn push_event(&mut self, event: ReplayEvent) {
+        match event {
+            ReplayEvent::FaultApplied { .. } => self.faults_applied += 1,
+        }
+        if self.events.len() == MAX_PENDING_EVENTS {
+            self.events.pop_front();
+        }
+        self.events.push_back(event);
+    }
+
+    /// Events since the last call, oldest first. Only the latest
+    /// `MAX_PENDING_EVENTS` are kept for callers that don't drain them.
+    pub fn drain_events(&mut self) -> impl Iterator<Item = ReplayEvent> + '_ {
+        self.events.drain(..)
+    }
+
+    /// Like `next_frame`, paired with the frame's recorded tag
+    /// (`RecordedFrame::decoded_event`, e.g. `book.update:BTC/USD`) so callers
+    /// can filter without parsing. The tag is None if it wasn't recorded.
+    pub fn next_classified(&mut self) -> Option<(Option<String>, String)> {
+        let frame = self.next_frame()?;
+        Some((self.last_tag.clone(), frame))
     }
     
     fn mutate_qty(&self, json: &mut serde_json::Value, delta_ticks: i32) -> Option<String> {
@@ -231,15 +590,961 @@ impl Replayer {
         None
     }
 
+    /// Shift the first ask price (or bid, with no asks) by `delta_ticks` of
+    /// the symbol's price increment
+    fn mutate_price(&self, json: &mut serde_json::Value, delta_ticks: i32, symbol: &str) -> Option<String> {
+        let increment = self.price_increments.get(symbol).copied().unwrap_or(self.default_price_increment);
+        let book_data = json.get_mut("data")?.as_array_mut()?.first_mut()?;
+        let side = ["asks", "bids"]
+            .into_iter()
+            .find(|side| book_data.get(side).and_then(|levels| levels.as_array()).is_some_and(|levels| !levels.is_empty()))?;
+        let price = book_data.get_mut(side)?.get_mut(0)?.get_mut("price")?;
+        let shift = increment * Decimal::from(delta_ticks);
+        *price = match price {
+            serde_json::Value::String(s) => serde_json::Value::String((s.parse::<Decimal>().ok()? + shift).to_string()),
+            serde_json::Value::Number(n) => {
+                let shifted = n.to_string().parse::<Decimal>().ok()? + shift;
+                serde_json::Value::Number(serde_json::Number::from_f64(shifted.to_f64()?)?)
+            }
+            _ => return None,
+        };
+        serde_json::to_string(json).ok()
+    }
+
+    fn learn_price_increments(&mut self, json: &serde_json::Value) {
+        let Ok(msg) = serde_json::from_value::<InstrumentMessage>(json.clone()) else {
+            return;
+        };
+        for pair in msg.data.pairs {
+            if let Ok(instrument) = InstrumentInfo::from_pair(pair) {
+                self.price_increments.insert(instrument.symbol, instrument.price_increment);
+            }
+        }
+    }
+
     pub fn is_done(&self) -> bool {
-        self.current_index >= self.frames.len()
+        self.head.is_none() && self.next_frame_buffer.is_none() && self.delayed.is_empty()
     }
 
+    /// Share of the recording's bytes played so far. For gzipped files this
+    /// follows the decoder's position in the compressed data.
     pub fn progress(&self) -> f64 {
-        if self.frames.is_empty() {
+        if self.is_done() || self.total_bytes == 0 {
             return 1.0;
         }
-        self.current_index as f64 / self.frames.len() as f64
+        let unplayed = self.head.as_ref().map_or(0, |frame| frame.bytes);
+        let played = self.source.position().saturating_sub(unplayed);
+        (played as f64 / self.total_bytes as f64).min(1.0)
+    }
+
+    /// Bytes of frame data held in memory: read-ahead frames and line
+    /// buffers, not the readers' fixed-size buffers
+    pub fn buffered_bytes(&self) -> usize {
+        let delayed = self.delayed.iter().map(|(_, frame)| frame);
+        let frames = self.head.iter().chain(self.next_frame_buffer.iter()).chain(delayed);
+        frames.map(ReplayFrame::heap_size).sum::<usize>() + self.source.buffered_bytes()
+    }
+}
+
+/// Longest `frame_stream` sleeps before asking its source again, so pausing,
+/// seeking or a gap in the recording don't leave it waiting on a stale delay
+#[cfg(feature = "stream")]
+const MAX_STREAM_WAIT: Duration = Duration::from_millis(50);
+
+/// A replayer for `frame_stream`: owned, or shared with whatever else
+/// controls it (pause, seek) and reads its progress
+#[cfg(feature = "stream")]
+pub trait ReplaySource {
+    /// Run `f` on the replayer; None if there's none
+    fn with_replayer<T>(&mut self, f: impl FnOnce(&mut Replayer) -> T) -> Option<T>;
+}
+
+#[cfg(feature = "stream")]
+impl ReplaySource for Replayer {
+    fn with_replayer<T>(&mut self, f: impl FnOnce(&mut Replayer) -> T) -> Option<T> {
+        Some(f(self))
+    }
+}
+
+#[cfg(feature = "stream")]
+impl ReplaySource for Arc<std::sync::Mutex<Option<Replayer>>> {
+    fn with_replayer<T>(&mut self, f: impl FnOnce(&mut Replayer) -> T) -> Option<T> {
+        // A panic elsewhere doesn't leave the replayer half-updated
+        let mut replayer = self.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        replayer.as_mut().map(f)
+    }
+}
+
+#[cfg(feature = "stream")]
+enum StreamStep {
+    Frame(DateTime<Utc>, String),
+    Wait(Duration),
+    Failed(anyhow::Error),
+    Done,
+}
+
+#[cfg(feature = "stream")]
+impl Replayer {
+    /// Every frame `next_frame` would return, with its recorded timestamp,
+    /// as the replay mode's pacing releases it. Starts the clock if `start`
+    /// wasn't called. Ends with the recording, or with an error if looping
+    /// playback couldn't start over.
+    pub fn into_stream(self) -> impl futures_util::Stream<Item = anyhow::Result<(DateTime<Utc>, String)>> {
+        frame_stream(self)
+    }
+}
+
+/// `Replayer::into_stream` for any `ReplaySource`. A shared replayer that's
+/// paused keeps the stream waiting; one taken away from the source ends it.
+#[cfg(feature = "stream")]
+pub fn frame_stream(
+    source: impl ReplaySource,
+) -> impl futures_util::Stream<Item = anyhow::Result<(DateTime<Utc>, String)>> {
+    futures_util::stream::unfold(Some(source), |source| async move {
+        let mut source = source?;
+        loop {
+            let step = source.with_replayer(|replayer| {
+                if replayer.start_time.is_none() {
+                    replayer.start();
+                }
+                if let Some(frame) = replayer.next_frame() {
+                    return StreamStep::Frame(replayer.current_ts.unwrap_or_default(), frame);
+                }
+                if let Some(e) = replayer.error.take() {
+                    return StreamStep::Failed(e);
+                }
+                if replayer.is_done() {
+                    return StreamStep::Done;
+                }
+                StreamStep::Wait(replayer.next_frame_delay().map_or(MAX_STREAM_WAIT, |wait| wait.min(MAX_STREAM_WAIT)))
+            })?;
+            match step {
+                StreamStep::Frame(ts, frame) => return Some((Ok((ts, frame)), Some(source))),
+                StreamStep::Wait(wait) => tokio::time::sleep(wait).await,
+                StreamStep::Failed(e) => return Some((Err(e), None)),
+                StreamStep::Done => return None,
+            }
+        }
+    })
+}
+
+/// A recorded frame read from a file
+#[derive(Clone)]
+struct ReplayFrame {
+    ts: DateTime<Utc>,
+    raw_frame: String,
+    tag: Option<String>,
+    bytes: u64, // length of the line in an uncompressed file, else 0
+}
+
+impl ReplayFrame {
+    fn is_loop_marker(&self) -> bool {
+        self.tag.as_deref() == Some(LOOP_TAG) && self.bytes == 0 && self.raw_frame == LOOP_MARKER
     }
+
+    fn heap_size(&self) -> usize {
+        self.raw_frame.capacity() + self.tag.as_ref().map_or(0, String::capacity)
+    }
+}
+
+/// Order faults on the same frame apply in: a dropped frame isn't mutated
+/// first, and the frame is moved only once it has all its mutations
+fn fault_precedence(fault: &FaultType) -> u8 {
+    match fault {
+        FaultType::Drop => 0,
+        FaultType::MutateQty { .. } | FaultType::MutatePrice { .. } | FaultType::CorruptChecksum { .. } => 1,
+        FaultType::Delay { .. } => 2,
+        FaultType::Reorder => 3,
+        FaultType::Duplicate => 4,
+    }
+}
+
+/// Whether `fault` decides where or how often the frame plays
+fn moves_frame(fault: &FaultType) -> bool {
+    fault_precedence(fault) != 1
+}
+
+/// XOR the advertised checksum of a book frame's first entry; None without one
+fn corrupt_checksum(json: &mut serde_json::Value, xor: u32) -> Option<String> {
+    let checksum = json.get_mut("data")?.as_array_mut()?.first_mut()?.get_mut("checksum")?;
+    let corrupted = checksum.as_u64()? as u32 ^ xor;
+    *checksum = serde_json::Value::from(corrupted);
+    serde_json::to_string(json).ok()
+}
+
+/// Symbols' books since a seek: updates are only played once a snapshot has
+struct SnapshotGate {
+    ready: HashSet<String>,
+    waiting: BTreeSet<String>, // seen or subscribed, no snapshot yet
 }
 
+impl SnapshotGate {
+    /// Whether to play a book frame for `symbol`
+    fn admit(&mut self, symbol: String, snapshot: bool) -> bool {
+        if snapshot {
+            self.waiting.remove(&symbol);
+            self.ready.insert(symbol);
+            return true;
+        }
+        if self.ready.contains(&symbol) {
+            return true;
+        }
+        self.waiting.insert(symbol);
+        false
+    }
+}
+
+#[derive(Deserialize)]
+struct BookHeader<'a> {
+    #[serde(borrow)]
+    channel: Cow<'a, str>,
+    #[serde(rename = "type", borrow, default)]
+    msg_type: Option<Cow<'a, str>>,
+    #[serde(borrow, default)]
+    data: Vec<BookSymbol<'a>>,
+}
+
+#[derive(Deserialize)]
+struct BookSymbol<'a> {
+    #[serde(borrow)]
+    symbol: Cow<'a, str>,
+}
+
+/// Symbol of a book frame and whether it's a snapshot, from its tag if recorded
+fn book_frame_symbol(frame: &ReplayFrame) -> Option<(String, bool)> {
+    if let Some(tag) = &frame.tag {
+        let (kind, symbol) = tag.strip_prefix("book.")?.split_once(':')?;
+        return Some((symbol.to_string(), kind == "snapshot"));
+    }
+    // Other channels' data doesn't fit, which rules them out too
+    let header: BookHeader = serde_json::from_str(&frame.raw_frame).ok()?;
+    if header.channel != "book" {
+        return None;
+    }
+    let symbol = header.data.into_iter().next()?.symbol.into_owned();
+    Some((symbol, header.msg_type.as_deref() == Some("snapshot")))
+}
+
+/// Where the replayer's frames come from
+enum FrameSource {
+    Sequential(Box<FileStream>),
+    // One stream per file of a per-symbol recording, each with its next frame
+    Merged(Vec<(Option<ReplayFrame>, FileStream)>),
+}
+
+impl FrameSource {
+    /// One stream is read in order; several are merged by timestamp
+    fn open(streams: &[Vec<PathBuf>]) -> anyhow::Result<Self> {
+        if let [files] = streams {
+            return Ok(FrameSource::Sequential(Box::new(FileStream::open(files.clone())?)));
+        }
+        let mut merged = Vec::new();
+        for files in streams {
+            let mut stream = FileStream::open(files.clone())?;
+            merged.push((stream.next(), stream));
+        }
+        Ok(FrameSource::Merged(merged))
+    }
+
+    fn next(&mut self) -> Option<ReplayFrame> {
+        match self {
+            FrameSource::Sequential(stream) => stream.next(),
+            FrameSource::Merged(streams) => {
+                // Earliest head; the first stream wins ties
+                let mut earliest: Option<(usize, DateTime<Utc>)> = None;
+                for (i, (head, _)) in streams.iter().enumerate() {
+                    if let Some(frame) = head {
+                        if earliest.is_none_or(|(_, ts)| frame.ts < ts) {
+                            earliest = Some((i, frame.ts));
+                        }
+                    }
+                }
+                let (head, stream) = &mut streams[earliest?.0];
+                std::mem::replace(head, stream.next())
+            }
+        }
+    }
+
+    /// Header of the first file that has one, the control file first when merged
+    fn metadata(&self) -> Option<&RecordingMetadata> {
+        match self {
+            FrameSource::Sequential(stream) => stream.metadata.as_ref(),
+            FrameSource::Merged(streams) => streams.iter().find_map(|(_, stream)| stream.metadata.as_ref()),
+        }
+    }
+
+    fn total_bytes(&self) -> u64 {
+        match self {
+            FrameSource::Sequential(stream) => stream.total_bytes,
+            FrameSource::Merged(streams) => streams.iter().map(|(_, stream)| stream.total_bytes).sum(),
+        }
+    }
+
+    /// Bytes read from disk, less the frames read ahead
+    fn position(&self) -> u64 {
+        match self {
+            FrameSource::Sequential(stream) => stream.position(),
+            FrameSource::Merged(streams) => streams
+                .iter()
+                .map(|(head, stream)| stream.position().saturating_sub(head.as_ref().map_or(0, |f| f.bytes)))
+                .sum(),
+        }
+    }
+
+    fn buffered_bytes(&self) -> usize {
+        match self {
+            FrameSource::Sequential(stream) => stream.line.capacity(),
+            FrameSource::Merged(streams) => streams
+                .iter()
+                .map(|(head, stream)| stream.line.capacity() + head.as_ref().map_or(0, ReplayFrame::heap_size))
+                .sum(),
+        }
+    }
+}
+
+/// `Read` that counts the bytes read through it
+struct CountingReader {
+    inner: File,
+    count: Arc<AtomicU64>,
+}
+
+impl Read for CountingReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.count.fetch_add(n as u64, Ordering::Relaxed);
+        Ok(n)
+    }
+}
+
+/// The frames of a recording's files, in order, read a line at a time.
+/// Unreadable files and malformed lines are skipped with a warning.
+struct FileStream {
+    pending: VecDeque<PathBuf>,
+    current: Option<(PathBuf, RecordingReader)>,
+    file_read: Arc<AtomicU64>, // bytes read from the current file
+    line_bytes: u64, // bytes of the current file's lines consumed
+    file_len: u64,
+    finished_bytes: u64, // size of the files already read
+    total_bytes: u64,
+    metadata: Option<RecordingMetadata>, // first header seen
+    line: Vec<u8>,
+}
+
+impl FileStream {
+    /// Open the first file, failing if it can't be read
+    fn open(files: Vec<PathBuf>) -> anyhow::Result<Self> {
+        let total_bytes = files.iter().filter_map(|f| std::fs::metadata(f).ok()).map(|m| m.len()).sum();
+        let mut stream = Self {
+            pending: files.into(),
+            current: None,
+            file_read: Arc::new(AtomicU64::new(0)),
+            line_bytes: 0,
+            file_len: 0,
+            finished_bytes: 0,
+            total_bytes,
+            metadata: None,
+            line: Vec::new(),
+        };
+        if let Some(first) = stream.pending.pop_front() {
+            stream.open_file(first)?;
+        }
+        Ok(stream)
+    }
+
+    fn open_file(&mut self, path: PathBuf) -> anyhow::Result<()> {
+        let file = File::open(&path)?;
+        self.file_len = file.metadata()?.len();
+        self.file_read = Arc::new(AtomicU64::new(0));
+        self.line_bytes = 0;
+        let reader = read_recording(CountingReader { inner: file, count: self.file_read.clone() })?;
+        self.current = Some((path, reader));
+        Ok(())
+    }
+
+    fn finish_file(&mut self) {
+        self.current = None;
+        self.finished_bytes += self.file_len;
+        self.file_len = 0;
+        self.line_bytes = 0;
+    }
+
+    fn next(&mut self) -> Option<ReplayFrame> {
+        loop {
+            if self.current.is_none() {
+                let path = self.pending.pop_front()?;
+                if let Err(e) = self.open_file(path.clone()) {
+                    warn!("Skipping recording {}: {}", path.display(), e);
+                    self.finish_file();
+                }
+                continue;
+            }
+            let (path, reader) = self.current.as_mut()?;
+            
+            self.line.clear();
+            let read = match reader.read_until(b'\n', &mut self.line) {
+                Ok(0) => {
+                    self.finish_file();
+                    continue;
+                }
+                Ok(read) => read,
+                Err(e) => {
+                    warn!("Stopped reading {}: {}", path.display(), e);
+                    self.finish_file();
+                    continue;
+                }
+            };
+            let bytes = if reader.is_compressed() { 0 } else { read as u64 };
+            self.line_bytes += read as u64;
+            
+            let line = String::from_utf8_lossy(&self.line);
+            if line.trim().is_empty() {
+                continue;
+            }
+            match parse_recording_line(&line) {
+                Ok(RecordingLine::Metadata(meta)) => {
+                    self.metadata.get_or_insert(meta);
+                }
+                Ok(RecordingLine::Frame(frame)) => {
+                    return Some(ReplayFrame {
+                        ts: frame.ts,
+                        raw_frame: frame.raw_frame,
+                        tag: frame.decoded_event,
+                        bytes,
+                    });
+                }
+                Err(e) => warn!("Skipping malformed line in {}: {}", path.display(), e),
+            }
+        }
+    }
+
+    /// Bytes of the files consumed so far: lines read for plain files, the
+    /// decoder's input for gzipped ones
+    fn position(&self) -> u64 {
+        let in_file = match &self.current {
+            Some((_, reader)) if reader.is_compressed() => self.file_read.load(Ordering::Relaxed),
+            Some(_) => self.line_bytes,
+            None => 0,
+        };
+        self.finished_bytes + in_file.min(self.file_len)
+    }
+}
+
+fn is_per_symbol_dir(path: &Path) -> bool {
+    path.is_dir()
+        && ["ndjson", "ndjson.gz"].iter().any(|ext| path.join(format!("{}.{}", CONTROL_STEM, ext)).is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recorder::Recorder;
+    use crate::types::FaultRule;
+
+    /// Far less than the recordings below
+    const MEMORY_BUDGET: usize = 16 * 1024;
+
+    fn big_frame(i: usize) -> String {
+        let padding = "x".repeat(2000);
+        format!(r#"{{"channel":"book","type":"update","data":[{{"symbol":"BTC/USD","seq":{},"pad":"{}"}}]}}"#, i, padding)
+    }
+
+    #[test]
+    fn test_streaming_memory_stays_flat() {
+        let dir = std::env::temp_dir().join(format!("blackbox_replayer_stream_{}", std::process::id()));
+        for name in ["big.ndjson", "big.ndjson.gz"] {
+            let path = dir.join(name);
+            let mut recorder = Recorder::new(path.clone()).unwrap();
+            for i in 0..2000 {
+                recorder.record_frame(&big_frame(i), Some("book.update:BTC/USD")).unwrap();
+            }
+            drop(recorder);
+            let plain = name.ends_with(".ndjson");
+            if plain {
+                assert!(std::fs::metadata(&path).unwrap().len() > 100 * MEMORY_BUDGET as u64);
+            }
+            
+            let fault = FaultRule::Every { n: 7, fault: FaultType::Reorder };
+            let mut replayer = Replayer::new(path, ReplayConfig { mode: ReplayMode::AsFast, faults: vec![fault], loop_playback: false }).unwrap();
+            replayer.start();
+            // Gzip progress follows the decoder, which reads ahead
+            if plain {
+                assert_eq!(replayer.progress(), 0.0);
+            }
+            let mut progress = 0.0;
+            let mut replayed = 0;
+            while !replayer.is_done() {
+                let (tag, _) = replayer.next_classified().unwrap();
+                assert_eq!(tag.as_deref(), Some("book.update:BTC/USD"));
+                replayed += 1;
+                assert!(replayer.buffered_bytes() <= MEMORY_BUDGET, "{} bytes buffered", replayer.buffered_bytes());
+                assert!(replayer.progress() >= progress);
+                progress = replayer.progress();
+            }
+            // Reordering keeps every frame
+            assert_eq!(replayed, 2000);
+            assert_eq!(replayer.progress(), 1.0);
+            assert!(replayer.next_frame().is_none());
+        }
+        
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    fn book(kind: &str, symbol: &str, seq: usize) -> String {
+        format!(r#"{{"channel":"book","type":"{}","data":[{{"symbol":"{}","seq":{}}}]}}"#, kind, symbol, seq)
+    }
+
+    /// Second `i` of the recording below
+    fn at(i: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(1_700_000_000 + i, 0).unwrap()
+    }
+
+    /// The `seq` of every frame played
+    fn played(replayer: &mut Replayer) -> Vec<u64> {
+        let mut seqs = Vec::new();
+        while let Some(frame) = replayer.next_frame() {
+            let json: serde_json::Value = serde_json::from_str(&frame).unwrap();
+            seqs.push(json["data"][0]["seq"].as_u64().unwrap());
+        }
+        seqs
+    }
+
+    fn seek_recording(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("blackbox_seek_{}_{}.ndjson", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let frames = [
+            ("snapshot", "BTC/USD"),
+            ("update", "BTC/USD"),
+            ("snapshot", "ETH/USD"),
+            ("update", "BTC/USD"),
+            ("update", "ETH/USD"),
+            ("snapshot", "BTC/USD"), // resubscribed
+            ("update", "BTC/USD"),
+            ("update", "ETH/USD"),
+        ];
+        let mut recorder = Recorder::new(path.clone()).unwrap();
+        for (i, (kind, symbol)) in frames.iter().enumerate() {
+            // Half the frames tagged, so both ways of finding the symbol are covered
+            let tag = format!("book.{}:{}", kind, symbol);
+            let tag = (i % 2 == 0).then_some(tag.as_str());
+            recorder.record_frame_at(at(i as i64), &book(kind, symbol, i), tag).unwrap();
+        }
+        path
+    }
+
+    fn as_fast() -> ReplayConfig {
+        ReplayConfig { mode: ReplayMode::AsFast, faults: vec![], loop_playback: false }
+    }
+
+    #[test]
+    fn test_seek_to() {
+        let path = seek_recording("bounds");
+
+        // Before the start: nothing skipped, and every book has its snapshot first
+        let mut replayer = Replayer::new(path.clone(), as_fast()).unwrap();
+        assert_eq!(replayer.seek_to(at(-10)).unwrap(), 0);
+        assert_eq!(played(&mut replayer), vec![0, 1, 2, 3, 4, 5, 6, 7]);
+        assert!(replayer.symbols_without_snapshot().is_empty());
+
+        // Mid-file: updates wait for their book's next snapshot
+        let mut replayer = Replayer::new(path.clone(), as_fast()).unwrap();
+        assert_eq!(replayer.seek_to(at(3)).unwrap(), 3);
+        assert_eq!(played(&mut replayer), vec![5, 6]);
+        assert_eq!(replayer.symbols_without_snapshot(), vec!["ETH/USD".to_string()]);
+
+        // Backwards after playing reopens the recording
+        assert_eq!(replayer.seek_to(at(2)).unwrap(), 2);
+        assert_eq!(played(&mut replayer), vec![2, 4, 5, 6, 7]);
+        assert!(replayer.symbols_without_snapshot().is_empty());
+
+        // After the end
+        let mut replayer = Replayer::new(path.clone(), as_fast()).unwrap();
+        assert_eq!(replayer.seek_to(at(60)).unwrap(), 8);
+        assert!(replayer.is_done());
+        assert!(replayer.next_frame().is_none());
+        assert_eq!(replayer.symbols_without_snapshot(), vec!["BTC/USD".to_string(), "ETH/USD".to_string()]);
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_looped_seek_past_the_end() {
+        let path = seek_recording("loop");
+        let config = ReplayConfig { loop_playback: true, ..as_fast() };
+        let mut replayer = Replayer::new(path.clone(), config).unwrap();
+
+        // Stops at the end of the pass rather than looping forever
+        assert_eq!(replayer.seek_to(at(60)).unwrap(), 8);
+        assert_eq!(replayer.seek_to(at(60)).unwrap(), 0);
+        assert!(!replayer.is_done());
+
+        // The next pass starts from the top, snapshots and all
+        let (tag, frame) = replayer.next_classified().unwrap();
+        assert_eq!((tag.as_deref(), frame.as_str()), (Some(LOOP_TAG), LOOP_MARKER));
+        let frame = replayer.next_frame().unwrap();
+        let json: serde_json::Value = serde_json::from_str(&frame).unwrap();
+        assert_eq!(json["data"][0]["seq"], 0);
+        assert!(replayer.symbols_without_snapshot().is_empty());
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_seek_window() {
+        let path = seek_recording("window");
+        let mut replayer = Replayer::new(path.clone(), as_fast()).unwrap();
+        replayer.seek_to(at(1)).unwrap();
+        replayer.set_end(at(5));
+        assert_eq!(played(&mut replayer), vec![2, 4, 5]);
+        assert!(replayer.is_done());
+        assert!(replayer.symbols_without_snapshot().is_empty());
+
+        // Without a seek nothing is held back
+        let mut replayer = Replayer::new(path.clone(), as_fast()).unwrap();
+        replayer.set_end(at(1));
+        assert_eq!(played(&mut replayer), vec![0, 1]);
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    /// `count` BTC/USD book updates, one every 50ms
+    fn every_50ms(name: &str, count: usize) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("blackbox_{}_{}.ndjson", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut recorder = Recorder::new(path.clone()).unwrap();
+        for i in 0..count {
+            let ts = at(0) + chrono::Duration::milliseconds(50 * i as i64);
+            recorder.record_frame_at(ts, &book("update", "BTC/USD", i), None).unwrap();
+        }
+        path
+    }
+
+    #[test]
+    fn test_pause_freezes_realtime_clock() {
+        let path = every_50ms("pause", 40);
+
+        let config = ReplayConfig { mode: ReplayMode::Realtime, faults: vec![], loop_playback: false };
+        let mut replayer = Replayer::new(path.clone(), config).unwrap();
+        replayer.start();
+        std::thread::sleep(Duration::from_millis(120));
+        let before = played(&mut replayer);
+        assert!(!before.is_empty(), "nothing played before pausing");
+        assert_eq!(before, (0..before.len() as u64).collect::<Vec<_>>());
+
+        replayer.pause();
+        std::thread::sleep(Duration::from_millis(500));
+        assert!(replayer.next_frame().is_none());
+
+        // Resuming picks up where the pause left off
+        let resumed = Instant::now();
+        replayer.resume();
+        let mut after = played(&mut replayer);
+        std::thread::sleep(Duration::from_millis(120));
+        after.extend(played(&mut replayer));
+        let elapsed = resumed.elapsed();
+        assert!(!after.is_empty(), "nothing played {:?} after resuming", elapsed);
+        let next = before.last().unwrap() + 1;
+        assert_eq!(after, (next..next + after.len() as u64).collect::<Vec<_>>());
+        // No burst through the ten frames the pause spanned: one frame may be
+        // due at the pause point, then one per 50ms since resuming
+        let covered = elapsed.as_millis() as usize / 50 + 2;
+        assert!(after.len() <= covered, "{} frames in {:?} after resuming", after.len(), elapsed);
+
+        // Stepping ignores both pause and pacing
+        replayer.pause();
+        replayer.step();
+        assert!(replayer.next_frame().is_some());
+        assert!(replayer.next_frame().is_none());
+        assert!(replayer.is_paused());
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_set_mode_mid_replay() {
+        let path = every_50ms("set_mode", 40);
+        let config = ReplayConfig { mode: ReplayMode::Realtime, faults: vec![], loop_playback: false };
+        let mut replayer = Replayer::new(path.clone(), config).unwrap();
+        replayer.start();
+        assert!(replayer.next_frame().is_some());
+        assert!(replayer.next_frame().is_none());
+
+        // The 39 frames left span 1.95s of recording, 19.5ms at 100x
+        replayer.set_mode(ReplayMode::Speed(100.0));
+        assert!(matches!(replayer.mode(), ReplayMode::Speed(_)));
+        std::thread::sleep(Duration::from_millis(100));
+        assert_eq!(std::iter::from_fn(|| replayer.next_frame()).count(), 39);
+        assert!(replayer.is_done());
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_loop_passes_rebuild_identical_books() {
+        use crate::orderbook::Orderbook;
+        use crate::types::BookMessage;
+        use std::collections::BTreeMap;
+
+        let recording = PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/recording_corrupted.ndjson"));
+        // Faults count book updates per pass, so both passes drop the same frames
+        let config = ReplayConfig {
+            mode: ReplayMode::AsFast,
+            faults: vec![FaultRule::Every { n: 3, fault: FaultType::Drop }],
+            loop_playback: true,
+        };
+        let mut replayer = Replayer::new(recording, config).unwrap();
+        replayer.start();
+
+        let mut passes: Vec<Vec<String>> = vec![Vec::new()];
+        let mut books: BTreeMap<String, Orderbook> = BTreeMap::new();
+        while passes.len() < 3 {
+            assert!(!replayer.is_done());
+            let (tag, frame) = replayer.next_classified().unwrap();
+            if frame == LOOP_MARKER {
+                assert_eq!(tag.as_deref(), Some(LOOP_TAG));
+                books.clear();
+                passes.push(Vec::new());
+                continue;
+            }
+            if let Ok(msg) = serde_json::from_str::<BookMessage>(&frame) {
+                for data in msg.data {
+                    let (bids, asks) = Orderbook::levels_from_book_data(&data).unwrap();
+                    let book = books.entry(data.symbol.clone()).or_default();
+                    if msg.msg_type == "snapshot" {
+                        book.apply_snapshot(bids, asks);
+                    } else {
+                        book.apply_updates(bids, asks);
+                    }
+                }
+            }
+            let state = books.iter().map(|(symbol, book)| format!("{} {:?} {:?}", symbol, book.bids_vec(None), book.asks_vec(None)));
+            passes.last_mut().unwrap().push(state.collect::<Vec<_>>().join("; "));
+        }
+
+        // The fixture's nine frames less the two dropped, twice over
+        assert_eq!(passes[0].len(), 7);
+        assert_eq!(passes[0], passes[1]);
+        // And a third pass begins
+        let (_, first) = replayer.next_classified().unwrap();
+        assert_ne!(first, LOOP_MARKER);
+    }
+
+    const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/recording_corrupted.ndjson");
+
+    fn replay_fixture(fault: FaultRule) -> Vec<String> {
+        let config = ReplayConfig { mode: ReplayMode::AsFast, faults: vec![fault], loop_playback: false };
+        let mut replayer = Replayer::new(PathBuf::from(FIXTURE), config).unwrap();
+        std::iter::from_fn(|| replayer.next_frame()).collect()
+    }
+
+    /// `verify_recording` of the frames, recorded again
+    fn verify_frames(name: &str, frames: &[String]) -> crate::verify::RecordingVerifyReport {
+        let path = std::env::temp_dir().join(format!("blackbox_{}_{}.ndjson", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut recorder = Recorder::new(path.clone()).unwrap();
+        for frame in frames {
+            recorder.record_frame(frame, None).unwrap();
+        }
+        drop(recorder);
+        let report = crate::verify::verify_recording(&path, &crate::types::InstrumentMap::new()).unwrap();
+        let _ = std::fs::remove_file(path);
+        report
+    }
+
+    #[test]
+    fn test_mutate_price_breaks_checksum() {
+        // Book frame #3 is the first bid update, 50000.7
+        let frames = replay_fixture(FaultRule::OnceAt { index: 3, fault: FaultType::MutatePrice { delta_ticks: 1 } });
+        // One tick of the recorded instrument's 0.1 increment
+        assert!(frames[4].contains("50000.8"), "{}", frames[4]);
+        let report = verify_frames("mutate_price", &frames);
+        assert_eq!(report.first_divergence.get("BTC/USD"), Some(&4));
+    }
+
+    #[test]
+    fn test_duplicate_keeps_checksum() {
+        // Updates set absolute quantities, so applying one twice changes nothing:
+        // the only mismatch left is the fixture's own corrupted frame
+        let frames = replay_fixture(FaultRule::OnceAt { index: 2, fault: FaultType::Duplicate });
+        assert_eq!(frames.len(), 10);
+        assert_eq!(frames[3], frames[4]);
+        let report = verify_frames("duplicate", &frames);
+        assert_eq!(report.checksums_verified, 7);
+        assert_eq!(report.mismatches.len(), 1);
+        assert_eq!(report.first_divergence.get("BTC/USD"), Some(&8));
+    }
+
+    #[test]
+    fn test_corrupt_checksum_leaves_book() {
+        let frames = replay_fixture(FaultRule::OnceAt { index: 2, fault: FaultType::CorruptChecksum { xor: 1 } });
+        assert!(frames[3].contains(&format!("\"checksum\":{}", 4267385745u32 ^ 1)), "{}", frames[3]);
+        // The corrupted frame fails alone; the next one verifies again
+        let report = verify_frames("corrupt_checksum", &frames);
+        let failed: Vec<usize> = report.mismatches.iter().map(|m| m.frame_index).collect();
+        assert_eq!(failed, vec![3, 7]);
+    }
+
+    #[test]
+    fn test_delay_lets_later_frames_pass() {
+        let path = every_50ms("delay_order", 10);
+        // Updates #4 and #8 held 120ms: past the two frames after each
+        let fault = FaultRule::Every { n: 4, fault: FaultType::Delay { ms: 120 } };
+        let mut replayer = Replayer::new(path.clone(), ReplayConfig { faults: vec![fault], ..as_fast() }).unwrap();
+        replayer.start();
+        // Released frames aren't counted again, so #8 is still the eighth recorded
+        assert_eq!(played(&mut replayer), vec![0, 1, 2, 4, 5, 3, 6, 8, 9, 7]);
+        assert!(replayer.is_done());
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_delay_follows_replay_speed() {
+        let path = every_50ms("delay_timing", 6);
+        for (mode, wall_ms) in [(ReplayMode::Realtime, 200), (ReplayMode::Speed(2.0), 100)] {
+            let fault = FaultRule::OnceAt { index: 1, fault: FaultType::Delay { ms: 200 } };
+            let mut replayer = Replayer::new(path.clone(), ReplayConfig { mode, faults: vec![fault], loop_playback: false }).unwrap();
+            // Taken first, so no release can look early
+            let started = Instant::now();
+            replayer.start();
+            let mut order = Vec::new();
+            let mut released = None;
+            while !replayer.is_done() {
+                let Some(frame) = replayer.next_frame() else {
+                    std::thread::sleep(Duration::from_millis(1));
+                    continue;
+                };
+                let json: serde_json::Value = serde_json::from_str(&frame).unwrap();
+                let seq = json["data"][0]["seq"].as_u64().unwrap();
+                if seq == 0 {
+                    released = Some(started.elapsed());
+                }
+                order.push(seq);
+            }
+            // Released with the frame recorded 200ms after it, which comes next
+            assert_eq!(order, vec![1, 2, 3, 0, 4, 5]);
+            let released = released.unwrap();
+            assert!(released >= Duration::from_millis(wall_ms), "{:?}", released);
+            // Loose enough for a busy machine, but a delay not scaled by the speed fails it
+            assert!(released < Duration::from_millis(wall_ms * 2), "{:?}", released);
+        }
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_overlapping_fault_rules() {
+        let once = |index, fault| FaultRule::OnceAt { index, fault };
+        let faults = vec![
+            once(3, FaultType::MutateQty { delta_ticks: 1 }),
+            FaultRule::Every { n: 3, fault: FaultType::Drop },
+            once(2, FaultType::Duplicate),
+            once(2, FaultType::CorruptChecksum { xor: 1 }),
+            once(2, FaultType::Delay { ms: 1500 }),
+        ];
+        let config = ReplayConfig { faults, ..as_fast() };
+        let mut replayer = Replayer::new(PathBuf::from(FIXTURE), config).unwrap();
+        let frames: Vec<String> = std::iter::from_fn(|| replayer.next_frame()).collect();
+
+        // Update #2 is corrupted, then delayed past the (dropped) #3 and the
+        // heartbeat; the delay wins over duplicating it
+        assert_eq!(frames.len(), 7);
+        assert_eq!(frames[3], r#"{"channel":"heartbeat"}"#);
+        assert!(frames[4].contains(&format!("\"checksum\":{}", 4267385745u32 ^ 1)), "{}", frames[4]);
+        let applied: Vec<(usize, FaultType, usize, usize)> = replayer
+            .drain_events()
+            .map(|ReplayEvent::FaultApplied { rule, fault, frame_index, update_index, .. }| (rule, fault, frame_index, update_index))
+            .collect();
+        // Drop wins over the mutation at #3 too
+        assert_eq!(
+            applied,
+            vec![
+                (3, FaultType::CorruptChecksum { xor: 1 }, 3, 2),
+                (4, FaultType::Delay { ms: 1500 }, 3, 2),
+                (1, FaultType::Drop, 4, 3),
+                (1, FaultType::Drop, 8, 6),
+            ]
+        );
+        assert_eq!(replayer.drain_events().count(), 0);
+        // Still counted once drained
+        assert_eq!(replayer.faults_applied(), 4);
+    }
+
+    #[test]
+    fn test_next_frame_delay_keeps_schedule() {
+        let path = every_50ms("delay_schedule", 8);
+        let config = ReplayConfig { mode: ReplayMode::Realtime, ..as_fast() };
+        let mut replayer = Replayer::new(path.clone(), config).unwrap();
+        assert_eq!(replayer.next_frame_delay(), Some(Duration::ZERO));
+        let started = Instant::now();
+        replayer.start();
+        let mut played = Vec::new();
+        while let Some(wait) = replayer.next_frame_delay() {
+            std::thread::sleep(wait);
+            if let Some(frame) = replayer.next_frame() {
+                let json: serde_json::Value = serde_json::from_str(&frame).unwrap();
+                played.push((json["data"][0]["seq"].as_u64().unwrap(), started.elapsed()));
+            }
+        }
+        assert_eq!(played.iter().map(|(seq, _)| *seq).collect::<Vec<_>>(), (0..8).collect::<Vec<_>>());
+        for (seq, at) in &played {
+            // Never early, and (allowing for a busy machine) played before the next is due
+            let due = Duration::from_millis(50 * seq);
+            assert!(*at >= due && *at < due + Duration::from_millis(50), "{:?}", played);
+        }
+        assert!(replayer.is_done());
+
+        // Nothing to wait for while paused, unless stepping
+        let mut replayer = Replayer::new(path.clone(), ReplayConfig { mode: ReplayMode::Realtime, ..as_fast() }).unwrap();
+        replayer.start();
+        replayer.pause();
+        assert_eq!(replayer.next_frame_delay(), None);
+        replayer.step();
+        assert_eq!(replayer.next_frame_delay(), Some(Duration::ZERO));
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[cfg(feature = "stream")]
+    #[tokio::test]
+    async fn test_into_stream_plays_every_frame_in_order() {
+        use futures_util::StreamExt;
+
+        let fault = FaultRule::Every { n: 3, fault: FaultType::Drop };
+        let expected = replay_fixture(fault.clone());
+        let config = ReplayConfig { faults: vec![fault], ..as_fast() };
+        let streamed: Vec<(DateTime<Utc>, String)> = Replayer::new(PathBuf::from(FIXTURE), config)
+            .unwrap()
+            .into_stream()
+            .map(|item| item.unwrap())
+            .collect()
+            .await;
+        assert_eq!(streamed.len(), 7);
+        assert!(streamed.windows(2).all(|pair| pair[0].0 <= pair[1].0));
+        let frames: Vec<String> = streamed.into_iter().map(|(_, frame)| frame).collect();
+        assert_eq!(frames, expected);
+
+        // A shared replayer, paced, paused until another task resumes it
+        let path = every_50ms("stream_shared", 4);
+        let mut replayer = Replayer::new(path.clone(), ReplayConfig { mode: ReplayMode::Speed(10.0), ..as_fast() }).unwrap();
+        replayer.pause();
+        let shared = Arc::new(std::sync::Mutex::new(Some(replayer)));
+        let control = shared.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            control.lock().unwrap().as_mut().unwrap().resume();
+        });
+        let started = Instant::now();
+        let seqs: Vec<u64> = frame_stream(shared)
+            .map(|item| {
+                let json: serde_json::Value = serde_json::from_str(&item.unwrap().1).unwrap();
+                json["data"][0]["seq"].as_u64().unwrap()
+            })
+            .collect()
+            .await;
+        assert_eq!(seqs, vec![0, 1, 2, 3]);
+        // The pause, then 150ms of recording at 10x
+        assert!(started.elapsed() >= Duration::from_millis(115), "{:?}", started.elapsed());
+
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -1,3 +1,4 @@
+use crate::precision::{parse_decimal, precision_from_increment};
 use chrono::{DateTime, Utc};
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
@@ -95,10 +96,11 @@ pub struct InstrumentData {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct InstrumentPair {
     pub symbol: String,
-    #[serde(rename = "price_precision")]
-    pub price_precision: u32,
-    #[serde(rename = "qty_precision")]
-    pub qty_precision: u32,
+    // Missing from some payloads; see InstrumentInfo::from_pair
+    #[serde(rename = "price_precision", default)]
+    pub price_precision: Option<u32>,
+    #[serde(rename = "qty_precision", default)]
+    pub qty_precision: Option<u32>,
     #[serde(rename = "price_increment", deserialize_with = "deserialize_decimal_string")]
     pub price_increment: String,
     #[serde(rename = "qty_increment", deserialize_with = "deserialize_decimal_string")]
@@ -156,7 +158,16 @@ pub struct PingMessage {
 
 // BookLevel struct moved to BookLevelData above for WebSocket message parsing
 
-#[derive(Debug, Clone, Default, Serialize)]
+/// Whether a precision was sent by the exchange or derived from the increment
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PrecisionSource {
+    #[default]
+    Explicit,
+    Derived,
+}
+
+#[derive(Debug, Clone, Serialize)]
 pub struct InstrumentInfo {
     pub symbol: String,
     pub price_precision: u32,
@@ -164,6 +175,66 @@ pub struct InstrumentInfo {
     pub price_increment: Decimal,
     pub qty_increment: Decimal,
     pub status: String,
+    pub checksum_levels: usize, // levels per side in the book checksum (Kraken: 10)
+    pub price_precision_source: PrecisionSource,
+    pub qty_precision_source: PrecisionSource,
+}
+
+impl InstrumentInfo {
+    /// Build from an instrument channel pair. A precision that's missing or
+    /// zero is taken from the increment's decimal places instead.
+    pub fn from_pair(pair: InstrumentPair) -> anyhow::Result<Self> {
+        let price_increment = parse_decimal(&pair.price_increment)?;
+        let qty_increment = parse_decimal(&pair.qty_increment)?;
+        let (price_precision, price_precision_source) = resolve_precision(pair.price_precision, &price_increment);
+        let (qty_precision, qty_precision_source) = resolve_precision(pair.qty_precision, &qty_increment);
+        
+        Ok(Self {
+            symbol: pair.symbol,
+            price_precision,
+            qty_precision,
+            price_increment,
+            qty_increment,
+            status: pair.status,
+            checksum_levels: crate::checksum::CHECKSUM_DEPTH,
+            price_precision_source,
+            qty_precision_source,
+        })
+    }
+    
+    pub fn has_derived_precision(&self) -> bool {
+        self.price_precision_source == PrecisionSource::Derived
+            || self.qty_precision_source == PrecisionSource::Derived
+    }
+}
+
+fn resolve_precision(explicit: Option<u32>, increment: &Decimal) -> (u32, PrecisionSource) {
+    match explicit {
+        Some(precision) if precision > 0 || increment.is_zero() => (precision, PrecisionSource::Explicit),
+        _ if increment.is_zero() => (0, PrecisionSource::Explicit),
+        _ => {
+            let derived = precision_from_increment(increment);
+            // An explicit 0 that the increment agrees with (e.g. tick 1) still counts as sent
+            let source = if explicit == Some(derived) { PrecisionSource::Explicit } else { PrecisionSource::Derived };
+            (derived, source)
+        }
+    }
+}
+
+impl Default for InstrumentInfo {
+    fn default() -> Self {
+        Self {
+            symbol: String::new(),
+            price_precision: 0,
+            qty_precision: 0,
+            price_increment: Decimal::ZERO,
+            qty_increment: Decimal::ZERO,
+            status: String::new(),
+            checksum_levels: crate::checksum::CHECKSUM_DEPTH,
+            price_precision_source: PrecisionSource::Explicit,
+            qty_precision_source: PrecisionSource::Explicit,
+        }
+    }
 }
 
 pub type InstrumentMap = HashMap<String, InstrumentInfo>;
@@ -175,10 +246,57 @@ pub struct RecordedFrame {
     pub decoded_event: Option<String>,
 }
 
+/// Recording schema version written by this build
+pub const RECORDING_SCHEMA_VERSION: u32 = 1;
+
+/// Capture context, written as the first line of each recording file as
+/// `{"_meta": {...}}`. Older recordings have no header.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordingMetadata {
+    pub version: u32, // schema version
+    #[serde(default)]
+    pub symbols: Vec<String>,
+    #[serde(default)]
+    pub depth: Option<u32>,
+    #[serde(default)]
+    pub started_at: DateTime<Utc>, // start of the first segment
+    #[serde(default)]
+    pub ws_url: Option<String>, // None for mock sessions
+    #[serde(default)]
+    pub crate_version: String,
+    // Only these channels were recorded (see `RecorderOptions::channel_filter`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub channels: Option<Vec<String>>,
+}
+
+impl RecordingMetadata {
+    pub fn new(symbols: Vec<String>, depth: u32) -> Self {
+        Self {
+            version: RECORDING_SCHEMA_VERSION,
+            symbols,
+            depth: Some(depth),
+            started_at: Utc::now(),
+            ws_url: None,
+            crate_version: env!("CARGO_PKG_VERSION").to_string(),
+            channels: None,
+        }
+    }
+
+    /// Whether this build understands the recording's schema
+    pub fn is_supported(&self) -> bool {
+        self.version <= RECORDING_SCHEMA_VERSION
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ReplayConfig {
     pub mode: ReplayMode,
-    pub fault: FaultRule,
+    // All are checked on every book update; configs from before this took a single `fault`
+    #[serde(default, alias = "fault", deserialize_with = "one_or_many_rules")]
+    pub faults: Vec<FaultRule>,
+    // Start over after the last frame, see `replayer::LOOP_MARKER`
+    #[serde(default)]
+    pub loop_playback: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -188,17 +306,126 @@ pub enum ReplayMode {
     AsFast,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum FaultType {
     Drop,
     Reorder,
     MutateQty { delta_ticks: i32 },
+    // Shifts the first ask (else bid) price: a phantom level, or a crossed book when moved through the spread
+    MutatePrice { delta_ticks: i32 },
+    // Plays the frame twice in a row
+    Duplicate,
+    // Flips bits of the advertised checksum, leaving the book alone
+    CorruptChecksum { xor: u32 },
+    // Holds the frame back by this much recording time while later frames play:
+    // wall-clock ms in Realtime, ms / speed with Speed, reordering by timestamp in AsFast
+    Delay { ms: u64 },
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl FaultType {
+    /// snake_case name, without parameters
+    pub fn name(&self) -> &'static str {
+        match self {
+            FaultType::Drop => "drop",
+            FaultType::Reorder => "reorder",
+            FaultType::MutateQty { .. } => "mutate_qty",
+            FaultType::MutatePrice { .. } => "mutate_price",
+            FaultType::Duplicate => "duplicate",
+            FaultType::CorruptChecksum { .. } => "corrupt_checksum",
+            FaultType::Delay { .. } => "delay",
+        }
+    }
+}
+
+/// Tick used by `FaultType::MutatePrice` for symbols whose instrument isn't known (0.1)
+pub const DEFAULT_PRICE_INCREMENT: Decimal = Decimal::from_parts(1, 0, 0, false, 1);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum FaultRule {
     Every { n: usize, fault: FaultType },
     OnceAt { index: usize, fault: FaultType },
     None,
 }
 
+impl FaultRule {
+    /// The fault to apply to a symbol's `update_index`th book update (from 1), if any
+    pub fn fault_at(&self, update_index: usize) -> Option<&FaultType> {
+        match self {
+            FaultRule::Every { n, fault } if update_index.is_multiple_of(*n) => Some(fault),
+            FaultRule::OnceAt { index, fault } if update_index == *index => Some(fault),
+            _ => None,
+        }
+    }
+}
+
+fn one_or_many_rules<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Vec<FaultRule>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(FaultRule),
+        Many(Vec<FaultRule>),
+    }
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(rule) => vec![rule],
+        OneOrMany::Many(rules) => rules,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replay_config_takes_one_fault_rule_or_many() {
+        let drop_at_2 = FaultRule::OnceAt { index: 2, fault: FaultType::Drop };
+        let single = r#"{"mode":"AsFast","fault":{"OnceAt":{"index":2,"fault":"Drop"}}}"#;
+        let config: ReplayConfig = serde_json::from_str(single).unwrap();
+        assert_eq!(config.faults, vec![drop_at_2.clone()]);
+
+        let many = r#"{"mode":"AsFast","faults":[{"OnceAt":{"index":2,"fault":"Drop"}},{"Every":{"n":5,"fault":"Duplicate"}}]}"#;
+        let config: ReplayConfig = serde_json::from_str(many).unwrap();
+        assert_eq!(config.faults, vec![drop_at_2, FaultRule::Every { n: 5, fault: FaultType::Duplicate }]);
+
+        let config: ReplayConfig = serde_json::from_str(r#"{"mode":"AsFast"}"#).unwrap();
+        assert!(config.faults.is_empty());
+        // Written back as a list
+        assert!(serde_json::to_string(&config).unwrap().contains(r#""faults":[]"#));
+    }
+
+    fn pair(price_precision: Option<u32>, qty_precision: Option<u32>, price_inc: &str, qty_inc: &str) -> InstrumentPair {
+        InstrumentPair {
+            symbol: "BTC/USD".to_string(),
+            price_precision,
+            qty_precision,
+            price_increment: price_inc.to_string(),
+            qty_increment: qty_inc.to_string(),
+            status: "online".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_instrument_precision_fallback() {
+        let info = InstrumentInfo::from_pair(pair(Some(1), Some(8), "0.1", "0.00000001")).unwrap();
+        assert_eq!((info.price_precision, info.qty_precision), (1, 8));
+        assert!(!info.has_derived_precision());
+        
+        // Missing or zero precision comes from the increment
+        let info = InstrumentInfo::from_pair(pair(None, Some(0), "0.5", "1e-8")).unwrap();
+        assert_eq!((info.price_precision, info.qty_precision), (1, 8));
+        assert_eq!(info.price_precision_source, PrecisionSource::Derived);
+        assert_eq!(info.qty_precision_source, PrecisionSource::Derived);
+        
+        // Zero that matches a whole-unit increment is explicit
+        let info = InstrumentInfo::from_pair(pair(Some(2), Some(0), "0.01", "1")).unwrap();
+        assert_eq!(info.qty_precision, 0);
+        assert_eq!(info.qty_precision_source, PrecisionSource::Explicit);
+        
+        // Missing precision in the JSON payload
+        let json = r#"{"symbol":"ETH/USD","price_increment":"0.01","qty_increment":0.001,"status":"online"}"#;
+        let info = InstrumentInfo::from_pair(serde_json::from_str(json).unwrap()).unwrap();
+        assert_eq!((info.price_precision, info.qty_precision), (2, 3));
+        assert!(info.has_derived_precision());
+        
+        assert!(InstrumentInfo::from_pair(pair(Some(1), Some(8), "tick", "0.1")).is_err());
+    }
+}
@@ -0,0 +1,285 @@
+//! Offline checksum verification of NDJSON recordings
+
+use crate::checksum::ChecksumVerifier;
+use crate::orderbook::Orderbook;
+use crate::recorder::{open_recording, parse_recording_line, RecordingLine};
+use crate::types::{BookMessage, InstrumentInfo, InstrumentMap, InstrumentMessage};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::io::BufRead;
+use std::path::Path;
+
+/// Book depths Kraken accepts on subscribe
+pub const SUPPORTED_BOOK_DEPTHS: &[u32] = &[10, 25, 100, 500, 1000];
+
+/// A checksum that didn't match the rebuilt book
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FrameMismatch {
+    pub frame_index: usize, // 0-based line in the recording (blank lines and the header skipped)
+    pub ts: DateTime<Utc>,  // when the frame was recorded
+    pub symbol: String,
+    pub expected: u32,
+    pub computed: u32,
+}
+
+/// What one symbol's book messages looked like in a recording
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SymbolVerifyStats {
+    pub book_frames: usize,
+    pub snapshots: usize,
+    pub updates: usize,
+    pub checksums_verified: usize,
+    pub mismatches: usize,
+}
+
+/// How `verify_recording_with_options` reads a recording
+#[derive(Debug, Clone, Default)]
+pub struct VerifyOptions {
+    pub stop_on_first: bool, // stop at the first mismatch
+}
+
+/// Result of `verify_recording`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RecordingVerifyReport {
+    pub total_frames: usize,
+    pub book_frames: usize,
+    pub checksums_verified: usize,
+    // Checksummed messages skipped because no precision was known for the symbol
+    pub checksums_skipped: usize,
+    pub malformed_frames: usize,
+    pub mismatches: Vec<FrameMismatch>,
+    pub first_divergence: BTreeMap<String, usize>, // symbol -> frame index
+    pub symbols: BTreeMap<String, SymbolVerifyStats>,
+    pub stopped_early: bool, // the rest of the file wasn't read (`stop_on_first`)
+}
+
+impl RecordingVerifyReport {
+    pub fn is_ok(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+struct RebuiltBook {
+    book: Orderbook,
+    depth: usize,
+}
+
+/// Rebuild every book in a recording and verify each checksum it carries.
+///
+/// Precisions come from `instruments`, falling back to the instrument snapshot
+/// recorded in the file. The subscribed depth isn't recorded, so each book is
+/// kept at the smallest supported depth that fits its snapshot.
+pub fn verify_recording<P: AsRef<Path>>(
+    path: P,
+    instruments: &InstrumentMap,
+) -> anyhow::Result<RecordingVerifyReport> {
+    verify_recording_with_options(path, instruments, &VerifyOptions::default())
+}
+
+/// `verify_recording`, optionally stopping at the first mismatch
+pub fn verify_recording_with_options<P: AsRef<Path>>(
+    path: P,
+    instruments: &InstrumentMap,
+    options: &VerifyOptions,
+) -> anyhow::Result<RecordingVerifyReport> {
+    let path = path.as_ref();
+    let reader = open_recording(path).with_context(|| format!("Failed to open recording {}", path.display()))?;
+    let mut report = RecordingVerifyReport::default();
+    let mut verifier = ChecksumVerifier::new();
+    for instrument in instruments.values() {
+        verifier.set_instrument(instrument);
+    }
+    let mut books: HashMap<String, RebuiltBook> = HashMap::new();
+
+    for line in reader.lines() {
+        if options.stop_on_first && !report.mismatches.is_empty() {
+            report.stopped_early = true;
+            break;
+        }
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let frame_index = report.total_frames;
+
+        let recorded = parse_recording_line(&line)
+            .with_context(|| format!("{}: frame {} is not a recorded frame", path.display(), frame_index))?;
+        let RecordingLine::Frame(frame) = recorded else {
+            continue;
+        };
+        report.total_frames += 1;
+        // Tagged acks, heartbeats and status frames need no parsing
+        if frame.decoded_event.as_deref().is_some_and(|tag| !tag.starts_with("book.") && !tag.starts_with("instrument.")) {
+            continue;
+        }
+        let Ok(json) = serde_json::from_str::<serde_json::Value>(&frame.raw_frame) else {
+            report.malformed_frames += 1;
+            continue;
+        };
+
+        match json.get("channel").and_then(|c| c.as_str()) {
+            Some("instrument") => {
+                if let Ok(msg) = serde_json::from_value::<InstrumentMessage>(json) {
+                    for pair in msg.data.pairs {
+                        // Explicit instruments win over the recorded ones
+                        if instruments.contains_key(&pair.symbol) {
+                            continue;
+                        }
+                        // Unparseable increments leave the symbol's checksums skipped
+                        if let Ok(instrument) = InstrumentInfo::from_pair(pair) {
+                            verifier.set_instrument(&instrument);
+                        }
+                    }
+                }
+            }
+            Some("book") => {
+                let Ok(msg) = serde_json::from_value::<BookMessage>(json) else {
+                    report.malformed_frames += 1;
+                    continue;
+                };
+                report.book_frames += 1;
+
+                for data in msg.data {
+                    let Ok((bids, asks)) = Orderbook::levels_from_book_data(&data) else {
+                        report.malformed_frames += 1;
+                        continue;
+                    };
+                    let symbol = data.symbol;
+                    let stats = report.symbols.entry(symbol.clone()).or_default();
+                    stats.book_frames += 1;
+
+                    if msg.msg_type == "snapshot" {
+                        stats.snapshots += 1;
+                        let depth = snapshot_depth(bids.len().max(asks.len()));
+                        let mut book = Orderbook::new();
+                        book.apply_snapshot(bids, asks);
+                        book.truncate(depth);
+                        books.insert(symbol.clone(), RebuiltBook { book, depth });
+                    } else {
+                        stats.updates += 1;
+                        if let Some(entry) = books.get_mut(&symbol) {
+                            entry.book.apply_updates(bids, asks);
+                            entry.book.truncate(entry.depth);
+                        }
+                    }
+                    // Updates before the first snapshot can't be placed
+                    let (Some(entry), Some(expected)) = (books.get_mut(&symbol), data.checksum) else {
+                        continue;
+                    };
+
+                    let Some(result) = verifier.verify(&symbol, &entry.book, expected) else {
+                        report.checksums_skipped += 1;
+                        continue;
+                    };
+                    report.checksums_verified += 1;
+                    stats.checksums_verified += 1;
+                    if !result.matched {
+                        stats.mismatches += 1;
+                        report.first_divergence.entry(symbol.clone()).or_insert(frame_index);
+                        report.mismatches.push(FrameMismatch {
+                            frame_index,
+                            ts: frame.ts,
+                            symbol,
+                            expected,
+                            computed: result.computed,
+                        });
+                    }
+                }
+            }
+            // Acks, heartbeats, status
+            _ => {}
+        }
+    }
+
+    Ok(report)
+}
+
+/// Smallest supported depth that holds a snapshot of `levels` levels per side
+fn snapshot_depth(levels: usize) -> usize {
+    SUPPORTED_BOOK_DEPTHS
+        .iter()
+        .map(|&depth| depth as usize)
+        .find(|&depth| depth >= levels)
+        .unwrap_or(levels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RECORDING: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/recording_corrupted.ndjson");
+
+    #[test]
+    fn test_verify_recording_finds_corrupted_frame() {
+        let report = verify_recording(RECORDING, &InstrumentMap::new()).unwrap();
+
+        assert_eq!(report.total_frames, 9);
+        assert_eq!(report.book_frames, 6);
+        assert_eq!(report.checksums_verified, 6);
+        assert_eq!(report.malformed_frames, 0);
+        assert!(!report.is_ok());
+
+        // Frame 7 carries a mutated qty; frame 8 overwrites the level and the book reconverges
+        assert_eq!(report.mismatches.len(), 1);
+        assert_eq!(report.mismatches[0].frame_index, 7);
+        assert_eq!(report.mismatches[0].symbol, "BTC/USD");
+        assert_ne!(report.mismatches[0].expected, report.mismatches[0].computed);
+        assert_eq!(report.first_divergence.get("BTC/USD"), Some(&7));
+        assert_eq!(report.mismatches[0].ts.to_rfc3339(), "2024-01-15T10:30:47+00:00");
+        assert!(!report.stopped_early);
+
+        let stats = &report.symbols["BTC/USD"];
+        assert_eq!((stats.book_frames, stats.snapshots, stats.updates), (6, 1, 5));
+        assert_eq!((stats.checksums_verified, stats.mismatches), (6, 1));
+    }
+
+    #[test]
+    fn test_verify_recording_clean_and_stop_on_first() {
+        let clean = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/recording_clean.ndjson");
+        let report = verify_recording(clean, &InstrumentMap::new()).unwrap();
+        assert!(report.is_ok());
+        assert_eq!(report.checksums_verified, 5);
+        assert_eq!(report.symbols["BTC/USD"].updates, 4);
+
+        // Every checksum mismatches under the wrong precisions; only the first is read
+        let mut instruments = InstrumentMap::new();
+        instruments.insert("BTC/USD".to_string(), InstrumentInfo {
+            symbol: "BTC/USD".to_string(),
+            price_precision: 2,
+            qty_precision: 8,
+            ..Default::default()
+        });
+        let options = VerifyOptions { stop_on_first: true };
+        let report = verify_recording_with_options(RECORDING, &instruments, &options).unwrap();
+        assert_eq!(report.mismatches.len(), 1);
+        assert_eq!(report.mismatches[0].frame_index, 2);
+        assert!(report.stopped_early);
+    }
+
+    #[test]
+    fn test_verify_recording_instrument_override() {
+        // Explicit instruments win over the recorded ones
+        let mut instruments = InstrumentMap::new();
+        instruments.insert("BTC/USD".to_string(), InstrumentInfo {
+            symbol: "BTC/USD".to_string(),
+            price_precision: 2,
+            qty_precision: 8,
+            ..Default::default()
+        });
+        let report = verify_recording(RECORDING, &instruments).unwrap();
+        assert_eq!(report.mismatches.len(), report.checksums_verified);
+        assert_eq!(report.first_divergence.get("BTC/USD"), Some(&2));
+
+        assert!(verify_recording("does/not/exist.ndjson", &InstrumentMap::new()).is_err());
+    }
+
+    #[test]
+    fn test_snapshot_depth() {
+        assert_eq!(snapshot_depth(3), 10);
+        assert_eq!(snapshot_depth(10), 10);
+        assert_eq!(snapshot_depth(11), 25);
+        assert_eq!(snapshot_depth(2000), 2000);
+    }
+}
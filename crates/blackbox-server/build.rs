@@ -0,0 +1,34 @@
+//! Sets BLACKBOX_GIT_HASH to the commit being built and BLACKBOX_BUILD_TIME
+//! to the Unix time of the build, for `GET /config` and `GET /info`. Builds
+//! outside a git checkout (e.g. from a crate tarball) leave the hash unset.
+
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn git(args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8(output.stdout).ok()?.trim().to_string())
+}
+
+fn main() {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    println!("cargo:rustc-env=BLACKBOX_BUILD_TIME={}", now);
+    println!("cargo:rerun-if-changed=src");
+
+    let Some(hash) = git(&["rev-parse", "--short", "HEAD"]) else {
+        return;
+    };
+    println!("cargo:rustc-env=BLACKBOX_GIT_HASH={}", hash);
+
+    // Rebuild when HEAD moves: to another branch, or to a new commit on it
+    if let Some(git_dir) = git(&["rev-parse", "--absolute-git-dir"]).map(PathBuf::from) {
+        println!("cargo:rerun-if-changed={}", git_dir.join("HEAD").display());
+        if let Some(head_ref) = git(&["symbolic-ref", "-q", "HEAD"]) {
+            println!("cargo:rerun-if-changed={}", git_dir.join(head_ref).display());
+        }
+    }
+}
@@ -0,0 +1,147 @@
+//! The one error shape every HTTP route replies with:
+//! `{"code": ..., "message": ..., "detail": ...}`. Clients match on `code`,
+//! which stays stable; `message` is for people and may change; `detail`
+//! carries extra fields when a code has any.
+
+use axum::{
+    extract::{FromRequest, FromRequestParts, Query, Request},
+    http::{request::Parts, StatusCode},
+    response::{IntoResponse, Json, Response},
+};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Stable error codes, as serialized into `code`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// 400: the body or query string doesn't parse
+    InvalidBody,
+    /// 400: it parses, but a value is out of range or unknown
+    InvalidRequest,
+    /// 401: missing or wrong bearer token
+    Unauthorized,
+    /// 404: nothing is known about the symbol on this route
+    SymbolNotFound,
+    /// 404: no such incident, or it has no bundle
+    NoIncident,
+    /// 409: the symbol is already subscribed
+    SymbolAlreadySubscribed,
+    /// 409: the book can't be checksummed yet
+    BookNotReady,
+    /// 409: not connected to a live feed, so subscriptions can't change
+    NotLive,
+    /// 409: already recording
+    RecorderBusy,
+    /// 409: no recording is being replayed
+    ReplayNotActive,
+    /// 422: the fault needs the replayer; `detail.supported` is false
+    FaultReplayOnly,
+    /// 422: Kraken refused the subscription
+    SubscribeRejected,
+    /// 429: the route's rate limit is spent; see `Retry-After`
+    RateLimited,
+    /// 500: reading or writing on our side failed
+    Internal,
+    /// 503: the WebSocket client isn't running, or lost the connection
+    ClientUnavailable,
+    /// 503: started without a metrics recorder
+    MetricsDisabled,
+    /// 504: Kraken didn't answer in time
+    UpstreamTimeout,
+}
+
+impl ErrorCode {
+    pub fn status(self) -> StatusCode {
+        match self {
+            ErrorCode::InvalidBody | ErrorCode::InvalidRequest => StatusCode::BAD_REQUEST,
+            ErrorCode::Unauthorized => StatusCode::UNAUTHORIZED,
+            ErrorCode::SymbolNotFound | ErrorCode::NoIncident => StatusCode::NOT_FOUND,
+            ErrorCode::SymbolAlreadySubscribed
+            | ErrorCode::BookNotReady
+            | ErrorCode::NotLive
+            | ErrorCode::RecorderBusy
+            | ErrorCode::ReplayNotActive => StatusCode::CONFLICT,
+            ErrorCode::FaultReplayOnly | ErrorCode::SubscribeRejected => StatusCode::UNPROCESSABLE_ENTITY,
+            ErrorCode::RateLimited => StatusCode::TOO_MANY_REQUESTS,
+            ErrorCode::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+            ErrorCode::ClientUnavailable | ErrorCode::MetricsDisabled => StatusCode::SERVICE_UNAVAILABLE,
+            ErrorCode::UpstreamTimeout => StatusCode::GATEWAY_TIMEOUT,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ApiError {
+    pub code: ErrorCode,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<serde_json::Value>,
+}
+
+impl ApiError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self { code, message: message.into(), detail: None }
+    }
+
+    pub fn with_detail(mut self, detail: serde_json::Value) -> Self {
+        self.detail = Some(detail);
+        self
+    }
+
+    pub fn symbol_not_found(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::SymbolNotFound, message)
+    }
+
+    pub fn invalid_request(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::InvalidRequest, message)
+    }
+
+    pub fn internal(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::Internal, message)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.code.status(), Json(self)).into_response()
+    }
+}
+
+/// `Json`, rejecting bodies that don't parse with an `ApiError`
+pub struct ApiJson<T>(pub T);
+
+#[axum::async_trait]
+impl<T: DeserializeOwned, S: Send + Sync> FromRequest<S> for ApiJson<T> {
+    type Rejection = ApiError;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, ApiError> {
+        match Json::<T>::from_request(request, state).await {
+            Ok(Json(value)) => Ok(ApiJson(value)),
+            Err(rejection) => Err(ApiError::new(ErrorCode::InvalidBody, rejection.body_text())),
+        }
+    }
+}
+
+/// `Query`, rejecting query strings that don't parse with an `ApiError`
+pub struct ApiQuery<T>(pub T);
+
+#[axum::async_trait]
+impl<T: DeserializeOwned, S: Send + Sync> FromRequestParts<S> for ApiQuery<T> {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, ApiError> {
+        match Query::<T>::from_request_parts(parts, state).await {
+            Ok(Query(value)) => Ok(ApiQuery(value)),
+            Err(rejection) => Err(ApiError::new(ErrorCode::InvalidBody, rejection.body_text())),
+        }
+    }
+}
+
+/// An optional JSON body: empty means `T::default()`
+pub fn optional_json<T: DeserializeOwned + Default>(body: &[u8]) -> Result<T, ApiError> {
+    if body.is_empty() {
+        return Ok(T::default());
+    }
+    serde_json::from_slice(body).map_err(|e| ApiError::new(ErrorCode::InvalidBody, format!("Invalid body: {}", e)))
+}
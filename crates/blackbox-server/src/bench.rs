@@ -0,0 +1,199 @@
+//! `blackbox bench`: orderbook, checksum and replay throughput, for comparing releases
+
+use crate::incident::IncidentManager;
+use crate::state::AppState;
+use blackbox_core::orderbook::{Orderbook, PriceLevels};
+use blackbox_core::precision::PrecisionFormatter;
+use blackbox_core::replayer::Replayer;
+use blackbox_core::types::{ReplayConfig, ReplayMode};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+/// What a bench run did and how fast
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchReport {
+    pub mode: &'static str, // "synthetic" or "recording"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frames: Option<usize>, // recording mode only
+    pub updates: usize,
+    pub verifications: usize,
+    pub elapsed_secs: f64,
+    pub updates_per_sec: f64,
+    pub verifications_per_sec: f64,
+}
+
+fn per_sec(count: usize, elapsed: Duration) -> f64 {
+    count as f64 / elapsed.as_secs_f64().max(f64::EPSILON)
+}
+
+/// xorshift64, so runs are repeatable without a rand dependency
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+}
+
+/// Build a book of `levels` levels per side, then apply `updates` random
+/// single-level updates, verifying the checksum after each. Most updates
+/// change a qty near the top; one in eight deletes a level and adds one at
+/// the back so the book keeps its size.
+pub fn run_synthetic(levels: usize, updates: usize) -> BenchReport {
+    let tick = Decimal::new(1, 1);
+    let mid = Decimal::from(50_000);
+    let mut rng = Rng(0x9E37_79B9_7F4A_7C15);
+    let qty = |rng: &mut Rng| Decimal::new(rng.below(10_000) as i64 + 1, 4);
+    let bids: Vec<_> = (0..levels).map(|i| (mid - tick * Decimal::from(i + 1), qty(&mut rng))).collect();
+    let asks: Vec<_> = (0..levels).map(|i| (mid + tick * Decimal::from(i + 1), qty(&mut rng))).collect();
+    let mut book = Orderbook::new();
+    book.apply_snapshot(bids, asks);
+    let formatter = PrecisionFormatter::new(1, 4);
+
+    // Generated up front so only the book and checksum work is timed
+    let mut deepest = [levels; 2];
+    let batches: Vec<(PriceLevels, PriceLevels)> = (0..updates)
+        .map(|_| {
+            let side = rng.below(2) as usize;
+            let offset = |i: usize| match side {
+                0 => mid - tick * Decimal::from(i),
+                _ => mid + tick * Decimal::from(i),
+            };
+            // Skewed towards the top of the book
+            let spread = rng.below(levels as u64) + 1;
+            let level = 1 + rng.below(spread) as usize;
+            let changes = if rng.below(8) == 0 {
+                deepest[side] += 1;
+                vec![(offset(level), Decimal::ZERO), (offset(deepest[side]), qty(&mut rng))]
+            } else {
+                vec![(offset(level), qty(&mut rng))]
+            };
+            if side == 0 { (changes, vec![]) } else { (vec![], changes) }
+        })
+        .collect();
+
+    let mut apply_time = Duration::ZERO;
+    let mut verify_time = Duration::ZERO;
+    let mut checksum = 0u32;
+    for (bids, asks) in batches {
+        let started = Instant::now();
+        book.apply_updates(bids, asks);
+        let applied = Instant::now();
+        checksum ^= book.checksum_with(&formatter);
+        verify_time += applied.elapsed();
+        apply_time += applied - started;
+    }
+    std::hint::black_box(checksum);
+
+    BenchReport {
+        mode: "synthetic",
+        frames: None,
+        updates,
+        verifications: updates,
+        elapsed_secs: (apply_time + verify_time).as_secs_f64(),
+        updates_per_sec: per_sec(updates, apply_time),
+        verifications_per_sec: per_sec(updates, verify_time),
+    }
+}
+
+/// Push a recording through the live processing pipeline (parse, apply,
+/// verify) as fast as it goes
+pub async fn run_recording(input: &Path) -> anyhow::Result<BenchReport> {
+    let config = ReplayConfig { mode: ReplayMode::AsFast, faults: vec![], loop_playback: false };
+    let mut replayer = Replayer::from_segments(input, config)?;
+    // Incidents for mismatches in the recording are written and thrown away
+    let incidents_dir = std::env::temp_dir().join(format!("blackbox_bench_{}", std::process::id()));
+    let incident_manager = Arc::new(IncidentManager::new(incidents_dir.clone())?);
+    let state = AppState::new();
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let processor_state = state.clone();
+    let started = Instant::now();
+    let processor = tokio::spawn(async move {
+        crate::process_ws_events(&processor_state, &incident_manager, &mut rx).await;
+    });
+    let mut frames = 0;
+    let mut updates = 0;
+    replayer.start();
+    while let Some(frame) = replayer.next_frame() {
+        frames += 1;
+        for event in crate::replay_frame_events(&state, frame, &[]) {
+            if matches!(event, blackbox_ws::client::WsEvent::BookSnapshot { .. } | blackbox_ws::client::WsEvent::BookUpdate { .. }) {
+                updates += 1;
+            }
+            let _ = tx.send(event);
+        }
+    }
+    drop(tx);
+    processor.await?;
+    let elapsed = started.elapsed();
+    let _ = std::fs::remove_dir_all(incidents_dir);
+
+    let verifications = state
+        .health
+        .iter()
+        .map(|health| (health.checksum_ok + health.checksum_fail) as usize)
+        .sum();
+    Ok(BenchReport {
+        mode: "recording",
+        frames: Some(frames),
+        updates,
+        verifications,
+        elapsed_secs: elapsed.as_secs_f64(),
+        updates_per_sec: per_sec(updates, elapsed),
+        verifications_per_sec: per_sec(verifications, elapsed),
+    })
+}
+
+/// Run the recording bench with `input`, else the synthetic one, and print the report
+pub async fn run_bench(input: Option<&Path>, levels: usize, updates: usize, json: bool) -> anyhow::Result<()> {
+    let report = match input {
+        Some(input) => run_recording(input).await?,
+        None => run_synthetic(levels, updates),
+    };
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+    match input {
+        Some(input) => println!("Recording: {} ({} frames)", input.display(), report.frames.unwrap_or(0)),
+        None => println!("Synthetic book: {} levels per side", levels),
+    }
+    println!("  {:>10} book updates       {:>14.0}/s", report.updates, report.updates_per_sec);
+    println!("  {:>10} checksum verifies  {:>14.0}/s", report.verifications, report.verifications_per_sec);
+    println!("  {:>10.3}s total", report.elapsed_secs);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_synthetic_bench() {
+        let report = run_synthetic(100, 2000);
+        assert_eq!((report.updates, report.verifications), (2000, 2000));
+        assert!(report.updates_per_sec > 0.0 && report.verifications_per_sec > 0.0);
+        assert!(report.frames.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_recording_bench() {
+        let fixture = concat!(env!("CARGO_MANIFEST_DIR"), "/../blackbox-core/tests/fixtures/recording_corrupted.ndjson");
+        let report = run_recording(Path::new(fixture)).await.unwrap();
+        assert_eq!(report.frames, Some(9));
+        assert_eq!(report.updates, 6);
+        assert_eq!(report.verifications, 6);
+    }
+}
@@ -0,0 +1,183 @@
+//! `blackbox compare`: rebuild the books of two recordings and report where they diverge
+
+use blackbox_core::diff::BookDiff;
+use blackbox_core::merge::MergePolicy;
+use blackbox_core::orderbook::Orderbook;
+use blackbox_ws::client::{book_event, WsEvent};
+use blackbox_ws::parser::{parse_frame, WsFrame};
+use std::collections::{BTreeMap, BTreeSet};
+use blackbox_core::recorder::{open_recording, parse_recording_line, RecordingLine};
+use std::io::BufRead;
+use std::path::Path;
+use tracing::warn;
+
+/// Final book per symbol after applying every book frame of an NDJSON recording
+pub fn load_books(path: &Path) -> anyhow::Result<BTreeMap<String, Orderbook>> {
+    let reader = open_recording(path)?;
+    let mut books: BTreeMap<String, Orderbook> = BTreeMap::new();
+    
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let RecordingLine::Frame(frame) = parse_recording_line(&line)? else {
+            continue;
+        };
+        // Acks, heartbeats, etc. are irrelevant here
+        let Ok(WsFrame::Book(msg)) = parse_frame(&frame.raw_frame) else {
+            continue;
+        };
+        
+        for data in msg.data {
+            match book_event(&msg.msg_type, data) {
+                WsEvent::BookSnapshot { symbol, bids, asks, .. } => {
+                    let book = books.entry(symbol).or_default();
+                    book.apply_snapshot(bids, asks);
+                    book.set_last_update_ts(frame.ts);
+                }
+                WsEvent::BookUpdate { symbol, bids, asks, .. } => {
+                    // Updates before the first snapshot can't be placed
+                    if let Some(book) = books.get_mut(&symbol) {
+                        book.apply_updates(bids, asks);
+                        book.set_last_update_ts(frame.ts);
+                    }
+                }
+                WsEvent::Error(e) => warn!("{}: {}", path.display(), e),
+                _ => {}
+            }
+        }
+    }
+    
+    Ok(books)
+}
+
+/// How one symbol's book differs between the two recordings
+pub struct SymbolComparison {
+    pub symbol: String,
+    pub in_a: bool,
+    pub in_b: bool,
+    pub diff: Option<BookDiff>,    // only when both recordings have the symbol
+    pub merged: Option<Orderbook>, // newest-wins merge of both books
+}
+
+impl SymbolComparison {
+    pub fn diverged(&self) -> bool {
+        !(self.in_a && self.in_b) || self.diff.as_ref().is_some_and(|d| !d.is_empty())
+    }
+}
+
+pub fn compare_books(
+    a: &BTreeMap<String, Orderbook>,
+    b: &BTreeMap<String, Orderbook>,
+    depth: usize,
+) -> Vec<SymbolComparison> {
+    let symbols: BTreeSet<&String> = a.keys().chain(b.keys()).collect();
+    symbols
+        .into_iter()
+        .map(|symbol| {
+            let (book_a, book_b) = (a.get(symbol), b.get(symbol));
+            let (diff, merged) = match (book_a, book_b) {
+                (Some(ba), Some(bb)) => (Some(ba.diff(bb, depth)), Some(ba.merged_with(bb, MergePolicy::Newest))),
+                _ => (None, None),
+            };
+            SymbolComparison {
+                symbol: symbol.clone(),
+                in_a: book_a.is_some(),
+                in_b: book_b.is_some(),
+                diff,
+                merged,
+            }
+        })
+        .collect()
+}
+
+/// Print a per-symbol divergence report; errors if any symbol diverged
+pub fn run_compare(a: &Path, b: &Path, depth: usize) -> anyhow::Result<()> {
+    let books_a = load_books(a)?;
+    let books_b = load_books(b)?;
+    let report = compare_books(&books_a, &books_b, depth);
+    
+    println!("Comparing top {} levels: A = {}, B = {}", depth, a.display(), b.display());
+    for cmp in &report {
+        match (&cmp.diff, &cmp.merged) {
+            (Some(diff), Some(merged)) if diff.is_empty() => {
+                println!("  {:<12} OK (best bid {:?}, best ask {:?})", cmp.symbol, merged.best_bid(), merged.best_ask());
+            }
+            (Some(diff), Some(merged)) => {
+                println!(
+                    "  {:<12} DIVERGED  bids +{} -{} ~{}  asks +{} -{} ~{}  (merged best bid {:?}, best ask {:?})",
+                    cmp.symbol,
+                    diff.bids.added.len(), diff.bids.removed.len(), diff.bids.changed.len(),
+                    diff.asks.added.len(), diff.asks.removed.len(), diff.asks.changed.len(),
+                    merged.best_bid(), merged.best_ask(),
+                );
+            }
+            _ => {
+                let only = if cmp.in_a { "A" } else { "B" };
+                println!("  {:<12} DIVERGED  only in {}", cmp.symbol, only);
+            }
+        }
+    }
+    
+    let diverged = report.iter().filter(|c| c.diverged()).count();
+    if diverged > 0 {
+        anyhow::bail!("{} of {} symbol(s) diverged", diverged, report.len());
+    }
+    println!("All {} symbol(s) match", report.len());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blackbox_core::types::RecordedFrame;
+    use rust_decimal_macros::dec;
+    use std::fs::File;
+    use std::io::Write;
+
+    fn write_recording(name: &str, frames: &[&str]) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("blackbox_compare_{}_{}.ndjson", name, std::process::id()));
+        let mut file = File::create(&path).unwrap();
+        for raw in frames {
+            let frame = RecordedFrame {
+                ts: chrono::Utc::now(),
+                raw_frame: raw.to_string(),
+                decoded_event: None,
+            };
+            writeln!(file, "{}", serde_json::to_string(&frame).unwrap()).unwrap();
+        }
+        path
+    }
+
+    const SNAPSHOT: &str = r#"{"channel":"book","type":"snapshot","data":[{"symbol":"BTC/USD","bids":[{"price":100.0,"qty":1.0}],"asks":[{"price":101.0,"qty":1.0}],"checksum":0}]}"#;
+    const UPDATE: &str = r#"{"channel":"book","type":"update","data":[{"symbol":"BTC/USD","bids":[{"price":100.0,"qty":2.0}],"asks":[],"checksum":0}]}"#;
+    const OTHER: &str = r#"{"channel":"book","type":"snapshot","data":[{"symbol":"ETH/USD","bids":[{"price":10.0,"qty":1.0}],"asks":[],"checksum":0}]}"#;
+
+    #[test]
+    fn test_compare_recordings() {
+        let a = write_recording("a", &[SNAPSHOT, UPDATE]);
+        let b = write_recording("b", &[SNAPSHOT, r#"{"channel":"heartbeat"}"#, UPDATE, OTHER]);
+        let books_a = load_books(&a).unwrap();
+        let books_b = load_books(&b).unwrap();
+        assert_eq!(books_a["BTC/USD"].best_bid(), Some((dec!(100), dec!(2))));
+        
+        let report = compare_books(&books_a, &books_b, 10);
+        assert_eq!(report.len(), 2);
+        assert_eq!(report[0].symbol, "BTC/USD");
+        assert!(!report[0].diverged());
+        assert_eq!(report[1].symbol, "ETH/USD");
+        assert!(report[1].diverged() && !report[1].in_a);
+        
+        // Same symbol, different quantity
+        let c = write_recording("c", &[SNAPSHOT]);
+        let books_c = load_books(&c).unwrap();
+        let report = compare_books(&books_a, &books_c, 10);
+        assert!(report[0].diverged());
+        assert_eq!(report[0].diff.as_ref().unwrap().bids.changed.len(), 1);
+        
+        for path in [a, b, c] {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
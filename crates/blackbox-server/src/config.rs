@@ -0,0 +1,134 @@
+//! The effective configuration: served on `GET /config` and written as every
+//! incident bundle's `config.json`, so the two can't drift apart
+
+use crate::disk::DiskGuard;
+use crate::http::FaultStatus;
+use crate::incident::IncidentManager;
+use crate::state::AppState;
+use blackbox_core::recorder::Compression;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Version of this build
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+/// Commit this build was made from, if it was built from a git checkout
+pub const GIT_HASH: Option<&str> = option_env!("BLACKBOX_GIT_HASH");
+
+/// When this build was made
+pub fn build_time() -> Option<DateTime<Utc>> {
+    let secs: i64 = env!("BLACKBOX_BUILD_TIME").parse().ok()?;
+    DateTime::from_timestamp(secs, 0)
+}
+
+/// Where the processed frames come from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunMode {
+    #[default]
+    Live,
+    Mock, // generated by the TUI's mock feed
+    Replay,
+    ReplayIncident, // an incident bundle's frames
+}
+
+/// A subscribed symbol and the depth requested for it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SymbolConfig {
+    pub symbol: String,
+    pub depth: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BookConfig {
+    pub track_level_meta: bool,
+    pub strict_book: bool,
+    pub tombstones: usize,
+    pub checksum_levels: Option<usize>, // None = the venue's
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RecordingConfig {
+    pub enabled: bool,
+    pub path: Option<String>, // segment being written
+    pub gzip: bool,
+    pub append: bool,
+    pub channels: Option<Vec<String>>, // None = all
+    pub decoded: bool, // frames are tagged with classify_frame
+    pub rotate_max_bytes: Option<u64>,
+    pub rotate_every_ms: Option<u64>,
+    pub flush_every_frames: Option<u64>,
+    pub flush_interval_ms: Option<u64>,
+    pub disk_guard: Option<DiskGuard>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct IncidentsConfig {
+    pub dir: PathBuf,
+    pub disk_guard: Option<DiskGuard>,
+}
+
+#[derive(Serialize)]
+pub struct RuntimeConfig {
+    pub version: &'static str,
+    pub git_hash: Option<&'static str>,
+    pub mode: RunMode,
+    pub ws_url: Option<String>, // None unless connected to a venue
+    pub ping_interval_ms: Option<u64>,
+    pub symbols: Vec<SymbolConfig>,
+    pub book: BookConfig,
+    pub recording: RecordingConfig,
+    pub incidents: IncidentsConfig,
+    pub fault: FaultStatus,
+    pub generated_at: DateTime<Utc>,
+}
+
+fn millis(duration: Duration) -> u64 {
+    duration.as_millis() as u64
+}
+
+impl RuntimeConfig {
+    pub async fn collect(state: &AppState, incident_manager: &IncidentManager) -> Self {
+        let options = &state.recorder_options;
+        let recording = RecordingConfig {
+            enabled: state.is_recording_enabled().await,
+            path: state.recording_path.read().await.clone(),
+            gzip: options.compression == Compression::Gzip,
+            append: options.append,
+            channels: options.channel_filter.clone(),
+            decoded: state.record_decoded,
+            rotate_max_bytes: options.rotation.max_bytes,
+            rotate_every_ms: options.rotation.max_duration.map(millis),
+            flush_every_frames: options.flush.every_frames,
+            flush_interval_ms: options.flush.interval.map(millis),
+            disk_guard: state.record_disk_guard,
+        };
+        Self {
+            version: VERSION,
+            git_hash: GIT_HASH,
+            mode: state.run_mode,
+            ws_url: state.ws_url.clone(),
+            ping_interval_ms: state.ping_interval.map(millis),
+            symbols: state.symbols_config().await,
+            book: BookConfig {
+                track_level_meta: state.track_level_meta,
+                strict_book: state.strict_book,
+                tombstones: state.tombstones,
+                checksum_levels: state.checksum_levels,
+            },
+            recording,
+            incidents: IncidentsConfig {
+                dir: incident_manager.incidents_dir().to_path_buf(),
+                disk_guard: incident_manager.disk_guard(),
+            },
+            fault: FaultStatus::of(state),
+            generated_at: Utc::now(),
+        }
+    }
+
+    /// As written into incident bundles
+    pub fn to_value(&self) -> serde_json::Value {
+        serde_json::to_value(self).expect("RuntimeConfig always serializes")
+    }
+}
@@ -0,0 +1,131 @@
+//! Disk usage limits for recordings and incident bundles
+
+use serde::Serialize;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::SystemTime;
+use tracing::warn;
+
+/// What a `DiskGuard` does once its limit is exceeded
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DiskPolicy {
+    #[default]
+    Stop, // stop recording, refuse new incident bundles
+    DeleteOldest, // delete the oldest closed files until back under the limit
+}
+
+impl FromStr for DiskPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "stop" => Ok(DiskPolicy::Stop),
+            "delete-oldest" => Ok(DiskPolicy::DeleteOldest),
+            _ => Err(anyhow::anyhow!("Unknown disk policy '{}' (expected stop or delete-oldest)", s)),
+        }
+    }
+}
+
+/// Result of `DiskGuard::enforce`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiskCheck {
+    Ok,
+    Deleted(Vec<PathBuf>), // back under the limit after deleting these
+    Full { used: u64, limit: u64 },
+}
+
+/// A byte limit on a set of files
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct DiskGuard {
+    pub max_bytes: u64,
+    pub policy: DiskPolicy,
+}
+
+impl DiskGuard {
+    pub fn new(max_bytes: u64, policy: DiskPolicy) -> Self {
+        Self { max_bytes, policy }
+    }
+
+    /// Check the on-disk size of `files` (oldest first) against the limit.
+    /// Under `DeleteOldest`, files are deleted by modification time until the
+    /// rest fit, skipping those `in_use`; if they never fit it's still `Full`.
+    pub fn enforce(&self, files: &[PathBuf], in_use: &[PathBuf]) -> DiskCheck {
+        let mut sized: Vec<(SystemTime, &PathBuf, u64)> = files
+            .iter()
+            .filter_map(|path| {
+                let meta = std::fs::metadata(path).ok()?;
+                Some((meta.modified().unwrap_or(SystemTime::UNIX_EPOCH), path, meta.len()))
+            })
+            .collect();
+        let mut used: u64 = sized.iter().map(|(_, _, len)| len).sum();
+        if used <= self.max_bytes {
+            return DiskCheck::Ok;
+        }
+        if self.policy == DiskPolicy::Stop {
+            return DiskCheck::Full { used, limit: self.max_bytes };
+        }
+
+        // Stable, so files modified together keep the given order
+        sized.sort_by_key(|(modified, _, _)| *modified);
+        let mut deleted = Vec::new();
+        for (_, path, len) in sized {
+            if used <= self.max_bytes {
+                break;
+            }
+            if in_use.contains(path) {
+                continue;
+            }
+            match std::fs::remove_file(path) {
+                Ok(()) => {
+                    used -= len;
+                    deleted.push(path.clone());
+                }
+                Err(e) => warn!("Failed to delete {}: {}", path.display(), e),
+            }
+        }
+        if used > self.max_bytes {
+            DiskCheck::Full { used, limit: self.max_bytes }
+        } else {
+            DiskCheck::Deleted(deleted)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delete_oldest() {
+        let dir = std::env::temp_dir().join(format!("blackbox_disk_guard_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let files: Vec<PathBuf> = (0..5).map(|i| dir.join(format!("segment.{:04}.ndjson", i))).collect();
+        for file in &files {
+            std::fs::write(file, vec![b'x'; 100]).unwrap();
+        }
+
+        assert_eq!(DiskGuard::new(500, DiskPolicy::DeleteOldest).enforce(&files, &[]), DiskCheck::Ok);
+        // Stop never deletes
+        assert_eq!(
+            DiskGuard::new(250, DiskPolicy::Stop).enforce(&files, &[]),
+            DiskCheck::Full { used: 500, limit: 250 }
+        );
+        assert!(files.iter().all(|f| f.exists()));
+
+        // The oldest closed files go first; the open one is kept
+        let guard = DiskGuard::new(250, DiskPolicy::DeleteOldest);
+        let in_use = [files[0].clone()];
+        assert_eq!(guard.enforce(&files, &in_use), DiskCheck::Deleted(vec![files[1].clone(), files[2].clone(), files[3].clone()]));
+        assert!(files[0].exists() && files[4].exists());
+
+        // Nothing left to delete
+        let guard = DiskGuard::new(50, DiskPolicy::DeleteOldest);
+        assert_eq!(guard.enforce(&files, &files), DiskCheck::Full { used: 200, limit: 50 });
+
+        assert_eq!("delete-oldest".parse::<DiskPolicy>().unwrap(), DiskPolicy::DeleteOldest);
+        assert!("wipe".parse::<DiskPolicy>().is_err());
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,246 @@
+//! The event log on disk: every `UiEventLogEntry` is appended to an NDJSON
+//! file as it's logged, and the last `EVENT_LOG_CAPACITY` entries are loaded
+//! back on startup, so the TUI and `/events` pick up where the previous run
+//! (or crash) left off. The file is rotated to `<file>.1` once it grows past
+//! `MAX_FILE_BYTES`; only the current file and that one are kept.
+
+use crate::state::{AppState, UiEventLogEntry, EVENT_LOG_CAPACITY};
+use anyhow::Context;
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use tokio::sync::broadcast::error::RecvError;
+use tracing::warn;
+
+/// Where events are kept when no --events-file is given
+pub const DEFAULT_EVENTS_FILE: &str = "events.ndjson";
+
+/// Size at which the file is rotated
+pub const MAX_FILE_BYTES: u64 = 16 * 1024 * 1024;
+
+/// The rotated-out file next to `path`
+fn rotated_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".1");
+    PathBuf::from(name)
+}
+
+/// The last `limit` entries of the rotated file and then `path`, oldest
+/// first. Lines that don't parse (a write cut short by a crash, say) are
+/// skipped with a warning; missing files are empty.
+pub fn load(path: &Path, limit: usize) -> anyhow::Result<Vec<UiEventLogEntry>> {
+    let mut entries = VecDeque::with_capacity(limit);
+    let mut skipped = 0;
+    for file_path in [rotated_path(path), path.to_path_buf()] {
+        let file = match File::open(&file_path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e).with_context(|| format!("Failed to open {}", file_path.display())),
+        };
+        for line in BufReader::new(file).lines() {
+            let line = line.with_context(|| format!("Failed to read {}", file_path.display()))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str(&line) {
+                Ok(entry) => {
+                    if entries.len() == limit {
+                        entries.pop_front();
+                    }
+                    if limit > 0 {
+                        entries.push_back(entry);
+                    }
+                }
+                Err(_) => skipped += 1,
+            }
+        }
+    }
+    if skipped > 0 {
+        warn!("Skipped {} unreadable line(s) in {}", skipped, path.display());
+    }
+    Ok(entries.into())
+}
+
+/// Appends entries to the events file, rotating it when it gets too big
+pub struct EventWriter {
+    path: PathBuf,
+    file: File,
+    bytes: u64,
+    max_bytes: u64,
+}
+
+impl EventWriter {
+    pub fn open(path: PathBuf, max_bytes: u64) -> anyhow::Result<Self> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(&path)
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+        let mut bytes = file.metadata()?.len();
+        // Don't glue the first new entry onto a line a crash cut short
+        if bytes > 0 {
+            let mut last = [0u8; 1];
+            file.seek(SeekFrom::End(-1))?;
+            file.read_exact(&mut last)?;
+            if last[0] != b'\n' {
+                file.write_all(b"\n")?;
+                bytes += 1;
+            }
+        }
+        Ok(Self { path, file, bytes, max_bytes })
+    }
+
+    /// Write one entry, flushed so it survives a crash right after
+    pub fn append(&mut self, entry: &UiEventLogEntry) -> anyhow::Result<()> {
+        if self.bytes >= self.max_bytes {
+            self.rotate()?;
+        }
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+        self.file.write_all(&line)?;
+        self.file.flush()?;
+        self.bytes += line.len() as u64;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> anyhow::Result<()> {
+        let rotated = rotated_path(&self.path);
+        std::fs::rename(&self.path, &rotated)
+            .with_context(|| format!("Failed to rotate {} to {}", self.path.display(), rotated.display()))?;
+        self.file = OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(&self.path)
+            .with_context(|| format!("Failed to open {}", self.path.display()))?;
+        self.bytes = 0;
+        Ok(())
+    }
+}
+
+/// Put `entries` back into the in-memory log ahead of anything new, without
+/// publishing them as fresh events
+pub async fn restore(state: &AppState, entries: Vec<UiEventLogEntry>) {
+    let mut log = state.event_log.write().await;
+    for entry in entries.into_iter().rev() {
+        if log.len() >= EVENT_LOG_CAPACITY {
+            break;
+        }
+        log.push_front(entry);
+    }
+}
+
+/// Reload the last run's events from `path`, then append every event
+/// `state` logs from now on. Writing happens on its own task, so
+/// `push_event` never waits on the disk.
+pub async fn persist(state: &AppState, path: PathBuf) -> anyhow::Result<()> {
+    let entries = load(&path, EVENT_LOG_CAPACITY)?;
+    let mut writer = EventWriter::open(path.clone(), MAX_FILE_BYTES)?;
+    // Subscribed before the reload is visible, so nothing logged in between is missed
+    let mut events = state.subscribe_events();
+    restore(state, entries).await;
+    tokio::spawn(async move {
+        loop {
+            match events.recv().await {
+                Ok(entry) => {
+                    if let Err(e) = writer.append(&entry) {
+                        warn!("Failed to persist event to {}: {:#}", path.display(), e);
+                    }
+                }
+                Err(RecvError::Lagged(missed)) => warn!("{} event(s) not persisted to {}", missed, path.display()),
+                Err(RecvError::Closed) => break,
+            }
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::UiEvent;
+    use chrono::{TimeZone, Utc};
+
+    fn entry(second: u32) -> UiEventLogEntry {
+        UiEventLogEntry {
+            timestamp: Utc.with_ymd_and_hms(2026, 1, 1, 0, 0, second).unwrap(),
+            event: UiEvent::ChecksumOk { symbol: format!("S{}", second) },
+        }
+    }
+
+    fn symbols(entries: &[UiEventLogEntry]) -> Vec<String> {
+        entries.iter().map(|e| e.event.symbol().unwrap().to_string()).collect()
+    }
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("blackbox_event_store_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn test_reload_keeps_order_across_rotation() {
+        let dir = test_dir("order");
+        let path = dir.join("events.ndjson");
+        // Small enough to rotate every few entries
+        let mut writer = EventWriter::open(path.clone(), 200).unwrap();
+        for second in 0..10 {
+            writer.append(&entry(second)).unwrap();
+        }
+        assert!(rotated_path(&path).exists());
+
+        // Three entries per file; older rotations are gone, the rest reads back oldest first
+        assert_eq!(symbols(&load(&path, 100).unwrap()), ["S6", "S7", "S8", "S9"]);
+        assert_eq!(symbols(&load(&path, 2).unwrap()), ["S8", "S9"]);
+        assert!(load(&dir.join("missing.ndjson"), 10).unwrap().is_empty());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_corrupt_lines_are_skipped() {
+        let dir = test_dir("corrupt");
+        let path = dir.join("events.ndjson");
+        let mut writer = EventWriter::open(path.clone(), MAX_FILE_BYTES).unwrap();
+        writer.append(&entry(1)).unwrap();
+        drop(writer);
+        // Garbage, then a line cut short by a crash
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"not json\n{\"timestamp\":\"2026-01-01T00:00:02Z\",\"ev").unwrap();
+        drop(file);
+
+        // Reopening starts on a fresh line, so the next entry still reads back
+        let mut writer = EventWriter::open(path.clone(), MAX_FILE_BYTES).unwrap();
+        writer.append(&entry(3)).unwrap();
+        assert_eq!(symbols(&load(&path, 10).unwrap()), ["S1", "S3"]);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_events_survive_a_restart() {
+        let dir = test_dir("restart");
+        let path = dir.join("events.ndjson");
+        let before = AppState::new();
+        persist(&before, path.clone()).await.unwrap();
+        before.push_event(UiEvent::Connected).await;
+        before.push_event(UiEvent::ChecksumMismatch { symbol: "BTC/USD".to_string() }).await;
+        // Let the writer task catch up
+        for _ in 0..100 {
+            if load(&path, 10).unwrap().len() == 2 {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+
+        let after = AppState::new();
+        persist(&after, path.clone()).await.unwrap();
+        after.push_event(UiEvent::Disconnected).await;
+        let kinds: Vec<_> = after.get_events(10).await.iter().map(|e| e.event.kind()).collect();
+        assert_eq!(kinds, ["connected", "checksum_mismatch", "disconnected"]);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,289 @@
+//! The UI event log over HTTP
+//!
+//! `/events` pages through the log, newest first. `/events/stream` sends
+//! events as Server-Sent Events: each is a `data:` line holding a
+//! `UiEventLogEntry` as JSON. Both take `?symbol=BTC/USD` to keep only events
+//! about that symbol and a list of kinds (`?type=` on `/events`, `?types=` on
+//! the stream) where `resync` matches both `resync_started` and
+//! `resync_done`. `?backfill=50` first replays the last 50 matching events
+//! from the log onto the stream.
+
+use crate::api_error::ApiQuery;
+use crate::incident::IncidentManager;
+use crate::state::{AppState, UiEvent, UiEventLogEntry};
+use axum::extract::State;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::Json;
+use chrono::{DateTime, Utc};
+use futures_util::stream::{self, Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+
+/// Comment sent on idle streams so proxies don't close them
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Page size of `/events` without `?limit`
+const DEFAULT_PAGE: usize = 100;
+
+#[derive(Deserialize)]
+pub struct EventsQuery {
+    limit: Option<usize>,
+    before: Option<DateTime<Utc>>, // only events strictly older, e.g. the last page's next_before
+    symbol: Option<String>,
+    #[serde(rename = "type")]
+    kind: Option<String>, // comma-separated kinds or kind prefixes
+}
+
+/// One page of `/events`
+#[derive(Debug, Serialize)]
+pub struct EventsPage {
+    pub events: Vec<EventItem>, // newest first
+    pub next_before: Option<DateTime<Utc>>, // `before` for the next page; None on the last one
+}
+
+#[derive(Debug, Serialize)]
+pub struct EventItem {
+    pub timestamp: DateTime<Utc>,
+    pub kind: &'static str, // `UiEvent::kind`
+    pub symbol: Option<String>,
+    pub event: UiEvent, // variant name -> fields, as in the event log
+}
+
+impl From<UiEventLogEntry> for EventItem {
+    fn from(entry: UiEventLogEntry) -> Self {
+        Self {
+            timestamp: entry.timestamp,
+            kind: entry.event.kind(),
+            symbol: entry.event.symbol().map(str::to_string),
+            event: entry.event,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct EventStreamQuery {
+    symbol: Option<String>,
+    types: Option<String>, // comma-separated kinds or kind prefixes
+    backfill: Option<usize>,
+}
+
+struct EventFilter {
+    symbol: Option<String>,
+    types: Vec<String>, // empty = all
+}
+
+impl EventFilter {
+    fn new(symbol: Option<String>, types: Option<&str>) -> Self {
+        let types = types
+            .map(|t| t.split(',').map(|k| k.trim().to_string()).filter(|k| !k.is_empty()).collect())
+            .unwrap_or_default();
+        Self { symbol, types }
+    }
+
+    fn matches(&self, event: &UiEvent) -> bool {
+        let kind = event.kind();
+        let symbol_ok = self.symbol.as_deref().is_none_or(|symbol| event.symbol() == Some(symbol));
+        let type_ok = self.types.is_empty()
+            || self.types.iter().any(|t| {
+                kind == t || kind.strip_prefix(t.as_str()).is_some_and(|rest| rest.starts_with('_'))
+            });
+        symbol_ok && type_ok
+    }
+}
+
+/// Page through the event log, newest first. Events sharing the timestamp of
+/// a page's last one are on that page, so none are skipped between pages.
+pub async fn events_handler(
+    State((state, _)): State<(AppState, Arc<IncidentManager>)>,
+    ApiQuery(query): ApiQuery<EventsQuery>,
+) -> Json<EventsPage> {
+    let filter = EventFilter::new(query.symbol, query.kind.as_deref());
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE);
+    let mut matching = state.get_events(usize::MAX).await
+        .into_iter()
+        .rev()
+        .filter(|entry| query.before.is_none_or(|before| entry.timestamp < before))
+        .filter(|entry| filter.matches(&entry.event))
+        .peekable();
+
+    let mut events: Vec<EventItem> = Vec::new();
+    while let Some(entry) = matching.next_if(|_| events.len() < limit) {
+        events.push(entry.into());
+    }
+    // Keep the last timestamp whole, else the next page's `before` would skip the rest of it
+    while let Some(entry) = matching.next_if(|entry| events.last().is_some_and(|last| last.timestamp == entry.timestamp)) {
+        events.push(entry.into());
+    }
+    let next_before = match matching.peek() {
+        Some(_) => events.last().map(|last| last.timestamp),
+        None => None,
+    };
+    Json(EventsPage { events, next_before })
+}
+
+fn to_sse(entry: &UiEventLogEntry) -> Event {
+    Event::default()
+        .json_data(entry)
+        .unwrap_or_else(|e| Event::default().comment(format!("failed to serialize event: {}", e)))
+}
+
+pub async fn events_stream_handler(
+    State((state, _)): State<(AppState, Arc<IncidentManager>)>,
+    ApiQuery(query): ApiQuery<EventStreamQuery>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let filter = EventFilter::new(query.symbol, query.types.as_deref());
+    // Subscribe before reading the log so nothing pushed in between is lost
+    let live = state.subscribe_events();
+
+    let mut backfill: Vec<UiEventLogEntry> = state.get_events(usize::MAX).await
+        .into_iter()
+        .filter(|entry| filter.matches(&entry.event))
+        .collect();
+    let backfill = backfill.split_off(backfill.len().saturating_sub(query.backfill.unwrap_or(0)));
+    let last_backfilled = backfill.last().map(|entry| entry.timestamp);
+    let backfill = stream::iter(backfill.into_iter().map(|entry| Ok(to_sse(&entry))));
+
+    let live = stream::unfold((live, filter), move |(mut live, filter)| async move {
+        loop {
+            let event = match live.recv().await {
+                // Also logged before the backfill was read
+                Ok(entry) if last_backfilled.is_some_and(|ts| entry.timestamp <= ts) => continue,
+                Ok(entry) if filter.matches(&entry.event) => to_sse(&entry),
+                Ok(_) => continue,
+                Err(RecvError::Lagged(missed)) => Event::default().comment(format!("missed {} events", missed)),
+                Err(RecvError::Closed) => return None,
+            };
+            return Some((Ok(event), (live, filter)));
+        }
+    });
+
+    Sse::new(backfill.chain(live)).keep_alive(KeepAlive::new().interval(HEARTBEAT_INTERVAL).text("heartbeat"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::{header, Request};
+    use tower::ServiceExt;
+
+    fn btc() -> String {
+        "BTC/USD".to_string()
+    }
+
+    #[tokio::test]
+    async fn test_events_arrive_on_the_stream() {
+        let state = AppState::new();
+        let incidents_dir = std::env::temp_dir().join(format!("blackbox_events_{}", std::process::id()));
+        let incident_manager = Arc::new(IncidentManager::new(incidents_dir.clone()).unwrap());
+        state.push_event(UiEvent::ChecksumMismatch { symbol: btc() }).await;
+        state.push_event(UiEvent::ChecksumMismatch { symbol: "ETH/USD".to_string() }).await;
+        state.push_event(UiEvent::ResyncStarted { symbol: btc() }).await;
+
+        let app = crate::http::router(state.clone(), incident_manager);
+        let request = Request::get("/events/stream?symbol=BTC/USD&types=checksum_mismatch,resync&backfill=1")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "text/event-stream");
+        let mut body = response.into_body().into_data_stream();
+
+        state.push_event(UiEvent::ChecksumMismatch { symbol: "ETH/USD".to_string() }).await; // other symbol
+        state.push_event(UiEvent::ChecksumOk { symbol: btc() }).await; // other type
+        state.push_event(UiEvent::Connected).await; // no symbol
+        state.push_event(UiEvent::ChecksumMismatch { symbol: btc() }).await;
+
+        let mut text = String::new();
+        while text.matches("data:").count() < 2 {
+            let chunk = tokio::time::timeout(Duration::from_secs(5), body.next()).await.unwrap().unwrap().unwrap();
+            text.push_str(std::str::from_utf8(&chunk).unwrap());
+        }
+        let entries: Vec<serde_json::Value> = text.lines()
+            .filter_map(|line| line.strip_prefix("data:"))
+            .map(|data| serde_json::from_str(data.trim()).unwrap())
+            .collect();
+        // Backfill holds only the newest match, then live events follow
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0]["event"]["ResyncStarted"]["symbol"], "BTC/USD");
+        assert_eq!(entries[1]["event"]["ChecksumMismatch"]["symbol"], "BTC/USD");
+        assert!(entries[1]["timestamp"].is_string());
+        let _ = std::fs::remove_dir_all(incidents_dir);
+    }
+
+    #[tokio::test]
+    async fn test_events_pages() {
+        let state = AppState::new();
+        let incidents_dir = std::env::temp_dir().join(format!("blackbox_events_pages_{}", std::process::id()));
+        let app = crate::http::router(state.clone(), Arc::new(IncidentManager::new(incidents_dir.clone()).unwrap()));
+        {
+            let ts = |secs: i64| DateTime::from_timestamp(1_700_000_000 + secs, 0).unwrap();
+            let mut log = state.event_log.write().await;
+            log.push_back(UiEventLogEntry { timestamp: ts(0), event: UiEvent::ChecksumMismatch { symbol: btc() } });
+            log.push_back(UiEventLogEntry { timestamp: ts(1), event: UiEvent::ChecksumOk { symbol: btc() } });
+            log.push_back(UiEventLogEntry { timestamp: ts(2), event: UiEvent::ChecksumMismatch { symbol: "ETH/USD".to_string() } });
+            log.push_back(UiEventLogEntry { timestamp: ts(3), event: UiEvent::ChecksumMismatch { symbol: btc() } });
+            log.push_back(UiEventLogEntry { timestamp: ts(3), event: UiEvent::ResyncStarted { symbol: btc() } });
+            log.push_back(UiEventLogEntry { timestamp: ts(4), event: UiEvent::ChecksumMismatch { symbol: btc() } });
+            log.push_back(UiEventLogEntry { timestamp: ts(5), event: UiEvent::Connected });
+        }
+        let get = |uri: &str| {
+            let request = Request::get(uri).body(Body::empty()).unwrap();
+            let app = app.clone();
+            async move {
+                let response = app.oneshot(request).await.unwrap();
+                let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                serde_json::from_slice::<serde_json::Value>(&bytes).unwrap()
+            }
+        };
+
+        let page = get("/events?limit=1&symbol=BTC/USD&type=checksum_mismatch").await;
+        assert_eq!(page, serde_json::json!({
+            "events": [{
+                "timestamp": "2023-11-14T22:13:24Z",
+                "kind": "checksum_mismatch",
+                "symbol": "BTC/USD",
+                "event": {"ChecksumMismatch": {"symbol": "BTC/USD"}},
+            }],
+            "next_before": "2023-11-14T22:13:24Z",
+        }));
+        let page = get("/events?limit=1&symbol=BTC/USD&type=checksum_mismatch&before=2023-11-14T22:13:24Z").await;
+        assert_eq!(page["events"][0]["timestamp"], "2023-11-14T22:13:23Z");
+        let page = get("/events?symbol=BTC/USD&type=checksum_mismatch&before=2023-11-14T22:13:23Z").await;
+        assert_eq!(page["events"][0]["timestamp"], "2023-11-14T22:13:20Z");
+        assert_eq!(page["next_before"], serde_json::Value::Null);
+
+        // Both events at :23 land on one page
+        let page = get("/events?limit=2&symbol=BTC/USD").await;
+        let kinds: Vec<&str> = page["events"].as_array().unwrap().iter().map(|e| e["kind"].as_str().unwrap()).collect();
+        assert_eq!(kinds, ["checksum_mismatch", "resync_started", "checksum_mismatch"]);
+        assert_eq!(page["next_before"], "2023-11-14T22:13:23Z");
+
+        let page = get("/events").await;
+        assert_eq!(page["events"].as_array().unwrap().len(), 7);
+        assert_eq!(page["events"][0]["kind"], "connected");
+        assert_eq!(page["events"][0]["symbol"], serde_json::Value::Null);
+        let _ = std::fs::remove_dir_all(incidents_dir);
+    }
+
+    #[test]
+    fn test_event_filter() {
+        let all = EventFilter::new(None, None);
+        assert!(all.matches(&UiEvent::Connected));
+
+        let filter = EventFilter::new(Some(btc()), Some("checksum_mismatch, incident"));
+        assert!(filter.matches(&UiEvent::ChecksumMismatch { symbol: btc() }));
+        assert!(!filter.matches(&UiEvent::ChecksumMismatch { symbol: "ETH/USD".to_string() }));
+        assert!(!filter.matches(&UiEvent::ChecksumOk { symbol: btc() }));
+        // Incidents aren't tied to a symbol
+        assert!(!filter.matches(&UiEvent::IncidentCaptured { id: "1".to_string(), reason: "x".to_string() }));
+
+        let filter = EventFilter::new(None, Some("incident"));
+        assert!(filter.matches(&UiEvent::IncidentCaptured { id: "1".to_string(), reason: "x".to_string() }));
+        assert!(filter.matches(&UiEvent::IncidentExported { path: "a.zip".to_string() }));
+        // Prefixes only match whole words
+        assert!(!EventFilter::new(None, Some("checksum_m")).matches(&UiEvent::ChecksumMismatch { symbol: btc() }));
+    }
+}
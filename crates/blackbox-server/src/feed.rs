@@ -0,0 +1,295 @@
+//! `/ws`: the normalized book, health and UI events as JSON over a WebSocket
+//!
+//! Clients send `{"type": "subscribe", "symbols": ["BTC/USD"]}` (no symbols
+//! means all) and get a `book_update` with `"snapshot": true` per book held,
+//! then a `book_update` per applied change, `health` every second and `event`
+//! for each logged UI event. Clients that fall more than the broadcast
+//! capacity behind are disconnected instead of holding up the processor.
+
+use crate::incident::IncidentManager;
+use crate::state::{AppState, BookChange, UiEventLogEntry};
+use axum::extract::ws::{close_code, CloseFrame, Message, WebSocket, WebSocketUpgrade};
+use axum::extract::State;
+use axum::response::Response;
+use blackbox_core::health::OverallHealth;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::warn;
+
+/// How often a subscribed connection gets a `health` message
+const HEALTH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Server -> client
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FeedMessage {
+    BookUpdate {
+        snapshot: bool, // the book as held on subscribe, then false for each change
+        #[serde(flatten)]
+        change: BookChange,
+    },
+    Health(OverallHealth),
+    Event(UiEventLogEntry),
+    Error { message: String },
+}
+
+/// Client -> server
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ClientMessage {
+    Subscribe {
+        #[serde(default)]
+        symbols: Vec<String>,
+    },
+}
+
+/// A connection's symbol filter
+struct Subscription {
+    symbols: Vec<String>, // empty = all
+    // Seq of each snapshot sent; changes at or below it were already queued
+    // when it was taken. Cleared by the first newer change.
+    snapshot_seqs: HashMap<String, u64>,
+}
+
+impl Subscription {
+    fn new(symbols: Vec<String>) -> Self {
+        Self { symbols, snapshot_seqs: HashMap::new() }
+    }
+
+    fn wants(&self, symbol: &str) -> bool {
+        self.symbols.is_empty() || self.symbols.iter().any(|s| s == symbol)
+    }
+
+    /// Snapshot messages for the wanted books, remembering their seqs
+    fn snapshots(&mut self, state: &AppState) -> Vec<FeedMessage> {
+        let mut changes: Vec<BookChange> = state.orderbooks.iter()
+            .filter(|entry| self.wants(entry.key()))
+            .map(|entry| BookChange::from_book(entry.key(), &entry.value().book))
+            .collect();
+        changes.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+        self.snapshot_seqs = changes.iter().map(|c| (c.symbol.clone(), c.seq)).collect();
+        changes.into_iter().map(|change| FeedMessage::BookUpdate { snapshot: true, change }).collect()
+    }
+
+    fn forward(&mut self, change: &BookChange) -> bool {
+        if !self.wants(&change.symbol) {
+            return false;
+        }
+        match self.snapshot_seqs.get(&change.symbol) {
+            Some(&seq) if change.seq <= seq => false,
+            Some(_) => {
+                self.snapshot_seqs.remove(&change.symbol);
+                true
+            }
+            None => true,
+        }
+    }
+
+    /// Events about other symbols are filtered; ones about no symbol always pass
+    fn wants_event(&self, entry: &UiEventLogEntry) -> bool {
+        entry.event.symbol().is_none_or(|symbol| self.wants(symbol))
+    }
+}
+
+pub async fn ws_handler(
+    ws: WebSocketUpgrade,
+    State((state, _)): State<(AppState, Arc<IncidentManager>)>,
+) -> Response {
+    ws.on_upgrade(move |socket| serve_feed(socket, state))
+}
+
+async fn serve_feed(mut socket: WebSocket, state: AppState) {
+    // Subscribe before any snapshot is taken so no change falls in between
+    let mut changes = state.subscribe_book_changes();
+    let mut events = state.subscribe_events();
+    let mut subscription: Option<Subscription> = None;
+    let mut health = tokio::time::interval(HEALTH_INTERVAL);
+
+    loop {
+        let outgoing = tokio::select! {
+            incoming = socket.recv() => match incoming {
+                Some(Ok(Message::Text(text))) => match serde_json::from_str::<ClientMessage>(&text) {
+                    Ok(ClientMessage::Subscribe { symbols }) => {
+                        let sub = subscription.insert(Subscription::new(symbols));
+                        let mut messages = sub.snapshots(&state);
+                        messages.push(FeedMessage::Health(state.overall_health_where(|s| sub.wants(s))));
+                        health.reset();
+                        messages
+                    }
+                    Err(e) => vec![FeedMessage::Error { message: format!("Invalid message: {}", e) }],
+                },
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => continue, // axum answers pings
+            },
+            change = changes.recv() => match change {
+                Ok(change) if subscription.as_mut().is_some_and(|sub| sub.forward(&change)) => {
+                    vec![FeedMessage::BookUpdate { snapshot: false, change }]
+                }
+                Ok(_) => continue,
+                Err(RecvError::Lagged(missed)) => return close_lagged(socket, missed).await,
+                Err(RecvError::Closed) => break,
+            },
+            event = events.recv() => match event {
+                Ok(entry) => match &subscription {
+                    Some(sub) if sub.wants_event(&entry) => vec![FeedMessage::Event(entry)],
+                    _ => continue,
+                },
+                Err(RecvError::Lagged(missed)) => return close_lagged(socket, missed).await,
+                Err(RecvError::Closed) => break,
+            },
+            _ = health.tick() => match &subscription {
+                Some(sub) => vec![FeedMessage::Health(state.overall_health_where(|s| sub.wants(s)))],
+                None => continue,
+            },
+        };
+
+        for message in outgoing {
+            let text = match serde_json::to_string(&message) {
+                Ok(text) => text,
+                Err(e) => {
+                    warn!("Failed to serialize feed message: {}", e);
+                    continue;
+                }
+            };
+            if socket.send(Message::Text(text)).await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+async fn close_lagged(mut socket: WebSocket, missed: u64) {
+    warn!("Disconnecting /ws client that fell {} messages behind", missed);
+    let frame = CloseFrame {
+        code: close_code::POLICY,
+        reason: format!("Too slow: missed {} messages", missed).into(),
+    };
+    let _ = socket.send(Message::Close(Some(frame))).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::UiEvent;
+    use blackbox_ws::client::WsEvent;
+    use futures_util::{SinkExt, StreamExt};
+    use rust_decimal_macros::dec;
+    use tokio::sync::mpsc;
+    use tokio_tungstenite::tungstenite::Message as ClientFrame;
+
+    fn book_event(symbol: &str, snapshot: bool, bid: rust_decimal::Decimal) -> WsEvent {
+        let symbol = symbol.to_string();
+        let bids = vec![(bid, dec!(1))];
+        let asks = vec![(dec!(200), dec!(1))];
+        if snapshot {
+            WsEvent::BookSnapshot { symbol, bids, asks, checksum: None }
+        } else {
+            WsEvent::BookUpdate { symbol, bids, asks: vec![], checksum: None, timestamp: None }
+        }
+    }
+
+    async fn next_json<S>(client: &mut S) -> serde_json::Value
+    where
+        S: StreamExt<Item = Result<ClientFrame, tokio_tungstenite::tungstenite::Error>> + Unpin,
+    {
+        loop {
+            let frame = tokio::time::timeout(Duration::from_secs(5), client.next()).await.unwrap().unwrap().unwrap();
+            if let ClientFrame::Text(text) = frame {
+                return serde_json::from_str(&text).unwrap();
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_feed_sends_snapshot_then_updates() {
+        let state = AppState::new();
+        let incidents_dir = std::env::temp_dir().join(format!("blackbox_feed_{}", std::process::id()));
+        let incident_manager = Arc::new(IncidentManager::new(incidents_dir.clone()).unwrap());
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let processor_state = state.clone();
+        let processor_incidents = incident_manager.clone();
+        let processor = tokio::spawn(async move {
+            crate::process_ws_events(&processor_state, &processor_incidents, &mut rx).await;
+        });
+        tx.send(book_event("BTC/USD", true, dec!(100))).unwrap();
+        tx.send(book_event("ETH/USD", true, dec!(10))).unwrap();
+        while state.orderbooks.len() < 2 {
+            tokio::task::yield_now().await;
+        }
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = crate::http::router(state.clone(), incident_manager);
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let (mut client, _) = tokio_tungstenite::connect_async(format!("ws://{}/ws", addr)).await.unwrap();
+        client.send(ClientFrame::Text(r#"{"type":"subscribe","symbols":["BTC/USD"]}"#.to_string())).await.unwrap();
+
+        let snapshot = next_json(&mut client).await;
+        assert_eq!(snapshot["type"], "book_update");
+        assert_eq!(snapshot["snapshot"], true);
+        assert_eq!(snapshot["symbol"], "BTC/USD");
+        assert_eq!(snapshot["seq"], 1);
+        let health = next_json(&mut client).await;
+        assert_eq!(health["type"], "health");
+
+        tx.send(book_event("ETH/USD", false, dec!(11))).unwrap(); // filtered out
+        tx.send(book_event("BTC/USD", false, dec!(101))).unwrap();
+        tx.send(book_event("BTC/USD", false, dec!(102))).unwrap();
+        state.push_event(UiEvent::ChecksumMismatch { symbol: "ETH/USD".to_string() }).await;
+        state.push_event(UiEvent::ChecksumMismatch { symbol: "BTC/USD".to_string() }).await;
+
+        let mut updates = Vec::new();
+        let mut events = Vec::new();
+        while updates.len() < 2 || events.is_empty() {
+            let message = next_json(&mut client).await;
+            match message["type"].as_str().unwrap() {
+                "book_update" => updates.push(message),
+                // Top-of-book events come too; only the mismatches were pushed by hand
+                "event" if message["event"].get("ChecksumMismatch").is_some() => events.push(message),
+                "event" | "health" => {}
+                other => panic!("unexpected message type {}", other),
+            }
+        }
+        assert!(updates.iter().all(|u| u["symbol"] == "BTC/USD" && u["snapshot"] == false));
+        assert_eq!(updates[0]["seq"], 2);
+        assert_eq!(updates[0]["best_bid"][0], "101");
+        assert_eq!(updates[1]["seq"], 3);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0]["event"]["ChecksumMismatch"]["symbol"], "BTC/USD");
+
+        client.send(ClientFrame::Text("not json".to_string())).await.unwrap();
+        loop {
+            let message = next_json(&mut client).await;
+            if message["type"] == "error" {
+                break;
+            }
+        }
+
+        drop(tx);
+        processor.await.unwrap();
+        let _ = std::fs::remove_dir_all(incidents_dir);
+    }
+
+    #[test]
+    fn test_changes_queued_before_the_snapshot_are_skipped() {
+        let change = |symbol: &str, seq| BookChange {
+            symbol: symbol.to_string(),
+            best_bid: None,
+            best_ask: None,
+            seq,
+            ts: chrono::Utc::now(),
+        };
+        let mut sub = Subscription::new(vec!["BTC/USD".to_string()]);
+        sub.snapshot_seqs.insert("BTC/USD".to_string(), 5);
+        assert!(!sub.forward(&change("ETH/USD", 9)));
+        assert!(!sub.forward(&change("BTC/USD", 4)));
+        assert!(!sub.forward(&change("BTC/USD", 5)));
+        assert!(sub.forward(&change("BTC/USD", 6)));
+        // A resync restarts the seq; later changes aren't held back
+        assert!(sub.forward(&change("BTC/USD", 1)));
+    }
+}
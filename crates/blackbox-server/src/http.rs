@@ -1,20 +1,104 @@
+use crate::api_error::{optional_json, ApiError, ApiJson, ApiQuery, ErrorCode};
+use crate::config::{RunMode, RuntimeConfig};
 use crate::incident::IncidentManager;
-use crate::state::AppState;
+use crate::integrity::proof::LatencyStats;
+use crate::integrity::IntegrityProof;
+use crate::integrity::fault::{FaultParams, FaultType, REPLAY_ONLY_FAULTS};
+use crate::state::{AppState, OpenRecordingError, StoredBook, UiEvent};
+use blackbox_core::checksum::{build_checksum_string_with_levels, compute_crc32, CHECKSUM_PREVIEW_LEN};
+use blackbox_core::orderbook::{LevelMeta, Side};
+use blackbox_core::replayer::Replayer;
+use blackbox_core::types::{RecordedFrame, ReplayMode};
+use blackbox_ws::client::ClientCommand;
 use axum::{
-    extract::{Path, Query, State},
-    http::StatusCode,
+    extract::{Path, Request, State},
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode},
+    middleware::{self, Next},
     response::{IntoResponse, Json, Response},
-    routing::{get, post},
+    routing::{delete, get, post},
     Router,
     body::Body,
 };
+use tower_http::compression::predicate::{And, DefaultPredicate, NotForContentType, Predicate};
+use tower_http::compression::CompressionLayer;
+use tower_http::cors::{AllowOrigin, CorsLayer};
 use chrono::Utc;
+use dashmap::mapref::entry::Entry;
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 #[derive(Deserialize)]
 struct BookQuery {
     limit: Option<usize>,
+    offset: Option<usize>,
+    cumulative: Option<bool>,
+}
+
+/// Default band for the liquidity fields on `/book/:symbol/top`
+const DEFAULT_LIQUIDITY_BPS: u32 = 5;
+/// Default number of levels per side for the imbalance field
+const DEFAULT_IMBALANCE_LEVELS: usize = 10;
+
+#[derive(Deserialize)]
+struct TopQuery {
+    bps: Option<u32>,
+    levels: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct FaultRequest {
+    symbol: String,
+    #[serde(rename = "type")]
+    kind: String, // e.g. mutate_qty, drop, corrupt_checksum
+    #[serde(default)]
+    params: FaultParams,
+}
+
+/// Reply of every `/fault` endpoint
+#[derive(Serialize)]
+pub struct FaultStatus {
+    pub armed: bool, // false once it fired on the symbol's next update, or was disarmed
+    pub symbol: Option<String>,
+    pub fault: FaultType, // the selected fault, as {"type": ..., "params": ...}
+    pub supported: bool, // whether the live pipeline applies it
+}
+
+impl FaultStatus {
+    pub fn of(state: &AppState) -> Self {
+        let injector = &state.fault_injector;
+        let symbol = injector.armed_symbol();
+        let fault = injector.fault_type();
+        let supported = match fault {
+            // Needs the qty increment: a symbol without instrument info is left untouched
+            FaultType::MutateQty { .. } => symbol.as_ref().is_none_or(|symbol| state.instruments.contains_key(symbol)),
+            FaultType::MutatePrice { .. } | FaultType::Duplicate | FaultType::CorruptChecksum { .. } | FaultType::Drop => true,
+        };
+        Self { armed: symbol.is_some(), symbol, fault, supported }
+    }
+}
+
+/// Checksum of the book as currently held, from `/book/:symbol/checksum`
+#[derive(Serialize)]
+struct ComputedChecksum {
+    computed: u32,
+    checksum_len: usize,
+    preview: String, // first CHECKSUM_PREVIEW_LEN chars of the checksum string
+    top_asks: Vec<(String, String)>, // the checksummed levels, low to high
+    top_bids: Vec<(String, String)>, // high to low
+    generated_at: chrono::DateTime<Utc>,
+}
+
+/// Frames `/frames/:symbol` returns without `?limit`
+const DEFAULT_FRAMES_LIMIT: usize = 100;
+
+#[derive(Deserialize)]
+struct FramesQuery {
+    limit: Option<usize>,
 }
 
 #[derive(Serialize)]
@@ -23,30 +107,395 @@ struct TopOfBook {
     best_bid: Option<(String, String)>,
     best_ask: Option<(String, String)>,
     spread: Option<String>,
+    spread_bps: Option<String>,
     mid: Option<String>,
+    microprice: Option<String>,
+    // Microprice over the top `imbalance_levels` levels
+    weighted_mid: Option<String>,
+    liquidity_bps: u32,
+    // (bid_qty, ask_qty) within `liquidity_bps` of mid
+    liquidity: Option<(String, String)>,
+    // (bid_notional, ask_notional) within `liquidity_bps` of mid
+    notional: Option<(String, String)>,
+    imbalance_levels: usize,
+    imbalance: Option<f64>,
+    // Only populated when the client runs with --level-meta
+    best_bid_meta: Option<LevelMeta>,
+    best_ask_meta: Option<LevelMeta>,
+    // Book predates the last disconnect and hasn't been re-snapshotted yet
+    stale: bool,
 }
 
 #[derive(Serialize)]
 struct BookResponse {
     symbol: String,
-    bids: Vec<(String, String)>,
-    asks: Vec<(String, String)>,
+    bids: BookLevels,
+    asks: BookLevels,
+    update_seq: u64,
+    last_update_ts: Option<String>,
+    stale: bool,
+    last_snapshot_ts: Option<String>,
 }
 
+/// How long `/symbols` waits for Kraken to acknowledge a subscription change
+const SUBSCRIBE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+#[derive(Deserialize)]
+struct AddSymbolRequest {
+    symbol: String,
+    depth: Option<u32>, // default: the client's --depth
+}
+
+#[derive(Deserialize)]
+struct ReplaySeekRequest {
+    ts: chrono::DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+struct ReplaySpeedRequest {
+    multiplier: f64,
+}
+
+/// Reply of every `/replay` endpoint
 #[derive(Serialize)]
-struct ExportBugResponse {
-    path: String,
-    incident_id: String,
+struct ReplayStatus {
+    paused: bool,
+    progress: f64, // share of the recording played, 0 to 1
+    done: bool,
+    current_ts: Option<chrono::DateTime<Utc>>, // recorded time of the last frame played
+    mode: ReplayMode,
+    faults_applied: usize,
+    symbols_without_snapshot: Vec<String>, // updates skipped since the last seek
+}
+
+/// Optional body of `POST /record/start`; unset fields use the session's options
+#[derive(Deserialize, Default)]
+struct RecordStartRequest {
+    path: Option<std::path::PathBuf>,
+    rotate_size: Option<ByteSize>,
+    channels: Option<Vec<String>>,
+}
+
+/// A byte count, or a size like "512M"
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ByteSize {
+    Bytes(u64),
+    Text(String),
+}
+
+/// An `IntegrityProof` with the latency stats its skipped history feeds
+#[derive(Serialize)]
+struct IntegrityResponse {
+    #[serde(flatten)]
+    proof: IntegrityProof,
+    matched: bool,
+    latency_stats: LatencyStats,
+}
+
+impl From<&IntegrityProof> for IntegrityResponse {
+    fn from(proof: &IntegrityProof) -> Self {
+        Self {
+            proof: proof.clone(),
+            matched: proof.is_match(),
+            latency_stats: proof.latency_stats(),
+        }
+    }
+}
+
+/// `[price, qty]` levels, or `[price, qty, cum_qty]` with `?cumulative=true`
+#[derive(Serialize)]
+#[serde(untagged)]
+enum BookLevels {
+    Plain(Vec<(String, String)>),
+    Cumulative(Vec<(String, String, String)>),
+}
+
+/// Access control for the HTTP API, from `--http-token`, `--cors-allow-origin`
+/// and `--rate-limit`
+#[derive(Debug, Clone, Default)]
+pub struct HttpOptions {
+    pub token: Option<String>, // required as a bearer token everywhere but `/` and `/health`
+    pub cors_allow_origins: Vec<String>, // `*` allows any origin; empty = no CORS headers
+    pub rate_limits: Vec<(String, RateLimit)>, // route prefix and its limit; see `rate_limits`
+}
+
+impl HttpOptions {
+    /// Layer `app` with the rate limits, the token check and CORS; call once
+    /// every route is added
+    pub fn apply(&self, mut app: Router) -> anyhow::Result<Router> {
+        // Inside the token check, so requests it turns away don't use up tokens
+        if !self.rate_limits.is_empty() {
+            app = app.layer(middleware::from_fn_with_state(Arc::new(RateLimiter::new(&self.rate_limits)), limit_rate));
+        }
+        if let Some(token) = &self.token {
+            app = app.layer(middleware::from_fn_with_state(Arc::<str>::from(token.as_str()), require_token));
+        }
+        // Outside the token check, so preflights and 401s get CORS headers too
+        if !self.cors_allow_origins.is_empty() {
+            app = app.layer(self.cors_layer()?);
+        }
+        Ok(app)
+    }
+
+    fn cors_layer(&self) -> anyhow::Result<CorsLayer> {
+        let origins = if self.cors_allow_origins.iter().any(|origin| origin == "*") {
+            AllowOrigin::any()
+        } else {
+            let origins = self.cors_allow_origins.iter()
+                .map(|origin| HeaderValue::from_str(origin).map_err(|_| anyhow::anyhow!("Invalid --cors-allow-origin {:?}", origin)))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            AllowOrigin::list(origins)
+        };
+        Ok(CorsLayer::new()
+            .allow_origin(origins)
+            .allow_methods([Method::GET, Method::POST, Method::DELETE])
+            .allow_headers([header::AUTHORIZATION, header::CONTENT_TYPE]))
+    }
+}
+
+/// 401 unless the request carries `Authorization: Bearer <token>`. The UI
+/// page, `/health` and the probes stay open for load balancers, uptime
+/// checks and orchestrators.
+async fn require_token(State(token): State<Arc<str>>, request: Request, next: Next) -> Response {
+    if matches!(request.uri().path(), "/" | "/health" | "/live" | "/ready") {
+        return next.run(request).await;
+    }
+    let presented = request.headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    match presented {
+        Some(presented) if constant_time_eq(presented.as_bytes(), token.as_bytes()) => next.run(request).await,
+        _ => {
+            let mut response = ApiError::new(ErrorCode::Unauthorized, "Missing or invalid bearer token").into_response();
+            response.headers_mut().insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+            response
+        }
+    }
+}
+
+/// At most `requests` per `period`, e.g. `5/min`. A burst of `requests` can
+/// go through at once; after that tokens come back evenly over the period.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    pub requests: u32,
+    pub period: Duration,
+}
+
+impl RateLimit {
+    pub const fn per_minute(requests: u32) -> Self {
+        Self { requests, period: Duration::from_secs(60) }
+    }
+}
+
+impl FromStr for RateLimit {
+    type Err = anyhow::Error;
+
+    /// `N/s`, `N/min` or `N/h`
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let (requests, period) = s.split_once('/')
+            .ok_or_else(|| anyhow::anyhow!("expected N/s, N/min or N/h, got {:?}", s))?;
+        let requests: u32 = requests.trim().parse()
+            .map_err(|_| anyhow::anyhow!("invalid request count in {:?}", s))?;
+        anyhow::ensure!(requests > 0, "a limit of 0 requests in {:?}; use `off` to lift the limit instead", s);
+        let period = match period.trim() {
+            "s" | "sec" => Duration::from_secs(1),
+            "m" | "min" => Duration::from_secs(60),
+            "h" | "hour" => Duration::from_secs(3600),
+            other => anyhow::bail!("unknown period {:?} in {:?}; expected s, min or h", other, s),
+        };
+        Ok(Self { requests, period })
+    }
+}
+
+impl fmt::Display for RateLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.period.as_secs() {
+            1 => write!(f, "{}/s", self.requests),
+            60 => write!(f, "{}/min", self.requests),
+            3600 => write!(f, "{}/h", self.requests),
+            secs => write!(f, "{}/{}s", self.requests, secs),
+        }
+    }
+}
+
+/// Limits on the mutating routes unless `--rate-limit` overrides them. The
+/// replay controls are left alone: they're stepped interactively.
+pub const DEFAULT_RATE_LIMITS: &[(&str, RateLimit)] = &[
+    ("/export-bug", RateLimit::per_minute(5)), // each one writes a bundle to disk
+    ("/record", RateLimit::per_minute(10)),
+    ("/fault", RateLimit::per_minute(30)),
+    ("/symbols", RateLimit::per_minute(30)),
+];
+
+/// `DEFAULT_RATE_LIMITS` with `overrides` applied in order; an override of
+/// None lifts the route's limit
+pub fn rate_limits(overrides: &[(String, Option<RateLimit>)]) -> Vec<(String, RateLimit)> {
+    let mut limits: Vec<(String, RateLimit)> = DEFAULT_RATE_LIMITS.iter()
+        .map(|(route, limit)| (route.to_string(), *limit))
+        .collect();
+    for (route, limit) in overrides {
+        limits.retain(|(existing, _)| existing != route);
+        if let Some(limit) = limit {
+            limits.push((route.clone(), *limit));
+        }
+    }
+    limits
+}
+
+struct TokenBucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl TokenBucket {
+    fn full(limit: RateLimit, now: Instant) -> Self {
+        Self { tokens: limit.requests as f64, refilled_at: now }
+    }
+
+    /// Take a token at `now`, or say how long until one is back
+    fn take(&mut self, limit: RateLimit, now: Instant) -> Result<(), Duration> {
+        let per_sec = limit.requests as f64 / limit.period.as_secs_f64();
+        let elapsed = now.saturating_duration_since(self.refilled_at).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * per_sec).min(limit.requests as f64);
+        self.refilled_at = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - self.tokens) / per_sec))
+        }
+    }
+}
+
+/// One bucket per limited route, longest route first so `/record/start`
+/// could be limited apart from the rest of `/record`
+struct RateLimiter {
+    routes: Vec<(String, RateLimit, Mutex<TokenBucket>)>,
+}
+
+impl RateLimiter {
+    fn new(limits: &[(String, RateLimit)]) -> Self {
+        let now = Instant::now();
+        let mut routes: Vec<_> = limits.iter()
+            .map(|(route, limit)| (route.clone(), *limit, Mutex::new(TokenBucket::full(*limit, now))))
+            .collect();
+        routes.sort_by_key(|(route, _, _)| std::cmp::Reverse(route.len()));
+        Self { routes }
+    }
+
+    /// The limit covering `path`: the route itself or anything under it
+    fn route_for(&self, path: &str) -> Option<&(String, RateLimit, Mutex<TokenBucket>)> {
+        self.routes.iter().find(|(route, _, _)| {
+            path.strip_prefix(route.as_str()).is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+        })
+    }
+}
+
+/// 429 with `Retry-After` once a limited route's bucket is empty. Only
+/// requests that change something count; reads are never limited.
+async fn limit_rate(State(limiter): State<Arc<RateLimiter>>, request: Request, next: Next) -> Response {
+    if matches!(*request.method(), Method::GET | Method::HEAD | Method::OPTIONS) {
+        return next.run(request).await;
+    }
+    let Some((route, limit, bucket)) = limiter.route_for(request.uri().path()) else {
+        return next.run(request).await;
+    };
+    let taken = bucket.lock().unwrap().take(*limit, Instant::now());
+    match taken {
+        Ok(()) => next.run(request).await,
+        Err(wait) => {
+            let retry_after = wait.as_secs_f64().ceil().max(1.0) as u64;
+            let message = format!("Rate limit of {} for {} exceeded; retry in {}s", limit, route, retry_after);
+            let mut response = ApiError::new(ErrorCode::RateLimited, message)
+                .with_detail(serde_json::json!({ "retry_after_secs": retry_after }))
+                .into_response();
+            response.headers_mut().insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
+            response
+        }
+    }
+}
+
+/// Compare without returning early, so timing doesn't leak how much matched
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
 }
 
 pub fn router(state: AppState, incident_manager: std::sync::Arc<crate::incident::IncidentManager>) -> Router {
     Router::new()
         .route("/health", get(health_handler))
+        .route("/live", get(live_handler))
+        .route("/ready", get(ready_handler))
+        .route("/info", get(info_handler))
         .route("/book/:symbol/top", get(book_top_handler))
+        .route("/book/:symbol/checksum", get(book_checksum_handler))
         .route("/book/:symbol", get(book_handler))
+        .route("/frames/:symbol", get(frames_handler))
+        .route("/stats", get(stats_handler))
+        .route("/config", get(config_handler))
+        .route("/fault", get(fault_status_handler).post(fault_arm_handler).delete(fault_disarm_handler))
+        .route("/symbols", post(add_symbol_handler))
+        .route("/symbols/:symbol", delete(remove_symbol_handler))
+        .route("/integrity", get(integrity_handler))
+        .route("/integrity/:symbol", get(integrity_symbol_handler))
+        .route("/record/start", post(record_start_handler))
+        .route("/record/stop", post(record_stop_handler))
+        .route("/record/status", get(record_status_handler))
         .route("/metrics", get(metrics_handler))
+        .route("/ws", get(crate::feed::ws_handler))
+        .route("/events", get(crate::events::events_handler))
+        .route("/events/stream", get(crate::events::events_stream_handler))
         .route("/export-bug", post(export_bug_handler))
+        .route("/incidents", get(incidents_handler))
+        .route("/incidents/:id/bundle", get(incident_bundle_handler))
+        .route("/replay/pause", post(replay_pause_handler))
+        .route("/replay/resume", post(replay_resume_handler))
+        .route("/replay/step", post(replay_step_handler))
+        .route("/replay/seek", post(replay_seek_handler))
+        .route("/replay/speed", post(replay_speed_handler))
+        .route("/replay/status", get(replay_status_handler))
         .with_state((state, incident_manager))
+        .layer(compression_layer())
+}
+
+/// Gzip for clients that accept it. Small responses, event streams, images
+/// and bundles (already zipped) are sent as is.
+fn compression_layer() -> CompressionLayer<And<DefaultPredicate, NotForContentType>> {
+    CompressionLayer::new()
+        .compress_when(DefaultPredicate::new().and(NotForContentType::const_new("application/zip")))
+}
+
+/// Validator for a book's responses. A book changes only with its update seq,
+/// which restarts on each snapshot, or its stale flag. Weak because the bytes
+/// differ with compression.
+fn book_etag(book: &StoredBook) -> String {
+    let snapshot = book.last_snapshot_ts.map_or(0, |ts| ts.timestamp_micros());
+    let stale = if book.stale { "-stale" } else { "" };
+    format!("W/\"{}-{}{}\"", snapshot, book.update_seq(), stale)
+}
+
+/// Whether the request's `If-None-Match` names `etag`, compared weakly
+fn etag_matches(headers: &HeaderMap, etag: &str) -> bool {
+    let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    headers.get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|tag| tag.trim() == "*" || opaque(tag) == opaque(etag))
+}
+
+/// `response` with an `ETag`, or a bodyless 304 if the client already has it
+fn conditional(headers: &HeaderMap, etag: String, response: impl FnOnce() -> Response) -> Response {
+    let mut response = if etag_matches(headers, &etag) {
+        StatusCode::NOT_MODIFIED.into_response()
+    } else {
+        response()
+    };
+    if let Ok(value) = HeaderValue::from_str(&etag) {
+        response.headers_mut().insert(header::ETAG, value);
+    }
+    response
 }
 
 async fn health_handler(State((state, _)): State<(AppState, Arc<IncidentManager>)>) -> impl IntoResponse {
@@ -54,144 +503,1451 @@ async fn health_handler(State((state, _)): State<(AppState, Arc<IncidentManager>
     Json(overall)
 }
 
+/// Liveness probe: answering at all means the process is up
+async fn live_handler() -> Json<serde_json::Value> {
+    Json(serde_json::json!({ "live": true }))
+}
+
+/// Reply of `/ready`
+#[derive(Serialize)]
+struct Readiness {
+    ready: bool,
+    connected: bool,
+    books: usize,
+}
+
+/// Readiness probe: 503 until the feed is connected and a book snapshot has
+/// arrived, so orchestrators hold traffic until data is flowing
+async fn ready_handler(State((state, _)): State<(AppState, Arc<IncidentManager>)>) -> Response {
+    let readiness = Readiness { ready: state.is_ready(), connected: state.is_connected(), books: state.orderbooks.len() };
+    let status = if readiness.ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    (status, Json(readiness)).into_response()
+}
+
+/// Reply of `/info`
+#[derive(Serialize)]
+struct BuildInfo {
+    version: &'static str,
+    git_hash: Option<&'static str>,
+    build_time: Option<chrono::DateTime<Utc>>,
+    uptime_seconds: u64,
+    mode: RunMode,
+}
+
+async fn info_handler(State((state, _)): State<(AppState, Arc<IncidentManager>)>) -> Json<BuildInfo> {
+    Json(BuildInfo {
+        version: crate::config::VERSION,
+        git_hash: crate::config::GIT_HASH,
+        build_time: crate::config::build_time(),
+        uptime_seconds: state.uptime_seconds(),
+        mode: state.run_mode,
+    })
+}
+
+/// The effective configuration, as written into incident bundles
+async fn config_handler(
+    State((state, incident_manager)): State<(AppState, Arc<IncidentManager>)>,
+) -> Json<RuntimeConfig> {
+    Json(RuntimeConfig::collect(&state, &incident_manager).await)
+}
+
+/// Arm a fault for the symbol's next book update, as the TUI's [D] does
+async fn fault_arm_handler(
+    State((state, _)): State<(AppState, Arc<IncidentManager>)>,
+    ApiJson(request): ApiJson<FaultRequest>,
+) -> Result<Json<FaultStatus>, ApiError> {
+    let Some(fault) = FaultType::from_name(&request.kind, &request.params) else {
+        if REPLAY_ONLY_FAULTS.contains(&request.kind.as_str()) {
+            let message = format!("{} holds frames back, which only `blackbox replay --fault-*` can do", request.kind);
+            return Err(ApiError::new(ErrorCode::FaultReplayOnly, message)
+                .with_detail(serde_json::json!({ "supported": false })));
+        }
+        return Err(ApiError::invalid_request(format!(
+            "Unknown fault type {:?}; expected mutate_qty, mutate_price, duplicate, corrupt_checksum or drop",
+            request.kind
+        )));
+    };
+    let known = state.orderbooks.contains_key(&request.symbol)
+        || state.get_requested_symbols().await.contains(&request.symbol);
+    if !known {
+        return Err(ApiError::symbol_not_found(format!("{} is not subscribed", request.symbol)));
+    }
+    state.fault_injector.arm(request.symbol.clone(), fault);
+    state.push_event(UiEvent::FaultInjected { fault_type: format!("{:?}", fault), symbol: request.symbol }).await;
+    Ok(Json(FaultStatus::of(&state)))
+}
+
+async fn fault_status_handler(State((state, _)): State<(AppState, Arc<IncidentManager>)>) -> Json<FaultStatus> {
+    Json(FaultStatus::of(&state))
+}
+
+async fn fault_disarm_handler(State((state, _)): State<(AppState, Arc<IncidentManager>)>) -> Json<FaultStatus> {
+    state.fault_injector.disarm();
+    Json(FaultStatus::of(&state))
+}
+
+/// The symbol's last book frames, oldest first, as recording lines that
+/// `blackbox verify` and `replay` read
+async fn frames_handler(
+    State((state, _)): State<(AppState, Arc<IncidentManager>)>,
+    Path(symbol): Path<String>,
+    ApiQuery(params): ApiQuery<FramesQuery>,
+) -> Result<Response, ApiError> {
+    let Some(frame_buffer) = state.per_symbol_frames.get(&symbol).map(|entry| entry.value().clone()) else {
+        return Err(ApiError::symbol_not_found(format!("No frames received for {}", symbol)));
+    };
+    let frames = frame_buffer.read().await;
+    let limit = params.limit.unwrap_or(DEFAULT_FRAMES_LIMIT);
+    let mut body = String::new();
+    for (ts, raw_frame) in frames.iter().skip(frames.len().saturating_sub(limit)) {
+        let line = RecordedFrame { ts: *ts, raw_frame: raw_frame.clone(), decoded_event: None };
+        match serde_json::to_string(&line) {
+            Ok(line) => {
+                body.push_str(&line);
+                body.push('\n');
+            }
+            Err(e) => return Err(ApiError::internal(format!("Failed to serialize frame: {}", e))),
+        }
+    }
+    Ok(([("Content-Type", "application/x-ndjson")], body).into_response())
+}
+
+fn level_strings<'a>(levels: impl Iterator<Item = (&'a Decimal, &'a Decimal)>) -> Vec<(String, String)> {
+    levels.map(|(p, q)| (p.to_string(), q.to_string())).collect()
+}
+
+/// The checksum the held book produces now, without waiting for the next
+/// frame to carry one
+async fn book_checksum_handler(
+    State((state, _)): State<(AppState, Arc<IncidentManager>)>,
+    Path(symbol): Path<String>,
+) -> Result<Json<ComputedChecksum>, ApiError> {
+    let Some(book) = state.orderbooks.get(&symbol) else {
+        return Err(no_book(&symbol));
+    };
+    let Some(instrument) = state.instruments.get(&symbol) else {
+        return Err(ApiError::new(
+            ErrorCode::BookNotReady,
+            format!("Precisions for {} are unknown: no instrument snapshot received yet", symbol),
+        ));
+    };
+    if book.best_bid().is_none() && book.best_ask().is_none() {
+        return Err(ApiError::new(ErrorCode::BookNotReady, format!("The {} book is empty", symbol)));
+    }
+
+    let levels = instrument.checksum_levels;
+    let checksum_str = build_checksum_string_with_levels(&book, instrument.price_precision, instrument.qty_precision, levels);
+    Ok(Json(ComputedChecksum {
+        computed: compute_crc32(&checksum_str),
+        checksum_len: checksum_str.len(),
+        preview: checksum_str.chars().take(CHECKSUM_PREVIEW_LEN).collect(),
+        top_asks: level_strings(book.top_asks(levels)),
+        top_bids: level_strings(book.top_bids(levels)),
+        generated_at: Utc::now(),
+    }))
+}
+
+fn no_book(symbol: &str) -> ApiError {
+    ApiError::symbol_not_found(format!("No book for {}", symbol))
+}
+
 async fn book_top_handler(
     State((state, _)): State<(AppState, Arc<IncidentManager>)>,
     Path(symbol): Path<String>,
-) -> impl IntoResponse {
-    if let Some(book) = state.orderbooks.get(&symbol) {
+    ApiQuery(params): ApiQuery<TopQuery>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let liquidity_bps = params.bps.unwrap_or(DEFAULT_LIQUIDITY_BPS);
+    let imbalance_levels = params.levels.unwrap_or(DEFAULT_IMBALANCE_LEVELS);
+    
+    let book = state.orderbooks.get(&symbol).ok_or_else(|| no_book(&symbol))?;
+    Ok(conditional(&headers, book_etag(&book), || {
         let best_bid = book.best_bid().map(|(p, q)| (p.to_string(), q.to_string()));
         let best_ask = book.best_ask().map(|(p, q)| (p.to_string(), q.to_string()));
         let spread = book.spread().map(|s| s.to_string());
+        let spread_bps = book.spread_bps().map(|s| s.to_string());
         let mid = book.mid().map(|m| m.to_string());
-        
+        let microprice = book.microprice().map(|m| m.to_string());
+        let weighted_mid = book.weighted_mid(imbalance_levels).map(|m| m.to_string());
+        let liquidity = book.liquidity_within_bps(liquidity_bps)
+            .map(|(b, a)| (b.to_string(), a.to_string()));
+        let notional = book.notional_within_bps(liquidity_bps)
+            .map(|(b, a)| (b.to_string(), a.to_string()));
+        let imbalance = book.imbalance(imbalance_levels);
+        let best_bid_meta = book.best_bid().and_then(|(p, _)| book.level_meta(p, Side::Bid));
+        let best_ask_meta = book.best_ask().and_then(|(p, _)| book.level_meta(p, Side::Ask));
+    
         Json(TopOfBook {
             symbol,
             best_bid,
             best_ask,
             spread,
+            spread_bps,
             mid,
+            microprice,
+            weighted_mid,
+            liquidity_bps,
+            liquidity,
+            notional,
+            imbalance_levels,
+            imbalance,
+            best_bid_meta,
+            best_ask_meta,
+            stale: book.stale,
         }).into_response()
-    } else {
-        (StatusCode::NOT_FOUND, Json(TopOfBook {
-            symbol,
-            best_bid: None,
-            best_ask: None,
-            spread: None,
-            mid: None,
-        })).into_response()
-    }
+    }))
 }
 
 async fn book_handler(
     State((state, _)): State<(AppState, Arc<IncidentManager>)>,
     Path(symbol): Path<String>,
-    Query(params): Query<BookQuery>,
-) -> impl IntoResponse {
-    if let Some(book) = state.orderbooks.get(&symbol) {
-        let limit = params.limit;
-        let bids: Vec<(String, String)> = book.bids_vec(limit)
-            .iter()
-            .map(|(p, q)| (p.to_string(), q.to_string()))
-            .collect();
-        let asks: Vec<(String, String)> = book.asks_vec(limit)
-            .iter()
-            .map(|(p, q)| (p.to_string(), q.to_string()))
-            .collect();
-        
+    ApiQuery(params): ApiQuery<BookQuery>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let book = state.orderbooks.get(&symbol).ok_or_else(|| no_book(&symbol))?;
+    Ok(conditional(&headers, book_etag(&book), || {
+        let limit = params.limit.unwrap_or(usize::MAX);
+        let offset = params.offset.unwrap_or(0);
+        let (bids, asks) = if params.cumulative.unwrap_or(false) {
+            // Cumulative quantity still counts from the best level, not the page start
+            let page = |levels: Vec<(Decimal, Decimal, Decimal)>| {
+                levels
+                    .iter()
+                    .skip(offset)
+                    .take(limit)
+                    .map(|(p, q, c)| (p.to_string(), q.to_string(), c.to_string()))
+                    .collect()
+            };
+            let end = Some(offset.saturating_add(limit));
+            (
+                BookLevels::Cumulative(page(book.bids_cumulative(end))),
+                BookLevels::Cumulative(page(book.asks_cumulative(end))),
+            )
+        } else {
+            let bids: Vec<(String, String)> = book.bids_page(offset, limit)
+                .iter()
+                .map(|(p, q)| (p.to_string(), q.to_string()))
+                .collect();
+            let asks: Vec<(String, String)> = book.asks_page(offset, limit)
+                .iter()
+                .map(|(p, q)| (p.to_string(), q.to_string()))
+                .collect();
+            (BookLevels::Plain(bids), BookLevels::Plain(asks))
+        };
+    
         Json(BookResponse {
             symbol,
             bids,
             asks,
+            update_seq: book.update_seq(),
+            last_update_ts: book.last_update_ts().map(|ts| ts.to_rfc3339()),
+            stale: book.stale,
+            last_snapshot_ts: book.last_snapshot_ts.map(|ts| ts.to_rfc3339()),
         }).into_response()
-    } else {
-        (StatusCode::NOT_FOUND, Json(BookResponse {
-            symbol,
-            bids: vec![],
-            asks: vec![],
-        })).into_response()
+    }))
+}
+
+#[derive(Serialize)]
+struct BookStats {
+    symbol: String,
+    bid_levels: usize,
+    ask_levels: usize,
+    bytes_estimate: usize,
+    update_seq: u64,
+}
+
+#[derive(Serialize)]
+struct StatsResponse {
+    books: Vec<BookStats>,
+    total_bytes_estimate: usize,
+    frames_buffered: usize, // in `last_frames`
+    frame_buffer_capacity: usize,
+}
+
+async fn stats_handler(State((state, _)): State<(AppState, Arc<IncidentManager>)>) -> impl IntoResponse {
+    let mut books: Vec<BookStats> = state.orderbooks.iter().map(|entry| {
+        let (ask_levels, bid_levels) = entry.depth();
+        BookStats {
+            symbol: entry.key().clone(),
+            bid_levels,
+            ask_levels,
+            bytes_estimate: entry.approx_bytes(),
+            update_seq: entry.update_seq(),
+        }
+    }).collect();
+    books.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+    let total_bytes_estimate = books.iter().map(|b| b.bytes_estimate).sum();
+    
+    Json(StatsResponse {
+        books,
+        total_bytes_estimate,
+        frames_buffered: state.last_frames.read().await.len(),
+        frame_buffer_capacity: state.last_frames_capacity,
+    })
+}
+
+async fn record_status_handler(State((state, _)): State<(AppState, Arc<IncidentManager>)>) -> impl IntoResponse {
+    Json(state.recording_status().await)
+}
+
+/// Wait for the client's answer to a subscription command
+async fn await_reply<T>(reply: tokio::sync::oneshot::Receiver<Result<T, String>>) -> Result<T, ApiError> {
+    match tokio::time::timeout(SUBSCRIBE_TIMEOUT, reply).await {
+        Ok(Ok(Ok(value))) => Ok(value),
+        Ok(Ok(Err(error))) => Err(ApiError::new(ErrorCode::SubscribeRejected, error)),
+        Ok(Err(_)) => Err(ApiError::new(ErrorCode::ClientUnavailable, "Connection to Kraken lost before it replied")),
+        Err(_) => Err(ApiError::new(ErrorCode::UpstreamTimeout, format!("No reply from Kraken within {:?}", SUBSCRIBE_TIMEOUT))),
+    }
+}
+
+fn not_live() -> ApiError {
+    ApiError::new(ErrorCode::NotLive, "Not connected to a live feed")
+}
+
+fn client_not_running() -> ApiError {
+    ApiError::new(ErrorCode::ClientUnavailable, "WebSocket client is not running")
+}
+
+/// Subscribe another symbol's book on the running client
+async fn add_symbol_handler(
+    State((state, _)): State<(AppState, Arc<IncidentManager>)>,
+    ApiJson(request): ApiJson<AddSymbolRequest>,
+) -> Result<Json<Vec<crate::config::SymbolConfig>>, ApiError> {
+    let commands = state.ws_commands.as_ref().ok_or_else(not_live)?;
+    // Every subscribed symbol has a depth: claiming one checks and reserves the
+    // symbol in one step, so a concurrent add of it gets the 409
+    let claimed_depth = request.depth.unwrap_or_else(|| state.get_depth(&request.symbol));
+    match state.depths.entry(request.symbol.clone()) {
+        Entry::Occupied(_) => {
+            return Err(ApiError::new(ErrorCode::SymbolAlreadySubscribed, format!("{} is already subscribed", request.symbol)));
+        }
+        Entry::Vacant(entry) => {
+            entry.insert(claimed_depth);
+        }
+    }
+    let (reply, replied) = tokio::sync::oneshot::channel();
+    let command = ClientCommand::Subscribe { symbol: request.symbol.clone(), depth: request.depth, reply };
+    let subscribed = match commands.send(command) {
+        Ok(()) => await_reply(replied).await,
+        Err(_) => Err(client_not_running()),
+    };
+    let depth = subscribed.inspect_err(|_| {
+        state.depths.remove(&request.symbol);
+    })?;
+    state.set_depth(&request.symbol, depth);
+    state.requested_symbols.write().await.push(request.symbol);
+    Ok(Json(state.symbols_config().await))
+}
+
+/// Unsubscribe a symbol's book and forget its state
+async fn remove_symbol_handler(
+    State((state, _)): State<(AppState, Arc<IncidentManager>)>,
+    Path(symbol): Path<String>,
+) -> Result<Json<Vec<crate::config::SymbolConfig>>, ApiError> {
+    let commands = state.ws_commands.as_ref().ok_or_else(not_live)?;
+    if !state.get_requested_symbols().await.contains(&symbol) {
+        return Err(ApiError::symbol_not_found(format!("{} is not subscribed", symbol)));
+    }
+    let (reply, replied) = tokio::sync::oneshot::channel();
+    commands.send(ClientCommand::Unsubscribe { symbol: symbol.clone(), reply }).map_err(|_| client_not_running())?;
+    await_reply(replied).await?;
+    state.forget_symbol(&symbol).await;
+    Ok(Json(state.symbols_config().await))
+}
+
+/// Start recording like the TUI toggle does; 409 if already recording
+async fn record_start_handler(
+    State((state, _)): State<(AppState, Arc<IncidentManager>)>,
+    body: axum::body::Bytes,
+) -> Result<Json<crate::state::RecordStatus>, ApiError> {
+    let request: RecordStartRequest = optional_json(&body)?;
+    
+    let mut options = state.recorder_options.clone();
+    match request.rotate_size {
+        Some(ByteSize::Bytes(bytes)) => options.rotation.max_bytes = Some(bytes),
+        Some(ByteSize::Text(size)) => match crate::parse_byte_size(&size) {
+            Ok(bytes) => options.rotation.max_bytes = Some(bytes),
+            Err(e) => return Err(ApiError::invalid_request(format!("Invalid rotate_size: {}", e))),
+        },
+        None => {}
+    }
+    if let Some(channels) = request.channels {
+        if let Some(unknown) = channels.iter().find(|c| !crate::RECORD_CHANNELS.contains(&c.as_str())) {
+            return Err(ApiError::invalid_request(format!(
+                "Unknown channel '{}' (expected one of {})",
+                unknown,
+                crate::RECORD_CHANNELS.join(", ")
+            )));
+        }
+        options.channel_filter = Some(channels);
+    }
+    
+    match state.open_recording(request.path, options).await {
+        Ok(_) => Ok(Json(state.recording_status().await)),
+        Err(OpenRecordingError::Busy(path)) => Err(ApiError::new(ErrorCode::RecorderBusy, format!("Already recording to {}", path))
+            .with_detail(serde_json::json!({ "path": path }))),
+        Err(OpenRecordingError::Open(e)) => {
+            state.push_event(crate::state::UiEvent::Error(format!("Record failed: {}", e))).await;
+            Err(ApiError::internal(format!("Failed to start recording: {}", e)))
+        }
+    }
+}
+
+/// Write out queued frames and stop recording; a no-op when not recording
+async fn record_stop_handler(State((state, _)): State<(AppState, Arc<IncidentManager>)>) -> impl IntoResponse {
+    state.stop_recording().await;
+    Json(state.recording_status().await)
+}
+
+type ReplayReply = Result<Json<ReplayStatus>, ApiError>;
+
+async fn replay_pause_handler(State((state, _)): State<(AppState, Arc<IncidentManager>)>) -> ReplayReply {
+    replay_control(&state, Replayer::pause)
+}
+
+async fn replay_resume_handler(State((state, _)): State<(AppState, Arc<IncidentManager>)>) -> ReplayReply {
+    replay_control(&state, Replayer::resume)
+}
+
+/// Releases one frame, even while paused
+async fn replay_step_handler(State((state, _)): State<(AppState, Arc<IncidentManager>)>) -> ReplayReply {
+    replay_control(&state, Replayer::step)
+}
+
+/// Play from the first frame at or after `ts`, backwards or forwards
+async fn replay_seek_handler(
+    State((state, _)): State<(AppState, Arc<IncidentManager>)>,
+    ApiJson(request): ApiJson<ReplaySeekRequest>,
+) -> ReplayReply {
+    // Seeking reads through the recording file; keep that and the replayer lock
+    // off the async workers
+    let (status, seeked) = tokio::task::spawn_blocking(move || {
+        let mut seeked = Ok(0);
+        let status = replay_control(&state, |replayer| seeked = replayer.seek_to(request.ts));
+        (status, seeked)
+    })
+    .await
+    .map_err(|e| ApiError::internal(format!("Seek failed: {}", e)))?;
+    let status = status?;
+    seeked.map_err(|e| ApiError::internal(format!("Seek failed: {:#}", e)))?;
+    Ok(status)
+}
+
+/// Change the pacing: 1 is realtime, 0 as fast as possible
+async fn replay_speed_handler(
+    State((state, _)): State<(AppState, Arc<IncidentManager>)>,
+    ApiJson(request): ApiJson<ReplaySpeedRequest>,
+) -> ReplayReply {
+    if !(request.multiplier >= 0.0 && request.multiplier.is_finite()) {
+        return Err(ApiError::invalid_request(format!("Invalid speed multiplier {}", request.multiplier)));
     }
+    replay_control(&state, |replayer| replayer.set_mode(crate::replay_mode(request.multiplier)))
 }
 
-async fn metrics_handler() -> impl IntoResponse {
-    // For now, return a simple metrics endpoint
-    // In production, you'd want to set up Prometheus exporter properly
-    (StatusCode::OK, "# Prometheus metrics endpoint\n# Install metrics exporter in main.rs\n")
+async fn replay_status_handler(State((state, _)): State<(AppState, Arc<IncidentManager>)>) -> ReplayReply {
+    replay_control(&state, |_| {})
+}
+
+/// Apply `action` to the replayer and return its status; 409 when not replaying
+fn replay_control(state: &AppState, action: impl FnOnce(&mut Replayer)) -> ReplayReply {
+    let status = state.with_replayer(|replayer| {
+        action(replayer);
+        ReplayStatus {
+            paused: replayer.is_paused(),
+            progress: replayer.progress(),
+            done: replayer.is_done(),
+            current_ts: replayer.current_ts(),
+            mode: replayer.mode().clone(),
+            faults_applied: replayer.faults_applied(),
+            symbols_without_snapshot: replayer.symbols_without_snapshot(),
+        }
+    });
+    status
+        .map(Json)
+        .ok_or_else(|| ApiError::new(ErrorCode::ReplayNotActive, "Not replaying a recording"))
+}
+
+async fn metrics_handler(State((state, _)): State<(AppState, Arc<IncidentManager>)>) -> Result<Response, ApiError> {
+    match &state.metrics {
+        Some(handle) => Ok((
+            [("Content-Type", "text/plain; version=0.0.4")],
+            handle.render(),
+        ).into_response()),
+        None => Err(ApiError::new(ErrorCode::MetricsDisabled, "Metrics recorder not installed")),
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ExportBugRequest {
+    symbol: Option<String>, // default: see default_export_symbol
+}
+
+/// The symbol whose checksum failed most recently; without any mismatch, the
+/// first requested symbol, else the first tracked one
+async fn default_export_symbol(state: &AppState) -> Option<String> {
+    let mismatched = state.health.iter()
+        .filter_map(|entry| entry.last_checksum_mismatch.map(|ts| (ts, entry.key().clone())))
+        .max();
+    if let Some((_, symbol)) = mismatched {
+        return Some(symbol);
+    }
+    if let Some(symbol) = state.get_requested_symbols().await.into_iter().next() {
+        return Some(symbol);
+    }
+    state.health.iter().map(|entry| entry.key().clone()).min()
 }
 
 async fn export_bug_handler(
-    State((state, incident_manager)): State<(AppState, Arc<IncidentManager>)>
-) -> axum::response::Response {
+    State((state, incident_manager)): State<(AppState, Arc<IncidentManager>)>,
+    body: axum::body::Bytes,
+) -> Result<Response, ApiError> {
     use blackbox_core::incident::IncidentReason;
-    
-    // Create a manual export incident
+
+    let request: ExportBugRequest = optional_json(&body)?;
+    let symbol = match request.symbol {
+        Some(symbol) if state.health.contains_key(&symbol) || state.orderbooks.contains_key(&symbol) => Some(symbol),
+        Some(symbol) => return Err(ApiError::symbol_not_found(format!("Unknown symbol {}", symbol))),
+        None => default_export_symbol(&state).await,
+    };
+
     let incident = incident_manager
-        .record_incident(
-            IncidentReason::ManualExport,
-            None,
-            serde_json::json!({}),
-        )
+        .record_incident(IncidentReason::ManualExport, symbol.clone(), serde_json::json!({}))
         .await;
-    
-    // Export bundle for the first symbol or use current state
-    let symbol = state.health.iter().next().map(|e| e.key().clone());
-    let symbol_str = symbol.as_deref().unwrap_or("unknown");
-    
-    let config = serde_json::json!({
-        "symbols": state.health.iter().map(|e| e.key().clone()).collect::<Vec<_>>(),
-        "timestamp": Utc::now().to_rfc3339(),
-    });
-    
-    let overall = state.overall_health();
-    let health = serde_json::to_value(&overall).unwrap();
-    
-    let instrument = state.instruments.get(symbol_str).map(|e| e.value().clone());
-    
-    let book_top = state.orderbooks.get(symbol_str).map(|book| {
-        serde_json::json!({
-            "best_bid": book.best_bid().map(|(p, q)| (p.to_string(), q.to_string())),
-            "best_ask": book.best_ask().map(|(p, q)| (p.to_string(), q.to_string())),
-        })
-    });
-    
-    let frames = state.last_frames.read().await;
-    let frames_vec: Vec<_> = frames.iter().cloned().collect();
-    
-    match incident_manager
-        .export_incident_bundle(
-            &incident,
-            config,
-            health,
-            instrument.as_ref(),
-            book_top,
-            &frames_vec,
-            incident.timestamp,
-        )
+    let path = crate::export_incident_for_symbol(&state, &incident_manager, &incident, symbol.as_deref())
         .await
-    {
-        Ok(path) => {
-            // Read the ZIP file and return it
-            match std::fs::read(&path) {
-                Ok(zip_bytes) => {
-                    Response::builder()
-                        .status(StatusCode::OK)
-                        .header("Content-Type", "application/zip")
-                        .header("Content-Disposition", format!("attachment; filename=\"{}.zip\"", incident.id))
-                        .body(Body::from(zip_bytes))
-                        .unwrap()
-                        .into_response()
+        .map_err(|e| ApiError::internal(format!("Failed to export bundle: {}", e)))?;
+    bundle_response(&path, &incident.id).await
+}
+
+async fn integrity_handler(State((state, _)): State<(AppState, Arc<IncidentManager>)>) -> impl IntoResponse {
+    let proofs: BTreeMap<String, IntegrityResponse> = state.integrity_proofs.iter()
+        .map(|entry| (entry.key().clone(), IntegrityResponse::from(entry.value())))
+        .collect();
+    Json(proofs)
+}
+
+async fn integrity_symbol_handler(
+    State((state, _)): State<(AppState, Arc<IncidentManager>)>,
+    Path(symbol): Path<String>,
+) -> Result<Json<IntegrityResponse>, ApiError> {
+    match state.integrity_proofs.get(&symbol) {
+        Some(proof) => Ok(Json(IntegrityResponse::from(proof.value()))),
+        None => Err(ApiError::symbol_not_found(format!("No checksum verified for {} yet", symbol))),
+    }
+}
+
+async fn incidents_handler(State((_, incident_manager)): State<(AppState, Arc<IncidentManager>)>) -> impl IntoResponse {
+    Json(incident_manager.list_incidents().await)
+}
+
+async fn incident_bundle_handler(
+    State((_, incident_manager)): State<(AppState, Arc<IncidentManager>)>,
+    Path(id): Path<String>,
+) -> Result<Response, ApiError> {
+    let Some(path) = incident_manager.bundle_path(&id).await else {
+        return Err(ApiError::new(ErrorCode::NoIncident, format!("No bundle for incident {}", id)));
+    };
+    bundle_response(&path, &id).await
+}
+
+/// Stream an incident bundle as a `{id}.zip` download
+async fn bundle_response(path: &std::path::Path, id: &str) -> Result<Response, ApiError> {
+    let file = tokio::fs::File::open(path)
+        .await
+        .map_err(|e| ApiError::internal(format!("Failed to read bundle: {}", e)))?;
+    let mut response = Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", "application/zip")
+        .header("Content-Disposition", format!("attachment; filename=\"{}.zip\"", id));
+    if let Ok(metadata) = file.metadata().await {
+        response = response.header("Content-Length", metadata.len());
+    }
+    Ok(response
+        .body(Body::from_stream(tokio_util::io::ReaderStream::new(file)))
+        .unwrap()
+        .into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::Request;
+    use blackbox_core::incident::IncidentReason;
+    use futures_util::StreamExt;
+    use tower::ServiceExt;
+
+    async fn get_body(app: &Router, uri: &str) -> (StatusCode, Vec<u8>) {
+        let response = app.clone().oneshot(Request::get(uri).body(Body::empty()).unwrap()).await.unwrap();
+        let status = response.status();
+        let mut body = response.into_body().into_data_stream();
+        let mut bytes = Vec::new();
+        while let Some(chunk) = body.next().await {
+            bytes.extend_from_slice(&chunk.unwrap());
+        }
+        (status, bytes)
+    }
+
+    async fn get_json(app: &Router, uri: &str) -> (StatusCode, serde_json::Value) {
+        let (status, body) = get_body(app, uri).await;
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    async fn post(app: &Router, uri: &str, body: &str) -> (StatusCode, serde_json::Value) {
+        let request = Request::post(uri).header("Content-Type", "application/json").body(Body::from(body.to_string())).unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap())
+    }
+
+    async fn delete(app: &Router, uri: &str) -> (StatusCode, serde_json::Value) {
+        let response = app.clone().oneshot(Request::delete(uri).body(Body::empty()).unwrap()).await.unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap())
+    }
+
+    /// A test's scratch directory, removed on drop so a failed assert doesn't
+    /// leave it behind
+    struct TestDir(std::path::PathBuf);
+
+    impl TestDir {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("blackbox_http_{}_{}", name, std::process::id()));
+            let _ = std::fs::remove_dir_all(&dir);
+            Self(dir)
+        }
+
+        fn incidents(&self) -> Arc<IncidentManager> {
+            Arc::new(IncidentManager::new(self.0.clone()).unwrap())
+        }
+    }
+
+    impl Drop for TestDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// The API over `state`, its incidents kept in a `TestDir` named `name`
+    fn test_router(name: &str, state: AppState) -> (Router, TestDir) {
+        let dir = TestDir::new(name);
+        (router(state, dir.incidents()), dir)
+    }
+
+    fn protected_app(name: &str, cors_allow_origins: Vec<String>) -> (Router, TestDir) {
+        let options = HttpOptions { token: Some("s3cret".to_string()), cors_allow_origins, ..Default::default() };
+        let (app, dir) = test_router(name, AppState::new());
+        (options.apply(app.route("/", get(|| async { "ui" }))).unwrap(), dir)
+    }
+
+    async fn status_of(app: &Router, request: Request<Body>) -> (StatusCode, axum::http::HeaderMap) {
+        let response = app.clone().oneshot(request).await.unwrap();
+        (response.status(), response.headers().clone())
+    }
+
+    #[tokio::test]
+    async fn test_bearer_token() {
+        let (app, _dir) = protected_app("auth", vec![]);
+        let get = |uri: &str, auth: Option<&str>| {
+            let mut request = Request::get(uri);
+            if let Some(auth) = auth {
+                request = request.header("Authorization", auth);
+            }
+            request.body(Body::empty()).unwrap()
+        };
+
+        let (status, headers) = status_of(&app, get("/stats", None)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(headers["www-authenticate"], "Bearer");
+        assert_eq!(status_of(&app, get("/stats", Some("Bearer wrong"))).await.0, StatusCode::UNAUTHORIZED);
+        assert_eq!(status_of(&app, get("/stats", Some("s3cret"))).await.0, StatusCode::UNAUTHORIZED);
+        assert_eq!(status_of(&app, get("/stats", Some("Bearer s3cret"))).await.0, StatusCode::OK);
+        let arm = Request::post("/fault")
+            .header("Content-Type", "application/json")
+            .body(Body::from(r#"{"symbol":"BTC/USD","type":"drop"}"#))
+            .unwrap();
+        assert_eq!(status_of(&app, arm).await.0, StatusCode::UNAUTHORIZED);
+        // Open without a token
+        assert_eq!(status_of(&app, get("/health", None)).await.0, StatusCode::OK);
+        assert_eq!(status_of(&app, get("/", None)).await.0, StatusCode::OK);
+        assert_eq!(status_of(&app, get("/live", None)).await.0, StatusCode::OK);
+        assert_eq!(status_of(&app, get("/ready", None)).await.0, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(status_of(&app, get("/info", None)).await.0, StatusCode::UNAUTHORIZED);
+        // No CORS headers unless asked for
+        let (_, headers) = status_of(&app, get("/stats", Some("Bearer s3cret"))).await;
+        assert!(!headers.contains_key("access-control-allow-origin"));
+    }
+
+    #[tokio::test]
+    async fn test_cors_preflight() {
+        let (app, _dir) = protected_app("cors", vec!["http://grafana.local:3000".to_string()]);
+        let preflight = |origin: &str| Request::builder()
+            .method(Method::OPTIONS)
+            .uri("/book/BTC%2FUSD/top")
+            .header("Origin", origin)
+            .header("Access-Control-Request-Method", "GET")
+            .header("Access-Control-Request-Headers", "authorization")
+            .body(Body::empty())
+            .unwrap();
+
+        // Answered before the token check, which a browser's preflight can't pass
+        let (status, headers) = status_of(&app, preflight("http://grafana.local:3000")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(headers["access-control-allow-origin"], "http://grafana.local:3000");
+        assert!(headers["access-control-allow-headers"].to_str().unwrap().contains("authorization"));
+        let (_, headers) = status_of(&app, preflight("http://elsewhere.example")).await;
+        assert!(!headers.contains_key("access-control-allow-origin"));
+
+        let request = Request::get("/stats")
+            .header("Origin", "http://grafana.local:3000")
+            .header("Authorization", "Bearer s3cret")
+            .body(Body::empty())
+            .unwrap();
+        let (status, headers) = status_of(&app, request).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(headers["access-control-allow-origin"], "http://grafana.local:3000");
+        // A 401 is readable cross-origin too
+        let request = Request::get("/stats").header("Origin", "http://grafana.local:3000").body(Body::empty()).unwrap();
+        let (status, headers) = status_of(&app, request).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(headers["access-control-allow-origin"], "http://grafana.local:3000");
+
+        let any = HttpOptions { cors_allow_origins: vec!["*".to_string()], ..Default::default() };
+        let app = any.apply(Router::new().route("/x", get(|| async { "x" }))).unwrap();
+        let request = Request::get("/x").header("Origin", "http://a.example").body(Body::empty()).unwrap();
+        assert_eq!(status_of(&app, request).await.1["access-control-allow-origin"], "*");
+        let bad = HttpOptions { cors_allow_origins: vec!["http://a\nb".to_string()], ..Default::default() };
+        assert!(bad.apply(Router::new()).is_err());
+    }
+
+    #[test]
+    fn test_token_bucket_refill() {
+        let limit: RateLimit = "2/min".parse().unwrap();
+        let start = Instant::now();
+        let mut bucket = TokenBucket::full(limit, start);
+        assert!(bucket.take(limit, start).is_ok());
+        assert!(bucket.take(limit, start).is_ok());
+        // Empty: one token comes back every 30s
+        assert_eq!(bucket.take(limit, start), Err(Duration::from_secs(30)));
+        let wait = bucket.take(limit, start + Duration::from_secs(20)).unwrap_err();
+        assert!((wait.as_secs_f64() - 10.0).abs() < 1e-6, "{:?}", wait);
+        assert!(bucket.take(limit, start + Duration::from_secs(30)).is_ok());
+        assert!(bucket.take(limit, start + Duration::from_secs(30)).is_err());
+        // A long idle spell refills to the burst size and no further
+        let later = start + Duration::from_secs(3600);
+        assert!(bucket.take(limit, later).is_ok());
+        assert!(bucket.take(limit, later).is_ok());
+        assert!(bucket.take(limit, later).is_err());
+    }
+
+    #[test]
+    fn test_parse_rate_limit() {
+        assert_eq!("5/min".parse::<RateLimit>().unwrap(), RateLimit::per_minute(5));
+        assert_eq!("10/s".parse::<RateLimit>().unwrap(), RateLimit { requests: 10, period: Duration::from_secs(1) });
+        assert_eq!("100/h".parse::<RateLimit>().unwrap().to_string(), "100/h");
+        for bad in ["5", "0/min", "x/min", "5/day", "-1/s"] {
+            assert!(bad.parse::<RateLimit>().is_err(), "{}", bad);
+        }
+
+        let limits = rate_limits(&[
+            ("/export-bug".to_string(), Some(RateLimit::per_minute(1))),
+            ("/fault".to_string(), None),
+            ("/replay".to_string(), Some(RateLimit::per_minute(60))),
+        ]);
+        assert!(limits.contains(&("/export-bug".to_string(), RateLimit::per_minute(1))));
+        assert!(limits.contains(&("/record".to_string(), RateLimit::per_minute(10))));
+        assert!(limits.contains(&("/replay".to_string(), RateLimit::per_minute(60))));
+        assert!(!limits.iter().any(|(route, _)| route == "/fault"));
+        assert_eq!(rate_limits(&[]).len(), DEFAULT_RATE_LIMITS.len());
+    }
+
+    #[tokio::test]
+    async fn test_rate_limits_are_per_route() {
+        let options = HttpOptions {
+            rate_limits: vec![("/a".to_string(), "2/min".parse().unwrap()), ("/b".to_string(), "1/h".parse().unwrap())],
+            ..Default::default()
+        };
+        use axum::routing::{delete, post};
+        let handler = || async { "ok" };
+        let app = options.apply(Router::new()
+            .route("/a", post(handler).get(handler))
+            .route("/a/:id", delete(handler))
+            .route("/ab", post(handler))
+            .route("/b", post(handler))
+            .route("/c", post(handler))).unwrap();
+        let send = |method: Method, uri: &str| Request::builder().method(method).uri(uri).body(Body::empty()).unwrap();
+
+        assert_eq!(status_of(&app, send(Method::POST, "/a")).await.0, StatusCode::OK);
+        // Routes under `/a` share its bucket
+        assert_eq!(status_of(&app, send(Method::DELETE, "/a/1")).await.0, StatusCode::OK);
+        let (status, headers) = status_of(&app, send(Method::POST, "/a")).await;
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(headers["retry-after"], "30");
+        // Reads aren't limited, and `/ab` isn't under `/a`
+        assert_eq!(status_of(&app, send(Method::GET, "/a")).await.0, StatusCode::OK);
+        assert_eq!(status_of(&app, send(Method::POST, "/ab")).await.0, StatusCode::OK);
+        // `/b` has a bucket of its own
+        assert_eq!(status_of(&app, send(Method::POST, "/b")).await.0, StatusCode::OK);
+        let (status, headers) = status_of(&app, send(Method::POST, "/b")).await;
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(headers["retry-after"], "3600");
+        for _ in 0..10 {
+            assert_eq!(status_of(&app, send(Method::POST, "/c")).await.0, StatusCode::OK);
+        }
+
+        // The 429 body is the usual error shape
+        let response = app.clone().oneshot(send(Method::POST, "/a")).await.unwrap();
+        let body: serde_json::Value =
+            serde_json::from_slice(&axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+        assert_eq!(body["code"], "rate_limited");
+        assert!(body["message"].as_str().unwrap().contains("2/min for /a"), "{}", body);
+    }
+
+    #[tokio::test]
+    async fn test_config_matches_bundle_config() {
+        use crate::disk::{DiskGuard, DiskPolicy};
+        use std::io::Read;
+
+        let mut state = AppState::new();
+        state.ws_url = Some("wss://beta-ws.kraken.com/v2".to_string());
+        state.ping_interval = Some(std::time::Duration::from_secs(5));
+        state.strict_book = true;
+        state.record_disk_guard = Some(DiskGuard::new(1 << 30, DiskPolicy::DeleteOldest));
+        state.recorder_options.rotation.max_bytes = Some(1 << 20);
+        state.recorder_options.channel_filter = Some(vec!["book".to_string()]);
+        state.set_requested_symbols(vec!["BTC/USD".to_string(), "ETH/USD".to_string()]).await;
+        state.set_depth("BTC/USD", 1000);
+        state.set_depth("ETH/USD", 25);
+        let (app, dir) = test_router("config", state);
+
+        let (status, mut config) = get_json(&app, "/config").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(config["version"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(config["mode"], "live");
+        assert_eq!(config["ws_url"], "wss://beta-ws.kraken.com/v2");
+        assert_eq!(config["ping_interval_ms"], 5000);
+        assert_eq!(config["symbols"], serde_json::json!([
+            {"symbol": "BTC/USD", "depth": 1000},
+            {"symbol": "ETH/USD", "depth": 25},
+        ]));
+        assert_eq!(config["book"]["strict_book"], true);
+        assert_eq!(config["recording"]["rotate_max_bytes"], 1 << 20);
+        assert_eq!(config["recording"]["channels"], serde_json::json!(["book"]));
+        assert_eq!(config["recording"]["disk_guard"], serde_json::json!({"max_bytes": 1 << 30, "policy": "delete-oldest"}));
+        assert_eq!(config["incidents"]["dir"], dir.0.to_str().unwrap());
+        assert_eq!(config["fault"]["armed"], false);
+
+        // Bundles carry the same document
+        let response = app.clone().oneshot(Request::post("/export-bug").body(Body::empty()).unwrap()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let mut bundle = zip::ZipArchive::new(std::io::Cursor::new(bytes)).unwrap();
+        let mut text = String::new();
+        bundle.by_name("config.json").unwrap().read_to_string(&mut text).unwrap();
+        let mut bundled: serde_json::Value = serde_json::from_str(&text).unwrap();
+        assert!(bundled["generated_at"].is_string());
+        bundled["generated_at"] = serde_json::Value::Null;
+        config["generated_at"] = serde_json::Value::Null;
+        assert_eq!(bundled, config);
+    }
+
+    #[tokio::test]
+    async fn test_book_checksum_on_demand() {
+        use blackbox_core::orderbook::Orderbook;
+        use blackbox_core::types::InstrumentInfo;
+        use crate::state::StoredBook;
+        use rust_decimal_macros::dec;
+
+        let state = AppState::new();
+        let (app, _dir) = test_router("checksum", state.clone());
+        assert_eq!(get_json(&app, "/book/BTC%2FUSD/checksum").await.0, StatusCode::NOT_FOUND);
+
+        let mut book = Orderbook::new();
+        book.apply_snapshot(vec![(dec!(100.0), dec!(2)), (dec!(99.5), dec!(0.5))], vec![(dec!(100.5), dec!(1.25))]);
+        state.orderbooks.insert("BTC/USD".to_string(), StoredBook::new(book, 10));
+        let (status, body) = get_json(&app, "/book/BTC%2FUSD/checksum").await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body["code"], "book_not_ready");
+        assert!(body["message"].as_str().unwrap().contains("Precisions"));
+
+        state.insert_instrument("BTC/USD".to_string(), InstrumentInfo {
+            symbol: "BTC/USD".to_string(),
+            price_precision: 1,
+            qty_precision: 8,
+            ..Default::default()
+        });
+        let (status, body) = get_json(&app, "/book/BTC%2FUSD/checksum").await;
+        assert_eq!(status, StatusCode::OK);
+        // Asks low to high, then bids high to low: price then qty, '.' and leading zeros dropped
+        let expected = "1005125000000".to_string() + "1000200000000" + "99550000000";
+        assert_eq!(body["preview"], expected);
+        assert_eq!(body["checksum_len"], expected.len());
+        assert_eq!(body["computed"], compute_crc32(&expected));
+        assert_eq!(body["top_asks"], serde_json::json!([["100.5", "1.25"]]));
+        assert_eq!(body["top_bids"], serde_json::json!([["100.0", "2"], ["99.5", "0.5"]]));
+        assert!(body["generated_at"].is_string());
+
+        state.orderbooks.insert("ETH/USD".to_string(), StoredBook::new(Orderbook::new(), 10));
+        state.insert_instrument("ETH/USD".to_string(), InstrumentInfo { symbol: "ETH/USD".to_string(), ..Default::default() });
+        let (status, body) = get_json(&app, "/book/ETH%2FUSD/checksum").await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert!(body["message"].as_str().unwrap().contains("empty"));
+    }
+
+    #[tokio::test]
+    async fn test_export_bug_picks_the_symbol() {
+        use blackbox_core::health::SymbolHealth;
+        use std::io::Read;
+
+        let state = AppState::new();
+        state.set_requested_symbols(vec!["BTC/USD".to_string(), "ETH/USD".to_string(), "SOL/USD".to_string()]).await;
+        let now = Utc::now();
+        for (symbol, mismatch) in [("BTC/USD", Some(now - chrono::Duration::seconds(5))), ("ETH/USD", Some(now)), ("SOL/USD", None)] {
+            let mut health = SymbolHealth::new(symbol.to_string());
+            health.last_checksum_mismatch = mismatch;
+            state.health.insert(symbol.to_string(), health);
+        }
+        let (app, _dir) = test_router("export_bug", state);
+        let export = |body: &'static str| {
+            let app = app.clone();
+            async move {
+                let response = app.oneshot(Request::post("/export-bug").body(Body::from(body)).unwrap()).await.unwrap();
+                let status = response.status();
+                let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                if status != StatusCode::OK {
+                    return (status, None);
                 }
-                Err(e) => {
-                    (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({
-                        "error": format!("Failed to read bundle: {}", e)
-                    }))).into_response()
+                let mut bundle = zip::ZipArchive::new(std::io::Cursor::new(bytes)).unwrap();
+                let mut text = String::new();
+                bundle.by_name("metadata.json").unwrap().read_to_string(&mut text).unwrap();
+                let metadata: serde_json::Value = serde_json::from_str(&text).unwrap();
+                (status, metadata["incident"]["symbol"].as_str().map(str::to_string))
+            }
+        };
+
+        // The most recent mismatch wins by default
+        assert_eq!(export("").await, (StatusCode::OK, Some("ETH/USD".to_string())));
+        assert_eq!(export(r#"{"symbol":"SOL/USD"}"#).await, (StatusCode::OK, Some("SOL/USD".to_string())));
+        assert_eq!(export(r#"{"symbol":"DOGE/USD"}"#).await.0, StatusCode::NOT_FOUND);
+        assert_eq!(export("{").await.0, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_probes_and_info() {
+        use blackbox_ws::client::{DisconnectCause, WsEvent};
+        use rust_decimal_macros::dec;
+
+        let dir = TestDir::new("probes");
+        let state = AppState::new();
+        let incident_manager = dir.incidents();
+        let app = router(state.clone(), incident_manager.clone());
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let processor_state = state.clone();
+        let processor = tokio::spawn(async move {
+            crate::process_ws_events(&processor_state, &incident_manager, &mut rx).await;
+        });
+        let ready = || async { get_json(&app, "/ready").await };
+
+        assert_eq!(get_json(&app, "/live").await, (StatusCode::OK, serde_json::json!({"live": true})));
+        let (status, body) = ready().await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body, serde_json::json!({"ready": false, "connected": false, "books": 0}));
+
+        tx.send(WsEvent::Connected).unwrap();
+        while !state.is_connected() {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(ready().await.0, StatusCode::SERVICE_UNAVAILABLE);
+
+        let snapshot = WsEvent::BookSnapshot {
+            symbol: "BTC/USD".to_string(),
+            bids: vec![(dec!(100), dec!(1))],
+            asks: vec![(dec!(101), dec!(1))],
+            checksum: None,
+        };
+        tx.send(snapshot).unwrap();
+        while state.orderbooks.is_empty() {
+            tokio::task::yield_now().await;
+        }
+        let (status, body) = ready().await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, serde_json::json!({"ready": true, "connected": true, "books": 1}));
+
+        // Held books go stale on disconnect; not ready until reconnected
+        tx.send(WsEvent::Disconnected { cause: DisconnectCause::ServerClose }).unwrap();
+        while state.is_connected() {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(ready().await.0, StatusCode::SERVICE_UNAVAILABLE);
+
+        let (status, info) = get_json(&app, "/info").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(info["version"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(info["mode"], "live");
+        assert!(info["build_time"].is_string());
+        assert!(info["uptime_seconds"].is_u64());
+
+        drop(tx);
+        processor.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_book_etag_and_compression() {
+        use blackbox_core::orderbook::Orderbook;
+        use rust_decimal_macros::dec;
+
+        let state = AppState::new();
+        let mut book = Orderbook::new();
+        let bids = (0..1000).map(|i| (dec!(50000) - Decimal::from(i), dec!(0.5))).collect();
+        let asks = (0..1000).map(|i| (dec!(50001) + Decimal::from(i), dec!(1.25))).collect();
+        book.apply_snapshot(bids, asks);
+        state.orderbooks.insert("BTC/USD".to_string(), StoredBook::new(book, 1000));
+        let (app, _dir) = test_router("etag", state.clone());
+        let get = |uri: &str, headers: &[(&str, &str)]| {
+            let mut request = Request::get(uri);
+            for (name, value) in headers {
+                request = request.header(*name, *value);
+            }
+            let request = request.body(Body::empty()).unwrap();
+            let app = app.clone();
+            async move {
+                let response = app.oneshot(request).await.unwrap();
+                let (parts, body) = response.into_parts();
+                (parts.status, parts.headers, axum::body::to_bytes(body, usize::MAX).await.unwrap())
+            }
+        };
+
+        let (status, headers, plain) = get("/book/BTC%2FUSD", &[]).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(!headers.contains_key("content-encoding"));
+        let etag = headers["etag"].to_str().unwrap().to_string();
+        assert!(etag.starts_with("W/\""));
+
+        let (status, headers, gzipped) = get("/book/BTC%2FUSD", &[("Accept-Encoding", "gzip")]).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(headers["content-encoding"], "gzip");
+        assert_eq!(headers["vary"], "accept-encoding");
+        assert_eq!(headers["etag"], etag.as_str());
+        assert!(gzipped.len() * 5 < plain.len(), "{} -> {} bytes", plain.len(), gzipped.len());
+
+        // Unchanged: 304 without a body, for either representation
+        for accept in ["identity", "gzip"] {
+            let (status, headers, body) = get("/book/BTC%2FUSD", &[("If-None-Match", &etag), ("Accept-Encoding", accept)]).await;
+            assert_eq!(status, StatusCode::NOT_MODIFIED);
+            assert_eq!(headers["etag"], etag.as_str());
+            assert!(!headers.contains_key("content-encoding"));
+            assert!(body.is_empty());
+        }
+        assert_eq!(get("/book/BTC%2FUSD", &[("If-None-Match", "W/\"other\", *")]).await.0, StatusCode::NOT_MODIFIED);
+        assert_eq!(get("/book/BTC%2FUSD/top", &[("If-None-Match", &etag)]).await.0, StatusCode::NOT_MODIFIED);
+
+        // Any update, or going stale, makes a new version
+        state.orderbooks.get_mut("BTC/USD").unwrap().apply_updates(vec![(dec!(50000), dec!(2))], vec![]);
+        let (status, headers, _) = get("/book/BTC%2FUSD", &[("If-None-Match", &etag)]).await;
+        assert_eq!(status, StatusCode::OK);
+        let updated = headers["etag"].to_str().unwrap().to_string();
+        assert_ne!(updated, etag);
+        state.mark_books_stale();
+        assert_eq!(get("/book/BTC%2FUSD/top", &[("If-None-Match", &updated)]).await.0, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_fault_arm_fire_disarm() {
+        use blackbox_core::types::InstrumentInfo;
+        let state = AppState::new();
+        state.set_requested_symbols(vec!["BTC/USD".to_string()]).await;
+        let (app, _dir) = test_router("fault", state.clone());
+
+        let (status, body) = get_json(&app, "/fault").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["armed"], false);
+
+        let (status, body) = post(&app, "/fault", r#"{"symbol":"BTC/USD","type":"mutate_qty","params":{"delta_ticks":3}}"#).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, serde_json::json!({
+            "armed": true,
+            "symbol": "BTC/USD",
+            "fault": {"type": "mutate_qty", "params": {"delta_ticks": 3}},
+            "supported": false,
+        }));
+        // Mutating a qty takes the symbol's instrument info
+        state.insert_instrument("BTC/USD".to_string(), InstrumentInfo { symbol: "BTC/USD".to_string(), ..Default::default() });
+        assert_eq!(get_json(&app, "/fault").await.1["supported"], true);
+        assert_eq!(state.get_events(1).await[0].event.kind(), "fault_injected");
+        // Fires on the next BTC/USD update only
+        assert!(state.fault_injector.consume("ETH/USD").is_none());
+        assert_eq!(state.fault_injector.consume("BTC/USD"), Some(FaultType::MutateQty { delta_ticks: 3 }));
+        assert!(state.fault_injector.consume("BTC/USD").is_none());
+        let (_, body) = get_json(&app, "/fault").await;
+        assert_eq!(body["armed"], false);
+        assert_eq!(body["fault"]["type"], "mutate_qty");
+
+        let (_, body) = post(&app, "/fault", r#"{"symbol":"BTC/USD","type":"drop"}"#).await;
+        assert_eq!(body["fault"], serde_json::json!({"type": "drop"}));
+        let (status, body) = delete(&app, "/fault").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["armed"], false);
+        assert!(state.fault_injector.consume("BTC/USD").is_none());
+
+        let (status, body) = post(&app, "/fault", r#"{"symbol":"BTC/USD","type":"reorder"}"#).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["code"], "fault_replay_only");
+        assert_eq!(body["detail"]["supported"], false);
+        assert_eq!(post(&app, "/fault", r#"{"symbol":"BTC/USD","type":"explode"}"#).await.0, StatusCode::BAD_REQUEST);
+        assert_eq!(post(&app, "/fault", r#"{"symbol":"DOGE/USD","type":"drop"}"#).await.0, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_frames_endpoint() {
+        let state = AppState::new();
+        let (app, _dir) = test_router("frames", state.clone());
+        let book_frame = |symbol: &str, i: usize| format!(
+            r#"{{"channel":"book","type":"update","data":[{{"symbol":"{}","bids":[{{"price":{},"qty":1}}],"asks":[],"checksum":1}}]}}"#,
+            symbol, i
+        );
+        let pushed = crate::state::FRAME_BUFFER_CAPACITY + 5;
+        for i in 0..pushed {
+            state.buffer_frame(&book_frame("BTC/USD", i)).await;
+        }
+        state.buffer_frame(&book_frame("ETH/USD", 0)).await;
+        state.buffer_frame(r#"{"channel":"heartbeat"}"#).await;
+        // Oldest frames go first
+        let btc = state.per_symbol_frames.get("BTC/USD").unwrap().clone();
+        assert_eq!(btc.read().await.len(), crate::state::FRAME_BUFFER_CAPACITY);
+        assert_eq!(btc.read().await.front().unwrap().1, book_frame("BTC/USD", 5));
+        assert_eq!(state.per_symbol_frames.len(), 2);
+
+        let response = app.clone()
+            .oneshot(Request::get("/frames/BTC%2FUSD?limit=3").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-type"], "application/x-ndjson");
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let lines: Vec<RecordedFrame> = std::str::from_utf8(&bytes).unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let expected: Vec<String> = (pushed - 3..pushed).map(|i| book_frame("BTC/USD", i)).collect();
+        assert_eq!(lines.iter().map(|f| f.raw_frame.clone()).collect::<Vec<_>>(), expected);
+
+        let (status, body) = get_body(&app, "/frames/BTC%2FUSD").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(std::str::from_utf8(&body).unwrap().lines().count(), DEFAULT_FRAMES_LIMIT);
+        let (status, _) = get_body(&app, "/frames/SOL%2FUSD").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_stats_report_the_frame_buffer() {
+        let mut state = AppState::new();
+        state.last_frames_capacity = 3;
+        let (app, _dir) = test_router("last_frames", state.clone());
+        for i in 0..5 {
+            state.push_last_frame(&format!(r#"{{"channel":"heartbeat","n":{}}}"#, i)).await;
+        }
+        let frames: Vec<String> = state.last_frames.read().await.iter().map(|(_, raw)| raw.clone()).collect();
+        assert_eq!(frames, (2..5).map(|i| format!(r#"{{"channel":"heartbeat","n":{}}}"#, i)).collect::<Vec<_>>());
+
+        let (status, stats) = get_json(&app, "/stats").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(stats["frames_buffered"], 3);
+        assert_eq!(stats["frame_buffer_capacity"], 3);
+
+        // 0 turns the buffer off
+        let mut off = AppState::new();
+        off.last_frames_capacity = 0;
+        off.push_last_frame(r#"{"channel":"heartbeat"}"#).await;
+        assert!(off.last_frames.read().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_metrics_are_rendered() {
+        let dir = TestDir::new("metrics");
+        let incidents = dir.incidents();
+        let (status, _) = get_body(&router(AppState::new(), incidents.clone()), "/metrics").await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+
+        let recorder = metrics_exporter_prometheus::PrometheusBuilder::new().build_recorder();
+        let mut state = AppState::new();
+        state.metrics = Some(recorder.handle());
+        metrics::with_local_recorder(&recorder, || {
+            crate::metrics::record_checksum_fail("BTC/USD");
+            crate::metrics::record_checksum_fail("BTC/USD");
+        });
+        let response = router(state, incidents)
+            .oneshot(Request::get("/metrics").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers()["content-type"].to_str().unwrap().starts_with("text/plain"));
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let text = String::from_utf8(bytes.to_vec()).unwrap();
+        assert!(text.contains("checksum_fail_total{symbol=\"BTC/USD\"} 2"), "{}", text);
+    }
+
+    #[tokio::test]
+    async fn test_add_and_remove_symbols() {
+        let dir = TestDir::new("symbols");
+        let incidents = dir.incidents();
+        let (status, _) = post(&router(AppState::new(), incidents.clone()), "/symbols", r#"{"symbol":"ETH/USD"}"#).await;
+        assert_eq!(status, StatusCode::CONFLICT); // not live
+
+        let mut state = AppState::new();
+        let (commands, mut command_rx) = tokio::sync::mpsc::unbounded_channel();
+        state.ws_commands = Some(commands);
+        state.set_requested_symbols(vec!["BTC/USD".to_string()]).await;
+        state.set_depth("BTC/USD", 10);
+        // Stands in for the client: Kraken refuses BAD/USD
+        let sent = tokio::spawn(async move {
+            let mut sent = Vec::new();
+            while let Some(command) = command_rx.recv().await {
+                match command {
+                    ClientCommand::Subscribe { symbol, depth, reply } => {
+                        let result = if symbol == "BAD/USD" { Err(format!("Currency pair not supported {}", symbol)) } else { Ok(25) };
+                        let _ = reply.send(result);
+                        sent.push(format!("subscribe {} {:?}", symbol, depth));
+                    }
+                    ClientCommand::Unsubscribe { symbol, reply } => {
+                        let _ = reply.send(Ok(()));
+                        sent.push(format!("unsubscribe {}", symbol));
+                    }
+                    ClientCommand::Resync { symbol } => sent.push(format!("resync {}", symbol)),
                 }
             }
+            sent
+        });
+        let app = router(state.clone(), incidents);
+
+        let (status, body) = post(&app, "/symbols", r#"{"symbol":"ETH/USD","depth":20}"#).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, serde_json::json!([{"symbol": "BTC/USD", "depth": 10}, {"symbol": "ETH/USD", "depth": 25}]));
+        let (status, body) = post(&app, "/symbols", r#"{"symbol":"BAD/USD"}"#).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["code"], "subscribe_rejected");
+        assert_eq!(body["message"], "Currency pair not supported BAD/USD");
+        assert!(!state.get_requested_symbols().await.contains(&"BAD/USD".to_string()));
+        assert!(!state.depths.contains_key("BAD/USD")); // a retry isn't a 409
+        let (status, _) = post(&app, "/symbols", r#"{"symbol":"ETH/USD"}"#).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        state.health.insert("BTC/USD".to_string(), blackbox_core::health::SymbolHealth::new("BTC/USD".to_string()));
+        let (status, body) = delete(&app, "/symbols/BTC%2FUSD").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, serde_json::json!([{"symbol": "ETH/USD", "depth": 25}]));
+        assert!(!state.health.contains_key("BTC/USD"));
+        let (status, _) = delete(&app, "/symbols/BTC%2FUSD").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        // Racing adds of one symbol: only one subscribes
+        let (a, b) = tokio::join!(post(&app, "/symbols", r#"{"symbol":"SOL/USD"}"#), post(&app, "/symbols", r#"{"symbol":"SOL/USD"}"#));
+        let mut statuses = [a.0, b.0];
+        statuses.sort();
+        assert_eq!(statuses, [StatusCode::OK, StatusCode::CONFLICT]);
+        assert_eq!(state.get_requested_symbols().await, ["ETH/USD", "SOL/USD"]);
+
+        drop(app);
+        drop(state);
+        assert_eq!(
+            sent.await.unwrap(),
+            ["subscribe ETH/USD Some(20)", "subscribe BAD/USD None", "unsubscribe BTC/USD", "subscribe SOL/USD None"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_record_start_status_stop() {
+        let dir = TestDir::new("record");
+        std::fs::create_dir_all(&dir.0).unwrap();
+        let state = AppState::new();
+        let app = router(state.clone(), Arc::new(IncidentManager::new(dir.0.join("incidents")).unwrap()));
+        let path = dir.0.join("http.ndjson");
+
+        let (status, body) = post(&app, "/record/start", r#"{"channels": ["trades"]}"#).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body["message"].as_str().unwrap().contains("trades"));
+        let (status, _) = post(&app, "/record/start", r#"{"rotate_size": "lots"}"#).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(!state.is_recording_enabled().await);
+
+        let start = serde_json::json!({ "path": path, "rotate_size": "1M", "channels": ["book"] }).to_string();
+        let (status, body) = post(&app, "/record/start", &start).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["recording"], true);
+        assert_eq!(body["path"], path.to_str().unwrap());
+        assert_eq!(state.recorder_options.rotation.max_bytes, None); // per recording, not the session's
+
+        let (status, body) = post(&app, "/record/start", "").await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert!(body["message"].as_str().unwrap().contains("http.ndjson"));
+
+        state.record_frame(r#"{"channel":"book","type":"update","data":[]}"#).await;
+        state.record_frame(r#"{"channel":"heartbeat"}"#).await; // filtered out
+        let (status, body) = get_body(&app, "/record/status").await;
+        assert_eq!(status, StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["recording"], true);
+        assert!(body["bytes_written"].is_u64());
+
+        let (status, body) = post(&app, "/record/stop", "").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["recording"], false);
+        assert!(body["path"].is_null());
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(content.lines().filter(|l| l.contains("raw_frame")).count(), 1);
+
+        let kinds: Vec<&str> = state.get_events(10).await.iter().map(|e| e.event.kind()).collect();
+        assert_eq!(kinds, ["record_started", "record_stopped"]);
+
+        // Racing starts: exactly one gets the recorder
+        let racing = |name: &str| serde_json::json!({ "path": dir.0.join(name) }).to_string();
+        let (start_a, start_b) = (racing("a.ndjson"), racing("b.ndjson"));
+        let (a, b) = tokio::join!(post(&app, "/record/start", &start_a), post(&app, "/record/start", &start_b));
+        let mut statuses = [a.0, b.0];
+        statuses.sort();
+        assert_eq!(statuses, [StatusCode::OK, StatusCode::CONFLICT]);
+        state.stop_recording().await;
+    }
+
+    #[tokio::test]
+    async fn test_integrity_after_mismatch() {
+        use blackbox_core::orderbook::Orderbook;
+        use blackbox_core::precision::PrecisionFormatter;
+        use blackbox_core::types::InstrumentInfo;
+        use rust_decimal_macros::dec;
+
+        let state = AppState::new();
+        let instrument = InstrumentInfo {
+            symbol: "BTC/USD".to_string(),
+            price_precision: 1,
+            qty_precision: 8,
+            price_increment: dec!(0.1),
+            qty_increment: dec!(0.00000001),
+            status: "online".to_string(),
+            ..Default::default()
+        };
+        let mut book = Orderbook::new();
+        book.apply_snapshot(vec![(dec!(100.0), dec!(1))], vec![(dec!(100.5), dec!(2))]);
+        let mut proof = IntegrityProof::new();
+        let formatter = PrecisionFormatter::from_instrument(&instrument);
+        let verification = crate::integrity::update_integrity_proof(&mut proof, &mut book, 0x1234_5678, &instrument, &formatter, "BTC/USD", None);
+        assert!(!verification.matched);
+        state.integrity_proofs.insert("BTC/USD".to_string(), proof);
+        state.integrity_proofs.insert("ETH/USD".to_string(), IntegrityProof::new());
+        let (app, _dir) = test_router("integrity", state);
+
+        let (status, body) = get_body(&app, "/integrity/BTC%2FUSD").await;
+        assert_eq!(status, StatusCode::OK);
+        let proof: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(proof["expected_checksum"], 0x1234_5678);
+        assert_ne!(proof["computed_checksum"], 0x1234_5678);
+        assert_eq!(proof["matched"], false);
+        assert!(proof["last_mismatch_ts"].is_string());
+        assert!(proof["diagnosis"].as_str().unwrap().contains("Expected 0x12345678"));
+        assert!(!proof["checksum_preview"].as_str().unwrap().is_empty());
+        assert_eq!(proof["top_bids"][0][0], "100.0");
+        assert!(proof["latency_stats"]["p95_ms"].is_u64());
+
+        let (status, body) = get_body(&app, "/integrity").await;
+        assert_eq!(status, StatusCode::OK);
+        let all: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(all.as_object().unwrap().len(), 2);
+        assert_eq!(all["BTC/USD"]["expected_checksum"], 0x1234_5678);
+        assert!(all["ETH/USD"]["last_mismatch_ts"].is_null());
+
+        let (status, _) = get_body(&app, "/integrity/SOL%2FUSD").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_list_and_download_incidents() {
+        let dir = TestDir::new("incidents");
+        let manager = dir.incidents();
+        let mut paths = Vec::new();
+        for (reason, symbol) in [(IncidentReason::ChecksumMismatch, Some("BTC/USD".to_string())), (IncidentReason::ManualExport, None)] {
+            let incident = manager.record_incident(reason, symbol, serde_json::json!({})).await;
+            let contents = crate::incident::BundleContents {
+                frames: vec![(incident.timestamp, r#"{"channel":"heartbeat"}"#.to_string())],
+                ..Default::default()
+            };
+            let path = manager.export_incident_bundle(&incident, contents).await.unwrap();
+            paths.push((incident.id, path));
+            tokio::time::sleep(std::time::Duration::from_millis(2)).await;
         }
-        Err(e) => {
-            (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({
-                "error": format!("Failed to export bundle: {}", e)
-            }))).into_response()
+        let app = router(AppState::new(), manager);
+
+        let (status, body) = get_body(&app, "/incidents").await;
+        assert_eq!(status, StatusCode::OK);
+        let list: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let list = list.as_array().unwrap();
+        assert_eq!(list.len(), 2);
+        // Newest first
+        assert_eq!(list[0]["id"], paths[1].0.as_str());
+        assert_eq!(list[0]["reason"], "ManualExport");
+        assert_eq!(list[1]["id"], paths[0].0.as_str());
+        assert_eq!(list[1]["symbol"], "BTC/USD");
+        assert_eq!(list[1]["zip_size"], std::fs::metadata(&paths[0].1).unwrap().len());
+        assert!(list[1]["created_at"].is_string());
+
+        let uri = format!("/incidents/{}/bundle", paths[0].0);
+        let response = app.clone().oneshot(Request::get(&uri).body(Body::empty()).unwrap()).await.unwrap();
+        assert_eq!(response.headers()["content-type"], "application/zip");
+        assert_eq!(
+            response.headers()["content-disposition"],
+            format!("attachment; filename=\"{}.zip\"", paths[0].0).as_str()
+        );
+        let (status, body) = get_body(&app, &uri).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, std::fs::read(&paths[0].1).unwrap());
+
+        let (status, body) = get_body(&app, "/incidents/incident_0_nope/bundle").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let error: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert!(error["message"].as_str().unwrap().contains("incident_0_nope"));
+    }
+
+    #[tokio::test]
+    async fn test_error_bodies() {
+        let (app, _dir) = test_router("errors", AppState::new());
+
+        // Unknown symbols: the same shape on every route, nothing else in it
+        for uri in [
+            "/book/DOGE%2FUSD",
+            "/book/DOGE%2FUSD/top",
+            "/book/DOGE%2FUSD/checksum",
+            "/frames/DOGE%2FUSD",
+            "/integrity/DOGE%2FUSD",
+        ] {
+            let (status, body) = get_json(&app, uri).await;
+            assert_eq!(status, StatusCode::NOT_FOUND, "{}", uri);
+            assert_eq!(body["code"], "symbol_not_found", "{}", uri);
+            assert!(body["message"].as_str().unwrap().contains("DOGE/USD"), "{}", uri);
+            assert_eq!(body.as_object().unwrap().len(), 2, "{}: {}", uri, body);
         }
+        let (status, body) = post(&app, "/export-bug", r#"{"symbol":"DOGE/USD"}"#).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["code"], "symbol_not_found");
+
+        let (status, body) = get_json(&app, "/incidents/incident_0_nope/bundle").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body, serde_json::json!({"code": "no_incident", "message": "No bundle for incident incident_0_nope"}));
+
+        // Bodies and query strings that don't parse
+        let (status, body) = post(&app, "/fault", r#"{"symbol":"BTC/USD"}"#).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "invalid_body");
+        let (status, body) = post(&app, "/record/start", "{").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "invalid_body");
+        let (status, body) = get_json(&app, "/events?limit=many").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "invalid_body");
+
+        let (status, body) = get_json(&app, "/replay/status").await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body["code"], "replay_not_active");
+        let (status, body) = delete(&app, "/symbols/BTC%2FUSD").await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body["code"], "not_live");
     }
 }
-
@@ -1,18 +1,90 @@
+use crate::disk::{DiskCheck, DiskGuard};
 use blackbox_core::incident::{Incident, IncidentMetadata, IncidentReason};
+use blackbox_core::diff::BookDiff;
+use blackbox_core::orderbook::BookSnapshot;
 use blackbox_core::types::InstrumentInfo;
 use chrono::{DateTime, Utc};
-use serde_json;
+use serde::Serialize;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use zip::{ZipWriter, write::FileOptions, CompressionMethod};
 use std::io::Write;
 
+/// `reason` as serialized, e.g. "ChecksumMismatch"
+fn reason_name(reason: &IncidentReason) -> String {
+    match serde_json::to_value(reason) {
+        Ok(serde_json::Value::String(reason)) => reason,
+        _ => format!("{:?}", reason),
+    }
+}
+
+/// One entry of `GET /incidents`
+#[derive(Debug, Clone, Serialize)]
+pub struct IncidentSummary {
+    pub id: String,
+    pub reason: String,
+    pub symbol: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub zip_size: Option<u64>, // None when no bundle was written
+}
+
+impl IncidentSummary {
+    fn from_incident(incident: &Incident) -> Self {
+        Self {
+            id: incident.id.clone(),
+            reason: reason_name(&incident.reason),
+            symbol: incident.symbol.clone(),
+            created_at: incident.timestamp,
+            zip_size: None,
+        }
+    }
+
+    /// Read a bundle's metadata.json, as written by `export_incident_bundle`
+    /// or the TUI export; falls back to the file name and modification time
+    fn from_bundle(path: &Path) -> Option<Self> {
+        let id = path.file_stem()?.to_str()?.to_string();
+        let metadata: Option<serde_json::Value> = std::fs::File::open(path)
+            .ok()
+            .and_then(|file| zip::ZipArchive::new(file).ok())
+            .and_then(|mut archive| {
+                let entry = archive.by_name("metadata.json").ok()?;
+                serde_json::from_reader(entry).ok()
+            });
+        if let Some(metadata) = &metadata {
+            if let Ok(incident) = serde_json::from_value::<Incident>(metadata["incident"].clone()) {
+                return Some(Self { id, ..Self::from_incident(&incident) });
+            }
+            if let Ok(meta) = serde_json::from_value::<crate::integrity::IncidentMeta>(metadata.clone()) {
+                return Some(Self { id, reason: meta.reason, symbol: Some(meta.symbol), created_at: meta.created_at, zip_size: None });
+            }
+        }
+        let modified = std::fs::metadata(path).and_then(|m| m.modified()).ok()?;
+        Some(Self { id, reason: "unknown".to_string(), symbol: None, created_at: modified.into(), zip_size: None })
+    }
+}
+
 #[derive(Clone)]
+/// What goes into an incident bundle besides the incident; each optional
+/// part is written as its own file when present
+#[derive(Debug, Default)]
+pub struct BundleContents {
+    pub config: serde_json::Value, // a RuntimeConfig
+    pub health: serde_json::Value,
+    pub instrument: Option<InstrumentInfo>,
+    pub book_top: Option<serde_json::Value>,
+    pub book_full: Option<BookSnapshot>, // every level of the reconstructed book
+    pub book_diff: Option<BookDiff>,
+    pub checksums: Option<serde_json::Value>,
+    pub frames: Vec<(DateTime<Utc>, String)>, // only t-30s to t+5s are kept
+}
+
 pub struct IncidentManager {
     incidents: Arc<RwLock<Vec<Incident>>>,
     last_incident: Arc<RwLock<Option<Incident>>>,
     incidents_dir: PathBuf,
+    disk_guard: Option<DiskGuard>, // Limits the space taken by bundles in incidents_dir
+    found_on_startup: Arc<Vec<IncidentSummary>>, // Bundles already in incidents_dir when created
 }
 
 impl IncidentManager {
@@ -23,11 +95,50 @@ impl IncidentManager {
         }
         std::fs::create_dir_all(&incidents_dir)?;
         
-        Ok(Self {
+        let mut manager = Self {
             incidents: Arc::new(RwLock::new(Vec::new())),
             last_incident: Arc::new(RwLock::new(None)),
             incidents_dir,
-        })
+            disk_guard: None,
+            found_on_startup: Arc::new(Vec::new()),
+        };
+        let found = manager.bundle_files().iter().filter_map(|path| IncidentSummary::from_bundle(path)).collect();
+        manager.found_on_startup = Arc::new(found);
+        Ok(manager)
+    }
+
+    pub fn with_disk_guard(mut self, guard: Option<DiskGuard>) -> Self {
+        self.disk_guard = guard;
+        self
+    }
+
+    /// Incident bundles in `incidents_dir`, by name
+    fn bundle_files(&self) -> Vec<PathBuf> {
+        let mut files: Vec<PathBuf> = std::fs::read_dir(&self.incidents_dir)
+            .map(|entries| entries.filter_map(|e| e.ok().map(|e| e.path())).collect())
+            .unwrap_or_default();
+        files.retain(|p| p.is_file() && p.extension().is_some_and(|ext| ext == "zip"));
+        files.sort();
+        files
+    }
+
+    /// Make room for a new bundle, or refuse it, per the disk guard
+    fn check_disk(&self) -> anyhow::Result<()> {
+        let Some(guard) = &self.disk_guard else {
+            return Ok(());
+        };
+        match guard.enforce(&self.bundle_files(), &[]) {
+            DiskCheck::Ok => {}
+            DiskCheck::Deleted(paths) => {
+                for path in paths {
+                    tracing::info!("Deleted old incident bundle {} to stay under the disk limit", path.display());
+                }
+            }
+            DiskCheck::Full { used, limit } => {
+                anyhow::bail!("Incident bundles use {} bytes, over the {} byte limit", used, limit);
+            }
+        }
+        Ok(())
     }
 
     pub async fn record_incident(
@@ -50,24 +161,18 @@ impl IncidentManager {
         }
         
         tracing::warn!("Incident recorded: {} - {:?} for {:?}", incident.id, incident.reason, symbol);
+        crate::metrics::record_incident(&reason_name(&incident.reason));
         
         incident
     }
 
-    pub async fn get_last_incident(&self) -> Option<Incident> {
-        self.last_incident.read().await.clone()
-    }
-
     pub async fn export_incident_bundle(
         &self,
         incident: &Incident,
-        config: serde_json::Value,
-        health: serde_json::Value,
-        instrument: Option<&InstrumentInfo>,
-        book_top: Option<serde_json::Value>,
-        frames: &[(DateTime<Utc>, String)],
-        incident_time: DateTime<Utc>,
+        contents: BundleContents,
     ) -> anyhow::Result<PathBuf> {
+        let BundleContents { config, health, instrument, book_top, book_full, book_diff, checksums, frames } = contents;
+        self.check_disk()?;
         let bundle_path = self.incidents_dir.join(format!("{}.zip", incident.id));
         
         let file = std::fs::File::create(&bundle_path)?;
@@ -80,7 +185,7 @@ impl IncidentManager {
             incident: incident.clone(),
             config: config.clone(),
             health: health.clone(),
-            instrument: instrument.map(|i| serde_json::to_value(i).unwrap()),
+            instrument: instrument.as_ref().map(|i| serde_json::to_value(i).unwrap()),
             book_top,
         };
         zip.start_file("metadata.json", options)?;
@@ -95,7 +200,7 @@ impl IncidentManager {
         zip.write_all(serde_json::to_string_pretty(&health)?.as_bytes())?;
 
         // Write instrument.json (if available)
-        if let Some(inst) = instrument {
+        if let Some(inst) = &instrument {
             zip.start_file("instrument.json", options)?;
             zip.write_all(serde_json::to_string_pretty(inst)?.as_bytes())?;
         }
@@ -106,9 +211,27 @@ impl IncidentManager {
             zip.write_all(serde_json::to_string_pretty(bt)?.as_bytes())?;
         }
 
+        // Write book_full.json (if available): every level of the reconstructed book
+        if let Some(book) = &book_full {
+            zip.start_file("book_full.json", options)?;
+            zip.write_all(serde_json::to_string_pretty(book)?.as_bytes())?;
+        }
+
+        // Write book_diff.json (if available): last verified book -> current book
+        if let Some(diff) = &book_diff {
+            zip.start_file("book_diff.json", options)?;
+            zip.write_all(serde_json::to_string_pretty(diff)?.as_bytes())?;
+        }
+
+        // Write checksums.json (if available): the symbol's last integrity proof
+        if let Some(checksums) = &checksums {
+            zip.start_file("checksums.json", options)?;
+            zip.write_all(serde_json::to_string_pretty(checksums)?.as_bytes())?;
+        }
+
         // Write frames.ndjson (t-30s to t+5s around incident)
-        let window_start = incident_time - chrono::Duration::seconds(30);
-        let window_end = incident_time + chrono::Duration::seconds(5);
+        let window_start = incident.timestamp - chrono::Duration::seconds(30);
+        let window_end = incident.timestamp + chrono::Duration::seconds(5);
         let relevant_frames: Vec<_> = frames
             .iter()
             .filter(|(ts, _)| *ts >= window_start && *ts <= window_end)
@@ -121,6 +244,9 @@ impl IncidentManager {
         }
 
         zip.finish()?;
+        if let Ok(written) = std::fs::metadata(&bundle_path) {
+            crate::metrics::update_incident_bundle_bytes(written.len());
+        }
         
         tracing::info!("Incident bundle exported: {:?}", bundle_path);
         Ok(bundle_path)
@@ -129,5 +255,99 @@ impl IncidentManager {
     pub fn incidents_dir(&self) -> &Path {
         &self.incidents_dir
     }
+
+    pub fn disk_guard(&self) -> Option<DiskGuard> {
+        self.disk_guard
+    }
+
+    /// Incidents recorded by this process plus bundles found on startup, newest first
+    pub async fn list_incidents(&self) -> Vec<IncidentSummary> {
+        let mut list: Vec<IncidentSummary> = self.incidents.read().await.iter().map(IncidentSummary::from_incident).collect();
+        for found in self.found_on_startup.iter() {
+            if !list.iter().any(|s| s.id == found.id) {
+                list.push(found.clone());
+            }
+        }
+        for summary in &mut list {
+            summary.zip_size = std::fs::metadata(self.bundle_path_for(&summary.id)).ok().map(|m| m.len());
+        }
+        list.sort_by_key(|s| std::cmp::Reverse(s.created_at));
+        list
+    }
+
+    /// An incident recorded by this process
+    pub async fn incident(&self, id: &str) -> Option<Incident> {
+        self.incidents.read().await.iter().find(|i| i.id == id).cloned()
+    }
+
+    /// The bundle of a listed incident, if one was written
+    pub async fn bundle_path(&self, id: &str) -> Option<PathBuf> {
+        let known = self.incidents.read().await.iter().any(|i| i.id == id)
+            || self.found_on_startup.iter().any(|s| s.id == id);
+        let path = self.bundle_path_for(id);
+        (known && path.is_file()).then_some(path)
+    }
+
+    fn bundle_path_for(&self, id: &str) -> PathBuf {
+        self.incidents_dir.join(format!("{}.zip", id))
+    }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_startup_scan_finds_bundles() {
+        let dir = std::env::temp_dir().join(format!("blackbox_incident_scan_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let manager = IncidentManager::new(dir.clone()).unwrap();
+        let incident = manager.record_incident(IncidentReason::ChecksumMismatch, Some("BTC/USD".to_string()), serde_json::json!({})).await;
+        manager.export_incident_bundle(&incident, BundleContents::default()).await.unwrap();
+        manager.record_incident(IncidentReason::Disconnect, None, serde_json::json!({})).await; // no bundle
+        std::fs::write(dir.join("stray.zip"), b"not a zip").unwrap();
+
+        let listed = manager.list_incidents().await;
+        assert_eq!(listed.len(), 2);
+        assert_eq!(listed[0].reason, "Disconnect");
+        assert_eq!(listed[0].zip_size, None);
+        assert!(manager.bundle_path(&listed[0].id).await.is_none());
+
+        // A restart only knows the bundles on disk
+        let restarted = IncidentManager::new(dir.clone()).unwrap();
+        let listed = restarted.list_incidents().await;
+        assert_eq!(listed.len(), 2);
+        let found = listed.iter().find(|s| s.id == incident.id).unwrap();
+        assert_eq!(found.reason, "ChecksumMismatch");
+        assert_eq!(found.symbol.as_deref(), Some("BTC/USD"));
+        assert_eq!(found.created_at, incident.timestamp);
+        assert!(found.zip_size.unwrap() > 0);
+        let stray = listed.iter().find(|s| s.id == "stray").unwrap();
+        assert_eq!(stray.reason, "unknown");
+        assert!(restarted.bundle_path("../stray").await.is_none());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_incident_metrics() {
+        let dir = std::env::temp_dir().join(format!("blackbox_incident_metrics_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let recorder = metrics_exporter_prometheus::PrometheusBuilder::new().build_recorder();
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        let bundle = ::metrics::with_local_recorder(&recorder, || runtime.block_on(async {
+            let manager = IncidentManager::new(dir.clone()).unwrap();
+            for _ in 0..2 {
+                manager.record_incident(IncidentReason::Disconnect, None, serde_json::json!({})).await;
+            }
+            let incident = manager.record_incident(IncidentReason::ChecksumMismatch, Some("BTC/USD".to_string()), serde_json::json!({})).await;
+            manager.export_incident_bundle(&incident, BundleContents::default()).await.unwrap()
+        }));
+
+        let text = recorder.handle().render();
+        assert!(text.contains("incidents_total{reason=\"Disconnect\"} 2"), "{}", text);
+        assert!(text.contains("incidents_total{reason=\"ChecksumMismatch\"} 1"), "{}", text);
+        let size = std::fs::metadata(bundle).unwrap().len();
+        assert!(text.contains(&format!("incident_bundle_bytes {}", size)), "{}", text);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
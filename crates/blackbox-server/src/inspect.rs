@@ -0,0 +1,53 @@
+//! `blackbox inspect`: what a recording contains, before replaying it
+
+use blackbox_core::inspect::{inspect_recording, RecordingSummary};
+use std::path::Path;
+
+/// Print a summary of a recording (as JSON with `json`)
+pub fn run_inspect(input: &Path, json: bool) -> anyhow::Result<()> {
+    let summary = inspect_recording(input)?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&summary)?);
+    } else {
+        print_summary(input, &summary);
+    }
+    Ok(())
+}
+
+fn print_summary(input: &Path, summary: &RecordingSummary) {
+    let ts_format = "%Y-%m-%dT%H:%M:%S%.6fZ";
+    println!("Recording: {}", input.display());
+    if let Some(meta) = &summary.metadata {
+        println!(
+            "  Header: version {}, symbols {:?}, depth {}, started {}",
+            meta.version,
+            meta.symbols,
+            meta.depth.map(|d| d.to_string()).unwrap_or_else(|| "-".to_string()),
+            meta.started_at.format(ts_format),
+        );
+    }
+    println!("  Frames: {}", summary.total_frames);
+    if let (Some(first), Some(last)) = (summary.first_ts, summary.last_ts) {
+        println!("  First:  {}", first.format(ts_format));
+        println!("  Last:   {}", last.format(ts_format));
+    }
+    println!("  Duration: {:.3}s", summary.duration_ms as f64 / 1000.0);
+    if let Some(gap) = &summary.largest_gap {
+        println!(
+            "  Largest gap: {:.3}s before frame {} ({} -> {})",
+            gap.gap_ms as f64 / 1000.0,
+            gap.frame_index,
+            gap.from.format(ts_format),
+            gap.to.format(ts_format),
+        );
+    }
+    println!("  Instrument snapshot: {}", if summary.instrument_snapshot { "yes" } else { "no" });
+    println!("  Channels:");
+    for (channel, count) in &summary.channels {
+        println!("    {:<12} {}", channel, count);
+    }
+    println!("  Symbols:");
+    for (symbol, counts) in &summary.symbols {
+        println!("    {:<12} {} snapshot(s), {} update(s)", symbol, counts.snapshots, counts.updates);
+    }
+}
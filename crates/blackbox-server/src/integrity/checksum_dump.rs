@@ -0,0 +1,148 @@
+use blackbox_core::checksum::compute_crc32;
+use blackbox_core::orderbook::Orderbook;
+use blackbox_core::precision::format_fixed;
+use blackbox_core::types::InstrumentInfo;
+use chrono::Utc;
+use dashmap::DashMap;
+use std::fmt::Write as _;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+/// Line that precedes the full checksum string in a dump file
+pub const CHECKSUM_STRING_HEADER: &str = "checksum_string:";
+
+/// Writes `checksum_debug_<symbol>_<ts>.txt` files on checksum mismatches,
+/// at most once per `min_interval` per symbol
+pub struct ChecksumDumper {
+    dir: PathBuf,
+    min_interval: Duration,
+    last_dump: DashMap<String, Instant>,
+}
+
+impl ChecksumDumper {
+    pub fn new(dir: PathBuf, min_interval: Duration) -> Self {
+        Self {
+            dir,
+            min_interval,
+            last_dump: DashMap::new(),
+        }
+    }
+
+    /// Dump the full checksum input for `symbol`, unless one was written recently.
+    /// Returns the file path when a dump was written.
+    pub fn dump(
+        &self,
+        symbol: &str,
+        book: &mut Orderbook,
+        instrument: &InstrumentInfo,
+        expected: u32,
+    ) -> Option<PathBuf> {
+        let now = Instant::now();
+        if let Some(last) = self.last_dump.get(symbol) {
+            if now.duration_since(*last) < self.min_interval {
+                return None;
+            }
+        }
+        self.last_dump.insert(symbol.to_string(), now);
+
+        let contents = render_dump(symbol, book, instrument, expected);
+        let filename = format!(
+            "checksum_debug_{}_{}.txt",
+            symbol.replace(['/', ':'], "_"),
+            Utc::now().format("%Y%m%dT%H%M%S%.3fZ"),
+        );
+        let path = self.dir.join(filename);
+        let written = std::fs::create_dir_all(&self.dir).and_then(|_| std::fs::write(&path, contents));
+        match written {
+            Ok(()) => Some(path),
+            Err(e) => {
+                warn!("Failed to write checksum dump {}: {}", path.display(), e);
+                None
+            }
+        }
+    }
+}
+
+/// Human-readable checksum input: precisions, both CRCs, every formatted level
+/// and the full concatenated string on the line after `CHECKSUM_STRING_HEADER`
+fn render_dump(symbol: &str, book: &mut Orderbook, instrument: &InstrumentInfo, expected: u32) -> String {
+    let (pp, qp) = (instrument.price_precision, instrument.qty_precision);
+    let levels = book.checksum_levels();
+    let checksum_str = book.checksum_string(pp, qp).to_string();
+    let computed = compute_crc32(&checksum_str);
+
+    let mut out = String::new();
+    let _ = writeln!(out, "symbol: {}", symbol);
+    let _ = writeln!(out, "timestamp: {}", Utc::now().to_rfc3339());
+    let _ = writeln!(out, "price_precision: {}", pp);
+    let _ = writeln!(out, "qty_precision: {}", qp);
+    let _ = writeln!(out, "checksum_levels: {}", levels);
+    let _ = writeln!(out, "expected_crc32: {} (0x{:08X})", expected, expected);
+    let _ = writeln!(out, "computed_crc32: {} (0x{:08X})", computed, computed);
+
+    let sides = [("asks (low -> high)", book.asks_vec(Some(levels))), ("bids (high -> low)", book.bids_vec(Some(levels)))];
+    for (title, side) in sides {
+        let _ = writeln!(out, "\n{}:", title);
+        for (i, (price, qty)) in side.iter().enumerate() {
+            let _ = writeln!(
+                out,
+                "  {:>3}  {} @ {}  ->  {} {}",
+                i + 1,
+                price,
+                qty,
+                format_fixed(price, pp),
+                format_fixed(qty, qp),
+            );
+        }
+    }
+
+    let _ = writeln!(out, "\n{}\n{}", CHECKSUM_STRING_HEADER, checksum_str);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    fn instrument() -> InstrumentInfo {
+        InstrumentInfo {
+            symbol: "BTC/USD".to_string(),
+            price_precision: 1,
+            qty_precision: 8,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_dump_round_trips_crc() {
+        let dir = std::env::temp_dir().join(format!("blackbox_checksum_dump_{}", std::process::id()));
+        let dumper = ChecksumDumper::new(dir.clone(), Duration::from_secs(60));
+        let mut book = Orderbook::new();
+        book.apply_snapshot(
+            vec![(dec!(100.5), dec!(1.25)), (dec!(99.0), dec!(0.001))],
+            vec![(dec!(101.0), dec!(2)), (dec!(101.5), dec!(0.5))],
+        );
+        let computed = book.checksum(1, 8);
+
+        let path = dumper.dump("BTC/USD", &mut book, &instrument(), 42).unwrap();
+        assert!(path.file_name().unwrap().to_string_lossy().starts_with("checksum_debug_BTC_USD_"));
+        let contents = std::fs::read_to_string(&path).unwrap();
+
+        // The string after the header hashes back to the recorded CRC
+        let mut lines = contents.lines().skip_while(|l| *l != CHECKSUM_STRING_HEADER);
+        lines.next();
+        let checksum_str = lines.next().unwrap();
+        assert_eq!(compute_crc32(checksum_str), computed);
+        assert!(contents.contains(&format!("computed_crc32: {} ", computed)));
+        assert!(contents.contains("expected_crc32: 42 "));
+        assert!(contents.contains("1010 200000000"));
+
+        // Rate limited per symbol
+        assert!(dumper.dump("BTC/USD", &mut book, &instrument(), 42).is_none());
+        assert!(dumper.dump("ETH/USD", &mut book, &instrument(), 42).is_some());
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
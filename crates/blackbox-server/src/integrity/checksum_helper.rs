@@ -1,60 +1,68 @@
+use crate::integrity::checksum_dump::ChecksumDumper;
 use crate::integrity::proof::IntegrityProof;
 use crate::metrics;
-use blackbox_core::checksum::{build_checksum_string, compute_crc32};
-use blackbox_core::orderbook::Orderbook;
+use blackbox_core::checksum::{verify_checksum_formatted, ChecksumVerification};
+use blackbox_core::orderbook::{Orderbook, Side};
+use blackbox_core::precision::PrecisionFormatter;
+use blackbox_core::types::InstrumentInfo;
 use chrono::Utc;
-use rust_decimal::Decimal;
-use std::time::Instant;
 
+/// Removals per side copied into the proof on mismatch
+const RECENT_REMOVALS_SHOWN: usize = 5;
+
+/// Verify `book` and record the result in `proof`; the verification is
+/// returned for the caller's own logging and incident
 pub fn update_integrity_proof(
     proof: &mut IntegrityProof,
-    book: &Orderbook,
+    book: &mut Orderbook,
     expected_checksum: u32,
-    price_precision: u32,
-    qty_precision: u32,
+    instrument: &InstrumentInfo,
+    formatter: &PrecisionFormatter,
     symbol: &str,
-) -> bool {
-    let start = Instant::now();
-    
-    // Build checksum string
-    let checksum_string = build_checksum_string(book, price_precision, qty_precision);
-    let computed = compute_crc32(&checksum_string);
-    
-    let latency_ms = start.elapsed().as_millis() as u64;
-    
-    // Record latency metric
-    metrics::record_latency(symbol, latency_ms as f64);
-    
-    // Get top 10 bids and asks
-    let top_asks: Vec<(Decimal, Decimal)> = book
-        .asks_vec(Some(10))
-        .into_iter()
-        .map(|(p, q)| (p, q))
-        .collect();
-    
-    let top_bids: Vec<(Decimal, Decimal)> = book
-        .bids_vec(Some(10))
-        .into_iter()
-        .map(|(p, q)| (p, q))
-        .collect();
+    dumper: Option<&ChecksumDumper>,
+) -> ChecksumVerification {
+    // Checksum of the book as last verified, if it was
+    let last_match = proof.is_match().then_some(proof.computed_checksum);
+    let checksum_levels = instrument.checksum_levels;
+    // Checksum string is cached until a checksummed level changes
+    book.set_checksum_levels(checksum_levels);
+    let verification = verify_checksum_formatted(book, expected_checksum, formatter);
+    let latency_ms = verification.elapsed.as_millis() as u64;
+    metrics::record_verify_latency(symbol, verification.elapsed);
     
     // Update proof
     proof.expected_checksum = expected_checksum;
-    proof.computed_checksum = computed;
-    proof.checksum_preview = checksum_string.chars().take(64).collect();
-    proof.checksum_len = checksum_string.len();
-    proof.top_asks = top_asks;
-    proof.top_bids = top_bids;
+    proof.computed_checksum = verification.computed;
+    proof.checksum_preview = verification.preview.clone();
+    proof.checksum_len = verification.checksum_len;
+    proof.checksum_levels = checksum_levels;
+    // Refill the checksummed levels in place, reusing the proof's buffers
+    proof.top_asks.clear();
+    proof.top_asks.extend(book.top_asks(checksum_levels).map(|(p, q)| (*p, *q)));
+    proof.top_bids.clear();
+    proof.top_bids.extend(book.top_bids(checksum_levels).map(|(p, q)| (*p, *q)));
     proof.record_latency(latency_ms);
     proof.last_verify_ts = Utc::now();
     
-    let is_match = expected_checksum == computed;
-    
-    if !is_match {
+    if !verification.matched {
         proof.last_mismatch_ts = Some(Utc::now());
-        proof.diagnosis = Some(format!("Expected 0x{:08X} but computed 0x{:08X}", expected_checksum, computed));
+        let mut diagnosis = format!("Expected 0x{:08X} but computed 0x{:08X}", expected_checksum, verification.computed);
+        if let Some(negative) = verification.negative_level {
+            // Explains the mismatch better than the CRCs do
+            diagnosis = format!("{} ({})", negative, diagnosis);
+        } else if last_match == Some(verification.computed) {
+            // The checksummed levels are still the ones Kraken last vouched for
+            diagnosis = format!("book unchanged since its last match, advertised checksum differs ({})", diagnosis);
+        }
+        if let Some(path) = dumper.and_then(|d| d.dump(symbol, book, instrument, expected_checksum)) {
+            diagnosis.push_str(&format!("; full input in {}", path.display()));
+        }
+        proof.diagnosis = Some(diagnosis);
+        // A level that should still be there often shows up among the recent removals
+        proof.recent_ask_removals = book.recent_removals(Side::Ask, RECENT_REMOVALS_SHOWN);
+        proof.recent_bid_removals = book.recent_removals(Side::Bid, RECENT_REMOVALS_SHOWN);
     }
     
-    is_match
+    verification
 }
 
@@ -1,3 +1,4 @@
+use serde::{Deserialize, Serialize};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
@@ -8,10 +9,56 @@ pub struct FaultInjector {
     pub fault_type: Arc<std::sync::RwLock<FaultType>>,
 }
 
-#[derive(Debug, Clone, Copy)]
+/// A fault the live pipeline applies to one book update
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", content = "params", rename_all = "snake_case")]
 pub enum FaultType {
-    MutateQty,
-    DropUpdate,
+    MutateQty { delta_ticks: i32 }, // first ask's qty, in qty increments
+    MutatePrice { delta_ticks: i32 }, // first ask (else bid) moved out, in price increments
+    Duplicate, // apply the update twice
+    CorruptChecksum { xor: u32 }, // flip bits of the advertised checksum
+    Drop, // skip the update, as if it never arrived
+}
+
+/// Bits the TUI's `CorruptChecksum` flips
+pub const CORRUPT_CHECKSUM_XOR: u32 = 1;
+
+/// Faults `blackbox replay --fault-*` can apply but the live pipeline can't:
+/// they hold frames back, which only the replayer does
+pub const REPLAY_ONLY_FAULTS: &[&str] = &["reorder", "delay"];
+
+/// Optional parameters of `POST /fault`
+#[derive(Debug, Default, Deserialize)]
+pub struct FaultParams {
+    pub delta_ticks: Option<i32>, // default 1
+    pub xor: Option<u32>, // default CORRUPT_CHECKSUM_XOR
+}
+
+impl FaultType {
+    /// The fault after this one, in the order the TUI cycles through them
+    pub fn next(self) -> Self {
+        match self {
+            FaultType::MutateQty { .. } => FaultType::MutatePrice { delta_ticks: 1 },
+            FaultType::MutatePrice { .. } => FaultType::Duplicate,
+            FaultType::Duplicate => FaultType::CorruptChecksum { xor: CORRUPT_CHECKSUM_XOR },
+            FaultType::CorruptChecksum { .. } => FaultType::Drop,
+            FaultType::Drop => FaultType::MutateQty { delta_ticks: 1 },
+        }
+    }
+
+    /// Build a fault from its snake_case name; None if the live pipeline
+    /// has no such fault
+    pub fn from_name(name: &str, params: &FaultParams) -> Option<Self> {
+        let delta_ticks = params.delta_ticks.unwrap_or(1);
+        Some(match name {
+            "mutate_qty" => FaultType::MutateQty { delta_ticks },
+            "mutate_price" => FaultType::MutatePrice { delta_ticks },
+            "duplicate" => FaultType::Duplicate,
+            "corrupt_checksum" => FaultType::CorruptChecksum { xor: params.xor.unwrap_or(CORRUPT_CHECKSUM_XOR) },
+            "drop" => FaultType::Drop,
+            _ => return None,
+        })
+    }
 }
 
 impl FaultInjector {
@@ -19,31 +66,56 @@ impl FaultInjector {
         Self {
             enabled: Arc::new(AtomicBool::new(false)),
             symbol: Arc::new(std::sync::RwLock::new(None)),
-            fault_type: Arc::new(std::sync::RwLock::new(FaultType::MutateQty)),
+            fault_type: Arc::new(std::sync::RwLock::new(FaultType::MutateQty { delta_ticks: 1 })),
         }
     }
 
+    /// Arm the selected fault for `symbol`'s next book update
     pub fn trigger(&self, symbol: String) {
-        self.enabled.store(true, Ordering::SeqCst);
         *self.symbol.write().unwrap() = Some(symbol);
+        self.enabled.store(true, Ordering::SeqCst);
+    }
+
+    /// Select `fault_type` and arm it for `symbol`
+    pub fn arm(&self, symbol: String, fault_type: FaultType) {
+        *self.fault_type.write().unwrap() = fault_type;
+        self.trigger(symbol);
+    }
+
+    pub fn disarm(&self) {
+        self.enabled.store(false, Ordering::SeqCst);
     }
 
-    pub fn should_inject(&self, symbol: &str) -> bool {
+    /// The symbol the fault is armed for, if it hasn't fired yet
+    pub fn armed_symbol(&self) -> Option<String> {
         if !self.enabled.load(Ordering::SeqCst) {
-            return false;
+            return None;
         }
-        let target = self.symbol.read().unwrap();
-        target.as_ref().map(|s| s == symbol).unwrap_or(false)
+        self.symbol.read().unwrap().clone()
     }
 
-    pub fn consume(&self) -> Option<(String, FaultType)> {
-        if !self.enabled.load(Ordering::SeqCst) {
+    pub fn fault_type(&self) -> FaultType {
+        *self.fault_type.read().unwrap()
+    }
+
+    /// Select the next fault type for `trigger`, returning it
+    pub fn cycle_fault_type(&self) -> FaultType {
+        let mut fault_type = self.fault_type.write().unwrap();
+        *fault_type = fault_type.next();
+        *fault_type
+    }
+
+    /// The armed fault if it's for `symbol`, disarming it: each arming fires
+    /// once. Updates for other symbols leave it armed.
+    pub fn consume(&self, symbol: &str) -> Option<FaultType> {
+        if self.armed_symbol().as_deref() != Some(symbol) {
             return None;
         }
-        self.enabled.store(false, Ordering::SeqCst);
-        let symbol = self.symbol.read().unwrap().clone()?;
-        let fault_type = *self.fault_type.read().unwrap();
-        Some((symbol, fault_type))
+        // Another update may have taken it in between
+        if !self.enabled.swap(false, Ordering::SeqCst) {
+            return None;
+        }
+        Some(self.fault_type())
     }
 }
 
@@ -53,3 +125,42 @@ impl Default for FaultInjector {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fault_fires_once_for_its_symbol() {
+        let injector = FaultInjector::new();
+        assert!(injector.consume("BTC/USD").is_none());
+
+        injector.arm("BTC/USD".to_string(), FaultType::Drop);
+        assert!(injector.consume("ETH/USD").is_none());
+        assert_eq!(injector.armed_symbol().as_deref(), Some("BTC/USD"));
+        assert_eq!(injector.consume("BTC/USD"), Some(FaultType::Drop));
+        assert!(injector.consume("BTC/USD").is_none());
+        assert!(injector.armed_symbol().is_none());
+
+        injector.trigger("BTC/USD".to_string());
+        injector.disarm();
+        assert!(injector.consume("BTC/USD").is_none());
+    }
+
+    #[test]
+    fn test_fault_from_name() {
+        let defaults = FaultParams::default();
+        assert_eq!(FaultType::from_name("mutate_qty", &defaults), Some(FaultType::MutateQty { delta_ticks: 1 }));
+        assert_eq!(
+            FaultType::from_name("corrupt_checksum", &FaultParams { xor: Some(0xff), ..Default::default() }),
+            Some(FaultType::CorruptChecksum { xor: 0xff })
+        );
+        assert_eq!(FaultType::from_name("reorder", &defaults), None);
+        // Every fault the TUI cycles through can be armed by name
+        let mut fault = FaultType::Drop;
+        for _ in 0..5 {
+            let name = serde_json::to_value(fault).unwrap()["type"].as_str().unwrap().to_string();
+            assert!(FaultType::from_name(&name, &defaults).is_some(), "{}", name);
+            fault = fault.next();
+        }
+    }
+}
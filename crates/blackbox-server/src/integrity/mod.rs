@@ -2,8 +2,10 @@ pub mod proof;
 pub mod incident;
 pub mod fault;
 pub mod checksum_helper;
+pub mod checksum_dump;
 
 pub use proof::IntegrityProof;
 pub use incident::IncidentMeta;
 pub use checksum_helper::update_integrity_proof;
+pub use checksum_dump::ChecksumDumper;
 
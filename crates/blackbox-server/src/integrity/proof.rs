@@ -1,3 +1,4 @@
+use blackbox_core::orderbook::Removal;
 use chrono::{DateTime, Utc};
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
@@ -9,12 +10,15 @@ pub struct IntegrityProof {
     pub computed_checksum: u32,
     pub checksum_preview: String, // First 64 chars of checksum string
     pub checksum_len: usize,
+    pub checksum_levels: usize, // Levels per side the checksum covers
     pub top_asks: Vec<(Decimal, Decimal)>, // (price, qty)
     pub top_bids: Vec<(Decimal, Decimal)>, // (price, qty)
     pub verify_latency_ms: u64, // Last latency
     pub last_verify_ts: DateTime<Utc>,
     pub last_mismatch_ts: Option<DateTime<Utc>>,
     pub diagnosis: Option<String>, // Reason for mismatch
+    pub recent_ask_removals: Vec<Removal>, // Newest first, captured on mismatch (needs --tombstones)
+    pub recent_bid_removals: Vec<Removal>,
     #[serde(skip)]
     latency_history: VecDeque<u64>, // Rolling window for statistics
 }
@@ -70,12 +74,15 @@ impl IntegrityProof {
             computed_checksum: 0,
             checksum_preview: String::new(),
             checksum_len: 0,
+            checksum_levels: blackbox_core::checksum::CHECKSUM_DEPTH,
             top_asks: Vec::new(),
             top_bids: Vec::new(),
             verify_latency_ms: 0,
             last_verify_ts: Utc::now(),
             last_mismatch_ts: None,
             diagnosis: None,
+            recent_ask_removals: Vec::new(),
+            recent_bid_removals: Vec::new(),
             latency_history: VecDeque::with_capacity(1000),
         }
     }
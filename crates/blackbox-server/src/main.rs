@@ -1,24 +1,45 @@
+mod api_error;
+mod bench;
+mod compare;
+mod config;
+mod disk;
+mod event_store;
+mod events;
+mod feed;
 mod http;
 mod incident;
+mod inspect;
 mod integrity;
 mod metrics;
+mod msg_rate;
+mod recording;
 mod state;
 mod static_ui;
 mod tui;
+mod verify;
 
 use anyhow::Context;
-use blackbox_core::checksum::verify_checksum;
+use blackbox_core::checksum::ChecksumVerification;
 use blackbox_core::orderbook::Orderbook;
-use blackbox_core::recorder::Recorder;
-use blackbox_core::replayer::Replayer;
-use blackbox_core::incident::IncidentReason;
-use blackbox_core::types::{FaultRule, FaultType, ReplayConfig, ReplayMode};
-use blackbox_ws::client::{WsClient, WsEvent};
+use blackbox_core::recorder::{FlushPolicy, RecorderOptions, RotationPolicy};
+use crate::config::{RunMode, RuntimeConfig};
+use crate::disk::{DiskGuard, DiskPolicy};
+use crate::recording::RecordSink;
+use blackbox_core::replayer::{frame_stream, ReplayEvent, Replayer, LOOP_MARKER};
+use blackbox_core::incident::{Incident, IncidentReason};
+use blackbox_core::types::{
+    FaultRule, FaultType, RecordingMetadata, ReplayConfig, ReplayMode, DEFAULT_PRICE_INCREMENT, RECORDING_SCHEMA_VERSION,
+};
+use blackbox_ws::client::{validate_ws_url, ClientCommand, WsClient, WsEvent, WS_URL};
 use clap::{Parser, Subcommand};
-use http::router;
-use incident::IncidentManager;
+use http::{router, HttpOptions, RateLimit};
+use incident::{BundleContents, IncidentManager};
+use integrity::{update_integrity_proof, IncidentMeta};
 use metrics::init_metrics;
-use state::AppState;
+use integrity::fault::FaultType as InjectedFault;
+use rust_decimal::Decimal;
+use state::{AppState, StoredBook};
+use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::Duration;
@@ -32,6 +53,40 @@ use axum::routing::get;
 #[command(name = "blackbox")]
 #[command(about = "Kraken WebSocket v2 market data client with orderbook engine and checksum verification")]
 struct Cli {
+    /// Write logs to this file instead of stdout, rotated daily (TUI sessions
+    /// default to logs/blackbox.log so nothing is logged to the screen)
+    #[arg(long, global = true)]
+    log_file: Option<PathBuf>,
+    /// Log line format: plain or json
+    #[arg(long, global = true, default_value = "plain")]
+    log_format: LogFormat,
+    /// Require `Authorization: Bearer <token>` on every HTTP route but /, /health, /live and /ready
+    #[arg(long, global = true)]
+    http_token: Option<String>,
+    /// Origin allowed to call the HTTP API from a browser, e.g.
+    /// http://grafana.local:3000; repeatable, `*` allows any
+    #[arg(long, global = true, value_parser = parse_cors_origin)]
+    cors_allow_origin: Vec<String>,
+    /// Limit a mutating route and everything under it, e.g. /export-bug=1/min
+    /// or /record=off; repeatable. Defaults: /export-bug 5/min, /record 10/min,
+    /// /fault and /symbols 30/min
+    #[arg(long, global = true, value_name = "ROUTE=N/PERIOD", value_parser = parse_rate_limit_arg)]
+    rate_limit: Vec<(String, Option<RateLimit>)>,
+    /// Also serve Prometheus metrics on their own listener at this address;
+    /// they are always on the HTTP server's /metrics
+    #[arg(long, global = true)]
+    metrics_addr: Option<std::net::SocketAddr>,
+    /// Keep the event log in this file, reloading its last 500 entries on
+    /// startup (run and tui; not when replaying)
+    #[arg(long, global = true, default_value = event_store::DEFAULT_EVENTS_FILE)]
+    events_file: PathBuf,
+    /// Keep the event log in memory only
+    #[arg(long, global = true)]
+    no_persist_events: bool,
+    /// Carry health counters and incidents over from the last run's
+    /// state_snapshot.json, written on every graceful shutdown (run and tui)
+    #[arg(long, global = true)]
+    resume_state: bool,
     #[command(subcommand)]
     command: Commands,
 }
@@ -40,21 +95,92 @@ struct Cli {
 enum Commands {
     /// Run the blackbox client
     Run {
-        /// Symbols to subscribe to (comma-separated)
-        #[arg(long, value_delimiter = ',')]
-        symbols: Vec<String>,
+        /// Symbols to subscribe to (comma-separated), each optionally at its own
+        /// depth: BTC/USD:1000,ETH/USD
+        #[arg(long, value_delimiter = ',', value_parser = parse_symbol_arg)]
+        symbols: Vec<SymbolArg>,
+        /// More symbols, one per line (`#` comments, optional `:depth`), merged with --symbols
+        #[arg(long)]
+        symbols_file: Option<PathBuf>,
         /// Orderbook depth
         #[arg(long, default_value = "100")]
         depth: u32,
         /// HTTP server address
         #[arg(long, default_value = "127.0.0.1:8080")]
         http: String,
-        /// Ping interval (e.g., "30s")
-        #[arg(long, default_value = "30s")]
-        ping_interval: String,
+        /// Ping interval (e.g. "30s", "500ms", "1m30s")
+        #[arg(long, value_parser = parse_duration, default_value = "30s")]
+        ping_interval: Duration,
+        /// WebSocket endpoint (ws:// or wss://), e.g. a beta endpoint or a local mock
+        #[arg(long, value_parser = parse_ws_url, default_value = WS_URL)]
+        ws_url: String,
         /// Recording file path (optional)
         #[arg(long)]
         record: Option<PathBuf>,
+        /// Record into this directory instead, one file per symbol plus _control.ndjson for everything else
+        #[arg(long, value_name = "DIR", conflicts_with = "record")]
+        record_per_symbol: Option<PathBuf>,
+        /// Continue an existing recording instead of overwriting it
+        #[arg(long)]
+        record_append: bool,
+        /// Only record these channels (comma-separated, e.g. "book,instrument"); default all
+        #[arg(long, value_delimiter = ',', value_parser = RECORD_CHANNELS)]
+        record_channels: Option<Vec<String>>,
+        /// Disk space the recording may use (e.g. "20G"); see --disk-policy
+        #[arg(long, value_parser = parse_byte_size)]
+        max_record_bytes: Option<u64>,
+        /// Disk space incident bundles in ./incidents may use (e.g. "1G"); see --disk-policy
+        #[arg(long, value_parser = parse_byte_size)]
+        max_incidents_bytes: Option<u64>,
+        /// Over a disk limit: "stop" recording / writing bundles, or "delete-oldest" segments and bundles
+        #[arg(long, default_value = "stop")]
+        disk_policy: DiskPolicy,
+        /// Start a new recording segment once the current one reaches this size (e.g. "512M", "2G")
+        #[arg(long, value_parser = parse_byte_size)]
+        record_rotate_size: Option<u64>,
+        /// Start a new recording segment after this long (e.g. "30m", "1h")
+        #[arg(long, value_parser = parse_duration)]
+        record_rotate_every: Option<Duration>,
+        /// Write buffered recording frames to disk at least this often (e.g. "500ms", "2s")
+        #[arg(long, value_parser = parse_duration, default_value = "500ms")]
+        record_flush_interval: Duration,
+        /// Also write buffered recording frames to disk after this many frames
+        #[arg(long, default_value = "1000")]
+        record_flush_frames: u64,
+        /// Store a short tag (e.g. "book.update:BTC/USD") with each recorded frame; false for the smallest files
+        #[arg(long, default_value_t = true, action = clap::ArgAction::Set)]
+        record_decoded: bool,
+        /// Track per-level update counts and times (shown on /book/:symbol/top)
+        #[arg(long)]
+        level_meta: bool,
+        /// Validate orderbook invariants after every change and record an incident on failure
+        #[arg(long)]
+        strict_book: bool,
+        /// Keep the last N removed levels per side and show them in the inspector on mismatch (0 = off)
+        #[arg(long, default_value = "0")]
+        tombstones: usize,
+        /// Levels per side to include in the book checksum (default: the venue's, 10 for Kraken)
+        #[arg(long)]
+        checksum_levels: Option<usize>,
+        /// Frames of every channel to keep for incident bundles without a symbol (0 = off)
+        #[arg(long, default_value_t = state::LAST_FRAMES_CAPACITY)]
+        frame_buffer: usize,
+        /// Book frames kept per symbol for incident bundles (0 = off)
+        #[arg(long, default_value_t = state::FRAME_BUFFER_CAPACITY)]
+        symbol_frame_buffer: usize,
+        /// Re-subscribe a symbol for a fresh snapshot after this many checksum
+        /// failures in a row, backing off 3s, 10s, then 30s between tries (0 = never)
+        #[arg(long, default_value_t = state::RESYNC_AFTER_FAILS)]
+        resync_after: u64,
+        /// On checksum mismatch, write the full checksum input to a file in this directory
+        #[arg(long)]
+        checksum_dump_dir: Option<PathBuf>,
+        /// Minimum seconds between checksum dumps per symbol
+        #[arg(long, default_value = "30")]
+        checksum_dump_interval: u64,
+        /// Show the Integrity Console instead of logging; q quits and shuts down
+        #[arg(long)]
+        tui: bool,
     },
     /// Replay a recording
     Replay {
@@ -67,54 +193,173 @@ enum Commands {
         /// HTTP server address
         #[arg(long, default_value = "127.0.0.1:8080")]
         http: String,
-        /// Fault injection: drop every N updates
+        /// Fault injection: drop every N updates. Every --fault-* flag can be repeated and combined
         #[arg(long)]
-        fault_drop_every: Option<usize>,
+        fault_drop_every: Vec<usize>,
         /// Fault injection: drop once at frame index
         #[arg(long)]
-        fault_drop_once: Option<usize>,
+        fault_drop_once: Vec<usize>,
         /// Fault injection: reorder once at frame index
         #[arg(long)]
-        fault_reorder_once: Option<usize>,
+        fault_reorder_once: Vec<usize>,
         /// Fault injection: mutate qty once at frame index
         #[arg(long)]
-        fault_mutate_once: Option<usize>,
-        /// Delta ticks for qty mutation
+        fault_mutate_once: Vec<usize>,
+        /// Fault injection: shift a price by the delta ticks once at frame index
+        #[arg(long)]
+        fault_mutate_price_once: Vec<usize>,
+        /// Fault injection: play a frame twice once at frame index
+        #[arg(long)]
+        fault_duplicate_once: Vec<usize>,
+        /// Fault injection: XOR the advertised checksum once at frame index
+        #[arg(long)]
+        fault_corrupt_checksum_once: Vec<usize>,
+        /// Bits to flip for checksum corruption
+        #[arg(long, default_value = "1")]
+        fault_checksum_xor: u32,
+        /// Fault injection: hold a frame back while later ones play, once at frame index
+        #[arg(long)]
+        fault_delay_once: Vec<usize>,
+        /// Delay in recorded milliseconds (divided by --speed on the wall clock)
+        #[arg(long, default_value = "250")]
+        fault_delay_ms: u64,
+        /// Delta ticks for qty and price mutation
         #[arg(long, default_value = "1")]
         fault_mutate_delta: i32,
+        /// Price tick for symbols the recording has no instrument for (default 0.1)
+        #[arg(long)]
+        fault_price_increment: Option<rust_decimal::Decimal>,
+        /// Replay even if the recording's schema version is newer than this build understands
+        #[arg(long)]
+        force: bool,
+        /// Start at the first frame at or after this time (RFC 3339)
+        #[arg(long)]
+        from: Option<chrono::DateTime<chrono::Utc>>,
+        /// Stop after the last frame at or before this time (RFC 3339)
+        #[arg(long)]
+        to: Option<chrono::DateTime<chrono::Utc>>,
+        /// Start over from the recording's first frame after its last
+        #[arg(long = "loop")]
+        loop_playback: bool,
+        /// Exit once the replay ends, printing the final /health
+        #[arg(long, default_value_t = true, action = clap::ArgAction::Set)]
+        exit_on_complete: bool,
+        /// Keep serving the final state after the replay ends (overrides --exit-on-complete)
+        #[arg(long)]
+        hold: bool,
     },
     /// Run with TUI (Integrity Console)
     Tui {
-        /// Symbols to subscribe to (comma-separated)
-        #[arg(long, value_delimiter = ',')]
-        symbols: Vec<String>,
+        /// Symbols to subscribe to (comma-separated), each optionally at its own
+        /// depth: BTC/USD:1000,ETH/USD
+        #[arg(long, value_delimiter = ',', value_parser = parse_symbol_arg)]
+        symbols: Vec<SymbolArg>,
+        /// More symbols, one per line (`#` comments, optional `:depth`), merged with --symbols
+        #[arg(long)]
+        symbols_file: Option<PathBuf>,
         /// Orderbook depth
         #[arg(long, default_value = "25")]
         depth: u32,
         /// HTTP server address
         #[arg(long, default_value = "127.0.0.1:8080")]
         http: String,
-        /// Ping interval (e.g., "30s")
-        #[arg(long, default_value = "30s")]
-        ping_interval: String,
+        /// Ping interval (e.g. "30s", "500ms", "1m30s")
+        #[arg(long, value_parser = parse_duration, default_value = "30s")]
+        ping_interval: Duration,
+        /// WebSocket endpoint (ws:// or wss://), e.g. a beta endpoint or a local mock
+        #[arg(long, value_parser = parse_ws_url, default_value = WS_URL)]
+        ws_url: String,
         /// Recording file path (optional)
         #[arg(long)]
         record: Option<PathBuf>,
+        /// Record into this directory instead, one file per symbol plus _control.ndjson for everything else
+        #[arg(long, value_name = "DIR", conflicts_with = "record")]
+        record_per_symbol: Option<PathBuf>,
+        /// Continue an existing recording instead of overwriting it
+        #[arg(long)]
+        record_append: bool,
+        /// Only record these channels (comma-separated, e.g. "book,instrument"); default all
+        #[arg(long, value_delimiter = ',', value_parser = RECORD_CHANNELS)]
+        record_channels: Option<Vec<String>>,
+        /// Disk space the recording may use (e.g. "20G"); see --disk-policy
+        #[arg(long, value_parser = parse_byte_size)]
+        max_record_bytes: Option<u64>,
+        /// Disk space incident bundles in ./incidents may use (e.g. "1G"); see --disk-policy
+        #[arg(long, value_parser = parse_byte_size)]
+        max_incidents_bytes: Option<u64>,
+        /// Over a disk limit: "stop" recording / writing bundles, or "delete-oldest" segments and bundles
+        #[arg(long, default_value = "stop")]
+        disk_policy: DiskPolicy,
+        /// Start a new recording segment once the current one reaches this size (e.g. "512M", "2G")
+        #[arg(long, value_parser = parse_byte_size)]
+        record_rotate_size: Option<u64>,
+        /// Start a new recording segment after this long (e.g. "30m", "1h")
+        #[arg(long, value_parser = parse_duration)]
+        record_rotate_every: Option<Duration>,
+        /// Write buffered recording frames to disk at least this often (e.g. "500ms", "2s")
+        #[arg(long, value_parser = parse_duration, default_value = "500ms")]
+        record_flush_interval: Duration,
+        /// Also write buffered recording frames to disk after this many frames
+        #[arg(long, default_value = "1000")]
+        record_flush_frames: u64,
+        /// Store a short tag (e.g. "book.update:BTC/USD") with each recorded frame; false for the smallest files
+        #[arg(long, default_value_t = true, action = clap::ArgAction::Set)]
+        record_decoded: bool,
         /// Replay recording file
         #[arg(long)]
         replay: Option<PathBuf>,
         /// Replay speed multiplier
         #[arg(long, default_value = "1.0")]
         speed: f64,
-        /// Fault injection: none, drop, reorder, mutate_qty
+        /// Fault injection: none, drop, reorder, mutate_qty, mutate_price, duplicate, corrupt_checksum, delay
         #[arg(long, default_value = "none")]
         fault: String,
         /// Fault injection: once at frame index
         #[arg(long)]
         once_at: Option<usize>,
+        /// Bits to flip for --fault corrupt_checksum
+        #[arg(long, default_value_t = integrity::fault::CORRUPT_CHECKSUM_XOR)]
+        fault_checksum_xor: u32,
+        /// Delay in recorded milliseconds for --fault delay (divided by --speed on the wall clock)
+        #[arg(long, default_value = "250")]
+        fault_delay_ms: u64,
+        /// Delta ticks for --fault mutate_qty and mutate_price
+        #[arg(long, default_value = "1")]
+        fault_mutate_delta: i32,
+        /// Start the --replay recording over after its last frame
+        #[arg(long = "loop")]
+        loop_playback: bool,
         /// Mock mode (no real connection)
         #[arg(long)]
         mock: bool,
+        /// Track per-level update counts and times (shown on /book/:symbol/top)
+        #[arg(long)]
+        level_meta: bool,
+        /// Validate orderbook invariants after every change and record an incident on failure
+        #[arg(long)]
+        strict_book: bool,
+        /// Keep the last N removed levels per side and show them in the inspector on mismatch (0 = off)
+        #[arg(long, default_value = "0")]
+        tombstones: usize,
+        /// Levels per side to include in the book checksum (default: the venue's, 10 for Kraken)
+        #[arg(long)]
+        checksum_levels: Option<usize>,
+        /// Frames of every channel to keep for incident bundles without a symbol (0 = off)
+        #[arg(long, default_value_t = state::LAST_FRAMES_CAPACITY)]
+        frame_buffer: usize,
+        /// Book frames kept per symbol for incident bundles (0 = off)
+        #[arg(long, default_value_t = state::FRAME_BUFFER_CAPACITY)]
+        symbol_frame_buffer: usize,
+        /// Re-subscribe a symbol for a fresh snapshot after this many checksum
+        /// failures in a row, backing off 3s, 10s, then 30s between tries (0 = never)
+        #[arg(long, default_value_t = state::RESYNC_AFTER_FAILS)]
+        resync_after: u64,
+        /// On checksum mismatch, write the full checksum input to a file in this directory
+        #[arg(long)]
+        checksum_dump_dir: Option<PathBuf>,
+        /// Minimum seconds between checksum dumps per symbol
+        #[arg(long, default_value = "30")]
+        checksum_dump_interval: u64,
     },
     /// Replay an incident bundle
     ReplayIncident {
@@ -128,25 +373,216 @@ enum Commands {
         #[arg(long, default_value = "127.0.0.1:8080")]
         http: String,
     },
+    /// Compare the final books of two recordings and report divergence per symbol
+    Compare {
+        /// First recording file
+        #[arg(long)]
+        a: PathBuf,
+        /// Second recording file
+        #[arg(long)]
+        b: PathBuf,
+        /// Levels per side to compare
+        #[arg(long, default_value = "25")]
+        depth: usize,
+    },
+    /// Rebuild the books of a recording and verify every checksum offline
+    Verify {
+        /// Recording file
+        #[arg(long)]
+        input: PathBuf,
+        /// Also check a JSON file of golden checksum vectors
+        #[arg(long)]
+        vectors: Option<PathBuf>,
+        /// Print the report as JSON
+        #[arg(long)]
+        json: bool,
+        /// Stop at the first mismatch
+        #[arg(long)]
+        stop_on_first: bool,
+    },
+    /// Summarize what a recording contains
+    Inspect {
+        /// Recording file
+        #[arg(long)]
+        input: PathBuf,
+        /// Print the summary as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Measure book update and checksum throughput
+    Bench {
+        /// Push this recording through the processing pipeline instead of a synthetic book
+        #[arg(long)]
+        input: Option<PathBuf>,
+        /// Levels per side of the synthetic book
+        #[arg(long, default_value = "1000")]
+        levels: usize,
+        /// Random updates to apply to the synthetic book
+        #[arg(long, default_value = "100000")]
+        updates: usize,
+        /// Print the report as JSON
+        #[arg(long)]
+        json: bool,
+    },
 }
 
-#[tokio::main]
-async fn main() -> anyhow::Result<()> {
-    tracing_subscriber::fmt()
+impl Commands {
+    /// Whether this command takes over the terminal
+    fn uses_tui(&self) -> bool {
+        matches!(self, Commands::Tui { .. } | Commands::Run { tui: true, .. })
+    }
+
+    /// Whether this command runs books through the pipeline, and so has metrics to export
+    fn exports_metrics(&self) -> bool {
+        matches!(self, Commands::Run { .. } | Commands::Replay { .. } | Commands::Tui { .. } | Commands::ReplayIncident { .. })
+    }
+}
+
+/// Where TUI sessions log when no --log-file is given
+const DEFAULT_TUI_LOG: &str = "logs/blackbox.log";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum LogFormat {
+    Plain,
+    Json, // one JSON object per line
+}
+
+/// The file to log to: `log_file`, else `DEFAULT_TUI_LOG` if a TUI owns the
+/// screen, else none (stdout)
+fn log_destination(log_file: Option<&std::path::Path>, tui: bool) -> Option<PathBuf> {
+    match log_file {
+        Some(path) => Some(path.to_path_buf()),
+        None if tui => Some(PathBuf::from(DEFAULT_TUI_LOG)),
+        None => None,
+    }
+}
+
+/// A non-blocking writer for `path`, rotated daily (`blackbox.log.2024-01-31`).
+/// Lines are only flushed while the guard lives.
+fn rolling_log_writer(
+    path: &std::path::Path,
+) -> anyhow::Result<(tracing_appender::non_blocking::NonBlocking, tracing_appender::non_blocking::WorkerGuard)> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => std::path::Path::new("."),
+    };
+    let file_name = path.file_name().with_context(|| format!("Log file {} has no file name", path.display()))?;
+    std::fs::create_dir_all(dir).with_context(|| format!("Failed to create log directory {}", dir.display()))?;
+    Ok(tracing_appender::non_blocking(tracing_appender::rolling::daily(dir, file_name)))
+}
+
+fn log_subscriber<W>(format: LogFormat, writer: W, ansi: bool) -> Box<dyn tracing::Subscriber + Send + Sync>
+where
+    W: for<'a> tracing_subscriber::fmt::MakeWriter<'a> + Send + Sync + 'static,
+{
+    let builder = tracing_subscriber::fmt()
         .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
-        .init();
+        .with_writer(writer)
+        .with_ansi(ansi);
+    match format {
+        LogFormat::Plain => Box::new(builder.finish()),
+        LogFormat::Json => Box::new(builder.json().finish()),
+    }
+}
+
+/// Install the global subscriber. Returns the log file in use, if any, and the
+/// guard that keeps its writer flushing; hold it until exit.
+fn init_tracing(
+    log_file: Option<&std::path::Path>,
+    format: LogFormat,
+    tui: bool,
+) -> anyhow::Result<(Option<PathBuf>, Option<tracing_appender::non_blocking::WorkerGuard>)> {
+    match log_destination(log_file, tui) {
+        Some(path) => {
+            let (writer, guard) = rolling_log_writer(&path)?;
+            tracing::subscriber::set_global_default(log_subscriber(format, writer, false))?;
+            Ok((Some(path), Some(guard)))
+        }
+        None => {
+            tracing::subscriber::set_global_default(log_subscriber(format, std::io::stdout, true))?;
+            Ok((None, None))
+        }
+    }
+}
 
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
     let cli = Cli::parse();
+    let (log_path, _log_guard) = init_tracing(cli.log_file.as_deref(), cli.log_format, cli.command.uses_tui())?;
+    let http_options = HttpOptions {
+        token: cli.http_token,
+        cors_allow_origins: cli.cors_allow_origin,
+        rate_limits: http::rate_limits(&cli.rate_limit),
+    };
+    if cli.command.exports_metrics() {
+        init_metrics(cli.metrics_addr);
+    }
+    let events_file = (!cli.no_persist_events).then_some(cli.events_file);
+    let resume_state = cli.resume_state;
 
     match cli.command {
         Commands::Run {
             symbols,
+            symbols_file,
             depth,
             http,
             ping_interval,
+            ws_url,
             record,
+            record_per_symbol,
+            record_append,
+            record_channels,
+            max_record_bytes,
+            max_incidents_bytes,
+            disk_policy,
+            record_rotate_size,
+            record_rotate_every,
+            record_flush_interval,
+            record_flush_frames,
+            record_decoded,
+            level_meta,
+            strict_book,
+            tombstones,
+            checksum_levels,
+            frame_buffer,
+            symbol_frame_buffer,
+            resync_after,
+            checksum_dump_dir,
+            checksum_dump_interval,
+            tui,
         } => {
-            run_client(symbols, depth, http, ping_interval, record).await?;
+            let (record_guard, incidents_guard) = disk_guards(max_record_bytes, max_incidents_bytes, disk_policy);
+            let (symbols, depths) = split_symbol_args(resolve_symbols(symbols, symbols_file.as_deref())?);
+            let options = ClientOptions {
+                symbols,
+                depth,
+                depths,
+                http_addr: http,
+                ping_interval,
+                ws_url,
+                record_per_symbol: record_per_symbol.is_some(),
+                record: record.or(record_per_symbol),
+                record_options: RecorderOptions {
+                    append: record_append,
+                    channel_filter: record_channels,
+                    ..recorder_options(record_rotate_size, record_rotate_every, record_flush_interval, record_flush_frames)
+                },
+                record_decoded,
+                record_guard,
+                incidents_guard,
+                level_meta,
+                strict_book,
+                tombstones,
+                checksum_levels,
+                last_frames_capacity: frame_buffer,
+                symbol_frames_capacity: symbol_frame_buffer,
+                resync_after,
+                checksum_dump: checksum_dump_dir.map(|dir| (dir, Duration::from_secs(checksum_dump_interval))),
+                log_path,
+                events_file,
+                resume_state,
+            };
+            run_client(options, http_options, tui).await?;
         }
         Commands::Replay {
             input,
@@ -156,154 +592,484 @@ async fn main() -> anyhow::Result<()> {
             fault_drop_once,
             fault_reorder_once,
             fault_mutate_once,
+            fault_mutate_price_once,
+            fault_duplicate_once,
+            fault_corrupt_checksum_once,
+            fault_checksum_xor,
+            fault_delay_once,
+            fault_delay_ms,
             fault_mutate_delta,
+            fault_price_increment,
+            force,
+            from,
+            to,
+            loop_playback,
+            exit_on_complete,
+            hold,
         } => {
-            let fault = build_fault_rule(
-                fault_drop_every,
-                fault_drop_once,
-                fault_reorder_once,
-                fault_mutate_once,
-                fault_mutate_delta,
-            );
-            replay_recording(input, speed, http, fault).await?;
+            let faults = FaultFlags {
+                drop_every: fault_drop_every,
+                drop_once: fault_drop_once,
+                reorder_once: fault_reorder_once,
+                mutate_once: fault_mutate_once,
+                mutate_price_once: fault_mutate_price_once,
+                duplicate_once: fault_duplicate_once,
+                corrupt_checksum_once: fault_corrupt_checksum_once,
+                delay_once: fault_delay_once,
+                checksum_xor: fault_checksum_xor,
+                delay_ms: fault_delay_ms,
+                mutate_delta: fault_mutate_delta,
+            }
+            .rules();
+            let options = ReplayOptions {
+                input,
+                speed,
+                http_addr: http,
+                faults,
+                price_increment: fault_price_increment,
+                force,
+                from,
+                to,
+                loop_playback,
+                exit_on_complete: exit_on_complete && !hold,
+            };
+            replay_recording(options, http_options).await?;
         }
         Commands::Tui {
             symbols,
+            symbols_file,
             depth,
             http,
             ping_interval,
+            ws_url,
             record,
+            record_per_symbol,
+            record_append,
+            record_channels,
+            max_record_bytes,
+            max_incidents_bytes,
+            disk_policy,
+            record_rotate_size,
+            record_rotate_every,
+            record_flush_interval,
+            record_flush_frames,
+            record_decoded,
             replay,
             speed,
             fault,
             once_at,
+            fault_checksum_xor,
+            fault_delay_ms,
+            fault_mutate_delta,
+            loop_playback,
             mock,
+            level_meta,
+            strict_book,
+            tombstones,
+            checksum_levels,
+            frame_buffer,
+            symbol_frame_buffer,
+            resync_after,
+            checksum_dump_dir,
+            checksum_dump_interval,
         } => {
-            run_tui_mode(symbols, depth, http, ping_interval, record, replay, speed, fault, once_at, mock).await?;
+            let (record_guard, incidents_guard) = disk_guards(max_record_bytes, max_incidents_bytes, disk_policy);
+            let (symbols, depths) = split_symbol_args(resolve_symbols(symbols, symbols_file.as_deref())?);
+            let options = ClientOptions {
+                symbols,
+                depth,
+                depths,
+                http_addr: http,
+                ping_interval,
+                ws_url,
+                record_per_symbol: record_per_symbol.is_some(),
+                record: record.or(record_per_symbol),
+                record_options: RecorderOptions {
+                    append: record_append,
+                    channel_filter: record_channels,
+                    ..recorder_options(record_rotate_size, record_rotate_every, record_flush_interval, record_flush_frames)
+                },
+                record_decoded,
+                record_guard,
+                incidents_guard,
+                level_meta,
+                strict_book,
+                tombstones,
+                checksum_levels,
+                last_frames_capacity: frame_buffer,
+                symbol_frames_capacity: symbol_frame_buffer,
+                resync_after,
+                checksum_dump: checksum_dump_dir.map(|dir| (dir, Duration::from_secs(checksum_dump_interval))),
+                log_path,
+                events_file,
+                resume_state,
+            };
+            let tui = TuiOptions {
+                replay,
+                speed,
+                fault,
+                once_at,
+                fault_checksum_xor,
+                fault_delay_ms,
+                fault_mutate_delta,
+                loop_playback,
+                mock,
+            };
+            run_tui_mode(options, tui).await?;
         }
         Commands::ReplayIncident { bundle, speed, http } => {
-            replay_incident_bundle(bundle, speed, http).await?;
+            replay_incident_bundle(bundle, speed, http, http_options).await?;
+        }
+        Commands::Compare { a, b, depth } => {
+            compare::run_compare(&a, &b, depth)?;
+        }
+        Commands::Verify { input, vectors, json, stop_on_first } => {
+            verify::run_verify(&input, vectors.as_deref(), json, stop_on_first)?;
+        }
+        Commands::Inspect { input, json } => {
+            inspect::run_inspect(&input, json)?;
+        }
+        Commands::Bench { input, levels, updates, json } => {
+            bench::run_bench(input.as_deref(), levels, updates, json).await?;
         }
     }
 
     Ok(())
 }
 
-async fn run_client(
+/// What `run` and `tui` both take from the command line: the feed, the
+/// books kept from it and how it's recorded
+struct ClientOptions {
     symbols: Vec<String>,
     depth: u32,
+    depths: HashMap<String, u32>, // per-symbol overrides of `depth`
     http_addr: String,
-    ping_interval_str: String,
-    record_path: Option<PathBuf>,
-) -> anyhow::Result<()> {
-    info!("Starting Kraken Blackbox");
-    info!("Symbols: {:?}, Depth: {}, HTTP: {}", symbols, depth, http_addr);
+    ping_interval: Duration,
+    ws_url: String,
+    record: Option<PathBuf>,
+    record_options: RecorderOptions,
+    record_per_symbol: bool,
+    record_decoded: bool,
+    record_guard: Option<DiskGuard>,
+    incidents_guard: Option<DiskGuard>,
+    level_meta: bool,
+    strict_book: bool,
+    tombstones: usize,
+    checksum_levels: Option<usize>,
+    last_frames_capacity: usize,
+    symbol_frames_capacity: usize,
+    resync_after: u64,
+    checksum_dump: Option<(PathBuf, Duration)>, // directory, interval
+    log_path: Option<PathBuf>,
+    events_file: Option<PathBuf>, // None = not persisted
+    resume_state: bool,
+}
 
-    // Parse ping interval
-    let ping_interval = parse_duration(&ping_interval_str)
-        .context("Invalid ping interval format (e.g., '30s', '1m')")?;
+/// The tui subcommand's own flags: what to play instead of the live feed
+struct TuiOptions {
+    replay: Option<PathBuf>,
+    speed: f64,
+    fault: String,
+    once_at: Option<usize>,
+    fault_checksum_xor: u32,
+    fault_delay_ms: u64,
+    fault_mutate_delta: i32,
+    loop_playback: bool,
+    mock: bool,
+}
 
-    // Initialize metrics
-    init_metrics();
-    let _metrics_handle = metrics_exporter_prometheus::PrometheusBuilder::new()
-        .install()
-        .context("Failed to install Prometheus metrics exporter")?;
+async fn run_client(options: ClientOptions, http_options: HttpOptions, tui: bool) -> anyhow::Result<()> {
+    let ClientOptions {
+        symbols,
+        depth,
+        depths,
+        http_addr,
+        ping_interval,
+        ws_url,
+        record,
+        record_options,
+        record_per_symbol,
+        record_decoded,
+        record_guard,
+        incidents_guard,
+        level_meta,
+        strict_book,
+        tombstones,
+        checksum_levels,
+        last_frames_capacity,
+        symbol_frames_capacity,
+        resync_after,
+        checksum_dump,
+        log_path,
+        events_file,
+        resume_state,
+    } = options;
+    info!("Starting Kraken Blackbox");
+    info!("Symbols: {:?}, Depth: {} (overrides {:?}), HTTP: {}", symbols, depth, depths, http_addr);
 
     // Create shared state
-    let state = AppState::new();
+    let mut state = AppState::new();
+    state.metrics = metrics::handle();
+    state.track_level_meta = level_meta;
+    state.strict_book = strict_book;
+    state.tombstones = tombstones;
+    state.checksum_levels = checksum_levels;
+    state.last_frames_capacity = last_frames_capacity;
+    state.symbol_frames_capacity = symbol_frames_capacity;
+    state.resync_after_fails = resync_after;
+    state.record_decoded = record_decoded;
+    state.record_disk_guard = record_guard;
+    state.checksum_dumper = checksum_dump
+        .map(|(dir, interval)| Arc::new(crate::integrity::ChecksumDumper::new(dir, interval)));
+    state.ws_url = Some(ws_url.clone());
+    state.ping_interval = Some(ping_interval);
+    // POST/DELETE /symbols change the client's subscriptions through this
+    let (command_tx, command_rx) = mpsc::unbounded_channel();
+    state.ws_commands = Some(command_tx);
     
-    // Set depth for all symbols
     for symbol in &symbols {
-        state.set_depth(symbol, depth);
+        state.set_depth(symbol, depths.get(symbol).copied().unwrap_or(depth));
+    }
+    state.set_requested_symbols(symbols.clone()).await;
+    if let Some(path) = events_file {
+        event_store::persist(&state, path).await?;
+    }
+    if resume_state {
+        resume_from_snapshot(&mut state).await;
     }
 
     // Create incident manager
     let incidents_dir = PathBuf::from("./incidents");
-    let incident_manager = Arc::new(IncidentManager::new(incidents_dir)?);
+    let incident_manager = Arc::new(IncidentManager::new(incidents_dir)?.with_disk_guard(incidents_guard));
 
-    // Create recorder if needed
-    let recorder = if let Some(path) = record_path {
-        Some(Recorder::new(path)?)
-    } else {
-        None
+    // Create recorder if needed; the TUI's record toggle reuses its options
+    let metadata = RecordingMetadata {
+        ws_url: Some(ws_url.clone()),
+        ..RecordingMetadata::new(symbols.clone(), depth)
     };
+    let recording_path = record.as_ref().map(|path| path.display().to_string());
+    state.recorder_options = RecorderOptions { metadata: Some(metadata), ..record_options };
+    if let Some(path) = record {
+        state.start_recording(RecordSink::open(path, state.recorder_options.clone(), record_per_symbol)?).await;
+    }
 
     // Create WebSocket event channel
     let (ws_tx, mut ws_rx) = mpsc::unbounded_channel();
 
     // Spawn WebSocket client
-    let client = WsClient::new(symbols.clone(), depth, ping_interval, ws_tx);
-    let client_handle = tokio::spawn(async move {
+    let client = WsClient::new(symbols.clone(), depth, ping_interval, ws_tx)
+        .with_depths(depths.clone())
+        .with_url(ws_url.clone())
+        .with_commands(command_rx);
+    let mut client_handle = tokio::spawn(async move {
         if let Err(e) = client.run().await {
             error!("WebSocket client error: {}", e);
         }
     });
 
+    tokio::spawn(msg_rate::sample_forever(state.clone()));
+
     // Spawn orderbook processor
     let state_clone = state.clone();
     let incident_manager_clone = incident_manager.clone();
-    let mut recorder_mut = recorder;
-    let processor_handle = tokio::spawn(async move {
-        process_ws_events(&state_clone, &incident_manager_clone, &mut ws_rx, recorder_mut.as_mut()).await;
+    let mut processor_handle = tokio::spawn(async move {
+        process_ws_events(&state_clone, &incident_manager_clone, &mut ws_rx).await;
     });
 
     // Start HTTP server
-    let app = router(state.clone(), incident_manager.clone())
-        .route("/", get(|| async { Html(static_ui::UI_HTML) }));
+    let app = http_options.apply(router(state.clone(), incident_manager.clone())
+        .route("/", get(|| async { Html(static_ui::UI_HTML) })))?;
     
-    let server_handle = tokio::spawn(async move {
+    let (stop_http, http_stopped) = tokio::sync::oneshot::channel::<()>();
+    let mut server_handle = tokio::spawn(async move {
         let listener = tokio::net::TcpListener::bind(&http_addr).await.unwrap();
         info!("HTTP server listening on http://{}", http_addr);
-        axum::serve(listener, app).await.unwrap();
+        axum::serve(listener, app)
+            .with_graceful_shutdown(async {
+                let _ = http_stopped.await;
+            })
+            .await
+            .unwrap();
     });
 
-    // Wait for all tasks
-    tokio::select! {
-        _ = client_handle => {
-            warn!("WebSocket client task ended");
+    // Run until the TUI quits, or a task ends or Ctrl-C. The TUI isn't raced
+    // against the tasks so that it always gets to restore the terminal.
+    let mut result = Ok(());
+    if tui {
+        let mut app = tui::TuiApp::new(state.clone(), recording_path);
+        app.log_path = log_path.map(|p| p.display().to_string());
+        result = tui::run_tui_with_manager(app, "LIVE".to_string(), "OFF".to_string(), Some(incident_manager.clone())).await;
+        info!("Shutting down");
+    } else {
+        tokio::select! {
+            _ = &mut client_handle => {
+                warn!("WebSocket client task ended");
+            }
+            _ = &mut processor_handle => {
+                warn!("Processor task ended");
+            }
+            _ = &mut server_handle => {
+                warn!("HTTP server task ended");
+            }
+            _ = tokio::signal::ctrl_c() => {
+                info!("Shutting down");
+            }
         }
-        _ = processor_handle => {
-            warn!("Processor task ended");
+    }
+    
+    drain_and_close(&state, client_handle, processor_handle).await;
+    save_snapshot(&state).await;
+    if !server_handle.is_finished() {
+        let _ = stop_http.send(());
+        if tokio::time::timeout(SHUTDOWN_DEADLINE, &mut server_handle).await.is_err() {
+            warn!("HTTP server didn't stop within {:?}", SHUTDOWN_DEADLINE);
+            server_handle.abort();
+        }
+    }
+
+    result
+}
+
+/// How long shutdown waits for queued events, and for the HTTP server
+const SHUTDOWN_DEADLINE: Duration = Duration::from_secs(5);
+
+/// Wind a live session down without losing what it received: stop taking WS
+/// events, process those already queued, then close the recording and write
+/// `health_summary.json` next to it. Either task may have ended already.
+async fn drain_and_close(state: &AppState, client: tokio::task::JoinHandle<()>, processor: tokio::task::JoinHandle<()>) {
+    // Dropping the client drops its sender, so the processor stops once it's
+    // through the queue
+    if !client.is_finished() {
+        client.abort();
+        let _ = client.await;
+    }
+    if !processor.is_finished() && tokio::time::timeout(SHUTDOWN_DEADLINE, processor).await.is_err() {
+        warn!("Stopped processing queued events after {:?}", SHUTDOWN_DEADLINE);
+    }
+    
+    close_recording(state).await;
+}
+
+/// Once `symbol` has failed `resync_after_fails` checksums in a row, ask the
+/// client to re-subscribe it, as often as the backoff allows, and drop the
+/// local book until the fresh snapshot arrives. Only live feeds can resync.
+async fn maybe_resync(state: &AppState, symbol: &str) {
+    let Some(commands) = &state.ws_commands else {
+        return;
+    };
+    let fails = state.health.get(symbol).map_or(0, |health| health.consecutive_fails);
+    if state.resync_after_fails == 0 || fails < state.resync_after_fails {
+        return;
+    }
+    if state.resyncing.contains(symbol) || !state.can_resync(symbol) {
+        return;
+    }
+    if commands.send(ClientCommand::Resync { symbol: symbol.to_string() }).is_err() {
+        warn!("Can't resync {}: the WebSocket client isn't running", symbol);
+        return;
+    }
+    warn!("Resyncing {} after {} consecutive checksum failures", symbol, fails);
+    state.record_resync(symbol);
+    state.resyncing.insert(symbol.to_string());
+    state.orderbooks.remove(symbol);
+    if let Some(mut health) = state.health.get_mut(symbol) {
+        health.resync_count += 1;
+    }
+    metrics::record_resync(symbol);
+    state.push_event(state::UiEvent::ResyncStarted { symbol: symbol.to_string() }).await;
+}
+
+/// A snapshot for `symbol` arrived: done, if a resync was waiting for it
+async fn finish_resync(state: &AppState, symbol: &str) {
+    if state.resyncing.remove(symbol).is_some() {
+        info!("Resynced {}", symbol);
+        state.push_event(state::UiEvent::ResyncDone { symbol: symbol.to_string() }).await;
+    }
+}
+
+/// Write `STATE_SNAPSHOT_FILE` for the next --resume-state
+async fn save_snapshot(state: &AppState) {
+    let path = PathBuf::from(state::STATE_SNAPSHOT_FILE);
+    match state.export_snapshot().await.save(&path) {
+        Ok(()) => info!("State snapshot written to {}", path.display()),
+        Err(e) => warn!("Failed to write state snapshot: {:#}", e),
+    }
+}
+
+/// Restore `STATE_SNAPSHOT_FILE`, if there is one; a bad one is skipped
+async fn resume_from_snapshot(state: &mut AppState) {
+    let path = PathBuf::from(state::STATE_SNAPSHOT_FILE);
+    if !path.exists() {
+        info!("No {} to resume from", path.display());
+        return;
+    }
+    match state::StateSnapshot::load(&path) {
+        Ok(snapshot) => {
+            info!("Resuming from {} saved at {}", path.display(), snapshot.saved_at);
+            state.restore(snapshot).await;
         }
-        _ = server_handle => {
-            warn!("HTTP server task ended");
+        Err(e) => warn!("Not resuming: {:#}", e),
+    }
+}
+
+/// Write out every queued frame and close the recording, if any, leaving a
+/// `health_summary.json` next to it
+async fn close_recording(state: &AppState) {
+    let recording = match state.recorder.read().await.is_some() {
+        true => state.last_recording.read().await.clone(),
+        false => None,
+    };
+    state.stop_recording().await;
+    if let Some((path, _)) = recording {
+        match write_health_summary(state, &path) {
+            Ok(summary) => info!("Health summary written to {}", summary.display()),
+            Err(e) => warn!("Failed to write health summary: {:#}", e),
         }
     }
+}
 
-    Ok(())
+/// `/health` as of now, in the recording's directory (or in the
+/// per-symbol recording directory itself)
+fn write_health_summary(state: &AppState, record_path: &std::path::Path) -> anyhow::Result<PathBuf> {
+    let dir = if record_path.is_dir() {
+        record_path
+    } else {
+        record_path.parent().unwrap_or(record_path)
+    };
+    let path = dir.join("health_summary.json");
+    std::fs::write(&path, serde_json::to_vec_pretty(&state.overall_health())?)
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(path)
 }
 
 async fn process_ws_events(
     state: &AppState,
     incident_manager: &Arc<IncidentManager>,
     ws_rx: &mut mpsc::UnboundedReceiver<WsEvent>,
-    mut recorder: Option<&mut Recorder>,
 ) {
     while let Some(event) = ws_rx.recv().await {
         match event {
             WsEvent::Connected => {
                 info!("WebSocket connected");
+                state.set_connected(true);
+                metrics::update_ws_connected(true);
             }
-            WsEvent::Disconnected => {
-                warn!("WebSocket disconnected");
+            WsEvent::Disconnected { cause } => {
+                warn!("WebSocket disconnected ({})", cause.as_str());
+                state.set_connected(false);
+                metrics::update_ws_connected(false);
+                state.mark_books_stale();
             }
             WsEvent::Frame(raw_frame) => {
                 // Record frame
-                if let Some(ref mut rec) = recorder {
-                    let _ = rec.record_frame(&raw_frame, None);
-                }
-                
-                // Store in ring buffer (keep last 1000 frames)
-                let mut frames = state.last_frames.write().await;
-                frames.push((chrono::Utc::now(), raw_frame.clone()));
-                if frames.len() > 1000 {
-                    frames.remove(0);
-                }
+                state.record_frame(&raw_frame).await;
+                state.buffer_frame(&raw_frame).await;
+                state.push_last_frame(&raw_frame).await;
             }
             WsEvent::InstrumentSnapshot(instruments) => {
                 info!("Received instrument snapshot with {} pairs", instruments.len());
                 for (symbol, info) in instruments {
-                    state.instruments.insert(symbol.clone(), info);
+                    state.insert_instrument(symbol, info);
                 }
             }
             WsEvent::BookSnapshot {
@@ -312,126 +1078,109 @@ async fn process_ws_events(
                 asks,
                 checksum,
             } => {
+                finish_resync(state, &symbol).await;
                 // Initialize orderbook
                 let asks_len = asks.len();
                 let bids_len = bids.len();
-                let mut book = Orderbook::new();
+                let depth = state.subscribed_depth(&symbol, bids.len().max(asks.len()));
+                let mut book = state.new_orderbook(depth);
                 book.apply_snapshot(bids.clone(), asks.clone());
-                let depth = state.get_depth(&symbol) as usize;
                 book.truncate(depth);
+                record_book_violation(incident_manager, &symbol, check_book_invariants(state, &symbol, &book)).await;
                 
-                // Verify checksum if available
-                if let Some(expected_checksum) = checksum {
-                    if let Some(instrument) = state.instruments.get(&symbol) {
-                        let is_valid = verify_checksum(
-                            &book,
-                            expected_checksum,
-                            instrument.price_precision,
-                            instrument.qty_precision,
-                        );
-                        
-                        let mut health = state.health.entry(symbol.clone()).or_insert_with(|| {
-                            blackbox_core::health::SymbolHealth::new(symbol.clone())
-                        });
-                        health.connected = true;
-                        health.record_message();
-                        
-                        if is_valid {
-                            health.record_checksum_ok();
-                            metrics::record_checksum_ok(&symbol);
-                        } else {
-                            health.record_checksum_fail();
-                            metrics::record_checksum_fail(&symbol);
-                            warn!("Checksum mismatch for {}: expected {}, computed different", symbol, expected_checksum);
-                            
-                            // Record incident
-                            let incident = incident_manager
-                                .record_incident(
-                                    IncidentReason::ChecksumMismatch,
-                                    Some(symbol.clone()),
-                                    serde_json::json!({
-                                        "expected_checksum": expected_checksum,
-                                        "symbol": symbol,
-                                    }),
-                                )
-                                .await;
-                            
-                            // Export incident bundle
-                            let _ = export_incident_for_symbol(state, incident_manager, &incident, &symbol).await;
-                        }
-                    }
+                if update_crossed_state(state, &symbol, &book) {
+                    state.push_event(crate::state::UiEvent::BookCrossed { symbol: symbol.clone() }).await;
                 }
                 
-                state.orderbooks.insert(symbol.clone(), book);
+                // Verify checksum if available
+                let check = checksum.map_or(BookCheck::Unchecked, |expected| verify_book(state, &symbol, &mut book, expected, true));
+                
+                state.publish_book_change(&symbol, &book);
+                update_book_size_metrics(&symbol, &book);
+                if state.is_requested(&symbol).await {
+                    update_top_of_book_metrics(&symbol, &book);
+                }
+                state.orderbooks.insert(symbol.clone(), StoredBook::new(book, depth));
                 metrics::update_orderbook_depth(&symbol, asks_len, bids_len);
+                
+                // Export incident bundle
+                if let Some(incident) = record_book_check(state, incident_manager, &symbol, check).await {
+                    let _ = export_incident_for_symbol(state, incident_manager, &incident, Some(&symbol)).await;
+                }
+                maybe_resync(state, &symbol).await;
             }
             WsEvent::BookUpdate {
                 symbol,
-                bids,
-                asks,
-                checksum,
-                timestamp: _,
+                mut bids,
+                mut asks,
+                mut checksum,
+                timestamp,
             } => {
+                let injected = apply_injected_fault(state, &symbol, &mut bids, &mut asks, &mut checksum);
+                if injected == Some(InjectedFault::Drop) {
+                    continue;
+                }
+                // Nothing awaits while the book guard is held: the subscription is
+                // read ahead of it, and the check, any violation and the UI events
+                // are collected under it and acted on once it is dropped
+                let subscribed = state.is_requested(&symbol).await;
+                let mut check = BookCheck::Unchecked;
+                let mut violation = None;
+                let mut events = Vec::new();
+                
                 if let Some(mut book_entry) = state.orderbooks.get_mut(&symbol) {
                     // Apply updates
-                    book_entry.apply_updates(bids.clone(), asks.clone());
+                    let summary = book_entry.apply_updates(bids.clone(), asks.clone());
+                    if injected == Some(InjectedFault::Duplicate) {
+                        // Absolute quantities: the second application must change nothing
+                        book_entry.apply_updates(bids.clone(), asks.clone());
+                    }
+                    record_book_timestamp(state, &mut book_entry, &symbol, timestamp.as_deref());
                     
                     // Truncate to configured depth
-                    let depth = state.get_depth(&symbol) as usize;
+                    let depth = book_entry.subscribed_depth;
                     book_entry.truncate(depth);
+                    violation = check_book_invariants(state, &symbol, &book_entry);
+                    
+                    if update_crossed_state(state, &symbol, &book_entry) {
+                        events.push(crate::state::UiEvent::BookCrossed { symbol: symbol.clone() });
+                    }
+                    if summary.top_of_book_moved() {
+                        events.push(crate::state::UiEvent::TopOfBookChanged { symbol: symbol.clone() });
+                    }
+                    state.publish_book_change(&symbol, &book_entry);
                     
                     // Verify checksum if available
-                    if let Some(expected_checksum) = checksum {
-                        if let Some(instrument) = state.instruments.get(&symbol) {
-                            let is_valid = verify_checksum(
-                                &book_entry,
-                                expected_checksum,
-                                instrument.price_precision,
-                                instrument.qty_precision,
-                            );
-                            
-                            let mut health = state.health.entry(symbol.clone()).or_insert_with(|| {
-                                blackbox_core::health::SymbolHealth::new(symbol.clone())
-                            });
-                            health.connected = true;
-                            health.record_message();
-                            
-                            if is_valid {
-                                health.record_checksum_ok();
-                                metrics::record_checksum_ok(&symbol);
-                            } else {
-                                health.record_checksum_fail();
-                                metrics::record_checksum_fail(&symbol);
-                                warn!("Checksum mismatch for {}: expected {}", symbol, expected_checksum);
-                                
-                                // Record incident
-                                let incident = incident_manager
-                                    .record_incident(
-                                        IncidentReason::ChecksumMismatch,
-                                        Some(symbol.clone()),
-                                        serde_json::json!({
-                                            "expected_checksum": expected_checksum,
-                                            "symbol": symbol,
-                                        }),
-                                    )
-                                    .await;
-                                
-                                // Export incident bundle
-                                let _ = export_incident_for_symbol(state, incident_manager, &incident, &symbol).await;
-                            }
-                        }
+                    // A stale book diverged while disconnected; wait for the next snapshot
+                    if let Some(expected_checksum) = checksum.filter(|_| !book_entry.stale) {
+                        check = verify_book(state, &symbol, &mut book_entry, expected_checksum, summary.top_levels_changed);
                     }
                     
-                    let (asks_depth, bids_depth) = book_entry.depth();
-                    metrics::update_orderbook_depth(&symbol, asks_depth, bids_depth);
+                    if !summary.is_empty() {
+                        let (asks_depth, bids_depth) = book_entry.depth();
+                        metrics::update_orderbook_depth(&symbol, asks_depth, bids_depth);
+                        update_book_size_metrics(&symbol, &book_entry);
+                        if subscribed {
+                            update_top_of_book_metrics(&symbol, &book_entry);
+                        }
+                    }
+                }
+                
+                record_book_violation(incident_manager, &symbol, violation).await;
+                for event in events {
+                    state.push_event(event).await;
                 }
+                // Export incident bundle
+                if let Some(incident) = record_book_check(state, incident_manager, &symbol, check).await {
+                    let _ = export_incident_for_symbol(state, incident_manager, &incident, Some(&symbol)).await;
+                }
+                maybe_resync(state, &symbol).await;
             }
             WsEvent::Error(err) => {
                 error!("WebSocket error: {}", err);
             }
             WsEvent::RateLimitExceeded => {
                 warn!("Rate limit exceeded, entering cooldown");
-                metrics::record_reconnect();
                 
                 // Record incident
                 let _ = incident_manager
@@ -448,187 +1197,599 @@ async fn process_ws_events(
     }
 }
 
-async fn export_incident_for_symbol(
-    state: &AppState,
-    incident_manager: &Arc<IncidentManager>,
-    incident: &blackbox_core::incident::Incident,
-    symbol: &str,
-) -> anyhow::Result<()> {
-    let config = serde_json::json!({
-        "symbol": symbol,
-        "depth": state.get_depth(symbol),
-    });
-    
-    let overall = state.overall_health();
-    let health = serde_json::to_value(&overall)?;
-    
-    let instrument = state.instruments.get(symbol).map(|e| e.value().clone());
-    
-    let book_top = state.orderbooks.get(symbol).map(|book| {
-        serde_json::json!({
-            "best_bid": book.best_bid().map(|(p, q)| (p.to_string(), q.to_string())),
-            "best_ask": book.best_ask().map(|(p, q)| (p.to_string(), q.to_string())),
-        })
-    });
-    
-    let frames = state.last_frames.read().await;
-    let frames_vec: Vec<_> = frames.iter().cloned().collect();
-    
-    incident_manager
-        .export_incident_bundle(
-            incident,
-            config,
-            health,
-            instrument.as_ref(),
-            book_top,
-            &frames_vec,
-            incident.timestamp,
-        )
-        .await?;
-    
-    Ok(())
-}
-
-async fn replay_recording(
-    input: PathBuf,
-    speed: f64,
-    http_addr: String,
-    fault: FaultRule,
-) -> anyhow::Result<()> {
-    info!("Replaying recording from {:?} at {}x speed", input, speed);
+/// Feed latencies above this are a frame stamped long ago or a skewed clock,
+/// not time in transit
+const MAX_FEED_LATENCY_MS: f64 = 60_000.0;
 
-    let mode = if speed == 1.0 {
-        ReplayMode::Realtime
-    } else if speed > 0.0 {
-        ReplayMode::Speed(speed)
+/// Latency of a frame stamped `frame_ts` and received at `received`, clamped
+/// to `0..=MAX_FEED_LATENCY_MS`; true if it needed clamping
+fn feed_latency_ms(frame_ts: chrono::DateTime<chrono::Utc>, received: chrono::DateTime<chrono::Utc>) -> (f64, bool) {
+    let elapsed = received.signed_duration_since(frame_ts);
+    let ms = elapsed.num_microseconds().map_or(elapsed.num_milliseconds() as f64, |us| us as f64 / 1000.0);
+    if ms < 0.0 {
+        (0.0, true)
+    } else if ms > MAX_FEED_LATENCY_MS {
+        (MAX_FEED_LATENCY_MS, true)
     } else {
-        ReplayMode::AsFast
-    };
-
-    let config = ReplayConfig { mode, fault };
-    let mut replayer = Replayer::new(input.clone(), config)?;
-    replayer.start();
+        (ms, false)
+    }
+}
 
-    // Create shared state
-    let state = AppState::new();
+/// Store the exchange timestamp on the book and record exchange -> local latency
+fn record_book_timestamp(state: &AppState, book: &mut Orderbook, symbol: &str, timestamp: Option<&str>) {
+    let Some(ts) = timestamp.and_then(|t| chrono::DateTime::parse_from_rfc3339(t).ok()) else {
+        return;
+    };
+    let ts = ts.with_timezone(&chrono::Utc);
+    book.set_last_update_ts(ts);
     
-    // Create incident manager
-    let incidents_dir = PathBuf::from("./incidents");
-    let incident_manager = Arc::new(IncidentManager::new(incidents_dir)?);
+    // Replayed frames carry the time they were recorded
+    if matches!(state.run_mode, RunMode::Replay | RunMode::ReplayIncident) {
+        return;
+    }
+    let (latency_ms, clamped) = feed_latency_ms(ts, chrono::Utc::now());
+    if clamped {
+        metrics::record_latency_anomaly(symbol);
+    }
+    metrics::record_latency(symbol, latency_ms);
+    state.health
+        .entry(symbol.to_string())
+        .or_insert_with(|| blackbox_core::health::SymbolHealth::new(symbol.to_string()))
+        .feed_latency_ms = Some(latency_ms);
+}
 
-    // Spawn processor for replay (simplified - full processing would require more work)
-    let _state_clone = state.clone();
-    let _incident_manager_clone = incident_manager.clone();
-    let processor_handle = tokio::spawn(async move {
-        use blackbox_ws::parser::parse_frame;
-        
-        // Process replayed frames (simplified - would need full processing logic)
-        while !replayer.is_done() {
-            if let Some(frame) = replayer.next_frame() {
-                // Parse frame similar to live processing
-                match parse_frame(&frame) {
-                    Ok(_parsed) => {
-                        // TODO: Process parsed frame through same pipeline as live
-                        // For now, just log
-                    }
-                    Err(e) => {
-                        warn!("Failed to parse replayed frame: {}", e);
-                    }
-                }
-            } else {
-                // Need to wait for next frame timing
-                sleep(Duration::from_millis(10)).await;
+/// Apply the fault armed for `symbol` (TUI [D] or `POST /fault`) to its
+/// update. Returns it so the caller can duplicate or drop the update.
+fn apply_injected_fault(
+    state: &AppState,
+    symbol: &str,
+    bids: &mut [(Decimal, Decimal)],
+    asks: &mut [(Decimal, Decimal)],
+    checksum: &mut Option<u32>,
+) -> Option<InjectedFault> {
+    let fault = state.fault_injector.consume(symbol)?;
+    match fault {
+        InjectedFault::MutateQty { delta_ticks } => {
+            // Needs the qty increment, so only known instruments are mutated
+            if let (Some(first_ask), Some(instrument)) = (asks.first_mut(), state.instruments.get(symbol)) {
+                first_ask.1 += instrument.qty_increment * Decimal::from(delta_ticks);
             }
         }
-        info!("Replay completed");
-    });
-
-    // Start HTTP server
-    let app = router(state.clone(), incident_manager.clone())
-        .route("/", get(|| async { Html(static_ui::UI_HTML) }));
-    
-    let server_handle = tokio::spawn(async move {
-        let listener = tokio::net::TcpListener::bind(&http_addr).await.unwrap();
-        info!("HTTP server listening on http://{}", http_addr);
-        axum::serve(listener, app).await.unwrap();
-    });
-
-    tokio::select! {
-        _ = processor_handle => {
-            info!("Replay completed");
+        InjectedFault::MutatePrice { delta_ticks } => {
+            // Move the first ask (else bid) out: the book gains a phantom level
+            let increment = state.instruments.get(symbol)
+                .map_or(DEFAULT_PRICE_INCREMENT, |instrument| instrument.price_increment)
+                * Decimal::from(delta_ticks);
+            if let Some(first_ask) = asks.first_mut() {
+                first_ask.0 += increment;
+            } else if let Some(first_bid) = bids.first_mut() {
+                first_bid.0 -= increment;
+            }
         }
-        _ = server_handle => {}
+        InjectedFault::CorruptChecksum { xor } => *checksum = checksum.map(|c| c ^ xor),
+        InjectedFault::Duplicate | InjectedFault::Drop => {}
     }
+    Some(fault)
+}
 
-    Ok(())
+fn update_book_size_metrics(symbol: &str, book: &Orderbook) {
+    let (asks, bids) = book.depth();
+    metrics::update_book_size(symbol, asks + bids, book.approx_bytes());
 }
 
-async fn run_tui_mode(
-    symbols: Vec<String>,
-    depth: u32,
-    http_addr: String,
-    ping_interval_str: String,
-    record_path: Option<PathBuf>,
-    replay_path: Option<PathBuf>,
-    speed: f64,
-    fault: String,
-    once_at: Option<usize>,
-    mock: bool,
-) -> anyhow::Result<()> {
-    info!("Starting Kraken Blackbox TUI - Integrity Tab");
-    info!("Symbols: {:?}, Depth: {}, Mock: {}", symbols, depth, mock);
+/// Best bid/ask, mid and spread gauges. Only called for subscribed symbols,
+/// so a replay or a stray frame can't add label sets without bound.
+fn update_top_of_book_metrics(symbol: &str, book: &Orderbook) {
+    metrics::update_top_of_book(
+        symbol,
+        book.best_bid().map(|(price, _)| price),
+        book.best_ask().map(|(price, _)| price),
+        book.mid(),
+        book.spread_bps(),
+    );
+}
 
-    let mode = if replay_path.is_some() {
-        "REPLAY"
-    } else if mock {
-        "MOCK"
-    } else {
-        "LIVE"
-    };
+/// What `verify_book` found, for `record_book_check` to act on once the
+/// caller's guards are dropped
+enum BookCheck {
+    /// No instrument yet to format the book with
+    Unchecked,
+    Matched,
+    Mismatched(ChecksumVerification),
+}
 
-    // Build fault status string
-    let fault_status = if fault == "none" || once_at.is_none() {
-        "OFF".to_string()
-    } else {
-        format!("{}@{}", fault, once_at.unwrap())
+/// Check `book` against the checksum sent with it, keeping the symbol's
+/// integrity proof, health and metrics in step. `top_levels_changed` is false
+/// for an update that left the checksummed levels alone. Never awaits, so it
+/// can run under the book guard.
+fn verify_book(
+    state: &AppState,
+    symbol: &str,
+    book: &mut Orderbook,
+    expected_checksum: u32,
+    top_levels_changed: bool,
+) -> BookCheck {
+    let Some(instrument) = state.instruments.get(symbol) else {
+        return BookCheck::Unchecked;
+    };
+    let verification = {
+        let mut proof = state.integrity_proofs.entry(symbol.to_string()).or_default();
+        // Nothing checksummed changed and Kraken still sends the checksum we
+        // last matched: the result is known, skip rebuilding the proof
+        if !top_levels_changed && proof.expected_checksum == expected_checksum && proof.is_match() {
+            None
+        } else {
+            Some(update_integrity_proof(
+                &mut proof,
+                book,
+                expected_checksum,
+                &instrument,
+                &state.formatter(symbol, &instrument),
+                symbol,
+                state.checksum_dumper.as_deref(),
+            ))
+        }
+    };
+    
+    let mut health = state.health.entry(symbol.to_string()).or_insert_with(|| {
+        blackbox_core::health::SymbolHealth::new(symbol.to_string())
+    });
+    health.connected = true;
+    health.record_message();
+    metrics::record_message(symbol);
+    
+    match verification {
+        Some(verification) if !verification.matched => {
+            health.record_checksum_fail();
+            metrics::update_checksum_streak(symbol, false, health.consecutive_fails);
+            metrics::record_checksum_fail(symbol);
+            warn!("Checksum mismatch for {}: expected {}, computed {}", symbol, expected_checksum, verification.computed);
+            if let Some(negative) = verification.negative_level {
+                warn!("{}: {}", symbol, negative);
+            }
+            BookCheck::Mismatched(verification)
+        }
+        _ => {
+            health.record_checksum_ok();
+            metrics::update_checksum_streak(symbol, true, health.consecutive_fails);
+            metrics::record_checksum_ok(symbol);
+            state.record_verified_book(symbol, book);
+            BookCheck::Matched
+        }
+    }
+}
+
+/// Log `verify_book`'s result and, on a mismatch, record the incident.
+/// Returns the incident so the caller can export its bundle.
+async fn record_book_check(
+    state: &AppState,
+    incident_manager: &Arc<IncidentManager>,
+    symbol: &str,
+    check: BookCheck,
+) -> Option<Incident> {
+    use crate::state::UiEvent;
+    
+    let verification = match check {
+        BookCheck::Unchecked => return None,
+        BookCheck::Matched => {
+            state.push_event(UiEvent::ChecksumOk { symbol: symbol.to_string() }).await;
+            return None;
+        }
+        BookCheck::Mismatched(verification) => verification,
+    };
+    state.push_event(UiEvent::ChecksumMismatch { symbol: symbol.to_string() }).await;
+    let incident = incident_manager
+        .record_incident(
+            IncidentReason::ChecksumMismatch,
+            Some(symbol.to_string()),
+            serde_json::json!({
+                "expected_checksum": verification.expected,
+                "computed_checksum": verification.computed,
+                "checksum_len": verification.checksum_len,
+                "negative_level": verification.negative_level.map(|n| n.to_string()),
+                "symbol": symbol,
+            }),
+        )
+        .await;
+    let reason = format!("{:?}", incident.reason);
+    state.set_last_incident(IncidentMeta::new(incident.id.clone(), symbol.to_string(), reason.clone())).await;
+    state.push_event(UiEvent::IncidentCaptured { id: incident.id.clone(), reason }).await;
+    Some(incident)
+}
+
+/// With --strict-book, validate the book. The first time a symbol's book
+/// turns invalid (cleared once it validates again), returns the metadata
+/// for the incident, which `record_book_violation` records once the caller
+/// has dropped its guards.
+fn check_book_invariants(state: &AppState, symbol: &str, book: &Orderbook) -> Option<serde_json::Value> {
+    if !state.strict_book {
+        return None;
+    }
+    
+    match book.validate() {
+        Ok(()) => {
+            state.invalid_books.remove(symbol);
+            None
+        }
+        Err(e) => {
+            if !state.invalid_books.insert(symbol.to_string()) {
+                return None;
+            }
+            error!("Book invariant violated for {}: {}", symbol, e);
+            Some(serde_json::json!({
+                "symbol": symbol,
+                "error": e.to_string(),
+                "update_seq": book.update_seq(),
+            }))
+        }
+    }
+}
+
+/// Record the incident `check_book_invariants` returned metadata for, if any
+async fn record_book_violation(incident_manager: &Arc<IncidentManager>, symbol: &str, violation: Option<serde_json::Value>) {
+    if let Some(metadata) = violation {
+        incident_manager
+            .record_incident(IncidentReason::BookInvariant, Some(symbol.to_string()), metadata)
+            .await;
+    }
+}
+
+/// Track crossed/locked state on the symbol's health.
+/// Returns true when the book has just become crossed (bumps `book_crossed_total`).
+fn update_crossed_state(state: &AppState, symbol: &str, book: &Orderbook) -> bool {
+    let mut health = state.health.entry(symbol.to_string()).or_insert_with(|| {
+        blackbox_core::health::SymbolHealth::new(symbol.to_string())
+    });
+    let newly_crossed = health.set_book_crossed(book.is_crossed());
+    if newly_crossed {
+        metrics::record_book_crossed(symbol);
+        warn!(
+            "Book crossed for {}: best bid {:?} >= best ask {:?}",
+            symbol,
+            book.best_bid().map(|(p, _)| p),
+            book.best_ask().map(|(p, _)| p)
+        );
+    }
+    newly_crossed
+}
+
+/// Write `incident`'s bundle from the current state: the config and overall
+/// health, plus `symbol`'s instrument, book, diff since its last verified
+/// checksum, integrity proof and buffered frames; without a symbol, the
+/// frames of `last_frames`. Automatic captures,
+/// `POST /export-bug` and the TUI's export all go through here.
+async fn export_incident_for_symbol(
+    state: &AppState,
+    incident_manager: &IncidentManager,
+    incident: &blackbox_core::incident::Incident,
+    symbol: Option<&str>,
+) -> anyhow::Result<PathBuf> {
+    let mut contents = BundleContents {
+        config: RuntimeConfig::collect(state, incident_manager).await.to_value(),
+        health: serde_json::to_value(state.overall_health())?,
+        ..Default::default()
+    };
+    if let Some(symbol) = symbol {
+        contents.instrument = state.instruments.get(symbol).map(|e| e.value().clone());
+        if let Some(book) = state.orderbooks.get(symbol) {
+            contents.book_top = Some(serde_json::json!({
+                "best_bid": book.best_bid().map(|(p, q)| (p.to_string(), q.to_string())),
+                "best_ask": book.best_ask().map(|(p, q)| (p.to_string(), q.to_string())),
+            }));
+            contents.book_full = Some(book.to_snapshot());
+            contents.book_diff = state.last_verified_books.get(symbol)
+                .map(|verified| verified.diff(&book, crate::state::BOOK_DIFF_DEPTH));
+        }
+        contents.checksums = state.integrity_proofs.get(symbol).map(|p| serde_json::json!({
+            "expected": p.expected_checksum,
+            "computed": p.computed_checksum,
+            "preview": p.checksum_preview,
+            "length": p.checksum_len,
+            "latency_ms": p.verify_latency_ms,
+        }));
+        let buffer = state.per_symbol_frames.get(symbol).map(|b| b.value().clone());
+        if let Some(buffer) = buffer {
+            contents.frames = buffer.read().await.iter().cloned().collect();
+        }
+    } else {
+        // No symbol to narrow it down: every channel's recent frames
+        contents.frames = state.last_frames.read().await.iter().cloned().collect();
+    }
+    incident_manager.export_incident_bundle(incident, contents).await
+}
+
+/// What `replay` takes from the command line: the recording, how it's
+/// played and what's done once it ends
+struct ReplayOptions {
+    input: PathBuf,
+    speed: f64,
+    http_addr: String,
+    faults: Vec<FaultRule>,
+    price_increment: Option<rust_decimal::Decimal>,
+    force: bool,
+    from: Option<chrono::DateTime<chrono::Utc>>,
+    to: Option<chrono::DateTime<chrono::Utc>>,
+    loop_playback: bool,
+    exit_on_complete: bool,
+}
+
+async fn replay_recording(options: ReplayOptions, http_options: HttpOptions) -> anyhow::Result<()> {
+    let ReplayOptions {
+        input,
+        speed,
+        http_addr,
+        faults,
+        price_increment,
+        force,
+        from,
+        to,
+        loop_playback,
+        exit_on_complete,
+    } = options;
+    info!("Replaying recording from {:?} at {}x speed", input, speed);
+    if let (Some(from), Some(to)) = (from, to) {
+        anyhow::ensure!(from <= to, "--from {} is after --to {}", from.to_rfc3339(), to.to_rfc3339());
+    }
+
+    let config = ReplayConfig { mode: replay_mode(speed), faults, loop_playback };
+    let mut replayer = Replayer::from_segments(&input, config)?;
+    match replayer.metadata() {
+        Some(meta) => {
+            println!("Recording: {}", input.display());
+            println!("  schema version: {}", meta.version);
+            println!("  symbols:        {}", meta.symbols.join(","));
+            println!("  depth:          {}", meta.depth.map_or("-".to_string(), |d| d.to_string()));
+            println!("  started at:     {}", meta.started_at.to_rfc3339());
+            println!("  ws url:         {}", meta.ws_url.as_deref().unwrap_or("- (mock)"));
+            println!("  recorded by:    blackbox {}", meta.crate_version);
+            if let Some(channels) = &meta.channels {
+                println!("  channels:       {} (others weren't recorded)", channels.join(","));
+            }
+            if !meta.is_supported() {
+                if !force {
+                    anyhow::bail!(
+                        "Recording schema version {} is newer than this build understands ({}); pass --force to replay anyway",
+                        meta.version,
+                        RECORDING_SCHEMA_VERSION,
+                    );
+                }
+                warn!("Replaying schema version {} recording with --force", meta.version);
+            }
+        }
+        None => println!("Recording: {} (no metadata header)", input.display()),
+    }
+    if let Some(increment) = price_increment {
+        replayer.set_default_price_increment(increment);
+    }
+    if let Some(to) = to {
+        replayer.set_end(to);
+    }
+    if let Some(from) = from {
+        let skipped = replayer.seek_to(from)?;
+        println!("Seeked to {} ({} frames skipped)", from.to_rfc3339(), skipped);
+    }
+    replayer.start();
+
+    // Create shared state
+    let mut state = AppState::new();
+    state.run_mode = RunMode::Replay;
+    state.metrics = metrics::handle();
+    
+    // Create incident manager
+    let incidents_dir = PathBuf::from("./incidents");
+    let incident_manager = Arc::new(IncidentManager::new(incidents_dir)?);
+
+    // Shared with the /replay endpoints
+    *state.replayer.lock().unwrap() = Some(replayer);
+
+    tokio::spawn(msg_rate::sample_forever(state.clone()));
+    let processor_handle = tokio::spawn(replay_into_state(state.clone(), incident_manager.clone()));
+
+    // Start HTTP server
+    let app = http_options.apply(router(state.clone(), incident_manager.clone())
+        .route("/", get(|| async { Html(static_ui::UI_HTML) })))?;
+    
+    let serve_addr = http_addr.clone();
+    let mut server_handle = tokio::spawn(async move {
+        let listener = tokio::net::TcpListener::bind(&http_addr).await.unwrap();
+        info!("HTTP server listening on http://{}", http_addr);
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    tokio::select! {
+        _ = processor_handle => {
+            if exit_on_complete {
+                info!("Replay completed");
+                println!("Final health:");
+                println!("{}", serde_json::to_string_pretty(&state.overall_health())?);
+            } else {
+                // The books stay up for inspection
+                info!("Replay completed; still serving http://{} (Ctrl-C to exit)", serve_addr);
+                let _ = server_handle.await;
+            }
+        }
+        _ = &mut server_handle => {}
+    }
+
+    Ok(())
+}
+
+/// Pacing for a `--speed` multiplier: 1 is realtime, 0 as fast as possible
+fn replay_mode(speed: f64) -> ReplayMode {
+    if speed == 1.0 {
+        ReplayMode::Realtime
+    } else if speed > 0.0 {
+        ReplayMode::Speed(speed)
+    } else {
+        ReplayMode::AsFast
+    }
+}
+
+/// Play `state.replayer` through the same pipeline as live data, into
+/// `state`'s books, health, integrity proofs and metrics. Returns once the
+/// replay is done and every frame was processed.
+async fn replay_into_state(state: AppState, incident_manager: Arc<IncidentManager>) {
+    use futures_util::StreamExt;
+    
+    let (ws_tx, mut ws_rx) = mpsc::unbounded_channel();
+    let feeder = async {
+        let _ = ws_tx.send(WsEvent::Connected);
+        let mut frames = std::pin::pin!(frame_stream(state.replayer.clone()));
+        while let Some(next) = frames.next().await {
+            let events = state.with_replayer(|r| r.drain_events().collect()).unwrap_or_default();
+            push_replay_events(&state, events).await;
+            match next {
+                Ok((_, frame)) => {
+                    if let Some(progress) = state.with_replayer(|r| r.progress()) {
+                        metrics::record_replay_frame(progress);
+                    }
+                    for event in replay_frame_events(&state, frame, &[]) {
+                        let _ = ws_tx.send(event);
+                    }
+                }
+                Err(e) => {
+                    error!("Replay stopped: {:#}", e);
+                    break;
+                }
+            }
+        }
+        if let Some(progress) = state.with_replayer(|r| r.progress()) {
+            metrics::update_replay_progress(progress);
+        }
+        let missing = state.with_replayer(|r| r.symbols_without_snapshot()).unwrap_or_default();
+        if !missing.is_empty() {
+            warn!("No book snapshot in the replayed range for {}; their updates were skipped", missing.join(","));
+        }
+        // The processor drains what's left, then stops
+        drop(ws_tx);
+    };
+    tokio::join!(feeder, process_ws_events_with_logging(&state, &incident_manager, &mut ws_rx));
+}
+
+async fn run_tui_mode(options: ClientOptions, tui: TuiOptions) -> anyhow::Result<()> {
+    let ClientOptions {
+        symbols,
+        depth,
+        depths,
+        ping_interval,
+        ws_url,
+        record: record_path,
+        record_options,
+        record_per_symbol,
+        record_decoded,
+        record_guard,
+        incidents_guard,
+        level_meta,
+        strict_book,
+        tombstones,
+        checksum_levels,
+        last_frames_capacity,
+        symbol_frames_capacity,
+        resync_after,
+        checksum_dump,
+        log_path,
+        events_file,
+        resume_state,
+        ..
+    } = options;
+    let TuiOptions {
+        replay: replay_path,
+        speed,
+        fault,
+        once_at,
+        fault_checksum_xor,
+        fault_delay_ms,
+        fault_mutate_delta,
+        loop_playback,
+        mock,
+    } = tui;
+    let fault_rule = build_fault_rule_from_str(&fault, once_at, fault_checksum_xor, fault_delay_ms, fault_mutate_delta)?;
+    info!("Starting Kraken Blackbox TUI - Integrity Tab");
+    info!("Symbols: {:?}, Depth: {}, Mock: {}", symbols, depth, mock);
+
+    let mode = if replay_path.is_some() {
+        "REPLAY"
+    } else if mock {
+        "MOCK"
+    } else {
+        "LIVE"
+    };
+
+    // Build fault status string
+    let fault_status = match once_at {
+        Some(index) if fault != "none" => format!("{}@{}", fault, index),
+        _ => "OFF".to_string(),
     };
 
     // Create shared state
-    let state = AppState::new();
+    let mut state = AppState::new();
+    state.run_mode = if replay_path.is_some() {
+        RunMode::Replay
+    } else if mock {
+        RunMode::Mock
+    } else {
+        state.ws_url = Some(ws_url.clone());
+        state.ping_interval = Some(ping_interval);
+        RunMode::Live
+    };
+    state.metrics = metrics::handle();
+    state.track_level_meta = level_meta;
+    state.strict_book = strict_book;
+    state.tombstones = tombstones;
+    state.checksum_levels = checksum_levels;
+    state.last_frames_capacity = last_frames_capacity;
+    state.symbol_frames_capacity = symbol_frames_capacity;
+    state.resync_after_fails = resync_after;
+    state.record_decoded = record_decoded;
+    state.record_disk_guard = record_guard;
+    state.recorder_options = RecorderOptions {
+        metadata: Some(RecordingMetadata {
+            ws_url: (!mock).then(|| ws_url.clone()),
+            ..RecordingMetadata::new(symbols.clone(), depth)
+        }),
+        ..record_options
+    };
+    state.checksum_dumper = checksum_dump
+        .map(|(dir, interval)| Arc::new(crate::integrity::ChecksumDumper::new(dir, interval)));
     
     // Store requested symbols and set depth for all symbols
     state.set_requested_symbols(symbols.clone()).await;
+    // A replay's events aren't this venue's history
+    if let Some(path) = events_file.filter(|_| replay_path.is_none()) {
+        event_store::persist(&state, path).await?;
+    }
     
     for symbol in &symbols {
-        state.set_depth(symbol, depth);
+        state.set_depth(symbol, depths.get(symbol).copied().unwrap_or(depth));
         // Initialize health entry for this symbol (so it shows up in UI immediately)
         if !state.health.contains_key(symbol) {
             state.health.insert(symbol.clone(), blackbox_core::health::SymbolHealth::new(symbol.clone()));
         }
     }
+    let live_or_mock = replay_path.is_none();
+    if resume_state && live_or_mock {
+        resume_from_snapshot(&mut state).await;
+    }
 
     // Create incident manager
     let incidents_dir = PathBuf::from("./incidents");
-    let incident_manager = Arc::new(IncidentManager::new(incidents_dir)?);
+    let incident_manager = Arc::new(IncidentManager::new(incidents_dir)?.with_disk_guard(incidents_guard));
 
     // Create recorder if needed (for both mock and live mode)
     // Store it in AppState so mock mode can access it
-    use crate::state::UiEvent;
     if let Some(path) = record_path.clone() {
-        match Recorder::new(path.clone()) {
-            Ok(rec) => {
-                let mut recorder_guard = state.recorder.write().await;
-                *recorder_guard = Some(rec);
-                state.set_recording_enabled(true).await;
-                state.set_recording_path(Some(path.to_string_lossy().to_string())).await;
-                state.push_event(UiEvent::RecordStarted { path: path.to_string_lossy().to_string() }).await;
-            }
+        match RecordSink::open(path, state.recorder_options.clone(), record_per_symbol) {
+            Ok(rec) => state.start_recording(rec).await,
             Err(e) => {
                 warn!("Failed to create recorder: {}", e);
             }
         }
     }
     
+    tokio::spawn(msg_rate::sample_forever(state.clone()));
+
+    // Live-mode tasks, drained when the TUI quits
+    let mut live_handles = None;
     if mock {
         // Mock mode: spawn fake data generator
         let state_clone = state.clone();
@@ -638,15 +1799,7 @@ async fn run_tui_mode(
         });
     } else if let Some(replay_file) = replay_path {
         // Replay mode
-        let fault_rule = build_fault_rule_from_str(&fault, once_at);
-        let mode = if speed == 1.0 {
-            ReplayMode::Realtime
-        } else if speed > 0.0 {
-            ReplayMode::Speed(speed)
-        } else {
-            ReplayMode::AsFast
-        };
-        let config = ReplayConfig { mode, fault: fault_rule };
+        let config = ReplayConfig { mode: replay_mode(speed), faults: vec![fault_rule], loop_playback };
         
         let state_clone = state.clone();
         let symbols_clone = symbols.clone();
@@ -658,41 +1811,47 @@ async fn run_tui_mode(
         
         // Note: We've already initialized symbols above, so they should appear in the UI
     } else {
-        // Live mode
-        let ping_interval = parse_duration(&ping_interval_str)
-            .context("Invalid ping interval format")?;
-        
+        // Live mode; the processor resyncs books through the command channel
         let (ws_tx, mut ws_rx) = mpsc::unbounded_channel();
-        let client = WsClient::new(symbols.clone(), depth, ping_interval, ws_tx);
+        let (command_tx, command_rx) = mpsc::unbounded_channel();
+        state.ws_commands = Some(command_tx);
+        let client = WsClient::new(symbols.clone(), depth, ping_interval, ws_tx)
+            .with_depths(depths.clone())
+            .with_url(ws_url.clone())
+            .with_commands(command_rx);
         let client_handle = tokio::spawn(async move {
             if let Err(e) = client.run().await {
                 error!("WebSocket client error: {}", e);
             }
         });
         
-        // Store recorder in AppState if provided (for live mode)
-        // (Already done above for both mock and live mode)
-        
         let state_clone = state.clone();
         let incident_manager_clone = incident_manager.clone();
         let processor_handle = tokio::spawn(async move {
-            process_ws_events_with_logging(&state_clone, &incident_manager_clone, &mut ws_rx, None).await;
-        });
-        
-        tokio::spawn(async move {
-            tokio::select! {
-                _ = client_handle => {}
-                _ = processor_handle => {}
-            }
+            process_ws_events_with_logging(&state_clone, &incident_manager_clone, &mut ws_rx).await;
         });
+        live_handles = Some((client_handle, processor_handle));
     }
 
     // Create TUI app
     let recording_path_str = record_path.as_ref().and_then(|p| p.to_str().map(|s| s.to_string()));
-    let tui_app = tui::TuiApp::new(state, recording_path_str);
+    let recording_state = state.clone();
+    let mut tui_app = tui::TuiApp::new(state, recording_path_str);
+    tui_app.log_path = log_path.map(|p| p.display().to_string());
     
     // Run TUI (blocks until quit)
-    tui::run_tui_with_manager(tui_app, mode.to_string(), fault_status, Some(incident_manager)).await?;
+    let result = tui::run_tui_with_manager(tui_app, mode.to_string(), fault_status, Some(incident_manager)).await;
+    // Don't leave queued frames behind
+    match live_handles {
+        Some((client_handle, processor_handle)) => {
+            drain_and_close(&recording_state, client_handle, processor_handle).await
+        }
+        None => close_recording(&recording_state).await,
+    }
+    if live_or_mock {
+        save_snapshot(&recording_state).await;
+    }
+    result?;
 
     Ok(())
 }
@@ -707,10 +1866,10 @@ async fn mock_data_generator(state: AppState, symbols: Vec<String>) {
     let base_prices: std::collections::HashMap<String, Decimal> = symbols.iter()
         .map(|s| {
             let price = match s.as_str() {
-                "BTC/USD" => Decimal::from(95000_00) / Decimal::from(100),
-                "ETH/USD" => Decimal::from(3000_00) / Decimal::from(100),
-                "SOL/USD" => Decimal::from(200_00) / Decimal::from(100),
-                _ => Decimal::from(100_00) / Decimal::from(100),
+                "BTC/USD" => Decimal::from(95_000),
+                "ETH/USD" => Decimal::from(3_000),
+                "SOL/USD" => Decimal::from(200),
+                _ => Decimal::from(100),
             };
             (s.clone(), price)
         })
@@ -722,7 +1881,7 @@ async fn mock_data_generator(state: AppState, symbols: Vec<String>) {
         
         // Create initial orderbook with fake data
         let mut book = Orderbook::new();
-        let base_price = base_prices.get(symbol).copied().unwrap_or(Decimal::from(100_00) / Decimal::from(100));
+        let base_price = base_prices.get(symbol).copied().unwrap_or(Decimal::from(100));
         
         // Generate initial bids and asks
         let mut bids = Vec::new();
@@ -737,8 +1896,9 @@ async fn mock_data_generator(state: AppState, symbols: Vec<String>) {
             asks.push((ask_price, ask_qty));
         }
         
+        let depth = state.subscribed_depth(symbol, bids.len());
         book.apply_snapshot(bids, asks);
-        state.orderbooks.insert(symbol.clone(), book);
+        state.orderbooks.insert(symbol.clone(), StoredBook::new(book, depth));
         
         // Create instrument info
         let instrument = InstrumentInfo {
@@ -748,10 +1908,12 @@ async fn mock_data_generator(state: AppState, symbols: Vec<String>) {
             price_increment: Decimal::from(1) / Decimal::from(100), // 0.01
             qty_increment: Decimal::from(1) / Decimal::from(1_000_000), // 0.000001
             status: "online".to_string(),
+            ..Default::default()
         };
-        state.instruments.insert(symbol.clone(), instrument);
+        state.insert_instrument(symbol.clone(), instrument);
     }
     
+    state.set_connected(true);
     state.push_event(UiEvent::Connected).await;
     state.push_event(UiEvent::SubscribedInstrument).await;
     state.push_event(UiEvent::SubscribedBook).await;
@@ -777,12 +1939,7 @@ async fn mock_data_generator(state: AppState, symbols: Vec<String>) {
             let frame_str = serde_json::to_string(&fake_frame).unwrap_or_default();
             
             // Record frame if recording is enabled
-            if state.is_recording_enabled().await {
-                let mut recorder_guard = state.recorder.write().await;
-                if let Some(ref mut rec) = *recorder_guard {
-                    let _ = rec.record_frame(&frame_str, None);
-                }
-            }
+            state.record_frame(&frame_str).await;
             if let Some(mut health) = state.health.get_mut(symbol) {
                 health.connected = true;
                 health.record_message();
@@ -790,7 +1947,7 @@ async fn mock_data_generator(state: AppState, symbols: Vec<String>) {
                 // Update orderbook with small price movements
                 if let Some(mut book_entry) = state.orderbooks.get_mut(symbol) {
                     let book = book_entry.value_mut();
-                    let base_price = base_prices.get(symbol).copied().unwrap_or(Decimal::from(100_00) / Decimal::from(100));
+                    let base_price = base_prices.get(symbol).copied().unwrap_or(Decimal::from(100));
                     
                     // Add some randomness to prices (simulate market movement)
                     let wiggle_val = (counter % 20) as i64 - 10;
@@ -804,7 +1961,7 @@ async fn mock_data_generator(state: AppState, symbols: Vec<String>) {
                     let ask_price = current_base + Decimal::from(level_to_update + 1) * Decimal::from(10_00) / Decimal::from(100);
                     
                     // Sometimes remove a level, sometimes add/update
-                    if counter % 50 == 0 && level_to_update < 5 {
+                    if counter.is_multiple_of(50) && level_to_update < 5 {
                         // Remove a level occasionally
                         book.apply_updates(
                             vec![(bid_price, Decimal::ZERO)],
@@ -823,7 +1980,7 @@ async fn mock_data_generator(state: AppState, symbols: Vec<String>) {
                     book.truncate(10);
                 }
                 
-                if counter % 1000 == 0 {
+                if counter.is_multiple_of(1000) {
                     // Occasional checksum failure for demo
                     health.record_checksum_fail();
                     state.push_event(UiEvent::ChecksumMismatch { symbol: symbol.clone() }).await;
@@ -831,23 +1988,94 @@ async fn mock_data_generator(state: AppState, symbols: Vec<String>) {
                     health.record_checksum_ok();
                     state.push_event(UiEvent::ChecksumOk { symbol: symbol.clone() }).await;
                 }
-                health.update_msg_rate(10.0);
             }
         }
     }
 }
 
-fn build_fault_rule_from_str(fault: &str, once_at: Option<usize>) -> FaultRule {
-    if fault == "none" || once_at.is_none() {
-        return FaultRule::None;
-    }
+/// The rule for `tui --fault`, which only applies with `--once-at`; an error
+/// for a fault the replayer doesn't have
+fn build_fault_rule_from_str(
+    fault: &str,
+    once_at: Option<usize>,
+    checksum_xor: u32,
+    delay_ms: u64,
+    mutate_delta: i32,
+) -> anyhow::Result<FaultRule> {
+    let fault = match fault {
+        "none" => return Ok(FaultRule::None),
+        "drop" => FaultType::Drop,
+        "reorder" => FaultType::Reorder,
+        "mutate_qty" => FaultType::MutateQty { delta_ticks: mutate_delta },
+        "mutate_price" => FaultType::MutatePrice { delta_ticks: mutate_delta },
+        "duplicate" => FaultType::Duplicate,
+        "corrupt_checksum" => FaultType::CorruptChecksum { xor: checksum_xor },
+        "delay" => FaultType::Delay { ms: delay_ms },
+        _ => anyhow::bail!(
+            "Unknown fault {:?}; expected none, drop, reorder, mutate_qty, mutate_price, duplicate, corrupt_checksum or delay",
+            fault
+        ),
+    };
+    Ok(once_at.map_or(FaultRule::None, |index| FaultRule::OnceAt { index, fault }))
+}
+
+/// Longest sleep between replayer polls, so a gap in the recording doesn't
+/// hold up pause, seek or shutdown, and how often a paused replay is polled
+const MAX_REPLAY_WAIT: Duration = Duration::from_millis(50);
+
+/// How long to sleep for `Replayer::next_frame_delay`
+fn replay_wait(delay: Option<Duration>) -> Duration {
+    delay.map_or(MAX_REPLAY_WAIT, |delay| delay.min(MAX_REPLAY_WAIT))
+}
+
+/// The events the live client would have sent for a replayed frame: the
+/// frame itself, then its instruments or books. `requested_symbols`, if any,
+/// filters out the others.
+fn replay_frame_events(state: &AppState, frame: String, requested_symbols: &[String]) -> Vec<WsEvent> {
+    use blackbox_ws::parser::{parse_frame, WsFrame};
     
-    let index = once_at.unwrap();
-    match fault {
-        "drop" => FaultRule::OnceAt { index, fault: FaultType::Drop },
-        "reorder" => FaultRule::OnceAt { index, fault: FaultType::Reorder },
-        "mutate_qty" => FaultRule::OnceAt { index, fault: FaultType::MutateQty { delta_ticks: 1 } },
-        _ => FaultRule::None,
+    let parsed = parse_frame(&frame);
+    let mut events = vec![WsEvent::Frame(frame)];
+    let requested = |symbol: &String| requested_symbols.is_empty() || requested_symbols.contains(symbol);
+    match parsed {
+        Ok(WsFrame::Instrument(msg)) if msg.msg_type == "snapshot" => {
+            let mut instruments = std::collections::HashMap::new();
+            for pair in msg.data.pairs.into_iter().filter(|pair| requested(&pair.symbol)) {
+                if let Ok(info) = blackbox_core::types::InstrumentInfo::from_pair(pair) {
+                    // Health already initialized from CLI args, but ensure it exists
+                    if !state.health.contains_key(&info.symbol) {
+                        state.health.insert(info.symbol.clone(), blackbox_core::health::SymbolHealth::new(info.symbol.clone()));
+                    }
+                    instruments.insert(info.symbol.clone(), info);
+                }
+            }
+            if !instruments.is_empty() {
+                info!("Replay: Sending InstrumentSnapshot with {} instruments (filtered from recording)", instruments.len());
+                events.push(WsEvent::InstrumentSnapshot(instruments));
+            }
+        }
+        Ok(WsFrame::Book(msg)) => {
+            for data in msg.data.into_iter().filter(|data| requested(&data.symbol)) {
+                let event = blackbox_ws::client::book_event(&msg.msg_type, data);
+                if let WsEvent::BookSnapshot { symbol, .. } = &event {
+                    info!("Replay: Sending BookSnapshot for {}", symbol);
+                }
+                events.push(event);
+            }
+        }
+        Ok(_) => {}
+        Err(e) => warn!("Failed to parse replayed frame: {}", e),
+    }
+    events
+}
+
+/// Show the replayer's faults in the event log like the injector's, and count them
+async fn push_replay_events(state: &AppState, events: Vec<ReplayEvent>) {
+    for event in events {
+        let ReplayEvent::FaultApplied { rule, fault, symbol, .. } = event;
+        metrics::record_replay_fault(fault.name());
+        let fault_type = format!("{:?} (rule #{})", fault, rule);
+        state.push_event(crate::state::UiEvent::FaultInjected { fault_type, symbol }).await;
     }
 }
 
@@ -859,7 +2087,6 @@ async fn replay_recording_internal(
 ) -> anyhow::Result<()> {
     use crate::state::UiEvent;
     use blackbox_core::replayer::Replayer;
-    use blackbox_ws::parser::parse_frame;
     use blackbox_ws::client::WsEvent;
     use tokio::sync::mpsc;
     
@@ -868,7 +2095,14 @@ async fn replay_recording_internal(
     state.push_event(UiEvent::Connected).await;
     
     // Create replayer
-    let mut replayer = Replayer::new(input.clone(), config.clone())?;
+    let mut replayer = Replayer::from_segments(&input, config.clone())?;
+    if let Some(meta) = replayer.metadata() {
+        info!("Recording metadata: {:?}", meta);
+        if !meta.is_supported() {
+            warn!("Recording schema version {} is newer than this build understands ({})", meta.version, RECORDING_SCHEMA_VERSION);
+            state.push_event(UiEvent::Error(format!("Recording schema v{} is newer than supported", meta.version))).await;
+        }
+    }
     info!("Replayer created, starting replay");
     replayer.start();
     
@@ -880,159 +2114,42 @@ async fn replay_recording_internal(
     let incident_manager = Arc::new(IncidentManager::new(std::path::PathBuf::from("./incidents"))?);
     let incident_manager_clone = incident_manager.clone();
     let processor_handle = tokio::spawn(async move {
-        process_ws_events_with_logging(&state_clone, &incident_manager_clone, &mut ws_rx, None).await;
+        process_ws_events_with_logging(&state_clone, &incident_manager_clone, &mut ws_rx).await;
     });
     
     // Send Connected event
     let _ = ws_tx.send(WsEvent::Connected);
     
     let mut frame_num = 0;
-    let mut consecutive_none = 0;
     loop {
         // Get next frame from replayer
-        match replayer.next_frame() {
+        let next = replayer.next_frame();
+        push_replay_events(&state, replayer.drain_events().collect()).await;
+        match next {
             Some(frame_data) => {
-                consecutive_none = 0;
+                metrics::record_replay_frame(replayer.progress());
                 frame_num += 1;
                 if frame_num % 50 == 0 || frame_num <= 5 {
                     info!("Replay progress: {} frames processed", frame_num);
                 }
                 
-                // Send Frame event
-                let _ = ws_tx.send(WsEvent::Frame(frame_data.clone()));
-                
-                // Parse frame and convert to WsEvent (same logic as WsClient)
-                if let Ok(parsed) = parse_frame(&frame_data) {
-                    match parsed {
-                blackbox_ws::parser::WsFrame::Instrument(msg) => {
-                    if msg.msg_type == "snapshot" {
-                        use blackbox_core::precision::parse_decimal;
-                        use std::collections::HashMap;
-                        let mut instruments = HashMap::new();
-                        // Filter to only include requested symbols
-                        for pair in msg.data.pairs {
-                            // Only process if this symbol was requested via CLI args
-                            if !requested_symbols.is_empty() && !requested_symbols.contains(&pair.symbol) {
-                                continue;
-                            }
-                            match (parse_decimal(&pair.price_increment), parse_decimal(&pair.qty_increment)) {
-                                (Ok(price_inc), Ok(qty_inc)) => {
-                                    let info = blackbox_core::types::InstrumentInfo {
-                                        symbol: pair.symbol.clone(),
-                                        price_precision: pair.price_precision,
-                                        qty_precision: pair.qty_precision,
-                                        price_increment: price_inc,
-                                        qty_increment: qty_inc,
-                                        status: pair.status,
-                                    };
-                                    instruments.insert(pair.symbol.clone(), info);
-                                    // Health already initialized from CLI args, but ensure it exists
-                                    if !state.health.contains_key(&pair.symbol) {
-                                        state.health.insert(pair.symbol.clone(), blackbox_core::health::SymbolHealth::new(pair.symbol.clone()));
-                                    }
-                                }
-                                _ => continue,
-                            }
-                        }
-                        if !instruments.is_empty() {
-                            info!("Replay: Sending InstrumentSnapshot with {} instruments (filtered from recording)", instruments.len());
-                            let _ = ws_tx.send(WsEvent::InstrumentSnapshot(instruments));
-                        }
-                    }
-                }
-                blackbox_ws::parser::WsFrame::Book(msg) => {
-                    for data in msg.data {
-                        // Filter: only process book events for requested symbols
-                        if !requested_symbols.is_empty() && !requested_symbols.contains(&data.symbol) {
-                            continue;
-                        }
-                        
-                        use blackbox_core::precision::parse_decimal;
-                        
-                        let mut bids = Vec::new();
-                        let mut asks = Vec::new();
-                        
-                        if let Some(bid_levels) = data.bids {
-                            for level in bid_levels {
-                                let price_str = match &level.price {
-                                    serde_json::Value::Number(n) => n.to_string(),
-                                    serde_json::Value::String(s) => s.clone(),
-                                    _ => continue,
-                                };
-                                let qty_str = match &level.qty {
-                                    serde_json::Value::Number(n) => n.to_string(),
-                                    serde_json::Value::String(s) => s.clone(),
-                                    _ => continue,
-                                };
-                                match (parse_decimal(&price_str), parse_decimal(&qty_str)) {
-                                    (Ok(price), Ok(qty)) => bids.push((price, qty)),
-                                    _ => continue,
-                                }
-                            }
-                        }
-                        
-                        if let Some(ask_levels) = data.asks {
-                            for level in ask_levels {
-                                let price_str = match &level.price {
-                                    serde_json::Value::Number(n) => n.to_string(),
-                                    serde_json::Value::String(s) => s.clone(),
-                                    _ => continue,
-                                };
-                                let qty_str = match &level.qty {
-                                    serde_json::Value::Number(n) => n.to_string(),
-                                    serde_json::Value::String(s) => s.clone(),
-                                    _ => continue,
-                                };
-                                match (parse_decimal(&price_str), parse_decimal(&qty_str)) {
-                                    (Ok(price), Ok(qty)) => asks.push((price, qty)),
-                                    _ => continue,
-                                }
-                            }
-                        }
-                        
-                        if msg.msg_type == "snapshot" {
-                            info!("Replay: Sending BookSnapshot for {}", data.symbol);
-                            let _ = ws_tx.send(WsEvent::BookSnapshot {
-                                symbol: data.symbol.clone(),
-                                bids,
-                                asks,
-                                checksum: data.checksum,
-                            });
-                        } else {
-                            if frame_num <= 5 {
-                                info!("Replay: Sending BookUpdate for {}", data.symbol);
-                            }
-                            let _ = ws_tx.send(WsEvent::BookUpdate {
-                                symbol: data.symbol,
-                                bids,
-                                asks,
-                                checksum: data.checksum,
-                                timestamp: data.timestamp,
-                            });
-                        }
-                    }
-                    }
-                    _ => {}
-                }
+                for event in replay_frame_events(&state, frame_data, &requested_symbols) {
+                    let _ = ws_tx.send(event);
                 }
                 
                 // Small delay for UI responsiveness  
                 tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
             }
-            None => {
-                consecutive_none += 1;
-                // If we get None multiple times in a row, we're probably done
-                // (either finished or waiting for timing - in AsFast mode we should never wait)
-                if consecutive_none > 100 {
-                    info!("Replay completed after {} frames (no more frames available)", frame_num);
-                    // Small delay to ensure all events are processed
-                    tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
-                    state.push_event(UiEvent::RecordStopped).await;
-                    break;
-                }
-                // Small delay when waiting (for Realtime/Speed modes)
-                tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+            // Paused playback isn't finished playback
+            None if replayer.is_done() => {
+                info!("Replay completed after {} frames", frame_num);
+                metrics::update_replay_progress(replayer.progress());
+                // Small delay to ensure all events are processed
+                tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
+                state.push_event(UiEvent::RecordStopped).await;
+                break;
             }
+            None => tokio::time::sleep(replay_wait(replayer.next_frame_delay())).await,
         }
     }
     
@@ -1048,59 +2165,39 @@ async fn process_ws_events_with_logging(
     state: &AppState,
     incident_manager: &Arc<IncidentManager>,
     ws_rx: &mut mpsc::UnboundedReceiver<WsEvent>,
-    mut recorder: Option<&mut Recorder>,
 ) {
     use crate::state::UiEvent;
-    use crate::integrity::{IntegrityProof, update_integrity_proof};
-    use crate::integrity::incident::IncidentMeta;
     
     while let Some(event) = ws_rx.recv().await {
         match event {
             WsEvent::Connected => {
                 info!("WebSocket connected");
+                state.set_connected(true);
+                metrics::update_ws_connected(true);
                 state.push_event(UiEvent::Connected).await;
             }
-            WsEvent::Disconnected => {
-                warn!("WebSocket disconnected");
+            WsEvent::Disconnected { cause } => {
+                warn!("WebSocket disconnected ({})", cause.as_str());
+                state.set_connected(false);
+                metrics::update_ws_connected(false);
+                state.mark_books_stale();
                 state.push_event(UiEvent::Disconnected).await;
             }
+            WsEvent::Frame(raw_frame) if raw_frame == LOOP_MARKER => {
+                info!("Replay starting over");
+                state.clear_books();
+            }
             WsEvent::Frame(raw_frame) => {
-                // Check state-based recorder first (for TUI toggle)
-                if state.is_recording_enabled().await {
-                    let mut rec_guard = state.recorder.write().await;
-                    if let Some(ref mut r) = *rec_guard {
-                        let _ = r.record_frame(&raw_frame, None);
-                    }
-                }
-                // Also use passed recorder if provided (for CLI --record)
-                if let Some(ref mut rec) = recorder {
-                    let _ = rec.record_frame(&raw_frame, None);
-                }
-                
-                let mut frames = state.last_frames.write().await;
-                frames.push((chrono::Utc::now(), raw_frame.clone()));
-                if frames.len() > 1000 {
-                    frames.remove(0);
-                }
-                
-                // Also store in per-symbol buffers if we can extract symbol from frame
-                // This is best-effort - we'll parse JSON to find symbol
-                if let Ok(json) = serde_json::from_str::<serde_json::Value>(&raw_frame) {
-                    if let Some(symbol) = json.get("data").and_then(|d| d.get("symbol")).and_then(|s| s.as_str()) {
-                        let frame_buffer = state.get_or_create_frame_buffer(symbol);
-                        let mut buf = frame_buffer.write().await;
-                        buf.push_back(raw_frame.clone());
-                        while buf.len() > 2000 {
-                            buf.pop_front();
-                        }
-                    }
-                }
+                // Recorded when --record or the TUI toggle started a recording
+                state.record_frame(&raw_frame).await;
+                state.buffer_frame(&raw_frame).await;
+                state.push_last_frame(&raw_frame).await;
             }
             WsEvent::InstrumentSnapshot(instruments) => {
                 info!("Received instrument snapshot with {} pairs", instruments.len());
                 state.push_event(UiEvent::SubscribedInstrument).await;
                 for (symbol, info) in instruments {
-                    state.instruments.insert(symbol.clone(), info);
+                    state.insert_instrument(symbol, info);
                 }
             }
             WsEvent::BookSnapshot {
@@ -1110,189 +2207,86 @@ async fn process_ws_events_with_logging(
                 checksum,
             } => {
                 state.push_event(UiEvent::SubscribedBook).await;
-                let mut book = Orderbook::new();
+                finish_resync(state, &symbol).await;
+                let depth = state.subscribed_depth(&symbol, bids.len().max(asks.len()));
+                let mut book = state.new_orderbook(depth);
                 book.apply_snapshot(bids, asks);
-                let depth = state.get_depth(&symbol) as usize;
                 book.truncate(depth);
+                record_book_violation(incident_manager, &symbol, check_book_invariants(state, &symbol, &book)).await;
                 
-                if let Some(expected_checksum) = checksum {
-                    if let Some(instrument) = state.instruments.get(&symbol) {
-                        // Update integrity proof
-                        let mut proof = state.integrity_proofs
-                            .entry(symbol.clone())
-                            .or_insert_with(|| IntegrityProof::new());
-                        
-                        let is_valid = update_integrity_proof(
-                            &mut proof,
-                            &book,
-                            expected_checksum,
-                            instrument.price_precision,
-                            instrument.qty_precision,
-                            &symbol,
-                        );
-                        
-                        let mut health = state.health.entry(symbol.clone()).or_insert_with(|| {
-                            blackbox_core::health::SymbolHealth::new(symbol.clone())
-                        });
-                        health.connected = true;
-                        health.record_message();
-                        
-                        if is_valid {
-                            health.record_checksum_ok();
-                            state.push_event(UiEvent::ChecksumOk { symbol: symbol.clone() }).await;
-                        } else {
-                            health.record_checksum_fail();
-                            state.push_event(UiEvent::ChecksumMismatch { symbol: symbol.clone() }).await;
-                            
-                                // Auto-resync: request resubscribe if backoff allows
-                                // Note: Full resubscribe requires WsClient changes (see FEATURE_VERIFICATION.md)
-                                // For now, we just increment the counter and log
-                                if state.can_resync(&symbol) {
-                                    state.record_resync(&symbol);
-                                    health.reconnect_count += 1; // Increment resync count
-                                    state.push_event(UiEvent::ResyncStarted { symbol: symbol.clone() }).await;
-                                    warn!("Auto-resync triggered for {} due to checksum mismatch (resubscribe requires WsClient integration)", symbol);
-                                }
-                            
-                            let incident = incident_manager
-                                .record_incident(
-                                    IncidentReason::ChecksumMismatch,
-                                    Some(symbol.clone()),
-                                    serde_json::json!({"expected_checksum": expected_checksum}),
-                                )
-                                .await;
-                            
-                            // Store frames for this symbol
-                            let frame_buffer = state.get_or_create_frame_buffer(&symbol);
-                            let frames: Vec<String> = frame_buffer.read().await.iter().cloned().collect();
-                            let frames_count = frames.len();
-                            
-                            // Create incident meta
-                            let incident_meta = IncidentMeta::new(
-                                incident.id.clone(),
-                                symbol.clone(),
-                                format!("{:?}", incident.reason),
-                            );
-                            
-                            state.set_last_incident(incident_meta).await;
-                            
-                            state.push_event(UiEvent::IncidentCaptured {
-                                id: incident.id,
-                                reason: format!("{:?}", incident.reason),
-                            }).await;
-                        }
-                    }
+                if update_crossed_state(state, &symbol, &book) {
+                    state.push_event(UiEvent::BookCrossed { symbol: symbol.clone() }).await;
                 }
                 
-                state.orderbooks.insert(symbol.clone(), book);
+                let check = checksum.map_or(BookCheck::Unchecked, |expected| verify_book(state, &symbol, &mut book, expected, true));
+                
+                state.publish_book_change(&symbol, &book);
+                update_book_size_metrics(&symbol, &book);
+                if state.is_requested(&symbol).await {
+                    update_top_of_book_metrics(&symbol, &book);
+                }
+                state.orderbooks.insert(symbol.clone(), StoredBook::new(book, depth));
+                record_book_check(state, incident_manager, &symbol, check).await;
+                maybe_resync(state, &symbol).await;
             }
             WsEvent::BookUpdate {
                 symbol,
-                bids,
+                mut bids,
                 mut asks,
-                checksum,
-                timestamp: _,
+                mut checksum,
+                timestamp,
             } => {
-                // Check for fault injection
-                if let Some((target_symbol, fault_type)) = state.fault_injector.consume() {
-                    if target_symbol == symbol {
-                        match fault_type {
-                            crate::integrity::fault::FaultType::MutateQty => {
-                                // Mutate first ask qty by smallest increment
-                                if let Some(first_ask) = asks.first_mut() {
-                                    if let Ok(mut qty) = first_ask.1.to_string().parse::<rust_decimal::Decimal>() {
-                                        // Add smallest increment
-                                        if let Some(instrument) = state.instruments.get(&symbol) {
-                                            qty = qty + instrument.qty_increment;
-                                            first_ask.1 = qty;
-                                        }
-                                    }
-                                }
-                            }
-                            crate::integrity::fault::FaultType::DropUpdate => {
-                                // Drop this update - return early
-                                continue;
-                            }
-                        }
-                    }
+                let injected = apply_injected_fault(state, &symbol, &mut bids, &mut asks, &mut checksum);
+                if injected == Some(InjectedFault::Drop) {
+                    continue;
                 }
+                // Nothing awaits while the book guard is held: the subscription is
+                // read ahead of it, and the check, any violation and the UI events
+                // are collected under it and acted on once it is dropped
+                let subscribed = state.is_requested(&symbol).await;
+                let mut check = BookCheck::Unchecked;
+                let mut violation = None;
+                let mut events = Vec::new();
                 
                 if let Some(mut book_entry) = state.orderbooks.get_mut(&symbol) {
-                    book_entry.apply_updates(bids.clone(), asks.clone());
-                    let depth = state.get_depth(&symbol) as usize;
+                    let summary = book_entry.apply_updates(bids.clone(), asks.clone());
+                    if injected == Some(InjectedFault::Duplicate) {
+                        // Absolute quantities: the second application must change nothing
+                        book_entry.apply_updates(bids.clone(), asks.clone());
+                    }
+                    record_book_timestamp(state, &mut book_entry, &symbol, timestamp.as_deref());
+                    let depth = book_entry.subscribed_depth;
                     book_entry.truncate(depth);
+                    violation = check_book_invariants(state, &symbol, &book_entry);
                     
-                    if let Some(expected_checksum) = checksum {
-                        if let Some(instrument) = state.instruments.get(&symbol) {
-                            // Update integrity proof
-                            let mut proof = state.integrity_proofs
-                                .entry(symbol.clone())
-                                .or_insert_with(|| IntegrityProof::new());
-                            
-                            let is_valid = update_integrity_proof(
-                                &mut proof,
-                                &book_entry,
-                                expected_checksum,
-                                instrument.price_precision,
-                                instrument.qty_precision,
-                                &symbol,
-                            );
-                            
-                            let mut health = state.health.entry(symbol.clone()).or_insert_with(|| {
-                                blackbox_core::health::SymbolHealth::new(symbol.clone())
-                            });
-                            health.connected = true;
-                            health.record_message();
-                            
-                            if is_valid {
-                                health.record_checksum_ok();
-                                state.push_event(UiEvent::ChecksumOk { symbol: symbol.clone() }).await;
-                            } else {
-                                health.record_checksum_fail();
-                                state.push_event(UiEvent::ChecksumMismatch { symbol: symbol.clone() }).await;
-                                
-                                // Auto-resync: request resubscribe if backoff allows
-                                // Note: Full resubscribe requires WsClient changes (see FEATURE_VERIFICATION.md)
-                                // For now, we just increment the counter and log
-                                if state.can_resync(&symbol) {
-                                    state.record_resync(&symbol);
-                                    health.reconnect_count += 1; // Increment resync count
-                                    state.push_event(UiEvent::ResyncStarted { symbol: symbol.clone() }).await;
-                                    warn!("Auto-resync triggered for {} due to checksum mismatch (resubscribe requires WsClient integration)", symbol);
-                                }
-                                
-                                let incident = incident_manager
-                                    .record_incident(
-                                        IncidentReason::ChecksumMismatch,
-                                        Some(symbol.clone()),
-                                        serde_json::json!({"expected_checksum": expected_checksum}),
-                                    )
-                                    .await;
-                                
-                                // Store frames for this symbol
-                                let frame_buffer = state.get_or_create_frame_buffer(&symbol);
-                                let _frames: Vec<String> = frame_buffer.read().await.iter().cloned().collect();
-                                
-                                // Create incident meta
-                                let incident_meta = IncidentMeta::new(
-                                    incident.id.clone(),
-                                    symbol.clone(),
-                                    format!("{:?}", incident.reason),
-                                );
-                                
-                                state.set_last_incident(incident_meta).await;
-                                
-                                state.push_event(UiEvent::IncidentCaptured {
-                                    id: incident.id,
-                                    reason: format!("{:?}", incident.reason),
-                                }).await;
-                            }
-                        }
+                    if update_crossed_state(state, &symbol, &book_entry) {
+                        events.push(UiEvent::BookCrossed { symbol: symbol.clone() });
+                    }
+                    if summary.top_of_book_moved() {
+                        events.push(UiEvent::TopOfBookChanged { symbol: symbol.clone() });
+                    }
+                    state.publish_book_change(&symbol, &book_entry);
+                    
+                    // A stale book diverged while disconnected; wait for the next snapshot
+                    if let Some(expected_checksum) = checksum.filter(|_| !book_entry.stale) {
+                        check = verify_book(state, &symbol, &mut book_entry, expected_checksum, summary.top_levels_changed);
                     }
                     
-                    let (asks_depth, bids_depth) = book_entry.depth();
-                    metrics::update_orderbook_depth(&symbol, asks_depth, bids_depth);
+                    if !summary.is_empty() {
+                        let (asks_depth, bids_depth) = book_entry.depth();
+                        metrics::update_orderbook_depth(&symbol, asks_depth, bids_depth);
+                        update_book_size_metrics(&symbol, &book_entry);
+                        if subscribed {
+                            update_top_of_book_metrics(&symbol, &book_entry);
+                        }
+                    }
+                }
+                record_book_violation(incident_manager, &symbol, violation).await;
+                for event in events {
+                    state.push_event(event).await;
                 }
+                record_book_check(state, incident_manager, &symbol, check).await;
+                maybe_resync(state, &symbol).await;
             }
             WsEvent::Error(err) => {
                 error!("WebSocket error: {}", err);
@@ -1310,6 +2304,7 @@ async fn replay_incident_bundle(
     bundle_path: PathBuf,
     speed: f64,
     http_addr: String,
+    http_options: HttpOptions,
 ) -> anyhow::Result<()> {
     use std::fs::File;
     use std::io::Read;
@@ -1321,64 +2316,69 @@ async fn replay_incident_bundle(
     let file = File::open(&bundle_path)?;
     let mut archive = ZipArchive::new(file)?;
     
-    let mut frames_file = archive.by_name("frames.ndjson")
-        .context("frames.ndjson not found in bundle")?;
-    
     let mut frames_content = String::new();
-    frames_file.read_to_string(&mut frames_content)?;
+    archive.by_name("frames.ndjson")
+        .context("frames.ndjson not found in bundle")?
+        .read_to_string(&mut frames_content)?;
+    
+    // Reconstructed book at incident time (bundles from older versions don't have one)
+    let restored_book = match archive.by_name("book_full.json") {
+        Ok(mut f) => {
+            let mut content = String::new();
+            f.read_to_string(&mut content)?;
+            Some(serde_json::from_str::<blackbox_core::orderbook::BookSnapshot>(&content)
+                .context("Invalid book_full.json in bundle")?)
+        }
+        Err(_) => None,
+    };
+    let incident_symbol = match archive.by_name("metadata.json") {
+        Ok(mut f) => {
+            let mut content = String::new();
+            f.read_to_string(&mut content)?;
+            serde_json::from_str::<blackbox_core::incident::IncidentMetadata>(&content)
+                .ok()
+                .and_then(|m| m.incident.symbol)
+        }
+        Err(_) => None,
+    };
     
     // Write frames to temporary file for replayer
     let temp_frames = std::env::temp_dir().join(format!("replay_{}.ndjson", chrono::Utc::now().timestamp()));
     std::fs::write(&temp_frames, frames_content)?;
     
     // Replay with no faults
-    let mode = if speed == 1.0 {
-        ReplayMode::Realtime
-    } else if speed > 0.0 {
-        ReplayMode::Speed(speed)
-    } else {
-        ReplayMode::AsFast
-    };
-    
     let config = ReplayConfig {
-        mode,
-        fault: FaultRule::None,
+        mode: replay_mode(speed),
+        faults: vec![],
+        loop_playback: false,
     };
     
     let mut replayer = Replayer::new(temp_frames.clone(), config)?;
     replayer.start();
     
     // Create shared state
-    let state = AppState::new();
+    let mut state = AppState::new();
+    state.run_mode = RunMode::ReplayIncident;
+    state.metrics = metrics::handle();
     
-    // Spawn processor for replay (simplified - would need full processing logic)
-    let processor_handle = tokio::spawn(async move {
-        use blackbox_ws::parser::parse_frame;
-        
-        while !replayer.is_done() {
-            if let Some(frame) = replayer.next_frame() {
-                match parse_frame(&frame) {
-                    Ok(_parsed) => {
-                        // Process frame (would need full processing logic here)
-                    }
-                    Err(e) => {
-                        warn!("Failed to parse replayed frame: {}", e);
-                    }
-                }
-            } else {
-                sleep(Duration::from_millis(10)).await;
-            }
-        }
-        info!("Replay completed");
-    });
+    if let (Some(snapshot), Some(symbol)) = (restored_book, incident_symbol) {
+        info!("Restored {} book from bundle (seq {})", symbol, snapshot.update_seq);
+        let depth = state.subscribed_depth(&symbol, snapshot.bids.len().max(snapshot.asks.len()));
+        state.orderbooks.insert(symbol, StoredBook::new(Orderbook::from_snapshot(snapshot), depth));
+    }
     
-    // Start HTTP server
     let incidents_dir = PathBuf::from("./incidents");
     let incident_manager = Arc::new(IncidentManager::new(incidents_dir)?);
-    let app = router(state.clone(), incident_manager.clone())
-        .route("/", get(|| async { Html(static_ui::UI_HTML) }));
+    *state.replayer.lock().unwrap() = Some(replayer);
+    tokio::spawn(msg_rate::sample_forever(state.clone()));
+    let processor_handle = tokio::spawn(replay_into_state(state.clone(), incident_manager.clone()));
+    
+    // Start HTTP server
+    let app = http_options.apply(router(state.clone(), incident_manager.clone())
+        .route("/", get(|| async { Html(static_ui::UI_HTML) })))?;
     
-    let server_handle = tokio::spawn(async move {
+    let serve_addr = http_addr.clone();
+    let mut server_handle = tokio::spawn(async move {
         let listener = tokio::net::TcpListener::bind(&http_addr).await.unwrap();
         info!("HTTP server listening on http://{}", http_addr);
         axum::serve(listener, app).await.unwrap();
@@ -1386,65 +2386,1416 @@ async fn replay_incident_bundle(
     
     tokio::select! {
         _ = processor_handle => {
-            info!("Replay completed");
+            // Cleanup temp file
+            let _ = std::fs::remove_file(&temp_frames);
+            info!("Replay completed; still serving http://{} (Ctrl-C to exit)", serve_addr);
+            let _ = server_handle.await;
+        }
+        _ = &mut server_handle => {
+            let _ = std::fs::remove_file(&temp_frames);
         }
-        _ = server_handle => {}
     }
     
-    // Cleanup temp file
-    let _ = std::fs::remove_file(&temp_frames);
-    
     Ok(())
 }
 
-fn build_fault_rule(
-    drop_every: Option<usize>,
-    drop_once: Option<usize>,
-    reorder_once: Option<usize>,
-    mutate_once: Option<usize>,
+/// The replay subcommand's --fault-* flags
+struct FaultFlags {
+    drop_every: Vec<usize>,
+    drop_once: Vec<usize>,
+    reorder_once: Vec<usize>,
+    mutate_once: Vec<usize>,
+    mutate_price_once: Vec<usize>,
+    duplicate_once: Vec<usize>,
+    corrupt_checksum_once: Vec<usize>,
+    delay_once: Vec<usize>,
+    checksum_xor: u32,
+    delay_ms: u64,
     mutate_delta: i32,
-) -> FaultRule {
-    if let Some(n) = drop_every {
-        return FaultRule::Every {
-            n,
-            fault: FaultType::Drop,
-        };
-    }
-    if let Some(idx) = drop_once {
-        return FaultRule::OnceAt {
-            index: idx,
-            fault: FaultType::Drop,
-        };
-    }
-    if let Some(idx) = reorder_once {
-        return FaultRule::OnceAt {
-            index: idx,
-            fault: FaultType::Reorder,
-        };
-    }
-    if let Some(idx) = mutate_once {
-        return FaultRule::OnceAt {
-            index: idx,
-            fault: FaultType::MutateQty {
-                delta_ticks: mutate_delta,
-            },
+}
+
+impl FaultFlags {
+    /// One rule per flag given, in the order of the flags above
+    fn rules(self) -> Vec<FaultRule> {
+        let once = |indices: Vec<usize>, fault: FaultType| {
+            indices.into_iter().map(move |index| FaultRule::OnceAt { index, fault: fault.clone() })
         };
+        let every = self.drop_every.into_iter().map(|n| FaultRule::Every { n, fault: FaultType::Drop });
+        every
+            .chain(once(self.drop_once, FaultType::Drop))
+            .chain(once(self.reorder_once, FaultType::Reorder))
+            .chain(once(self.mutate_once, FaultType::MutateQty { delta_ticks: self.mutate_delta }))
+            .chain(once(self.mutate_price_once, FaultType::MutatePrice { delta_ticks: self.mutate_delta }))
+            .chain(once(self.duplicate_once, FaultType::Duplicate))
+            .chain(once(self.corrupt_checksum_once, FaultType::CorruptChecksum { xor: self.checksum_xor }))
+            .chain(once(self.delay_once, FaultType::Delay { ms: self.delay_ms }))
+            .collect()
     }
-    FaultRule::None
 }
 
-fn parse_duration(s: &str) -> anyhow::Result<Duration> {
-    let s = s.trim();
-    if s.ends_with('s') {
-        let secs: u64 = s[..s.len() - 1].parse()?;
-        Ok(Duration::from_secs(secs))
-    } else if s.ends_with('m') {
-        let mins: u64 = s[..s.len() - 1].parse()?;
-        Ok(Duration::from_secs(mins * 60))
-    } else {
-        // Try parsing as seconds
-        let secs: u64 = s.parse()?;
-        Ok(Duration::from_secs(secs))
-    }
+/// A `--symbols` entry: `BTC/USD`, or `BTC/USD:1000` to override `--depth`
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct SymbolArg {
+    symbol: String,
+    depth: Option<u32>,
 }
 
+fn parse_symbol_arg(s: &str) -> anyhow::Result<SymbolArg> {
+    let s = s.trim();
+    let (symbol, depth) = match s.rsplit_once(':') {
+        Some((symbol, depth)) => {
+            let depth: u32 = depth
+                .trim()
+                .parse()
+                .with_context(|| format!("Invalid depth '{}' for {}", depth, symbol))?;
+            anyhow::ensure!(depth > 0, "Depth for {} must be positive", symbol);
+            (symbol.trim(), Some(depth))
+        }
+        None => (s, None),
+    };
+    anyhow::ensure!(!symbol.is_empty(), "Empty symbol in '{}'", s);
+    Ok(SymbolArg { symbol: symbol.to_string(), depth })
+}
+
+/// `*` or an origin such as `https://console.example:8443`
+fn parse_cors_origin(s: &str) -> anyhow::Result<String> {
+    if s != "*" {
+        anyhow::ensure!(
+            (s.starts_with("http://") || s.starts_with("https://")) && axum::http::HeaderValue::from_str(s).is_ok(),
+            "expected * or an origin like https://host:port, got {:?}",
+            s
+        );
+    }
+    Ok(s.trim_end_matches('/').to_string())
+}
+
+/// `/route=N/PERIOD`, or `/route=off` to lift the route's limit
+fn parse_rate_limit_arg(s: &str) -> anyhow::Result<(String, Option<RateLimit>)> {
+    let (route, limit) = s.split_once('=')
+        .ok_or_else(|| anyhow::anyhow!("expected ROUTE=N/PERIOD or ROUTE=off, got {:?}", s))?;
+    let route = route.trim().trim_end_matches('/');
+    anyhow::ensure!(route.starts_with('/'), "route must start with /, got {:?}", s);
+    let limit = match limit.trim() {
+        "off" => None,
+        limit => Some(limit.parse()?),
+    };
+    Ok((route.to_string(), limit))
+}
+
+fn parse_ws_url(s: &str) -> anyhow::Result<String> {
+    validate_ws_url(s)?;
+    Ok(s.to_string())
+}
+
+/// One `SymbolArg` per line; blank lines and `#` comments are skipped
+fn parse_symbols_file(content: &str) -> anyhow::Result<Vec<SymbolArg>> {
+    content
+        .lines()
+        .enumerate()
+        .filter_map(|(index, line)| {
+            let line = line.split('#').next().unwrap_or("").trim();
+            (!line.is_empty()).then(|| parse_symbol_arg(line).with_context(|| format!("line {}", index + 1)))
+        })
+        .collect()
+}
+
+/// `--symbols` followed by `--symbols-file`, without repeats; the first
+/// mention of a symbol wins. Prints the result.
+fn resolve_symbols(symbols: Vec<SymbolArg>, symbols_file: Option<&std::path::Path>) -> anyhow::Result<Vec<SymbolArg>> {
+    let mut all = symbols;
+    if let Some(path) = symbols_file {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read symbols file {}", path.display()))?;
+        all.extend(parse_symbols_file(&content).with_context(|| format!("Invalid symbols file {}", path.display()))?);
+    }
+    let resolved = dedup_symbols(all);
+    let listed: Vec<String> = resolved
+        .iter()
+        .map(|arg| match arg.depth {
+            Some(depth) => format!("{}:{}", arg.symbol, depth),
+            None => arg.symbol.clone(),
+        })
+        .collect();
+    println!("Symbols ({}): {}", listed.len(), listed.join(","));
+    Ok(resolved)
+}
+
+fn dedup_symbols(args: Vec<SymbolArg>) -> Vec<SymbolArg> {
+    let mut seen = std::collections::HashSet::new();
+    args.into_iter()
+        .filter(|arg| {
+            let first = seen.insert(arg.symbol.clone());
+            if !first {
+                warn!("{} is listed more than once; keeping its first entry", arg.symbol);
+            }
+            first
+        })
+        .collect()
+}
+
+/// Symbols in order, and the depths of those that set their own
+fn split_symbol_args(args: Vec<SymbolArg>) -> (Vec<String>, HashMap<String, u32>) {
+    let depths = args.iter().filter_map(|arg| Some((arg.symbol.clone(), arg.depth?))).collect();
+    (args.into_iter().map(|arg| arg.symbol).collect(), depths)
+}
+
+/// Byte count with an optional K/M/G suffix (powers of 1024)
+fn parse_byte_size(s: &str) -> anyhow::Result<u64> {
+    let s = s.trim();
+    let (digits, unit) = match s.char_indices().find(|(_, c)| !c.is_ascii_digit()) {
+        Some((at, _)) => s.split_at(at),
+        None => (s, ""),
+    };
+    let shift = match unit.trim().to_ascii_uppercase().trim_end_matches('B') {
+        "" => 0,
+        "K" => 10,
+        "M" => 20,
+        "G" => 30,
+        other => anyhow::bail!("Unknown size unit '{}' (use K, M or G)", other),
+    };
+    let bytes: u64 = digits.parse()?;
+    bytes.checked_mul(1 << shift).ok_or_else(|| anyhow::anyhow!("Size too large: {}", s))
+}
+
+/// Recording and incident-bundle guards from --max-record-bytes, --max-incidents-bytes and --disk-policy
+fn disk_guards(
+    max_record_bytes: Option<u64>,
+    max_incidents_bytes: Option<u64>,
+    policy: DiskPolicy,
+) -> (Option<DiskGuard>, Option<DiskGuard>) {
+    let guard = |max_bytes| DiskGuard::new(max_bytes, policy);
+    (max_record_bytes.map(guard), max_incidents_bytes.map(guard))
+}
+
+/// Values accepted by --record-channels
+const RECORD_CHANNELS: [&str; 6] = ["book", "instrument", "status", "heartbeat", "ping", "ack"];
+
+fn recorder_options(
+    rotate_size: Option<u64>,
+    rotate_every: Option<Duration>,
+    flush_interval: Duration,
+    flush_frames: u64,
+) -> RecorderOptions {
+    RecorderOptions {
+        rotation: RotationPolicy { max_bytes: rotate_size, max_duration: rotate_every },
+        // 0 turns either limit off
+        flush: FlushPolicy {
+            every_frames: Some(flush_frames).filter(|&n| n > 0),
+            interval: Some(flush_interval).filter(|interval| !interval.is_zero()),
+        },
+        ..Default::default()
+    }
+}
+
+/// Parse a duration such as `30`, `500ms`, `2m` or `1h30m`; a bare number is seconds.
+/// Units are `ms`, `s`, `m` and `h`, and parts may be separated by spaces.
+fn parse_duration(s: &str) -> anyhow::Result<Duration> {
+    let input = s.trim();
+    if input.is_empty() {
+        anyhow::bail!("Empty duration");
+    }
+    if input.bytes().all(|b| b.is_ascii_digit()) {
+        let secs: u64 = input.parse().with_context(|| format!("Duration '{}' is too large", input))?;
+        return Ok(Duration::from_secs(secs));
+    }
+    
+    let mut total = Duration::ZERO;
+    let mut rest = input;
+    while !rest.is_empty() {
+        let digits = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+        if digits == 0 {
+            anyhow::bail!("Invalid duration '{}': expected a number at '{}'", input, rest);
+        }
+        let value: u64 = rest[..digits].parse().with_context(|| format!("Duration '{}' is too large", input))?;
+        rest = rest[digits..].trim_start();
+        let unit_len = rest.find(|c: char| c.is_ascii_digit() || c.is_whitespace()).unwrap_or(rest.len());
+        let secs_per_unit = match &rest[..unit_len] {
+            "ms" => None,
+            "s" => Some(1),
+            "m" => Some(60),
+            "h" => Some(3600),
+            "" => anyhow::bail!("Invalid duration '{}': {} has no unit (ms, s, m or h)", input, value),
+            unit => anyhow::bail!("Invalid duration '{}': unknown unit '{}' (expected ms, s, m or h)", input, unit),
+        };
+        let part = match secs_per_unit {
+            None => Some(Duration::from_millis(value)),
+            Some(secs_per_unit) => value.checked_mul(secs_per_unit).map(Duration::from_secs),
+        };
+        total = part
+            .and_then(|part| total.checked_add(part))
+            .with_context(|| format!("Duration '{}' is too large", input))?;
+        rest = rest[unit_len..].trim_start();
+    }
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blackbox_core::recorder::Recorder;
+    use blackbox_core::types::InstrumentInfo;
+    use blackbox_ws::client::DisconnectCause;
+    use rust_decimal::Decimal;
+    use rust_decimal_macros::dec;
+
+    fn level(price: Decimal, qty: Decimal) -> Vec<(Decimal, Decimal)> {
+        vec![(price, qty)]
+    }
+
+    #[tokio::test]
+    async fn test_processor_broadcasts_book_changes_in_order() {
+        let state = AppState::new();
+        let incidents_dir = std::env::temp_dir().join(format!("blackbox_test_{}", std::process::id()));
+        let incident_manager = Arc::new(IncidentManager::new(incidents_dir.clone()).unwrap());
+        let mut changes = state.subscribe_book_changes();
+        
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        tx.send(WsEvent::BookSnapshot {
+            symbol: "BTC/USD".to_string(),
+            bids: level(dec!(100), dec!(1)),
+            asks: level(dec!(101), dec!(1)),
+            checksum: None,
+        }).unwrap();
+        tx.send(WsEvent::BookUpdate {
+            symbol: "BTC/USD".to_string(),
+            bids: level(dec!(100.5), dec!(2)),
+            asks: vec![],
+            checksum: None,
+            timestamp: Some("2024-01-15T10:30:45.123456Z".to_string()),
+        }).unwrap();
+        tx.send(WsEvent::BookUpdate {
+            symbol: "BTC/USD".to_string(),
+            bids: vec![],
+            asks: level(dec!(101), dec!(0)),
+            checksum: None,
+            timestamp: None,
+        }).unwrap();
+        drop(tx);
+        
+        process_ws_events(&state, &incident_manager, &mut rx).await;
+        
+        let first = changes.try_recv().unwrap();
+        assert_eq!(first.symbol, "BTC/USD");
+        assert_eq!(first.seq, 1);
+        assert_eq!(first.best_bid, Some((dec!(100), dec!(1))));
+        assert_eq!(first.best_ask, Some((dec!(101), dec!(1))));
+        
+        let second = changes.try_recv().unwrap();
+        assert_eq!(second.seq, 2);
+        assert_eq!(second.best_bid, Some((dec!(100.5), dec!(2))));
+        assert_eq!(second.ts.to_rfc3339(), "2024-01-15T10:30:45.123456+00:00");
+        
+        let third = changes.try_recv().unwrap();
+        assert_eq!(third.seq, 3);
+        assert_eq!(third.best_ask, None);
+        
+        assert!(changes.try_recv().is_err());
+        let _ = std::fs::remove_dir_all(incidents_dir);
+    }
+
+    #[test]
+    fn test_feed_latency_is_clamped() {
+        let received = chrono::Utc::now();
+        let ms = |ms: i64| received - chrono::Duration::milliseconds(ms);
+        assert_eq!(feed_latency_ms(ms(250), received), (250.0, false));
+        assert_eq!(feed_latency_ms(received, received), (0.0, false));
+        let (latency, clamped) = feed_latency_ms(received - chrono::Duration::microseconds(1500), received);
+        assert!((latency - 1.5).abs() < 1e-9 && !clamped);
+        // Stamped in the future by a clock ahead of ours
+        assert_eq!(feed_latency_ms(ms(-40), received), (0.0, true));
+        assert_eq!(feed_latency_ms(ms(3_600_000), received), (MAX_FEED_LATENCY_MS, true));
+        let ancient = chrono::DateTime::parse_from_rfc3339("1970-01-01T00:00:00Z").unwrap().with_timezone(&chrono::Utc);
+        assert_eq!(feed_latency_ms(ancient, received), (MAX_FEED_LATENCY_MS, true));
+    }
+
+    #[test]
+    fn test_book_timestamp_records_feed_latency() {
+        let recorder = metrics_exporter_prometheus::PrometheusBuilder::new().build_recorder();
+        let mut state = AppState::new();
+        let mut book = Orderbook::new();
+        let stamp = |ago_ms: i64| (chrono::Utc::now() - chrono::Duration::milliseconds(ago_ms)).to_rfc3339();
+        ::metrics::with_local_recorder(&recorder, || {
+            record_book_timestamp(&state, &mut book, "BTC/USD", Some(&stamp(200)));
+            let latency = state.health.get("BTC/USD").unwrap().feed_latency_ms.unwrap();
+            assert!((200.0..1000.0).contains(&latency), "{}", latency);
+
+            record_book_timestamp(&state, &mut book, "BTC/USD", Some(&stamp(-5_000)));
+            assert_eq!(state.health.get("BTC/USD").unwrap().feed_latency_ms, Some(0.0));
+            record_book_timestamp(&state, &mut book, "BTC/USD", Some("2024-01-15T10:30:45.123456Z"));
+            assert_eq!(state.health.get("BTC/USD").unwrap().feed_latency_ms, Some(MAX_FEED_LATENCY_MS));
+            record_book_timestamp(&state, &mut book, "BTC/USD", Some("not a time"));
+            record_book_timestamp(&state, &mut book, "BTC/USD", None);
+        });
+        assert_eq!(book.last_update_ts().unwrap().to_rfc3339(), "2024-01-15T10:30:45.123456+00:00");
+        let text = recorder.handle().render();
+        assert!(text.contains("latency_anomaly_total{symbol=\"BTC/USD\"} 2"), "{}", text);
+        assert!(text.contains("message_latency_ms_count{symbol=\"BTC/USD\"} 3"), "{}", text);
+
+        // Replayed frames are as old as the recording: no latency
+        state.run_mode = RunMode::Replay;
+        ::metrics::with_local_recorder(&recorder, || {
+            record_book_timestamp(&state, &mut book, "ETH/USD", Some(&stamp(200)));
+        });
+        assert!(state.health.get("ETH/USD").is_none());
+        assert!(!recorder.handle().render().contains("ETH/USD"));
+    }
+
+    fn snapshot_event() -> WsEvent {
+        WsEvent::BookSnapshot {
+            symbol: "BTC/USD".to_string(),
+            bids: level(dec!(100), dec!(1)),
+            asks: level(dec!(101), dec!(1)),
+            checksum: None,
+        }
+    }
+
+    fn update_event(checksum: Option<u32>) -> WsEvent {
+        WsEvent::BookUpdate {
+            symbol: "BTC/USD".to_string(),
+            bids: level(dec!(99), dec!(2)),
+            asks: vec![],
+            checksum, // never matches the book
+            timestamp: None,
+        }
+    }
+
+    /// Status and JSON body of a GET to a server on `addr`
+    async fn http_get(addr: std::net::SocketAddr, path: &str) -> (u16, serde_json::Value) {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let request = format!("GET {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n", path, addr);
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        let status = head.split_whitespace().nth(1).unwrap().parse().unwrap();
+        (status, serde_json::from_str(body).unwrap())
+    }
+
+    /// Status and JSON body of a POST of `body` to a server on `addr`
+    async fn http_post(addr: std::net::SocketAddr, path: &str, body: &str) -> (u16, serde_json::Value) {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let request = format!(
+            "POST {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            path, addr, body.len(), body
+        );
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        let status = head.split_whitespace().nth(1).unwrap().parse().unwrap();
+        (status, serde_json::from_str(body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_replay_control_endpoints() {
+        let incidents_dir = std::env::temp_dir().join(format!("blackbox_replay_control_test_{}", std::process::id()));
+        let incident_manager = Arc::new(IncidentManager::new(incidents_dir.clone()).unwrap());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let live = listener.local_addr().unwrap();
+        let app = router(AppState::new(), incident_manager.clone());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        assert_eq!(http_get(live, "/replay/status").await.0, 409);
+        assert_eq!(http_post(live, "/replay/speed", r#"{"multiplier":2}"#).await.0, 409);
+
+        // The fixture's frames span 4s, far longer than the test waits at realtime
+        let fixture = PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/../blackbox-core/tests/fixtures/recording_corrupted.ndjson"));
+        let config = ReplayConfig { mode: ReplayMode::Realtime, faults: vec![], loop_playback: false };
+        let mut replayer = Replayer::new(fixture, config).unwrap();
+        replayer.pause();
+        let state = AppState::new();
+        *state.replayer.lock().unwrap() = Some(replayer);
+        let replay = tokio::spawn(replay_into_state(state.clone(), incident_manager.clone()));
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = router(state.clone(), incident_manager);
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let (status, body) = http_get(addr, "/replay/status").await;
+        assert_eq!(status, 200);
+        assert_eq!(body["paused"], true);
+        assert_eq!(body["done"], false);
+        assert_eq!(body["current_ts"], serde_json::Value::Null);
+        assert_eq!(body["mode"], "Realtime");
+
+        let (_, body) = http_post(addr, "/replay/step", "").await;
+        assert_eq!(body["paused"], true);
+        while http_get(addr, "/replay/status").await.1["current_ts"].is_null() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let (_, body) = http_get(addr, "/replay/status").await;
+        assert_eq!(body["current_ts"], "2024-01-15T10:30:44Z");
+
+        // Forward past the book snapshot: updates wait for one that never comes
+        let (status, body) = http_post(addr, "/replay/seek", r#"{"ts":"2024-01-15T10:30:47Z"}"#).await;
+        assert_eq!(status, 200);
+        assert_eq!(body["symbols_without_snapshot"], serde_json::json!(["BTC/USD"]));
+        let progress = body["progress"].as_f64().unwrap();
+        assert!(progress > 0.5 && progress < 1.0, "{}", progress);
+
+        assert_eq!(http_post(addr, "/replay/speed", r#"{"multiplier":-1}"#).await.0, 400);
+        let (status, body) = http_post(addr, "/replay/speed", r#"{"multiplier":0}"#).await;
+        assert_eq!(status, 200);
+        assert_eq!(body["mode"], "AsFast");
+
+        let (_, body) = http_post(addr, "/replay/resume", "").await;
+        assert_eq!(body["paused"], false);
+        tokio::time::timeout(Duration::from_secs(5), replay).await.unwrap().unwrap();
+        let (_, body) = http_get(addr, "/replay/status").await;
+        assert_eq!(body["done"], true);
+        assert_eq!(body["progress"], 1.0);
+        assert_eq!(body["faults_applied"], 0);
+
+        let _ = std::fs::remove_dir_all(incidents_dir);
+    }
+
+    #[tokio::test]
+    async fn test_replay_populates_http_state() {
+        let incidents_dir = std::env::temp_dir().join(format!("blackbox_replay_state_test_{}", std::process::id()));
+        let incident_manager = Arc::new(IncidentManager::new(incidents_dir.clone()).unwrap());
+        let fixture = PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/../blackbox-core/tests/fixtures/recording_corrupted.ndjson"));
+        let config = ReplayConfig { mode: ReplayMode::AsFast, faults: vec![], loop_playback: false };
+        let state = AppState::new();
+        *state.replayer.lock().unwrap() = Some(Replayer::new(fixture, config).unwrap());
+        replay_into_state(state.clone(), incident_manager.clone()).await;
+        // From the recording's instrument snapshot
+        assert_eq!(state.instruments.get("BTC/USD").unwrap().price_precision, 1);
+        // Its snapshot and five updates, kept for /frames
+        let btc_frames = state.per_symbol_frames.get("BTC/USD").unwrap().clone();
+        assert_eq!(btc_frames.read().await.len(), 6);
+        
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = router(state.clone(), incident_manager);
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        
+        let (status, top) = http_get(addr, "/book/BTC%2FUSD/top").await;
+        assert_eq!(status, 200);
+        assert_eq!(top["best_bid"], serde_json::json!(["50000.7", "3.0"]));
+        assert_eq!(top["best_ask"][0], "50001.5");
+        
+        // Every checksummed frame verified, the fixture's corrupted one failing
+        let (status, health) = http_get(addr, "/health").await;
+        assert_eq!(status, 200);
+        let btc = &health["symbols"][0];
+        assert_eq!(btc["symbol"], "BTC/USD");
+        assert_eq!(btc["checksum_ok"], 5);
+        assert_eq!(btc["checksum_fail"], 1);
+        
+        let _ = std::fs::remove_dir_all(incidents_dir);
+    }
+
+    #[test]
+    fn test_replay_exports_progress_and_faults() {
+        use blackbox_core::types::{FaultRule, FaultType};
+        let incidents_dir = std::env::temp_dir().join(format!("blackbox_replay_metrics_{}", std::process::id()));
+        let incident_manager = Arc::new(IncidentManager::new(incidents_dir.clone()).unwrap());
+        let fixture = PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/../blackbox-core/tests/fixtures/recording_corrupted.ndjson"));
+        let faults = vec![FaultRule::OnceAt { index: 2, fault: FaultType::Duplicate }];
+        let config = ReplayConfig { mode: ReplayMode::AsFast, faults, loop_playback: false };
+        let state = AppState::new();
+        *state.replayer.lock().unwrap() = Some(Replayer::new(fixture, config).unwrap());
+
+        let recorder = metrics_exporter_prometheus::PrometheusBuilder::new().build_recorder();
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        ::metrics::with_local_recorder(&recorder, || runtime.block_on(replay_into_state(state.clone(), incident_manager)));
+
+        let text = recorder.handle().render();
+        assert!(text.contains("replay_progress 1\n"), "{}", text);
+        // The fixture's 9 frames, one of them played twice
+        assert!(text.contains("replay_frames_emitted_total 10"), "{}", text);
+        assert!(text.contains("replay_faults_applied_total{type=\"duplicate\"} 1"), "{}", text);
+        let _ = std::fs::remove_dir_all(incidents_dir);
+    }
+
+    fn stale_test_state() -> AppState {
+        let state = AppState::new();
+        state.instruments.insert("BTC/USD".to_string(), InstrumentInfo {
+            symbol: "BTC/USD".to_string(),
+            price_precision: 1,
+            qty_precision: 8,
+            price_increment: dec!(0.1),
+            qty_increment: dec!(0.00000001),
+            status: "online".to_string(),
+            ..Default::default()
+        });
+        state
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_fault_keeps_checksum() {
+        let incidents_dir = std::env::temp_dir().join(format!("blackbox_duplicate_test_{}", std::process::id()));
+        let incident_manager = Arc::new(IncidentManager::new(incidents_dir.clone()).unwrap());
+        let bids: Vec<_> = (0..10).map(|i| (dec!(100) - Decimal::from(i), dec!(1))).collect();
+        let asks: Vec<_> = (0..10).map(|i| (dec!(101) + Decimal::from(i), dec!(2))).collect();
+        let mut reference = Orderbook::new();
+        reference.apply_snapshot(bids.clone(), asks.clone());
+        reference.apply_updates(level(dec!(100), dec!(3)), level(dec!(101), dec!(0)));
+        
+        let state = stale_test_state();
+        state.fault_injector.arm("BTC/USD".to_string(), InjectedFault::Duplicate);
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        tx.send(WsEvent::BookSnapshot { symbol: "BTC/USD".to_string(), bids, asks, checksum: None }).unwrap();
+        tx.send(WsEvent::BookUpdate {
+            symbol: "BTC/USD".to_string(),
+            bids: level(dec!(100), dec!(3)),
+            asks: level(dec!(101), dec!(0)),
+            checksum: Some(reference.checksum(1, 8)),
+            timestamp: None,
+        }).unwrap();
+        drop(tx);
+        process_ws_events_with_logging(&state, &incident_manager, &mut rx).await;
+        
+        // Updates carry absolute quantities, so applying one twice is harmless
+        assert!(state.fault_injector.consume("BTC/USD").is_none());
+        let health = state.health.get("BTC/USD").unwrap();
+        assert_eq!((health.checksum_ok, health.checksum_fail), (1, 0));
+        
+        let _ = std::fs::remove_dir_all(incidents_dir);
+    }
+
+    #[tokio::test]
+    async fn test_drop_fault_on_the_live_path() {
+        let incidents_dir = std::env::temp_dir().join(format!("blackbox_drop_fault_test_{}", std::process::id()));
+        let incident_manager = Arc::new(IncidentManager::new(incidents_dir.clone()).unwrap());
+        let state = stale_test_state();
+        let update = |symbol: &str, price| WsEvent::BookUpdate {
+            symbol: symbol.to_string(),
+            bids: level(price, dec!(1)),
+            asks: vec![],
+            checksum: None,
+            timestamp: None,
+        };
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        for symbol in ["BTC/USD", "ETH/USD"] {
+            tx.send(WsEvent::BookSnapshot { symbol: symbol.to_string(), bids: level(dec!(90), dec!(1)), asks: level(dec!(110), dec!(1)), checksum: None }).unwrap();
+        }
+        drop(tx);
+        process_ws_events(&state, &incident_manager, &mut rx).await;
+
+        state.fault_injector.arm("BTC/USD".to_string(), InjectedFault::Drop);
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        tx.send(update("ETH/USD", dec!(91))).unwrap(); // other symbol: still armed
+        tx.send(update("BTC/USD", dec!(92))).unwrap(); // dropped
+        tx.send(update("BTC/USD", dec!(93))).unwrap();
+        drop(tx);
+        process_ws_events(&state, &incident_manager, &mut rx).await;
+
+        assert_eq!(state.orderbooks.get("ETH/USD").unwrap().best_bid().unwrap().0, dec!(91));
+        let btc = state.orderbooks.get("BTC/USD").unwrap();
+        assert_eq!(btc.best_bid().unwrap().0, dec!(93));
+        assert_eq!(btc.bids_vec(None), vec![(dec!(93), dec!(1)), (dec!(90), dec!(1))]);
+        assert!(state.fault_injector.armed_symbol().is_none());
+
+        let _ = std::fs::remove_dir_all(incidents_dir);
+    }
+
+    #[tokio::test]
+    async fn test_processor_buffers_book_frames_per_symbol() {
+        let incidents_dir = std::env::temp_dir().join(format!("blackbox_symbol_frames_test_{}", std::process::id()));
+        let incident_manager = Arc::new(IncidentManager::new(incidents_dir.clone()).unwrap());
+        let mut state = AppState::new();
+        state.symbol_frames_capacity = 5;
+        let book_frame = |symbol: &str, i: usize| format!(
+            r#"{{"channel":"book","type":"update","data":[{{"symbol":"{}","bids":[{{"price":{},"qty":1}}],"asks":[],"checksum":1}}]}}"#,
+            symbol, i
+        );
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        for i in 0..8 {
+            tx.send(WsEvent::Frame(book_frame("BTC/USD", i))).unwrap();
+        }
+        for i in 0..3 {
+            tx.send(WsEvent::Frame(book_frame("ETH/USD", i))).unwrap();
+        }
+        tx.send(WsEvent::Frame(r#"{"channel":"heartbeat"}"#.to_string())).unwrap();
+        drop(tx);
+        process_ws_events(&state, &incident_manager, &mut rx).await;
+
+        // The most recent min(N, capacity) frames of each symbol
+        let buffered = |symbol: &str| -> Vec<String> {
+            let buffer = state.per_symbol_frames.get(symbol).unwrap().clone();
+            let frames = buffer.try_read().unwrap().iter().map(|(_, raw)| raw.clone()).collect();
+            frames
+        };
+        assert_eq!(buffered("BTC/USD"), (3..8).map(|i| book_frame("BTC/USD", i)).collect::<Vec<_>>());
+        assert_eq!(buffered("ETH/USD"), (0..3).map(|i| book_frame("ETH/USD", i)).collect::<Vec<_>>());
+        // Non-book frames only reach the global buffer
+        assert_eq!(state.per_symbol_frames.len(), 2);
+        assert_eq!(state.last_frames.read().await.back().unwrap().1, r#"{"channel":"heartbeat"}"#);
+        assert_eq!(state.last_frames.read().await.len(), 12);
+
+        let _ = std::fs::remove_dir_all(incidents_dir);
+    }
+
+    #[tokio::test]
+    async fn test_repeated_mismatches_resync_against_mock_server() {
+        use futures_util::{SinkExt, StreamExt};
+        use tokio_tungstenite::tungstenite::Message;
+
+        const INSTRUMENT_SNAPSHOT: &str = r#"{"channel":"instrument","type":"snapshot","data":{"assets":[],"pairs":[{"symbol":"BTC/USD","base":"BTC","quote":"USD","status":"online","price_precision":1,"qty_precision":8,"price_increment":0.1,"qty_increment":0.00000001}]}}"#;
+        let snapshot = |bid: Decimal, ask: Decimal| {
+            let mut book = Orderbook::new();
+            book.apply_snapshot(level(bid, dec!(1)), level(ask, dec!(2)));
+            format!(
+                r#"{{"channel":"book","type":"snapshot","data":[{{"symbol":"BTC/USD","bids":[{{"price":{},"qty":1}}],"asks":[{{"price":{},"qty":2}}],"checksum":{}}}]}}"#,
+                bid, ask, book.checksum(1, 8)
+            )
+        };
+        let bad_update = |bid: Decimal| format!(
+            r#"{{"channel":"book","type":"update","data":[{{"symbol":"BTC/USD","bids":[{{"price":{},"qty":1}}],"asks":[],"checksum":1}}]}}"#,
+            bid
+        );
+
+        /// The next request the client sends, pings aside
+        async fn next_request(ws: &mut tokio_tungstenite::WebSocketStream<tokio::net::TcpStream>) -> serde_json::Value {
+            loop {
+                if let Some(Ok(Message::Text(text))) = ws.next().await {
+                    let request: serde_json::Value = serde_json::from_str(&text).unwrap();
+                    if request["method"] != "ping" {
+                        return request;
+                    }
+                }
+            }
+        }
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (first, fresh) = (snapshot(dec!(100.0), dec!(101.0)), snapshot(dec!(200.0), dec!(201.0)));
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+            let mut requests = Vec::new();
+            requests.push(next_request(&mut ws).await); // instrument
+            ws.send(Message::Text(INSTRUMENT_SNAPSHOT.to_string())).await.unwrap();
+            requests.push(next_request(&mut ws).await); // book
+            ws.send(Message::Text(first)).await.unwrap();
+            for bid in [dec!(99.0), dec!(98.0), dec!(97.0)] {
+                ws.send(Message::Text(bad_update(bid))).await.unwrap();
+            }
+            requests.push(next_request(&mut ws).await);
+            requests.push(next_request(&mut ws).await);
+            ws.send(Message::Text(fresh)).await.unwrap();
+            while let Some(Ok(_)) = ws.next().await {}
+            requests
+        });
+
+        let incidents_dir = std::env::temp_dir().join(format!("blackbox_resync_test_{}", std::process::id()));
+        let incident_manager = Arc::new(IncidentManager::new(incidents_dir.clone()).unwrap());
+        let mut state = AppState::new();
+        let (command_tx, command_rx) = mpsc::unbounded_channel();
+        state.ws_commands = Some(command_tx);
+        let mut events = state.subscribe_events();
+        let (ws_tx, mut ws_rx) = mpsc::unbounded_channel();
+        let client = WsClient::new(vec!["BTC/USD".to_string()], 10, Duration::from_secs(3600), ws_tx)
+            .with_url(format!("ws://{}", addr))
+            .with_commands(command_rx);
+        let client = tokio::spawn(async move { client.run().await });
+        let processor_state = state.clone();
+        let processor = tokio::spawn(async move {
+            process_ws_events(&processor_state, &incident_manager, &mut ws_rx).await;
+        });
+
+        let mut kinds = Vec::new();
+        tokio::time::timeout(Duration::from_secs(10), async {
+            while kinds.last() != Some(&"resync_done") {
+                kinds.push(events.recv().await.unwrap().event.kind());
+            }
+        })
+        .await
+        .expect("the resync never finished");
+        // The fresh snapshot still has to be checked and stored
+        let recovered = || state.orderbooks.get("BTC/USD").is_some_and(|book| book.best_bid().unwrap().0 == dec!(200.0));
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while !recovered() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("the book didn't recover from the fresh snapshot");
+        client.abort();
+        let _ = client.await;
+        let requests = tokio::time::timeout(Duration::from_secs(5), server).await.unwrap().unwrap();
+        let _ = processor.await;
+
+        assert_eq!(kinds.iter().filter(|kind| **kind == "resync_started").count(), 1);
+        assert_eq!(requests[2]["method"], "unsubscribe");
+        assert_eq!(requests[3]["method"], "subscribe");
+        for request in &requests[2..] {
+            assert_eq!(request["params"]["channel"], "book");
+            assert_eq!(request["params"]["symbol"], serde_json::json!(["BTC/USD"]));
+            assert_eq!(request["params"]["depth"], 10);
+        }
+        assert_eq!(requests[3]["params"]["snapshot"], true);
+        let health = state.health.get("BTC/USD").unwrap();
+        assert_eq!((health.checksum_fail, health.consecutive_fails, health.resync_count), (3, 0, 1));
+        assert!(state.resyncing.is_empty());
+
+        let _ = std::fs::remove_dir_all(incidents_dir);
+    }
+
+    #[tokio::test]
+    async fn test_corrupt_checksum_fault_fires_once() {
+        let incidents_dir = std::env::temp_dir().join(format!("blackbox_corrupt_test_{}", std::process::id()));
+        let incident_manager = Arc::new(IncidentManager::new(incidents_dir.clone()).unwrap());
+        let bids: Vec<_> = (0..25).map(|i| (dec!(100) - Decimal::from(i), dec!(1))).collect();
+        let asks: Vec<_> = (0..25).map(|i| (dec!(101) + Decimal::from(i), dec!(2))).collect();
+        let mut reference = Orderbook::new();
+        reference.apply_snapshot(bids.clone(), asks.clone());
+        // Below the checksummed top 10
+        let deep = reference.checksum(1, 8);
+        reference.apply_updates(level(dec!(100), dec!(3)), vec![]);
+        let top = reference.checksum(1, 8);
+        let update = |bids, checksum| WsEvent::BookUpdate {
+            symbol: "BTC/USD".to_string(),
+            bids,
+            asks: vec![],
+            checksum: Some(checksum),
+            timestamp: None,
+        };
+        
+        let state = stale_test_state();
+        let fault = crate::integrity::fault::FaultType::CorruptChecksum { xor: 0x10 };
+        let process = |events: Vec<WsEvent>| {
+            let (state, incident_manager) = (state.clone(), incident_manager.clone());
+            async move {
+                let (tx, mut rx) = mpsc::unbounded_channel();
+                for event in events {
+                    tx.send(event).unwrap();
+                }
+                drop(tx);
+                process_ws_events_with_logging(&state, &incident_manager, &mut rx).await;
+            }
+        };
+        process(vec![
+            WsEvent::BookSnapshot { symbol: "BTC/USD".to_string(), bids, asks, checksum: None },
+            update(level(dec!(80), dec!(5)), deep),
+        ]).await;
+        state.fault_injector.arm("BTC/USD".to_string(), fault);
+        process(vec![update(level(dec!(79), dec!(5)), deep)]).await;
+        
+        // The book is the one last verified; only the advertised checksum changed
+        let diagnosis = state.integrity_proofs.get("BTC/USD").unwrap().diagnosis.clone().unwrap();
+        assert!(diagnosis.starts_with("book unchanged since its last match"), "{}", diagnosis);
+        
+        process(vec![update(level(dec!(100), dec!(3)), top)]).await;
+        let health = state.health.get("BTC/USD").unwrap();
+        assert_eq!((health.checksum_ok, health.checksum_fail), (2, 1));
+        assert_eq!(state.get_incident_count().await, 1);
+        
+        let _ = std::fs::remove_dir_all(incidents_dir);
+    }
+
+    #[tokio::test]
+    async fn test_checksum_verifies_after_best_level_delete_at_shallow_depth() {
+        let incidents_dir = std::env::temp_dir().join(format!("blackbox_depth_test_{}", std::process::id()));
+        let incident_manager = Arc::new(IncidentManager::new(incidents_dir.clone()).unwrap());
+        
+        // Recorded at depth 25, replayed with --depth 10
+        let bids: Vec<_> = (0..25).map(|i| (dec!(100) - Decimal::from(i), dec!(1))).collect();
+        let asks: Vec<_> = (0..25).map(|i| (dec!(101) + Decimal::from(i), dec!(2))).collect();
+        let mut reference = Orderbook::new();
+        reference.apply_snapshot(bids.clone(), asks.clone());
+        // Each best ask delete pulls the next ask into the checksummed top 10;
+        // Kraken doesn't resend it since it was already within the subscribed depth
+        reference.apply_updates(vec![], level(dec!(101), dec!(0)));
+        let first = reference.checksum(1, 8);
+        reference.apply_updates(vec![], level(dec!(102), dec!(0)));
+        let second = reference.checksum(1, 8);
+        
+        for with_logging in [false, true] {
+            let state = stale_test_state();
+            state.set_depth("BTC/USD", 10);
+            
+            let (tx, mut rx) = mpsc::unbounded_channel();
+            tx.send(WsEvent::BookSnapshot {
+                symbol: "BTC/USD".to_string(),
+                bids: bids.clone(),
+                asks: asks.clone(),
+                checksum: None,
+            }).unwrap();
+            for (price, checksum) in [(dec!(101), first), (dec!(102), second)] {
+                tx.send(WsEvent::BookUpdate {
+                    symbol: "BTC/USD".to_string(),
+                    bids: vec![],
+                    asks: level(price, dec!(0)),
+                    checksum: Some(checksum),
+                    timestamp: None,
+                }).unwrap();
+            }
+            drop(tx);
+            
+            if with_logging {
+                process_ws_events_with_logging(&state, &incident_manager, &mut rx).await;
+            } else {
+                process_ws_events(&state, &incident_manager, &mut rx).await;
+            }
+            
+            let health = state.health.get("BTC/USD").unwrap();
+            assert_eq!((health.checksum_ok, health.checksum_fail), (2, 0));
+            assert_eq!(state.orderbooks.get("BTC/USD").unwrap().subscribed_depth, 25);
+        }
+        
+        let _ = std::fs::remove_dir_all(incidents_dir);
+    }
+
+    #[tokio::test]
+    async fn test_strict_book_flags_invalid_books() {
+        let incidents_dir = std::env::temp_dir().join(format!("blackbox_strict_test_{}", std::process::id()));
+        let incident_manager = Arc::new(IncidentManager::new(incidents_dir.clone()).unwrap());
+        
+        for strict in [false, true] {
+            let mut state = AppState::new();
+            state.strict_book = strict;
+            
+            let (tx, mut rx) = mpsc::unbounded_channel();
+            tx.send(snapshot_event()).unwrap();
+            // Negative quantity slips past the zero-qty removal
+            tx.send(WsEvent::BookUpdate {
+                symbol: "BTC/USD".to_string(),
+                bids: level(dec!(99), dec!(-1)),
+                asks: vec![],
+                checksum: None,
+                timestamp: None,
+            }).unwrap();
+            drop(tx);
+            process_ws_events(&state, &incident_manager, &mut rx).await;
+            assert_eq!(state.invalid_books.contains("BTC/USD"), strict);
+            
+            // Removing the bad level clears the flag
+            let (tx, mut rx) = mpsc::unbounded_channel();
+            tx.send(WsEvent::BookUpdate {
+                symbol: "BTC/USD".to_string(),
+                bids: level(dec!(99), dec!(0)),
+                asks: vec![],
+                checksum: None,
+                timestamp: None,
+            }).unwrap();
+            drop(tx);
+            process_ws_events(&state, &incident_manager, &mut rx).await;
+            assert!(!state.invalid_books.contains("BTC/USD"));
+        }
+        
+        let _ = std::fs::remove_dir_all(incidents_dir);
+    }
+
+    #[tokio::test]
+    async fn test_both_processors_keep_integrity_state() {
+        let mut reference = Orderbook::new();
+        reference.apply_snapshot(level(dec!(100), dec!(1)), level(dec!(101), dec!(1)));
+        let matching = reference.checksum(1, 8);
+        
+        for with_logging in [false, true] {
+            let incidents_dir = std::env::temp_dir()
+                .join(format!("blackbox_integrity_state_{}_{}", with_logging, std::process::id()));
+            let _ = std::fs::remove_dir_all(&incidents_dir);
+            let incident_manager = Arc::new(IncidentManager::new(incidents_dir.clone()).unwrap());
+            let state = stale_test_state();
+            let (tx, mut rx) = mpsc::unbounded_channel();
+            tx.send(WsEvent::BookSnapshot {
+                symbol: "BTC/USD".to_string(),
+                bids: level(dec!(100), dec!(1)),
+                asks: level(dec!(101), dec!(1)),
+                checksum: Some(matching),
+            }).unwrap();
+            tx.send(update_event(Some(1))).unwrap();
+            drop(tx);
+            if with_logging {
+                process_ws_events_with_logging(&state, &incident_manager, &mut rx).await;
+            } else {
+                process_ws_events(&state, &incident_manager, &mut rx).await;
+            }
+            
+            assert!(!state.integrity_proofs.get("BTC/USD").unwrap().is_match());
+            assert!(state.last_verified_books.contains_key("BTC/USD"));
+            let last = state.get_last_incident().await.unwrap();
+            let kinds: Vec<_> = state.get_events(10).await.iter().map(|e| e.event.kind()).collect();
+            for kind in ["checksum_ok", "checksum_mismatch", "incident_captured"] {
+                assert!(kinds.contains(&kind), "{:?}", kinds);
+            }
+            // Run mode exports the bundle, diffed against the book last matched
+            if !with_logging {
+                let bundle = std::fs::File::open(incidents_dir.join(format!("{}.zip", last.id))).unwrap();
+                assert!(zip::ZipArchive::new(bundle).unwrap().by_name("book_diff.json").is_ok());
+            }
+            let _ = std::fs::remove_dir_all(incidents_dir);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mismatch_captures_recent_removals() {
+        let incidents_dir = std::env::temp_dir().join(format!("blackbox_tombstone_test_{}", std::process::id()));
+        let incident_manager = Arc::new(IncidentManager::new(incidents_dir.clone()).unwrap());
+        
+        let mut state = stale_test_state();
+        state.tombstones = 5;
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        tx.send(snapshot_event()).unwrap();
+        tx.send(WsEvent::BookUpdate {
+            symbol: "BTC/USD".to_string(),
+            bids: vec![],
+            asks: level(dec!(101), dec!(0)),
+            checksum: Some(1), // never matches the book
+            timestamp: None,
+        }).unwrap();
+        drop(tx);
+        process_ws_events_with_logging(&state, &incident_manager, &mut rx).await;
+        
+        let proof = state.integrity_proofs.get("BTC/USD").unwrap();
+        assert!(!proof.is_match());
+        assert_eq!(proof.recent_ask_removals.len(), 1);
+        assert_eq!((proof.recent_ask_removals[0].price, proof.recent_ask_removals[0].qty), (dec!(101), dec!(1)));
+        assert!(proof.recent_bid_removals.is_empty());
+        
+        let _ = std::fs::remove_dir_all(incidents_dir);
+    }
+
+    #[tokio::test]
+    async fn test_negative_qty_diagnosis() {
+        let incidents_dir = std::env::temp_dir().join(format!("blackbox_negative_test_{}", std::process::id()));
+        let incident_manager = Arc::new(IncidentManager::new(incidents_dir.clone()).unwrap());
+        
+        let state = stale_test_state();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        tx.send(snapshot_event()).unwrap();
+        tx.send(WsEvent::BookUpdate {
+            symbol: "BTC/USD".to_string(),
+            bids: vec![],
+            asks: level(dec!(101), dec!(-0.5)),
+            checksum: Some(1),
+            timestamp: None,
+        }).unwrap();
+        drop(tx);
+        process_ws_events_with_logging(&state, &incident_manager, &mut rx).await;
+        
+        let proof = state.integrity_proofs.get("BTC/USD").unwrap();
+        assert!(!proof.is_match());
+        let diagnosis = proof.diagnosis.as_deref().unwrap();
+        assert!(diagnosis.starts_with("negative qty in book: ask -0.5 @ 101"), "{}", diagnosis);
+        
+        let _ = std::fs::remove_dir_all(incidents_dir);
+    }
+
+    #[test]
+    fn test_top_of_book_gauges_follow_updates() {
+        let incidents_dir = std::env::temp_dir().join(format!("blackbox_top_metrics_{}", std::process::id()));
+        let incident_manager = Arc::new(IncidentManager::new(incidents_dir.clone()).unwrap());
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        for with_logging in [false, true] {
+            let recorder = metrics_exporter_prometheus::PrometheusBuilder::new().build_recorder();
+            let state = stale_test_state();
+            let (tx, mut rx) = mpsc::unbounded_channel();
+            tx.send(snapshot_event()).unwrap();
+            tx.send(WsEvent::BookUpdate {
+                symbol: "BTC/USD".to_string(),
+                bids: level(dec!(100.5), dec!(1)),
+                asks: vec![],
+                checksum: None,
+                timestamp: None,
+            })
+            .unwrap();
+            // Not subscribed, so not exported
+            tx.send(WsEvent::BookSnapshot {
+                symbol: "ETH/USD".to_string(),
+                bids: level(dec!(2000), dec!(1)),
+                asks: level(dec!(2001), dec!(1)),
+                checksum: None,
+            })
+            .unwrap();
+            drop(tx);
+            ::metrics::with_local_recorder(&recorder, || runtime.block_on(async {
+                state.set_requested_symbols(vec!["BTC/USD".to_string()]).await;
+                if with_logging {
+                    process_ws_events_with_logging(&state, &incident_manager, &mut rx).await;
+                } else {
+                    process_ws_events(&state, &incident_manager, &mut rx).await;
+                }
+            }));
+
+            let text = recorder.handle().render();
+            assert!(text.contains("book_best_bid{symbol=\"BTC/USD\"} 100.5"), "{}", text);
+            assert!(text.contains("book_best_ask{symbol=\"BTC/USD\"} 101"), "{}", text);
+            assert!(text.contains("book_mid{symbol=\"BTC/USD\"} 100.75"), "{}", text);
+            // 0.5 / 100.75 * 10_000
+            assert!(text.contains("book_spread_bps{symbol=\"BTC/USD\"} 49.62"), "{}", text);
+            assert!(!text.contains("book_mid{symbol=\"ETH/USD\"}"), "{}", text);
+        }
+        let _ = std::fs::remove_dir_all(incidents_dir);
+    }
+
+    #[test]
+    fn test_checksum_metrics_are_exported() {
+        let incidents_dir = std::env::temp_dir().join(format!("blackbox_checksum_metrics_{}", std::process::id()));
+        let incident_manager = Arc::new(IncidentManager::new(incidents_dir.clone()).unwrap());
+        // The local recorder is per thread, so the loops run on this one
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        let mut reference = Orderbook::new();
+        reference.apply_snapshot(level(dec!(100), dec!(1)), level(dec!(101), dec!(1)));
+        reference.apply_updates(level(dec!(99), dec!(2)), vec![]);
+        let matching = WsEvent::BookUpdate {
+            symbol: "BTC/USD".to_string(),
+            bids: vec![],
+            asks: vec![],
+            checksum: Some(reference.checksum(1, 8)),
+            timestamp: None,
+        };
+
+        for with_logging in [false, true] {
+            let recorder = metrics_exporter_prometheus::PrometheusBuilder::new().build_recorder();
+            let state = stale_test_state();
+            let run = |events: Vec<WsEvent>| {
+                let (tx, mut rx) = mpsc::unbounded_channel();
+                for event in events {
+                    tx.send(event).unwrap();
+                }
+                drop(tx);
+                ::metrics::with_local_recorder(&recorder, || runtime.block_on(async {
+                    if with_logging {
+                        process_ws_events_with_logging(&state, &incident_manager, &mut rx).await;
+                    } else {
+                        process_ws_events(&state, &incident_manager, &mut rx).await;
+                    }
+                }));
+                recorder.handle().render()
+            };
+
+            let text = run(vec![snapshot_event(), update_event(Some(1)), update_event(Some(1))]);
+            assert!(text.contains("integrity_proof_last_match{symbol=\"BTC/USD\"} 0"), "{}", text);
+            assert!(text.contains("consecutive_checksum_fails{symbol=\"BTC/USD\"} 2"), "{}", text);
+            assert!(text.contains("checksum_verify_duration_count{symbol=\"BTC/USD\"} 2"), "{}", text);
+
+            let text = run(vec![matching.clone()]);
+            assert!(text.contains("integrity_proof_last_match{symbol=\"BTC/USD\"} 1"), "{}", text);
+            assert!(text.contains("consecutive_checksum_fails{symbol=\"BTC/USD\"} 0"), "{}", text);
+            assert!(text.contains("checksum_verify_duration_count{symbol=\"BTC/USD\"} 3"), "{}", text);
+
+            assert!(run(vec![WsEvent::Connected]).contains("ws_connected 1"));
+            let text = run(vec![WsEvent::Disconnected { cause: DisconnectCause::IdleTimeout }]);
+            assert!(text.contains("ws_connected 0"), "{}", text);
+        }
+        let _ = std::fs::remove_dir_all(incidents_dir);
+    }
+
+    #[tokio::test]
+    async fn test_disconnect_marks_books_stale_and_skips_checksum() {
+        let incidents_dir = std::env::temp_dir().join(format!("blackbox_stale_test_{}", std::process::id()));
+        let incident_manager = Arc::new(IncidentManager::new(incidents_dir.clone()).unwrap());
+        
+        for with_logging in [false, true] {
+            let state = stale_test_state();
+            let (tx, mut rx) = mpsc::unbounded_channel();
+            tx.send(snapshot_event()).unwrap();
+            tx.send(WsEvent::Disconnected { cause: DisconnectCause::Error }).unwrap();
+            tx.send(update_event(Some(1))).unwrap();
+            drop(tx);
+            
+            if with_logging {
+                process_ws_events_with_logging(&state, &incident_manager, &mut rx).await;
+            } else {
+                process_ws_events(&state, &incident_manager, &mut rx).await;
+            }
+            
+            // Update applied to the stale book, but not verified
+            {
+                let book = state.orderbooks.get("BTC/USD").unwrap();
+                assert!(book.stale);
+                assert_eq!(book.bids_vec(None).len(), 2);
+            }
+            assert_eq!(state.health.get("BTC/USD").map_or(0, |h| h.checksum_fail), 0);
+            
+            // The next snapshot clears the marker and verification resumes
+            let (tx, mut rx) = mpsc::unbounded_channel();
+            tx.send(snapshot_event()).unwrap();
+            tx.send(update_event(Some(1))).unwrap();
+            drop(tx);
+            
+            if with_logging {
+                process_ws_events_with_logging(&state, &incident_manager, &mut rx).await;
+            } else {
+                process_ws_events(&state, &incident_manager, &mut rx).await;
+            }
+            
+            let book = state.orderbooks.get("BTC/USD").unwrap();
+            assert!(!book.stale);
+            assert!(book.last_snapshot_ts.is_some());
+            assert_eq!(state.health.get("BTC/USD").unwrap().checksum_fail, 1);
+        }
+        
+        let _ = std::fs::remove_dir_all(incidents_dir);
+    }
+
+    #[test]
+    fn test_parse_rotation_flags() {
+        assert_eq!(parse_byte_size("4096").unwrap(), 4096);
+        assert_eq!(parse_byte_size("512K").unwrap(), 512 << 10);
+        assert_eq!(parse_byte_size("64mb").unwrap(), 64 << 20);
+        assert_eq!(parse_byte_size("2G").unwrap(), 2 << 30);
+        assert!(parse_byte_size("10T").is_err());
+        assert!(parse_byte_size("M").is_err());
+        assert_eq!(parse_duration("1h").unwrap(), Duration::from_secs(3600));
+        assert_eq!(parse_duration("250ms").unwrap(), Duration::from_millis(250));
+        
+        let options = recorder_options(None, None, Duration::ZERO, 64);
+        assert_eq!(options.flush, FlushPolicy { every_frames: Some(64), interval: None });        
+        let cli = Cli::try_parse_from(["blackbox", "run", "--symbols", "BTC/USD", "--record-channels", "book,instrument"]).unwrap();
+        let Commands::Run { record_channels, .. } = cli.command else {
+            panic!("not a run command");
+        };
+        assert_eq!(record_channels, Some(vec!["book".to_string(), "instrument".to_string()]));
+        assert!(Cli::try_parse_from(["blackbox", "run", "--record-channels", "trades"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_http_access_flags() {
+        let cli = Cli::try_parse_from([
+            "blackbox", "replay", "--input", "r.ndjson", "--http-token", "s3cret",
+            "--cors-allow-origin", "http://grafana.local:3000/", "--cors-allow-origin", "*",
+        ]).unwrap();
+        assert_eq!(cli.http_token.as_deref(), Some("s3cret"));
+        assert_eq!(cli.cors_allow_origin, ["http://grafana.local:3000", "*"]);
+        assert!(Cli::try_parse_from(["blackbox", "run", "--cors-allow-origin", "grafana.local"]).is_err());
+        let cli = Cli::try_parse_from(["blackbox", "run"]).unwrap();
+        assert!(cli.http_token.is_none() && cli.cors_allow_origin.is_empty());
+        assert!(cli.rate_limit.is_empty());
+
+        let cli = Cli::try_parse_from([
+            "blackbox", "run", "--rate-limit", "/export-bug=1/min", "--rate-limit", "/record/=off",
+        ]).unwrap();
+        assert_eq!(cli.rate_limit, [
+            ("/export-bug".to_string(), Some(RateLimit::per_minute(1))),
+            ("/record".to_string(), None),
+        ]);
+        for bad in ["/export-bug", "export-bug=5/min", "/fault=5/day", "/fault=0/s"] {
+            assert!(Cli::try_parse_from(["blackbox", "run", "--rate-limit", bad]).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        for (input, expected) in [
+            ("0s", Duration::ZERO),
+            ("0", Duration::ZERO),
+            ("45", Duration::from_secs(45)),
+            ("500ms", Duration::from_millis(500)),
+            ("30s", Duration::from_secs(30)),
+            ("2m", Duration::from_secs(120)),
+            ("1h", Duration::from_secs(3600)),
+            ("1m30s", Duration::from_secs(90)),
+            ("1h2m3s4ms", Duration::from_millis(3_723_004)),
+            ("1500ms", Duration::from_millis(1500)),
+            ("  10s ", Duration::from_secs(10)),
+            ("1m 30s", Duration::from_secs(90)),
+            ("5 s", Duration::from_secs(5)),
+        ] {
+            assert_eq!(parse_duration(input).unwrap(), expected, "{:?}", input);
+        }
+        
+        for input in ["", "   ", "s", "ms", "1.5s", "-1s", "10x", "1m30", "abc", "1d", "99999999999999999999s"] {
+            assert!(parse_duration(input).is_err(), "{:?} should not parse", input);
+        }
+        let err = parse_duration("10x").unwrap_err().to_string();
+        assert!(err.contains("unknown unit 'x'"), "{}", err);
+        let err = parse_duration("1m30").unwrap_err().to_string();
+        assert!(err.contains("30 has no unit"), "{}", err);
+        assert!(parse_duration(&format!("{}h", u64::MAX / 60)).is_err());
+        
+        let cli = Cli::try_parse_from(["blackbox", "run", "--ping-interval", "1m30s"]).unwrap();
+        let Commands::Run { ping_interval, .. } = cli.command else {
+            panic!("not a run command");
+        };
+        assert_eq!(ping_interval, Duration::from_secs(90));
+        assert!(Cli::try_parse_from(["blackbox", "run", "--ping-interval", "soon"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_symbol_depths() {
+        let cli = Cli::try_parse_from(["blackbox", "run", "--symbols", "BTC/USD:1000,ETH/USD:100,SOL/USD", "--depth", "25"]).unwrap();
+        let Commands::Run { symbols, depth, .. } = cli.command else {
+            panic!("not a run command");
+        };
+        assert_eq!(depth, 25);
+        let (symbols, depths) = split_symbol_args(symbols);
+        assert_eq!(symbols, vec!["BTC/USD", "ETH/USD", "SOL/USD"]);
+        assert_eq!(depths, HashMap::from([("BTC/USD".to_string(), 1000), ("ETH/USD".to_string(), 100)]));
+        
+        assert_eq!(parse_symbol_arg(" XBT/EUR : 10 ").unwrap(), SymbolArg { symbol: "XBT/EUR".to_string(), depth: Some(10) });
+        for bad in ["BTC/USD:", "BTC/USD:deep", "BTC/USD:0", ":100", ""] {
+            assert!(parse_symbol_arg(bad).is_err(), "{:?} should not parse", bad);
+        }
+        assert!(Cli::try_parse_from(["blackbox", "tui", "--symbols", "BTC/USD:x"]).is_err());
+    }
+
+    #[test]
+    fn test_tui_fault_rule() {
+        assert_eq!(
+            build_fault_rule_from_str("delay", Some(7), 1, 900, 3).unwrap(),
+            FaultRule::OnceAt { index: 7, fault: FaultType::Delay { ms: 900 } }
+        );
+        assert_eq!(
+            build_fault_rule_from_str("mutate_price", Some(2), 1, 250, -4).unwrap(),
+            FaultRule::OnceAt { index: 2, fault: FaultType::MutatePrice { delta_ticks: -4 } }
+        );
+        assert_eq!(build_fault_rule_from_str("drop", None, 1, 250, 1).unwrap(), FaultRule::None);
+        assert_eq!(build_fault_rule_from_str("none", Some(3), 1, 250, 1).unwrap(), FaultRule::None);
+        // A typo is an error, not a replay with no fault
+        assert!(build_fault_rule_from_str("dropp", Some(3), 1, 250, 1).is_err());
+        assert!(build_fault_rule_from_str("dropp", None, 1, 250, 1).is_err());
+    }
+
+    #[test]
+    fn test_parse_symbols_file() {
+        let content = "# watchlist\r\nBTC/USD:1000\r\n\r\n  ETH/USD   # majors\r\nSOL/USD:25\n# done\n";
+        let parsed = parse_symbols_file(content).unwrap();
+        let expected = vec![
+            SymbolArg { symbol: "BTC/USD".to_string(), depth: Some(1000) },
+            SymbolArg { symbol: "ETH/USD".to_string(), depth: None },
+            SymbolArg { symbol: "SOL/USD".to_string(), depth: Some(25) },
+        ];
+        assert_eq!(parsed, expected);
+        assert!(parse_symbols_file("").unwrap().is_empty());
+        
+        let err = parse_symbols_file("BTC/USD\nETH/USD:lots\n").unwrap_err();
+        assert_eq!(err.to_string(), "line 2");
+        
+        // --symbols first, then the file; the first mention wins
+        let dir = std::env::temp_dir().join(format!("blackbox_symbols_file_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("watchlist.txt");
+        std::fs::write(&path, "ETH/USD:100\r\nXRP/USD\r\nBTC/USD:10\r\nXRP/USD:25\r\n").unwrap();
+        let cli = vec![parse_symbol_arg("BTC/USD:1000").unwrap()];
+        let (symbols, depths) = split_symbol_args(resolve_symbols(cli, Some(&path)).unwrap());
+        assert_eq!(symbols, vec!["BTC/USD", "ETH/USD", "XRP/USD"]);
+        assert_eq!(depths, HashMap::from([("BTC/USD".to_string(), 1000), ("ETH/USD".to_string(), 100)]));
+        assert!(resolve_symbols(vec![], Some(&dir.join("missing.txt"))).is_err());
+        let _ = std::fs::remove_dir_all(dir);
+        
+        let cli = Cli::try_parse_from(["blackbox", "run", "--ws-url", "ws://127.0.0.1:9001"]).unwrap();
+        let Commands::Run { ws_url, .. } = cli.command else {
+            panic!("not a run command");
+        };
+        assert_eq!(ws_url, "ws://127.0.0.1:9001");
+        assert!(Cli::try_parse_from(["blackbox", "run", "--ws-url", "https://ws.kraken.com/v2"]).is_err());
+    }
+
+    #[test]
+    fn test_tui_commands_keep_logs_off_the_screen() {
+        let cli = Cli::try_parse_from(["blackbox", "run", "--symbols", "BTC/USD", "--tui"]).unwrap();
+        assert!(cli.command.uses_tui());
+        assert!(cli.log_file.is_none());
+        let cli = Cli::try_parse_from(["blackbox", "tui", "--log-file", "blackbox.log"]).unwrap();
+        assert!(cli.command.uses_tui());
+        assert_eq!(cli.log_file, Some(PathBuf::from("blackbox.log")));
+        assert!(!Cli::try_parse_from(["blackbox", "run", "--symbols", "BTC/USD"]).unwrap().command.uses_tui());
+
+        assert_eq!(log_destination(None, true), Some(PathBuf::from(DEFAULT_TUI_LOG)));
+        assert_eq!(log_destination(None, false), None);
+        assert_eq!(log_destination(Some(std::path::Path::new("a.log")), false), Some(PathBuf::from("a.log")));
+    }
+
+    #[test]
+    fn test_json_log_lines() {
+        let cli = Cli::try_parse_from(["blackbox", "inspect", "--input", "x.ndjson", "--log-format", "json"]).unwrap();
+        assert_eq!(cli.log_format, LogFormat::Json);
+        assert!(Cli::try_parse_from(["blackbox", "inspect", "--input", "x.ndjson", "--log-format", "xml"]).is_err());
+
+        let dir = std::env::temp_dir().join(format!("blackbox_json_logs_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let (writer, guard) = rolling_log_writer(&dir.join("blackbox.log")).unwrap();
+        tracing::subscriber::with_default(log_subscriber(LogFormat::Json, writer, false), || {
+            // The default filter only lets errors through
+            error!(symbol = "BTC/USD", "Checksum mismatch");
+            error!("Second line with \"quotes\"");
+        });
+        drop(guard); // flushes
+
+        let files: Vec<_> = std::fs::read_dir(&dir).unwrap().map(|e| e.unwrap().path()).collect();
+        assert_eq!(files.len(), 1);
+        assert!(files[0].file_name().unwrap().to_str().unwrap().starts_with("blackbox.log."));
+        let content = std::fs::read_to_string(&files[0]).unwrap();
+        let lines: Vec<serde_json::Value> = content.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["level"], "ERROR");
+        assert_eq!(lines[0]["fields"]["message"], "Checksum mismatch");
+        assert_eq!(lines[0]["fields"]["symbol"], "BTC/USD");
+        assert_eq!(lines[1]["fields"]["message"], "Second line with \"quotes\"");
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_record_status_follows_rotation() {
+        let dir = std::env::temp_dir().join(format!("blackbox_record_status_{}", std::process::id()));
+        let state = AppState::new();
+        assert!(!state.recording_status().await.recording);
+        
+        let options = RecorderOptions {
+            rotation: RotationPolicy { max_bytes: Some(2048), max_duration: None },
+            ..Default::default()
+        };
+        state.start_recording(Recorder::new_with_options(dir.join("status.ndjson"), options).unwrap()).await;
+        let frame = r#"{"channel":"heartbeat"}"#;
+        for _ in 0..200 {
+            state.record_frame(frame).await;
+        }
+        
+        // Stats catch up with the writer and keep counting across segments
+        let mut status = state.recording_status().await;
+        for _ in 0..200 {
+            if status.stats.is_some_and(|s| s.frames_written == 200) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            status = state.recording_status().await;
+        }
+        let stats = status.stats.unwrap();
+        assert!(status.recording);
+        assert_eq!(stats.frames_written, 200);
+        assert!(stats.bytes_written > 2048);
+        assert_eq!(status.dropped_frames, 0);
+        let json = serde_json::to_value(&status).unwrap();
+        assert_eq!(json["frames_written"], 200);
+        
+        state.stop_recording().await;
+        let status = state.recording_status().await;
+        assert!(!status.recording);
+        assert!(status.stats.is_none());
+        
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_drains_pending_frames() {
+        let dir = std::env::temp_dir().join(format!("blackbox_shutdown_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let incident_manager = Arc::new(IncidentManager::new(dir.join("incidents")).unwrap());
+        let state = AppState::new();
+        let record_path = dir.join("session.ndjson");
+        state.start_recording(Recorder::new_with_options(record_path.clone(), RecorderOptions::default()).unwrap()).await;
+        
+        // A source that queues its frames up front and then never hangs up
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let client = tokio::spawn(async move {
+            for _ in 0..500 {
+                tx.send(WsEvent::Frame(r#"{"channel":"heartbeat"}"#.to_string())).unwrap();
+            }
+            std::future::pending::<()>().await;
+        });
+        tokio::task::yield_now().await;
+        let processor_state = state.clone();
+        let processor_incidents = incident_manager.clone();
+        let processor = tokio::spawn(async move {
+            process_ws_events(&processor_state, &processor_incidents, &mut rx).await;
+        });
+        
+        drain_and_close(&state, client, processor).await;
+        
+        let recorded = std::fs::read_to_string(&record_path).unwrap();
+        assert_eq!(recorded.lines().filter(|line| line.contains("heartbeat")).count(), 500);
+        assert!(state.recorder.read().await.is_none());
+        let summary: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(dir.join("health_summary.json")).unwrap()).unwrap();
+        assert!(summary.is_object());
+        
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -1,12 +1,95 @@
-use metrics::{counter, gauge, histogram};
+use metrics::{counter, describe_counter, describe_gauge, describe_histogram, gauge, histogram, Unit};
+use metrics_exporter_prometheus::{PrometheusBuilder, PrometheusHandle};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use std::net::SocketAddr;
 use std::sync::OnceLock;
+use std::time::Duration;
+use tracing::{info, warn};
 
-static METRICS_INIT: OnceLock<()> = OnceLock::new();
+static METRICS_HANDLE: OnceLock<Option<PrometheusHandle>> = OnceLock::new();
 
-pub fn init_metrics() {
-    METRICS_INIT.get_or_init(|| {
-        // Metrics will be registered automatically when used
-    });
+/// Install the global Prometheus recorder, once per process. `/metrics` on
+/// the HTTP server renders it; with `addr`, the exporter also serves it
+/// there. Neither a taken `addr` nor a recorder installed already stops the
+/// run: they're logged, and the metrics go without that endpoint.
+pub fn init_metrics(addr: Option<SocketAddr>) -> Option<PrometheusHandle> {
+    METRICS_HANDLE
+        .get_or_init(|| {
+            let handle = install(addr)?;
+            describe_metrics();
+            Some(handle)
+        })
+        .clone()
+}
+
+/// The handle `init_metrics` installed, if any
+pub fn handle() -> Option<PrometheusHandle> {
+    METRICS_HANDLE.get().cloned().flatten()
+}
+
+fn install(addr: Option<SocketAddr>) -> Option<PrometheusHandle> {
+    let Some(addr) = addr else {
+        return install_recorder();
+    };
+    match PrometheusBuilder::new().with_http_listener(addr).build() {
+        Ok((recorder, exporter)) => {
+            let handle = recorder.handle();
+            if let Err(e) = ::metrics::set_global_recorder(recorder) {
+                warn!("Failed to install the metrics recorder: {}", e);
+                return None;
+            }
+            tokio::spawn(exporter);
+            info!("Prometheus exporter listening on http://{}", addr);
+            Some(handle)
+        }
+        Err(e) => {
+            warn!("Prometheus exporter not started on {}: {}; metrics are still on /metrics", addr, e);
+            install_recorder()
+        }
+    }
+}
+
+fn install_recorder() -> Option<PrometheusHandle> {
+    PrometheusBuilder::new()
+        .install_recorder()
+        .map_err(|e| warn!("Failed to install the metrics recorder: {}", e))
+        .ok()
+}
+
+/// HELP lines (and units) for everything exported, including the
+/// WebSocket client's `reconnects_total`
+fn describe_metrics() {
+    describe_counter!("checksum_ok_total", "Book checksums that matched Kraken's");
+    describe_counter!("checksum_fail_total", "Book checksums that didn't match Kraken's");
+    describe_counter!("messages_total", "Book messages received");
+    describe_gauge!("messages_per_second", "Smoothed rate of book messages");
+    describe_counter!("book_crossed_total", "Times a book became crossed");
+    describe_counter!("resyncs_total", "Re-subscriptions after repeated checksum failures");
+    describe_counter!("recording_dropped_frames_total", "Frames dropped because the recording writer fell behind");
+    describe_counter!("frames_recorded_total", "Frames written to recordings");
+    describe_counter!("recording_bytes_total", Unit::Bytes, "NDJSON bytes written to recordings, headers included");
+    describe_gauge!("recording_active", "1 while frames are being recorded");
+    describe_counter!("incidents_total", "Incidents recorded, by reason");
+    describe_gauge!("incident_bundle_bytes", Unit::Bytes, "Size of the last incident bundle written");
+    describe_gauge!("replay_progress", "Share of the recording replayed, 0 to 1");
+    describe_counter!("replay_frames_emitted_total", "Frames the replayer played, faults included");
+    describe_counter!("replay_faults_applied_total", "Faults the replayer applied, by type");
+    describe_gauge!("ws_connected", "1 while the WebSocket is connected");
+    describe_counter!("reconnects_total", "WebSocket reconnections, by the cause of the disconnect");
+    describe_gauge!("orderbook_asks_depth", "Ask levels in the book");
+    describe_gauge!("orderbook_bids_depth", "Bid levels in the book");
+    describe_gauge!("book_levels_total", "Levels in the book, both sides");
+    describe_gauge!("book_bytes_estimate", Unit::Bytes, "Approximate memory held by the book");
+    describe_gauge!("book_best_bid", "Best bid price (approximate)");
+    describe_gauge!("book_best_ask", "Best ask price (approximate)");
+    describe_gauge!("book_mid", "Mid price (approximate)");
+    describe_gauge!("book_spread_bps", "Spread relative to mid, in basis points");
+    describe_histogram!("message_latency_ms", Unit::Milliseconds, "Exchange timestamp to local receipt");
+    describe_counter!("latency_anomaly_total", "Feed latencies clamped for clock skew or stale frames");
+    describe_histogram!("checksum_verify_duration", Unit::Microseconds, "Time to build and check a book checksum");
+    describe_gauge!("integrity_proof_last_match", "Whether the last checksum matched (1/0)");
+    describe_gauge!("consecutive_checksum_fails", "Checksum mismatches in a row");
 }
 
 pub fn record_checksum_ok(symbol: &str) {
@@ -17,12 +100,70 @@ pub fn record_checksum_fail(symbol: &str) {
     counter!("checksum_fail_total", "symbol" => symbol.to_string()).increment(1);
 }
 
+pub fn record_resync(symbol: &str) {
+    counter!("resyncs_total", "symbol" => symbol.to_string()).increment(1);
+}
+
 pub fn record_message(symbol: &str) {
     counter!("messages_total", "symbol" => symbol.to_string()).increment(1);
 }
 
-pub fn record_reconnect() {
-    counter!("reconnects_total").increment(1);
+/// Smoothed message rate, from the msg_rate task
+pub fn update_messages_per_second(symbol: &str, rate: f64) {
+    gauge!("messages_per_second", "symbol" => symbol.to_string()).set(rate);
+}
+
+pub fn record_book_crossed(symbol: &str) {
+    counter!("book_crossed_total", "symbol" => symbol.to_string()).increment(1);
+}
+
+/// A frame the background recorder had to drop because the disk fell behind
+pub fn record_recording_dropped() {
+    counter!("recording_dropped_frames_total").increment(1);
+}
+
+/// Frames and NDJSON bytes (headers included) written to recordings since
+/// the last report
+pub fn record_recording_progress(frames: u64, bytes: u64) {
+    counter!("frames_recorded_total").increment(frames);
+    counter!("recording_bytes_total").increment(bytes);
+}
+
+/// 1 while frames are being recorded
+pub fn update_recording_active(active: bool) {
+    gauge!("recording_active").set(if active { 1.0 } else { 0.0 });
+}
+
+/// An incident was recorded, bundled or not
+pub fn record_incident(reason: &str) {
+    counter!("incidents_total", "reason" => reason.to_string()).increment(1);
+}
+
+/// Size of the last incident bundle written
+pub fn update_incident_bundle_bytes(bytes: u64) {
+    gauge!("incident_bundle_bytes").set(bytes as f64);
+}
+
+/// A frame the replayer played, faults included
+pub fn record_replay_frame(progress: f64) {
+    counter!("replay_frames_emitted_total").increment(1);
+    update_replay_progress(progress);
+}
+
+/// Share of the recording played, 0..1
+pub fn update_replay_progress(progress: f64) {
+    gauge!("replay_progress").set(progress);
+}
+
+/// A fault the replayer applied, by `FaultType::name`
+pub fn record_replay_fault(fault_type: &str) {
+    counter!("replay_faults_applied_total", "type" => fault_type.to_string()).increment(1);
+}
+
+/// 1 while the WebSocket is connected. Reconnects are counted by the
+/// client itself, as `reconnects_total{cause}`.
+pub fn update_ws_connected(connected: bool) {
+    gauge!("ws_connected").set(if connected { 1.0 } else { 0.0 });
 }
 
 pub fn update_orderbook_depth(symbol: &str, asks: usize, bids: usize) {
@@ -30,7 +171,54 @@ pub fn update_orderbook_depth(symbol: &str, asks: usize, bids: usize) {
     gauge!("orderbook_bids_depth", "symbol" => symbol.to_string()).set(bids as f64);
 }
 
+/// Top of book, for charting next to integrity failures. Decimal to f64
+/// keeps about 15 significant digits: fine for a chart, but these aren't
+/// exact prices. A missing side leaves its gauges at their last value.
+pub fn update_top_of_book(
+    symbol: &str,
+    best_bid: Option<Decimal>,
+    best_ask: Option<Decimal>,
+    mid: Option<Decimal>,
+    spread_bps: Option<Decimal>,
+) {
+    let gauges = [
+        ("book_best_bid", best_bid),
+        ("book_best_ask", best_ask),
+        ("book_mid", mid),
+        ("book_spread_bps", spread_bps),
+    ];
+    for (name, value) in gauges {
+        if let Some(value) = value.and_then(|value| value.to_f64()) {
+            gauge!(name, "symbol" => symbol.to_string()).set(value);
+        }
+    }
+}
+
+pub fn update_book_size(symbol: &str, levels: usize, bytes: usize) {
+    gauge!("book_levels_total", "symbol" => symbol.to_string()).set(levels as f64);
+    gauge!("book_bytes_estimate", "symbol" => symbol.to_string()).set(bytes as f64);
+}
+
+/// Exchange timestamp -> local receive time
 pub fn record_latency(symbol: &str, latency_ms: f64) {
     histogram!("message_latency_ms", "symbol" => symbol.to_string()).record(latency_ms);
 }
 
+/// A feed latency that had to be clamped: a skewed clock or a stale frame
+pub fn record_latency_anomaly(symbol: &str) {
+    counter!("latency_anomaly_total", "symbol" => symbol.to_string()).increment(1);
+}
+
+/// Time spent building and checking the checksum, in microseconds: most
+/// verifications take well under a millisecond
+pub fn record_verify_latency(symbol: &str, elapsed: Duration) {
+    histogram!("checksum_verify_duration", "symbol" => symbol.to_string()).record(elapsed.as_micros() as f64);
+}
+
+/// Where the symbol's verification stands after a checksum: whether it
+/// matched (1/0), and how many mismatches in a row led up to it
+pub fn update_checksum_streak(symbol: &str, matched: bool, consecutive_fails: u64) {
+    gauge!("integrity_proof_last_match", "symbol" => symbol.to_string()).set(if matched { 1.0 } else { 0.0 });
+    gauge!("consecutive_checksum_fails", "symbol" => symbol.to_string()).set(consecutive_fails as f64);
+}
+
@@ -0,0 +1,131 @@
+//! Per-symbol message rates. A background task samples each symbol's
+//! `total_msgs` once a second and smooths the per-interval rate with an
+//! exponentially weighted moving average, so `msg_rate_estimate` (the TUI's
+//! Msg/s, `/health`) and the `messages_per_second` gauge follow the feed
+//! without jumping on every burst.
+
+use crate::metrics;
+use crate::state::AppState;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// How often `total_msgs` is sampled
+pub const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Time constant of the average: a step change in rate is ~63% reflected
+/// after this long
+const SMOOTHING: Duration = Duration::from_secs(5);
+
+/// EWMA of one symbol's message rate
+#[derive(Debug, Default)]
+pub struct RateEstimator {
+    last: Option<(Instant, u64)>, // previous sample: when, and the running total then
+    rate: f64, // messages per second
+}
+
+impl RateEstimator {
+    /// Fold in the running total as sampled at `now`, returning the rate.
+    /// Weighted by the time since the previous sample, so a late tick
+    /// counts for more; the first sample only sets the baseline.
+    pub fn sample(&mut self, total: u64, now: Instant) -> f64 {
+        if let Some((then, last_total)) = self.last {
+            let elapsed = now.saturating_duration_since(then).as_secs_f64();
+            if elapsed > 0.0 {
+                // A total below the last one means the symbol's health was reset
+                let current = total.saturating_sub(last_total) as f64 / elapsed;
+                let alpha = 1.0 - (-elapsed / SMOOTHING.as_secs_f64()).exp();
+                self.rate += alpha * (current - self.rate);
+            }
+        }
+        self.last = Some((now, total));
+        self.rate
+    }
+}
+
+/// One round of sampling: update every symbol's rate and drop estimators of
+/// symbols no longer tracked
+fn sample_all(state: &AppState, estimators: &mut HashMap<String, RateEstimator>, now: Instant) {
+    estimators.retain(|symbol, _| state.health.contains_key(symbol));
+    for mut health in state.health.iter_mut() {
+        let symbol = health.key().clone();
+        let rate = estimators.entry(symbol.clone()).or_default().sample(health.total_msgs, now);
+        health.update_msg_rate(rate);
+        metrics::update_messages_per_second(&symbol, rate);
+    }
+}
+
+/// Sample every `SAMPLE_INTERVAL` until the runtime shuts down
+pub async fn sample_forever(state: AppState) {
+    let mut estimators = HashMap::new();
+    let mut ticker = tokio::time::interval(SAMPLE_INTERVAL);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        ticker.tick().await;
+        sample_all(&state, &mut estimators, Instant::now());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blackbox_core::health::SymbolHealth;
+
+    #[test]
+    fn test_rate_converges_on_a_steady_feed() {
+        let start = Instant::now();
+        let mut estimator = RateEstimator::default();
+        assert_eq!(estimator.sample(0, start), 0.0);
+        // 100 msg/s for a second moves the average 1 - e^(-1/5) of the way
+        let alpha = 1.0 - (-0.2f64).exp();
+        let rate = estimator.sample(100, start + Duration::from_secs(1));
+        assert!((rate - 100.0 * alpha).abs() < 1e-9, "{}", rate);
+        let mut rate = rate;
+        for second in 2..=60 {
+            rate = estimator.sample(100 * second, start + Duration::from_secs(second));
+        }
+        assert!((rate - 100.0).abs() < 0.01, "{}", rate);
+
+        // The feed stops: the rate decays rather than dropping to 0 at once
+        let stopped = estimator.sample(6000, start + Duration::from_secs(61));
+        assert!((stopped - rate * (1.0 - alpha)).abs() < 1e-9, "{}", stopped);
+    }
+
+    #[test]
+    fn test_rate_weights_by_elapsed_time() {
+        let start = Instant::now();
+        let mut one_tick = RateEstimator::default();
+        one_tick.sample(0, start);
+        // A single sample 5s late reflects the same step as five on time...
+        let late = one_tick.sample(250, start + Duration::from_secs(5));
+        let mut five_ticks = RateEstimator::default();
+        five_ticks.sample(0, start);
+        let mut on_time = 0.0;
+        for second in 1..=5 {
+            on_time = five_ticks.sample(50 * second, start + Duration::from_secs(second));
+        }
+        assert!((late - on_time).abs() < 1e-9, "{} vs {}", late, on_time);
+        // ...and a repeated instant changes nothing
+        assert_eq!(one_tick.sample(300, start + Duration::from_secs(5)), late);
+        // A reset total reads as silence, not a negative rate
+        assert!(one_tick.sample(0, start + Duration::from_secs(6)) >= 0.0);
+    }
+
+    #[test]
+    fn test_sample_all_updates_health() {
+        let state = AppState::new();
+        state.health.insert("BTC/USD".to_string(), SymbolHealth::new("BTC/USD".to_string()));
+        state.health.insert("ETH/USD".to_string(), SymbolHealth::new("ETH/USD".to_string()));
+        let mut estimators = HashMap::new();
+        let start = Instant::now();
+        sample_all(&state, &mut estimators, start);
+
+        state.health.get_mut("BTC/USD").unwrap().total_msgs = 40;
+        sample_all(&state, &mut estimators, start + Duration::from_secs(1));
+        assert!(state.health.get("BTC/USD").unwrap().msg_rate_estimate > 0.0);
+        assert_eq!(state.health.get("ETH/USD").unwrap().msg_rate_estimate, 0.0);
+
+        state.health.remove("ETH/USD");
+        sample_all(&state, &mut estimators, start + Duration::from_secs(2));
+        assert_eq!(estimators.len(), 1);
+    }
+}
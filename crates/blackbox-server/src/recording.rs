@@ -0,0 +1,513 @@
+//! Background recording: frames are queued by the processor and written to
+//! disk on a blocking task, so slow I/O never stalls book processing
+
+use crate::disk::{DiskCheck, DiskGuard};
+use blackbox_core::recorder::{recording_segments, FlushPolicy, Recorder, RecorderOptions, RecordingStats, SymbolRecorder};
+use chrono::{DateTime, Utc};
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+/// Frames buffered between the processor and the writer task
+pub const RECORD_QUEUE_CAPACITY: usize = 16_384;
+
+/// How often the writer checks the recording against its `DiskGuard`, besides on rotation
+const DISK_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Reported by the writer task
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecorderEvent {
+    Rotated(PathBuf), // a new segment was started
+    DiskFull { used: u64, limit: u64 }, // recording stopped by the disk guard
+}
+
+struct QueuedFrame {
+    ts: DateTime<Utc>,
+    raw_frame: String,
+    decoded_event: Option<String>,
+    symbol: Option<String>, // book frames, for per-symbol recordings
+}
+
+/// Where the writer task puts frames: one recording, or one file per symbol
+#[allow(clippy::large_enum_variant)] // Moved into the writer task once
+pub enum RecordSink {
+    Single(Recorder),
+    PerSymbol(SymbolRecorder),
+}
+
+impl From<Recorder> for RecordSink {
+    fn from(recorder: Recorder) -> Self {
+        RecordSink::Single(recorder)
+    }
+}
+
+impl From<SymbolRecorder> for RecordSink {
+    fn from(recorder: SymbolRecorder) -> Self {
+        RecordSink::PerSymbol(recorder)
+    }
+}
+
+impl RecordSink {
+    /// Open a recording at `path`, or a per-symbol recording in directory `path`
+    pub fn open(path: PathBuf, options: RecorderOptions, per_symbol: bool) -> anyhow::Result<Self> {
+        if per_symbol {
+            Ok(SymbolRecorder::new(path, options)?.into())
+        } else {
+            Ok(Recorder::new_with_options(path, options)?.into())
+        }
+    }
+
+    /// Path shown as the recording: the file, or the per-symbol directory
+    pub fn path(&self) -> PathBuf {
+        match self {
+            RecordSink::Single(recorder) => recorder.path().clone(),
+            RecordSink::PerSymbol(recorder) => recorder.dir().clone(),
+        }
+    }
+
+    fn record(&mut self, frame: &QueuedFrame) -> anyhow::Result<Option<PathBuf>> {
+        let decoded_event = frame.decoded_event.as_deref();
+        match self {
+            RecordSink::Single(recorder) => recorder.record_frame_at(frame.ts, &frame.raw_frame, decoded_event),
+            RecordSink::PerSymbol(recorder) => {
+                // Rotating one symbol's file doesn't move the recording
+                recorder.record_frame_at(frame.ts, &frame.raw_frame, decoded_event, frame.symbol.as_deref())?;
+                Ok(None)
+            }
+        }
+    }
+
+    fn flush(&mut self) -> anyhow::Result<()> {
+        match self {
+            RecordSink::Single(recorder) => recorder.flush(),
+            RecordSink::PerSymbol(recorder) => recorder.flush(),
+        }
+    }
+
+    fn close(&mut self) -> anyhow::Result<()> {
+        match self {
+            RecordSink::Single(recorder) => recorder.close(),
+            RecordSink::PerSymbol(recorder) => recorder.close(),
+        }
+    }
+
+    fn stats(&self) -> RecordingStats {
+        match self {
+            RecordSink::Single(recorder) => recorder.stats(),
+            RecordSink::PerSymbol(recorder) => recorder.stats(),
+        }
+    }
+
+    fn flush_policy(&self) -> FlushPolicy {
+        match self {
+            RecordSink::Single(recorder) => recorder.flush_policy(),
+            RecordSink::PerSymbol(recorder) => recorder.flush_policy(),
+        }
+    }
+
+    /// Every file of the recording, oldest segments first
+    fn files(&self) -> Vec<PathBuf> {
+        recording_segments(&self.path()).unwrap_or_default()
+    }
+
+    /// Files still being written
+    fn open_files(&self) -> Vec<PathBuf> {
+        match self {
+            RecordSink::Single(recorder) => vec![recorder.path().clone()],
+            RecordSink::PerSymbol(recorder) => recorder.paths(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Queue {
+    frames: VecDeque<QueuedFrame>,
+    closed: bool,
+}
+
+struct Shared {
+    queue: Mutex<Queue>,
+    ready: Condvar,
+    capacity: usize,
+    dropped: AtomicU64,
+    stats: Mutex<RecordingStats>, // as of the writer's last batch
+}
+
+impl Shared {
+    /// Queue a frame, dropping the oldest one if the writer is `capacity` behind
+    fn push(&self, frame: QueuedFrame) {
+        let mut queue = self.queue.lock().unwrap();
+        if queue.closed {
+            return;
+        }
+        if queue.frames.len() >= self.capacity {
+            queue.frames.pop_front();
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            crate::metrics::record_recording_dropped();
+        }
+        queue.frames.push_back(frame);
+        drop(queue);
+        self.ready.notify_one();
+    }
+
+    fn close(&self) {
+        self.queue.lock().unwrap().closed = true;
+        self.ready.notify_one();
+    }
+}
+
+/// A `Recorder` driven by a dedicated writer task. `record` never blocks on
+/// disk; when the writer falls `capacity` frames behind, the oldest queued
+/// frame is dropped and counted.
+pub struct AsyncRecorder {
+    shared: Arc<Shared>,
+    writer: Mutex<Option<JoinHandle<()>>>,
+    per_symbol: bool,
+}
+
+impl AsyncRecorder {
+    /// Start the writer task, reporting rotations and a full disk to `events`
+    /// if given. With a `guard`, the recording's files are kept under its limit.
+    pub fn spawn(
+        recorder: impl Into<RecordSink>,
+        capacity: usize,
+        events: Option<mpsc::UnboundedSender<RecorderEvent>>,
+        guard: Option<DiskGuard>,
+    ) -> Self {
+        let recorder = recorder.into();
+        let per_symbol = matches!(recorder, RecordSink::PerSymbol(_));
+        let shared = Arc::new(Shared {
+            queue: Mutex::new(Queue::default()),
+            ready: Condvar::new(),
+            capacity: capacity.max(1),
+            dropped: AtomicU64::new(0),
+            stats: Mutex::new(recorder.stats()),
+        });
+        let writer_shared = shared.clone();
+        let writer = tokio::task::spawn_blocking(move || write_frames(recorder, &writer_shared, events, guard));
+
+        Self {
+            shared,
+            writer: Mutex::new(Some(writer)),
+            per_symbol,
+        }
+    }
+
+    /// Queue a frame received now. `symbol` routes book frames in a
+    /// per-symbol recording and is ignored otherwise.
+    pub fn record(&self, raw_frame: &str, decoded_event: Option<&str>, symbol: Option<&str>) {
+        self.shared.push(QueuedFrame {
+            ts: Utc::now(),
+            raw_frame: raw_frame.to_string(),
+            decoded_event: decoded_event.map(|s| s.to_string()),
+            symbol: symbol.map(|s| s.to_string()),
+        });
+    }
+
+    /// Whether frames need a symbol to be routed
+    pub fn per_symbol(&self) -> bool {
+        self.per_symbol
+    }
+
+    /// Frames dropped because the writer couldn't keep up
+    pub fn dropped_frames(&self) -> u64 {
+        self.shared.dropped.load(Ordering::Relaxed)
+    }
+
+    /// What has reached the recorder so far; frames still queued aren't counted
+    pub fn stats(&self) -> RecordingStats {
+        *self.shared.stats.lock().unwrap()
+    }
+
+    /// Stop accepting frames, write everything still queued and close the file
+    pub async fn close(&self) {
+        self.shared.close();
+        let writer = self.writer.lock().unwrap().take();
+        if let Some(writer) = writer {
+            if let Err(e) = writer.await {
+                warn!("Recording writer task failed: {}", e);
+            }
+        }
+    }
+}
+
+impl Drop for AsyncRecorder {
+    fn drop(&mut self) {
+        // The writer drains the queue and closes the file on its own
+        self.shared.close();
+    }
+}
+
+/// Writer task: drain the queue until it is closed and empty. Once the disk
+/// guard stops the recording, queued frames are discarded.
+fn write_frames(
+    mut recorder: RecordSink,
+    shared: &Shared,
+    events: Option<mpsc::UnboundedSender<RecorderEvent>>,
+    guard: Option<DiskGuard>,
+) {
+    let send = |event| {
+        if let Some(events) = &events {
+            let _ = events.send(event);
+        }
+    };
+    // Idle recordings still get flushed on the policy's interval
+    let idle_flush = recorder.flush_policy().interval;
+    let mut stopped = false;
+    let mut last_disk_check = Instant::now();
+    let mut reported = recorder.stats();
+    crate::metrics::record_recording_progress(reported.frames_written, reported.bytes_written);
+    loop {
+        let batch: Vec<QueuedFrame> = {
+            let mut queue = shared.queue.lock().unwrap();
+            while queue.frames.is_empty() && !queue.closed {
+                match idle_flush {
+                    Some(interval) => {
+                        let (guard, timeout) = shared.ready.wait_timeout(queue, interval).unwrap();
+                        queue = guard;
+                        if timeout.timed_out() && queue.frames.is_empty() && !stopped {
+                            if let Err(e) = recorder.flush() {
+                                warn!("Failed to flush recording: {}", e);
+                            }
+                        }
+                    }
+                    None => queue = shared.ready.wait(queue).unwrap(),
+                }
+            }
+            if queue.frames.is_empty() {
+                break;
+            }
+            queue.frames.drain(..).collect()
+        };
+
+        if stopped {
+            continue;
+        }
+        for frame in batch {
+            let rotated = match recorder.record(&frame) {
+                Ok(Some(path)) => {
+                    send(RecorderEvent::Rotated(path));
+                    true
+                }
+                Ok(None) => false,
+                Err(e) => {
+                    warn!("Failed to record frame: {}", e);
+                    false
+                }
+            };
+            let Some(guard) = &guard else {
+                continue;
+            };
+            if rotated || last_disk_check.elapsed() >= DISK_CHECK_INTERVAL {
+                last_disk_check = Instant::now();
+                if let Some((used, limit)) = check_disk(guard, &mut recorder) {
+                    stopped = true;
+                    send(RecorderEvent::DiskFull { used, limit });
+                    break;
+                }
+            }
+        }
+        let stats = recorder.stats();
+        crate::metrics::record_recording_progress(
+            stats.frames_written.saturating_sub(reported.frames_written),
+            stats.bytes_written.saturating_sub(reported.bytes_written),
+        );
+        reported = stats;
+        *shared.stats.lock().unwrap() = stats;
+    }
+
+    if let Err(e) = recorder.close() {
+        warn!("Failed to close recording: {}", e);
+    }
+}
+
+/// Apply the guard to the recording's files. Returns the usage and limit if
+/// the recording had to be stopped, after closing it.
+fn check_disk(guard: &DiskGuard, recorder: &mut RecordSink) -> Option<(u64, u64)> {
+    match guard.enforce(&recorder.files(), &recorder.open_files()) {
+        DiskCheck::Ok => None,
+        DiskCheck::Deleted(paths) => {
+            for path in paths {
+                info!("Deleted old recording segment {} to stay under the disk limit", path.display());
+            }
+            None
+        }
+        DiskCheck::Full { used, limit } => {
+            warn!("Recording uses {} bytes, over its {} byte limit; stopping", used, limit);
+            if let Err(e) = recorder.close() {
+                warn!("Failed to close recording: {}", e);
+            }
+            Some((used, limit))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disk::DiskPolicy;
+    use blackbox_core::recorder::RotationPolicy;
+    use blackbox_core::replayer::Replayer;
+    use blackbox_core::types::{ReplayConfig, ReplayMode};
+
+    fn frame(i: usize) -> String {
+        format!(r#"{{"channel":"heartbeat","seq":{}}}"#, i)
+    }
+
+    fn queued(i: usize) -> QueuedFrame {
+        QueuedFrame { ts: Utc::now(), raw_frame: frame(i), decoded_event: None, symbol: None }
+    }
+
+    #[test]
+    fn test_full_queue_drops_oldest() {
+        let shared = Shared {
+            queue: Mutex::new(Queue::default()),
+            ready: Condvar::new(),
+            capacity: 4,
+            dropped: AtomicU64::new(0),
+            stats: Mutex::new(RecordingStats { frames_written: 0, bytes_written: 0, started_at: Utc::now(), last_frame_at: None }),
+        };
+        for i in 0..10 {
+            shared.push(queued(i));
+        }
+        assert_eq!(shared.dropped.load(Ordering::Relaxed), 6);
+        let kept: Vec<String> = shared.queue.lock().unwrap().frames.iter().map(|f| f.raw_frame.clone()).collect();
+        assert_eq!(kept, (6..10).map(frame).collect::<Vec<_>>());
+
+        // Nothing is accepted after close
+        shared.close();
+        shared.push(queued(10));
+        assert_eq!(shared.queue.lock().unwrap().frames.len(), 4);
+    }
+
+    #[test]
+    fn test_writer_exports_recording_metrics() {
+        let dir = std::env::temp_dir().join(format!("blackbox_recording_metrics_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let recorder = Recorder::new_with_options(dir.join("metrics.ndjson"), RecorderOptions::default()).unwrap();
+        let shared = Shared {
+            queue: Mutex::new(Queue::default()),
+            ready: Condvar::new(),
+            capacity: 16,
+            dropped: AtomicU64::new(0),
+            stats: Mutex::new(recorder.stats()),
+        };
+        for i in 0..10 {
+            shared.push(queued(i));
+        }
+        shared.close();
+
+        // On this thread, so the frames are counted by the local recorder
+        let metrics = metrics_exporter_prometheus::PrometheusBuilder::new().build_recorder();
+        ::metrics::with_local_recorder(&metrics, || write_frames(recorder.into(), &shared, None, None));
+        let stats = *shared.stats.lock().unwrap();
+        assert_eq!(stats.frames_written, 10);
+        let text = metrics.handle().render();
+        assert!(text.contains("frames_recorded_total 10"), "{}", text);
+        assert!(text.contains(&format!("recording_bytes_total {}", stats.bytes_written)), "{}", text);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_recording_active_follows_start_and_stop() {
+        let dir = std::env::temp_dir().join(format!("blackbox_recording_active_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let metrics = metrics_exporter_prometheus::PrometheusBuilder::new().build_recorder();
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        let state = crate::state::AppState::new();
+        let recorder = Recorder::new_with_options(dir.join("active.ndjson"), RecorderOptions::default()).unwrap();
+        ::metrics::with_local_recorder(&metrics, || runtime.block_on(state.start_recording(recorder)));
+        assert!(metrics.handle().render().contains("recording_active 1"));
+        ::metrics::with_local_recorder(&metrics, || runtime.block_on(state.stop_recording()));
+        assert!(metrics.handle().render().contains("recording_active 0"));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_close_drains_queue() {
+        let dir = std::env::temp_dir().join(format!("blackbox_async_recorder_{}", std::process::id()));
+        let base = dir.join("async.ndjson");
+        let options = RecorderOptions {
+            rotation: RotationPolicy { max_bytes: Some(4096), max_duration: None },
+            ..Default::default()
+        };
+        let recorder = Recorder::new_with_options(base.clone(), options).unwrap();
+        let (events_tx, mut events_rx) = mpsc::unbounded_channel();
+        let rec = AsyncRecorder::spawn(recorder, RECORD_QUEUE_CAPACITY, Some(events_tx), None);
+        for i in 0..1000 {
+            rec.record(&frame(i), Some("heartbeat"), None);
+        }
+        rec.close().await;
+        assert_eq!(rec.dropped_frames(), 0);
+        assert_eq!(rec.stats().frames_written, 1000);
+        rec.record(&frame(1000), None, None);
+
+        // Every frame made it to disk, in order, across the rotated segments
+        let mut rotated = 0;
+        while let Some(event) = events_rx.recv().await {
+            assert!(matches!(event, RecorderEvent::Rotated(_)));
+            rotated += 1;
+        }
+        assert!(rotated > 0);
+        let config = ReplayConfig { mode: ReplayMode::AsFast, faults: vec![], loop_playback: false };
+        let mut replayer = Replayer::from_segments(&base, config).unwrap();
+        replayer.start();
+        let replayed: Vec<String> = std::iter::from_fn(|| replayer.next_frame()).collect();
+        assert_eq!(replayed, (0..1000).map(frame).collect::<Vec<_>>());
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_disk_guard_deletes_oldest_segments() {
+        let dir = std::env::temp_dir().join(format!("blackbox_record_guard_{}", std::process::id()));
+        let base = dir.join("guarded.ndjson");
+        let options = RecorderOptions {
+            rotation: RotationPolicy { max_bytes: Some(2048), max_duration: None },
+            ..Default::default()
+        };
+        let guard = DiskGuard::new(6000, DiskPolicy::DeleteOldest);
+        let rec = AsyncRecorder::spawn(Recorder::new_with_options(base.clone(), options.clone()).unwrap(), RECORD_QUEUE_CAPACITY, None, Some(guard));
+        for i in 0..500 {
+            rec.record(&frame(i), None, None);
+        }
+        rec.close().await;
+        assert_eq!(rec.stats().frames_written, 500);
+        
+        // The oldest segments are gone; the rest is the end of the recording
+        assert!(!base.exists());
+        let segments = recording_segments(&dir).unwrap();
+        let on_disk: u64 = segments.iter().map(|s| std::fs::metadata(s).unwrap().len()).sum();
+        assert!(on_disk <= 6000 + 2048, "{} bytes left", on_disk);
+        let config = ReplayConfig { mode: ReplayMode::AsFast, faults: vec![], loop_playback: false };
+        let mut replayer = Replayer::from_segments(&dir, config).unwrap();
+        replayer.start();
+        let replayed: Vec<String> = std::iter::from_fn(|| replayer.next_frame()).collect();
+        let first = 500 - replayed.len();
+        assert_eq!(replayed, (first..500).map(frame).collect::<Vec<_>>());
+        
+        // Stop: the writer gives up and reports it
+        let stopped = dir.join("stopped.ndjson");
+        let guard = DiskGuard::new(4096, DiskPolicy::Stop);
+        let (events_tx, mut events_rx) = mpsc::unbounded_channel();
+        let rec = AsyncRecorder::spawn(Recorder::new_with_options(stopped.clone(), options).unwrap(), RECORD_QUEUE_CAPACITY, Some(events_tx), Some(guard));
+        for i in 0..500 {
+            rec.record(&frame(i), None, None);
+        }
+        rec.close().await;
+        assert!(rec.stats().frames_written < 500);
+        let mut full = false;
+        while let Some(event) = events_rx.recv().await {
+            full |= matches!(event, RecorderEvent::DiskFull { limit: 4096, .. });
+        }
+        assert!(full);
+        assert!(stopped.exists());
+        
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -1,14 +1,27 @@
 use blackbox_core::health::{HealthStatus, SymbolHealth};
 use blackbox_core::orderbook::Orderbook;
+use blackbox_core::precision::PrecisionFormatter;
+use blackbox_core::recorder::{RecorderOptions, RecordingStats};
+use blackbox_core::replayer::Replayer;
 use blackbox_core::types::InstrumentInfo;
+use blackbox_ws::client::ClientCommand;
+use metrics_exporter_prometheus::PrometheusHandle;
+use anyhow::Context;
 use chrono::Utc;
-use dashmap::DashMap;
+use dashmap::{DashMap, DashSet};
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use std::collections::VecDeque;
+use std::ops::{Deref, DerefMut};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use tokio::sync::RwLock;
-use std::time::Instant;
-use crate::integrity::{IntegrityProof, IncidentMeta};
+use tokio::sync::{broadcast, RwLock, RwLockWriteGuard};
+use std::time::{Duration, Instant};
+use crate::integrity::{ChecksumDumper, IntegrityProof, IncidentMeta};
+use crate::config::{RunMode, SymbolConfig};
+use crate::disk::DiskGuard;
+use crate::recording::{AsyncRecorder, RecordSink, RecorderEvent, RECORD_QUEUE_CAPACITY};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum UiEvent {
@@ -25,10 +38,50 @@ pub enum UiEvent {
     IncidentCaptured { id: String, reason: String },
     IncidentExported { path: String },
     FaultInjected { fault_type: String, symbol: String },
+    BookCrossed { symbol: String },
+    TopOfBookChanged { symbol: String },
     Error(String),
 }
 
-#[derive(Debug, Clone, Serialize)]
+impl UiEvent {
+    /// Stable machine-readable tag, e.g. `checksum_mismatch`
+    pub fn kind(&self) -> &'static str {
+        match self {
+            UiEvent::Connected => "connected",
+            UiEvent::Disconnected => "disconnected",
+            UiEvent::SubscribedInstrument => "subscribed_instrument",
+            UiEvent::SubscribedBook => "subscribed_book",
+            UiEvent::ChecksumOk { .. } => "checksum_ok",
+            UiEvent::ChecksumMismatch { .. } => "checksum_mismatch",
+            UiEvent::ResyncStarted { .. } => "resync_started",
+            UiEvent::ResyncDone { .. } => "resync_done",
+            UiEvent::RecordStarted { .. } => "record_started",
+            UiEvent::RecordStopped => "record_stopped",
+            UiEvent::IncidentCaptured { .. } => "incident_captured",
+            UiEvent::IncidentExported { .. } => "incident_exported",
+            UiEvent::FaultInjected { .. } => "fault_injected",
+            UiEvent::BookCrossed { .. } => "book_crossed",
+            UiEvent::TopOfBookChanged { .. } => "top_of_book_changed",
+            UiEvent::Error(_) => "error",
+        }
+    }
+
+    /// The symbol this event is about, if any
+    pub fn symbol(&self) -> Option<&str> {
+        match self {
+            UiEvent::ChecksumOk { symbol }
+            | UiEvent::ChecksumMismatch { symbol }
+            | UiEvent::ResyncStarted { symbol }
+            | UiEvent::ResyncDone { symbol }
+            | UiEvent::FaultInjected { symbol, .. }
+            | UiEvent::BookCrossed { symbol }
+            | UiEvent::TopOfBookChanged { symbol } => Some(symbol),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UiEventLogEntry {
     pub timestamp: chrono::DateTime<Utc>,
     pub event: UiEvent,
@@ -41,15 +94,167 @@ pub struct AggregatedEvent {
     pub color: crate::tui::widgets::EventColor,
 }
 
+/// Top-of-book change published after every applied snapshot/update
+#[derive(Debug, Clone, Serialize)]
+pub struct BookChange {
+    pub symbol: String,
+    pub best_bid: Option<(Decimal, Decimal)>,
+    pub best_ask: Option<(Decimal, Decimal)>,
+    pub seq: u64,
+    pub ts: chrono::DateTime<Utc>, // exchange timestamp if known, else local time
+}
+
+impl BookChange {
+    pub fn from_book(symbol: &str, book: &Orderbook) -> Self {
+        Self {
+            symbol: symbol.to_string(),
+            best_bid: book.best_bid(),
+            best_ask: book.best_ask(),
+            seq: book.update_seq(),
+            ts: book.last_update_ts().unwrap_or_else(Utc::now),
+        }
+    }
+}
+
+/// Buffered book changes per subscriber before it starts missing messages
+const BOOK_CHANGE_CAPACITY: usize = 1024;
+/// Consecutive checksum failures after which a symbol is re-subscribed
+pub const RESYNC_AFTER_FAILS: u64 = 3;
+/// Least time between one resync of a symbol and the next, by how many came
+/// before; the last applies from then on
+pub const RESYNC_BACKOFF: [Duration; 3] = [Duration::from_secs(3), Duration::from_secs(10), Duration::from_secs(30)];
+/// A resync this long after the previous one starts the backoff over
+const RESYNC_BACKOFF_RESET: Duration = Duration::from_secs(300);
+
+/// Events kept in `event_log`
+pub const EVENT_LOG_CAPACITY: usize = 500;
+/// Buffered UI events per subscriber before it starts missing messages
+const UI_EVENT_CAPACITY: usize = 256;
+
+/// Levels per side kept for pre-mismatch book diffs
+pub const BOOK_DIFF_DEPTH: usize = 25;
+
+/// An orderbook as held in `AppState`, marked stale while the feed is down.
+/// Derefs to the inner `Orderbook`.
+#[derive(Debug, Clone)]
+pub struct StoredBook {
+    pub book: Orderbook,
+    pub subscribed_depth: usize, // levels per side kept for checksum verification
+    pub stale: bool, // set on disconnect, cleared by the next snapshot
+    pub last_snapshot_ts: Option<chrono::DateTime<Utc>>,
+}
+
+impl StoredBook {
+    /// Wrap a book that was just built from a snapshot
+    pub fn new(book: Orderbook, subscribed_depth: usize) -> Self {
+        Self {
+            book,
+            subscribed_depth,
+            stale: false,
+            last_snapshot_ts: Some(Utc::now()),
+        }
+    }
+}
+
+impl Deref for StoredBook {
+    type Target = Orderbook;
+    
+    fn deref(&self) -> &Orderbook {
+        &self.book
+    }
+}
+
+impl DerefMut for StoredBook {
+    fn deref_mut(&mut self) -> &mut Orderbook {
+        &mut self.book
+    }
+}
+
+/// A raw frame paired with its local receive time
+pub type TimestampedFrame = (chrono::DateTime<Utc>, String);
+
+/// Book frames kept per symbol, for `/frames/:symbol` and incident bundles
+pub const FRAME_BUFFER_CAPACITY: usize = 2000;
+
+/// Frames of every channel kept by default, for bundles without a symbol
+pub const LAST_FRAMES_CAPACITY: usize = 1000;
+
+/// Append to a ring buffer of `capacity` frames, dropping the oldest
+fn push_bounded(buffer: &mut VecDeque<TimestampedFrame>, frame: TimestampedFrame, capacity: usize) {
+    if capacity == 0 {
+        return;
+    }
+    while buffer.len() >= capacity {
+        buffer.pop_front();
+    }
+    buffer.push_back(frame);
+}
+
+/// Symbol of a `classify_frame` tag like `book.update:BTC/USD`
+fn book_tag_symbol(tag: &str) -> Option<&str> {
+    tag.strip_prefix("book.")?.split_once(':').map(|(_, symbol)| symbol)
+}
+
+/// Current recording, for `/record/status` and the TUI header
+/// Why `open_recording` didn't start a recording
+#[derive(Debug, thiserror::Error)]
+pub enum OpenRecordingError {
+    #[error("Already recording to {0}")]
+    Busy(String), // path of the running recording
+    #[error(transparent)]
+    Open(#[from] anyhow::Error),
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RecordStatus {
+    pub recording: bool,
+    pub path: Option<String>, // segment being written
+    #[serde(flatten)]
+    pub stats: Option<RecordingStats>,
+    pub dropped_frames: u64,
+}
+
+/// Where `StateSnapshot`s are written on shutdown
+pub const STATE_SNAPSHOT_FILE: &str = "state_snapshot.json";
+
+/// What a warm restart (--resume-state) carries over: health counters and
+/// incidents. Books aren't kept; they need fresh snapshots from the venue.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StateSnapshot {
+    pub saved_at: chrono::DateTime<Utc>,
+    pub uptime_seconds: u64,
+    pub health: Vec<SymbolHealth>,
+    pub incident_count: u64,
+    pub last_incident: Option<IncidentMeta>,
+}
+
+impl StateSnapshot {
+    /// Write to `path`, through a temporary file so a crash mid-write leaves the old one
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec_pretty(self)?)
+            .with_context(|| format!("Failed to write {}", tmp.display()))?;
+        std::fs::rename(&tmp, path).with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let bytes = std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        serde_json::from_slice(&bytes).with_context(|| format!("Failed to parse {}", path.display()))
+    }
+}
+
 #[derive(Clone)]
 pub struct AppState {
-    pub orderbooks: Arc<DashMap<String, Orderbook>>,
+    pub orderbooks: Arc<DashMap<String, StoredBook>>,
     pub instruments: Arc<DashMap<String, InstrumentInfo>>,
+    pub formatters: Arc<DashMap<String, PrecisionFormatter>>, // Checksum formatting per instrument, built on insert
     pub health: Arc<DashMap<String, SymbolHealth>>,
     pub depths: Arc<DashMap<String, u32>>, // Track depth per symbol
     pub start_time: Instant,
-    pub last_frames: Arc<RwLock<Vec<(chrono::DateTime<Utc>, String)>>>, // Global frame buffer
-    pub per_symbol_frames: Arc<DashMap<String, Arc<RwLock<VecDeque<String>>>>>, // Per-symbol ring buffer
+    pub last_frames: Arc<RwLock<VecDeque<TimestampedFrame>>>, // Ring buffer of every frame
+    pub last_frames_capacity: usize, // 0 = off
+    pub per_symbol_frames: Arc<DashMap<String, Arc<RwLock<VecDeque<TimestampedFrame>>>>>, // Per-symbol ring buffer of book frames
+    pub symbol_frames_capacity: usize, // 0 = off
     pub event_log: Arc<RwLock<VecDeque<UiEventLogEntry>>>, // Ring buffer for events
     pub last_incident: Arc<RwLock<Option<IncidentMeta>>>,
     pub incident_count: Arc<RwLock<u64>>,
@@ -58,8 +263,30 @@ pub struct AppState {
     pub requested_symbols: Arc<RwLock<Vec<String>>>, // Symbols requested via CLI args
     pub recording_enabled: Arc<RwLock<bool>>, // Recording toggle state
     pub recording_path: Arc<RwLock<Option<String>>>, // Current recording file path
-    pub recorder: Arc<RwLock<Option<blackbox_core::recorder::Recorder>>>, // Shared recorder instance
-    pub last_resync: Arc<DashMap<String, Instant>>, // Last resync time per symbol (for backoff)
+    pub last_recording: Arc<RwLock<Option<(PathBuf, bool)>>>, // Last recording's path and whether it was per-symbol
+    pub recorder: Arc<RwLock<Option<AsyncRecorder>>>, // Shared recorder instance
+    pub recorder_options: RecorderOptions, // Used by CLI --record and the TUI toggle alike
+    pub record_decoded: bool, // Tag recorded frames with classify_frame
+    pub record_disk_guard: Option<DiskGuard>, // Limits the disk space of each recording
+    pub last_resync: Arc<DashMap<String, (Instant, usize)>>, // Last resync per symbol, and resyncs in a row (for backoff)
+    pub resyncing: Arc<DashSet<String>>, // Symbols waiting for the snapshot a resync asked for
+    pub resync_after_fails: u64, // 0 = never resync
+    pub last_verified_books: Arc<DashMap<String, Orderbook>>, // Top of book at the last checksum match
+    pub book_changes: broadcast::Sender<BookChange>, // Fan-out of applied book changes
+    pub ui_events: broadcast::Sender<UiEventLogEntry>, // Fan-out of events as they're logged
+    pub track_level_meta: bool, // Keep per-level update metadata on new books
+    pub strict_book: bool, // Validate book invariants after every change
+    pub tombstones: usize, // Removed levels to remember per side on new books (0 = off)
+    pub checksum_levels: Option<usize>, // Overrides the venue's checksum level count
+    pub checksum_dumper: Option<Arc<ChecksumDumper>>, // Writes full checksum input on mismatch
+    pub invalid_books: Arc<DashSet<String>>, // Symbols whose book currently fails validation
+    pub replayer: Arc<std::sync::Mutex<Option<Replayer>>>, // Recording being replayed; None when live
+    pub ws_commands: Option<tokio::sync::mpsc::UnboundedSender<ClientCommand>>, // Subscription changes for the live client
+    pub metrics: Option<PrometheusHandle>, // Renders /metrics; None when no recorder is installed
+    pub run_mode: RunMode, // Where frames come from, for /config
+    pub ws_url: Option<String>, // Venue endpoint; None unless connected live
+    pub ping_interval: Option<Duration>, // Client ping interval; None unless connected live
+    pub connected: Arc<AtomicBool>, // Feed (venue, replay or mock) currently connected
 }
 
 impl AppState {
@@ -67,11 +294,14 @@ impl AppState {
         Self {
             orderbooks: Arc::new(DashMap::new()),
             instruments: Arc::new(DashMap::new()),
+            formatters: Arc::new(DashMap::new()),
             health: Arc::new(DashMap::new()),
             depths: Arc::new(DashMap::new()),
             start_time: Instant::now(),
-            last_frames: Arc::new(RwLock::new(Vec::new())),
+            last_frames: Arc::new(RwLock::new(VecDeque::new())),
+            last_frames_capacity: LAST_FRAMES_CAPACITY,
             per_symbol_frames: Arc::new(DashMap::new()),
+            symbol_frames_capacity: FRAME_BUFFER_CAPACITY,
             event_log: Arc::new(RwLock::new(VecDeque::new())),
             last_incident: Arc::new(RwLock::new(None)),
             incident_count: Arc::new(RwLock::new(0)),
@@ -80,13 +310,72 @@ impl AppState {
             requested_symbols: Arc::new(RwLock::new(Vec::new())),
             recording_enabled: Arc::new(RwLock::new(false)),
             recording_path: Arc::new(RwLock::new(None)),
+            last_recording: Arc::new(RwLock::new(None)),
             recorder: Arc::new(RwLock::new(None)),
+            recorder_options: RecorderOptions::default(),
+            record_decoded: true,
+            record_disk_guard: None,
             last_resync: Arc::new(DashMap::new()),
+            resyncing: Arc::new(DashSet::new()),
+            resync_after_fails: RESYNC_AFTER_FAILS,
+            last_verified_books: Arc::new(DashMap::new()),
+            book_changes: broadcast::channel(BOOK_CHANGE_CAPACITY).0,
+            ui_events: broadcast::channel(UI_EVENT_CAPACITY).0,
+            track_level_meta: false,
+            strict_book: false,
+            tombstones: 0,
+            checksum_levels: None,
+            checksum_dumper: None,
+            invalid_books: Arc::new(DashSet::new()),
+            replayer: Arc::new(std::sync::Mutex::new(None)),
+            ws_commands: None,
+            metrics: None,
+            run_mode: RunMode::default(),
+            ws_url: None,
+            ping_interval: None,
+            connected: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Run `f` on the replayer; None when not replaying. The lock is held
+    /// only for the call, never across an await.
+    pub fn with_replayer<T>(&self, f: impl FnOnce(&mut Replayer) -> T) -> Option<T> {
+        let mut replayer = self.replayer.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        replayer.as_mut().map(f)
+    }
+    
+    /// Fresh book for a snapshot at `depth`, with 2x headroom so bursts
+    /// between truncations stay bounded
+    pub fn new_orderbook(&self, depth: usize) -> Orderbook {
+        let book = Orderbook::with_max_levels(depth * 2).with_tombstones(self.tombstones);
+        if self.track_level_meta {
+            book.with_level_meta()
+        } else {
+            book
         }
     }
     
+    /// Store instrument info, applying the configured checksum level override
+    pub fn insert_instrument(&self, symbol: String, mut info: InstrumentInfo) {
+        if let Some(levels) = self.checksum_levels {
+            info.checksum_levels = levels;
+        }
+        self.formatters.insert(symbol.clone(), PrecisionFormatter::from_instrument(&info));
+        self.instruments.insert(symbol, info);
+    }
+    
+    /// The stored checksum formatter for `symbol`, or one built from `instrument`
+    /// if it was inserted without `insert_instrument`
+    pub fn formatter(&self, symbol: &str, instrument: &InstrumentInfo) -> PrecisionFormatter {
+        self.formatters
+            .get(symbol)
+            .map(|f| *f)
+            .unwrap_or_else(|| PrecisionFormatter::from_instrument(instrument))
+    }
+    
     pub async fn set_recording_enabled(&self, enabled: bool) {
         *self.recording_enabled.write().await = enabled;
+        crate::metrics::update_recording_active(enabled);
     }
     
     pub async fn is_recording_enabled(&self) -> bool {
@@ -97,20 +386,153 @@ impl AppState {
         *self.recording_path.write().await = path;
     }
     
-    pub async fn get_recording_path(&self) -> Option<String> {
-        self.recording_path.read().await.clone()
+    /// `decoded_event` to record with a frame, unless disabled
+    pub fn frame_tag(&self, raw_frame: &str) -> Option<String> {
+        if self.record_decoded {
+            blackbox_ws::parser::classify_frame(raw_frame)
+        } else {
+            None
+        }
     }
     
+    /// Queue a frame for the shared recorder if recording is on
+    pub async fn record_frame(&self, raw_frame: &str) {
+        if !self.is_recording_enabled().await {
+            return;
+        }
+        if let Some(rec) = self.recorder.read().await.as_ref() {
+            if !rec.per_symbol() {
+                rec.record(raw_frame, self.frame_tag(raw_frame).as_deref(), None);
+                return;
+            }
+            // Per-symbol recordings route on the tag even when it isn't kept
+            let tag = blackbox_ws::parser::classify_frame(raw_frame);
+            let symbol = tag.as_deref().and_then(book_tag_symbol);
+            let decoded_event = if self.record_decoded { tag.as_deref() } else { None };
+            rec.record(raw_frame, decoded_event, symbol);
+        }
+    }
+    
+    /// Record into `recorder` on a background writer, replacing any current
+    /// recording. Each new rotation segment is announced as `RecordStarted`;
+    /// if the disk guard stops the recording, an `Error` is pushed.
+    pub async fn start_recording(&self, recorder: impl Into<RecordSink>) {
+        let slot = self.recorder.write().await;
+        self.start_recording_in(slot, recorder.into()).await;
+    }
+    
+    /// Put `recorder` in the locked recorder slot, closing any it replaces
+    async fn start_recording_in(&self, mut slot: RwLockWriteGuard<'_, Option<AsyncRecorder>>, recorder: RecordSink) {
+        let per_symbol = matches!(recorder, RecordSink::PerSymbol(_));
+        *self.last_recording.write().await = Some((recorder.path(), per_symbol));
+        let path = recorder.path().to_string_lossy().to_string();
+        let (events_tx, mut events_rx) = tokio::sync::mpsc::unbounded_channel();
+        let rec = AsyncRecorder::spawn(recorder, RECORD_QUEUE_CAPACITY, Some(events_tx), self.record_disk_guard);
+        let previous = slot.replace(rec);
+        drop(slot);
+        if let Some(previous) = previous {
+            previous.close().await;
+        }
+        self.set_recording_enabled(true).await;
+        self.set_recording_path(Some(path.clone())).await;
+        self.push_event(UiEvent::RecordStarted { path }).await;
+        
+        let state = self.clone();
+        tokio::spawn(async move {
+            while let Some(event) = events_rx.recv().await {
+                match event {
+                    RecorderEvent::Rotated(segment) => {
+                        let segment = segment.to_string_lossy().to_string();
+                        state.set_recording_path(Some(segment.clone())).await;
+                        state.push_event(UiEvent::RecordStarted { path: segment }).await;
+                    }
+                    RecorderEvent::DiskFull { used, limit } => {
+                        let message = format!("Recording stopped: {} bytes on disk, limit {}", used, limit);
+                        state.push_event(UiEvent::Error(message)).await;
+                        state.stop_recording().await;
+                    }
+                }
+            }
+        });
+    }
+    
+    /// Start recording to `path`; without one, resume this session's last
+    /// recording, or start a new file with a generated name. Returns the path,
+    /// or `Busy` if a recording is already running.
+    pub async fn open_recording(&self, path: Option<PathBuf>, options: RecorderOptions) -> Result<PathBuf, OpenRecordingError> {
+        // Held from the check to the start, so two callers can't both start one
+        let slot = self.recorder.write().await;
+        if slot.is_some() {
+            return Err(OpenRecordingError::Busy(self.recording_path.read().await.clone().unwrap_or_default()));
+        }
+        let last = match path {
+            Some(path) => Some((path, false)),
+            None => self.last_recording.read().await.clone(),
+        };
+        let resumed = last.is_some();
+        let (path, per_symbol) = last.unwrap_or_else(|| {
+            let timestamp = Utc::now().format("%Y%m%d_%H%M%S");
+            (PathBuf::from(format!("recording_{}.ndjson", timestamp)), false)
+        });
+        let options = RecorderOptions {
+            append: options.append || resumed,
+            ..options
+        };
+        let rec = RecordSink::open(path.clone(), options, per_symbol)?;
+        self.start_recording_in(slot, rec).await;
+        Ok(path)
+    }
+    
+    /// Write out every queued frame and close the recording, if any
+    pub async fn stop_recording(&self) {
+        self.set_recording_enabled(false).await;
+        let rec = self.recorder.write().await.take();
+        if let Some(rec) = rec {
+            rec.close().await;
+            if rec.dropped_frames() > 0 {
+                tracing::warn!("Recording dropped {} frames while the disk fell behind", rec.dropped_frames());
+            }
+            self.set_recording_path(None).await;
+            self.push_event(UiEvent::RecordStopped).await;
+        }
+    }
+    
+    pub async fn recording_status(&self) -> RecordStatus {
+        let recorder = self.recorder.read().await;
+        RecordStatus {
+            recording: recorder.is_some() && self.is_recording_enabled().await,
+            path: self.recording_path.read().await.clone(),
+            stats: recorder.as_ref().map(|rec| rec.stats()),
+            dropped_frames: recorder.as_ref().map_or(0, |rec| rec.dropped_frames()),
+        }
+    }
+    
+    /// Whether the backoff lets `symbol` resync now: 3s after the first
+    /// resync, 10s after the second, 30s after any later one
     pub fn can_resync(&self, symbol: &str) -> bool {
-        if let Some(last) = self.last_resync.get(symbol) {
-            last.elapsed().as_secs() >= 3 // Min 3s between resyncs
-        } else {
-            true
+        self.can_resync_at(symbol, Instant::now())
+    }
+    
+    fn can_resync_at(&self, symbol: &str, now: Instant) -> bool {
+        match self.last_resync.get(symbol) {
+            Some(entry) => {
+                let (last, attempts) = *entry;
+                let backoff = RESYNC_BACKOFF[attempts.clamp(1, RESYNC_BACKOFF.len()) - 1];
+                now.saturating_duration_since(last) >= backoff
+            }
+            None => true,
         }
     }
     
     pub fn record_resync(&self, symbol: &str) {
-        self.last_resync.insert(symbol.to_string(), Instant::now());
+        self.record_resync_at(symbol, Instant::now());
+    }
+    
+    fn record_resync_at(&self, symbol: &str, now: Instant) {
+        let mut entry = self.last_resync.entry(symbol.to_string()).or_insert((now, 0));
+        let (last, attempts) = *entry;
+        let attempts = if now.saturating_duration_since(last) >= RESYNC_BACKOFF_RESET { 1 } else { attempts + 1 };
+        *entry = (now, attempts);
     }
     
     pub async fn set_requested_symbols(&self, symbols: Vec<String>) {
@@ -121,22 +543,64 @@ impl AppState {
         self.requested_symbols.read().await.clone()
     }
     
-    pub fn get_or_create_frame_buffer(&self, symbol: &str) -> Arc<RwLock<VecDeque<String>>> {
+    pub async fn is_requested(&self, symbol: &str) -> bool {
+        self.requested_symbols.read().await.iter().any(|s| s == symbol)
+    }
+    
+    /// Drop a symbol that's no longer subscribed, and everything kept for it
+    pub async fn forget_symbol(&self, symbol: &str) {
+        self.requested_symbols.write().await.retain(|s| s != symbol);
+        self.depths.remove(symbol);
+        self.orderbooks.remove(symbol);
+        self.health.remove(symbol);
+        self.integrity_proofs.remove(symbol);
+        self.last_verified_books.remove(symbol);
+        self.invalid_books.remove(symbol);
+        self.per_symbol_frames.remove(symbol);
+        self.last_resync.remove(symbol);
+        self.resyncing.remove(symbol);
+    }
+    
+    pub fn get_or_create_frame_buffer(&self, symbol: &str) -> Arc<RwLock<VecDeque<TimestampedFrame>>> {
         self.per_symbol_frames
             .entry(symbol.to_string())
-            .or_insert_with(|| Arc::new(RwLock::new(VecDeque::with_capacity(2000))))
+            .or_insert_with(|| Arc::new(RwLock::new(VecDeque::with_capacity(self.symbol_frames_capacity))))
             .value()
             .clone()
     }
     
+    /// Keep a book frame in its symbol's ring buffer; other frames are ignored.
+    /// The frame is copied before the lock is taken, so readers exporting
+    /// the buffer only ever hold up the processor for a push.
+    pub async fn buffer_frame(&self, raw_frame: &str) {
+        if self.symbol_frames_capacity == 0 {
+            return;
+        }
+        let tag = blackbox_ws::parser::classify_frame(raw_frame);
+        let Some(symbol) = tag.as_deref().and_then(book_tag_symbol) else {
+            return;
+        };
+        let frame_buffer = self.get_or_create_frame_buffer(symbol);
+        let frame = (Utc::now(), raw_frame.to_string());
+        push_bounded(&mut *frame_buffer.write().await, frame, self.symbol_frames_capacity);
+    }
+    
+    /// Keep any frame in `last_frames`
+    pub async fn push_last_frame(&self, raw_frame: &str) {
+        let frame = (Utc::now(), raw_frame.to_string());
+        push_bounded(&mut *self.last_frames.write().await, frame, self.last_frames_capacity);
+    }
+    
     pub async fn push_event(&self, event: UiEvent) {
-        let mut log = self.event_log.write().await;
-        log.push_back(UiEventLogEntry {
+        let entry = UiEventLogEntry {
             timestamp: Utc::now(),
             event,
-        });
-        // Keep last 500 events
-        while log.len() > 500 {
+        };
+        // No subscribers is not an error
+        let _ = self.ui_events.send(entry.clone());
+        let mut log = self.event_log.write().await;
+        log.push_back(entry);
+        while log.len() > EVENT_LOG_CAPACITY {
             log.pop_front();
         }
     }
@@ -160,15 +624,14 @@ impl AppState {
                     let mut count = 1;
                     let mut j = i + 1;
                     while j < events.len() {
-                        if let UiEvent::ChecksumOk { symbol: s } = &events[j].event {
-                            if s == symbol {
+                        match &events[j].event {
+                            UiEvent::ChecksumOk { symbol: s } if s == symbol => {
                                 count += 1;
                                 j += 1;
-                            } else {
-                                break;
                             }
-                        } else {
-                            break;
+                            // Don't let top-of-book moves split a run of checksum OKs
+                            UiEvent::TopOfBookChanged { .. } => j += 1,
+                            _ => break,
                         }
                     }
                     if count > 1 {
@@ -211,6 +674,18 @@ impl AppState {
                     });
                     i += 1;
                 }
+                UiEvent::TopOfBookChanged { .. } => {
+                    // Published for downstream consumers; too frequent for the event log
+                    i += 1;
+                }
+                UiEvent::BookCrossed { symbol } => {
+                    aggregated.push(AggregatedEvent {
+                        timestamp: current.timestamp,
+                        text: format!("BOOK_CROSSED {}", symbol),
+                        color: crate::tui::widgets::EventColor::Warning,
+                    });
+                    i += 1;
+                }
                 UiEvent::FaultInjected { fault_type, symbol } => {
                     aggregated.push(AggregatedEvent {
                         timestamp: current.timestamp,
@@ -252,6 +727,58 @@ impl AppState {
         *count
     }
     
+    /// Subscribe to book changes; slow subscribers miss messages rather than block the processor
+    pub fn subscribe_book_changes(&self) -> broadcast::Receiver<BookChange> {
+        self.book_changes.subscribe()
+    }
+    
+    pub fn publish_book_change(&self, symbol: &str, book: &Orderbook) {
+        // No subscribers is not an error
+        let _ = self.book_changes.send(BookChange::from_book(symbol, book));
+    }
+    
+    /// Subscribe to events as `push_event` logs them, on the same terms as book changes
+    pub fn subscribe_events(&self) -> broadcast::Receiver<UiEventLogEntry> {
+        self.ui_events.subscribe()
+    }
+    
+    /// Record whether the feed's websocket is up
+    pub fn set_connected(&self, connected: bool) {
+        self.connected.store(connected, Ordering::SeqCst);
+    }
+
+    /// Whether the feed's websocket is up, as last reported by the client
+    pub fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::SeqCst)
+    }
+
+    /// Connected and holding at least one book, i.e. data is flowing
+    pub fn is_ready(&self) -> bool {
+        self.is_connected() && !self.orderbooks.is_empty()
+    }
+
+    /// Flag every stored book as stale until its next snapshot arrives
+    pub fn mark_books_stale(&self) {
+        for mut entry in self.orderbooks.iter_mut() {
+            entry.stale = true;
+        }
+    }
+    
+    /// Forget every book, e.g. when a looping replay starts over
+    pub fn clear_books(&self) {
+        self.orderbooks.clear();
+        self.last_verified_books.clear();
+        self.invalid_books.clear();
+    }
+    
+    /// Keep the top `BOOK_DIFF_DEPTH` levels of a book that just passed its checksum,
+    /// so a later mismatch can be diffed against it
+    pub fn record_verified_book(&self, symbol: &str, book: &Orderbook) {
+        let mut top = Orderbook::new();
+        top.apply_snapshot(book.bids_vec(Some(BOOK_DIFF_DEPTH)), book.asks_vec(Some(BOOK_DIFF_DEPTH)));
+        self.last_verified_books.insert(symbol.to_string(), top);
+    }
+    
     pub fn set_depth(&self, symbol: &str, depth: u32) {
         self.depths.insert(symbol.to_string(), depth);
     }
@@ -260,12 +787,69 @@ impl AppState {
         self.depths.get(symbol).map(|e| *e.value()).unwrap_or(100)
     }
 
+    /// The requested symbols and their depths, as recorded in incident bundle configs
+    pub async fn symbols_config(&self) -> Vec<SymbolConfig> {
+        let symbols = self.get_requested_symbols().await;
+        symbols
+            .into_iter()
+            .map(|symbol| SymbolConfig { depth: self.get_depth(&symbol), symbol })
+            .collect()
+    }
+    
+    /// Depth the feed actually maintains the book at, which the checksum is
+    /// computed over. Kraken rounds `--depth` up to a supported value, and a
+    /// replayed snapshot can carry more levels than the current `--depth`.
+    /// Truncating below this drops levels Kraken won't resend.
+    pub fn subscribed_depth(&self, symbol: &str, snapshot_levels: usize) -> usize {
+        let depth = self.get_depth(symbol).max(snapshot_levels as u32);
+        blackbox_ws::subscriptions::supported_depth(depth) as usize
+    }
+
     pub fn uptime_seconds(&self) -> u64 {
         self.start_time.elapsed().as_secs()
     }
 
+    pub async fn export_snapshot(&self) -> StateSnapshot {
+        StateSnapshot {
+            saved_at: Utc::now(),
+            uptime_seconds: self.uptime_seconds(),
+            health: self.health.iter().map(|e| e.value().clone()).collect(),
+            incident_count: self.get_incident_count().await,
+            last_incident: self.get_last_incident().await,
+        }
+    }
+
+    /// Carry on from `snapshot`: uptime continues from where it stopped, and
+    /// the health of every requested symbol picks up its counters, marked
+    /// disconnected until the feed says otherwise. Symbols no longer
+    /// requested are left out.
+    pub async fn restore(&mut self, snapshot: StateSnapshot) {
+        let requested = self.get_requested_symbols().await;
+        for mut health in snapshot.health {
+            if !requested.contains(&health.symbol) {
+                continue;
+            }
+            health.connected = false;
+            health.msg_rate_estimate = 0.0;
+            self.health.insert(health.symbol.clone(), health);
+        }
+        *self.incident_count.write().await = snapshot.incident_count;
+        *self.last_incident.write().await = snapshot.last_incident;
+        if let Some(start) = Instant::now().checked_sub(Duration::from_secs(snapshot.uptime_seconds)) {
+            self.start_time = start;
+        }
+    }
+
     pub fn overall_health(&self) -> blackbox_core::health::OverallHealth {
-        let symbols: Vec<SymbolHealth> = self.health.iter().map(|e| e.value().clone()).collect();
+        self.overall_health_where(|_| true)
+    }
+    
+    /// Overall health over only the symbols `include` accepts
+    pub fn overall_health_where(&self, include: impl Fn(&str) -> bool) -> blackbox_core::health::OverallHealth {
+        let symbols: Vec<SymbolHealth> = self.health.iter()
+            .filter(|e| include(e.key()))
+            .map(|e| e.value().clone())
+            .collect();
         let worst_status = symbols.iter()
             .map(|s| s.status())
             .min_by_key(|s| match s {
@@ -289,3 +873,71 @@ impl Default for AppState {
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_counters_survive_a_restart() {
+        let dir = std::env::temp_dir().join(format!("blackbox_state_snapshot_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(STATE_SNAPSHOT_FILE);
+
+        let before = AppState::new();
+        for symbol in ["BTC/USD", "ETH/USD"] {
+            let mut health = SymbolHealth::new(symbol.to_string());
+            health.connected = true;
+            health.checksum_ok = 40;
+            health.record_checksum_fail();
+            health.record_checksum_fail();
+            before.health.insert(symbol.to_string(), health);
+        }
+        before.set_last_incident(IncidentMeta::new("inc-1".into(), "BTC/USD".into(), "ChecksumMismatch".into())).await;
+        before.set_last_incident(IncidentMeta::new("inc-2".into(), "BTC/USD".into(), "ChecksumMismatch".into())).await;
+        before.export_snapshot().await.save(&path).unwrap();
+
+        // ETH/USD isn't subscribed after the restart
+        let mut after = AppState::new();
+        after.set_requested_symbols(vec!["BTC/USD".to_string()]).await;
+        after.restore(StateSnapshot::load(&path).unwrap()).await;
+        let btc = after.health.get("BTC/USD").unwrap().clone();
+        assert_eq!((btc.checksum_ok, btc.checksum_fail, btc.consecutive_fails), (40, 2, 2));
+        assert!(!btc.connected);
+        assert!(!after.health.contains_key("ETH/USD"));
+        assert_eq!(after.get_incident_count().await, 2);
+        assert_eq!(after.get_last_incident().await.unwrap().id, "inc-2");
+        // Still failing, so the badge doesn't go back to green
+        assert!(!matches!(after.overall_health().status, HealthStatus::Ok));
+
+        assert!(StateSnapshot::load(&dir.join("missing.json")).is_err());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_resync_backoff_grows() {
+        let state = AppState::new();
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+        assert!(state.can_resync_at("BTC/USD", start));
+        state.record_resync_at("BTC/USD", start);
+        // 3s, then 10s, then 30s from then on
+        assert!(!state.can_resync_at("BTC/USD", at(2)));
+        assert!(state.can_resync_at("BTC/USD", at(3)));
+        state.record_resync_at("BTC/USD", at(3));
+        assert!(!state.can_resync_at("BTC/USD", at(12)));
+        assert!(state.can_resync_at("BTC/USD", at(13)));
+        state.record_resync_at("BTC/USD", at(13));
+        assert!(!state.can_resync_at("BTC/USD", at(42)));
+        state.record_resync_at("BTC/USD", at(43));
+        assert!(!state.can_resync_at("BTC/USD", at(72)));
+        assert!(state.can_resync_at("BTC/USD", at(73)));
+        // Other symbols have their own backoff
+        assert!(state.can_resync_at("ETH/USD", at(44)));
+
+        // Quiet long enough, and it starts over at 3s
+        let later = at(73) + RESYNC_BACKOFF_RESET;
+        state.record_resync_at("BTC/USD", later);
+        assert!(state.can_resync_at("BTC/USD", later + Duration::from_secs(3)));
+    }
+}
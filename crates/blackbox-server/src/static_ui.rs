@@ -128,6 +128,21 @@ pub const UI_HTML: &str = r#"
             font-size: 0.8em;
             margin-top: 5px;
         }
+        .event-ticker {
+            background: white;
+            border-radius: 8px;
+            padding: 15px 20px;
+            margin-top: 20px;
+            font-family: monospace;
+            font-size: 0.85em;
+            color: #2c3e50;
+        }
+        .event-ticker:empty {
+            display: none;
+        }
+        .event-ticker div {
+            padding: 2px 0;
+        }
         .refresh-info {
             text-align: center;
             color: #7f8c8d;
@@ -140,6 +155,7 @@ pub const UI_HTML: &str = r#"
     <div class="container">
         <h1>🦑 Kraken Blackbox Monitor</h1>
         <div id="status-grid" class="status-grid"></div>
+        <div id="event-ticker" class="event-ticker"></div>
         <div class="refresh-info">Auto-refreshing every 2 seconds</div>
     </div>
     <script>
@@ -216,6 +232,10 @@ pub const UI_HTML: &str = r#"
                             <span class="book-label">Spread:</span>
                             <span class="book-value spread">${top.spread ? formatNumber(top.spread) : 'N/A'}</span>
                         </div>
+                        <div class="book-row">
+                            <span class="book-label">Spread (bps):</span>
+                            <span class="book-value spread">${top.spread_bps ? parseFloat(top.spread_bps).toFixed(2) : 'N/A'}</span>
+                        </div>
                         <div class="book-row">
                             <span class="book-label">Mid:</span>
                             <span class="book-value">${top.mid ? formatNumber(top.mid) : 'N/A'}</span>
@@ -247,8 +267,31 @@ pub const UI_HTML: &str = r#"
             }
         }
         
+        // Live event ticker; the page works without it
+        const TICKER_LINES = 10;
+        function startEventTicker() {
+            if (!window.EventSource) return;
+            const types = 'connected,disconnected,checksum_mismatch,resync,incident,fault_injected,book_crossed,error';
+            const source = new EventSource(`/events/stream?backfill=${TICKER_LINES}&types=${types}`);
+            const ticker = document.getElementById('event-ticker');
+            source.onmessage = (message) => {
+                const entry = JSON.parse(message.data);
+                // Unit variants are strings, the rest single-key objects
+                const name = typeof entry.event === 'string' ? entry.event : Object.keys(entry.event)[0];
+                const fields = typeof entry.event === 'string' ? {} : entry.event[name];
+                const detail = typeof fields === 'object' ? Object.values(fields).join(' ') : fields;
+                const line = document.createElement('div');
+                line.textContent = `${new Date(entry.timestamp).toLocaleTimeString()}  ${name}  ${detail}`;
+                ticker.prepend(line);
+                while (ticker.children.length > TICKER_LINES) {
+                    ticker.lastChild.remove();
+                }
+            };
+        }
+        
         // Initial load
         fetchHealth();
+        startEventTicker();
         
         // Auto-refresh every 2 seconds
         setInterval(fetchHealth, 2000);
@@ -1,6 +1,5 @@
 use crate::state::AppState;
 use crate::tui::keys::TuiAction;
-use std::sync::Arc;
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum TuiTab {
@@ -14,11 +13,12 @@ pub struct TuiApp {
     pub state: AppState,
     pub current_tab: TuiTab,
     pub recording_path: Option<String>,
-    pub fault_injection_enabled: bool,
     pub alerts_acknowledged: bool,
     pub selected_symbol_index: usize, // Index into symbol list for selection
     pub show_help: bool, // Toggle help panel
+    pub cumulative_depth: bool, // Scale depth bars by cumulative qty
     pub export_notification: Option<(String, std::time::Instant)>, // (message, timestamp)
+    pub log_path: Option<String>, // Where tracing output goes while the TUI owns the screen
 }
 
 impl TuiApp {
@@ -27,11 +27,12 @@ impl TuiApp {
             state,
             current_tab: TuiTab::Integrity, // Default to Integrity tab
             recording_path,
-            fault_injection_enabled: false,
             alerts_acknowledged: false,
             selected_symbol_index: 0,
             show_help: false,
+            cumulative_depth: false,
             export_notification: None,
+            log_path: None,
         }
     }
     
@@ -39,11 +40,7 @@ impl TuiApp {
         if snapshot.symbols.is_empty() {
             None
         } else {
-            let idx = if snapshot.symbols.len() > 0 {
-                self.selected_symbol_index % snapshot.symbols.len()
-            } else {
-                0
-            };
+            let idx = self.selected_symbol_index % snapshot.symbols.len();
             Some(snapshot.symbols[idx].clone())
         }
     }
@@ -68,7 +65,7 @@ impl TuiApp {
                 // Toggle recording (for now just log, actual toggle would need state management)
                 false
             }
-            TuiAction::ExportIncident | TuiAction::InjectFault | TuiAction::ReplayLastIncident => {
+            TuiAction::ExportIncident | TuiAction::InjectFault | TuiAction::CycleFaultType | TuiAction::ReplayLastIncident => {
                 // These are handled in UI layer
                 false
             }
@@ -94,6 +91,10 @@ impl TuiApp {
                 self.show_help = !self.show_help;
                 false
             }
+            TuiAction::ToggleCumulativeDepth => {
+                self.cumulative_depth = !self.cumulative_depth;
+                false
+            }
         }
     }
 }
@@ -1,4 +1,4 @@
-use crossterm::event::KeyCode;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum TuiAction {
@@ -6,6 +6,7 @@ pub enum TuiAction {
     ToggleRecording,
     ExportIncident,
     InjectFault,
+    CycleFaultType,
     ReplayLastIncident,
     AcknowledgeAlert,
     MoveSelectionUp,
@@ -15,14 +16,20 @@ pub enum TuiAction {
     SwitchTabIntegrity,
     SwitchTabReplay,
     ToggleHelp,
+    ToggleCumulativeDepth,
 }
 
-pub fn key_to_action(key: KeyCode) -> Option<TuiAction> {
-    match key {
+pub fn key_to_action(key: KeyEvent) -> Option<TuiAction> {
+    // Raw mode turns Ctrl-C into a key press instead of SIGINT
+    if key.modifiers.contains(KeyModifiers::CONTROL) && matches!(key.code, KeyCode::Char('c') | KeyCode::Char('C')) {
+        return Some(TuiAction::Quit);
+    }
+    match key.code {
         KeyCode::Char('q') | KeyCode::Esc => Some(TuiAction::Quit),
         KeyCode::Char('r') | KeyCode::Char('R') => Some(TuiAction::ToggleRecording),
         KeyCode::Char('e') | KeyCode::Char('E') => Some(TuiAction::ExportIncident),
         KeyCode::Char('d') | KeyCode::Char('D') => Some(TuiAction::InjectFault),
+        KeyCode::Char('f') | KeyCode::Char('F') => Some(TuiAction::CycleFaultType),
         KeyCode::Char('p') | KeyCode::Char('P') => Some(TuiAction::ReplayLastIncident),
         KeyCode::Char('a') | KeyCode::Char('A') => Some(TuiAction::AcknowledgeAlert),
         KeyCode::Up => Some(TuiAction::MoveSelectionUp),
@@ -32,6 +39,7 @@ pub fn key_to_action(key: KeyCode) -> Option<TuiAction> {
         KeyCode::Char('3') => Some(TuiAction::SwitchTabIntegrity),
         KeyCode::Char('4') => Some(TuiAction::SwitchTabReplay),
         KeyCode::Char('?') | KeyCode::Char('h') | KeyCode::Char('H') => Some(TuiAction::ToggleHelp),
+        KeyCode::Char('c') | KeyCode::Char('C') => Some(TuiAction::ToggleCumulativeDepth),
         _ => None,
     }
 }
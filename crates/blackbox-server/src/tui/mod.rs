@@ -5,5 +5,5 @@ pub mod widgets;
 pub mod keys;
 
 pub use app::TuiApp;
-pub use ui::{run_tui, run_tui_with_manager};
+pub use ui::run_tui_with_manager;
 
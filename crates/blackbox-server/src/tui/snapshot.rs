@@ -1,6 +1,5 @@
 use crate::integrity::IntegrityProof;
-use crate::state::AppState;
-use blackbox_core::health::HealthStatus;
+use crate::state::{AppState, RecordStatus};
 use chrono::Utc;
 
 #[derive(Clone)]
@@ -10,9 +9,9 @@ pub struct UiSnapshot {
     pub symbols: Vec<String>,
     pub msg_rate: f64,
     pub recording_path: Option<String>,
+    pub recording: RecordStatus,
     pub fault_status: String,
     pub uptime_seconds: u64,
-    pub health_status: HealthStatus,
     pub symbol_health: Vec<SymbolHealthRow>,
     pub last_incident: Option<LastIncidentInfo>,
     pub incident_count: u64,
@@ -31,6 +30,9 @@ pub struct SymbolHealthRow {
     pub last_mismatch: Option<String>,
     pub resync_count: u64,
     pub last_msg_age: Option<u64>,
+    pub book_crossed: bool,
+    pub feed_latency_ms: Option<f64>,
+    pub msg_rate: f64,
 }
 
 #[derive(Clone)]
@@ -67,9 +69,10 @@ impl UiSnapshot {
             msg_rate += health_entry.value().msg_rate_estimate;
         }
         
-        let symbol_health: Vec<SymbolHealthRow> = state
-            .health
+        // Rows follow the same order as `symbols` so the selection index lines up
+        let symbol_health: Vec<SymbolHealthRow> = symbols
             .iter()
+            .filter_map(|s| state.health.get(s))
             .map(|e| {
                 let h = e.value();
                 let last_mismatch = h.last_checksum_mismatch.map(|ts| {
@@ -94,8 +97,11 @@ impl UiSnapshot {
                     ok_rate: h.checksum_ok_rate(),
                     consecutive_fail: h.consecutive_fails,
                     last_mismatch,
-                    resync_count: h.reconnect_count,
+                    resync_count: h.resync_count,
                     last_msg_age,
+                    book_crossed: h.book_crossed,
+                    feed_latency_ms: h.feed_latency_ms,
+                    msg_rate: h.msg_rate_estimate,
                 }
             })
             .collect();
@@ -112,6 +118,10 @@ impl UiSnapshot {
             }
         });
         
+        // The live recording (toggled or rotated) wins over the CLI path
+        let recording = state.recording_status().await;
+        let recording_path = if recording.recording { recording.path.clone().or(recording_path) } else { None };
+        
         let incident_count = state.get_incident_count().await;
         let events = state.get_aggregated_events(30).await;
         
@@ -125,9 +135,9 @@ impl UiSnapshot {
             symbols,
             msg_rate,
             recording_path,
+            recording,
             fault_status: fault_status.to_string(),
             uptime_seconds: state.uptime_seconds(),
-            health_status: overall.status,
             symbol_health,
             last_incident,
             incident_count,
@@ -148,7 +158,7 @@ impl UiSnapshot {
         
         // Check if any symbol has issues
         let has_issues = self.symbol_health.iter().any(|s| {
-            s.ok_rate < 0.9999 || s.consecutive_fail > 0
+            s.ok_rate < 0.9999 || s.consecutive_fail > 0 || s.book_crossed
         });
         
         let has_broken = self.symbol_health.iter().any(|s| {
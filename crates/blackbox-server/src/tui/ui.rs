@@ -19,14 +19,6 @@ use std::sync::Arc;
 use std::time::Duration;
 use tokio::time::interval;
 
-pub async fn run_tui(
-    mut app: TuiApp,
-    mode: String,
-    fault_status: String,
-) -> anyhow::Result<()> {
-    run_tui_with_manager(app, mode, fault_status, None).await
-}
-
 pub async fn run_tui_with_manager(
     mut app: TuiApp,
     mode: String,
@@ -43,8 +35,24 @@ pub async fn run_tui_with_manager(
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = ratatui::Terminal::new(backend).context("Failed to create terminal")?;
     
+    // The terminal is restored however the loop ends
+    let result = tui_loop(&mut terminal, &mut app, &mode, &fault_status, incident_manager).await;
+    disable_raw_mode()?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    result
+}
+
+async fn tui_loop(
+    terminal: &mut ratatui::Terminal<CrosstermBackend<io::Stdout>>,
+    app: &mut TuiApp,
+    mode: &str,
+    fault_status: &str,
+    incident_manager: Option<Arc<IncidentManager>>,
+) -> anyhow::Result<()> {
     let mut should_quit = false;
     let mut snapshot_interval = interval(Duration::from_millis(150));
+    // SIGINT from outside the terminal (kill -INT) quits like 'q'
+    let mut interrupted = std::pin::pin!(tokio::signal::ctrl_c());
     
     loop {
         // Update snapshot
@@ -53,9 +61,9 @@ pub async fn run_tui_with_manager(
         // Create snapshot to get selected symbol
         let temp_snapshot = UiSnapshot::from_state(
             &app.state,
-            &mode,
+            mode,
             app.recording_path.clone(),
-            &fault_status,
+            fault_status,
             None,
             if requested_symbols.is_empty() { None } else { Some(&requested_symbols[..]) },
         ).await;
@@ -65,15 +73,15 @@ pub async fn run_tui_with_manager(
         // Create final snapshot with selected symbol
         let snapshot = UiSnapshot::from_state(
             &app.state,
-            &mode,
+            mode,
             app.recording_path.clone(),
-            &fault_status,
+            fault_status,
             selected_symbol.as_deref(),
             if requested_symbols.is_empty() { None } else { Some(&requested_symbols[..]) },
         ).await;
         
         // Render
-        terminal.draw(|f| render_ui(f, &app, &snapshot))?;
+        terminal.draw(|f| render_ui(f, app, &snapshot))?;
         
         // Clear expired notifications
         if let Some((_, timestamp)) = &app.export_notification {
@@ -86,13 +94,13 @@ pub async fn run_tui_with_manager(
         if crossterm::event::poll(Duration::from_millis(33))? {
             if let Event::Key(key) = event::read()? {
                 if key.kind == KeyEventKind::Press {
-                    if let Some(action) = key_to_action(key.code) {
+                    if let Some(action) = key_to_action(key) {
                         match action {
                             crate::tui::keys::TuiAction::ExportIncident => {
                                 if let Some(ref manager) = incident_manager {
                                     match handle_export_incident(&app.state, manager).await {
                                         Ok(path) => {
-                                            let short_path = path.split('/').last().unwrap_or(&path);
+                                            let short_path = path.rsplit('/').next().unwrap_or(&path);
                                             app.export_notification = Some((format!("✓ Exported: {}", short_path), std::time::Instant::now()));
                                         }
                                         Err(e) => {
@@ -116,6 +124,10 @@ pub async fn run_tui_with_manager(
                                     handle_fault_injection(&app.state, &symbol).await;
                                 }
                             }
+                            crate::tui::keys::TuiAction::CycleFaultType => {
+                                let fault_type = app.state.fault_injector.cycle_fault_type();
+                                app.export_notification = Some((format!("Fault type: {:?}", fault_type), std::time::Instant::now()));
+                            }
                             crate::tui::keys::TuiAction::ReplayLastIncident => {
                                 handle_replay_incident(&app.state).await;
                             }
@@ -143,11 +155,12 @@ pub async fn run_tui_with_manager(
             break;
         }
         
-        snapshot_interval.tick().await;
+        tokio::select! {
+            _ = snapshot_interval.tick() => {}
+            _ = &mut interrupted => break,
+        }
     }
     
-    disable_raw_mode()?;
-    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
     Ok(())
 }
 
@@ -171,7 +184,7 @@ fn render_ui(f: &mut Frame, app: &TuiApp, snapshot: &UiSnapshot) {
         _ => render_placeholder_tab(f, chunks[1], &format!("{:?} tab not implemented", app.current_tab)),
     }
     
-    render_footer(f, chunks[2], app.current_tab);
+    render_footer(f, chunks[2], app.current_tab, app.log_path.as_deref());
     
     // Show help panel as overlay if toggled
     if app.show_help {
@@ -210,15 +223,47 @@ fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
         .split(popup_layout[1])[1]
 }
 
+/// 12345 -> "12,345"
+fn format_count(n: u64) -> String {
+    let digits = n.to_string();
+    let mut out = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// Binary units, whole numbers from 10 up: "512 B", "3.4 KB", "48 MB"
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 || value >= 10.0 {
+        format!("{:.0} {}", value, UNITS[unit])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
 fn render_header(f: &mut Frame, area: Rect, snapshot: &UiSnapshot, _app: &TuiApp) {
     let status_icon = if snapshot.connected { "●" } else { "○" };
     let status_color = if snapshot.connected { Color::Green } else { Color::Red };
-    let recording_status = if snapshot.recording_path.is_some() { "ON" } else { "OFF" };
-    let recording_info = if let Some(ref path) = snapshot.recording_path {
-        format!("{} ({})", recording_status, 
-            path.split('/').last().unwrap_or(path.as_str()))
-    } else {
-        recording_status.to_string()
+    let recording_info = match (&snapshot.recording_path, snapshot.recording.stats) {
+        (Some(path), Some(stats)) => format!(
+            "ON ({} frames / {}) {}",
+            format_count(stats.frames_written),
+            format_bytes(stats.bytes_written),
+            path.rsplit('/').next().unwrap_or(path.as_str()),
+        ),
+        (Some(path), None) => format!("ON ({})", path.rsplit('/').next().unwrap_or(path.as_str())),
+        (None, _) => "OFF".to_string(),
     };
     
     let line = Line::from(vec![
@@ -248,10 +293,12 @@ fn render_header(f: &mut Frame, area: Rect, snapshot: &UiSnapshot, _app: &TuiApp
 }
 
 fn render_integrity_tab(f: &mut Frame, area: Rect, snapshot: &UiSnapshot, app: &TuiApp) {
-    // Layout: Top row (Badge + Symbol Selector) | Main (Orderbook | Inspector + Incident + Events)
+    // Layout: Top row (Badge + Symbol Selector) | Integrity table | Main (Orderbook | Inspector + Incident + Events)
+    // Table height: one row per symbol plus header and borders, capped so the book keeps most of the screen
+    let table_height = (snapshot.symbol_health.len() as u16 + 3).clamp(4, 10);
     let main_chunks = Layout::default()
         .direction(Direction::Vertical)
-        .constraints([Constraint::Length(8), Constraint::Min(0)])
+        .constraints([Constraint::Length(8), Constraint::Length(table_height), Constraint::Min(0)])
         .split(area);
     
     // Top row: Badge + Symbol Selector
@@ -263,18 +310,21 @@ fn render_integrity_tab(f: &mut Frame, area: Rect, snapshot: &UiSnapshot, app: &
     widgets::render_integrity_badge(f, top_chunks[0], snapshot);
     widgets::render_symbol_selector(f, top_chunks[1], &snapshot.symbols, app.selected_symbol_index);
     
+    // Per-symbol checksum counters
+    widgets::render_integrity_table(f, main_chunks[1], &snapshot.symbol_health, app.selected_symbol_index);
+    
     // Main area: Orderbook + Inspector | Sidebar
     let content_chunks = Layout::default()
         .direction(Direction::Horizontal)
         .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
-        .split(main_chunks[1]);
+        .split(main_chunks[2]);
     
     // Left: Orderbook (full height)
     let selected_symbol = snapshot.selected_symbol.as_deref();
     let depth = selected_symbol
         .and_then(|s| app.state.depths.get(s).map(|d| *d.value() as usize))
         .unwrap_or(10);
-    widgets::render_orderbook(f, content_chunks[0], &app.state, selected_symbol, depth);
+    widgets::render_orderbook(f, content_chunks[0], &app.state, selected_symbol, depth, app.cumulative_depth);
     
     // Right: Inspector + Incident + Events
     let right_chunks = Layout::default()
@@ -286,13 +336,13 @@ fn render_integrity_tab(f: &mut Frame, area: Rect, snapshot: &UiSnapshot, app: &
     widgets::render_integrity_inspector(f, right_chunks[0], snapshot.integrity_proof.as_ref(), selected_symbol);
     
     // Incident panel
-    render_incident_panel(f, right_chunks[1], snapshot);
+    render_incident_panel(f, right_chunks[1], snapshot, app);
     
     // Event log
     widgets::render_event_log(f, right_chunks[2], &snapshot.events);
 }
 
-fn render_incident_panel(f: &mut Frame, area: Rect, snapshot: &UiSnapshot) {
+fn render_incident_panel(f: &mut Frame, area: Rect, snapshot: &UiSnapshot, app: &TuiApp) {
     let mut lines = vec![
         Line::from("Last Incident:"),
     ];
@@ -302,13 +352,13 @@ fn render_incident_panel(f: &mut Frame, area: Rect, snapshot: &UiSnapshot) {
             Span::raw(format!("  ID: {}", inc.id)),
         ]));
         lines.push(Line::from(vec![
-            Span::raw(format!("  Symbol: {}", inc.symbol.as_ref().map(|s| s.clone()).unwrap_or_else(|| "N/A".to_string()))),
+            Span::raw(format!("  Symbol: {}", inc.symbol.clone().unwrap_or_else(|| "N/A".to_string()))),
         ]));
         lines.push(Line::from(vec![
             Span::raw(format!("  Reason: {}", inc.reason)),
         ]));
         lines.push(Line::from(vec![
-            Span::raw(format!("  Time: {}", inc.timestamp.format("%H:%M:%S").to_string())),
+            Span::raw(format!("  Time: {}", inc.timestamp.format("%H:%M:%S"))),
         ]));
     } else {
         lines.push(Line::from("  (none)"));
@@ -318,7 +368,8 @@ fn render_incident_panel(f: &mut Frame, area: Rect, snapshot: &UiSnapshot) {
     lines.push(Line::from("Controls:"));
     lines.push(Line::from("  [R] toggle recording"));
     lines.push(Line::from("  [E] export bug bundle"));
-    lines.push(Line::from("  [F] toggle fault injection"));
+    lines.push(Line::from(format!("  [D] inject fault ({:?})", app.state.fault_injector.fault_type())));
+    lines.push(Line::from("  [F] cycle fault type"));
     lines.push(Line::from("  [A] acknowledge alert"));
     
     let block = Block::default()
@@ -343,36 +394,18 @@ fn render_placeholder_tab(f: &mut Frame, area: Rect, message: &str) {
 
 async fn handle_toggle_recording(state: &AppState) {
     use crate::state::UiEvent;
-    use blackbox_core::recorder::Recorder;
-    use std::path::PathBuf;
     
     let currently_enabled = state.is_recording_enabled().await;
     
     if currently_enabled {
-        // Stop recording
-        let mut recorder = state.recorder.write().await;
-        if let Some(ref mut rec) = *recorder {
-            let _ = rec.close();
-        }
-        *recorder = None;
-        state.set_recording_enabled(false).await;
-        state.set_recording_path(None).await;
-        state.push_event(UiEvent::RecordStopped).await;
+        // Stop recording once the queued frames are written
+        state.stop_recording().await;
         tracing::info!("Recording stopped");
     } else {
-        // Start recording - generate filename
-        let timestamp = chrono::Utc::now().format("%Y%m%d_%H%M%S");
-        let path = format!("recording_{}.ndjson", timestamp);
-        let path_buf = PathBuf::from(&path);
-        
-        match Recorder::new(path_buf.clone()) {
-            Ok(rec) => {
-                let mut recorder = state.recorder.write().await;
-                *recorder = Some(rec);
-                state.set_recording_enabled(true).await;
-                state.set_recording_path(Some(path.clone())).await;
-                state.push_event(UiEvent::RecordStarted { path: path.clone() }).await;
-                tracing::info!("Recording started: {}", path);
+        // Resume this session's recording, or start one with a generated filename
+        match state.open_recording(None, state.recorder_options.clone()).await {
+            Ok(path) => {
+                tracing::info!("Recording started: {}", path.display());
             }
             Err(e) => {
                 tracing::error!("Failed to start recording: {}", e);
@@ -389,7 +422,7 @@ async fn handle_fault_injection(state: &AppState, symbol: &str) {
     state.fault_injector.trigger(symbol.to_string());
     
     state.push_event(UiEvent::FaultInjected { 
-        fault_type: "MutateQty".to_string(), 
+        fault_type: format!("{:?}", state.fault_injector.fault_type()), 
         symbol: symbol.to_string() 
     }).await;
 }
@@ -418,7 +451,14 @@ async fn replay_incident_frames(state: &AppState, frames_path: &std::path::Path)
     // Read NDJSON file and replay frames
     use crate::state::UiEvent;
     
-    let content = tokio::fs::read_to_string(frames_path).await?;
+    // Plain or gzipped NDJSON
+    let path = frames_path.to_path_buf();
+    let content = tokio::task::spawn_blocking(move || -> anyhow::Result<String> {
+        let mut content = String::new();
+        std::io::Read::read_to_string(&mut blackbox_core::recorder::open_recording(&path)?, &mut content)?;
+        Ok(content)
+    })
+    .await??;
     let lines: Vec<&str> = content.lines().collect();
     
     for (idx, line) in lines.iter().enumerate() {
@@ -443,85 +483,34 @@ async fn replay_incident_frames(state: &AppState, frames_path: &std::path::Path)
 
 async fn handle_export_incident(state: &AppState, manager: &Arc<IncidentManager>) -> anyhow::Result<String> {
     use crate::state::UiEvent;
-    use std::io::Write;
-    use zip::write::{FileOptions, ZipWriter};
-    use zip::CompressionMethod;
-    
-    let last_incident_meta = state.get_last_incident().await;
-    if let Some(inc_meta) = last_incident_meta {
-        // Get frames for this symbol
-        let frame_buffer = state.get_or_create_frame_buffer(&inc_meta.symbol);
-        let frames: Vec<String> = frame_buffer.read().await.iter().cloned().collect();
-        
-        // Get integrity proof
-        let proof = state.integrity_proofs.get(&inc_meta.symbol);
-        
-        // Create ZIP bundle
-        let incidents_dir = std::path::PathBuf::from("./incidents");
-        std::fs::create_dir_all(&incidents_dir)?;
-        let zip_path = incidents_dir.join(format!("{}.zip", inc_meta.id));
-        
-        let file = std::fs::File::create(&zip_path)?;
-        let mut zip = ZipWriter::new(std::io::BufWriter::new(file));
-        let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
-        
-        // metadata.json
-        zip.start_file("metadata.json", options)?;
-        zip.write_all(serde_json::to_string_pretty(&inc_meta)?.as_bytes())?;
-        
-        // config.json
-        let config = serde_json::json!({
-            "symbols": state.health.iter().map(|e| e.key().clone()).collect::<Vec<_>>(),
-        });
-        zip.start_file("config.json", options)?;
-        zip.write_all(serde_json::to_string_pretty(&config)?.as_bytes())?;
-        
-        // health.json
-        let overall = state.overall_health();
-        let health = serde_json::to_value(&overall)?;
-        zip.start_file("health.json", options)?;
-        zip.write_all(serde_json::to_string_pretty(&health)?.as_bytes())?;
-        
-        // frames.ndjson
-        zip.start_file("frames.ndjson", options)?;
-        for frame in &frames {
-            zip.write_all(format!("{}\n", frame).as_bytes())?;
-        }
-        
-        // checksums.json (if proof exists)
-        if let Some(p) = proof {
-            let checksums_json = serde_json::json!({
-                "expected": p.expected_checksum,
-                "computed": p.computed_checksum,
-                "preview": p.checksum_preview,
-                "length": p.checksum_len,
-                "latency_ms": p.verify_latency_ms,
-            });
-            zip.start_file("checksums.json", options)?;
-            zip.write_all(serde_json::to_string_pretty(&checksums_json)?.as_bytes())?;
-        }
-        
-        zip.finish()?;
-        
-        // Update incident meta with zip path
-        let mut updated_meta = inc_meta.clone();
-        updated_meta.zip_path = Some(zip_path.clone());
-        updated_meta.frames_path = Some(incidents_dir.join(format!("{}_frames.ndjson", inc_meta.id)));
-        updated_meta.frame_count = frames.len();
-        
-        // Write frames file
-        tokio::fs::write(&updated_meta.frames_path.as_ref().unwrap(), frames.join("\n")).await?;
-        
-        state.set_last_incident(updated_meta).await;
-        state.push_event(UiEvent::IncidentExported { path: zip_path.to_string_lossy().to_string() }).await;
-        
-        Ok(zip_path.to_string_lossy().to_string())
-    } else {
-        Err(anyhow::anyhow!("No incident to export"))
-    }
+    
+    let Some(inc_meta) = state.get_last_incident().await else {
+        anyhow::bail!("No incident to export");
+    };
+    let incident = manager.incident(&inc_meta.id).await
+        .with_context(|| format!("Incident {} is no longer held", inc_meta.id))?;
+    let zip_path = crate::export_incident_for_symbol(state, manager, &incident, Some(&inc_meta.symbol)).await?;
+    
+    // Get frames for this symbol
+    let frame_buffer = state.get_or_create_frame_buffer(&inc_meta.symbol);
+    let frames: Vec<String> = frame_buffer.read().await.iter().map(|(_, frame)| frame.clone()).collect();
+    
+    // Update incident meta with zip path
+    let mut updated_meta = inc_meta.clone();
+    updated_meta.zip_path = Some(zip_path.clone());
+    updated_meta.frames_path = Some(manager.incidents_dir().join(format!("{}_frames.ndjson", inc_meta.id)));
+    updated_meta.frame_count = frames.len();
+    
+    // Write frames file
+    tokio::fs::write(&updated_meta.frames_path.as_ref().unwrap(), frames.join("\n")).await?;
+    
+    state.set_last_incident(updated_meta).await;
+    state.push_event(UiEvent::IncidentExported { path: zip_path.to_string_lossy().to_string() }).await;
+    
+    Ok(zip_path.to_string_lossy().to_string())
 }
 
-fn render_footer(f: &mut Frame, area: Rect, current_tab: TuiTab) {
+fn render_footer(f: &mut Frame, area: Rect, current_tab: TuiTab, log_path: Option<&str>) {
     let market_style = if current_tab == TuiTab::Market {
         Style::default().fg(Color::Cyan).add_modifier(ratatui::style::Modifier::BOLD)
     } else {
@@ -546,7 +535,7 @@ fn render_footer(f: &mut Frame, area: Rect, current_tab: TuiTab) {
         Style::default().fg(Color::DarkGray)
     };
     
-    let line = Line::from(vec![
+    let mut spans = vec![
         Span::styled("[1] Market", market_style),
         Span::raw(" (disabled) "),
         Span::styled("[2] Analytics", analytics_style),
@@ -555,8 +544,12 @@ fn render_footer(f: &mut Frame, area: Rect, current_tab: TuiTab) {
         Span::raw(" (active) "),
         Span::styled("[4] Replay", replay_style),
         Span::raw(" (disabled) │ "),
-        Span::raw("[R]ecord [E]xport [D]emo [P]lay [↑↓]Select [?]Help [Q]uit"),
-    ]);
+        Span::raw("[R]ecord [E]xport [D]emo [P]lay [↑↓]Select [C]um [?]Help [Q]uit"),
+    ];
+    if let Some(path) = log_path {
+        spans.push(Span::styled(format!(" │ Log: {}", path), Style::default().fg(Color::DarkGray)));
+    }
+    let line = Line::from(spans);
     
     let block = Block::default().borders(Borders::ALL);
     let paragraph = Paragraph::new(vec![line])
@@ -566,3 +559,102 @@ fn render_footer(f: &mut Frame, area: Rect, current_tab: TuiTab) {
     f.render_widget(paragraph, area);
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recording_header_formats() {
+        assert_eq!(format_count(0), "0");
+        assert_eq!(format_count(999), "999");
+        assert_eq!(format_count(12_345), "12,345");
+        assert_eq!(format_count(1_234_567), "1,234,567");
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(3_500), "3.4 KB");
+        assert_eq!(format_bytes(48 * 1024 * 1024), "48 MB");
+    }
+
+    /// Names of the files in a bundle, sorted
+    fn bundle_files(zip: &[u8]) -> Vec<String> {
+        let archive = zip::ZipArchive::new(std::io::Cursor::new(zip)).unwrap();
+        let mut names: Vec<String> = archive.file_names().map(str::to_string).collect();
+        names.sort();
+        names
+    }
+
+    #[tokio::test]
+    async fn test_tui_and_http_exports_write_the_same_bundle() {
+        use crate::integrity::{IncidentMeta, IntegrityProof};
+        use crate::state::StoredBook;
+        use axum::body::Body;
+        use axum::http::{Request, StatusCode};
+        use blackbox_core::health::SymbolHealth;
+        use blackbox_core::incident::IncidentReason;
+        use blackbox_core::orderbook::Orderbook;
+        use blackbox_core::types::InstrumentInfo;
+        use rust_decimal_macros::dec;
+        use tower::ServiceExt;
+
+        let dir = std::env::temp_dir().join(format!("blackbox_tui_export_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let manager = Arc::new(IncidentManager::new(dir.clone()).unwrap());
+        let state = AppState::new();
+        let symbol = "BTC/USD".to_string();
+        state.insert_instrument(symbol.clone(), InstrumentInfo { symbol: symbol.clone(), ..Default::default() });
+        let mut book = Orderbook::new();
+        book.apply_snapshot(vec![(dec!(100.0), dec!(1))], vec![(dec!(100.5), dec!(2))]);
+        state.last_verified_books.insert(symbol.clone(), book.clone());
+        state.orderbooks.insert(symbol.clone(), StoredBook::new(book, 10));
+        state.integrity_proofs.insert(symbol.clone(), IntegrityProof::new());
+        let mut health = SymbolHealth::new(symbol.clone());
+        health.last_checksum_mismatch = Some(chrono::Utc::now());
+        state.health.insert(symbol.clone(), health);
+        state.buffer_frame(r#"{"channel":"book","type":"update","data":[{"symbol":"BTC/USD","bids":[],"asks":[],"checksum":1}]}"#).await;
+
+        let incident = manager.record_incident(IncidentReason::ChecksumMismatch, Some(symbol.clone()), serde_json::json!({})).await;
+        state.set_last_incident(IncidentMeta::new(incident.id.clone(), symbol.clone(), "ChecksumMismatch".to_string())).await;
+        let tui_bundle = std::fs::read(handle_export_incident(&state, &manager).await.unwrap()).unwrap();
+
+        // The mismatched symbol is the default
+        let app = crate::http::router(state, manager);
+        let response = app.oneshot(Request::post("/export-bug").body(Body::empty()).unwrap()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let http_bundle = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+
+        let files = bundle_files(&tui_bundle);
+        assert_eq!(files, [
+            "book_diff.json", "book_full.json", "book_top.json", "checksums.json", "config.json",
+            "frames.ndjson", "health.json", "instrument.json", "metadata.json",
+        ]);
+        assert_eq!(bundle_files(&http_bundle), files);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_toggle_resumes_recording() {
+        use blackbox_core::replayer::Replayer;
+        use blackbox_core::types::{ReplayConfig, ReplayMode};
+        
+        let dir = std::env::temp_dir().join(format!("blackbox_toggle_resume_{}", std::process::id()));
+        let path = dir.join("session.ndjson");
+        let state = AppState::new();
+        // As if recorded earlier in the session
+        *state.last_recording.write().await = Some((path.clone(), false));
+        
+        for i in 0..2 {
+            handle_toggle_recording(&state).await;
+            assert!(state.is_recording_enabled().await);
+            state.record_frame(&format!(r#"{{"channel":"heartbeat","seq":{}}}"#, i)).await;
+            handle_toggle_recording(&state).await;
+            assert!(!state.is_recording_enabled().await);
+        }
+        
+        let config = ReplayConfig { mode: ReplayMode::AsFast, faults: vec![], loop_playback: false };
+        let mut replayer = Replayer::new(path, config).unwrap();
+        replayer.start();
+        let frames: Vec<String> = std::iter::from_fn(|| replayer.next_frame()).collect();
+        assert_eq!(frames, vec![r#"{"channel":"heartbeat","seq":0}"#, r#"{"channel":"heartbeat","seq":1}"#]);
+        
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -1,7 +1,8 @@
 use crate::integrity::IntegrityProof;
+use blackbox_core::orderbook::Removal;
 use crate::state::AppState;
 use crate::tui::snapshot::{IntegrityStatus, SymbolHealthRow};
-use blackbox_core::orderbook::Orderbook;
+use chrono::Utc;
 use ratatui::layout::{Constraint, Direction, Layout, Rect};
 use ratatui::style::{Color, Modifier, Style};
 use ratatui::text::{Line, Span};
@@ -106,21 +107,25 @@ pub fn render_integrity_table(f: &mut Frame, area: Rect, rows: &[SymbolHealthRow
             Cell::from(row.checksum_fail.to_string()).style(Style::default().fg(Color::Red).bg(bg_color)),
             Cell::from(format!("{:.2}%", row.ok_rate * 100.0)).style(Style::default().fg(ok_color).bg(bg_color)),
             Cell::from(row.consecutive_fail.to_string()).style(Style::default().bg(bg_color)),
-            Cell::from(row.last_mismatch.as_ref().map(|s| s.clone()).unwrap_or_else(|| "-".to_string())).style(Style::default().bg(bg_color)),
+            Cell::from(row.last_mismatch.clone().unwrap_or_else(|| "-".to_string())).style(Style::default().bg(bg_color)),
             Cell::from(row.resync_count.to_string()).style(Style::default().bg(bg_color)),
-            Cell::from(row.last_msg_age.map(|a| format_duration(a)).unwrap_or_else(|| "-".to_string())).style(Style::default().bg(bg_color)),
+            Cell::from(row.last_msg_age.map(format_duration).unwrap_or_else(|| "-".to_string())).style(Style::default().bg(bg_color)),
+            Cell::from(format!("{:.1}", row.msg_rate)).style(Style::default().bg(bg_color)),
+            Cell::from(row.feed_latency_ms.map(format_latency).unwrap_or_else(|| "-".to_string())).style(Style::default().bg(bg_color)),
         ])
     }).collect();
     
     let table = Table::new(table_rows, [
-        ratatui::layout::Constraint::Percentage(18),
-        ratatui::layout::Constraint::Percentage(12),
-        ratatui::layout::Constraint::Percentage(12),
-        ratatui::layout::Constraint::Percentage(12),
-        ratatui::layout::Constraint::Percentage(12),
-        ratatui::layout::Constraint::Percentage(15),
+        ratatui::layout::Constraint::Percentage(14),
+        ratatui::layout::Constraint::Percentage(9),
+        ratatui::layout::Constraint::Percentage(9),
         ratatui::layout::Constraint::Percentage(10),
+        ratatui::layout::Constraint::Percentage(8),
+        ratatui::layout::Constraint::Percentage(14),
+        ratatui::layout::Constraint::Percentage(8),
+        ratatui::layout::Constraint::Percentage(8),
         ratatui::layout::Constraint::Percentage(9),
+        ratatui::layout::Constraint::Percentage(11),
     ])
     .header(
         Row::new(vec![
@@ -132,6 +137,8 @@ pub fn render_integrity_table(f: &mut Frame, area: Rect, rows: &[SymbolHealthRow
             Cell::from("Last Mismatch"),
             Cell::from("Resync"),
             Cell::from("Msg Age"),
+            Cell::from("Msg/s"),
+            Cell::from("Feed Latency"),
         ]).style(Style::default().add_modifier(Modifier::BOLD))
     )
     .block(Block::default().borders(Borders::ALL).title("Per-Symbol Integrity"));
@@ -168,7 +175,7 @@ pub fn render_integrity_inspector(f: &mut Frame, area: Rect, proof: Option<&Inte
             ]),
             Line::from(vec![
                 Span::raw(if p.is_match() {
-                    format!("  ✓ Match!")
+                    "  ✓ Match!".to_string()
                 } else {
                     format!("  ✗ Mismatch! (diff: 0x{:08X})", p.expected_checksum.wrapping_sub(p.computed_checksum))
                 }),
@@ -180,13 +187,18 @@ pub fn render_integrity_inspector(f: &mut Frame, area: Rect, proof: Option<&Inte
             Line::from(vec![
                 Span::raw(format!("Checksum Length: {} chars", p.checksum_len)),
             ]),
+            Line::from(vec![
+                Span::raw(format!(
+                    "Checksum Levels: {} asks + {} bids (max {}/side)",
+                    p.top_asks.len(),
+                    p.top_bids.len(),
+                    p.checksum_levels,
+                )),
+            ]),
             Line::from(""),
-            {
-                let stats = p.latency_stats();
-                Line::from(vec![
-                    Span::styled("Verify Latency:", Style::default().fg(Color::Yellow)),
-                ])
-            },
+            Line::from(vec![
+                Span::styled("Verify Latency:", Style::default().fg(Color::Yellow)),
+            ]),
             {
                 let stats = p.latency_stats();
                 Line::from(vec![
@@ -237,8 +249,16 @@ pub fn render_integrity_inspector(f: &mut Frame, area: Rect, proof: Option<&Inte
                 Line::from(vec![
                     Span::raw(format!("Last Mismatch: {} ({})", ts.format("%H:%M:%S%.3f"), p.diagnosis.as_deref().unwrap_or("unknown")))
                 ])
-            }).into_iter()
+            })
         )
+        .chain(if p.is_match() {
+            Vec::new()
+        } else {
+            removal_lines("Recent Ask Removals:", &p.recent_ask_removals)
+                .into_iter()
+                .chain(removal_lines("Recent Bid Removals:", &p.recent_bid_removals))
+                .collect()
+        })
         .collect::<Vec<_>>()
     } else {
         vec![
@@ -260,6 +280,26 @@ pub fn render_integrity_inspector(f: &mut Frame, area: Rect, proof: Option<&Inte
     f.render_widget(paragraph, area);
 }
 
+fn removal_lines(title: &'static str, removals: &[Removal]) -> Vec<Line<'static>> {
+    if removals.is_empty() {
+        return Vec::new();
+    }
+    let mut lines = vec![
+        Line::from(""),
+        Line::from(Span::styled(title, Style::default().fg(Color::Yellow))),
+    ];
+    lines.extend(removals.iter().map(|r| {
+        Line::from(format!(
+            "  {} @ {} ({:?}, {})",
+            r.price,
+            r.qty,
+            r.cause,
+            r.removed_at.format("%H:%M:%S%.3f"),
+        ))
+    }));
+    lines
+}
+
 pub fn render_event_log(f: &mut Frame, area: Rect, events: &[crate::state::AggregatedEvent]) {
     let log_lines: Vec<Line> = events.iter().rev().take(30).map(|entry| {
         let time_str = entry.timestamp.format("%H:%M:%S%.3f").to_string();
@@ -280,7 +320,10 @@ pub fn render_event_log(f: &mut Frame, area: Rect, events: &[crate::state::Aggre
     f.render_widget(paragraph, area);
 }
 
-pub fn render_orderbook(f: &mut Frame, area: Rect, state: &AppState, symbol: Option<&str>, depth: usize) {
+/// Levels per side used for the imbalance and weighted mid figures in the orderbook header
+const IMBALANCE_LEVELS: usize = 10;
+
+pub fn render_orderbook(f: &mut Frame, area: Rect, state: &AppState, symbol: Option<&str>, depth: usize, cumulative: bool) {
     if let Some(sym) = symbol {
         if let Some(book_entry) = state.orderbooks.get(sym) {
             let book = book_entry.value();
@@ -288,21 +331,43 @@ pub fn render_orderbook(f: &mut Frame, area: Rect, state: &AppState, symbol: Opt
             // Layout: Summary header + Orderbook (Bids | Asks)
             let chunks = Layout::default()
                 .direction(Direction::Vertical)
-                .constraints([Constraint::Length(5), Constraint::Min(0)])
+                .constraints([Constraint::Length(6), Constraint::Min(0)])
                 .split(area);
             
             // Summary header
             let best_bid = book.best_bid();
             let best_ask = book.best_ask();
             let spread = book.spread();
+            let spread_bps = book.spread_bps();
             let mid = book.mid();
+            let imbalance = book.imbalance(IMBALANCE_LEVELS);
+            let microprice = book.microprice();
+            let weighted_mid = book.weighted_mid(IMBALANCE_LEVELS);
             
-            let mut summary_lines = vec![
-                Line::from(vec![
-                    Span::styled("Orderbook: ", Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)),
-                    Span::styled(sym, Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)),
-                ]),
+            // Staleness: time since the exchange timestamp of the last update
+            let age = book.last_update_ts()
+                .map(|ts| Utc::now().signed_duration_since(ts).num_milliseconds().max(0));
+            let age_str = match age {
+                Some(ms) if ms < 1000 => format!("{}ms ago", ms),
+                Some(ms) => format!("{:.1}s ago", ms as f64 / 1000.0),
+                None => "n/a".to_string(),
+            };
+            let age_color = match age {
+                Some(ms) if ms < 1000 => Color::Green,
+                Some(ms) if ms < 5000 => Color::Yellow,
+                _ => Color::DarkGray,
+            };
+            
+            let mut title_spans = vec![
+                Span::styled("Orderbook: ", Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)),
+                Span::styled(sym, Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)),
+                Span::styled(format!("  seq {}  │  updated ", book.update_seq()), Style::default().fg(Color::DarkGray)),
+                Span::styled(age_str, Style::default().fg(age_color)),
             ];
+            if book.stale {
+                title_spans.push(Span::styled("  STALE (awaiting snapshot)", Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)));
+            }
+            let mut summary_lines = vec![Line::from(title_spans)];
             
             if let (Some((bid_price, bid_qty)), Some((ask_price, ask_qty))) = (best_bid, best_ask) {
                 summary_lines.push(Line::from(vec![
@@ -314,17 +379,39 @@ pub fn render_orderbook(f: &mut Frame, area: Rect, state: &AppState, symbol: Opt
                 ]));
                 
                 if let Some(sp) = spread {
-                    summary_lines.push(Line::from(vec![
+                    let mut spans = vec![
                         Span::raw("Spread: "),
                         Span::styled(format!("{:.4}", sp), Style::default().fg(Color::Yellow)),
-                    ]));
+                    ];
+                    
+                    if let Some(bps) = spread_bps {
+                        spans.push(Span::styled(format!(" ({:.2} bps)", bps), Style::default().fg(Color::Yellow)));
+                    }
                     
                     if let Some(m) = mid {
-                        summary_lines.push(Line::from(vec![
-                            Span::raw("Mid: "),
-                            Span::styled(format!("{:.4}", m), Style::default().fg(Color::Cyan)),
-                        ]));
+                        spans.push(Span::raw("  │  Mid: "));
+                        spans.push(Span::styled(format!("{:.4}", m), Style::default().fg(Color::Cyan)));
+                    }
+                    
+                    if let Some(imb) = imbalance {
+                        let imb_color = if imb > 0.0 { Color::Green } else if imb < 0.0 { Color::Red } else { Color::White };
+                        spans.push(Span::raw(format!("  │  Imb({}): ", IMBALANCE_LEVELS)));
+                        spans.push(Span::styled(format!("{:+.3}", imb), Style::default().fg(imb_color)));
                     }
+                    
+                    summary_lines.push(Line::from(spans));
+                }
+                
+                if let Some(micro) = microprice {
+                    let mut spans = vec![
+                        Span::raw("Micro: "),
+                        Span::styled(format!("{:.4}", micro), Style::default().fg(Color::Magenta)),
+                    ];
+                    if let Some(wm) = weighted_mid {
+                        spans.push(Span::raw(format!("  │  WMid({}): ", IMBALANCE_LEVELS)));
+                        spans.push(Span::styled(format!("{:.4}", wm), Style::default().fg(Color::Magenta)));
+                    }
+                    summary_lines.push(Line::from(spans));
                 }
             } else {
                 summary_lines.push(Line::from("Waiting for orderbook data..."));
@@ -351,21 +438,15 @@ pub fn render_orderbook(f: &mut Frame, area: Rect, state: &AppState, symbol: Opt
             let max_rows = available_height.saturating_sub(1); // Subtract header row
             let display_depth = depth.min(max_rows.max(10) as usize); // Use at least 10, or what fits
             
-            // Get bids and asks with calculated depth
-            let bids = book.bids_vec(Some(display_depth));
-            let asks = book.asks_vec(Some(display_depth));
-            
-            // Calculate max quantity for depth bars (use all available data for scaling)
-            let max_qty = bids.iter()
-                .chain(asks.iter())
-                .map(|(_, q)| q.to_f64().unwrap_or(0.0))
-                .fold(0.0, f64::max);
+            // Calculate max quantity for depth bars (use all displayed levels for scaling)
+            let max_qty = side_max_qty(book.top_bids(display_depth), cumulative)
+                .max(side_max_qty(book.top_asks(display_depth), cumulative));
             
             // Render bids (left side)
-            render_orderbook_side(f, orderbook_chunks[0], "BIDS", &bids, true, max_qty, best_bid.as_ref());
+            render_orderbook_side(f, orderbook_chunks[0], "BIDS", book.top_bids(display_depth), true, max_qty, cumulative, best_bid.as_ref());
             
             // Render asks (right side)
-            render_orderbook_side(f, orderbook_chunks[1], "ASKS", &asks, false, max_qty, best_ask.as_ref());
+            render_orderbook_side(f, orderbook_chunks[1], "ASKS", book.top_asks(display_depth), false, max_qty, cumulative, best_ask.as_ref());
         } else {
             // No orderbook data yet
             let no_data_lines = vec![
@@ -406,13 +487,23 @@ pub fn render_orderbook(f: &mut Frame, area: Rect, state: &AppState, symbol: Opt
     }
 }
 
-fn render_orderbook_side(
+/// Largest depth bar value on one side: the running total when cumulative,
+/// otherwise the largest single level
+fn side_max_qty<'a>(levels: impl Iterator<Item = (&'a Decimal, &'a Decimal)>, cumulative: bool) -> f64 {
+    let qtys = levels.map(|(_, q)| *q);
+    let max = if cumulative { qtys.sum() } else { qtys.max().unwrap_or_default() };
+    max.to_f64().unwrap_or(0.0)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn render_orderbook_side<'a>(
     f: &mut Frame,
     area: Rect,
     title: &str,
-    levels: &[(Decimal, Decimal)],
+    levels: impl Iterator<Item = (&'a Decimal, &'a Decimal)>,
     is_bids: bool,
     max_qty: f64,
+    cumulative: bool,
     best_level: Option<&(Decimal, Decimal)>,
 ) {
     let color = if is_bids { Color::Green } else { Color::Red };
@@ -423,16 +514,19 @@ fn render_orderbook_side(
     rows.push(Row::new(vec![
         Cell::from("Price").style(Style::default().fg(color).add_modifier(Modifier::BOLD)),
         Cell::from("Qty").style(Style::default().fg(color).add_modifier(Modifier::BOLD)),
-        Cell::from("Depth").style(Style::default().fg(color).add_modifier(Modifier::BOLD)),
+        Cell::from(if cumulative { "Cum Depth" } else { "Depth" }).style(Style::default().fg(color).add_modifier(Modifier::BOLD)),
     ]));
     
     // Data rows
-    for (price, qty) in levels.iter() {
+    let mut cum_qty = Decimal::ZERO;
+    for (price, qty) in levels {
+        cum_qty += qty;
         let price_str = format!("{:.2}", price);
         let qty_str = format!("{:.6}", qty);
         
         // Calculate depth bar width (use full available width)
-        let qty_f64: f64 = qty.to_f64().unwrap_or(0.0);
+        let bar_qty = if cumulative { &cum_qty } else { qty };
+        let qty_f64: f64 = bar_qty.to_f64().unwrap_or(0.0);
         let depth_bar_width = if max_qty > 0.0 {
             // Use reasonable max width for depth bars (scale based on quantity)
             ((qty_f64 / max_qty) * 25.0) as usize
@@ -515,8 +609,10 @@ pub fn render_help_panel(f: &mut Frame, area: Rect) {
         Line::from("  R     Toggle recording"),
         Line::from("  E     Export incident bundle"),
         Line::from("  D     Inject fault (demo)"),
+        Line::from("  F     Cycle fault type"),
         Line::from("  P     Replay last incident"),
         Line::from("  A     Acknowledge alert"),
+        Line::from("  C     Toggle cumulative depth bars"),
         Line::from("  Q/Esc Quit"),
         Line::from(""),
         Line::from(vec![
@@ -621,3 +717,11 @@ fn format_duration(seconds: u64) -> String {
         format!("{}h {}m", seconds / 3600, (seconds % 3600) / 60)
     }
 }
+
+fn format_latency(ms: f64) -> String {
+    if ms < 1000.0 {
+        format!("{:.0}ms", ms)
+    } else {
+        format!("{:.1}s", ms / 1000.0)
+    }
+}
@@ -0,0 +1,97 @@
+//! `blackbox verify`: offline checksum verification of a recording (and optional golden vectors)
+
+use blackbox_core::fixtures::{run_checksum_vectors, VectorResult};
+use blackbox_core::types::InstrumentMap;
+use blackbox_core::verify::{verify_recording_with_options, RecordingVerifyReport, VerifyOptions};
+use serde::Serialize;
+use std::path::Path;
+
+/// `--json` output
+#[derive(Serialize)]
+struct VerifyOutput<'a> {
+    ok: bool,
+    recording: &'a Path,
+    report: &'a RecordingVerifyReport,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    vectors: Option<&'a [VectorResult]>,
+}
+
+/// Print a verification report (as JSON with `json`); errors if any checksum mismatched
+pub fn run_verify(input: &Path, vectors: Option<&Path>, json: bool, stop_on_first: bool) -> anyhow::Result<()> {
+    let vector_results = vectors.map(run_checksum_vectors).transpose()?;
+    // Precisions come from the recorded instrument snapshot
+    let options = VerifyOptions { stop_on_first };
+    let report = verify_recording_with_options(input, &InstrumentMap::new(), &options)?;
+    
+    let failures = report.mismatches.len()
+        + vector_results.iter().flatten().filter(|r| !r.passed).count();
+    
+    if json {
+        let output = VerifyOutput {
+            ok: failures == 0,
+            recording: input,
+            report: &report,
+            vectors: vector_results.as_deref(),
+        };
+        println!("{}", serde_json::to_string_pretty(&output)?);
+    } else {
+        if let (Some(vectors), Some(results)) = (vectors, &vector_results) {
+            print_vectors(vectors, results);
+        }
+        print_report(input, &report);
+    }
+    
+    if failures > 0 {
+        anyhow::bail!("{} checksum failure(s)", failures);
+    }
+    if !json {
+        println!("All checksums match");
+    }
+    Ok(())
+}
+
+fn print_vectors(path: &Path, results: &[VectorResult]) {
+    println!("Checksum vectors: {}", path.display());
+    for result in results {
+        if result.passed {
+            println!("  {:<36} OK", result.name);
+        } else {
+            println!("  {:<36} FAIL  expected {} computed {}", result.name, result.expected, result.computed);
+        }
+    }
+}
+
+fn print_report(input: &Path, report: &RecordingVerifyReport) {
+    println!("Recording: {}", input.display());
+    println!(
+        "  {} frames, {} book frames, {} checksums verified, {} skipped (no instrument), {} malformed",
+        report.total_frames,
+        report.book_frames,
+        report.checksums_verified,
+        report.checksums_skipped,
+        report.malformed_frames,
+    );
+    for (symbol, stats) in &report.symbols {
+        let status = match report.first_divergence.get(symbol) {
+            Some(frame_index) => format!("DIVERGED at frame {} ({} mismatch(es))", frame_index, stats.mismatches),
+            None => "OK".to_string(),
+        };
+        println!(
+            "  {:<12} {} book frames, {} snapshots, {} updates, {} checksums  {}",
+            symbol, stats.book_frames, stats.snapshots, stats.updates, stats.checksums_verified, status,
+        );
+    }
+    for m in &report.mismatches {
+        println!(
+            "    frame {:>8}  {}  {:<12} expected {} computed {}",
+            m.frame_index,
+            m.ts.format("%Y-%m-%dT%H:%M:%S%.6fZ"),
+            m.symbol,
+            m.expected,
+            m.computed,
+        );
+    }
+    if report.stopped_early {
+        println!("  Stopped at the first mismatch (--stop-on-first)");
+    }
+}
@@ -0,0 +1,75 @@
+//! `GET /config` reports the flags `blackbox run` was started with
+
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// A port nothing is listening on right now
+fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port()
+}
+
+fn get_config(port: u16) -> Option<serde_json::Value> {
+    let mut stream = TcpStream::connect(("127.0.0.1", port)).ok()?;
+    write!(stream, "GET /config HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n").ok()?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response).ok()?;
+    let (_, body) = response.split_once("\r\n\r\n")?;
+    serde_json::from_str(body).ok()
+}
+
+#[test]
+fn test_run_flags_show_in_config() {
+    let workdir = std::env::temp_dir().join(format!("blackbox_config_cli_{}", std::process::id()));
+    std::fs::create_dir_all(&workdir).unwrap();
+    let http_port = free_port();
+    // Nothing listens on the feed port; the client keeps reconnecting
+    let ws_url = format!("ws://127.0.0.1:{}", free_port());
+    let mut child = Command::new(env!("CARGO_BIN_EXE_blackbox"))
+        .args(["run", "--symbols", "BTC/USD:1000,ETH/USD", "--depth", "25", "--ping-interval", "7s"])
+        .args(["--ws-url", &ws_url, "--http", &format!("127.0.0.1:{}", http_port)])
+        .args(["--record-rotate-size", "1M", "--record-flush-frames", "50", "--record-channels", "book,instrument"])
+        .args(["--max-incidents-bytes", "1G", "--disk-policy", "delete-oldest", "--strict-book", "--checksum-levels", "25"])
+        .args(["--record-decoded", "false", "--tombstones", "3", "--checksum-dump-dir", "dumps"])
+        .current_dir(&workdir)
+        .stdout(Stdio::null())
+        .spawn()
+        .unwrap();
+
+    let started = Instant::now();
+    let config = loop {
+        if let Some(config) = get_config(http_port) {
+            break config;
+        }
+        if started.elapsed() > Duration::from_secs(20) {
+            child.kill().unwrap();
+            panic!("/config never answered");
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    };
+    child.kill().unwrap();
+    let _ = child.wait();
+
+    assert_eq!(config["mode"], "live");
+    assert_eq!(config["ws_url"], ws_url);
+    assert_eq!(config["ping_interval_ms"], 7000);
+    assert_eq!(config["symbols"], serde_json::json!([
+        {"symbol": "BTC/USD", "depth": 1000},
+        {"symbol": "ETH/USD", "depth": 25},
+    ]));
+    assert_eq!(config["book"]["strict_book"], true);
+    assert_eq!(config["book"]["checksum_levels"], 25);
+    assert_eq!(config["book"]["tombstones"], 3);
+    let recording = &config["recording"];
+    assert_eq!(recording["enabled"], false);
+    assert_eq!(recording["decoded"], false);
+    assert_eq!(recording["rotate_max_bytes"], 1 << 20);
+    assert_eq!(recording["flush_every_frames"], 50);
+    assert_eq!(recording["flush_interval_ms"], 500);
+    assert_eq!(recording["channels"], serde_json::json!(["book", "instrument"]));
+    assert_eq!(config["incidents"]["disk_guard"], serde_json::json!({"max_bytes": 1u64 << 30, "policy": "delete-oldest"}));
+    assert_eq!(config["fault"]["armed"], false);
+
+    let _ = std::fs::remove_dir_all(workdir);
+}
@@ -0,0 +1,80 @@
+//! `--metrics-addr`: a separate Prometheus listener that never stops the run
+
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../blackbox-core/tests/fixtures");
+
+fn replay(workdir: &std::path::Path, metrics_addr: SocketAddr, extra: &[&str]) -> Child {
+    std::fs::create_dir_all(workdir).unwrap();
+    Command::new(env!("CARGO_BIN_EXE_blackbox"))
+        .args(["replay", "--speed", "0", "--http", "127.0.0.1:0", "--metrics-addr"])
+        .arg(metrics_addr.to_string())
+        .args(extra)
+        .arg("--input")
+        .arg(format!("{}/recording_corrupted.ndjson", FIXTURES))
+        .current_dir(workdir)
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap()
+}
+
+/// Body of a GET to `addr`, or None if nothing answers yet
+fn http_get(addr: SocketAddr, path: &str) -> Option<String> {
+    let mut stream = TcpStream::connect_timeout(&addr, Duration::from_secs(1)).ok()?;
+    write!(stream, "GET {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n", path, addr).ok()?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response).ok()?;
+    response.split_once("\r\n\r\n").map(|(_, body)| body.to_string())
+}
+
+#[test]
+fn test_replay_runs_with_metrics_port_taken() {
+    let workdir = std::env::temp_dir().join(format!("blackbox_metrics_taken_{}", std::process::id()));
+    let taken = TcpListener::bind("127.0.0.1:0").unwrap();
+    let mut child = replay(&workdir, taken.local_addr().unwrap(), &[]);
+
+    let started = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait().unwrap() {
+            break status;
+        }
+        if started.elapsed() > Duration::from_secs(20) {
+            child.kill().unwrap();
+            panic!("replay didn't exit after the recording ended");
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    };
+    assert!(status.success());
+    let mut stdout = String::new();
+    child.stdout.take().unwrap().read_to_string(&mut stdout).unwrap();
+    assert!(stdout.contains("Final health:"), "{}", stdout);
+    drop(taken);
+    let _ = std::fs::remove_dir_all(workdir);
+}
+
+#[test]
+fn test_metrics_exporter_serves_described_metrics() {
+    let workdir = std::env::temp_dir().join(format!("blackbox_metrics_exporter_{}", std::process::id()));
+    let addr = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    let mut child = replay(&workdir, addr, &["--hold"]);
+
+    let started = Instant::now();
+    let body = loop {
+        match http_get(addr, "/metrics") {
+            Some(body) if body.contains("replay_progress 1") => break body,
+            _ if started.elapsed() > Duration::from_secs(20) => {
+                child.kill().unwrap();
+                panic!("no metrics from the exporter on {}", addr);
+            }
+            _ => std::thread::sleep(Duration::from_millis(50)),
+        }
+    };
+    child.kill().unwrap();
+    let _ = child.wait();
+    assert!(body.contains("# HELP replay_progress Share of the recording replayed, 0 to 1"), "{}", body);
+    assert!(body.contains("# HELP incidents_total"), "{}", body);
+    let _ = std::fs::remove_dir_all(workdir);
+}
@@ -0,0 +1,45 @@
+//! `blackbox replay` runs to completion and exits, as in CI
+
+use std::io::Read;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../blackbox-core/tests/fixtures");
+
+#[test]
+fn test_replay_exits_on_complete_with_final_health() {
+    let workdir = std::env::temp_dir().join(format!("blackbox_replay_cli_{}", std::process::id()));
+    std::fs::create_dir_all(&workdir).unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_blackbox"))
+        .args(["replay", "--speed", "0", "--http", "127.0.0.1:0", "--input"])
+        .arg(format!("{}/recording_corrupted.ndjson", FIXTURES))
+        // Incident bundles for the corrupted frame land here
+        .current_dir(&workdir)
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    let started = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait().unwrap() {
+            break status;
+        }
+        if started.elapsed() > Duration::from_secs(20) {
+            child.kill().unwrap();
+            panic!("replay didn't exit after the recording ended");
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    };
+    assert!(status.success());
+
+    let mut stdout = String::new();
+    child.stdout.take().unwrap().read_to_string(&mut stdout).unwrap();
+    let (_, health) = stdout.split_once("Final health:\n").expect("no final health printed");
+    let health: serde_json::Value = serde_json::from_str(health).unwrap();
+    let btc = &health["symbols"][0];
+    assert_eq!(btc["symbol"], "BTC/USD");
+    assert_eq!(btc["checksum_ok"], 5);
+    assert_eq!(btc["checksum_fail"], 1);
+
+    let _ = std::fs::remove_dir_all(workdir);
+}
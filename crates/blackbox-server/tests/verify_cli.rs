@@ -0,0 +1,50 @@
+//! `blackbox verify` against the core recording fixtures
+
+use std::process::{Command, Output};
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../blackbox-core/tests/fixtures");
+
+fn verify(fixture: &str, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_blackbox"))
+        .arg("verify")
+        .arg("--input")
+        .arg(format!("{}/{}", FIXTURES, fixture))
+        .args(args)
+        .env_remove("RUST_BACKTRACE")
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_verify_clean_recording() {
+    let output = verify("recording_clean.ndjson", &[]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("BTC/USD      5 book frames, 1 snapshots, 4 updates, 5 checksums  OK"), "{}", stdout);
+    assert!(stdout.contains("All checksums match"));
+
+    let output = verify("recording_clean.ndjson", &["--json", "--vectors", &format!("{}/checksum_vectors.json", FIXTURES)]);
+    assert!(output.status.success());
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(json["ok"], true);
+    assert_eq!(json["report"]["symbols"]["BTC/USD"]["checksums_verified"], 5);
+    assert!(json["vectors"].as_array().is_some_and(|v| !v.is_empty()));
+}
+
+#[test]
+fn test_verify_corrupted_recording() {
+    let output = verify("recording_corrupted.ndjson", &[]);
+    assert!(!output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("DIVERGED at frame 7 (1 mismatch(es))"), "{}", stdout);
+    assert!(stdout.contains("2024-01-15T10:30:47.000000Z  BTC/USD"), "{}", stdout);
+
+    let output = verify("recording_corrupted.ndjson", &["--json", "--stop-on-first"]);
+    assert!(!output.status.success());
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(json["ok"], false);
+    assert_eq!(json["report"]["mismatches"][0]["frame_index"], 7);
+    assert_eq!(json["report"]["stopped_early"], true);
+    // The frame after the mismatch wasn't read
+    assert_eq!(json["report"]["total_frames"], 8);
+}
@@ -1,32 +1,83 @@
 use crate::parser::{parse_frame, WsFrame};
-use crate::subscriptions::{ping, subscribe_book, subscribe_instrument};
+use crate::subscriptions::{normalize_depth, ping, subscribe_book, subscribe_books, subscribe_instrument, unsubscribe_book};
 use anyhow::Context;
-use blackbox_core::types::InstrumentInfo;
+use blackbox_core::orderbook::Orderbook;
+use blackbox_core::types::{BookData, InstrumentInfo};
 use futures_util::{SinkExt, StreamExt};
 use std::collections::HashMap;
+use std::sync::Mutex;
 use std::time::{Duration, Instant};
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, oneshot};
 use tokio::time::sleep;
+use tokio_tungstenite::tungstenite::http::Uri;
 use tokio_tungstenite::{connect_async, tungstenite::Message};
 use tracing::{debug, error, info, warn};
 
-const WS_URL: &str = "wss://ws.kraken.com/v2";
-const DEFAULT_PING_INTERVAL: Duration = Duration::from_secs(30);
-const IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+pub const WS_URL: &str = "wss://ws.kraken.com/v2";
+/// Reconnect when nothing has been received for this long
+pub const IDLE_TIMEOUT: Duration = Duration::from_secs(60);
 const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(300); // 5 minutes
 const INITIAL_RECONNECT_DELAY: Duration = Duration::from_secs(1);
 
 pub struct WsClient {
-    symbols: Vec<String>,
+    url: String,
+    books: Mutex<BookSubscriptions>, // changed by commands, resubscribed on reconnect
     depth: u32,
     ping_interval: Duration,
+    idle_timeout: Duration,
     tx: mpsc::UnboundedSender<WsEvent>,
+    commands: Option<tokio::sync::Mutex<mpsc::UnboundedReceiver<ClientCommand>>>,
+}
+
+struct BookSubscriptions {
+    symbols: Vec<String>,
+    depths: HashMap<String, u32>, // per-symbol overrides of `depth`
+}
+
+/// A change to the book subscriptions of a running client (see `WsClient::with_commands`).
+/// Each gets its reply once Kraken acknowledges it, with Kraken's error if it refused.
+/// Commands wait while the client is reconnecting.
+#[derive(Debug)]
+pub enum ClientCommand {
+    /// Subscribe `symbol` at `depth` (default the client's); replies with the depth used
+    Subscribe { symbol: String, depth: Option<u32>, reply: oneshot::Sender<Result<u32, String>> },
+    Unsubscribe { symbol: String, reply: oneshot::Sender<Result<(), String>> },
+    /// Unsubscribe and re-subscribe `symbol` at its depth, for a fresh snapshot;
+    /// the subscription itself doesn't change
+    Resync { symbol: String },
+}
+
+/// A command sent to Kraken, waiting for the ack with its `req_id`
+enum PendingAck {
+    Subscribe { symbol: String, depth: u32, reply: oneshot::Sender<Result<u32, String>> },
+    Unsubscribe { symbol: String, reply: oneshot::Sender<Result<(), String>> },
+}
+
+/// Why a connection ended, as carried by `WsEvent::Disconnected`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisconnectCause {
+    Error, // couldn't connect, or the socket failed
+    ServerClose, // a close frame, or the stream just ended
+    IdleTimeout, // nothing received for the idle timeout
+    RateLimit, // Kraken said we exceeded its message rate
+}
+
+impl DisconnectCause {
+    /// As the `cause` label of `reconnects_total`
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DisconnectCause::Error => "error",
+            DisconnectCause::ServerClose => "server_close",
+            DisconnectCause::IdleTimeout => "idle_timeout",
+            DisconnectCause::RateLimit => "rate_limit",
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub enum WsEvent {
     Connected,
-    Disconnected,
+    Disconnected { cause: DisconnectCause },
     Frame(String),
     InstrumentSnapshot(HashMap<String, InstrumentInfo>),
     BookSnapshot { symbol: String, bids: Vec<(rust_decimal::Decimal, rust_decimal::Decimal)>, asks: Vec<(rust_decimal::Decimal, rust_decimal::Decimal)>, checksum: Option<u32> },
@@ -35,6 +86,46 @@ pub enum WsEvent {
     RateLimitExceeded,
 }
 
+/// Check that `url` is a ws:// or wss:// URL with a host
+pub fn validate_ws_url(url: &str) -> anyhow::Result<()> {
+    let uri: Uri = url.parse().with_context(|| format!("Invalid WebSocket URL '{}'", url))?;
+    match uri.scheme_str() {
+        Some("ws") | Some("wss") => {}
+        _ => anyhow::bail!("WebSocket URL '{}' must start with ws:// or wss://", url),
+    }
+    anyhow::ensure!(uri.host().is_some_and(|host| !host.is_empty()), "WebSocket URL '{}' has no host", url);
+    Ok(())
+}
+
+/// Turn one entry of a book message into a `BookSnapshot`/`BookUpdate` event,
+/// or an `Error` event if any of its levels fail to parse
+pub fn book_event(msg_type: &str, data: BookData) -> WsEvent {
+    let (bids, asks) = match Orderbook::levels_from_book_data(&data) {
+        Ok(levels) => levels,
+        Err(e) => {
+            warn!("Malformed book {} for {}: {:#}", msg_type, data.symbol, e);
+            return WsEvent::Error(format!("Malformed book {} for {}: {:#}", msg_type, data.symbol, e));
+        }
+    };
+    
+    if msg_type == "snapshot" {
+        WsEvent::BookSnapshot {
+            symbol: data.symbol,
+            bids,
+            asks,
+            checksum: data.checksum,
+        }
+    } else {
+        WsEvent::BookUpdate {
+            symbol: data.symbol,
+            bids,
+            asks,
+            checksum: data.checksum,
+            timestamp: data.timestamp,
+        }
+    }
+}
+
 impl WsClient {
     pub fn new(
         symbols: Vec<String>,
@@ -43,31 +134,129 @@ impl WsClient {
         tx: mpsc::UnboundedSender<WsEvent>,
     ) -> Self {
         Self {
-            symbols,
+            url: WS_URL.to_string(),
+            books: Mutex::new(BookSubscriptions { symbols, depths: HashMap::new() }),
             depth,
             ping_interval,
+            idle_timeout: IDLE_TIMEOUT,
             tx,
+            commands: None,
+        }
+    }
+
+    /// Connect to `url` (a beta endpoint, a local mock) instead of `WS_URL`
+    pub fn with_url(mut self, url: impl Into<String>) -> Self {
+        self.url = url.into();
+        self
+    }
+
+    /// Subscribe these symbols at their own depth rather than the client's
+    pub fn with_depths(mut self, depths: HashMap<String, u32>) -> Self {
+        self.books.get_mut().unwrap_or_else(|poisoned| poisoned.into_inner()).depths = depths;
+        self
+    }
+
+    /// Reconnect after `idle_timeout` without a message instead of `IDLE_TIMEOUT`
+    pub fn with_idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.idle_timeout = idle_timeout;
+        self
+    }
+
+    /// Take subscription changes from `commands` while running
+    pub fn with_commands(mut self, commands: mpsc::UnboundedReceiver<ClientCommand>) -> Self {
+        self.commands = Some(tokio::sync::Mutex::new(commands));
+        self
+    }
+
+    fn books(&self) -> std::sync::MutexGuard<'_, BookSubscriptions> {
+        self.books.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// The unsubscribe and subscribe that fetch `symbol` a fresh snapshot;
+    /// none if it isn't subscribed
+    fn resync_requests(&self, symbol: &str) -> Vec<serde_json::Value> {
+        let books = self.books();
+        if !books.symbols.iter().any(|s| s == symbol) {
+            warn!("Not resyncing {}: not subscribed", symbol);
+            return Vec::new();
+        }
+        let depth = books.depths.get(symbol).copied().unwrap_or(self.depth);
+        let symbols = [symbol.to_string()];
+        vec![unsubscribe_book(&symbols, depth), subscribe_book(&symbols, depth, true)]
+    }
+
+    /// Send `command` to Kraken, tagged with `req_id`; None if it was answered already
+    fn command_request(&self, command: ClientCommand, req_id: u64) -> Option<(serde_json::Value, PendingAck)> {
+        let (mut request, pending) = match command {
+            ClientCommand::Subscribe { symbol, depth, reply } => {
+                if self.books().symbols.contains(&symbol) {
+                    let _ = reply.send(Err(format!("{} is already subscribed", symbol)));
+                    return None;
+                }
+                let depth = normalize_depth(depth.unwrap_or(self.depth));
+                (subscribe_book(std::slice::from_ref(&symbol), depth, true), PendingAck::Subscribe { symbol, depth, reply })
+            }
+            ClientCommand::Resync { .. } => unreachable!("resyncs are sent by resync_requests"),
+            ClientCommand::Unsubscribe { symbol, reply } => {
+                let books = self.books();
+                if !books.symbols.contains(&symbol) {
+                    let _ = reply.send(Err(format!("{} is not subscribed", symbol)));
+                    return None;
+                }
+                let depth = books.depths.get(&symbol).copied().unwrap_or(self.depth);
+                drop(books);
+                (unsubscribe_book(std::slice::from_ref(&symbol), depth), PendingAck::Unsubscribe { symbol, reply })
+            }
+        };
+        request["req_id"] = serde_json::json!(req_id);
+        Some((request, pending))
+    }
+
+    /// Apply an acknowledged command and answer it
+    fn complete(&self, pending: PendingAck, error: Option<String>) {
+        match pending {
+            PendingAck::Subscribe { symbol, depth, reply } => {
+                if error.is_none() {
+                    let mut books = self.books();
+                    books.symbols.push(symbol.clone());
+                    books.depths.insert(symbol, depth);
+                }
+                let _ = reply.send(error.map_or(Ok(depth), Err));
+            }
+            PendingAck::Unsubscribe { symbol, reply } => {
+                if error.is_none() {
+                    let mut books = self.books();
+                    books.symbols.retain(|s| *s != symbol);
+                    books.depths.remove(&symbol);
+                }
+                let _ = reply.send(error.map_or(Ok(()), Err));
+            }
         }
     }
 
+    /// Connect and reconnect forever. Every reconnection counts towards
+    /// `reconnects_total`, labelled with the cause of the disconnect.
     pub async fn run(&self) -> anyhow::Result<()> {
         let mut reconnect_delay = INITIAL_RECONNECT_DELAY;
         let mut reconnect_count = 0u64;
         
         loop {
-            match self.connect_and_run().await {
-                Ok(()) => {
-                    // Normal disconnect, reset delay
-                    reconnect_delay = INITIAL_RECONNECT_DELAY;
-                    reconnect_count += 1;
-                    let _ = self.tx.send(WsEvent::Disconnected);
+            let cause = match self.connect_and_run().await {
+                Ok(cause) => {
+                    // Normal disconnect, reset delay; a rate limit keeps backing off
+                    if cause != DisconnectCause::RateLimit {
+                        reconnect_delay = INITIAL_RECONNECT_DELAY;
+                    }
+                    cause
                 }
                 Err(e) => {
                     error!("Connection error: {}", e);
-                    reconnect_count += 1;
-                    let _ = self.tx.send(WsEvent::Disconnected);
+                    DisconnectCause::Error
                 }
-            }
+            };
+            reconnect_count += 1;
+            metrics::counter!("reconnects_total", "cause" => cause.as_str()).increment(1);
+            let _ = self.tx.send(WsEvent::Disconnected { cause });
             
             // Exponential backoff with jitter
             let jitter = Duration::from_millis(rand::random::<u64>() % 1000);
@@ -79,11 +268,12 @@ impl WsClient {
         }
     }
 
-    async fn connect_and_run(&self) -> anyhow::Result<()> {
-        info!("Connecting to {}", WS_URL);
-        let (ws_stream, _) = connect_async(WS_URL)
+    /// One connection, until it ends; `Err` if it failed
+    async fn connect_and_run(&self) -> anyhow::Result<DisconnectCause> {
+        info!("Connecting to {}", self.url);
+        let (ws_stream, _) = connect_async(self.url.as_str())
             .await
-            .context("Failed to connect to Kraken WebSocket")?;
+            .with_context(|| format!("Failed to connect to {}", self.url))?;
         
         let (mut write, mut read) = ws_stream.split();
         let _ = self.tx.send(WsEvent::Connected);
@@ -119,8 +309,16 @@ impl WsClient {
         
         // Main read loop with ping handling
         let mut last_activity = Instant::now();
+        let mut commands = match &self.commands {
+            Some(commands) => Some(commands.lock().await),
+            None => None,
+        };
+        // Dropped with the connection, which tells their senders it was lost
+        let mut pending: HashMap<u64, PendingAck> = HashMap::new();
+        let mut next_req_id = 1u64;
         
-        loop {
+        let cause = 'connection: loop {
+            let idle_deadline = tokio::time::Instant::from_std(last_activity + self.idle_timeout);
             tokio::select! {
                 msg_opt = read.next() => {
                     match msg_opt {
@@ -134,7 +332,7 @@ impl WsClient {
                                         let _ = self.tx.send(WsEvent::RateLimitExceeded);
                                         // Close connection and reconnect after delay
                                         drop(ping_task);
-                                        return Err(anyhow::anyhow!("Rate limit exceeded"));
+                                        return Ok(DisconnectCause::RateLimit);
                                     }
                                     
                                     let _ = self.tx.send(WsEvent::Frame(text.clone()));
@@ -145,22 +343,23 @@ impl WsClient {
                                                 WsFrame::Instrument(msg) => {
                                                     debug!("Received instrument message, type: {:?}, pairs count: {}", msg.msg_type, msg.data.pairs.len());
                                                     if msg.msg_type == "snapshot" {
-                                                        use blackbox_core::precision::parse_decimal;
                                                         for pair in msg.data.pairs {
-                                                            match (parse_decimal(&pair.price_increment), parse_decimal(&pair.qty_increment)) {
-                                                                (Ok(price_inc), Ok(qty_inc)) => {
-                                                                    let info = InstrumentInfo {
-                                                                        symbol: pair.symbol.clone(),
-                                                                        price_precision: pair.price_precision,
-                                                                        qty_precision: pair.qty_precision,
-                                                                        price_increment: price_inc,
-                                                                        qty_increment: qty_inc,
-                                                                        status: pair.status,
-                                                                    };
-                                                                    instruments.insert(pair.symbol, info);
+                                                            let symbol = pair.symbol.clone();
+                                                            match InstrumentInfo::from_pair(pair) {
+                                                                Ok(info) => {
+                                                                    if info.has_derived_precision() {
+                                                                        info!(
+                                                                            "{}: precision missing, derived price {} / qty {} from increments {} / {}",
+                                                                            symbol, info.price_precision, info.qty_precision,
+                                                                            info.price_increment, info.qty_increment,
+                                                                        );
+                                                                    } else {
+                                                                        debug!("{}: explicit precision price {} / qty {}", symbol, info.price_precision, info.qty_precision);
+                                                                    }
+                                                                    instruments.insert(symbol, info);
                                                                 }
-                                                                (Err(e), _) | (_, Err(e)) => {
-                                                                    warn!("Failed to parse increment for {}: {}", pair.symbol, e);
+                                                                Err(e) => {
+                                                                    warn!("Failed to parse increment for {}: {}", symbol, e);
                                                                 }
                                                             }
                                                         }
@@ -170,86 +369,35 @@ impl WsClient {
                                                             info!("Received instrument snapshot with {} pairs", instruments.len());
                                                             let _ = self.tx.send(WsEvent::InstrumentSnapshot(instruments.clone()));
                                                             
-                                                            // Now subscribe to book
-                                                            let book_sub = subscribe_book(&self.symbols, self.depth, true);
-                                                            match serde_json::to_string(&book_sub) {
-                                                                Ok(msg) => {
-                                                                    debug!("Sending book subscription: {}", msg);
-                                                                    if let Err(e) = write.send(Message::Text(msg)).await {
-                                                                        error!("Failed to send book subscription: {}", e);
-                                                                        return Err(anyhow::anyhow!("Failed to send book subscription: {}", e));
+                                                            // Now subscribe to book, once per depth
+                                                            let book_subs = {
+                                                                let books = self.books();
+                                                                subscribe_books(&books.symbols, self.depth, &books.depths, true)
+                                                            };
+                                                            for book_sub in book_subs {
+                                                                let msg = match serde_json::to_string(&book_sub) {
+                                                                    Ok(msg) => msg,
+                                                                    Err(e) => {
+                                                                        error!("Failed to serialize book subscription: {}", e);
+                                                                        return Err(anyhow::anyhow!("Failed to serialize book subscription: {}", e));
                                                                     }
-                                                                    info!("Subscribed to book channel for symbols: {:?}", self.symbols);
-                                                                }
-                                                                Err(e) => {
-                                                                    error!("Failed to serialize book subscription: {}", e);
-                                                                    return Err(anyhow::anyhow!("Failed to serialize book subscription: {}", e));
+                                                                };
+                                                                debug!("Sending book subscription: {}", msg);
+                                                                if let Err(e) = write.send(Message::Text(msg)).await {
+                                                                    error!("Failed to send book subscription: {}", e);
+                                                                    return Err(anyhow::anyhow!("Failed to send book subscription: {}", e));
                                                                 }
+                                                                info!(
+                                                                    "Subscribed to book channel at depth {} for symbols: {:?}",
+                                                                    book_sub["params"]["depth"], book_sub["params"]["symbol"],
+                                                                );
                                                             }
                                                         }
                                                     }
                                                 }
                                                 WsFrame::Book(msg) => {
                                                     for data in msg.data {
-                                                        use blackbox_core::precision::parse_decimal;
-                                                        
-                                                        let mut bids = Vec::new();
-                                                        let mut asks = Vec::new();
-                                                        
-                                                        if let Some(bid_levels) = data.bids {
-                                                            for level in bid_levels {
-                                                                let price_str = match &level.price {
-                                                                    serde_json::Value::Number(n) => n.to_string(),
-                                                                    serde_json::Value::String(s) => s.clone(),
-                                                                    _ => continue,
-                                                                };
-                                                                let qty_str = match &level.qty {
-                                                                    serde_json::Value::Number(n) => n.to_string(),
-                                                                    serde_json::Value::String(s) => s.clone(),
-                                                                    _ => continue,
-                                                                };
-                                                                match (parse_decimal(&price_str), parse_decimal(&qty_str)) {
-                                                                    (Ok(price), Ok(qty)) => bids.push((price, qty)),
-                                                                    _ => continue,
-                                                                }
-                                                            }
-                                                        }
-                                                        
-                                                        if let Some(ask_levels) = data.asks {
-                                                            for level in ask_levels {
-                                                                let price_str = match &level.price {
-                                                                    serde_json::Value::Number(n) => n.to_string(),
-                                                                    serde_json::Value::String(s) => s.clone(),
-                                                                    _ => continue,
-                                                                };
-                                                                let qty_str = match &level.qty {
-                                                                    serde_json::Value::Number(n) => n.to_string(),
-                                                                    serde_json::Value::String(s) => s.clone(),
-                                                                    _ => continue,
-                                                                };
-                                                                match (parse_decimal(&price_str), parse_decimal(&qty_str)) {
-                                                                    (Ok(price), Ok(qty)) => asks.push((price, qty)),
-                                                                    _ => continue,
-                                                                }
-                                                            }
-                                                        }
-                                                        
-                                                        if msg.msg_type == "snapshot" {
-                                                            let _ = self.tx.send(WsEvent::BookSnapshot {
-                                                                symbol: data.symbol,
-                                                                bids,
-                                                                asks,
-                                                                checksum: data.checksum,
-                                                            });
-                                                        } else {
-                                                            let _ = self.tx.send(WsEvent::BookUpdate {
-                                                                symbol: data.symbol,
-                                                                bids,
-                                                                asks,
-                                                                checksum: data.checksum,
-                                                                timestamp: data.timestamp,
-                                                            });
-                                                        }
+                                                        let _ = self.tx.send(book_event(&msg.msg_type, data));
                                                     }
                                                 }
                                                 WsFrame::Heartbeat(_) => {
@@ -268,6 +416,9 @@ impl WsClient {
                                                     } else {
                                                         debug!("ACK: method={}, success={:?}", ack.method, ack.success);
                                                     }
+                                                    if let Some(command) = ack.req_id.and_then(|id| pending.remove(&id)) {
+                                                        self.complete(command, ack.error.clone());
+                                                    }
                                                 }
                                             }
                                         }
@@ -278,7 +429,7 @@ impl WsClient {
                                 }
                                 Message::Close(_) => {
                                     info!("WebSocket closed by server");
-                                    break;
+                                    break DisconnectCause::ServerClose;
                                 }
                                 Message::Ping(_) | Message::Pong(_) => {
                                     // Handle automatically by tokio-tungstenite
@@ -288,36 +439,69 @@ impl WsClient {
                         }
                         Some(Err(e)) => {
                             error!("WebSocket error: {}", e);
-                            break;
+                            break DisconnectCause::Error;
                         }
                         None => {
                             info!("WebSocket stream ended");
-                            break;
+                            break DisconnectCause::ServerClose;
+                        }
+                    }
+                }
+                command = next_command(&mut commands) => {
+                    let Some(command) = command else {
+                        // Nobody left to send commands
+                        commands = None;
+                        continue;
+                    };
+                    if let ClientCommand::Resync { symbol } = command {
+                        for request in self.resync_requests(&symbol) {
+                            info!("Sending {}", request);
+                            if write.send(Message::Text(request.to_string())).await.is_err() {
+                                break 'connection DisconnectCause::Error;
+                            }
                         }
+                        continue;
+                    }
+                    let req_id = next_req_id;
+                    next_req_id += 1;
+                    if let Some((request, command)) = self.command_request(command, req_id) {
+                        info!("Sending {}", request);
+                        if write.send(Message::Text(request.to_string())).await.is_err() {
+                            break DisconnectCause::Error;
+                        }
+                        pending.insert(req_id, command);
                     }
                 }
                 ping_msg_opt = ping_rx.recv() => {
                     if let Some(ping_msg) = ping_msg_opt {
                         if write.send(Message::Text(ping_msg)).await.is_err() {
-                            break;
+                            break DisconnectCause::Error;
                         }
                         debug!("Sent ping");
                     } else {
                         // Ping channel closed
-                        break;
+                        break DisconnectCause::Error;
                     }
                 }
+                _ = tokio::time::sleep_until(idle_deadline) => {
+                    warn!("Idle timeout, reconnecting");
+                    break DisconnectCause::IdleTimeout;
+                }
             }
-            
-            // Check for idle timeout
-            if last_activity.elapsed() > IDLE_TIMEOUT {
-                warn!("Idle timeout, reconnecting");
-                break;
-            }
-        }
+        };
         
         drop(ping_task);
-        Ok(())
+        Ok(cause)
+    }
+}
+
+/// Next command, or never if the client takes none
+async fn next_command(
+    commands: &mut Option<tokio::sync::MutexGuard<'_, mpsc::UnboundedReceiver<ClientCommand>>>,
+) -> Option<ClientCommand> {
+    match commands {
+        Some(commands) => commands.recv().await,
+        None => std::future::pending().await,
     }
 }
 
@@ -338,3 +522,310 @@ mod rand {
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::Decimal;
+    use std::str::FromStr;
+
+    fn data(levels: serde_json::Value) -> BookData {
+        serde_json::from_value(serde_json::json!({
+            "symbol": "BTC/USD",
+            "bids": levels,
+            "asks": [],
+            "checksum": 42,
+            "timestamp": "2024-01-15T10:30:45.123456Z",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_book_event_parses_string_and_numeric_levels() {
+        let event = book_event("snapshot", data(serde_json::json!([
+            {"price": "100.5", "qty": 2},
+            {"price": 99.25, "qty": "0.5"},
+        ])));
+        match event {
+            WsEvent::BookSnapshot { symbol, bids, asks, checksum } => {
+                assert_eq!(symbol, "BTC/USD");
+                assert_eq!(bids, vec![
+                    (Decimal::from_str("100.5").unwrap(), Decimal::from(2)),
+                    (Decimal::from_str("99.25").unwrap(), Decimal::from_str("0.5").unwrap()),
+                ]);
+                assert!(asks.is_empty());
+                assert_eq!(checksum, Some(42));
+            }
+            other => panic!("expected BookSnapshot, got {:?}", other),
+        }
+        
+        let event = book_event("update", data(serde_json::json!([{"price": "100", "qty": "0"}])));
+        assert!(matches!(
+            event,
+            WsEvent::BookUpdate { timestamp: Some(_), checksum: Some(42), .. }
+        ));
+    }
+
+    #[test]
+    fn test_book_event_reports_malformed_levels() {
+        let event = book_event("update", data(serde_json::json!([
+            {"price": "100", "qty": "1"},
+            {"price": {"bad": true}, "qty": "1"},
+        ])));
+        match event {
+            WsEvent::Error(msg) => {
+                assert!(msg.contains("BTC/USD"), "{}", msg);
+                assert!(msg.contains("bid level 1"), "{}", msg);
+            }
+            other => panic!("expected Error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_validate_ws_url() {
+        assert!(validate_ws_url(WS_URL).is_ok());
+        assert!(validate_ws_url("wss://beta-ws.kraken.com/v2").is_ok());
+        assert!(validate_ws_url("ws://127.0.0.1:9001").is_ok());
+        for bad in ["https://ws.kraken.com/v2", "ws.kraken.com/v2", "ws://", "not a url", ""] {
+            assert!(validate_ws_url(bad).is_err(), "{:?} should be rejected", bad);
+        }
+    }
+
+    const INSTRUMENT_SNAPSHOT: &str = r#"{"channel":"instrument","type":"snapshot","data":{"assets":[],"pairs":[{"symbol":"BTC/USD","base":"BTC","quote":"USD","status":"online","price_precision":1,"qty_precision":8,"price_increment":0.1,"qty_increment":0.00000001}]}}"#;
+    const BOOK_SNAPSHOT: &str = r#"{"channel":"book","type":"snapshot","data":[{"symbol":"BTC/USD","bids":[{"price":50000.5,"qty":1.25}],"asks":[{"price":50001.5,"qty":0.5}],"checksum":1234}]}"#;
+
+    type MockSocket = tokio_tungstenite::WebSocketStream<tokio::net::TcpStream>;
+
+    /// Next request from the client, skipping pings
+    async fn next_request(ws: &mut MockSocket) -> serde_json::Value {
+        loop {
+            match ws.next().await.unwrap().unwrap() {
+                Message::Text(text) => {
+                    let request: serde_json::Value = serde_json::from_str(&text).unwrap();
+                    if request["method"] != "ping" {
+                        return request;
+                    }
+                }
+                _ => continue,
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_client_subscribes_against_mock_server() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+            let mut requests = vec![next_request(&mut ws).await];
+            ws.send(Message::Text(INSTRUMENT_SNAPSHOT.to_string())).await.unwrap();
+            // One book subscription per depth
+            requests.push(next_request(&mut ws).await);
+            requests.push(next_request(&mut ws).await);
+            ws.send(Message::Text(BOOK_SNAPSHOT.to_string())).await.unwrap();
+            // Hold the connection until the client goes away
+            while let Some(Ok(_)) = ws.next().await {}
+            requests
+        });
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let symbols = vec!["BTC/USD".to_string(), "ETH/USD".to_string()];
+        let client = WsClient::new(symbols, 10, Duration::from_secs(3600), tx)
+            .with_url(format!("ws://{}", addr))
+            .with_depths(HashMap::from([("ETH/USD".to_string(), 100)]));
+        let client = tokio::spawn(async move { client.run().await });
+
+        let mut events = Vec::new();
+        let snapshot = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                match rx.recv().await.unwrap() {
+                    WsEvent::BookSnapshot { symbol, bids, asks, checksum } => break (symbol, bids, asks, checksum),
+                    event => events.push(event),
+                }
+            }
+        })
+        .await
+        .expect("no book snapshot from the mock server");
+        client.abort();
+        let requests = tokio::time::timeout(Duration::from_secs(5), server).await.unwrap().unwrap();
+
+        assert!(matches!(events[0], WsEvent::Connected));
+        let instruments = events
+            .iter()
+            .find_map(|event| match event {
+                WsEvent::InstrumentSnapshot(instruments) => Some(instruments),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(instruments["BTC/USD"].price_precision, 1);
+        // Raw frames are passed on for recording
+        assert_eq!(events.iter().filter(|event| matches!(event, WsEvent::Frame(_))).count(), 2);
+
+        assert_eq!(snapshot.0, "BTC/USD");
+        assert_eq!(snapshot.1, vec![(Decimal::from_str("50000.5").unwrap(), Decimal::from_str("1.25").unwrap())]);
+        assert_eq!(snapshot.2.len(), 1);
+        assert_eq!(snapshot.3, Some(1234));
+
+        assert_eq!(requests[0]["method"], "subscribe");
+        assert_eq!(requests[0]["params"]["channel"], "instrument");
+        assert_eq!(requests[1]["params"]["channel"], "book");
+        assert_eq!(requests[1]["params"]["depth"], 10);
+        assert_eq!(requests[1]["params"]["symbol"], serde_json::json!(["BTC/USD"]));
+        assert_eq!(requests[2]["params"]["depth"], 100);
+        assert_eq!(requests[2]["params"]["symbol"], serde_json::json!(["ETH/USD"]));
+    }
+
+    /// The ack Kraken sends for `request`, refusing it with `error`
+    fn ack(request: &serde_json::Value, error: Option<&str>) -> Message {
+        let mut ack = serde_json::json!({
+            "method": request["method"],
+            "req_id": request["req_id"],
+            "success": error.is_none(),
+            "time_in": "2024-01-15T10:30:45.123456Z",
+            "time_out": "2024-01-15T10:30:45.123556Z",
+        });
+        if let Some(error) = error {
+            ack["error"] = serde_json::json!(error);
+        }
+        Message::Text(ack.to_string())
+    }
+
+    #[tokio::test]
+    async fn test_commands_change_subscriptions() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+            next_request(&mut ws).await;
+            ws.send(Message::Text(INSTRUMENT_SNAPSHOT.to_string())).await.unwrap();
+            next_request(&mut ws).await; // the initial BTC/USD subscription
+            let mut requests = Vec::new();
+            for error in [None, Some("Currency pair not supported BAD/USD"), None] {
+                let request = next_request(&mut ws).await;
+                ws.send(ack(&request, error)).await.unwrap();
+                requests.push(request);
+            }
+            while let Some(Ok(_)) = ws.next().await {}
+            requests
+        });
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let (commands, commands_rx) = mpsc::unbounded_channel();
+        let client = WsClient::new(vec!["BTC/USD".to_string()], 10, Duration::from_secs(3600), tx)
+            .with_url(format!("ws://{}", addr))
+            .with_commands(commands_rx);
+        let client = tokio::spawn(async move { client.run().await });
+
+        // Commands go out after the initial subscriptions
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while !matches!(rx.recv().await.unwrap(), WsEvent::InstrumentSnapshot(_)) {}
+        })
+        .await
+        .unwrap();
+
+        let timeout = |reply| tokio::time::timeout(Duration::from_secs(5), reply);
+        let (reply, subscribed) = oneshot::channel();
+        commands.send(ClientCommand::Subscribe { symbol: "SOL/USD".to_string(), depth: Some(20), reply }).unwrap();
+        assert_eq!(timeout(subscribed).await.unwrap().unwrap(), Ok(25));
+
+        // Refused by Kraken, or by the client before anything is sent
+        let (reply, refused) = oneshot::channel();
+        commands.send(ClientCommand::Subscribe { symbol: "BAD/USD".to_string(), depth: None, reply }).unwrap();
+        assert_eq!(timeout(refused).await.unwrap().unwrap(), Err("Currency pair not supported BAD/USD".to_string()));
+        let (reply, duplicate) = oneshot::channel();
+        commands.send(ClientCommand::Subscribe { symbol: "SOL/USD".to_string(), depth: None, reply }).unwrap();
+        assert!(timeout(duplicate).await.unwrap().unwrap().unwrap_err().contains("already subscribed"));
+
+        let (reply, unsubscribed) = oneshot::channel();
+        commands.send(ClientCommand::Unsubscribe { symbol: "SOL/USD".to_string(), reply }).unwrap();
+        let unsubscribed = tokio::time::timeout(Duration::from_secs(5), unsubscribed).await.unwrap().unwrap();
+        assert_eq!(unsubscribed, Ok(()));
+        client.abort();
+        let requests = tokio::time::timeout(Duration::from_secs(5), server).await.unwrap().unwrap();
+
+        assert_eq!(requests[0]["method"], "subscribe");
+        assert_eq!(requests[0]["params"]["channel"], "book");
+        assert_eq!(requests[0]["params"]["symbol"], serde_json::json!(["SOL/USD"]));
+        assert_eq!(requests[0]["params"]["depth"], 25);
+        assert_eq!(requests[1]["params"]["depth"], 10);
+        assert_ne!(requests[0]["req_id"], requests[1]["req_id"]);
+        // At the depth it was subscribed at
+        assert_eq!(requests[2]["method"], "unsubscribe");
+        assert_eq!(requests[2]["params"]["symbol"], serde_json::json!(["SOL/USD"]));
+        assert_eq!(requests[2]["params"]["depth"], 25);
+    }
+
+    /// What the mock server does once the client has subscribed
+    #[derive(Clone, Copy)]
+    enum Hangup {
+        Close,
+        DropSocket,
+        RateLimit,
+        GoQuiet,
+    }
+
+    #[test]
+    fn test_disconnect_causes_are_reported_and_counted() {
+        let recorder = metrics_exporter_prometheus::PrometheusBuilder::new().build_recorder();
+        let handle = recorder.handle();
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        let causes = ::metrics::with_local_recorder(&recorder, || runtime.block_on(async {
+            let mut causes = Vec::new();
+            for hangup in [Hangup::Close, Hangup::DropSocket, Hangup::RateLimit, Hangup::GoQuiet] {
+                let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+                let addr = listener.local_addr().unwrap();
+                let server = tokio::spawn(async move {
+                    let (stream, _) = listener.accept().await.unwrap();
+                    let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+                    next_request(&mut ws).await;
+                    match hangup {
+                        Hangup::Close => ws.close(None).await.unwrap(),
+                        Hangup::DropSocket => drop(ws),
+                        Hangup::RateLimit => {
+                            ws.send(Message::Text(r#"{"error":"Exceeded msg rate"}"#.to_string())).await.unwrap();
+                        }
+                        Hangup::GoQuiet => {
+                            while let Some(Ok(_)) = ws.next().await {}
+                        }
+                    }
+                });
+
+                let (tx, mut rx) = mpsc::unbounded_channel();
+                let client = WsClient::new(vec!["BTC/USD".to_string()], 10, Duration::from_secs(3600), tx)
+                    .with_url(format!("ws://{}", addr))
+                    .with_idle_timeout(Duration::from_millis(200));
+                let disconnected = async {
+                    loop {
+                        if let WsEvent::Disconnected { cause } = rx.recv().await.unwrap() {
+                            break cause;
+                        }
+                    }
+                };
+                let cause = tokio::time::timeout(Duration::from_secs(5), async {
+                    tokio::select! {
+                        _ = client.run() => unreachable!("the client reconnects forever"),
+                        cause = disconnected => cause,
+                    }
+                })
+                .await
+                .expect("the client never disconnected");
+                causes.push(cause);
+                server.abort();
+            }
+            causes
+        }));
+
+        assert_eq!(causes, vec![
+            DisconnectCause::ServerClose,
+            DisconnectCause::Error,
+            DisconnectCause::RateLimit,
+            DisconnectCause::IdleTimeout,
+        ]);
+        let text = handle.render();
+        for cause in ["server_close", "error", "rate_limit", "idle_timeout"] {
+            assert!(text.contains(&format!("reconnects_total{{cause=\"{}\"}} 1", cause)), "{}", text);
+        }
+    }
+}
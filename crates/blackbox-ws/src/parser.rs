@@ -1,5 +1,7 @@
 use blackbox_core::types::*;
+use serde::Deserialize;
 use serde_json::Value;
+use std::borrow::Cow;
 
 /// Parse a raw WebSocket frame into a normalized message
 pub fn parse_frame(frame: &str) -> anyhow::Result<WsFrame> {
@@ -60,3 +62,108 @@ pub enum WsFrame {
     Ping(PingMessage),
 }
 
+/// Top-level fields `classify_frame` looks at; everything else is skipped
+#[derive(Deserialize)]
+struct FrameEnvelope<'a> {
+    #[serde(borrow, default)]
+    channel: Option<Cow<'a, str>>,
+    #[serde(borrow, default, rename = "type")]
+    msg_type: Option<Cow<'a, str>>,
+    #[serde(borrow, default)]
+    method: Option<Cow<'a, str>>,
+    #[serde(default)]
+    success: Option<bool>,
+}
+
+#[derive(Deserialize)]
+struct BookEnvelope<'a> {
+    #[serde(borrow)]
+    data: Vec<SymbolOnly<'a>>,
+}
+
+#[derive(Deserialize)]
+struct SymbolOnly<'a> {
+    #[serde(borrow)]
+    symbol: Cow<'a, str>,
+}
+
+/// Compact tag for a raw frame, stored as `RecordedFrame::decoded_event`:
+/// `book.update:BTC/USD`, `instrument.snapshot`, `status.update`, `heartbeat`,
+/// `ping`, `ack.subscribe`. Reads only the fields it needs, without building
+/// the message; None for frames `parse_frame` wouldn't recognise.
+pub fn classify_frame(frame: &str) -> Option<String> {
+    let envelope: FrameEnvelope = serde_json::from_str(frame).ok()?;
+    
+    // Same precedence as parse_frame: responses first
+    if envelope.method.is_some() || envelope.success.is_some() {
+        return Some(match envelope.method {
+            Some(method) => format!("ack.{}", method),
+            None => "ack".to_string(),
+        });
+    }
+    
+    let channel = envelope.channel?;
+    let tag = match channel.as_ref() {
+        "book" => {
+            let book: BookEnvelope = serde_json::from_str(frame).ok()?;
+            let symbol = book.data.first().map(|d| d.symbol.as_ref()).unwrap_or_default();
+            format!("book.{}:{}", envelope.msg_type?, symbol)
+        }
+        "instrument" | "status" => format!("{}.{}", channel, envelope.msg_type?),
+        "heartbeat" | "ping" => channel.into_owned(),
+        _ => return None,
+    };
+    Some(tag)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kind(frame: &WsFrame) -> &'static str {
+        match frame {
+            WsFrame::Ack(_) => "ack",
+            WsFrame::Book(_) => "book",
+            WsFrame::Instrument(_) => "instrument",
+            WsFrame::Status(_) => "status",
+            WsFrame::Heartbeat(_) => "heartbeat",
+            WsFrame::Ping(_) => "ping",
+        }
+    }
+
+    #[test]
+    fn test_classify_matches_parse_frame() {
+        let cases = [
+            (
+                r#"{"channel":"book","type":"snapshot","data":[{"symbol":"BTC/USD","bids":[{"price":100.5,"qty":1.0}],"asks":[{"price":101.0,"qty":2.0}],"checksum":123}]}"#,
+                "book.snapshot:BTC/USD",
+            ),
+            (
+                r#"{"data":[{"symbol":"ETH/USD","bids":[],"asks":[{"price":2000.1,"qty":0.5}],"checksum":7,"timestamp":"2026-01-01T00:00:00.000000Z"}],"type":"update","channel":"book"}"#,
+                "book.update:ETH/USD",
+            ),
+            (
+                r#"{"channel":"instrument","type":"snapshot","data":{"assets":[],"pairs":[{"symbol":"BTC/USD","price_precision":1,"qty_precision":8,"price_increment":"0.1","qty_increment":"0.00000001","status":"online"}]}}"#,
+                "instrument.snapshot",
+            ),
+            (r#"{"channel":"heartbeat"}"#, "heartbeat"),
+            (
+                r#"{"method":"subscribe","result":{"channel":"book","symbol":"BTC/USD","depth":10,"snapshot":true},"success":true,"time_in":"2026-01-01T00:00:00.000000Z","time_out":"2026-01-01T00:00:00.000001Z"}"#,
+                "ack.subscribe",
+            ),
+            (r#"{"method":"pong","time_in":"2026-01-01T00:00:00.000000Z","time_out":"2026-01-01T00:00:00.000001Z"}"#, "ack.pong"),
+        ];
+        for (frame, tag) in cases {
+            let parsed = parse_frame(frame).unwrap_or_else(|e| panic!("{}: {}", frame, e));
+            let classified = classify_frame(frame).unwrap();
+            assert_eq!(classified, tag);
+            assert_eq!(classified.split(['.', ':']).next(), Some(kind(&parsed)));
+        }
+        
+        // Neither recognises these
+        for frame in [r#"{"channel":"trade","type":"update","data":[]}"#, r#"{"data":[]}"#, "not json"] {
+            assert!(parse_frame(frame).is_err());
+            assert_eq!(classify_frame(frame), None);
+        }
+    }
+}
@@ -1,24 +1,26 @@
+use blackbox_core::verify::SUPPORTED_BOOK_DEPTHS as SUPPORTED_DEPTHS;
 use serde_json::json;
+use std::collections::{BTreeMap, HashMap};
 use tracing::warn;
 
-/// Kraken WebSocket v2 supported depth values
-const SUPPORTED_DEPTHS: &[u32] = &[10, 25, 100, 500, 1000];
+/// Smallest supported depth >= `depth`, capped at the max supported depth
+pub fn supported_depth(depth: u32) -> u32 {
+    SUPPORTED_DEPTHS
+        .iter()
+        .copied()
+        .find(|&supported| supported >= depth)
+        .unwrap_or(*SUPPORTED_DEPTHS.last().unwrap())
+}
 
 /// Normalize depth to nearest supported value
 pub fn normalize_depth(depth: u32) -> u32 {
-    if SUPPORTED_DEPTHS.contains(&depth) {
-        return depth;
-    }
-    // Find nearest supported depth (prefer smaller)
-    for &supported in SUPPORTED_DEPTHS {
-        if supported >= depth {
-            warn!("Depth {} not supported by Kraken, using {}", depth, supported);
-            return supported;
-        }
+    let supported = supported_depth(depth);
+    if supported > depth {
+        warn!("Depth {} not supported by Kraken, using {}", depth, supported);
+    } else if supported < depth {
+        warn!("Depth {} exceeds max supported ({}), using max", depth, supported);
     }
-    // If larger than max, use max
-    warn!("Depth {} exceeds max supported ({}), using max", depth, SUPPORTED_DEPTHS.last().unwrap());
-    *SUPPORTED_DEPTHS.last().unwrap()
+    supported
 }
 
 /// Build a subscribe message for instrument channel
@@ -49,6 +51,33 @@ pub fn subscribe_book(symbols: &[String], depth: u32, snapshot: bool) -> serde_j
     })
 }
 
+/// Build an unsubscribe message for book channel; Kraken matches it on the
+/// depth the symbols were subscribed at
+pub fn unsubscribe_book(symbols: &[String], depth: u32) -> serde_json::Value {
+    json!({
+        "method": "unsubscribe",
+        "params": {
+            "channel": "book",
+            "symbol": symbols,
+            "depth": supported_depth(depth)
+        }
+    })
+}
+
+/// One book subscribe message per depth: symbols in `depths` use their own
+/// depth, the rest `depth`. Symbols keep their order within a message.
+pub fn subscribe_books(symbols: &[String], depth: u32, depths: &HashMap<String, u32>, snapshot: bool) -> Vec<serde_json::Value> {
+    let mut by_depth: BTreeMap<u32, Vec<String>> = BTreeMap::new();
+    for symbol in symbols {
+        let symbol_depth = normalize_depth(depths.get(symbol).copied().unwrap_or(depth));
+        by_depth.entry(symbol_depth).or_default().push(symbol.clone());
+    }
+    by_depth
+        .into_iter()
+        .map(|(depth, symbols)| subscribe_book(&symbols, depth, snapshot))
+        .collect()
+}
+
 /// Build a ping message
 pub fn ping() -> serde_json::Value {
     json!({
@@ -72,3 +101,45 @@ pub fn unsubscribe(channel: &str, symbols: Option<&[String]>) -> serde_json::Val
     })
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_supported_depth() {
+        assert_eq!(supported_depth(5), 10);
+        assert_eq!(supported_depth(10), 10);
+        assert_eq!(supported_depth(24), 25);
+        assert_eq!(supported_depth(5000), 1000);
+        assert_eq!(normalize_depth(26), 100);
+    }
+
+    #[test]
+    fn test_subscribe_books_groups_by_depth() {
+        let symbols: Vec<String> = ["BTC/USD", "ETH/USD", "SOL/USD", "XRP/USD", "ADA/USD"].map(String::from).to_vec();
+        let depths = HashMap::from([
+            ("BTC/USD".to_string(), 1000),
+            ("ETH/USD".to_string(), 100),
+            // Normalized before grouping
+            ("XRP/USD".to_string(), 20),
+        ]);
+        let subs = subscribe_books(&symbols, 25, &depths, true);
+        let groups: Vec<(u64, Vec<&str>)> = subs
+            .iter()
+            .map(|sub| {
+                let symbols = sub["params"]["symbol"].as_array().unwrap().iter().map(|s| s.as_str().unwrap()).collect();
+                (sub["params"]["depth"].as_u64().unwrap(), symbols)
+            })
+            .collect();
+        assert_eq!(groups, vec![
+            (25, vec!["SOL/USD", "XRP/USD", "ADA/USD"]),
+            (100, vec!["ETH/USD"]),
+            (1000, vec!["BTC/USD"]),
+        ]);
+        assert!(subs.iter().all(|sub| sub["params"]["channel"] == "book" && sub["params"]["snapshot"] == true));
+
+        // Without overrides it's the single subscription it always was
+        assert_eq!(subscribe_books(&symbols, 10, &HashMap::new(), true), vec![subscribe_book(&symbols, 10, true)]);
+    }
+}